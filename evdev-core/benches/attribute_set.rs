@@ -0,0 +1,79 @@
+//! Compares the inline, `Copy` bit arrays of `AttributeSet` with the boxed arrays they
+//! replaced, on what devices do with key sets: create them, copy device state, and test and
+//! set bits.
+
+use std::hint::black_box;
+
+use bitvec::array::BitArray;
+use bitvec::BitArr;
+use criterion::{criterion_group, criterion_main, Criterion};
+use evdev_core::{AttributeSet, Key};
+
+type Boxed = Box<Bits>;
+
+const KEYS: [Key; 4] = [
+    Key::KEY_A,
+    Key::KEY_LEFTSHIFT,
+    Key::BTN_LEFT,
+    Key::BTN_TRIGGER_HAPPY40,
+];
+
+fn new(c: &mut Criterion) {
+    let mut group = c.benchmark_group("new");
+    group.bench_function("inline", |b| {
+        b.iter(|| black_box(AttributeSet::<Key>::new()))
+    });
+    group.bench_function("boxed", |b| {
+        b.iter(|| black_box(Boxed::new(BitArray::ZERO)))
+    });
+    group.finish();
+}
+
+fn copy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("copy");
+    let mut inline = AttributeSet::<Key>::new();
+    let mut boxed = Boxed::new(BitArray::ZERO);
+    for key in KEYS {
+        inline.insert(key);
+        boxed.set(key.code() as usize, true);
+    }
+    group.bench_function("inline", |b| b.iter(|| black_box(*black_box(&inline))));
+    group.bench_function("boxed", |b| b.iter(|| black_box(black_box(&boxed).clone())));
+    group.finish();
+}
+
+type Bits = BitArr!(for Key::COUNT, in u8);
+
+fn set_get(bits: &mut Bits) -> bool {
+    for key in KEYS {
+        bits.set(black_box(key).code() as usize, true);
+    }
+    KEYS.iter().all(|&key| bits[black_box(key).code() as usize])
+}
+
+/// The raw arrays go through the same bitvec calls, so only the storage differs;
+/// `attribute_set` adds the `AttributeSet` API on top of inline storage.
+fn insert_contains(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_contains");
+    group.bench_function("attribute_set", |b| {
+        let mut set = AttributeSet::<Key>::new();
+        b.iter(|| {
+            for key in KEYS {
+                set.insert(black_box(key));
+            }
+            KEYS.iter().all(|&key| set.contains(black_box(key)))
+        })
+    });
+    group.bench_function("inline", |b| {
+        let mut bits = Bits::ZERO;
+        b.iter(|| set_get(&mut bits))
+    });
+    group.bench_function("boxed", |b| {
+        let mut bits = Boxed::new(BitArray::ZERO);
+        b.iter(|| set_get(&mut bits))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, new, copy, insert_contains);
+criterion_main!(benches);
@@ -1,6 +1,6 @@
 use bitvec::prelude::*;
-use std::fmt;
-use std::ops::{Deref, DerefMut};
+use core::fmt;
+use core::ops::{Deref, DerefMut};
 
 /// A collection of bits representing either device capability or state.
 ///
@@ -9,7 +9,7 @@ use std::ops::{Deref, DerefMut};
 /// whether a key or button is depressed).
 #[repr(transparent)]
 pub struct AttributeSetRef<T> {
-    _indexer: std::marker::PhantomData<T>,
+    _indexer: core::marker::PhantomData<T>,
     bitslice: BitSlice<u8>,
 }
 
@@ -29,7 +29,7 @@ impl<T: EvdevEnum> AttributeSetRef<T> {
     /// Returns `true` if this AttributeSet contains the passed T.
     #[inline]
     pub fn contains(&self, attr: T) -> bool {
-        self.bitslice.get(attr.to_index()).map_or(false, |b| *b)
+        self.bitslice.get(attr.to_index()).is_some_and(|b| *b)
     }
 
     /// Provides an iterator over all "set" bits in the collection.
@@ -38,8 +38,9 @@ impl<T: EvdevEnum> AttributeSetRef<T> {
         self.bitslice.iter_ones().map(T::from_index)
     }
 
+    #[doc(hidden)]
     #[inline]
-    pub(crate) fn slice(&self, start: T) -> &Self {
+    pub fn slice(&self, start: T) -> &Self {
         Self::new(&self.bitslice[start.to_index()..])
     }
 
@@ -51,9 +52,9 @@ impl<T: EvdevEnum> AttributeSetRef<T> {
         self.set(attr, false)
     }
 
-    // TODO: figure out a good name for this if we make it public
+    /// Inserts or removes `attr` depending on `on`.
     #[inline]
-    pub(crate) fn set(&mut self, attr: T, on: bool) {
+    pub fn set(&mut self, attr: T, on: bool) {
         self.bitslice.set(attr.to_index(), on)
     }
 }
@@ -64,15 +65,20 @@ impl<T: EvdevEnum + fmt::Debug> fmt::Debug for AttributeSetRef<T> {
     }
 }
 
+/// An owned [`AttributeSetRef`].
+///
+/// The bits are stored inline in an array sized for every possible value of `T`, so sets never
+/// allocate, are `Copy`, and can be created in a `const` context.
 pub struct AttributeSet<T: ArrayedEvdevEnum> {
     container: T::Array,
 }
 
 impl<T: ArrayedEvdevEnum> AttributeSet<T> {
-    pub fn new() -> Self {
-        Self {
-            container: T::zeroed_array(),
-        }
+    /// An empty set.
+    pub const EMPTY: Self = Self::new();
+
+    pub const fn new() -> Self {
+        Self { container: T::ZERO }
     }
 
     fn as_bitslice(&self) -> &BitSlice<u8> {
@@ -83,8 +89,9 @@ impl<T: ArrayedEvdevEnum> AttributeSet<T> {
         T::array_as_slice_mut(&mut self.container)
     }
 
+    /// The underlying bytes, laid out as the kernel's `EVIOCGBIT`-style bitmasks.
     #[inline]
-    pub(crate) fn as_mut_raw_slice(&mut self) -> &mut [u8] {
+    pub fn as_mut_raw_slice(&mut self) -> &mut [u8] {
         T::array_as_buf(&mut self.container)
     }
 }
@@ -95,7 +102,7 @@ impl<T: ArrayedEvdevEnum> Default for AttributeSet<T> {
     }
 }
 
-impl<T: ArrayedEvdevEnum> std::iter::FromIterator<T> for AttributeSet<T> {
+impl<T: ArrayedEvdevEnum> core::iter::FromIterator<T> for AttributeSet<T> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         let mut set = AttributeSet::default();
         iter.into_iter().for_each(|el| set.insert(el));
@@ -103,6 +110,12 @@ impl<T: ArrayedEvdevEnum> std::iter::FromIterator<T> for AttributeSet<T> {
     }
 }
 
+impl<T: ArrayedEvdevEnum> Extend<T> for AttributeSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|el| self.insert(el));
+    }
+}
+
 impl<T: ArrayedEvdevEnum> Deref for AttributeSet<T> {
     type Target = AttributeSetRef<T>;
     fn deref(&self) -> &AttributeSetRef<T> {
@@ -116,6 +129,8 @@ impl<T: ArrayedEvdevEnum> DerefMut for AttributeSet<T> {
     }
 }
 
+impl<T: ArrayedEvdevEnum> Copy for AttributeSet<T> where T::Array: Copy {}
+
 impl<T: ArrayedEvdevEnum> Clone for AttributeSet<T>
 where
     T::Array: Clone,
@@ -146,25 +161,18 @@ pub trait ArrayedEvdevEnum: EvdevEnum {
     fn array_as_slice(arr: &Self::Array) -> &BitSlice<u8>;
     fn array_as_slice_mut(arr: &mut Self::Array) -> &mut BitSlice<u8>;
     fn array_as_buf(arr: &mut Self::Array) -> &mut [u8];
-    fn zeroed_array() -> Self::Array;
+    const ZERO: Self::Array;
 }
 
+#[doc(hidden)]
+#[macro_export]
 macro_rules! evdev_enum {
     ($t:ty, Array, $($(#[$attr:meta])* $c:ident = $val:expr,)*) => {
-        evdev_enum!(
-            $t,
-            Array: bitvec::BitArr!(for <$t>::COUNT, in u8),
-            bitvec::array::BitArray::as_raw_mut_slice,
-            bitvec::array::BitArray::ZERO,
-            $($(#[$attr])* $c = $val,)*
-        );
-    };
-    ($t:ty, box Array, $($(#[$attr:meta])* $c:ident = $val:expr,)*) => {
-        evdev_enum!(
+        $crate::evdev_enum!(
             $t,
-            Array: Box<bitvec::BitArr!(for <$t>::COUNT, in u8)>,
-            bitvec::array::BitArray::as_raw_mut_slice,
-            Box::new(bitvec::array::BitArray::ZERO),
+            Array: $crate::__bitvec::BitArr!(for <$t>::COUNT, in u8),
+            $crate::__bitvec::array::BitArray::as_raw_mut_slice,
+            $crate::__bitvec::array::BitArray::ZERO,
             $($(#[$attr])* $c = $val,)*
         );
     };
@@ -175,27 +183,25 @@ macro_rules! evdev_enum {
     ) => {
         impl $crate::attribute_set::ArrayedEvdevEnum for $t {
             type Array = $Array;
-            fn array_as_slice(arr: &Self::Array) -> &bitvec::slice::BitSlice<u8> {
+            fn array_as_slice(arr: &Self::Array) -> &$crate::__bitvec::slice::BitSlice<u8> {
                 arr
             }
-            fn array_as_slice_mut(arr: &mut Self::Array) -> &mut bitvec::slice::BitSlice<u8> {
+            fn array_as_slice_mut(arr: &mut Self::Array) -> &mut $crate::__bitvec::slice::BitSlice<u8> {
                 arr
             }
             fn array_as_buf(arr: &mut Self::Array) -> &mut [u8] {
                 $arr_as_buf(arr)
             }
-            fn zeroed_array() -> Self::Array {
-                $zero
-            }
+            const ZERO: Self::Array = $zero;
         }
-        evdev_enum!($t, $($(#[$attr])* $c = $val,)*);
+        $crate::evdev_enum!($t, $($(#[$attr])* $c = $val,)*);
     };
     ($t:ty, $($(#[$attr:meta])* $c:ident = $val:expr,)*) => {
         impl $t {
             $($(#[$attr])* pub const $c: Self = Self($val);)*
         }
-        impl std::str::FromStr for $t {
-            type Err = crate::EnumParseError;
+        impl core::str::FromStr for $t {
+            type Err = $crate::EnumParseError;
 
             fn from_str(s: &str) -> Result<Self, Self::Err> {
                 let map: &[(&'static str, $t)] = &[
@@ -204,16 +210,16 @@ macro_rules! evdev_enum {
 
                 match map.iter().find(|e| e.0 == s) {
                     Some(e) => Ok(e.1),
-                    None => Err(crate::EnumParseError(())),
+                    None => Err($crate::EnumParseError::new()),
                 }
             }
         }
-        impl std::fmt::Debug for $t {
-            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        impl core::fmt::Debug for $t {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
                 #[allow(unreachable_patterns)]
                 match *self {
                     $(Self::$c => f.pad(stringify!($c)),)*
-                    _ => write!(f, "unknown key: {}", self.0),
+                    _ => core::write!(f, "unknown key: {}", self.0),
                 }
             }
         }
@@ -229,3 +235,20 @@ macro_rules! evdev_enum {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{AttributeSet, Key};
+
+    const NONE: AttributeSet<Key> = AttributeSet::new();
+
+    #[test]
+    fn const_and_copy() {
+        let mut keys = NONE;
+        keys.insert(Key::BTN_LEFT);
+        let copy = keys;
+        assert!(copy.contains(Key::BTN_LEFT) && keys.contains(Key::BTN_LEFT));
+        assert!(!NONE.contains(Key::BTN_LEFT));
+        assert_eq!(core::mem::size_of::<AttributeSet<Key>>(), Key::COUNT / 8);
+    }
+}
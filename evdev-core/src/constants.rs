@@ -2,7 +2,7 @@
 ///
 /// Values correspond to [/usr/include/linux/input-event-codes.h](https://github.com/torvalds/linux/blob/master/include/uapi/linux/input-event-codes.h)
 ///
-/// This is implemented as a newtype around the u16 "type" field of `libc::input_event`.
+/// This is implemented as a newtype around the u16 "type" field of `input_event`.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct EventType(pub u16);
 
@@ -42,10 +42,14 @@ evdev_enum!(
     POWER = 0x16,
     /// A force feedback effect's state changed.
     FORCEFEEDBACKSTATUS = 0x17,
+    /// An event sent by uinput to the creator of a virtual device, e.g. a request to upload a
+    /// force feedback effect. Never seen on regular devices.
+    UINPUT = 0x0101,
 );
 
 impl EventType {
-    pub(crate) const COUNT: usize = libc::EV_CNT;
+    /// The number of codes of this type, `EV_CNT`.
+    pub const COUNT: usize = 0x20;
 }
 
 /// A "synchronization" message type published by the kernel into the events stream.
@@ -89,7 +93,8 @@ evdev_enum!(
 );
 
 impl PropType {
-    pub(crate) const COUNT: usize = libc::INPUT_PROP_CNT;
+    /// The number of codes of this type, `INPUT_PROP_CNT`.
+    pub const COUNT: usize = 0x20;
 }
 
 /// A type of relative axis measurement, typically produced by mice.
@@ -115,7 +120,8 @@ evdev_enum!(
 );
 
 impl RelativeAxisType {
-    pub(crate) const COUNT: usize = libc::REL_CNT;
+    /// The number of codes of this type, `REL_CNT`.
+    pub const COUNT: usize = 0x10;
 }
 
 /// A type of absolute axis measurement, typically used for touch events and joysticks.
@@ -184,7 +190,8 @@ evdev_enum!(
 );
 
 impl AbsoluteAxisType {
-    pub(crate) const COUNT: usize = libc::ABS_CNT;
+    /// The number of codes of this type, `ABS_CNT`.
+    pub const COUNT: usize = 0x40;
 }
 
 /// An event type corresponding to a physical or virtual switch.
@@ -231,7 +238,8 @@ evdev_enum!(
 );
 
 impl SwitchType {
-    pub(crate) const COUNT: usize = libc::SW_CNT;
+    /// The number of codes of this type, `SW_CNT`.
+    pub const COUNT: usize = 0x12;
 }
 
 /// LEDs specified by USB HID.
@@ -259,7 +267,8 @@ evdev_enum!(
 );
 
 impl LedType {
-    pub(crate) const COUNT: usize = libc::LED_CNT;
+    /// The number of codes of this type, `LED_CNT`.
+    pub const COUNT: usize = 0x10;
 }
 
 /// Various miscellaneous event types.
@@ -284,38 +293,46 @@ evdev_enum!(
 );
 
 impl MiscType {
-    pub(crate) const COUNT: usize = libc::MSC_CNT;
+    /// The number of codes of this type, `MSC_CNT`.
+    pub const COUNT: usize = 0x08;
 }
 
-// pub enum FFStatusDataIndex {
-//     FF_STATUS_STOPPED = 0x00,
-//     FF_STATUS_PLAYING = 0x01,
-// }
+/// The value of an `EV_FF_STATUS` event for an effect that has stopped playing.
+pub const FF_STATUS_STOPPED: i32 = 0x00;
+/// The value of an `EV_FF_STATUS` event for an effect that is playing.
+pub const FF_STATUS_PLAYING: i32 = 0x01;
 
-// #[derive(Copy, Clone, Copy, Clone)]
-// pub enum FFEffect {
-//     FF_RUMBLE = 0x50,
-//     FF_PERIODIC = 0x51,
-//     FF_CONSTANT = 0x52,
-//     FF_SPRING = 0x53,
-//     FF_FRICTION = 0x54,
-//     FF_DAMPER = 0x55,
-//     FF_INERTIA = 0x56,
-//     FF_RAMP = 0x57,
-//     FF_SQUARE = 0x58,
-//     FF_TRIANGLE = 0x59,
-//     FF_SINE = 0x5a,
-//     FF_SAW_UP = 0x5b,
-//     FF_SAW_DOWN = 0x5c,
-//     FF_CUSTOM = 0x5d,
-//     FF_GAIN = 0x60,
-//     FF_AUTOCENTER = 0x61,
-// }
+/// Force feedback effect types and device properties.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct FFEffectType(pub u16);
 
-// impl FFEffect {
-//     // Needs to be a multiple of 8
-//     pub const COUNT: usize = libc::FF_CNT;
-// }
+evdev_enum!(
+    FFEffectType,
+    Array,
+    FF_RUMBLE = 0x50,
+    FF_PERIODIC = 0x51,
+    FF_CONSTANT = 0x52,
+    FF_SPRING = 0x53,
+    FF_FRICTION = 0x54,
+    FF_DAMPER = 0x55,
+    FF_INERTIA = 0x56,
+    FF_RAMP = 0x57,
+    FF_SQUARE = 0x58,
+    FF_TRIANGLE = 0x59,
+    FF_SINE = 0x5a,
+    FF_SAW_UP = 0x5b,
+    FF_SAW_DOWN = 0x5c,
+    FF_CUSTOM = 0x5d,
+    /// Not an effect: writing this code sets the device's overall gain.
+    FF_GAIN = 0x60,
+    /// Not an effect: writing this code sets the device's autocenter strength.
+    FF_AUTOCENTER = 0x61,
+);
+
+impl FFEffectType {
+    /// The number of codes of this type, `FF_CNT`.
+    pub const COUNT: usize = 0x80;
+}
 
 // #[derive(Copy, Clone, PartialEq, Eq)]
 // pub struct RepeatType(pub u16);
@@ -323,7 +340,8 @@ impl MiscType {
 // evdev_enum!(RepeatType, REP_DELAY = 0x00, REP_PERIOD = 0x01,);
 
 // impl RepeatType {
-//     pub(crate) const COUNT: usize = libc::REP_CNT;
+//     /// The number of codes of this type, `REP_CNT`.
+pub const COUNT: usize = 0x02;
 // }
 
 /// A type associated with simple sounds, such as beeps or tones.
@@ -339,5 +357,6 @@ evdev_enum!(
 );
 
 impl SoundType {
-    pub(crate) const COUNT: usize = libc::SND_CNT;
+    /// The number of codes of this type, `SND_CNT`.
+    pub const COUNT: usize = 0x08;
 }
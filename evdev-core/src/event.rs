@@ -0,0 +1,162 @@
+use crate::{EventType, Synchronization};
+
+/// An event as it travels over the wire, independent of the host's `struct input_event`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawEvent {
+    pub sec: i64,
+    pub usec: u32,
+    pub type_: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl RawEvent {
+    pub const fn new(type_: EventType, code: u16, value: i32) -> Self {
+        RawEvent {
+            sec: 0,
+            usec: 0,
+            type_: type_.0,
+            code,
+            value,
+        }
+    }
+
+    #[inline]
+    pub const fn event_type(&self) -> EventType {
+        EventType(self.type_)
+    }
+
+    /// Returns `true` for the `SYN_REPORT` that ends each frame.
+    #[inline]
+    pub const fn is_syn_report(&self) -> bool {
+        self.type_ == EventType::SYNCHRONIZATION.0 && self.code == Synchronization::SYN_REPORT.0
+    }
+}
+
+/// The width of the `sec` and `usec` fields of `struct input_event`, which follows the
+/// sender's `long`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeWidth {
+    Bits32,
+    Bits64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// The layout of `struct input_event` on the machine that produced the bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WireFormat {
+    pub time_width: TimeWidth,
+    pub endianness: Endianness,
+}
+
+impl WireFormat {
+    /// The layout used by the machine this code was compiled for.
+    pub const NATIVE: WireFormat = WireFormat {
+        time_width: if cfg!(target_pointer_width = "64") {
+            TimeWidth::Bits64
+        } else {
+            TimeWidth::Bits32
+        },
+        endianness: if cfg!(target_endian = "big") {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        },
+    };
+
+    pub const fn new(time_width: TimeWidth, endianness: Endianness) -> Self {
+        WireFormat {
+            time_width,
+            endianness,
+        }
+    }
+
+    /// The size of one event in bytes: 16 with 32-bit time fields, 24 with 64-bit ones.
+    #[inline]
+    pub const fn event_size(self) -> usize {
+        self.time_size() * 2 + 8
+    }
+
+    const fn time_size(self) -> usize {
+        match self.time_width {
+            TimeWidth::Bits32 => 4,
+            TimeWidth::Bits64 => 8,
+        }
+    }
+
+    /// Decode the event at the start of `bytes`, or return `None` if there are fewer than
+    /// [`event_size`](Self::event_size) bytes.
+    pub fn decode(self, bytes: &[u8]) -> Option<RawEvent> {
+        let bytes = bytes.get(..self.event_size())?;
+        let t = self.time_size();
+        let (sec, usec) = match self.time_width {
+            TimeWidth::Bits32 => (
+                self.read::<4>(&bytes[..4]) as i32 as i64,
+                self.read::<4>(&bytes[4..8]) as u32,
+            ),
+            TimeWidth::Bits64 => (
+                self.read::<8>(&bytes[..8]) as i64,
+                self.read::<8>(&bytes[8..16]) as u32,
+            ),
+        };
+        let rest = &bytes[t * 2..];
+        Some(RawEvent {
+            sec,
+            usec,
+            type_: self.read::<2>(&rest[..2]) as u16,
+            code: self.read::<2>(&rest[2..4]) as u16,
+            value: self.read::<4>(&rest[4..8]) as u32 as i32,
+        })
+    }
+
+    /// Encode `ev` into the first [`event_size`](Self::event_size) bytes of `out`, returning
+    /// the number of bytes written.
+    ///
+    /// # Panics
+    ///
+    /// If `out` is too short.
+    pub fn encode(self, ev: &RawEvent, out: &mut [u8]) -> usize {
+        let t = self.time_size();
+        let out = &mut out[..self.event_size()];
+        self.write(&mut out[..t], ev.sec as u64);
+        self.write(&mut out[t..t * 2], ev.usec as u64);
+        let rest = &mut out[t * 2..];
+        self.write(&mut rest[..2], ev.type_ as u64);
+        self.write(&mut rest[2..4], ev.code as u64);
+        self.write(&mut rest[4..8], ev.value as u32 as u64);
+        self.event_size()
+    }
+
+    fn read<const N: usize>(self, bytes: &[u8]) -> u64 {
+        let mut buf = [0u8; 8];
+        match self.endianness {
+            Endianness::Little => {
+                buf[..N].copy_from_slice(bytes);
+                u64::from_le_bytes(buf)
+            }
+            Endianness::Big => {
+                buf[8 - N..].copy_from_slice(bytes);
+                u64::from_be_bytes(buf)
+            }
+        }
+    }
+
+    fn write(self, out: &mut [u8], value: u64) {
+        let n = out.len();
+        match self.endianness {
+            Endianness::Little => out.copy_from_slice(&value.to_le_bytes()[..n]),
+            Endianness::Big => out.copy_from_slice(&value.to_be_bytes()[8 - n..]),
+        }
+    }
+}
+
+impl Default for WireFormat {
+    fn default() -> Self {
+        WireFormat::NATIVE
+    }
+}
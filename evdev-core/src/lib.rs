@@ -0,0 +1,40 @@
+//! The platform-independent part of the `evdev` crate.
+//!
+//! This crate holds the event code constants, attribute sets and a parser for the binary
+//! `input_event` format, without touching file descriptors or the standard library. It is
+//! meant for programs that receive forwarded events over a serial link or socket, possibly on
+//! another OS or a microcontroller, and want the same types as `evdev` itself. `evdev`
+//! re-exports everything here, so code on Linux should use it directly.
+
+#![no_std]
+#![deny(warnings)]
+
+extern crate alloc;
+
+// has to be first for its macro
+#[macro_use]
+pub mod attribute_set;
+
+pub mod constants;
+mod event;
+mod parser;
+pub mod scancodes;
+
+pub use attribute_set::{AttributeSet, AttributeSetRef};
+pub use constants::*;
+pub use event::{Endianness, RawEvent, TimeWidth, WireFormat};
+pub use parser::FrameParser;
+pub use scancodes::*;
+
+#[doc(hidden)]
+pub use bitvec as __bitvec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumParseError(());
+
+impl EnumParseError {
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        EnumParseError(())
+    }
+}
@@ -0,0 +1,126 @@
+use alloc::vec::Vec;
+
+use crate::{EventType, RawEvent, Synchronization, WireFormat};
+
+/// Splits a byte stream of `input_event`s into frames.
+///
+/// Bytes can be fed in chunks of any size, e.g. as they arrive from a socket; events split
+/// across chunks are reassembled. Following the kernel's rules for `SYN_DROPPED`, the frame it
+/// interrupts is discarded along with everything up to the next `SYN_REPORT`.
+#[derive(Debug, Clone)]
+pub struct FrameParser {
+    format: WireFormat,
+    buf: Vec<u8>,
+    pos: usize,
+    frame: Vec<RawEvent>,
+    dropping: bool,
+    dropped: usize,
+}
+
+impl FrameParser {
+    pub fn new(format: WireFormat) -> Self {
+        FrameParser {
+            format,
+            buf: Vec::new(),
+            pos: 0,
+            frame: Vec::new(),
+            dropping: false,
+            dropped: 0,
+        }
+    }
+
+    pub fn format(&self) -> WireFormat {
+        self.format
+    }
+
+    /// Append received bytes.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the next complete frame, including its `SYN_REPORT`, or `None` if more bytes
+    /// are needed.
+    pub fn next_frame(&mut self) -> Option<Vec<RawEvent>> {
+        while let Some(ev) = self.format.decode(&self.buf[self.pos..]) {
+            self.pos += self.format.event_size();
+            if ev.event_type() == EventType::SYNCHRONIZATION
+                && ev.code == Synchronization::SYN_DROPPED.0
+            {
+                self.frame.clear();
+                self.dropping = true;
+                self.dropped += 1;
+                continue;
+            }
+            if self.dropping {
+                self.dropping = !ev.is_syn_report();
+                continue;
+            }
+            self.frame.push(ev);
+            if ev.is_syn_report() {
+                return Some(core::mem::take(&mut self.frame));
+            }
+        }
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        None
+    }
+
+    /// The number of `SYN_DROPPED` events seen so far.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Discard buffered bytes and any partial frame, e.g. after reconnecting.
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.pos = 0;
+        self.frame.clear();
+        self.dropping = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Endianness, Key, TimeWidth};
+
+    #[test]
+    fn reassembles_frames() {
+        let format = WireFormat::new(TimeWidth::Bits32, Endianness::Big);
+        let events = [
+            RawEvent::new(EventType::KEY, Key::KEY_A.code(), 1),
+            RawEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+            RawEvent::new(EventType::KEY, Key::KEY_B.code(), 1),
+            RawEvent::new(
+                EventType::SYNCHRONIZATION,
+                Synchronization::SYN_DROPPED.0,
+                0,
+            ),
+            RawEvent::new(EventType::KEY, Key::KEY_C.code(), 1),
+            RawEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+            RawEvent {
+                sec: -1,
+                usec: 999_999,
+                ..RawEvent::new(EventType::KEY, Key::KEY_A.code(), 0)
+            },
+            RawEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+        ];
+        let mut bytes = Vec::new();
+        for ev in &events {
+            let mut buf = [0; 16];
+            format.encode(ev, &mut buf);
+            bytes.extend_from_slice(&buf);
+        }
+
+        let mut parser = FrameParser::new(format);
+        let mut frames = Vec::new();
+        for chunk in bytes.chunks(5) {
+            parser.feed(chunk);
+            while let Some(frame) = parser.next_frame() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames, [&events[..2], &events[6..]]);
+        assert_eq!(parser.dropped(), 1);
+    }
+}
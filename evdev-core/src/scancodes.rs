@@ -18,12 +18,13 @@ impl Key {
         self.0
     }
 
-    pub(crate) const COUNT: usize = libc::KEY_CNT;
+    /// The number of codes of this type, `KEY_CNT`.
+    pub const COUNT: usize = 0x300;
 }
 
 evdev_enum!(
     Key,
-    box Array,
+    Array,
     KEY_RESERVED = 0,
     KEY_ESC = 1,
     KEY_1 = 2,
@@ -576,7 +577,7 @@ evdev_enum!(
 
 #[test]
 fn from_str() {
-    use std::str::FromStr;
+    use core::str::FromStr;
 
     assert_eq!(Key::from_str("KEY_A"), Ok(Key::KEY_A));
     assert!(Key::from_str("KEY_FOOBAR").is_err());
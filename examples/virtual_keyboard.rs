@@ -1,7 +1,7 @@
 // Create a virtual keyboard, just while this is running.
 // Generally this requires root.
 
-use evdev::{uinput::VirtualDeviceBuilder, AttributeSet, EventType, InputEvent, Key, LedType};
+use evdev::{uinput::VirtualDeviceBuilder, AttributeSet, EventType, InputEvent, Key};
 use std::thread::sleep;
 use std::time::Duration;
 
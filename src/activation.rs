@@ -0,0 +1,134 @@
+//! Receiving devices from systemd or a privileged parent.
+//!
+//! A daemon that reads input doesn't need the privileges to open device nodes, if something
+//! else opens them: systemd passes descriptors to socket activated services, and keeps the
+//! descriptors a service stored in its file descriptor store across restarts. [`listen_fds`]
+//! takes the descriptors systemd passed, and [`store_fd`] stores one, so a restarted daemon
+//! gets its devices back even if it can no longer open them. Descriptors from a parent that
+//! passed them some other way can be adopted with
+//! [`Device::from_inherited_fd`](crate::Device::from_inherited_fd).
+//!
+//! Either way, converting a descriptor into a [`Device`](crate::Device) checks that it is an
+//! evdev device.
+//!
+//! ```no_run
+//! use evdev::activation;
+//! use evdev::Device;
+//!
+//! let mut devices = Vec::new();
+//! for (name, fd) in activation::listen_fds()? {
+//!     let device = Device::try_from(fd)?;
+//!     // Keep it across restarts
+//!     activation::store_fd(&device, &name)?;
+//!     devices.push(device);
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::env;
+use std::io;
+use std::os::unix::io::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::sys::socket::{sendmsg, ControlMessage, MsgFlags, SockAddr, UnixAddr};
+use nix::sys::uio::IoVec;
+
+/// The first descriptor systemd passes, after stdin, stdout and stderr.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Takes the descriptors systemd passed to this process, with their names from
+/// `FileDescriptorName=` or `FDNAME=`, or `unknown` for unnamed ones. Like `sd_listen_fds`.
+///
+/// The variables describing them are removed from the environment, so they aren't passed on to
+/// child processes and later calls return nothing. Call it early, before starting threads that
+/// read the environment.
+pub fn listen_fds() -> io::Result<Vec<(String, OwnedFd)>> {
+    let pid = env::var("LISTEN_PID").ok();
+    let count = env::var("LISTEN_FDS").ok();
+    let names = env::var("LISTEN_FDNAMES").ok();
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(var);
+    }
+
+    // The variables may have been meant for a parent that didn't remove them
+    if pid.and_then(|pid| pid.parse().ok()) != Some(std::process::id()) {
+        return Ok(Vec::new());
+    }
+    let count: RawFd = match count.map(|count| count.parse()) {
+        Some(Ok(count)) => count,
+        None => return Ok(Vec::new()),
+        Some(Err(_)) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "LISTEN_FDS is not a number",
+            ))
+        }
+    };
+    let mut names = names.iter().flat_map(|names| names.split(':'));
+
+    let mut fds = Vec::new();
+    for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+        fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+        let name = names.next().unwrap_or("unknown").to_owned();
+        // SAFETY: systemd passed the descriptor to this process, and the environment no longer
+        // tells anything else about it
+        fds.push((name, unsafe { OwnedFd::from_raw_fd(fd) }));
+    }
+    Ok(fds)
+}
+
+/// Stores a copy of `fd` in systemd's file descriptor store under `name`, to be passed back
+/// by [`listen_fds`] when the service restarts. Like `sd_pid_notify_with_fds` with
+/// `FDSTORE=1`.
+///
+/// The service needs `FileDescriptorStoreMax=` set for systemd to keep it. Returns `false` if
+/// the process wasn't started by systemd. systemd drops stored device descriptors once the
+/// device is unplugged.
+pub fn store_fd(fd: &impl AsFd, name: &str) -> io::Result<bool> {
+    let path = match env::var("NOTIFY_SOCKET") {
+        Ok(path) => path,
+        Err(_) => return Ok(false),
+    };
+    let addr = match path.strip_prefix('@') {
+        Some(name) => UnixAddr::new_abstract(name.as_bytes())?,
+        None => UnixAddr::new(path.as_str())?,
+    };
+    let message = format!("FDSTORE=1\nFDNAME={}", name);
+    let socket = UnixDatagram::unbound()?;
+    let fds = [fd.as_fd().as_raw_fd()];
+    sendmsg(
+        socket.as_raw_fd(),
+        &[IoVec::from_slice(message.as_bytes())],
+        &[ControlMessage::ScmRights(&fds)],
+        MsgFlags::empty(),
+        Some(&SockAddr::Unix(addr)),
+    )?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Device;
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+
+    #[test]
+    fn reject_inherited_non_device() -> std::io::Result<()> {
+        let file = File::open("/dev/null")?;
+        let err = match unsafe { Device::from_inherited_fd(file.as_raw_fd()) } {
+            Ok(_) => panic!("/dev/null is not a device"),
+            Err(err) => crate::Error::from(err),
+        };
+        assert!(matches!(
+            err,
+            crate::Error::Ioctl {
+                name: "EVIOCGVERSION",
+                ..
+            }
+        ));
+        // Still open, and still owned by `file`
+        nix::fcntl::fcntl(file.as_raw_fd(), nix::fcntl::FcntlArg::F_GETFD)?;
+        Ok(())
+    }
+}
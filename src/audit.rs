@@ -0,0 +1,237 @@
+//! Logging input without recording what was typed.
+//!
+//! Debugging input problems on a production machine, like a keyboard that drops keys or a mouse
+//! that stutters, needs a record of the events and their timing, but a plain event log is a
+//! keylogger. An [`AuditLog`] writes one line per frame with the timestamp, the time since the
+//! previous frame and the events, with key codes and scancodes masked or hashed according to
+//! its [`KeyPrivacy`], and periodically a summary of the frame and event rates:
+//!
+//! ```text
+//! [   12345.678901] +8.012ms EV_MSC MSC_SCAN *, EV_KEY key#5e0c2a91 DOWN
+//! [   12345.686913] +8.012ms EV_REL REL_X 3, EV_REL REL_Y -1
+//! # 10.000s: 125.1 frames/s, 250.3 events/s (EV_KEY 12, EV_MSC 12, EV_REL 2479)
+//! ```
+//!
+//! Buttons (`BTN_*`) are recorded as they are, since they don't reveal what was typed.
+//!
+//! Attach a log to a [`Proxy`](crate::proxy::Proxy) with
+//! [`with_audit`](crate::proxy::Proxy::with_audit) to record the frames it reads.
+//!
+//! ```no_run
+//! use evdev::audit::{AuditLog, KeyPrivacy};
+//! use evdev::proxy::Proxy;
+//!
+//! let device = evdev::Device::open("/dev/input/event3")?;
+//! let sink = evdev::uinput::VirtualDeviceBuilder::new()?
+//!     .name("proxied keyboard")
+//!     .with_keys(device.supported_keys().unwrap())?
+//!     .build()?;
+//! let log = std::fs::File::create("/var/log/input-audit.log")?;
+//! let mut proxy = Proxy::new(device, sink).with_audit(AuditLog::new(log, KeyPrivacy::Hash));
+//! proxy.run()?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime};
+
+use crate::getevent::{code_name, KEY_VALUES, TYPE_NAMES};
+use crate::transform::is_syn_report;
+use crate::{EventType, InputEvent, Key, MiscType, RawEvent};
+
+/// How key codes appear in an [`AuditLog`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum KeyPrivacy {
+    /// Every key is written as `KEY_*`.
+    Mask,
+    /// Keys are written as a hash of their code, like `key#5e0c2a91`, so repeated presses of
+    /// the same key can be told apart from presses of different keys, e.g. to spot a key that
+    /// bounces. The hash is salted with a secret that differs between logs, so the same key
+    /// hashes differently in each.
+    Hash,
+}
+
+/// Returns `true` if `key` is a key, rather than a button.
+fn is_key(key: Key) -> bool {
+    let button = (Key::BTN_0.code()..=Key::BTN_GEAR_UP.code()).contains(&key.code())
+        || key.code() >= Key::BTN_TRIGGER_HAPPY1.code();
+    !button
+}
+
+/// Writes privacy-filtered event logs. See the [module documentation](self).
+pub struct AuditLog {
+    writer: Box<dyn Write + Send>,
+    privacy: KeyPrivacy,
+    salt: RandomState,
+    summary_interval: Duration,
+    last_frame: Option<SystemTime>,
+    window_start: Option<SystemTime>,
+    window_frames: u64,
+    window_events: [u64; EventType::COUNT],
+}
+
+impl AuditLog {
+    /// Log to `writer`, with a summary of the rates every 10 seconds.
+    pub fn new(writer: impl Write + Send + 'static, privacy: KeyPrivacy) -> Self {
+        AuditLog {
+            writer: Box::new(writer),
+            privacy,
+            salt: RandomState::new(),
+            summary_interval: Duration::from_secs(10),
+            last_frame: None,
+            window_start: None,
+            window_frames: 0,
+            window_events: [0; EventType::COUNT],
+        }
+    }
+
+    /// Summarize the rates every `interval` instead, or never if it is zero.
+    pub fn summary_interval(mut self, interval: Duration) -> Self {
+        self.summary_interval = interval;
+        self
+    }
+
+    pub fn privacy(&self) -> KeyPrivacy {
+        self.privacy
+    }
+
+    /// Format an event, without its timestamp, filtering the key code.
+    pub fn format_event(&self, ev: &InputEvent) -> String {
+        let ty = ev.event_type();
+        let mut line = match TYPE_NAMES.iter().find(|(_, t)| *t == ty) {
+            Some((name, _)) => name.to_string(),
+            None => format!("{:04x}", ty.0),
+        };
+        let key = Key::new(ev.code());
+        if ty == EventType::KEY && is_key(key) {
+            match self.privacy {
+                KeyPrivacy::Mask => line += " KEY_*",
+                KeyPrivacy::Hash => {
+                    line += &format!(" key#{:08x}", self.salt.hash_one(key.code()) as u32)
+                }
+            }
+        } else {
+            match code_name(ty, ev.code()) {
+                Some(name) => line += &format!(" {}", name),
+                None => line += &format!(" {:04x}", ev.code()),
+            }
+        }
+        let value = match ty {
+            EventType::KEY => KEY_VALUES.get(ev.value() as usize).map(|v| v.to_string()),
+            // Scancodes identify the key as well as the key code does
+            EventType::MISC if MiscType(ev.code()) == MiscType::MSC_SCAN => Some("*".into()),
+            _ => None,
+        };
+        line += &format!(" {}", value.unwrap_or_else(|| ev.value().to_string()));
+        line
+    }
+
+    /// Record a frame, ending in `SYN_REPORT`.
+    pub fn record_frame(&mut self, frame: &[InputEvent]) -> io::Result<()> {
+        let Some(syn) = frame.last() else {
+            return Ok(());
+        };
+        let time = syn.timestamp();
+        self.maybe_summarize(time)?;
+
+        let raw = RawEvent::from(*syn);
+        let mut line = format!("[{:8}.{:06}]", raw.sec, raw.usec);
+        if let Some(elapsed) = self
+            .last_frame
+            .and_then(|last| time.duration_since(last).ok())
+        {
+            line += &format!(" +{:.3}ms", elapsed.as_secs_f64() * 1000.0);
+        }
+        self.last_frame = Some(time);
+        let events: Vec<String> = frame
+            .iter()
+            .filter(|ev| !is_syn_report(ev))
+            .map(|ev| self.format_event(ev))
+            .collect();
+        line += " ";
+        line += &events.join(", ");
+        writeln!(self.writer, "{}", line)?;
+
+        self.window_frames += 1;
+        for ev in frame.iter().filter(|ev| !is_syn_report(ev)) {
+            if let Some(count) = self.window_events.get_mut(ev.event_type().0 as usize) {
+                *count += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the rates summary if the current window is over as of `now`.
+    fn maybe_summarize(&mut self, now: SystemTime) -> io::Result<()> {
+        if self.summary_interval.is_zero() {
+            return Ok(());
+        }
+        let start = *self.window_start.get_or_insert(now);
+        let elapsed = now.duration_since(start).unwrap_or_default();
+        if elapsed < self.summary_interval {
+            return Ok(());
+        }
+        let secs = elapsed.as_secs_f64();
+        let total: u64 = self.window_events.iter().sum();
+        let counts: Vec<String> = TYPE_NAMES
+            .iter()
+            .filter_map(|(name, ty)| {
+                let count = self.window_events[ty.0 as usize];
+                (count > 0).then(|| format!("{} {}", name, count))
+            })
+            .collect();
+        writeln!(
+            self.writer,
+            "# {:.3}s: {:.1} frames/s, {:.1} events/s ({})",
+            secs,
+            self.window_frames as f64 / secs,
+            total as f64 / secs,
+            counts.join(", ")
+        )?;
+        self.window_start = Some(now);
+        self.window_frames = 0;
+        self.window_events = [0; EventType::COUNT];
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("privacy", &self.privacy)
+            .field("summary_interval", &self.summary_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_filtered() {
+        let key = |key: Key| InputEvent::new(EventType::KEY, key.code(), 1);
+        let scan = InputEvent::new(EventType::MISC, MiscType::MSC_SCAN.0, 0x70004);
+
+        let masked = AuditLog::new(io::sink(), KeyPrivacy::Mask);
+        assert_eq!(masked.format_event(&key(Key::KEY_A)), "EV_KEY KEY_* DOWN");
+        assert_eq!(masked.format_event(&scan), "EV_MSC MSC_SCAN *");
+        assert_eq!(
+            masked.format_event(&key(Key::BTN_LEFT)),
+            "EV_KEY BTN_LEFT DOWN"
+        );
+
+        let hashed = AuditLog::new(io::sink(), KeyPrivacy::Hash);
+        let a = hashed.format_event(&key(Key::KEY_A));
+        assert!(a.starts_with("EV_KEY key#"));
+        assert_eq!(a, hashed.format_event(&key(Key::KEY_A)));
+        assert_ne!(a, hashed.format_event(&key(Key::KEY_B)));
+    }
+}
@@ -0,0 +1,156 @@
+//! Batteries of input devices, found through sysfs.
+//!
+//! Wireless controllers and other battery powered input devices register a `power_supply`
+//! node next to their input device: under the HID device for Bluetooth and `hid-*` drivers,
+//! under the USB interface for `xpad`.
+
+use std::fs;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+
+/// Whether a battery is charging.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BatteryStatus {
+    Unknown,
+    Charging,
+    Discharging,
+    NotCharging,
+    Full,
+}
+
+/// A coarse charge level, reported by devices that don't know their exact capacity.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CapacityLevel {
+    Unknown,
+    Critical,
+    Low,
+    Normal,
+    High,
+    Full,
+}
+
+/// The battery of an input device, a node in `/sys/class/power_supply`.
+///
+/// Values are read from sysfs on every call, so they are always current.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Battery {
+    path: PathBuf,
+}
+
+impl Battery {
+    /// Returns the sysfs directory of the power supply.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the name of the power supply, e.g. `ps-controller-battery-<mac>`.
+    pub fn name(&self) -> Option<&str> {
+        self.path.file_name()?.to_str()
+    }
+
+    fn read(&self, attribute: &str) -> io::Result<Option<String>> {
+        match fs::read_to_string(self.path.join(attribute)) {
+            Ok(value) => Ok(Some(value.trim().to_owned())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the charge in percent, or `None` if the device doesn't report it.
+    pub fn capacity(&self) -> io::Result<Option<u8>> {
+        Ok(self.read("capacity")?.and_then(|c| c.parse().ok()))
+    }
+
+    /// Returns the coarse charge level. Devices that report an exact
+    /// [`capacity`](Self::capacity) may not report this.
+    pub fn capacity_level(&self) -> io::Result<Option<CapacityLevel>> {
+        let level = self.read("capacity_level")?;
+        Ok(level.map(|level| match level.as_str() {
+            "Critical" => CapacityLevel::Critical,
+            "Low" => CapacityLevel::Low,
+            "Normal" => CapacityLevel::Normal,
+            "High" => CapacityLevel::High,
+            "Full" => CapacityLevel::Full,
+            _ => CapacityLevel::Unknown,
+        }))
+    }
+
+    pub fn status(&self) -> io::Result<BatteryStatus> {
+        let status = self.read("status")?;
+        Ok(match status.as_deref() {
+            Some("Charging") => BatteryStatus::Charging,
+            Some("Discharging") => BatteryStatus::Discharging,
+            Some("Not charging") => BatteryStatus::NotCharging,
+            Some("Full") => BatteryStatus::Full,
+            _ => BatteryStatus::Unknown,
+        })
+    }
+}
+
+/// Returns the sysfs directory of the input device behind `fd`, e.g.
+/// `/sys/devices/.../input/input12`.
+pub(crate) fn sysfs_path(fd: RawFd) -> io::Result<PathBuf> {
+    let rdev = nix::sys::stat::fstat(fd)?.st_rdev;
+    let (major, minor) = (libc::major(rdev), libc::minor(rdev));
+    // This links to the event node's directory, whose parent is the input device
+    let event = fs::canonicalize(format!("/sys/dev/char/{}:{}", major, minor))?;
+    event
+        .parent()
+        .map(Path::to_owned)
+        .ok_or_else(|| io::ErrorKind::NotFound.into())
+}
+
+/// Find the battery of the input device at `input`, looking for a `power_supply` directory
+/// in the devices it hangs off.
+pub(crate) fn find_battery(input: &Path) -> io::Result<Option<Battery>> {
+    for dir in input.ancestors().skip(1) {
+        if dir.file_name().is_none_or(|name| name == "devices") {
+            break;
+        }
+        let supplies = match fs::read_dir(dir.join("power_supply")) {
+            Ok(supplies) => supplies,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for supply in supplies {
+            let path = supply?.path();
+            let kind = fs::read_to_string(path.join("type")).unwrap_or_default();
+            if kind.trim() == "Battery" {
+                return Ok(Some(Battery { path }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_controller_battery() -> io::Result<()> {
+        let root = std::env::temp_dir().join(format!("evdev-battery-{}", std::process::id()));
+        let hid = root.join("devices/pci0000:00/bluetooth/0005:054C:09CC.0003");
+        let input = hid.join("input/input12");
+        let supply = hid.join("power_supply/ps-controller-battery-00:11:22:33:44:55");
+        fs::create_dir_all(&input)?;
+        fs::create_dir_all(&supply)?;
+        fs::write(supply.join("type"), "Battery\n")?;
+        fs::write(supply.join("capacity"), "85\n")?;
+        fs::write(supply.join("status"), "Not charging\n")?;
+
+        let battery = find_battery(&input)?.unwrap();
+        assert_eq!(
+            battery.name(),
+            Some("ps-controller-battery-00:11:22:33:44:55")
+        );
+        assert_eq!(battery.capacity()?, Some(85));
+        assert_eq!(battery.capacity_level()?, None);
+        assert_eq!(battery.status()?, BatteryStatus::NotCharging);
+        assert!(find_battery(&root.join("devices/virtual/input/input3"))?.is_none());
+
+        fs::remove_dir_all(&root)
+    }
+}
@@ -0,0 +1,352 @@
+//! Reading a device once on behalf of several consumers.
+//!
+//! A logger, a gesture engine and a forwarder that all want a device's events would otherwise
+//! each open it, which doesn't work once one of them grabs it. A [`Broadcaster`] reads the
+//! device, splits its events into frames, and hands a copy of every frame to each
+//! [`Subscriber`], typically running on its own thread. Each subscriber has a bounded queue and
+//! its own [`Backpressure`] policy for when it falls behind.
+//!
+//! ```no_run
+//! use evdev::broadcast::{Backpressure, Broadcaster};
+//!
+//! let mut device = evdev::Device::open("/dev/input/event3")?;
+//! device.grab()?;
+//! let mut broadcaster = Broadcaster::new(device);
+//! let logger = broadcaster.subscribe(1024, Backpressure::DropOldest);
+//! std::thread::spawn(move || {
+//!     for frame in logger {
+//!         println!("{:?}", frame);
+//!     }
+//! });
+//! broadcaster.run()?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::transform::is_syn_report;
+use crate::{Device, InputEvent};
+
+/// What happens when a subscriber's queue is full and another frame arrives.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Backpressure {
+    /// Discard the oldest queued frame to make room. Suits consumers that only care about
+    /// recent input, like gesture recognizers.
+    DropOldest,
+    /// Discard the new frame.
+    DropNewest,
+    /// Wait for the subscriber to make room. This holds up every other subscriber too, and
+    /// eventually the device, so it suits consumers that must not lose events, like
+    /// forwarders, and that reliably keep up.
+    Block,
+    /// Stop delivering to the subscriber. It receives what was already queued, then nothing.
+    Disconnect,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    frames: VecDeque<Vec<InputEvent>>,
+    dropped: u64,
+    /// The subscriber fell behind under [`Backpressure::Disconnect`], or was dropped.
+    disconnected: bool,
+    /// The broadcaster is gone.
+    closed: bool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+struct Subscription {
+    shared: Arc<Shared>,
+    capacity: usize,
+    backpressure: Backpressure,
+}
+
+impl Subscription {
+    /// Queue a frame. Returns `false` if the subscriber is gone.
+    fn send(&self, frame: &[InputEvent]) -> bool {
+        let mut state = self.shared.lock();
+        while !state.disconnected && state.frames.len() >= self.capacity {
+            match self.backpressure {
+                Backpressure::DropOldest => {
+                    state.frames.pop_front();
+                    state.dropped += 1;
+                }
+                Backpressure::DropNewest => {
+                    state.dropped += 1;
+                    return true;
+                }
+                Backpressure::Block => state = self.shared.cond.wait(state).unwrap(),
+                Backpressure::Disconnect => {
+                    state.dropped += 1;
+                    state.disconnected = true;
+                }
+            }
+        }
+        if state.disconnected {
+            self.shared.cond.notify_all();
+            return false;
+        }
+        state.frames.push_back(frame.to_vec());
+        self.shared.cond.notify_all();
+        true
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.cond.notify_all();
+    }
+}
+
+/// Reads a device and hands its frames to subscribers. See the [module documentation](self).
+pub struct Broadcaster {
+    device: Device,
+    subscriptions: Vec<Subscription>,
+    frame: Vec<InputEvent>,
+}
+
+impl Broadcaster {
+    pub fn new(device: Device) -> Self {
+        Broadcaster {
+            device,
+            subscriptions: Vec::new(),
+            frame: Vec::new(),
+        }
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    /// Stop broadcasting and return the device. Subscribers receive what was already queued,
+    /// then nothing.
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+
+    /// Add a subscriber that queues up to `capacity` frames, and handles a full queue with
+    /// `backpressure`. It receives the frames that complete from now on.
+    pub fn subscribe(&mut self, capacity: usize, backpressure: Backpressure) -> Subscriber {
+        let shared = Arc::new(Shared::default());
+        self.subscriptions.push(Subscription {
+            shared: shared.clone(),
+            capacity: capacity.max(1),
+            backpressure,
+        });
+        Subscriber { shared }
+    }
+
+    /// Returns the number of subscribers still receiving frames.
+    pub fn subscriber_count(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Add an event to the current frame, handing the frame to the subscribers once complete.
+    pub fn process_event(&mut self, ev: &InputEvent) {
+        self.frame.push(*ev);
+        if is_syn_report(ev) {
+            let frame = &self.frame;
+            self.subscriptions.retain(|s| s.send(frame));
+            self.frame.clear();
+        }
+    }
+
+    /// Fetch the next batch of events from the device and hand the completed frames to the
+    /// subscribers.
+    ///
+    /// Like [`Device::fetch_events`], this blocks unless the device is non-blocking.
+    pub fn fetch_events(&mut self) -> io::Result<()> {
+        let events: Vec<InputEvent> = self.device.fetch_events()?.collect();
+        for ev in &events {
+            self.process_event(ev);
+        }
+        Ok(())
+    }
+
+    /// Broadcast until an error occurs, such as the device being unplugged.
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.fetch_events()?;
+        }
+    }
+}
+
+impl AsRawFd for Broadcaster {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for Broadcaster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Broadcaster")
+            .field("device", &self.device.name())
+            .field("subscribers", &self.subscriptions.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Receives the frames of a [`Broadcaster`].
+///
+/// Iterating blocks for each frame, and ends once no more will arrive.
+pub struct Subscriber {
+    shared: Arc<Shared>,
+}
+
+impl Subscriber {
+    /// Wait for the next frame. Returns `None` once the broadcaster is gone, or has stopped
+    /// delivering to this subscriber, and the queue is empty.
+    pub fn recv(&self) -> Option<Vec<InputEvent>> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                self.shared.cond.notify_all();
+                return Some(frame);
+            }
+            if state.closed || state.disconnected {
+                return None;
+            }
+            state = self.shared.cond.wait(state).unwrap();
+        }
+    }
+
+    /// Wait up to `timeout` for the next frame.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Vec<InputEvent>> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                self.shared.cond.notify_all();
+                return Some(frame);
+            }
+            let now = Instant::now();
+            if state.closed || state.disconnected || now >= deadline {
+                return None;
+            }
+            state = self
+                .shared
+                .cond
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Take the next frame if one is queued.
+    pub fn try_recv(&self) -> Option<Vec<InputEvent>> {
+        let frame = self.shared.lock().frames.pop_front();
+        if frame.is_some() {
+            self.shared.cond.notify_all();
+        }
+        frame
+    }
+
+    /// Returns the number of frames discarded because the queue was full.
+    pub fn dropped_frames(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// Returns `true` once no more frames will be queued, because the broadcaster is gone or
+    /// the subscriber fell behind under [`Backpressure::Disconnect`].
+    pub fn is_disconnected(&self) -> bool {
+        let state = self.shared.lock();
+        state.closed || state.disconnected
+    }
+}
+
+impl Iterator for Subscriber {
+    type Item = Vec<InputEvent>;
+
+    fn next(&mut self) -> Option<Vec<InputEvent>> {
+        self.recv()
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        // Releases a broadcaster blocked on this subscriber
+        self.shared.lock().disconnected = true;
+        self.shared.cond.notify_all();
+    }
+}
+
+impl fmt::Debug for Subscriber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.shared.lock();
+        f.debug_struct("Subscriber")
+            .field("queued", &state.frames.len())
+            .field("dropped", &state.dropped)
+            .field("disconnected", &(state.closed || state.disconnected))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventType;
+
+    #[test]
+    fn backpressure_policies() {
+        let subscription = |backpressure| {
+            let shared = Arc::new(Shared::default());
+            let subscription = Subscription {
+                shared: shared.clone(),
+                capacity: 2,
+                backpressure,
+            };
+            (subscription, Subscriber { shared })
+        };
+        let frame = |value| {
+            vec![
+                InputEvent::new(EventType::RELATIVE, 0, value),
+                InputEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+            ]
+        };
+        let values = |subscriber: &Subscriber| {
+            std::iter::from_fn(|| subscriber.try_recv())
+                .map(|f| f[0].value())
+                .collect::<Vec<_>>()
+        };
+
+        let (oldest, oldest_rx) = subscription(Backpressure::DropOldest);
+        let (newest, newest_rx) = subscription(Backpressure::DropNewest);
+        let (disconnect, disconnect_rx) = subscription(Backpressure::Disconnect);
+        for value in 1..=3 {
+            assert!(oldest.send(&frame(value)));
+            assert!(newest.send(&frame(value)));
+            assert_eq!(disconnect.send(&frame(value)), value < 3);
+        }
+        assert_eq!(values(&oldest_rx), [2, 3]);
+        assert_eq!(values(&newest_rx), [1, 2]);
+        assert_eq!(newest_rx.dropped_frames(), 1);
+        assert_eq!(values(&disconnect_rx), [1, 2]);
+        assert!(disconnect_rx.is_disconnected());
+
+        let (block, block_rx) = subscription(Backpressure::Block);
+        let sender = std::thread::spawn(move || (1..=3).all(|value| block.send(&frame(value))));
+        let received: Vec<_> = block_rx.take(3).map(|f| f[0].value()).collect();
+        assert_eq!(received, [1, 2, 3]);
+        assert!(sender.join().unwrap());
+    }
+}
@@ -0,0 +1,132 @@
+//! Classifying devices by their capabilities, the way udev's `input_id` builtin does.
+
+use crate::{AbsoluteAxisType, AttributeSetRef, Key, PropType, RelativeAxisType, SwitchType};
+
+/// What kind of input device something is, named after udev's `ID_INPUT_*` properties.
+///
+/// Classes are worked out from the capabilities alone, without udev or its hwdb, so they are
+/// available in static builds and containers too. A device can be of several classes, e.g. a
+/// keyboard with a built-in touchpad.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum DeviceClass {
+    /// A full keyboard, with at least the keys from `KEY_ESC` to `KEY_D`.
+    Keyboard,
+    /// Any device with keys that aren't buttons, like power buttons and media remotes.
+    Key,
+    Mouse,
+    PointingStick,
+    Touchpad,
+    Touchscreen,
+    /// A pen tablet.
+    Tablet,
+    /// The buttons and rings of a pen tablet.
+    TabletPad,
+    Joystick,
+    Accelerometer,
+    Switch,
+}
+
+pub(crate) fn classify(
+    props: &AttributeSetRef<PropType>,
+    keys: Option<&AttributeSetRef<Key>>,
+    rel: Option<&AttributeSetRef<RelativeAxisType>>,
+    abs: Option<&AttributeSetRef<AbsoluteAxisType>>,
+    switches: Option<&AttributeSetRef<SwitchType>>,
+) -> Vec<DeviceClass> {
+    let key = |k: Key| keys.is_some_and(|keys| keys.contains(k));
+    let rel = |a: RelativeAxisType| rel.is_some_and(|rel| rel.contains(a));
+    let abs = |a: AbsoluteAxisType| abs.is_some_and(|abs| abs.contains(a));
+    let mut classes = Vec::new();
+
+    let abs_xy = abs(AbsoluteAxisType::ABS_X) && abs(AbsoluteAxisType::ABS_Y);
+    let mt_xy =
+        abs(AbsoluteAxisType::ABS_MT_POSITION_X) && abs(AbsoluteAxisType::ABS_MT_POSITION_Y);
+    let stylus = key(Key::BTN_STYLUS) || key(Key::BTN_TOOL_PEN);
+    let finger = key(Key::BTN_TOOL_FINGER);
+    let touch = key(Key::BTN_TOUCH);
+    let left = key(Key::BTN_LEFT);
+    // From BTN_TRIGGER through the gamepad buttons
+    let joystick_buttons = (Key::BTN_TRIGGER.code()..=Key::BTN_THUMBR.code())
+        .chain(Key::BTN_TRIGGER_HAPPY1.code()..=Key::BTN_TRIGGER_HAPPY40.code())
+        .any(|code| key(Key::new(code)));
+
+    if props.contains(PropType::ACCELEROMETER) {
+        classes.push(DeviceClass::Accelerometer);
+    } else if abs_xy || mt_xy {
+        if stylus {
+            classes.push(DeviceClass::Tablet);
+        } else if key(Key::BTN_0) && !touch {
+            classes.push(DeviceClass::TabletPad);
+        } else if finger && !props.contains(PropType::DIRECT) {
+            classes.push(DeviceClass::Touchpad);
+        } else if touch || props.contains(PropType::DIRECT) {
+            classes.push(DeviceClass::Touchscreen);
+        } else if left {
+            // Absolute mice, like the tablets of virtual machines
+            classes.push(DeviceClass::Mouse);
+        } else if joystick_buttons || abs_xy {
+            classes.push(DeviceClass::Joystick);
+        }
+    } else if joystick_buttons {
+        classes.push(DeviceClass::Joystick);
+    }
+    if rel(RelativeAxisType::REL_X) && rel(RelativeAxisType::REL_Y) && left {
+        classes.push(if props.contains(PropType::POINTING_STICK) {
+            DeviceClass::PointingStick
+        } else {
+            DeviceClass::Mouse
+        });
+    }
+
+    if (Key::KEY_ESC.code()..=Key::KEY_D.code()).all(|code| key(Key::new(code))) {
+        classes.push(DeviceClass::Keyboard);
+    }
+    // Keys below the buttons, and the newer keys after them
+    let is_key = |k: Key| k.code() < Key::BTN_0.code() || k.code() >= Key::KEY_OK.code();
+    let is_button = |k: Key| {
+        (Key::BTN_DPAD_UP.code()..=Key::BTN_DPAD_RIGHT.code()).contains(&k.code())
+            || k.code() >= Key::BTN_TRIGGER_HAPPY1.code()
+    };
+    if keys.is_some_and(|keys| keys.iter().any(|k| is_key(k) && !is_button(k))) {
+        classes.push(DeviceClass::Key);
+    }
+    if switches.is_some_and(|switches| switches.iter().next().is_some()) {
+        classes.push(DeviceClass::Switch);
+    }
+    classes.dedup();
+    classes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AttributeSet;
+
+    #[test]
+    fn classify_devices() {
+        let props = AttributeSet::<PropType>::new();
+        let keys: AttributeSet<Key> = [Key::BTN_LEFT, Key::BTN_RIGHT].into_iter().collect();
+        let rel: AttributeSet<RelativeAxisType> =
+            [RelativeAxisType::REL_X, RelativeAxisType::REL_Y]
+                .into_iter()
+                .collect();
+        let mouse = classify(&props, Some(&keys), Some(&rel), None, None);
+        assert_eq!(mouse, [DeviceClass::Mouse]);
+
+        let keys: AttributeSet<Key> = (1..=Key::KEY_CAPSLOCK.code()).map(Key::new).collect();
+        let keyboard = classify(&props, Some(&keys), None, None, None);
+        assert_eq!(keyboard, [DeviceClass::Keyboard, DeviceClass::Key]);
+
+        let keys: AttributeSet<Key> = [Key::BTN_TOUCH, Key::BTN_TOOL_FINGER].into_iter().collect();
+        let abs: AttributeSet<AbsoluteAxisType> =
+            [AbsoluteAxisType::ABS_X, AbsoluteAxisType::ABS_Y]
+                .into_iter()
+                .collect();
+        let touchpad = classify(&props, Some(&keys), None, Some(&abs), None);
+        assert_eq!(touchpad, [DeviceClass::Touchpad]);
+        let direct: AttributeSet<PropType> = [PropType::DIRECT].into_iter().collect();
+        let touchscreen = classify(&direct, Some(&keys), None, Some(&abs), None);
+        assert_eq!(touchscreen, [DeviceClass::Touchscreen]);
+    }
+}
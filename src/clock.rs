@@ -0,0 +1,259 @@
+//! A source of time that can be replaced in tests.
+//!
+//! Components that act on time, like [`LongPress`](crate::transform::LongPress) (tap-hold),
+//! [`MouseKeys`](crate::transform::MouseKeys) (autorepeat),
+//! [`BounceKeys`](crate::transform::BounceKeys) (debounce) and
+//! [`replay`](crate::getevent::replay_with_clock), take the time from a [`Clock`], the
+//! [`SystemClock`] by default. Both the times of events and the current time come from the
+//! clock, so a [`MockClock`] makes their timing deterministic:
+//!
+//! ```
+//! use evdev::clock::{Clock, MockClock};
+//! use evdev::transform::{EventTransform, LongPress, LongPressAction};
+//! use evdev::{EventType, InputEvent, Key};
+//! use std::time::Duration;
+//!
+//! let clock = MockClock::default();
+//! let mut longpress = LongPress::new()
+//!     .bind(Key::KEY_A, Duration::from_millis(500), LongPressAction::Key(Key::KEY_B))
+//!     .clock(clock.clone());
+//! let press = [
+//!     InputEvent::new(EventType::KEY, Key::KEY_A.code(), 1),
+//!     InputEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+//! ];
+//! let mut out = Vec::new();
+//! longpress.process(&press, &mut out);
+//! assert!(out.is_empty());
+//!
+//! clock.advance(Duration::from_millis(500));
+//! longpress.tick_now(&mut out);
+//! assert_eq!(out[0].code(), Key::KEY_B.code());
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::InputEvent;
+
+/// The current time, and a way to wait.
+pub trait Clock {
+    /// Returns the current time, on the realtime clock that event timestamps use by default.
+    fn now(&self) -> SystemTime;
+
+    /// Wait for `duration`.
+    fn sleep(&self, duration: Duration);
+
+    /// Returns how long until `deadline`, or zero if it passed.
+    fn until(&self, deadline: SystemTime) -> Duration {
+        deadline.duration_since(self.now()).unwrap_or_default()
+    }
+
+    /// Returns the real time to block for, e.g. on a condition variable, to wait for
+    /// `deadline`.
+    ///
+    /// This is [`until`](Self::until) by default. Clocks that don't follow real time move to
+    /// `deadline` instead, so the waiting thread isn't held up.
+    fn timeout(&self, deadline: SystemTime) -> Duration {
+        self.until(deadline)
+    }
+
+    /// Returns the time of `ev`, as seen by this clock.
+    ///
+    /// Devices stamp events with the realtime clock, so by default this is the event's
+    /// timestamp.
+    fn event_time(&self, ev: &InputEvent) -> SystemTime {
+        ev.timestamp()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+
+    fn timeout(&self, deadline: SystemTime) -> Duration {
+        (**self).timeout(deadline)
+    }
+
+    fn event_time(&self, ev: &InputEvent) -> SystemTime {
+        (**self).event_time(ev)
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+
+    fn timeout(&self, deadline: SystemTime) -> Duration {
+        (**self).timeout(deadline)
+    }
+
+    fn event_time(&self, ev: &InputEvent) -> SystemTime {
+        (**self).event_time(ev)
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+
+    fn timeout(&self, deadline: SystemTime) -> Duration {
+        (**self).timeout(deadline)
+    }
+
+    fn event_time(&self, ev: &InputEvent) -> SystemTime {
+        (**self).event_time(ev)
+    }
+}
+
+/// The system's realtime clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// A clock that only moves when told to, or when something sleeps or waits on it.
+///
+/// Clones share the same time, so a test can keep one and hand another to the code under test.
+/// It starts at the Unix epoch by default.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl MockClock {
+    pub fn new(start: SystemTime) -> Self {
+        MockClock {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    fn time(&self) -> std::sync::MutexGuard<'_, SystemTime> {
+        self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Move the time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.time() += duration;
+    }
+
+    /// Set the time, which may go backwards like the realtime clock can.
+    pub fn set(&self, time: SystemTime) {
+        *self.time() = time;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.time()
+    }
+
+    /// Returns right away, with the time moved forward by `duration`.
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration)
+    }
+
+    /// Returns zero, with the time moved forward to `deadline` if it is later.
+    fn timeout(&self, deadline: SystemTime) -> Duration {
+        let mut now = self.time();
+        *now = (*now).max(deadline);
+        Duration::ZERO
+    }
+
+    /// Returns the current time: devices know nothing of the mock time, so events are taken
+    /// to happen when they are processed.
+    fn event_time(&self, _ev: &InputEvent) -> SystemTime {
+        self.now()
+    }
+}
+
+/// A clock shared by a component and its clones, the [`SystemClock`] by default.
+#[derive(Clone)]
+pub(crate) struct SharedClock(Arc<dyn Clock + Send + Sync>);
+
+impl SharedClock {
+    pub(crate) fn new(clock: impl Clock + Send + Sync + 'static) -> Self {
+        SharedClock(Arc::new(clock))
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        Self::new(SystemClock)
+    }
+}
+
+impl std::fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("SharedClock")
+    }
+}
+
+impl Clock for SharedClock {
+    fn now(&self) -> SystemTime {
+        self.0.now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.0.sleep(duration)
+    }
+
+    fn timeout(&self, deadline: SystemTime) -> Duration {
+        self.0.timeout(deadline)
+    }
+
+    fn event_time(&self, ev: &InputEvent) -> SystemTime {
+        self.0.event_time(ev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_clock() {
+        let clock = MockClock::default();
+        let shared = clock.clone();
+        let at = |ms| SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+
+        shared.sleep(Duration::from_millis(250));
+        assert_eq!(clock.now(), at(250));
+        assert_eq!(clock.until(at(400)), Duration::from_millis(150));
+        clock.set(at(500));
+        assert_eq!(shared.until(at(400)), Duration::ZERO);
+        assert_eq!(shared.timeout(at(400)), Duration::ZERO);
+        assert_eq!(clock.now(), at(500));
+        assert_eq!(shared.timeout(at(600)), Duration::ZERO);
+        assert_eq!(clock.now(), at(600));
+    }
+}
@@ -0,0 +1,230 @@
+//! Recognizing key combinations in a keyboard's events.
+//!
+//! Command palettes, shortcut editors and on-screen overlays want to know what was pressed as
+//! a whole, like `Ctrl+Shift+P`, rather than as separate modifier and key events. [`Combos`]
+//! wraps the events of one keyboard and yields each of them along with the [`KeyCombo`] it
+//! completes, if any. Combos are [`Hotkey`]s, so they print the way hotkeys are written and
+//! can be compared against parsed ones.
+//!
+//! ```no_run
+//! use evdev::combo::{ComboParser, Combos};
+//!
+//! let mut device = evdev::Device::open("/dev/input/event0")?;
+//! let mut parser = ComboParser::new().lone_modifiers(true);
+//! let palette = "Ctrl+Shift+P".parse()?;
+//! loop {
+//!     for ev in Combos::with_parser(device.fetch_events()?, &mut parser) {
+//!         match ev.combo {
+//!             Some(combo) if combo == palette => println!("opening the command palette"),
+//!             Some(combo) => println!("{}", combo),
+//!             None => {}
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::borrow::BorrowMut;
+
+use crate::hotkey::Hotkey;
+use crate::modifiers::{ModifierTracker, Modifiers};
+use crate::{EventType, InputEvent, Key};
+
+/// A key pressed while some modifiers were held.
+pub type KeyCombo = Hotkey;
+
+/// An event read from the keyboard, with the combo it completed.
+#[derive(Debug, Copy, Clone)]
+pub struct ComboEvent {
+    pub event: InputEvent,
+    /// The combo, for the press of a key other than a modifier, or the release of a lone
+    /// modifier if [enabled](ComboParser::lone_modifiers).
+    pub combo: Option<KeyCombo>,
+}
+
+/// Works out the combos of a keyboard from its events. See the [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct ComboParser {
+    modifiers: ModifierTracker,
+    ignored: Modifiers,
+    lone_modifiers: bool,
+    repeats: bool,
+    /// The modifier key pressed last, while nothing else has been pressed since.
+    lone: Option<Key>,
+}
+
+impl ComboParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave `modifiers` out of combos, e.g. [`Modifiers::SHIFT`] to report `Shift+1` as `1`
+    /// for layouts where Shift only picks the character. None by default.
+    pub fn ignore(mut self, modifiers: Modifiers) -> Self {
+        self.ignored = modifiers.intersection(Modifiers::HELD);
+        self
+    }
+
+    /// Report a modifier pressed and released on its own as a combo of that key, e.g. a
+    /// tapped Super key as `Leftmeta`, to open a launcher. Off by default.
+    pub fn lone_modifiers(mut self, enabled: bool) -> Self {
+        self.lone_modifiers = enabled;
+        self
+    }
+
+    /// Report the combo again on every autorepeat of its key. Off by default.
+    pub fn repeats(mut self, enabled: bool) -> Self {
+        self.repeats = enabled;
+        self
+    }
+
+    /// Returns the modifiers held, without the ignored ones.
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers.held().difference(self.ignored)
+    }
+
+    /// Process an event, returning the combo it completes, if any.
+    pub fn process_event(&mut self, ev: &InputEvent) -> Option<KeyCombo> {
+        if ev.event_type() != EventType::KEY {
+            return None;
+        }
+        let key = Key::new(ev.code());
+        if Modifiers::from_key(key).is_some() {
+            self.modifiers.process_event(0, ev);
+            return match ev.value() {
+                1 => {
+                    self.lone = Some(key);
+                    None
+                }
+                0 if self.lone == Some(key) => {
+                    self.lone = None;
+                    let combo = KeyCombo::new(self.modifiers(), key);
+                    self.lone_modifiers.then_some(combo)
+                }
+                _ => None,
+            };
+        }
+        match ev.value() {
+            1 => self.lone = None,
+            2 if self.repeats => {}
+            _ => return None,
+        }
+        Some(KeyCombo::new(self.modifiers(), key))
+    }
+}
+
+/// Yields the events of a keyboard with the combos they complete. See the
+/// [module documentation](self).
+///
+/// Combos span frames, so keep one [`ComboParser`] across reads, with
+/// [`with_parser`](Self::with_parser), when reading with
+/// [`fetch_events`](crate::Device::fetch_events).
+#[derive(Debug, Clone)]
+pub struct Combos<I, P = ComboParser> {
+    events: I,
+    parser: P,
+}
+
+impl<I> Combos<I> {
+    pub fn new(events: impl IntoIterator<IntoIter = I>) -> Self {
+        Self::with_parser(events, ComboParser::new())
+    }
+}
+
+impl<I, P: BorrowMut<ComboParser>> Combos<I, P> {
+    /// Parse with `parser`, either owned or borrowed.
+    pub fn with_parser(events: impl IntoIterator<IntoIter = I>, parser: P) -> Self {
+        Combos {
+            events: events.into_iter(),
+            parser,
+        }
+    }
+
+    pub fn parser(&self) -> &ComboParser {
+        self.parser.borrow()
+    }
+
+    fn pair(&mut self, event: InputEvent) -> ComboEvent {
+        let combo = self.parser.borrow_mut().process_event(&event);
+        ComboEvent { event, combo }
+    }
+}
+
+impl<I: Iterator<Item = InputEvent>, P: BorrowMut<ComboParser>> Iterator for Combos<I, P> {
+    type Item = ComboEvent;
+
+    fn next(&mut self) -> Option<ComboEvent> {
+        let ev = self.events.next()?;
+        Some(self.pair(ev))
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod stream {
+    use super::*;
+
+    use futures_core::{ready, Stream};
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// Wraps a stream of events, such as an `EventStream`, in place of an iterator.
+    impl<S, P> Stream for Combos<S, P>
+    where
+        S: Stream<Item = io::Result<InputEvent>> + Unpin,
+        P: BorrowMut<ComboParser> + Unpin,
+    {
+        type Item = io::Result<ComboEvent>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            let item = ready!(Pin::new(&mut this.events).poll_next(cx));
+            Poll::Ready(item.map(|ev| ev.map(|ev| this.pair(ev))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_combos() {
+        let key = |key: Key, value| InputEvent::new(EventType::KEY, key.code(), value);
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        let events = [
+            key(Key::KEY_LEFTCTRL, 1),
+            syn,
+            key(Key::KEY_RIGHTSHIFT, 1),
+            syn,
+            key(Key::KEY_P, 1),
+            syn,
+            key(Key::KEY_P, 2),
+            syn,
+            key(Key::KEY_P, 0),
+            key(Key::KEY_RIGHTSHIFT, 0),
+            key(Key::KEY_LEFTCTRL, 0),
+            syn,
+            key(Key::KEY_LEFTMETA, 1),
+            syn,
+            key(Key::KEY_LEFTMETA, 0),
+            syn,
+        ];
+
+        let combos = |parser| -> Vec<String> {
+            Combos::with_parser(events, parser)
+                .filter_map(|ev| ev.combo.map(|c| c.to_string()))
+                .collect()
+        };
+        assert_eq!(combos(ComboParser::new()), ["Ctrl+Shift+P"]);
+        let parser = ComboParser::new()
+            .ignore(Modifiers::SHIFT)
+            .lone_modifiers(true)
+            .repeats(true);
+        assert_eq!(combos(parser), ["Ctrl+P", "Ctrl+P", "Leftmeta"]);
+        assert_eq!(
+            "Ctrl+Shift+P".parse::<KeyCombo>().unwrap(),
+            KeyCombo::new(Modifiers::CTRL | Modifiers::SHIFT, Key::KEY_P)
+        );
+    }
+}
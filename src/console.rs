@@ -0,0 +1,572 @@
+//! Key translation through the kernel console keymap.
+//!
+//! Text consoles usually have no XKB configuration, but the kernel has a keymap of its own,
+//! loaded by `loadkeys` from the files in `/usr/share/kbd/keymaps`. [`ConsoleKeymap`] reads it
+//! back from the kernel, or parses a keymap file directly, and [`ConsoleTranslator`] uses it to
+//! turn key events into the characters the console would type.
+//!
+//! ```no_run
+//! use evdev::console::{ConsoleKeymap, ConsoleTranslator};
+//!
+//! let mut device = evdev::Device::open("/dev/input/event0")?;
+//! let mut translator = ConsoleTranslator::new(ConsoleKeymap::from_console()?);
+//! loop {
+//!     for ev in device.fetch_events()? {
+//!         if let Some(c) = translator.process(&ev) {
+//!             print!("{}", c);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! Only the parts of the keymap that type text are interpreted: dead keys, compose sequences,
+//! function key strings and console switching are left to the caller.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use crate::error::ioctl_error;
+use crate::{sys, EventType, InputEvent, Key};
+
+/// Modifier bits. Each table of a keymap applies to one combination of these.
+pub mod modifiers {
+    pub const SHIFT: u8 = 1 << 0;
+    pub const ALTGR: u8 = 1 << 1;
+    pub const CONTROL: u8 = 1 << 2;
+    pub const ALT: u8 = 1 << 3;
+    pub const SHIFT_L: u8 = 1 << 4;
+    pub const SHIFT_R: u8 = 1 << 5;
+    pub const CTRL_L: u8 = 1 << 6;
+    pub const CTRL_R: u8 = 1 << 7;
+}
+
+/// The number of keys per table and tables per keymap the kernel supports.
+const NR_KEYS: usize = 256;
+const NR_TABLES: usize = 256;
+
+// Key types and values, from linux/keyboard.h
+const KT_LATIN: u8 = 0;
+const KT_SPEC: u8 = 2;
+const KT_PAD: u8 = 3;
+const KT_SHIFT: u8 = 7;
+const KT_LETTER: u8 = 11;
+const K_HOLE: u16 = 0x0200;
+const K_NOSUCHMAP: u16 = 0x027f;
+const K_ENTER: u8 = 1;
+const K_CAPS: u8 = 7;
+const K_NUM: u8 = 8;
+
+/// The characters typed by the keypad keys, indexed by their `KT_PAD` value.
+const PAD_CHARS: &[u8] = b"0123456789+-*/\r,.?()#";
+/// Keypad keys that only type while Num Lock is on: the digits, comma and period.
+const PAD_NUM_LOCKED: u8 = 10;
+const PAD_COMMA: u8 = 15;
+const PAD_PERIOD: u8 = 16;
+
+/// Names of the printable ASCII characters, starting at space.
+#[rustfmt::skip]
+const ASCII_NAMES: [&str; 95] = [
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "apostrophe",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "minus", "period", "slash", "zero",
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "colon", "semicolon",
+    "less", "equal", "greater", "question", "at", "A", "B", "C", "D", "E", "F", "G", "H", "I", "J",
+    "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z", "bracketleft",
+    "backslash", "bracketright", "asciicircum", "underscore", "grave", "a", "b", "c", "d", "e", "f",
+    "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u", "v", "w", "x", "y",
+    "z", "braceleft", "bar", "braceright", "asciitilde",
+];
+
+/// Names of the Latin-1 characters, starting at no-break space.
+#[rustfmt::skip]
+const LATIN1_NAMES: [&str; 96] = [
+    "nobreakspace", "exclamdown", "cent", "sterling", "currency", "yen", "brokenbar", "section",
+    "diaeresis", "copyright", "ordfeminine", "guillemotleft", "notsign", "hyphen", "registered",
+    "macron", "degree", "plusminus", "twosuperior", "threesuperior", "acute", "mu", "paragraph",
+    "periodcentered", "cedilla", "onesuperior", "masculine", "guillemotright", "onequarter",
+    "onehalf", "threequarters", "questiondown", "Agrave", "Aacute", "Acircumflex", "Atilde",
+    "Adiaeresis", "Aring", "AE", "Ccedilla", "Egrave", "Eacute", "Ecircumflex", "Ediaeresis",
+    "Igrave", "Iacute", "Icircumflex", "Idiaeresis", "ETH", "Ntilde", "Ograve", "Oacute",
+    "Ocircumflex", "Otilde", "Odiaeresis", "multiply", "Ooblique", "Ugrave", "Uacute",
+    "Ucircumflex", "Udiaeresis", "Yacute", "THORN", "ssharp", "agrave", "aacute", "acircumflex",
+    "atilde", "adiaeresis", "aring", "ae", "ccedilla", "egrave", "eacute", "ecircumflex",
+    "ediaeresis", "igrave", "iacute", "icircumflex", "idiaeresis", "eth", "ntilde", "ograve",
+    "oacute", "ocircumflex", "otilde", "odiaeresis", "division", "oslash", "ugrave", "uacute",
+    "ucircumflex", "udiaeresis", "yacute", "thorn", "ydiaeresis",
+];
+
+/// Modifier keysym names, in the order of their bits.
+const MODIFIER_NAMES: [&str; 8] = [
+    "Shift", "AltGr", "Control", "Alt", "ShiftL", "ShiftR", "CtrlL", "CtrlR",
+];
+
+/// Modifier prefixes of `keycode` lines, in the order of their bits.
+const MODIFIER_PREFIXES: [&str; 8] = [
+    "shift", "altgr", "control", "alt", "shiftl", "shiftr", "ctrll", "ctrlr",
+];
+
+/// Keypad keysym names, in the order of their `KT_PAD` values.
+#[rustfmt::skip]
+const PAD_NAMES: [&str; 18] = [
+    "KP_0", "KP_1", "KP_2", "KP_3", "KP_4", "KP_5", "KP_6", "KP_7", "KP_8", "KP_9", "KP_Add",
+    "KP_Subtract", "KP_Multiply", "KP_Divide", "KP_Enter", "KP_Comma", "KP_Period", "KP_MinPlus",
+];
+
+/// What a key does in one table of the keymap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyAction {
+    /// Types a character.
+    Char(char),
+    /// Types a letter. Caps Lock switches letters to the shifted table.
+    Letter(char),
+    /// The Enter key, which types a carriage return.
+    Enter,
+    /// A keypad key, by its kernel index: the digits, then `+ - * /`, Enter, comma and period.
+    Pad(u8),
+    /// Holds a modifier, given by its bit number in [`modifiers`].
+    Modifier(u8),
+    CapsLock,
+    NumLock,
+    /// Any other kernel keysym, such as a function key or a console switch.
+    Other(u16),
+}
+
+impl KeyAction {
+    /// Interpret a keysym as returned by `KDGKBENT`; `None` for holes.
+    fn from_kernel(value: u16) -> Option<Self> {
+        let [ty, val] = value.to_be_bytes();
+        Some(match ty {
+            _ if value == K_HOLE || value == K_NOSUCHMAP => return None,
+            KT_LATIN => KeyAction::Char(char::from(val)),
+            KT_LETTER => KeyAction::Letter(char::from(val)),
+            KT_SPEC if val == K_ENTER => KeyAction::Enter,
+            KT_SPEC if val == K_CAPS => KeyAction::CapsLock,
+            KT_SPEC if val == K_NUM => KeyAction::NumLock,
+            KT_PAD => KeyAction::Pad(val),
+            KT_SHIFT if val < 8 => KeyAction::Modifier(val),
+            _ => KeyAction::Other(value),
+        })
+    }
+
+    /// Interpret a keysym name from a keymap file; `None` for `VoidSymbol` and symbols that
+    /// don't affect typing.
+    fn parse(sym: &str) -> Result<Option<Self>, ()> {
+        let (letter, sym) = match sym.strip_prefix('+') {
+            Some(sym) => (true, sym),
+            None => (false, sym),
+        };
+        let action = if let Some(hex) = sym.strip_prefix("U+") {
+            let c = u32::from_str_radix(hex, 16).map_err(drop)?;
+            KeyAction::Char(char::from_u32(c).ok_or(())?)
+        } else if let Some(hex) = sym.strip_prefix("0x") {
+            match KeyAction::from_kernel(u16::from_str_radix(hex, 16).map_err(drop)?) {
+                Some(action) => action,
+                None => return Ok(None),
+            }
+        } else if let Some(pos) = ASCII_NAMES.iter().position(|&n| n == sym) {
+            KeyAction::Char(char::from(b' ' + pos as u8))
+        } else if let Some(pos) = LATIN1_NAMES.iter().position(|&n| n == sym) {
+            KeyAction::Char(char::from(0xa0 + pos as u8))
+        } else if let Some(pos) = MODIFIER_NAMES.iter().position(|&n| n == sym) {
+            KeyAction::Modifier(pos as u8)
+        } else if let Some(pos) = PAD_NAMES.iter().position(|&n| n == sym) {
+            KeyAction::Pad(pos as u8)
+        } else if let Some(c) = sym.strip_prefix("Control_").and_then(control_char) {
+            KeyAction::Char(c)
+        } else {
+            match sym {
+                "Return" => KeyAction::Enter,
+                "Caps_Lock" => KeyAction::CapsLock,
+                "Num_Lock" => KeyAction::NumLock,
+                "Tab" => KeyAction::Char('\t'),
+                "BackSpace" => KeyAction::Char('\x08'),
+                "Linefeed" => KeyAction::Char('\n'),
+                "Escape" => KeyAction::Char('\x1b'),
+                "Delete" => KeyAction::Char('\x7f'),
+                "nul" => KeyAction::Char('\0'),
+                _ => {
+                    let mut chars = sym.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => KeyAction::Char(c),
+                        _ => return Ok(None),
+                    }
+                }
+            }
+        };
+        Ok(Some(match action {
+            KeyAction::Char(c) if letter || c.is_ascii_alphabetic() => KeyAction::Letter(c),
+            action => action,
+        }))
+    }
+}
+
+/// Returns the control character for `Control_<name>`, e.g. `Control_c` is ETX.
+fn control_char(name: &str) -> Option<char> {
+    let c = match name {
+        "at" => b'@',
+        "bracketleft" => b'[',
+        "backslash" => b'\\',
+        "bracketright" => b']',
+        "asciicircum" => b'^',
+        "underscore" => b'_',
+        _ if name.len() == 1 && name.as_bytes()[0].is_ascii_lowercase() => name.as_bytes()[0],
+        _ => return None,
+    };
+    Some(char::from(c & 0x1f))
+}
+
+/// A console keymap: for every combination of modifiers, what each key does.
+#[derive(Clone)]
+pub struct ConsoleKeymap {
+    tables: Vec<Option<Box<[Option<KeyAction>]>>>,
+}
+
+impl ConsoleKeymap {
+    fn empty() -> Self {
+        ConsoleKeymap {
+            tables: vec![None; NR_TABLES],
+        }
+    }
+
+    /// Read the keymap of the kernel console, through the calling process's terminal if it is
+    /// a virtual console, and through `/dev/tty0` or `/dev/console` otherwise.
+    pub fn from_console() -> io::Result<Self> {
+        let mut last_err = None;
+        for path in ["/dev/tty", "/dev/tty0", "/dev/console"] {
+            let file = OpenOptions::new()
+                .read(true)
+                .open(path)
+                .or_else(|_| OpenOptions::new().write(true).open(path));
+            match file.and_then(|file| Self::from_tty(&file)) {
+                Ok(keymap) => return Ok(keymap),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap())
+    }
+
+    /// Read the keymap through an open virtual console.
+    pub fn from_tty(tty: &File) -> io::Result<Self> {
+        let mut keymap = Self::empty();
+        for table in 0..NR_TABLES {
+            let get = |index: usize| -> io::Result<u16> {
+                let mut entry = sys::kbentry {
+                    kb_table: table as u8,
+                    kb_index: index as u8,
+                    kb_value: 0,
+                };
+                unsafe { sys::kdgkbent(tty.as_raw_fd(), &mut entry) }
+                    .map_err(ioctl_error("KDGKBENT"))?;
+                Ok(entry.kb_value)
+            };
+            let first = get(0)?;
+            if first == K_NOSUCHMAP {
+                continue;
+            }
+            let mut keys = vec![None; NR_KEYS].into_boxed_slice();
+            keys[0] = KeyAction::from_kernel(first);
+            for (index, key) in keys.iter_mut().enumerate().skip(1) {
+                *key = KeyAction::from_kernel(get(index)?);
+            }
+            keymap.tables[table] = Some(keys);
+        }
+        Ok(keymap)
+    }
+
+    /// Parse a keymap in the format of `loadkeys`, e.g. the contents of
+    /// `/usr/share/kbd/keymaps/i386/qwertz/de.map` (decompressed).
+    ///
+    /// `include` directives are ignored, so the files a keymap includes, such as the common
+    /// definitions of modifier keys, must be parsed and [merged](Self::merge) separately.
+    /// Keysyms that don't affect typing are skipped.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut keymap = Self::empty();
+        // The tables filled by the columns of a plain `keycode` line
+        let mut columns: Vec<u8> = (0..=255).collect();
+        let mut lines = text.lines().enumerate();
+        while let Some((lineno, line)) = lines.next() {
+            let mut line = line.to_owned();
+            while line.ends_with('\\') {
+                line.pop();
+                match lines.next() {
+                    Some((_, next)) => line.push_str(next),
+                    None => break,
+                }
+            }
+            let line = match line.find(['#', '!']) {
+                Some(pos) => &line[..pos],
+                None => &line,
+            };
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid keymap line {}: {}", lineno + 1, line.trim()),
+                )
+            };
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.first().copied() {
+                None => {}
+                Some("keymaps") => {
+                    columns = parse_ranges(words.get(1).ok_or_else(invalid)?).ok_or_else(invalid)?
+                }
+                Some("include" | "string" | "compose" | "charset" | "alt_is_meta" | "strings") => {}
+                Some(_) => keymap.parse_keycode(&words, &columns).ok_or_else(invalid)?,
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Parse a `[modifiers] keycode <n> = <keysyms>` line.
+    fn parse_keycode(&mut self, words: &[&str], columns: &[u8]) -> Option<()> {
+        let kw = words.iter().position(|&w| w == "keycode")?;
+        let mut mask = None;
+        for word in &words[..kw] {
+            let bit = match *word {
+                "plain" => 0,
+                // Tables beyond the first 256 aren't supported
+                "capsshift" => return Some(()),
+                word => 1 << MODIFIER_PREFIXES.iter().position(|&p| p == word)?,
+            };
+            mask = Some(mask.unwrap_or(0) | bit);
+        }
+        let code: usize = parse_number(words.get(kw + 1)?)?;
+        if words.get(kw + 2) != Some(&"=") || code >= NR_KEYS {
+            return None;
+        }
+        let syms = &words[kw + 3..];
+        let actions = syms
+            .iter()
+            .map(|sym| KeyAction::parse(sym))
+            .collect::<Result<Vec<_>, ()>>()
+            .ok()?;
+
+        match (mask, actions.as_slice()) {
+            (Some(_), []) => return None,
+            (Some(mask), [action, ..]) => self.set(mask, code, *action),
+            // A lone letter gets its case and control variants in every table, and anything
+            // else the same action in every table
+            (None, [Some(KeyAction::Letter(c))]) => {
+                for &table in columns {
+                    let action = if table & modifiers::ALT != 0 {
+                        None
+                    } else if table & modifiers::CONTROL != 0 {
+                        control_char(&c.to_ascii_lowercase().to_string()).map(KeyAction::Char)
+                    } else if table & (modifiers::SHIFT | modifiers::SHIFT_L | modifiers::SHIFT_R)
+                        != 0
+                    {
+                        Some(KeyAction::Letter(c.to_ascii_uppercase()))
+                    } else {
+                        Some(KeyAction::Letter(*c))
+                    };
+                    self.set(table, code, action);
+                }
+            }
+            (None, [action]) => {
+                for &table in columns {
+                    self.set(table, code, *action);
+                }
+            }
+            (None, actions) => {
+                if actions.len() > columns.len() {
+                    return None;
+                }
+                for (&table, action) in columns.iter().zip(actions) {
+                    self.set(table, code, *action);
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn set(&mut self, table: u8, code: usize, action: Option<KeyAction>) {
+        let keys = self.tables[usize::from(table)]
+            .get_or_insert_with(|| vec![None; NR_KEYS].into_boxed_slice());
+        keys[code] = action;
+    }
+
+    /// Add the definitions of `other`, overriding those already present.
+    pub fn merge(&mut self, other: &ConsoleKeymap) {
+        for (table, keys) in other.tables.iter().enumerate() {
+            for (code, action) in keys.iter().flat_map(|keys| keys.iter().enumerate()) {
+                if action.is_some() {
+                    self.set(table as u8, code, *action);
+                }
+            }
+        }
+    }
+
+    /// Returns what `key` does while exactly the given [`modifiers`] are held.
+    pub fn lookup(&self, key: Key, modifiers: u8) -> Option<KeyAction> {
+        let keys = self.tables[usize::from(modifiers)].as_ref()?;
+        keys.get(usize::from(key.code())).copied().flatten()
+    }
+}
+
+impl fmt::Debug for ConsoleKeymap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tables: Vec<usize> = (0..NR_TABLES)
+            .filter(|&t| self.tables[t].is_some())
+            .collect();
+        f.debug_struct("ConsoleKeymap")
+            .field("tables", &tables)
+            .finish()
+    }
+}
+
+fn parse_number(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Parse the argument of a `keymaps` line, such as `0-2,4-6,8`.
+fn parse_ranges(s: &str) -> Option<Vec<u8>> {
+    let mut tables = Vec::new();
+    for range in s.split(',') {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        tables.extend(start.parse::<u8>().ok()?..=end.parse::<u8>().ok()?);
+    }
+    Some(tables)
+}
+
+/// Translates key events into characters according to a [`ConsoleKeymap`], tracking
+/// modifiers and the Caps Lock and Num Lock states.
+#[derive(Debug, Clone)]
+pub struct ConsoleTranslator {
+    keymap: ConsoleKeymap,
+    /// Keys currently holding modifiers, with the modifier's bit number.
+    held: Vec<(Key, u8)>,
+    caps_lock: bool,
+    num_lock: bool,
+}
+
+impl ConsoleTranslator {
+    /// Create a translator with no modifiers held and both locks off.
+    pub fn new(keymap: ConsoleKeymap) -> Self {
+        ConsoleTranslator {
+            keymap,
+            held: Vec::new(),
+            caps_lock: false,
+            num_lock: false,
+        }
+    }
+
+    pub fn keymap(&self) -> &ConsoleKeymap {
+        &self.keymap
+    }
+
+    /// The [`modifiers`] currently held.
+    pub fn modifiers(&self) -> u8 {
+        self.held.iter().fold(0, |mask, &(_, bit)| mask | 1 << bit)
+    }
+
+    pub fn caps_lock(&self) -> bool {
+        self.caps_lock
+    }
+
+    /// Set the Caps Lock state, e.g. to match the keyboard's LED when starting up.
+    pub fn set_caps_lock(&mut self, on: bool) {
+        self.caps_lock = on;
+    }
+
+    pub fn num_lock(&self) -> bool {
+        self.num_lock
+    }
+
+    /// Set the Num Lock state, e.g. to match the keyboard's LED when starting up.
+    pub fn set_num_lock(&mut self, on: bool) {
+        self.num_lock = on;
+    }
+
+    /// Update the state with a key event, returning the character typed by presses and
+    /// repeats, if any.
+    ///
+    /// Events other than key events are ignored.
+    pub fn process(&mut self, ev: &InputEvent) -> Option<char> {
+        if ev.event_type() != EventType::KEY {
+            return None;
+        }
+        let key = Key::new(ev.code());
+        if ev.value() == 0 {
+            self.held.retain(|&(k, _)| k != key);
+            return None;
+        }
+        let pressed = ev.value() == 1;
+        let mods = self.modifiers();
+        let action = match self.keymap.lookup(key, mods)? {
+            KeyAction::Letter(_) if self.caps_lock => {
+                self.keymap.lookup(key, mods ^ modifiers::SHIFT)?
+            }
+            action => action,
+        };
+        match action {
+            KeyAction::Char(c) | KeyAction::Letter(c) => Some(c),
+            KeyAction::Enter => Some('\r'),
+            KeyAction::Pad(n) => {
+                let needs_lock = n < PAD_NUM_LOCKED || n == PAD_COMMA || n == PAD_PERIOD;
+                if needs_lock && !self.num_lock {
+                    return None;
+                }
+                PAD_CHARS.get(usize::from(n)).map(|&c| char::from(c))
+            }
+            KeyAction::Modifier(bit) if pressed => {
+                self.held.push((key, bit));
+                None
+            }
+            KeyAction::CapsLock if pressed => {
+                self.caps_lock = !self.caps_lock;
+                None
+            }
+            KeyAction::NumLock if pressed => {
+                self.num_lock = !self.num_lock;
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_translate() {
+        let keymap = ConsoleKeymap::parse(
+            "keymaps 0-2,4 # plain, shift, altgr, control\n\
+             keycode 42 = Shift\n\
+             keycode 100 = AltGr\n\
+             keycode 58 = Caps_Lock\n\
+             keycode 2 = one exclam\n\
+             keycode 16 = +q\n\
+             keycode 18 = e\n\
+             \taltgr keycode 18 = U+20AC\n\
+             keycode 28 = Return\n",
+        )
+        .unwrap();
+        let mut xlat = ConsoleTranslator::new(keymap);
+        let mut typed = String::new();
+        let mut key = |code: Key, value: i32| {
+            let ev = InputEvent::new(EventType::KEY, code.code(), value);
+            typed.extend(xlat.process(&ev));
+        };
+        for k in [Key::KEY_Q, Key::KEY_1, Key::KEY_CAPSLOCK, Key::KEY_E] {
+            key(k, 1);
+            key(k, 0);
+        }
+        key(Key::KEY_LEFTSHIFT, 1);
+        for k in [Key::KEY_E, Key::KEY_1, Key::KEY_ENTER] {
+            key(k, 1);
+            key(k, 0);
+        }
+        key(Key::KEY_LEFTSHIFT, 0);
+        key(Key::KEY_RIGHTALT, 1);
+        key(Key::KEY_E, 1);
+        assert_eq!(typed, "q1Ee!\r€");
+    }
+}
@@ -0,0 +1,342 @@
+//! Media, volume, brightness and other hotkeys, by what they mean.
+//!
+//! Keyboards, remotes, headsets and laptop firmware report the same intent with different
+//! keys: a play button may be `KEY_PLAYPAUSE`, `KEY_PLAY` or `KEY_PLAYCD`, and the browser key
+//! `KEY_WWW` or `KEY_HOMEPAGE`. [`ConsumerControl`] groups them, and [`ConsumerControls`]
+//! calls subscribers for the controls they care about, wherever the key comes from.
+//!
+//! ```no_run
+//! use evdev::consumer::{ConsumerAction, ConsumerControl, ConsumerControls};
+//!
+//! let mut devices = evdev::consumer::find_devices();
+//! let mut controls = ConsumerControls::new();
+//! controls.subscribe([ConsumerControl::VolumeUp, ConsumerControl::VolumeDown], |ev| {
+//!     if ev.action != ConsumerAction::Released {
+//!         println!("{:?}", ev.control);
+//!     }
+//! });
+//! controls.run(&mut devices)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fmt;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use crate::{Device, EventType, InputEvent, Key};
+
+/// A hotkey, by meaning rather than key code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum ConsumerControl {
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    MicMute,
+    PlayPause,
+    Play,
+    Pause,
+    Stop,
+    Next,
+    Previous,
+    FastForward,
+    Rewind,
+    Record,
+    Eject,
+    BrightnessUp,
+    BrightnessDown,
+    /// Turn the display off or on.
+    DisplayToggle,
+    /// Cycle through external display configurations.
+    DisplaySwitch,
+    KeyboardBacklightUp,
+    KeyboardBacklightDown,
+    KeyboardBacklightToggle,
+    /// Open the media player.
+    MediaPlayer,
+    Browser,
+    Mail,
+    Calculator,
+    Search,
+    FileManager,
+    Screenshot,
+    Sleep,
+    /// Toggle all radios, or just wireless networking.
+    Wireless,
+    Bluetooth,
+    TouchpadToggle,
+}
+
+/// Every control with the keys reporting it, the preferred key first.
+const CONTROL_KEYS: &[(ConsumerControl, &[Key])] = &[
+    (ConsumerControl::VolumeUp, &[Key::KEY_VOLUMEUP]),
+    (ConsumerControl::VolumeDown, &[Key::KEY_VOLUMEDOWN]),
+    (ConsumerControl::Mute, &[Key::KEY_MUTE]),
+    (ConsumerControl::MicMute, &[Key::KEY_MICMUTE]),
+    (ConsumerControl::PlayPause, &[Key::KEY_PLAYPAUSE]),
+    (ConsumerControl::Play, &[Key::KEY_PLAY, Key::KEY_PLAYCD]),
+    (ConsumerControl::Pause, &[Key::KEY_PAUSECD]),
+    (ConsumerControl::Stop, &[Key::KEY_STOPCD]),
+    (ConsumerControl::Next, &[Key::KEY_NEXTSONG]),
+    (ConsumerControl::Previous, &[Key::KEY_PREVIOUSSONG]),
+    (ConsumerControl::FastForward, &[Key::KEY_FASTFORWARD]),
+    (ConsumerControl::Rewind, &[Key::KEY_REWIND]),
+    (ConsumerControl::Record, &[Key::KEY_RECORD]),
+    (
+        ConsumerControl::Eject,
+        &[Key::KEY_EJECTCD, Key::KEY_EJECTCLOSECD],
+    ),
+    (ConsumerControl::BrightnessUp, &[Key::KEY_BRIGHTNESSUP]),
+    (ConsumerControl::BrightnessDown, &[Key::KEY_BRIGHTNESSDOWN]),
+    (ConsumerControl::DisplayToggle, &[Key::KEY_DISPLAYTOGGLE]),
+    (ConsumerControl::DisplaySwitch, &[Key::KEY_SWITCHVIDEOMODE]),
+    (ConsumerControl::KeyboardBacklightUp, &[Key::KEY_KBDILLUMUP]),
+    (
+        ConsumerControl::KeyboardBacklightDown,
+        &[Key::KEY_KBDILLUMDOWN],
+    ),
+    (
+        ConsumerControl::KeyboardBacklightToggle,
+        &[Key::KEY_KBDILLUMTOGGLE],
+    ),
+    (ConsumerControl::MediaPlayer, &[Key::KEY_MEDIA]),
+    (ConsumerControl::Browser, &[Key::KEY_WWW, Key::KEY_HOMEPAGE]),
+    (ConsumerControl::Mail, &[Key::KEY_MAIL]),
+    (ConsumerControl::Calculator, &[Key::KEY_CALC]),
+    (ConsumerControl::Search, &[Key::KEY_SEARCH]),
+    (
+        ConsumerControl::FileManager,
+        &[Key::KEY_FILE, Key::KEY_COMPUTER],
+    ),
+    (
+        ConsumerControl::Screenshot,
+        &[Key::KEY_SELECTIVE_SCREENSHOT],
+    ),
+    (ConsumerControl::Sleep, &[Key::KEY_SLEEP]),
+    (ConsumerControl::Wireless, &[Key::KEY_RFKILL, Key::KEY_WLAN]),
+    (ConsumerControl::Bluetooth, &[Key::KEY_BLUETOOTH]),
+    (ConsumerControl::TouchpadToggle, &[Key::KEY_TOUCHPAD_TOGGLE]),
+];
+
+impl ConsumerControl {
+    /// Returns the control `key` reports, if any.
+    ///
+    /// Keys that also have a plain meaning on keyboards, such as `KEY_PRINT` and
+    /// `KEY_PAUSE`, aren't treated as controls.
+    pub fn from_key(key: Key) -> Option<Self> {
+        CONTROL_KEYS
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|&(control, _)| control)
+    }
+
+    /// Returns the keys reporting this control, the preferred one, e.g. for emitting it on a
+    /// virtual device, first.
+    pub fn keys(self) -> &'static [Key] {
+        CONTROL_KEYS
+            .iter()
+            .find(|&&(control, _)| control == self)
+            .map_or(&[], |&(_, keys)| keys)
+    }
+
+    /// Returns every control.
+    pub fn all() -> impl Iterator<Item = ConsumerControl> {
+        CONTROL_KEYS.iter().map(|&(control, _)| control)
+    }
+}
+
+/// What happened to a control.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConsumerAction {
+    Pressed,
+    /// The key is held and auto-repeating, e.g. to keep turning the volume up.
+    Repeated,
+    Released,
+}
+
+/// A press, repeat or release of a control.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ConsumerEvent {
+    pub control: ConsumerControl,
+    pub action: ConsumerAction,
+    /// The key that was reported.
+    pub key: Key,
+}
+
+impl ConsumerEvent {
+    /// Returns the consumer event `ev` represents, if it is a key event of a control.
+    pub fn from_event(ev: &InputEvent) -> Option<Self> {
+        if ev.event_type() != EventType::KEY {
+            return None;
+        }
+        let key = Key::new(ev.code());
+        let action = match ev.value() {
+            0 => ConsumerAction::Released,
+            1 => ConsumerAction::Pressed,
+            _ => ConsumerAction::Repeated,
+        };
+        Some(ConsumerEvent {
+            control: ConsumerControl::from_key(key)?,
+            action,
+            key,
+        })
+    }
+}
+
+/// Returns `true` if `device` has any of the keys of [`ConsumerControl`].
+pub fn has_consumer_controls(device: &Device) -> bool {
+    device.supported_keys().is_some_and(|keys| {
+        CONTROL_KEYS
+            .iter()
+            .flat_map(|(_, k)| k.iter())
+            .any(|&key| keys.contains(key))
+    })
+}
+
+/// Open every device in `/dev/input` with consumer controls.
+pub fn find_devices() -> Vec<Device> {
+    crate::enumerate()
+        .map(|(_, device)| device)
+        .filter(has_consumer_controls)
+        .collect()
+}
+
+/// Identifies a subscription, for [`ConsumerControls::unsubscribe`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Subscription(u64);
+
+type Subscriber = Box<dyn FnMut(ConsumerEvent) + Send>;
+
+/// Delivers consumer control events to subscribers. See the [module documentation](self).
+#[derive(Default)]
+pub struct ConsumerControls {
+    subscribers: Vec<(Subscription, Option<Vec<ConsumerControl>>, Subscriber)>,
+    next_id: u64,
+}
+
+impl fmt::Debug for ConsumerControls {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConsumerControls")
+            .field("subscribers", &self.subscribers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ConsumerControls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(&mut self, controls: Option<Vec<ConsumerControl>>, f: Subscriber) -> Subscription {
+        let id = Subscription(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push((id, controls, f));
+        id
+    }
+
+    /// Call `f` with the events of `controls`.
+    pub fn subscribe(
+        &mut self,
+        controls: impl IntoIterator<Item = ConsumerControl>,
+        f: impl FnMut(ConsumerEvent) + Send + 'static,
+    ) -> Subscription {
+        self.add(Some(controls.into_iter().collect()), Box::new(f))
+    }
+
+    /// Call `f` with the events of every control.
+    pub fn subscribe_all(&mut self, f: impl FnMut(ConsumerEvent) + Send + 'static) -> Subscription {
+        self.add(None, Box::new(f))
+    }
+
+    /// Stop calling a subscriber. Returns `false` if it was already removed.
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> bool {
+        let len = self.subscribers.len();
+        self.subscribers.retain(|(id, ..)| *id != subscription);
+        self.subscribers.len() != len
+    }
+
+    /// Deliver `ev` to the subscribers of its control, if it is one. Returns the event
+    /// delivered.
+    pub fn process_event(&mut self, ev: &InputEvent) -> Option<ConsumerEvent> {
+        let consumer = ConsumerEvent::from_event(ev)?;
+        for (_, controls, f) in &mut self.subscribers {
+            if controls
+                .as_ref()
+                .is_none_or(|controls| controls.contains(&consumer.control))
+            {
+                f(consumer);
+            }
+        }
+        Some(consumer)
+    }
+
+    /// Deliver the events of `devices` until an error occurs.
+    pub fn run(&mut self, devices: &mut [Device]) -> io::Result<()> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        loop {
+            let mut fds: Vec<_> = devices
+                .iter()
+                .map(|device| PollFd::new(device.as_raw_fd(), PollFlags::POLLIN))
+                .collect();
+            poll(&mut fds, -1)?;
+            for (fd, device) in fds.iter().zip(devices.iter_mut()) {
+                if fd.revents().is_some_and(|r| !r.is_empty()) {
+                    for ev in device.fetch_events()? {
+                        self.process_event(&ev);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn subscribe_to_controls() {
+        let volume = Arc::new(Mutex::new(Vec::new()));
+        let all = Arc::new(Mutex::new(0));
+        let mut controls = ConsumerControls::new();
+        let seen = volume.clone();
+        controls.subscribe(
+            [ConsumerControl::VolumeUp, ConsumerControl::VolumeDown],
+            move |ev| seen.lock().unwrap().push((ev.control, ev.action)),
+        );
+        let count = all.clone();
+        let subscription = controls.subscribe_all(move |_| *count.lock().unwrap() += 1);
+
+        for (key, value) in [
+            (Key::KEY_VOLUMEUP, 1),
+            (Key::KEY_VOLUMEUP, 2),
+            (Key::KEY_VOLUMEUP, 0),
+            (Key::KEY_A, 1),
+            (Key::KEY_PLAYCD, 1),
+        ] {
+            controls.process_event(&InputEvent::new(EventType::KEY, key.code(), value));
+        }
+        assert!(controls.unsubscribe(subscription));
+        controls.process_event(&InputEvent::new(
+            EventType::KEY,
+            Key::KEY_HOMEPAGE.code(),
+            1,
+        ));
+
+        assert_eq!(
+            *volume.lock().unwrap(),
+            [
+                (ConsumerControl::VolumeUp, ConsumerAction::Pressed),
+                (ConsumerControl::VolumeUp, ConsumerAction::Repeated),
+                (ConsumerControl::VolumeUp, ConsumerAction::Released),
+            ]
+        );
+        assert_eq!(*all.lock().unwrap(), 4);
+        assert_eq!(
+            ConsumerControl::from_key(Key::KEY_HOMEPAGE),
+            Some(ConsumerControl::Browser)
+        );
+        assert!(ConsumerControl::all().all(|c| ConsumerControl::from_key(c.keys()[0]) == Some(c)));
+    }
+}
@@ -21,10 +21,10 @@ impl Clone for DeviceState {
     fn clone(&self) -> Self {
         Self {
             timestamp: self.timestamp,
-            key_vals: self.key_vals.clone(),
+            key_vals: self.key_vals,
             abs_vals: self.abs_vals.clone(),
-            switch_vals: self.switch_vals.clone(),
-            led_vals: self.led_vals.clone(),
+            switch_vals: self.switch_vals,
+            led_vals: self.led_vals,
         }
     }
     fn clone_from(&mut self, other: &Self) {
@@ -0,0 +1,261 @@
+//! Clicking by holding the pointer still.
+//!
+//! Users who can move a pointer, e.g. with a head tracker, but can't press a button can click
+//! by letting the pointer rest. [`DwellClick`] watches the pointer motion of any number of
+//! devices and clicks on a virtual mouse once the pointer has been still for the dwell time.
+//! Small movements within the jitter tolerance don't count as motion, and the pointer has to
+//! move again before the next click, so resting doesn't click repeatedly.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use evdev::dwell::{CancelGesture, DwellClick};
+//! use evdev::Device;
+//! use std::time::Duration;
+//!
+//! let mut tracker = [Device::open("/dev/input/event5")?];
+//! let mut dwell = DwellClick::new(Duration::from_millis(800))?
+//!     .jitter(4)
+//!     .cancel(CancelGesture::Flick { distance: 60 });
+//! dwell.run(&mut tracker)
+//! # }
+//! ```
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use crate::{AttributeSet, Device, EventType, InputEvent, Key, RelativeAxisType};
+
+/// A way to call off a pending click.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CancelGesture {
+    /// Pressing the key cancels the click. The key press is still delivered as usual.
+    Key(Key),
+    /// Moving at least `distance` units within a single frame cancels the click, and the
+    /// pointer doesn't click where it comes to rest after the flick.
+    Flick { distance: i32 },
+}
+
+/// Clicks when the pointer rests. See the [module documentation](self).
+pub struct DwellClick {
+    device: VirtualDevice,
+    dwell: Duration,
+    jitter: i32,
+    button: Key,
+    cancel: Option<CancelGesture>,
+    /// Motion since the pointer came to rest, which is ignored within the jitter tolerance.
+    drift: (i32, i32),
+    /// Motion in the frame being read.
+    frame: (i32, i32),
+    /// When the pointer last moved, while a click is pending.
+    pending: Option<SystemTime>,
+    clock: SharedClock,
+}
+
+impl DwellClick {
+    /// Create a virtual mouse that clicks after the pointer has been still for `dwell`.
+    pub fn new(dwell: Duration) -> io::Result<Self> {
+        let keys: AttributeSet<Key> = [Key::BTN_LEFT, Key::BTN_RIGHT, Key::BTN_MIDDLE]
+            .into_iter()
+            .collect();
+        let axes: AttributeSet<RelativeAxisType> =
+            [RelativeAxisType::REL_X, RelativeAxisType::REL_Y]
+                .into_iter()
+                .collect();
+        let device = VirtualDeviceBuilder::new()?
+            .name("evdev dwell click")
+            .with_keys(&keys)?
+            .with_relative_axes(&axes)?
+            .build()?;
+        Ok(Self::with_device(device, dwell))
+    }
+
+    /// Click on an existing device, which must support the button.
+    pub fn with_device(device: VirtualDevice, dwell: Duration) -> Self {
+        DwellClick {
+            device,
+            dwell,
+            jitter: 0,
+            button: Key::BTN_LEFT,
+            cancel: None,
+            drift: (0, 0),
+            frame: (0, 0),
+            pending: None,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Ignore motion of up to `distance` units in each direction from where the pointer came to
+    /// rest.
+    pub fn jitter(mut self, distance: i32) -> Self {
+        self.jitter = distance.max(0);
+        self
+    }
+
+    /// Click `button` instead of the left button.
+    pub fn button(mut self, button: Key) -> Self {
+        self.button = button;
+        self
+    }
+
+    /// Let `gesture` cancel a pending click.
+    pub fn cancel(mut self, gesture: CancelGesture) -> Self {
+        self.cancel = Some(gesture);
+        self
+    }
+
+    /// Take the time of events and in [`run`](Self::run) from `clock` rather than the system
+    /// clock.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    pub fn device(&self) -> &VirtualDevice {
+        &self.device
+    }
+
+    /// Returns `true` if the pointer moved and will click once it rests.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Returns the time of the pending click, if the pointer stays still until then.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        self.pending.map(|moved| moved + self.dwell)
+    }
+
+    /// Call off the pending click, until the pointer moves again.
+    pub fn cancel_pending(&mut self) {
+        self.pending = None;
+        self.drift = (0, 0);
+    }
+
+    /// Take an event from one of the watched devices into account.
+    pub fn observe(&mut self, ev: &InputEvent) {
+        match ev.event_type() {
+            EventType::RELATIVE => match RelativeAxisType(ev.code()) {
+                RelativeAxisType::REL_X => self.frame.0 += ev.value(),
+                RelativeAxisType::REL_Y => self.frame.1 += ev.value(),
+                _ => {}
+            },
+            EventType::KEY if ev.value() == 1 => {
+                let key = Key::new(ev.code());
+                // A mouse button pressed by hand replaces the click
+                let is_button =
+                    (Key::BTN_LEFT.code()..Key::BTN_TRIGGER.code()).contains(&key.code());
+                if is_button || self.cancel == Some(CancelGesture::Key(key)) {
+                    self.cancel_pending();
+                }
+            }
+            EventType::SYNCHRONIZATION => self.end_frame(self.clock.event_time(ev)),
+            _ => {}
+        }
+    }
+
+    fn end_frame(&mut self, time: SystemTime) {
+        let (dx, dy) = std::mem::take(&mut self.frame);
+        if (dx, dy) == (0, 0) {
+            return;
+        }
+        if let Some(CancelGesture::Flick { distance }) = self.cancel {
+            if dx.abs().max(dy.abs()) >= distance {
+                self.cancel_pending();
+                return;
+            }
+        }
+        self.drift = (self.drift.0 + dx, self.drift.1 + dy);
+        if self.drift.0.abs() > self.jitter || self.drift.1.abs() > self.jitter {
+            self.drift = (0, 0);
+            self.pending = Some(time);
+        }
+    }
+
+    /// Click if the pointer has rested for the dwell time as of `now`. Returns `true` if it
+    /// clicked.
+    pub fn tick(&mut self, now: SystemTime) -> io::Result<bool> {
+        match self.next_deadline() {
+            Some(deadline) if deadline <= now => {
+                self.cancel_pending();
+                let code = self.button.code();
+                self.device
+                    .emit(&[InputEvent::new(EventType::KEY, code, 1)])?;
+                self.device
+                    .emit(&[InputEvent::new(EventType::KEY, code, 0)])?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Watch `devices` and click until an error occurs.
+    pub fn run(&mut self, devices: &mut [Device]) -> io::Result<()> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        loop {
+            let timeout = self.next_deadline().map_or(-1, |deadline| {
+                let remaining = self.clock.until(deadline);
+                remaining.as_millis().clamp(1, i32::MAX as u128) as i32
+            });
+            let mut fds: Vec<_> = devices
+                .iter()
+                .map(|device| PollFd::new(device.as_raw_fd(), PollFlags::POLLIN))
+                .collect();
+            poll(&mut fds, timeout)?;
+            for (fd, device) in fds.iter().zip(devices.iter_mut()) {
+                if fd.revents().is_some_and(|r| !r.is_empty()) {
+                    for ev in device.fetch_events()? {
+                        self.observe(&ev);
+                    }
+                }
+            }
+            self.tick(self.clock.now())?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn motion(dwell: &mut DwellClick, dx: i32, ms: u64) {
+        let time = crate::systime_to_timeval(&(SystemTime::UNIX_EPOCH + Duration::from_millis(ms)));
+        for mut ev in [
+            InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_X.0, dx),
+            InputEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+        ] {
+            ev.0.time = time;
+            dwell.observe(&ev);
+        }
+    }
+
+    #[test]
+    fn click_after_rest() -> io::Result<()> {
+        let at = |ms| SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+        let keys: AttributeSet<Key> = [Key::BTN_LEFT].into_iter().collect();
+        let device = VirtualDeviceBuilder::in_memory()
+            .with_keys(&keys)?
+            .build()?;
+        let sink = device.memory_sink().unwrap();
+        let mut dwell = DwellClick::with_device(device, Duration::from_millis(500))
+            .jitter(3)
+            .cancel(CancelGesture::Flick { distance: 50 });
+
+        motion(&mut dwell, 10, 0);
+        // Jitter doesn't restart the dwell
+        motion(&mut dwell, 2, 300);
+        assert!(!dwell.tick(at(400))?);
+        assert!(dwell.tick(at(500))?);
+        assert_eq!(sink.drain().len(), 2);
+        // Resting doesn't click again
+        assert!(!dwell.tick(at(2000))?);
+
+        // A flick cancels the click
+        motion(&mut dwell, 10, 3000);
+        motion(&mut dwell, -80, 3100);
+        assert!(!dwell.tick(at(4000))?);
+        assert!(sink.drain().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,405 @@
+//! Structured errors.
+//!
+//! Fallible functions in this crate return [`io::Result`], so that they compose with the rest
+//! of the I/O ecosystem. Errors raised by the crate itself carry an [`Error`] inside the
+//! `io::Error`; converting an `io::Error` back into an [`Error`] recovers it, and classifies
+//! plain OS errors where possible, so callers can match on the cause:
+//!
+//! ```no_run
+//! use evdev::{Device, Error};
+//!
+//! match Device::open("/dev/input/event0") {
+//!     Ok(device) => println!("{}", device),
+//!     Err(e) => match Error::from(e) {
+//!         Error::DeviceGone => println!("device was unplugged"),
+//!         other => println!("error: {}", other),
+//!     },
+//! }
+//! ```
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+
+use crate::{EventType, Violation};
+
+/// The cause of a failed operation.
+///
+/// An `io::Error` carrying an [`Error`] has no OS error code of its own, even when one caused
+/// it: [`io::Error::raw_os_error`] returns `None` for failed ioctls, a busy grab or a denied
+/// open. Code checking for an errno should use [`Error::raw_os_error_of`], which also looks
+/// inside:
+///
+/// ```no_run
+/// use evdev::{Device, Error};
+///
+/// let mut device = Device::open("/dev/input/event0")?;
+/// if let Err(e) = device.grab() {
+///     assert_eq!(e.raw_os_error(), None);
+///     assert_eq!(Error::raw_os_error_of(&e), Some(libc::EBUSY));
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An I/O error that doesn't fall into any of the other categories.
+    Io(io::Error),
+    /// An ioctl failed.
+    Ioctl { name: &'static str, errno: Errno },
+    /// A device name was too long or otherwise unusable.
+    InvalidName,
+    /// The running kernel doesn't support the requested operation.
+    UnsupportedKernel,
+    /// The device has been disconnected.
+    DeviceGone,
+    /// The device doesn't have the capability the operation needs.
+    NotSupportedByDevice(&'static str),
+    /// An event was emitted on a virtual device that wasn't set up to produce it.
+    UndeclaredEvent { event_type: EventType, code: u16 },
+    /// Events broke the evdev protocol.
+    InvalidStream(Violation),
+    /// The device is grabbed by another client. `holders` lists the other processes that
+    /// have the device open, one of which is likely the grabber; it may be incomplete if their
+    /// `/proc` entries aren't readable.
+    DeviceBusy { holders: Vec<DeviceHolder> },
+    /// Opening a device node failed, for a reason users can likely fix themselves.
+    Open {
+        path: PathBuf,
+        source: io::Error,
+        reason: OpenFailure,
+    },
+    /// Grabbing one device of a [`DeviceGroup`](crate::group::DeviceGroup) failed, so none
+    /// were grabbed. `index` is the device's position in the group.
+    GroupGrab { index: usize, source: Box<Error> },
+}
+
+/// Why opening an input device or `/dev/uinput` failed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OpenFailure {
+    /// The user lacks permission to open the input device.
+    PermissionDenied,
+    /// The input device doesn't exist.
+    DeviceMissing,
+    /// The user lacks permission to open uinput.
+    UinputPermissionDenied,
+    /// The uinput kernel module isn't loaded.
+    UinputNotLoaded,
+    /// uinput is loaded, but its device node is missing, e.g. because it wasn't passed
+    /// through to a container.
+    UinputPathMissing,
+}
+
+/// A process that has an input device open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceHolder {
+    pub pid: u32,
+    /// The process's command name, as found in `/proc/<pid>/comm`.
+    pub name: String,
+}
+
+impl fmt::Display for DeviceHolder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (pid {})", self.name, self.pid)
+    }
+}
+
+impl OpenFailure {
+    /// Returns a sentence explaining the likely cause to an end user.
+    pub fn hint(self) -> &'static str {
+        match self {
+            OpenFailure::PermissionDenied => {
+                "reading input devices usually requires being in the `input` group, or a udev \
+                 rule granting access to the device"
+            }
+            OpenFailure::DeviceMissing => {
+                "the device does not exist; it may have been unplugged, or the path is wrong"
+            }
+            OpenFailure::UinputPermissionDenied => {
+                "creating virtual devices requires write access to uinput, usually granted by a \
+                 udev rule or by running as root"
+            }
+            OpenFailure::UinputNotLoaded => {
+                "the uinput kernel module is not loaded; try `modprobe uinput`"
+            }
+            OpenFailure::UinputPathMissing => {
+                "the uinput module is loaded but its device node is missing; in a container, \
+                 pass /dev/uinput through"
+            }
+        }
+    }
+}
+
+impl Error {
+    fn kind(&self) -> io::ErrorKind {
+        match self {
+            Error::Io(e) | Error::Open { source: e, .. } => e.kind(),
+            Error::Ioctl { errno, .. } => io::Error::from(*errno).kind(),
+            Error::InvalidName | Error::UndeclaredEvent { .. } => io::ErrorKind::InvalidInput,
+            Error::InvalidStream(_) => io::ErrorKind::InvalidData,
+            Error::UnsupportedKernel | Error::NotSupportedByDevice(_) => io::ErrorKind::Unsupported,
+            Error::DeviceGone => io::ErrorKind::NotFound,
+            Error::DeviceBusy { .. } => io::ErrorKind::ResourceBusy,
+            Error::GroupGrab { source, .. } => source.kind(),
+        }
+    }
+
+    /// Returns the OS error number behind this error, if there is one.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            Error::Io(e) | Error::Open { source: e, .. } => e.raw_os_error(),
+            Error::Ioctl { errno, .. } => Some(*errno as i32),
+            Error::DeviceGone => Some(libc::ENODEV),
+            Error::DeviceBusy { .. } => Some(libc::EBUSY),
+            Error::GroupGrab { source, .. } => source.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    /// Returns the OS error number behind `err`, whether it's a plain OS error or carries an
+    /// [`Error`].
+    pub fn raw_os_error_of(err: &io::Error) -> Option<i32> {
+        match err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<Error>())
+        {
+            Some(inner) => inner.raw_os_error(),
+            None => err.raw_os_error(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => e.fmt(f),
+            Error::Ioctl { name, errno } => write!(f, "ioctl {} failed: {}", name, errno.desc()),
+            Error::InvalidName => f.write_str("invalid device name"),
+            Error::UnsupportedKernel => f.write_str("operation not supported by this kernel"),
+            Error::DeviceGone => f.write_str("device has been disconnected"),
+            Error::NotSupportedByDevice(what) => write!(f, "device does not support {}", what),
+            Error::UndeclaredEvent { event_type, code } => write!(
+                f,
+                "event {:?} code {} was not enabled on the virtual device",
+                event_type, code
+            ),
+            Error::DeviceBusy { holders } => {
+                f.write_str("device is grabbed by another client")?;
+                for (i, holder) in holders.iter().enumerate() {
+                    f.write_str(if i == 0 { "; open in " } else { ", " })?;
+                    holder.fmt(f)?;
+                }
+                Ok(())
+            }
+            Error::InvalidStream(violation) => write!(f, "invalid event stream: {}", violation),
+            Error::Open {
+                path,
+                source,
+                reason,
+            } => write!(
+                f,
+                "failed to open {}: {} ({})",
+                path.display(),
+                source,
+                reason.hint()
+            ),
+            Error::GroupGrab { index, source } => {
+                write!(
+                    f,
+                    "failed to grab device {} of the group: {}",
+                    index, source
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) | Error::Open { source: e, .. } => Some(e),
+            Error::Ioctl { errno, .. } => Some(errno),
+            Error::InvalidStream(violation) => Some(violation),
+            Error::GroupGrab { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            return *err.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
+        match err.raw_os_error() {
+            Some(libc::ENODEV) => Error::DeviceGone,
+            _ => Error::Io(err),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(e) => e,
+            err => io::Error::new(err.kind(), err),
+        }
+    }
+}
+
+/// Returns a closure converting a failed ioctl's error into an `io::Error` naming the ioctl.
+pub(crate) fn ioctl_error(name: &'static str) -> impl FnOnce(Errno) -> io::Error {
+    move |errno| {
+        trace_event!(debug, ioctl = name, %errno, "ioctl failed");
+        Error::Ioctl { name, errno }.into()
+    }
+}
+
+/// Find other processes that have the character device `rdev` open, by scanning `/proc`.
+pub(crate) fn device_holders(rdev: u64) -> Vec<DeviceHolder> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let own_pid = std::process::id();
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut holders = Vec::new();
+    for entry in procs.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        if pid == own_pid {
+            continue;
+        }
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let has_open = fds.flatten().any(|fd| {
+            std::fs::metadata(fd.path())
+                .is_ok_and(|m| m.file_type().is_char_device() && m.rdev() == rdev)
+        });
+        if has_open {
+            let name = std::fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+            holders.push(DeviceHolder {
+                pid,
+                name: name.trim_end().to_owned(),
+            });
+        }
+    }
+    holders
+}
+
+/// Attach the likely cause to an error from opening an input device.
+pub(crate) fn open_error(path: &Path, err: io::Error) -> io::Error {
+    let reason = match err.raw_os_error() {
+        Some(libc::EACCES | libc::EPERM) => OpenFailure::PermissionDenied,
+        Some(libc::ENOENT) => OpenFailure::DeviceMissing,
+        _ => return err,
+    };
+    Error::Open {
+        path: path.to_owned(),
+        source: err,
+        reason,
+    }
+    .into()
+}
+
+/// Attach the likely cause to an error from opening uinput.
+pub(crate) fn uinput_open_error(path: &Path, err: io::Error) -> io::Error {
+    // The misc device is registered whenever the module is loaded or built in.
+    let loaded = Path::new("/sys/class/misc/uinput").exists();
+    uinput_open_error_with(path, err, loaded)
+}
+
+fn uinput_open_error_with(path: &Path, err: io::Error, loaded: bool) -> io::Error {
+    let reason = match err.raw_os_error() {
+        Some(libc::EACCES | libc::EPERM) => OpenFailure::UinputPermissionDenied,
+        Some(libc::ENOENT) if loaded => OpenFailure::UinputPathMissing,
+        Some(libc::ENOENT | libc::ENODEV) => OpenFailure::UinputNotLoaded,
+        _ => return err,
+    };
+    Error::Open {
+        path: path.to_owned(),
+        source: err,
+        reason,
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_through_io_error() {
+        let err: io::Error = Error::NotSupportedByDevice("force feedback").into();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(matches!(
+            Error::from(err),
+            Error::NotSupportedByDevice("force feedback")
+        ));
+
+        let err: io::Error = ioctl_error("EVIOCGBIT")(Errno::ENOTTY);
+        // The errno is only found by looking inside
+        assert_eq!(err.raw_os_error(), None);
+        assert_eq!(Error::raw_os_error_of(&err), Some(libc::ENOTTY));
+        assert!(matches!(
+            Error::from(err),
+            Error::Ioctl {
+                name: "EVIOCGBIT",
+                errno: Errno::ENOTTY
+            }
+        ));
+
+        let err = io::Error::from_raw_os_error(libc::ENODEV);
+        assert!(matches!(Error::from(err), Error::DeviceGone));
+
+        let err = uinput_open_error(
+            Path::new("/dev/uinput"),
+            io::Error::from_raw_os_error(libc::EACCES),
+        );
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(Error::raw_os_error_of(&err), Some(libc::EACCES));
+        assert!(matches!(
+            Error::from(err),
+            Error::Open {
+                reason: OpenFailure::UinputPermissionDenied,
+                ..
+            }
+        ));
+    }
+
+    fn open_failure(err: io::Error) -> Option<OpenFailure> {
+        match Error::from(err) {
+            Error::Open { reason, .. } => Some(reason),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn open_failures() {
+        let os = io::Error::from_raw_os_error;
+        let path = Path::new("/dev/input/event3");
+        let err = open_error(path, os(libc::ENOENT));
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(Error::raw_os_error_of(&err), Some(libc::ENOENT));
+        assert!(err.to_string().contains("/dev/input/event3"));
+        assert_eq!(open_failure(err), Some(OpenFailure::DeviceMissing));
+        let err = open_error(path, os(libc::EPERM));
+        assert_eq!(open_failure(err), Some(OpenFailure::PermissionDenied));
+        // Nothing to explain
+        let err = open_error(path, os(libc::EIO));
+        assert_eq!(err.raw_os_error(), Some(libc::EIO));
+
+        let uinput = Path::new("/dev/uinput");
+        let missing =
+            |loaded| open_failure(uinput_open_error_with(uinput, os(libc::ENOENT), loaded));
+        assert_eq!(missing(true), Some(OpenFailure::UinputPathMissing));
+        assert_eq!(missing(false), Some(OpenFailure::UinputNotLoaded));
+        let err = uinput_open_error_with(uinput, os(libc::ENODEV), true);
+        assert_eq!(open_failure(err), Some(OpenFailure::UinputNotLoaded));
+    }
+}
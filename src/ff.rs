@@ -0,0 +1,1155 @@
+//! Force feedback effects.
+//!
+//! Force feedback devices (rumble gamepads, wheels, joysticks) keep a small number of effects
+//! uploaded in the kernel. An [`FFEffect`] describes an effect; uploading it to a device with
+//! [`Device::upload_ff_effect`](crate::Device::upload_ff_effect) returns an [`FFEffectHandle`]
+//! that owns the kernel's slot for as long as it is alive. Applications juggling many effects
+//! can let an [`FFEffectPool`] keep track of them instead.
+
+use std::fs::File;
+use std::io::{self, Write};
+#[cfg(not(test))]
+use std::os::unix::io::AsRawFd;
+use std::sync::{Arc, Weak};
+
+use crate::{EventType, FFEffectType, InputEvent};
+
+/// The playback status of an uploaded effect, as reported by an `EV_FF_STATUS` event.
+///
+/// Not all drivers report effect status.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EffectStatus {
+    pub id: i16,
+    pub playing: bool,
+}
+
+/// The scheduling of an effect: how long it plays and how long to wait before starting.
+/// Both are in milliseconds.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FFReplay {
+    pub length: u16,
+    pub delay: u16,
+}
+
+/// A button that triggers an effect, and the minimum time between two triggers in milliseconds.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FFTrigger {
+    pub button: u16,
+    pub interval: u16,
+}
+
+/// The attack and fade of an effect. Lengths are in milliseconds.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FFEnvelope {
+    pub attack_length: u16,
+    pub attack_level: u16,
+    pub fade_length: u16,
+    pub fade_level: u16,
+}
+
+/// A rumble effect, driving a heavy (strong) and a light (weak) motor.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FFRumble {
+    pub strong_magnitude: u16,
+    pub weak_magnitude: u16,
+}
+
+/// The shape of a periodic effect.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FFWaveform {
+    Square,
+    Triangle,
+    Sine,
+    SawUp,
+    SawDown,
+}
+
+impl FFWaveform {
+    /// Returns the effect type the kernel uses for this waveform.
+    pub fn effect_type(self) -> FFEffectType {
+        match self {
+            FFWaveform::Square => FFEffectType::FF_SQUARE,
+            FFWaveform::Triangle => FFEffectType::FF_TRIANGLE,
+            FFWaveform::Sine => FFEffectType::FF_SINE,
+            FFWaveform::SawUp => FFEffectType::FF_SAW_UP,
+            FFWaveform::SawDown => FFEffectType::FF_SAW_DOWN,
+        }
+    }
+}
+
+/// A periodic effect, e.g. a sine wave. The period and phase are in milliseconds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FFPeriodic {
+    pub waveform: FFWaveform,
+    pub period: u16,
+    pub magnitude: i16,
+    pub offset: i16,
+    pub phase: u16,
+    pub envelope: FFEnvelope,
+}
+
+/// A constant force.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FFConstant {
+    pub level: i16,
+    pub envelope: FFEnvelope,
+}
+
+/// A force changing linearly from `start_level` to `end_level`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FFRamp {
+    pub start_level: i16,
+    pub end_level: i16,
+    pub envelope: FFEnvelope,
+}
+
+/// A condition effect along one axis, where the force depends on the position of the device.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FFCondition {
+    pub right_saturation: u16,
+    pub left_saturation: u16,
+    pub right_coefficient: i16,
+    pub left_coefficient: i16,
+    pub deadband: u16,
+    pub center: i16,
+}
+
+/// The kind of an effect, with its type-specific parameters.
+///
+/// Condition effects take one [`FFCondition`] per axis, X first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FFEffectKind {
+    Rumble(FFRumble),
+    Periodic(FFPeriodic),
+    Constant(FFConstant),
+    Ramp(FFRamp),
+    Spring([FFCondition; 2]),
+    Friction([FFCondition; 2]),
+    Damper([FFCondition; 2]),
+    Inertia([FFCondition; 2]),
+}
+
+impl FFEffectKind {
+    /// Returns the effect type the kernel uses for this kind of effect.
+    pub fn effect_type(&self) -> FFEffectType {
+        match self {
+            FFEffectKind::Rumble(_) => FFEffectType::FF_RUMBLE,
+            FFEffectKind::Periodic(_) => FFEffectType::FF_PERIODIC,
+            FFEffectKind::Constant(_) => FFEffectType::FF_CONSTANT,
+            FFEffectKind::Ramp(_) => FFEffectType::FF_RAMP,
+            FFEffectKind::Spring(_) => FFEffectType::FF_SPRING,
+            FFEffectKind::Friction(_) => FFEffectType::FF_FRICTION,
+            FFEffectKind::Damper(_) => FFEffectType::FF_DAMPER,
+            FFEffectKind::Inertia(_) => FFEffectType::FF_INERTIA,
+        }
+    }
+}
+
+/// A force feedback effect that can be uploaded to a device.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FFEffect {
+    pub kind: FFEffectKind,
+    /// The direction of the effect, where 0x4000 is left, 0x8000 is down and 0xc000 is right.
+    pub direction: u16,
+    pub trigger: FFTrigger,
+    pub replay: FFReplay,
+}
+
+impl FFEffect {
+    /// Create an effect of the given kind, playing for `length` milliseconds.
+    pub fn new(kind: FFEffectKind, length: u16) -> Self {
+        FFEffect {
+            kind,
+            direction: 0,
+            trigger: FFTrigger::default(),
+            replay: FFReplay { length, delay: 0 },
+        }
+    }
+
+    /// Convert the effect into the kernel's `ff_effect` layout, with the given effect id.
+    ///
+    /// Use an id of -1 to have the kernel allocate a new slot on upload.
+    pub fn to_raw(self, id: i16) -> libc::ff_effect {
+        let mut raw = libc::ff_effect {
+            type_: self.kind.effect_type().0,
+            id,
+            direction: self.direction,
+            trigger: libc::ff_trigger {
+                button: self.trigger.button,
+                interval: self.trigger.interval,
+            },
+            replay: libc::ff_replay {
+                length: self.replay.length,
+                delay: self.replay.delay,
+            },
+            u: Default::default(),
+        };
+        let u = raw.u.as_mut_ptr() as *mut u8;
+        // SAFETY: `u` is the kernel's union of effect parameters; it is large and aligned
+        // enough for each of the types written here.
+        unsafe {
+            match self.kind {
+                FFEffectKind::Rumble(r) => {
+                    *(u as *mut libc::ff_rumble_effect) = libc::ff_rumble_effect {
+                        strong_magnitude: r.strong_magnitude,
+                        weak_magnitude: r.weak_magnitude,
+                    }
+                }
+                FFEffectKind::Periodic(p) => {
+                    *(u as *mut libc::ff_periodic_effect) = libc::ff_periodic_effect {
+                        waveform: p.waveform.effect_type().0,
+                        period: p.period,
+                        magnitude: p.magnitude,
+                        offset: p.offset,
+                        phase: p.phase,
+                        envelope: p.envelope.into(),
+                        custom_len: 0,
+                        custom_data: std::ptr::null_mut(),
+                    }
+                }
+                FFEffectKind::Constant(c) => {
+                    *(u as *mut libc::ff_constant_effect) = libc::ff_constant_effect {
+                        level: c.level,
+                        envelope: c.envelope.into(),
+                    }
+                }
+                FFEffectKind::Ramp(r) => {
+                    *(u as *mut libc::ff_ramp_effect) = libc::ff_ramp_effect {
+                        start_level: r.start_level,
+                        end_level: r.end_level,
+                        envelope: r.envelope.into(),
+                    }
+                }
+                FFEffectKind::Spring(c)
+                | FFEffectKind::Friction(c)
+                | FFEffectKind::Damper(c)
+                | FFEffectKind::Inertia(c) => {
+                    *(u as *mut [libc::ff_condition_effect; 2]) = [c[0].into(), c[1].into()]
+                }
+            }
+        }
+        raw
+    }
+
+    /// Convert an effect from the kernel's `ff_effect` layout.
+    ///
+    /// Returns `None` for effect types this crate doesn't model, such as custom waveforms.
+    pub fn from_raw(raw: &libc::ff_effect) -> Option<Self> {
+        let u = raw.u.as_ptr() as *const u8;
+        // SAFETY: the union is interpreted according to its type tag, and every bit pattern is
+        // valid for the plain integer structs read here.
+        let kind = unsafe {
+            match FFEffectType(raw.type_) {
+                FFEffectType::FF_RUMBLE => {
+                    let r = &*(u as *const libc::ff_rumble_effect);
+                    FFEffectKind::Rumble(FFRumble {
+                        strong_magnitude: r.strong_magnitude,
+                        weak_magnitude: r.weak_magnitude,
+                    })
+                }
+                FFEffectType::FF_PERIODIC => {
+                    let p = &*(u as *const libc::ff_periodic_effect);
+                    let waveform = match FFEffectType(p.waveform) {
+                        FFEffectType::FF_SQUARE => FFWaveform::Square,
+                        FFEffectType::FF_TRIANGLE => FFWaveform::Triangle,
+                        FFEffectType::FF_SINE => FFWaveform::Sine,
+                        FFEffectType::FF_SAW_UP => FFWaveform::SawUp,
+                        FFEffectType::FF_SAW_DOWN => FFWaveform::SawDown,
+                        _ => return None,
+                    };
+                    FFEffectKind::Periodic(FFPeriodic {
+                        waveform,
+                        period: p.period,
+                        magnitude: p.magnitude,
+                        offset: p.offset,
+                        phase: p.phase,
+                        envelope: p.envelope.into(),
+                    })
+                }
+                FFEffectType::FF_CONSTANT => {
+                    let c = &*(u as *const libc::ff_constant_effect);
+                    FFEffectKind::Constant(FFConstant {
+                        level: c.level,
+                        envelope: c.envelope.into(),
+                    })
+                }
+                FFEffectType::FF_RAMP => {
+                    let r = &*(u as *const libc::ff_ramp_effect);
+                    FFEffectKind::Ramp(FFRamp {
+                        start_level: r.start_level,
+                        end_level: r.end_level,
+                        envelope: r.envelope.into(),
+                    })
+                }
+                ty => {
+                    let c = &*(u as *const [libc::ff_condition_effect; 2]);
+                    let c = [c[0].into(), c[1].into()];
+                    match ty {
+                        FFEffectType::FF_SPRING => FFEffectKind::Spring(c),
+                        FFEffectType::FF_FRICTION => FFEffectKind::Friction(c),
+                        FFEffectType::FF_DAMPER => FFEffectKind::Damper(c),
+                        FFEffectType::FF_INERTIA => FFEffectKind::Inertia(c),
+                        _ => return None,
+                    }
+                }
+            }
+        };
+        Some(FFEffect {
+            kind,
+            direction: raw.direction,
+            trigger: FFTrigger {
+                button: raw.trigger.button,
+                interval: raw.trigger.interval,
+            },
+            replay: FFReplay {
+                length: raw.replay.length,
+                delay: raw.replay.delay,
+            },
+        })
+    }
+}
+
+/// The largest duration, in milliseconds, the kernel's force feedback API supports.
+pub const FF_MAX_DURATION: u16 = 0x7fff;
+
+/// The largest envelope level the kernel's force feedback API supports.
+pub const FF_MAX_ENVELOPE_LEVEL: u16 = 0x7fff;
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn check_duration(value: u16, msg: &'static str) -> io::Result<()> {
+    if value > FF_MAX_DURATION {
+        return Err(invalid(msg));
+    }
+    Ok(())
+}
+
+impl FFEnvelope {
+    /// Check that the envelope's lengths and levels are within kernel limits.
+    pub fn validate(&self) -> io::Result<()> {
+        check_duration(self.attack_length, "envelope attack length out of range")?;
+        check_duration(self.fade_length, "envelope fade length out of range")?;
+        if self.attack_level > FF_MAX_ENVELOPE_LEVEL || self.fade_level > FF_MAX_ENVELOPE_LEVEL {
+            return Err(invalid("envelope level out of range"));
+        }
+        Ok(())
+    }
+}
+
+impl FFEffect {
+    /// Check that the effect's durations, levels and envelope are within kernel limits.
+    ///
+    /// The kernel accepts out-of-range values, but their behavior is unspecified and differs
+    /// between drivers.
+    pub fn validate(&self) -> io::Result<()> {
+        check_duration(self.replay.length, "replay length out of range")?;
+        check_duration(self.replay.delay, "replay delay out of range")?;
+        check_duration(self.trigger.interval, "trigger interval out of range")?;
+        match &self.kind {
+            FFEffectKind::Rumble(_) => {}
+            FFEffectKind::Periodic(p) => {
+                if p.period == 0 {
+                    return Err(invalid("periodic effect period must be nonzero"));
+                }
+                check_duration(p.period, "periodic effect period out of range")?;
+                check_duration(p.phase, "periodic effect phase out of range")?;
+                p.envelope.validate()?;
+            }
+            FFEffectKind::Constant(c) => c.envelope.validate()?,
+            FFEffectKind::Ramp(r) => r.envelope.validate()?,
+            // Condition parameters span their whole integer range.
+            FFEffectKind::Spring(_)
+            | FFEffectKind::Friction(_)
+            | FFEffectKind::Damper(_)
+            | FFEffectKind::Inertia(_) => {}
+        }
+        Ok(())
+    }
+}
+
+macro_rules! ff_builder_common {
+    () => {
+        /// Set the direction of the effect, where 0x4000 is left, 0x8000 is down and 0xc000
+        /// is right.
+        #[inline]
+        pub fn direction(mut self, direction: u16) -> Self {
+            self.direction = direction;
+            self
+        }
+
+        /// Set how long the effect plays, in milliseconds.
+        #[inline]
+        pub fn length(mut self, length: u16) -> Self {
+            self.replay.length = length;
+            self
+        }
+
+        /// Set how long to wait before the effect starts playing, in milliseconds.
+        #[inline]
+        pub fn delay(mut self, delay: u16) -> Self {
+            self.replay.delay = delay;
+            self
+        }
+
+        /// Set the button that triggers the effect and the minimum interval between
+        /// triggers, in milliseconds.
+        #[inline]
+        pub fn trigger(mut self, button: u16, interval: u16) -> Self {
+            self.trigger = FFTrigger { button, interval };
+            self
+        }
+    };
+}
+
+/// A builder for [periodic](FFPeriodic) effects.
+#[derive(Debug, Copy, Clone)]
+pub struct FFPeriodicBuilder {
+    periodic: FFPeriodic,
+    direction: u16,
+    trigger: FFTrigger,
+    replay: FFReplay,
+}
+
+impl FFPeriodicBuilder {
+    /// Start building a periodic effect with the given waveform, period (in milliseconds)
+    /// and magnitude.
+    pub fn new(waveform: FFWaveform, period: u16, magnitude: i16) -> Self {
+        FFPeriodicBuilder {
+            periodic: FFPeriodic {
+                waveform,
+                period,
+                magnitude,
+                offset: 0,
+                phase: 0,
+                envelope: FFEnvelope::default(),
+            },
+            direction: 0,
+            trigger: FFTrigger::default(),
+            replay: FFReplay::default(),
+        }
+    }
+
+    ff_builder_common!();
+
+    /// Set the offset of the wave's center from zero.
+    #[inline]
+    pub fn offset(mut self, offset: i16) -> Self {
+        self.periodic.offset = offset;
+        self
+    }
+
+    /// Set the phase shift of the wave, in milliseconds.
+    #[inline]
+    pub fn phase(mut self, phase: u16) -> Self {
+        self.periodic.phase = phase;
+        self
+    }
+
+    #[inline]
+    pub fn envelope(mut self, envelope: FFEnvelope) -> Self {
+        self.periodic.envelope = envelope;
+        self
+    }
+
+    /// Validate the parameters and build the effect.
+    pub fn build(self) -> io::Result<FFEffect> {
+        let effect = FFEffect {
+            kind: FFEffectKind::Periodic(self.periodic),
+            direction: self.direction,
+            trigger: self.trigger,
+            replay: self.replay,
+        };
+        effect.validate()?;
+        Ok(effect)
+    }
+}
+
+/// A builder for [ramp](FFRamp) effects.
+#[derive(Debug, Copy, Clone)]
+pub struct FFRampBuilder {
+    ramp: FFRamp,
+    direction: u16,
+    trigger: FFTrigger,
+    replay: FFReplay,
+}
+
+impl FFRampBuilder {
+    /// Start building a ramp effect going from `start_level` to `end_level`.
+    pub fn new(start_level: i16, end_level: i16) -> Self {
+        FFRampBuilder {
+            ramp: FFRamp {
+                start_level,
+                end_level,
+                envelope: FFEnvelope::default(),
+            },
+            direction: 0,
+            trigger: FFTrigger::default(),
+            replay: FFReplay::default(),
+        }
+    }
+
+    ff_builder_common!();
+
+    #[inline]
+    pub fn envelope(mut self, envelope: FFEnvelope) -> Self {
+        self.ramp.envelope = envelope;
+        self
+    }
+
+    /// Validate the parameters and build the effect.
+    pub fn build(self) -> io::Result<FFEffect> {
+        let effect = FFEffect {
+            kind: FFEffectKind::Ramp(self.ramp),
+            direction: self.direction,
+            trigger: self.trigger,
+            replay: self.replay,
+        };
+        effect.validate()?;
+        Ok(effect)
+    }
+}
+
+/// A builder for condition effects: springs, friction, dampers and inertia.
+#[derive(Debug, Copy, Clone)]
+pub struct FFConditionBuilder {
+    kind: fn([FFCondition; 2]) -> FFEffectKind,
+    conditions: [FFCondition; 2],
+    direction: u16,
+    trigger: FFTrigger,
+    replay: FFReplay,
+}
+
+impl FFConditionBuilder {
+    fn new(kind: fn([FFCondition; 2]) -> FFEffectKind) -> Self {
+        FFConditionBuilder {
+            kind,
+            conditions: Default::default(),
+            direction: 0,
+            trigger: FFTrigger::default(),
+            replay: FFReplay::default(),
+        }
+    }
+
+    pub fn spring() -> Self {
+        Self::new(FFEffectKind::Spring)
+    }
+
+    pub fn friction() -> Self {
+        Self::new(FFEffectKind::Friction)
+    }
+
+    pub fn damper() -> Self {
+        Self::new(FFEffectKind::Damper)
+    }
+
+    pub fn inertia() -> Self {
+        Self::new(FFEffectKind::Inertia)
+    }
+
+    ff_builder_common!();
+
+    /// Set the condition for the X axis.
+    #[inline]
+    pub fn x(mut self, condition: FFCondition) -> Self {
+        self.conditions[0] = condition;
+        self
+    }
+
+    /// Set the condition for the Y axis.
+    #[inline]
+    pub fn y(mut self, condition: FFCondition) -> Self {
+        self.conditions[1] = condition;
+        self
+    }
+
+    /// Validate the parameters and build the effect.
+    pub fn build(self) -> io::Result<FFEffect> {
+        let effect = FFEffect {
+            kind: (self.kind)(self.conditions),
+            direction: self.direction,
+            trigger: self.trigger,
+            replay: self.replay,
+        };
+        effect.validate()?;
+        Ok(effect)
+    }
+}
+
+impl From<libc::ff_envelope> for FFEnvelope {
+    fn from(e: libc::ff_envelope) -> Self {
+        FFEnvelope {
+            attack_length: e.attack_length,
+            attack_level: e.attack_level,
+            fade_length: e.fade_length,
+            fade_level: e.fade_level,
+        }
+    }
+}
+
+impl From<libc::ff_condition_effect> for FFCondition {
+    fn from(c: libc::ff_condition_effect) -> Self {
+        FFCondition {
+            right_saturation: c.right_saturation,
+            left_saturation: c.left_saturation,
+            right_coefficient: c.right_coeff,
+            left_coefficient: c.left_coeff,
+            deadband: c.deadband,
+            center: c.center,
+        }
+    }
+}
+
+impl From<FFEnvelope> for libc::ff_envelope {
+    fn from(e: FFEnvelope) -> Self {
+        libc::ff_envelope {
+            attack_length: e.attack_length,
+            attack_level: e.attack_level,
+            fade_length: e.fade_length,
+            fade_level: e.fade_level,
+        }
+    }
+}
+
+impl From<FFCondition> for libc::ff_condition_effect {
+    fn from(c: FFCondition) -> Self {
+        libc::ff_condition_effect {
+            right_saturation: c.right_saturation,
+            left_saturation: c.left_saturation,
+            right_coeff: c.right_coefficient,
+            left_coeff: c.left_coefficient,
+            deadband: c.deadband,
+            center: c.center,
+        }
+    }
+}
+
+/// An effect uploaded to a device.
+///
+/// The effect occupies one of the device's effect slots until the handle is dropped, at which
+/// point it is erased from the device.
+///
+/// Handles don't keep the device open. Closing any descriptor of an open device erases every
+/// effect uploaded through it, so a handle only refers to the device's own descriptor: once the
+/// device is dropped, its effects are gone and the handle fails with
+/// [`io::ErrorKind::NotConnected`].
+#[derive(Debug)]
+pub struct FFEffectHandle {
+    file: Weak<File>,
+    id: i16,
+}
+
+impl FFEffectHandle {
+    /// Upload `effect` to the device behind `file`.
+    pub(crate) fn upload(file: &Arc<File>, effect: &FFEffect) -> io::Result<Self> {
+        let id = upload_raw(file, effect, -1)?;
+        Ok(FFEffectHandle {
+            file: Arc::downgrade(file),
+            id,
+        })
+    }
+
+    fn file(&self) -> io::Result<Arc<File>> {
+        self.file
+            .upgrade()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "device was closed"))
+    }
+
+    /// Returns the id the kernel assigned to this effect.
+    pub fn id(&self) -> i16 {
+        self.id
+    }
+
+    /// Start playing the effect `count` times in a row.
+    pub fn play(&mut self, count: i32) -> io::Result<()> {
+        self.write_control(count)
+    }
+
+    /// Stop playing the effect.
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.write_control(0)
+    }
+
+    fn write_control(&mut self, value: i32) -> io::Result<()> {
+        let ev = InputEvent::new(EventType::FORCEFEEDBACK, self.id as u16, value);
+        let bytes = unsafe { crate::cast_to_bytes(&ev) };
+        (&*self.file()?).write_all(bytes)
+    }
+
+    /// Replace the parameters of the uploaded effect, keeping its slot.
+    ///
+    /// If the effect is playing, the kernel updates it in place.
+    pub fn update(&mut self, effect: &FFEffect) -> io::Result<()> {
+        upload_raw(&*self.file()?, effect, self.id)?;
+        Ok(())
+    }
+}
+
+impl Drop for FFEffectHandle {
+    fn drop(&mut self) {
+        // Closing the device already erased the effect
+        if let Some(file) = self.file.upgrade() {
+            let _ = erase_raw(&file, self.id);
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn upload_raw(file: &File, effect: &FFEffect, id: i16) -> io::Result<i16> {
+    let mut raw = effect.to_raw(id);
+    unsafe { crate::sys::eviocsff(file.as_raw_fd(), &mut raw) }
+        .map_err(crate::error::ioctl_error("EVIOCSFF"))?;
+    Ok(raw.id)
+}
+
+#[cfg(not(test))]
+fn erase_raw(file: &File, id: i16) -> io::Result<()> {
+    unsafe { crate::sys::eviocrmff(file.as_raw_fd(), id as nix::sys::ioctl::ioctl_param_type) }
+        .map_err(crate::error::ioctl_error("EVIOCRMFF"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+use fake_slots::{erase as erase_raw, upload as upload_raw};
+
+/// Stands in for the kernel's effect slots in tests, which have no device to upload to.
+/// Like the kernel's, the slots belong to the descriptor the effects were uploaded through.
+#[cfg(test)]
+pub(crate) mod fake_slots {
+    use super::FFEffect;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::{AsRawFd, RawFd};
+
+    /// The number of effects a device holds, as few as on many gamepads.
+    pub(crate) const SLOTS: usize = 2;
+
+    thread_local! {
+        static DEVICES: RefCell<HashMap<RawFd, [Option<FFEffect>; SLOTS]>> =
+            RefCell::default();
+    }
+
+    pub(crate) fn upload(file: &File, effect: &FFEffect, id: i16) -> io::Result<i16> {
+        DEVICES.with_borrow_mut(|devices| {
+            let slots = devices.entry(file.as_raw_fd()).or_default();
+            let id = match id {
+                -1 => slots.iter().position(Option::is_none),
+                id => usize::try_from(id)
+                    .ok()
+                    .filter(|&id| slots.get(id).is_some_and(Option::is_some)),
+            };
+            let id = id.ok_or(io::Error::from_raw_os_error(libc::ENOSPC))?;
+            slots[id] = Some(*effect);
+            Ok(id as i16)
+        })
+    }
+
+    pub(crate) fn erase(file: &File, id: i16) -> io::Result<()> {
+        DEVICES.with_borrow_mut(|devices| {
+            let slot = devices
+                .get_mut(&file.as_raw_fd())
+                .and_then(|slots| slots.get_mut(id as usize))
+                .and_then(Option::take);
+            slot.map(drop)
+                .ok_or(io::Error::from_raw_os_error(libc::EINVAL))
+        })
+    }
+
+    /// Returns the ids of the effects uploaded through `fd`.
+    pub(crate) fn uploaded(fd: &impl AsRawFd) -> Vec<i16> {
+        DEVICES.with_borrow(|devices| match devices.get(&fd.as_raw_fd()) {
+            Some(slots) => (0..SLOTS as i16)
+                .filter(|&id| slots[id as usize].is_some())
+                .collect(),
+            None => Vec::new(),
+        })
+    }
+}
+
+/// A key identifying an effect within an [`FFEffectPool`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FFEffectKey(usize);
+
+#[derive(Debug)]
+struct PoolEntry {
+    effect: FFEffect,
+    handle: Option<FFEffectHandle>,
+}
+
+/// Keeps track of the effects an application has uploaded to a device.
+///
+/// Removed effects keep their kernel slot and are reused for the next insertion, so an
+/// application churning through short-lived effects doesn't run out of slots. All effects are
+/// erased from the device when the pool is dropped.
+///
+/// The pool remembers every effect's parameters. When a device is reconnected, e.g. after a
+/// wireless controller dropped out, [`reupload`](Self::reupload) restores all effects on the new
+/// device without invalidating the keys the application holds.
+///
+/// A pool belongs to the device it first uploads to, since effect slots belong to the open
+/// device they were uploaded through. Inserting with another device fails with
+/// [`io::ErrorKind::InvalidInput`]; [`reupload`](Self::reupload) moves the pool to a new one.
+#[derive(Debug, Default)]
+pub struct FFEffectPool {
+    entries: Vec<Option<PoolEntry>>,
+    spare: Vec<FFEffectHandle>,
+    /// The descriptor of the device the effects were uploaded to.
+    device: Option<Weak<File>>,
+}
+
+impl FFEffectPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tie the pool to the device behind `file`, failing if it already belongs to another one.
+    ///
+    /// Holding on to the descriptor's allocation keeps it from being mistaken for a device
+    /// opened later.
+    fn bind(&mut self, file: &Arc<File>) -> io::Result<()> {
+        match &self.device {
+            Some(bound) if !std::ptr::eq(bound.as_ptr(), Arc::as_ptr(file)) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "effect pool belongs to another device",
+            )),
+            _ => {
+                self.device = Some(Arc::downgrade(file));
+                Ok(())
+            }
+        }
+    }
+
+    /// Upload an effect to `device`, reusing a free slot if one is available.
+    ///
+    /// Fails if the pool belongs to another device.
+    pub fn insert(
+        &mut self,
+        device: &mut crate::Device,
+        effect: FFEffect,
+    ) -> io::Result<FFEffectKey> {
+        self.bind(device.shared_file())?;
+        let spare = self.spare.pop().and_then(|mut handle| {
+            // A slot that can't be reused is erased before uploading to a new one
+            handle.update(&effect).ok().map(|()| handle)
+        });
+        let handle = match spare {
+            Some(handle) => handle,
+            None => device.upload_ff_effect(&effect)?,
+        };
+        let entry = PoolEntry {
+            effect,
+            handle: Some(handle),
+        };
+        let idx = match self.entries.iter().position(Option::is_none) {
+            Some(idx) => {
+                self.entries[idx] = Some(entry);
+                idx
+            }
+            None => {
+                self.entries.push(Some(entry));
+                self.entries.len() - 1
+            }
+        };
+        Ok(FFEffectKey(idx))
+    }
+
+    fn entry(&mut self, key: FFEffectKey) -> io::Result<&mut PoolEntry> {
+        self.entries
+            .get_mut(key.0)
+            .and_then(Option::as_mut)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such effect in pool"))
+    }
+
+    fn handle(&mut self, key: FFEffectKey) -> io::Result<&mut FFEffectHandle> {
+        self.entry(key)?
+            .handle
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "effect is not uploaded"))
+    }
+
+    /// Returns the parameters of an effect in the pool.
+    pub fn get(&self, key: FFEffectKey) -> Option<&FFEffect> {
+        self.entries.get(key.0)?.as_ref().map(|e| &e.effect)
+    }
+
+    /// Returns the kernel id of an effect, if it is currently uploaded.
+    pub fn effect_id(&self, key: FFEffectKey) -> Option<i16> {
+        self.entries
+            .get(key.0)?
+            .as_ref()?
+            .handle
+            .as_ref()
+            .map(FFEffectHandle::id)
+    }
+
+    /// Replace the parameters of an effect.
+    pub fn update(&mut self, key: FFEffectKey, effect: FFEffect) -> io::Result<()> {
+        let entry = self.entry(key)?;
+        entry.effect = effect;
+        match entry.handle.as_mut() {
+            Some(handle) => handle.update(&effect),
+            None => Ok(()),
+        }
+    }
+
+    /// Start playing an effect `count` times.
+    pub fn play(&mut self, key: FFEffectKey, count: i32) -> io::Result<()> {
+        self.handle(key)?.play(count)
+    }
+
+    /// Stop playing an effect.
+    pub fn stop(&mut self, key: FFEffectKey) -> io::Result<()> {
+        self.handle(key)?.stop()
+    }
+
+    /// Remove an effect from the pool. Its slot is kept for reuse by the next insertion.
+    pub fn remove(&mut self, key: FFEffectKey) -> Option<FFEffect> {
+        let entry = self.entries.get_mut(key.0)?.take()?;
+        if let Some(mut handle) = entry.handle {
+            if handle.stop().is_ok() {
+                self.spare.push(handle);
+            }
+        }
+        Some(entry.effect)
+    }
+
+    /// Returns the number of effects in the pool.
+    pub fn len(&self) -> usize {
+        self.entries.iter().filter(|e| e.is_some()).count()
+    }
+
+    /// Returns `true` if there are no effects in the pool.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Upload every effect in the pool to `device`, replacing the previous uploads.
+    ///
+    /// Call this after reopening a device that was disconnected. Keys stay valid, but the kernel
+    /// ids of the effects may change. If an upload fails, the remaining effects are still
+    /// uploaded and the first error is returned.
+    pub fn reupload(&mut self, device: &mut crate::Device) -> io::Result<()> {
+        // Erase every old upload first, in case it's the same device and its slots are needed
+        self.spare.clear();
+        for entry in self.entries.iter_mut().flatten() {
+            entry.handle = None;
+        }
+        self.device = Some(Arc::downgrade(device.shared_file()));
+        let mut result = Ok(());
+        for entry in self.entries.iter_mut().flatten() {
+            match device.upload_ff_effect(&entry.effect) {
+                Ok(handle) => entry.handle = Some(handle),
+                Err(e) => {
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::fcntl::OFlag;
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    #[test]
+    fn raw_roundtrip() {
+        let condition = FFCondition {
+            right_saturation: 1,
+            left_saturation: 2,
+            right_coefficient: -3,
+            left_coefficient: 4,
+            deadband: 5,
+            center: -6,
+        };
+        let kinds = [
+            FFEffectKind::Rumble(FFRumble {
+                strong_magnitude: 0x8000,
+                weak_magnitude: 0x1000,
+            }),
+            FFEffectKind::Periodic(FFPeriodic {
+                waveform: FFWaveform::SawDown,
+                period: 100,
+                magnitude: -200,
+                offset: 3,
+                phase: 4,
+                envelope: FFEnvelope {
+                    attack_length: 1,
+                    attack_level: 2,
+                    fade_length: 3,
+                    fade_level: 4,
+                },
+            }),
+            FFEffectKind::Damper([condition, FFCondition::default()]),
+        ];
+        for kind in kinds {
+            let effect = FFEffect::new(kind, 500);
+            let raw = effect.to_raw(3);
+            assert_eq!(raw.id, 3);
+            assert_eq!(FFEffect::from_raw(&raw), Some(effect));
+        }
+    }
+
+    /// A device writing to a pipe, and the pipe's read end.
+    fn pipe_device() -> (crate::Device, File) {
+        let (read, write) = nix::unistd::pipe2(OFlag::O_NONBLOCK | OFlag::O_CLOEXEC).unwrap();
+        // SAFETY: the pipe was just created and nothing else owns its ends
+        let (read, write) = unsafe { (File::from_raw_fd(read), File::from_raw_fd(write)) };
+        let raw = crate::raw_stream::RawDevice::from_file_unchecked(write);
+        (crate::Device::from_raw_device(raw), read)
+    }
+
+    /// Returns the `(code, value)` of the events written to the device.
+    fn written(pipe: &mut File) -> Vec<(u16, i32)> {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+            .chunks_exact(std::mem::size_of::<libc::input_event>())
+            .map(|chunk| {
+                let ev: libc::input_event =
+                    unsafe { std::ptr::read_unaligned(chunk.as_ptr().cast()) };
+                (ev.code, ev.value)
+            })
+            .collect()
+    }
+
+    fn rumble() -> FFEffect {
+        let kind = FFEffectKind::Rumble(FFRumble {
+            strong_magnitude: 0x8000,
+            weak_magnitude: 0,
+        });
+        FFEffect::new(kind, 100)
+    }
+
+    #[test]
+    fn dropping_a_handle_keeps_other_effects() -> io::Result<()> {
+        let (mut device, mut pipe) = pipe_device();
+        let first = device.upload_ff_effect(&rumble())?;
+        let mut second = device.upload_ff_effect(&rumble())?;
+        drop(first);
+        assert_eq!(fake_slots::uploaded(&device), [second.id()]);
+        second.play(1)?;
+        assert_eq!(written(&mut pipe), [(second.id() as u16, 1)]);
+
+        // The device's effects are gone with it
+        drop(device);
+        assert_eq!(
+            second.play(1).unwrap_err().kind(),
+            io::ErrorKind::NotConnected
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pool_bound_to_device() -> io::Result<()> {
+        let (mut device, _pipe) = pipe_device();
+        let (mut other, _other_pipe) = pipe_device();
+        let mut pool = FFEffectPool::new();
+        pool.insert(&mut device, rumble())?;
+        let err = pool.insert(&mut other, rumble()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // Not even a device opened later, which may get the same descriptor number
+        drop(device);
+        let (mut reopened, _pipe) = pipe_device();
+        assert!(pool.insert(&mut reopened, rumble()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pool_reuses_slots() -> io::Result<()> {
+        let (mut device, mut pipe) = pipe_device();
+        let mut pool = FFEffectPool::new();
+        let first = pool.insert(&mut device, rumble())?;
+        let id = pool.effect_id(first);
+        assert_eq!(pool.remove(first), Some(rumble()));
+        // Removing stops the effect, but keeps it uploaded for reuse
+        assert_eq!(written(&mut pipe), [(id.unwrap() as u16, 0)]);
+        assert_eq!(fake_slots::uploaded(&device).len(), 1);
+
+        let second = pool.insert(&mut device, rumble())?;
+        assert_eq!(pool.effect_id(second), id);
+        let third = pool.insert(&mut device, rumble())?;
+        assert_eq!(fake_slots::uploaded(&device).len(), fake_slots::SLOTS);
+        assert!(pool.insert(&mut device, rumble()).is_err());
+        pool.play(third, 1)?;
+        assert_eq!(
+            written(&mut pipe),
+            [(pool.effect_id(third).unwrap() as u16, 1)]
+        );
+
+        drop(pool);
+        assert_eq!(fake_slots::uploaded(&device), []);
+        Ok(())
+    }
+
+    #[test]
+    fn pool_reupload() -> io::Result<()> {
+        let (mut device, _pipe) = pipe_device();
+        let mut pool = FFEffectPool::new();
+        let keys = [
+            pool.insert(&mut device, rumble())?,
+            pool.insert(&mut device, rumble())?,
+        ];
+        // Every slot is taken, so the old uploads must be erased first
+        pool.reupload(&mut device)?;
+        assert_eq!(fake_slots::uploaded(&device).len(), fake_slots::SLOTS);
+
+        let (mut reopened, mut pipe) = pipe_device();
+        pool.reupload(&mut reopened)?;
+        assert_eq!(fake_slots::uploaded(&device), []);
+        assert_eq!(fake_slots::uploaded(&reopened).len(), fake_slots::SLOTS);
+        pool.play(keys[1], 1)?;
+        assert_eq!(
+            written(&mut pipe),
+            [(pool.effect_id(keys[1]).unwrap() as u16, 1)]
+        );
+        assert!(pool.insert(&mut device, rumble()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn builder_validation() {
+        let effect = FFPeriodicBuilder::new(FFWaveform::Sine, 50, 0x4000)
+            .length(1000)
+            .build()
+            .unwrap();
+        assert_eq!(effect.replay.length, 1000);
+
+        assert!(FFPeriodicBuilder::new(FFWaveform::Sine, 0, 0x4000)
+            .build()
+            .is_err());
+        assert!(FFRampBuilder::new(0, 0x7fff)
+            .length(0x8000)
+            .build()
+            .is_err());
+        assert!(FFRampBuilder::new(0, 0x7fff)
+            .envelope(FFEnvelope {
+                attack_level: 0xffff,
+                ..Default::default()
+            })
+            .build()
+            .is_err());
+
+        let condition = FFCondition {
+            right_saturation: 0x7fff,
+            left_saturation: 0x7fff,
+            deadband: 0x100,
+            ..Default::default()
+        };
+        let effect = FFConditionBuilder::spring()
+            .x(condition)
+            .y(condition)
+            .build()
+            .unwrap();
+        assert_eq!(effect.kind, FFEffectKind::Spring([condition; 2]));
+    }
+}
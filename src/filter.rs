@@ -0,0 +1,343 @@
+//! Filtering and mapping combinators for event iterators and streams.
+//!
+//! Consumers interested in a few events often end up writing the same loop: match on the type
+//! and code, skip the rest. [`EventIteratorExt`] adds combinators for that to any iterator of
+//! events, such as the one returned by [`Device::fetch_events`](crate::Device::fetch_events),
+//! and, with the `tokio` feature, `EventStreamExt` adds the same to streams of events such as
+//! `EventStream`.
+//!
+//! ```no_run
+//! use evdev::filter::EventIteratorExt;
+//! use evdev::{AttributeSet, EventType, Key};
+//!
+//! let mut device = evdev::Device::open("/dev/input/event0")?;
+//! let keys: AttributeSet<Key> = [Key::KEY_VOLUMEUP, Key::KEY_VOLUMEDOWN].into_iter().collect();
+//! loop {
+//!     for ev in device.fetch_events()?.filter_codes(&keys) {
+//!         println!("{:?} {}", Key::new(ev.code()), ev.value());
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::attribute_set::ArrayedEvdevEnum;
+use crate::constants::*;
+use crate::transform::is_syn_report;
+use crate::{AttributeSet, AttributeSetRef, InputEvent, Key};
+
+/// A type of event codes, such as [`Key`], with the event type its codes belong to.
+pub trait EventCode: ArrayedEvdevEnum {
+    const EVENT_TYPE: EventType;
+}
+
+macro_rules! event_codes {
+    ($($t:ty => $ty:expr,)*) => {
+        $(impl EventCode for $t {
+            const EVENT_TYPE: EventType = $ty;
+        })*
+    };
+}
+
+event_codes! {
+    Key => EventType::KEY,
+    RelativeAxisType => EventType::RELATIVE,
+    AbsoluteAxisType => EventType::ABSOLUTE,
+    MiscType => EventType::MISC,
+    SwitchType => EventType::SWITCH,
+    LedType => EventType::LED,
+    SoundType => EventType::SOUND,
+    FFEffectType => EventType::FORCEFEEDBACK,
+}
+
+/// Keeps the events of one type. See [`EventIteratorExt::filter_type`].
+#[derive(Debug, Clone)]
+pub struct FilterType<I> {
+    inner: I,
+    ty: EventType,
+}
+
+impl<I> FilterType<I> {
+    fn keep(&self, ev: &InputEvent) -> bool {
+        ev.event_type() == self.ty
+    }
+}
+
+/// Keeps the events with codes in a set. See [`EventIteratorExt::filter_codes`].
+#[derive(Debug)]
+pub struct FilterCodes<I, T: EventCode> {
+    inner: I,
+    codes: AttributeSet<T>,
+}
+
+impl<I, T: EventCode> FilterCodes<I, T> {
+    fn keep(&self, ev: &InputEvent) -> bool {
+        ev.event_type() == T::EVENT_TYPE && self.codes.contains(T::from_index(ev.code() as usize))
+    }
+}
+
+/// Passes each frame through a function. See [`EventIteratorExt::map_frames`].
+#[derive(Debug, Clone)]
+pub struct MapFrames<I, F> {
+    inner: I,
+    f: F,
+    frame: Vec<InputEvent>,
+    out: Vec<InputEvent>,
+    ready: VecDeque<InputEvent>,
+}
+
+impl<I, F: FnMut(&[InputEvent], &mut Vec<InputEvent>)> MapFrames<I, F> {
+    fn push(&mut self, ev: InputEvent) {
+        self.frame.push(ev);
+        if is_syn_report(&ev) {
+            (self.f)(&self.frame, &mut self.out);
+            self.frame.clear();
+            self.ready.extend(self.out.drain(..));
+        }
+    }
+
+    /// Called once the input ends; passes on what's left of an incomplete frame.
+    fn finish(&mut self) {
+        self.ready.extend(self.frame.drain(..));
+    }
+}
+
+/// Combinators for iterators of events.
+pub trait EventIteratorExt: Iterator<Item = InputEvent> + Sized {
+    /// Keep only events of type `ty`.
+    ///
+    /// Unless `ty` is [`EventType::SYNCHRONIZATION`], the `SYN_REPORT`s ending frames are
+    /// dropped too; filter inside [`map_frames`](Self::map_frames) to keep frames whole.
+    fn filter_type(self, ty: EventType) -> FilterType<Self> {
+        FilterType { inner: self, ty }
+    }
+
+    /// Keep only events with one of `codes`, such as some [`Key`]s.
+    fn filter_codes<T: EventCode>(self, codes: &AttributeSetRef<T>) -> FilterCodes<Self, T> {
+        FilterCodes {
+            inner: self,
+            codes: codes.iter().collect(),
+        }
+    }
+
+    /// Pass each frame, ending with its `SYN_REPORT`, to `f`, which appends the events to
+    /// yield in its place to the `Vec`, like an
+    /// [`EventTransform`](crate::transform::EventTransform).
+    ///
+    /// Events after the last `SYN_REPORT` are yielded as they are when the iterator ends, so
+    /// frames split across two [`fetch_events`](crate::Device::fetch_events) aren't lost, but
+    /// aren't mapped either. Streams don't end between frames, so they don't have this issue.
+    fn map_frames<F>(self, f: F) -> MapFrames<Self, F>
+    where
+        F: FnMut(&[InputEvent], &mut Vec<InputEvent>),
+    {
+        MapFrames {
+            inner: self,
+            f,
+            frame: Vec::new(),
+            out: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = InputEvent>> EventIteratorExt for I {}
+
+impl<I: Iterator<Item = InputEvent>> Iterator for FilterType<I> {
+    type Item = InputEvent;
+
+    fn next(&mut self) -> Option<InputEvent> {
+        loop {
+            let ev = self.inner.next()?;
+            if self.keep(&ev) {
+                return Some(ev);
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = InputEvent>, T: EventCode> Iterator for FilterCodes<I, T> {
+    type Item = InputEvent;
+
+    fn next(&mut self) -> Option<InputEvent> {
+        loop {
+            let ev = self.inner.next()?;
+            if self.keep(&ev) {
+                return Some(ev);
+            }
+        }
+    }
+}
+
+impl<I, F> Iterator for MapFrames<I, F>
+where
+    I: Iterator<Item = InputEvent>,
+    F: FnMut(&[InputEvent], &mut Vec<InputEvent>),
+{
+    type Item = InputEvent;
+
+    fn next(&mut self) -> Option<InputEvent> {
+        loop {
+            if let Some(ev) = self.ready.pop_front() {
+                return Some(ev);
+            }
+            match self.inner.next() {
+                Some(ev) => self.push(ev),
+                None => {
+                    self.finish();
+                    return self.ready.pop_front();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::stream::EventStreamExt;
+
+#[cfg(feature = "tokio")]
+mod stream {
+    use super::*;
+
+    use futures_core::{ready, Stream};
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// Combinators for streams of events, like [`EventIteratorExt`] for iterators.
+    pub trait EventStreamExt: Stream<Item = io::Result<InputEvent>> + Unpin + Sized {
+        /// Keep only events of type `ty`. See [`EventIteratorExt::filter_type`].
+        fn filter_type(self, ty: EventType) -> FilterType<Self> {
+            FilterType { inner: self, ty }
+        }
+
+        /// Keep only events with one of `codes`. See [`EventIteratorExt::filter_codes`].
+        fn filter_codes<T: EventCode>(self, codes: &AttributeSetRef<T>) -> FilterCodes<Self, T> {
+            FilterCodes {
+                inner: self,
+                codes: codes.iter().collect(),
+            }
+        }
+
+        /// Pass each frame to `f`. See [`EventIteratorExt::map_frames`].
+        fn map_frames<F>(self, f: F) -> MapFrames<Self, F>
+        where
+            F: FnMut(&[InputEvent], &mut Vec<InputEvent>) + Unpin,
+        {
+            MapFrames {
+                inner: self,
+                f,
+                frame: Vec::new(),
+                out: Vec::new(),
+                ready: VecDeque::new(),
+            }
+        }
+    }
+
+    impl<S: Stream<Item = io::Result<InputEvent>> + Unpin> EventStreamExt for S {}
+
+    impl<S: Stream<Item = io::Result<InputEvent>> + Unpin> Stream for FilterType<S> {
+        type Item = io::Result<InputEvent>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            loop {
+                match ready!(Pin::new(&mut this.inner).poll_next(cx)) {
+                    Some(Ok(ev)) if !this.keep(&ev) => continue,
+                    item => return Poll::Ready(item),
+                }
+            }
+        }
+    }
+
+    impl<S, T> Stream for FilterCodes<S, T>
+    where
+        S: Stream<Item = io::Result<InputEvent>> + Unpin,
+        T: EventCode,
+        AttributeSet<T>: Unpin,
+    {
+        type Item = io::Result<InputEvent>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            loop {
+                match ready!(Pin::new(&mut this.inner).poll_next(cx)) {
+                    Some(Ok(ev)) if !this.keep(&ev) => continue,
+                    item => return Poll::Ready(item),
+                }
+            }
+        }
+    }
+
+    impl<S, F> Stream for MapFrames<S, F>
+    where
+        S: Stream<Item = io::Result<InputEvent>> + Unpin,
+        F: FnMut(&[InputEvent], &mut Vec<InputEvent>) + Unpin,
+    {
+        type Item = io::Result<InputEvent>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            loop {
+                if let Some(ev) = this.ready.pop_front() {
+                    return Poll::Ready(Some(Ok(ev)));
+                }
+                match ready!(Pin::new(&mut this.inner).poll_next(cx)) {
+                    Some(Ok(ev)) => this.push(ev),
+                    Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                    None => {
+                        this.finish();
+                        return Poll::Ready(this.ready.pop_front().map(Ok));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_and_map() {
+        let key = |key: Key, value| InputEvent::new(EventType::KEY, key.code(), value);
+        let rel = |value| InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_X.0, value);
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        let events = [
+            key(Key::KEY_A, 1),
+            rel(3),
+            syn,
+            key(Key::KEY_B, 1),
+            syn,
+            rel(1),
+        ];
+
+        let codes = |events: &mut dyn Iterator<Item = InputEvent>| -> Vec<(u16, u16)> {
+            events.map(|ev| (ev.event_type().0, ev.code())).collect()
+        };
+        assert_eq!(
+            codes(&mut events.into_iter().filter_type(EventType::RELATIVE)),
+            [(2, 0), (2, 0)]
+        );
+        let keys: AttributeSet<Key> = [Key::KEY_B].into_iter().collect();
+        assert_eq!(
+            codes(&mut events.into_iter().filter_codes(&keys)),
+            [(1, Key::KEY_B.code())]
+        );
+
+        // Drop the motion from every frame; the incomplete frame passes through
+        let no_motion = events.into_iter().map_frames(|frame, out| {
+            out.extend(
+                frame
+                    .iter()
+                    .filter(|ev| ev.event_type() != EventType::RELATIVE),
+            )
+        });
+        assert_eq!(
+            codes(&mut no_motion.into_iter()),
+            [(1, 30), (0, 0), (1, 48), (0, 0), (2, 0)]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{InputEvent, InputEventKind, Key};
+
+/// The `BTN_TOOL_*` keys that report the number of fingers on a touchpad, in order.
+const TOOL_KEYS: [Key; 5] = [
+    Key::BTN_TOOL_FINGER,
+    Key::BTN_TOOL_DOUBLETAP,
+    Key::BTN_TOOL_TRIPLETAP,
+    Key::BTN_TOOL_QUADTAP,
+    Key::BTN_TOOL_QUINTTAP,
+];
+
+/// Tracks how many fingers are on a touchpad from the `BTN_TOUCH` and `BTN_TOOL_*` keys.
+///
+/// Touchpads report the number of fingers by pressing exactly one of `BTN_TOOL_FINGER`,
+/// `BTN_TOOL_DOUBLETAP`, `BTN_TOOL_TRIPLETAP`, `BTN_TOOL_QUADTAP` or `BTN_TOOL_QUINTTAP`, and
+/// whether there is physical contact with `BTN_TOUCH`. This is independent of the number of
+/// multitouch slots, so it also works for devices that track fewer contacts than they detect.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FingerTracker {
+    touching: bool,
+    /// Bit `n` is set while the tool key for `n + 1` fingers is held.
+    tools: u8,
+}
+
+impl FingerTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the state from an event. Returns `true` if the finger count or touch state changed.
+    pub fn process_event(&mut self, ev: &InputEvent) -> bool {
+        let before = *self;
+        if let InputEventKind::Key(key) = ev.kind() {
+            let pressed = ev.value() != 0;
+            if key == Key::BTN_TOUCH {
+                self.touching = pressed;
+            } else if let Some(idx) = TOOL_KEYS.iter().position(|&k| k == key) {
+                if pressed {
+                    self.tools |= 1 << idx;
+                } else {
+                    self.tools &= !(1 << idx);
+                }
+            }
+        }
+        *self != before
+    }
+
+    /// Update the state from every event in a frame. Returns `true` if anything changed.
+    pub fn process_frame(&mut self, frame: &[InputEvent]) -> bool {
+        let before = *self;
+        frame.iter().for_each(|ev| {
+            self.process_event(ev);
+        });
+        *self != before
+    }
+
+    /// Returns the number of fingers the device reports, or 0 if none.
+    ///
+    /// If more than one tool key is held, e.g. while the kernel is switching between them within
+    /// a frame, the highest count wins.
+    pub fn finger_count(&self) -> u8 {
+        8 - self.tools.leading_zeros() as u8
+    }
+
+    /// Returns `true` if the device reports physical contact.
+    pub fn touching(&self) -> bool {
+        self.touching
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventType;
+
+    fn key(key: Key, value: i32) -> InputEvent {
+        InputEvent::new(EventType::KEY, key.code(), value)
+    }
+
+    #[test]
+    fn test_finger_count() {
+        let mut tracker = FingerTracker::new();
+        assert_eq!(tracker.finger_count(), 0);
+        assert!(tracker.process_frame(&[key(Key::BTN_TOUCH, 1), key(Key::BTN_TOOL_FINGER, 1)]));
+        assert!(tracker.touching());
+        assert_eq!(tracker.finger_count(), 1);
+        tracker.process_frame(&[
+            key(Key::BTN_TOOL_FINGER, 0),
+            key(Key::BTN_TOOL_DOUBLETAP, 1),
+        ]);
+        assert_eq!(tracker.finger_count(), 2);
+        assert!(!tracker.process_event(&key(Key::BTN_LEFT, 1)));
+        tracker.process_frame(&[key(Key::BTN_TOOL_DOUBLETAP, 0), key(Key::BTN_TOUCH, 0)]);
+        assert_eq!(tracker.finger_count(), 0);
+        assert!(!tracker.touching());
+    }
+}
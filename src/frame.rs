@@ -0,0 +1,193 @@
+//! Owned frames of events.
+//!
+//! A frame is the group of events the kernel reports together, terminated by `SYN_REPORT`.
+//! [`Frame`] stores one without touching the heap as long as it fits in its inline capacity,
+//! and [`FrameIter`] groups a stream of events into frames.
+
+use std::fmt;
+use std::ops::Deref;
+
+use crate::transform::is_syn_report;
+use crate::InputEvent;
+
+/// The inline capacity of a [`Frame`] unless specified otherwise. Enough for keyboards, mice
+/// and single-touch devices; multitouch panels with many slots may want more.
+pub const DEFAULT_FRAME_CAPACITY: usize = 16;
+
+const EMPTY_EVENT: InputEvent = InputEvent(libc::input_event {
+    time: libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    },
+    type_: 0,
+    code: 0,
+    value: 0,
+});
+
+/// A group of events stored inline for up to `N` events, spilling onto the heap beyond that.
+#[derive(Clone)]
+pub struct Frame<const N: usize = DEFAULT_FRAME_CAPACITY> {
+    inline: [InputEvent; N],
+    len: usize,
+    heap: Option<Vec<InputEvent>>,
+}
+
+impl<const N: usize> Frame<N> {
+    pub const fn new() -> Self {
+        Frame {
+            inline: [EMPTY_EVENT; N],
+            len: 0,
+            heap: None,
+        }
+    }
+
+    /// Append an event, moving the frame to the heap if it is full.
+    pub fn push(&mut self, event: InputEvent) {
+        match &mut self.heap {
+            Some(heap) => heap.push(event),
+            None if self.len < N => {
+                self.inline[self.len] = event;
+                self.len += 1;
+            }
+            None => {
+                let mut heap = Vec::with_capacity(N * 2);
+                heap.extend_from_slice(&self.inline);
+                heap.push(event);
+                self.heap = Some(heap);
+            }
+        }
+    }
+
+    /// Remove all events. A spilled frame keeps its heap allocation for reuse.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        if let Some(heap) = &mut self.heap {
+            heap.clear();
+        }
+    }
+
+    /// Returns true if the frame has outgrown its inline capacity.
+    pub fn spilled(&self) -> bool {
+        self.heap.is_some()
+    }
+
+    /// Returns true if the frame ends with `SYN_REPORT`.
+    pub fn is_complete(&self) -> bool {
+        self.last().is_some_and(is_syn_report)
+    }
+
+    pub fn as_slice(&self) -> &[InputEvent] {
+        match &self.heap {
+            Some(heap) => heap,
+            None => &self.inline[..self.len],
+        }
+    }
+}
+
+impl<const N: usize> Default for Frame<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Deref for Frame<N> {
+    type Target = [InputEvent];
+
+    fn deref(&self) -> &[InputEvent] {
+        self.as_slice()
+    }
+}
+
+impl<const N: usize> fmt::Debug for Frame<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<const N: usize> Extend<InputEvent> for Frame<N> {
+    fn extend<I: IntoIterator<Item = InputEvent>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|ev| self.push(ev))
+    }
+}
+
+impl<const N: usize> From<&[InputEvent]> for Frame<N> {
+    fn from(events: &[InputEvent]) -> Self {
+        let mut frame = Self::new();
+        frame.extend(events.iter().copied());
+        frame
+    }
+}
+
+/// Groups an iterator of events into [`Frame`]s.
+///
+/// Events after the last `SYN_REPORT` are held back until the frame is completed, so the
+/// same `FrameIter` can be fed batches from successive reads with [`feed`](Self::feed).
+pub struct FrameIter<I, const N: usize = DEFAULT_FRAME_CAPACITY> {
+    events: I,
+    current: Frame<N>,
+}
+
+impl<I: Iterator<Item = InputEvent>, const N: usize> FrameIter<I, N> {
+    pub fn new(events: impl IntoIterator<IntoIter = I>) -> Self {
+        FrameIter {
+            events: events.into_iter(),
+            current: Frame::new(),
+        }
+    }
+
+    /// Continue with another batch of events, keeping any incomplete frame.
+    pub fn feed<J: Iterator<Item = InputEvent>>(
+        self,
+        events: impl IntoIterator<IntoIter = J>,
+    ) -> FrameIter<J, N> {
+        FrameIter {
+            events: events.into_iter(),
+            current: self.current,
+        }
+    }
+
+    /// Returns the events received since the last complete frame.
+    pub fn incomplete(&self) -> &Frame<N> {
+        &self.current
+    }
+}
+
+impl<I: Iterator<Item = InputEvent>, const N: usize> Iterator for FrameIter<I, N> {
+    type Item = Frame<N>;
+
+    fn next(&mut self) -> Option<Frame<N>> {
+        for ev in self.events.by_ref() {
+            self.current.push(ev);
+            if is_syn_report(&ev) {
+                return Some(std::mem::take(&mut self.current));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventType, Key};
+
+    #[test]
+    fn spill_and_group() {
+        let key = |v| InputEvent::new(EventType::KEY, Key::KEY_A.code(), v);
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        let events = [key(1), syn, key(0), key(1), key(0), syn, key(1)];
+
+        let mut iter = FrameIter::<_, 2>::new(events);
+        let first = iter.next().unwrap();
+        assert_eq!(first.len(), 2);
+        assert!(!first.spilled() && first.is_complete());
+        let second = iter.next().unwrap();
+        assert_eq!(second.len(), 4);
+        assert!(second.spilled() && second.is_complete());
+        assert!(iter.next().is_none());
+        assert_eq!(iter.incomplete().len(), 1);
+
+        let mut iter = iter.feed([syn]);
+        assert_eq!(iter.next().unwrap().len(), 2);
+    }
+}
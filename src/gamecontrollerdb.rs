@@ -0,0 +1,557 @@
+//! Controller layouts from SDL's game controller database.
+//!
+//! Game controllers disagree wildly about which button code means what. SDL keeps a community
+//! maintained database, `gamecontrollerdb.txt`, that maps each model, identified by a GUID
+//! derived from its bus type, vendor, product and version, to a standard layout: the buttons
+//! and sticks of an Xbox-style gamepad. [`ControllerDb`] loads that file and resolves the
+//! mapping of a [`Device`] into evdev codes.
+//!
+//! ```no_run
+//! use evdev::gamecontrollerdb::{ControllerDb, GamepadButton};
+//!
+//! let db = ControllerDb::load("gamecontrollerdb.txt")?;
+//! let device = evdev::Device::open("/dev/input/event0")?;
+//! if let Some(mapping) = db.mapping_for(&device) {
+//!     println!("{}: A is {:?}", mapping.name, mapping.button(GamepadButton::A));
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{AbsoluteAxisType, AttributeSetRef, Device, InputId, Key};
+
+/// The buttons of the standard gamepad layout, named after their SDL roles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    /// The bottom face button.
+    A,
+    /// The right face button.
+    B,
+    /// The left face button.
+    X,
+    /// The top face button.
+    Y,
+    Back,
+    Guide,
+    Start,
+    LeftStick,
+    RightStick,
+    LeftShoulder,
+    RightShoulder,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    /// An extra button, such as the share or capture button.
+    Misc1,
+    Paddle1,
+    Paddle2,
+    Paddle3,
+    Paddle4,
+    Touchpad,
+}
+
+/// The axes of the standard gamepad layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+const BUTTON_NAMES: [(&str, GamepadButton); 21] = [
+    ("a", GamepadButton::A),
+    ("b", GamepadButton::B),
+    ("x", GamepadButton::X),
+    ("y", GamepadButton::Y),
+    ("back", GamepadButton::Back),
+    ("guide", GamepadButton::Guide),
+    ("start", GamepadButton::Start),
+    ("leftstick", GamepadButton::LeftStick),
+    ("rightstick", GamepadButton::RightStick),
+    ("leftshoulder", GamepadButton::LeftShoulder),
+    ("rightshoulder", GamepadButton::RightShoulder),
+    ("dpup", GamepadButton::DPadUp),
+    ("dpdown", GamepadButton::DPadDown),
+    ("dpleft", GamepadButton::DPadLeft),
+    ("dpright", GamepadButton::DPadRight),
+    ("misc1", GamepadButton::Misc1),
+    ("paddle1", GamepadButton::Paddle1),
+    ("paddle2", GamepadButton::Paddle2),
+    ("paddle3", GamepadButton::Paddle3),
+    ("paddle4", GamepadButton::Paddle4),
+    ("touchpad", GamepadButton::Touchpad),
+];
+
+const AXIS_NAMES: [(&str, GamepadAxis); 6] = [
+    ("leftx", GamepadAxis::LeftX),
+    ("lefty", GamepadAxis::LeftY),
+    ("rightx", GamepadAxis::RightX),
+    ("righty", GamepadAxis::RightY),
+    ("lefttrigger", GamepadAxis::LeftTrigger),
+    ("righttrigger", GamepadAxis::RightTrigger),
+];
+
+/// Which part of an axis' range a binding covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AxisRange {
+    Full,
+    /// From the center to the maximum.
+    Positive,
+    /// From the center to the minimum.
+    Negative,
+}
+
+/// A control of the abstract SDL joystick, as referenced by mappings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoystickInput {
+    /// `b<index>`
+    Button(usize),
+    /// `a<index>`, with an optional `+`/`-` range prefix and `~` inversion suffix.
+    Axis {
+        index: usize,
+        range: AxisRange,
+        inverted: bool,
+    },
+    /// `h<index>.<mask>`: a direction of a hat switch, 1 up, 2 right, 4 down and 8 left.
+    Hat { index: usize, mask: u8 },
+}
+
+/// A gamepad control that a mapping binds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadControl {
+    Button(GamepadButton),
+    /// An axis, or half of one for the `+leftx`-style bindings.
+    Axis(GamepadAxis, AxisRange),
+}
+
+/// An SDL joystick GUID, as used to identify controllers in the database.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    /// Build the GUID SDL uses for an evdev device, with the name CRC left empty, which
+    /// matches entries both with and without one.
+    pub fn new(id: InputId, name: &str) -> Self {
+        let mut guid = [0; 16];
+        guid[..2].copy_from_slice(&id.bus_type().0.to_le_bytes());
+        if id.vendor() == 0 && id.product() == 0 {
+            // Without ids, SDL identifies the device by the start of its name
+            let len = name.len().min(12);
+            guid[4..4 + len].copy_from_slice(&name.as_bytes()[..len]);
+        } else {
+            guid[4..6].copy_from_slice(&id.vendor().to_le_bytes());
+            guid[8..10].copy_from_slice(&id.product().to_le_bytes());
+            guid[12..14].copy_from_slice(&id.version().to_le_bytes());
+        }
+        Guid(guid)
+    }
+
+    fn crc(&self) -> u16 {
+        u16::from_le_bytes([self.0[2], self.0[3]])
+    }
+
+    fn without_crc(mut self) -> Self {
+        self.0[2..4].fill(0);
+        self
+    }
+
+    fn without_version(mut self) -> Self {
+        self.0[12..14].fill(0);
+        self
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Guid({})", self)
+    }
+}
+
+impl FromStr for Guid {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid controller GUID");
+        if s.len() != 32 || !s.is_ascii() {
+            return Err(invalid());
+        }
+        let mut guid = [0; 16];
+        for (i, byte) in guid.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Guid(guid))
+    }
+}
+
+/// One line of the database: the layout of one controller model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    pub guid: Guid,
+    pub name: String,
+    pub bindings: Vec<(GamepadControl, JoystickInput)>,
+}
+
+impl FromStr for Mapping {
+    type Err = io::Error;
+
+    /// Parse a mapping line, such as
+    /// `030000005e0400008e02000014010000,Xbox 360 Controller,a:b0,b:b1,leftx:a0,...`.
+    fn from_str(line: &str) -> io::Result<Self> {
+        let invalid = |what: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid controller mapping {}: {}", what, line),
+            )
+        };
+        let mut fields = line.trim().split(',');
+        let guid = fields.next().ok_or_else(|| invalid("GUID"))?.parse()?;
+        let name = fields.next().ok_or_else(|| invalid("name"))?.to_owned();
+
+        let mut bindings = Vec::new();
+        for field in fields.filter(|f| !f.is_empty()) {
+            let (control, input) = field.split_once(':').ok_or_else(|| invalid(field))?;
+            if control == "platform" || control == "crc" || control == "hint" {
+                continue;
+            }
+            let (range, control) = split_range(control);
+            let control = if let Some(&(_, b)) = BUTTON_NAMES.iter().find(|(n, _)| *n == control) {
+                GamepadControl::Button(b)
+            } else if let Some(&(_, a)) = AXIS_NAMES.iter().find(|(n, _)| *n == control) {
+                GamepadControl::Axis(a, range)
+            } else {
+                // Newer SDL versions add controls; skip the ones we don't know
+                continue;
+            };
+            let input = parse_input(input).ok_or_else(|| invalid(field))?;
+            bindings.push((control, input));
+        }
+        Ok(Mapping {
+            guid,
+            name,
+            bindings,
+        })
+    }
+}
+
+/// Splits off a `+` or `-` prefix selecting half of an axis.
+fn split_range(s: &str) -> (AxisRange, &str) {
+    if let Some(s) = s.strip_prefix('+') {
+        (AxisRange::Positive, s)
+    } else if let Some(s) = s.strip_prefix('-') {
+        (AxisRange::Negative, s)
+    } else {
+        (AxisRange::Full, s)
+    }
+}
+
+fn parse_input(s: &str) -> Option<JoystickInput> {
+    let (range, s) = split_range(s);
+    let (inverted, s) = match s.strip_suffix('~') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let kind = s.get(..1)?;
+    let rest = &s[1..];
+    Some(match kind {
+        "b" => JoystickInput::Button(rest.parse().ok()?),
+        "a" => JoystickInput::Axis {
+            index: rest.parse().ok()?,
+            range,
+            inverted,
+        },
+        "h" => {
+            let (index, mask) = rest.split_once('.')?;
+            JoystickInput::Hat {
+                index: index.parse().ok()?,
+                mask: mask.parse().ok()?,
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// The evdev control behind a binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvdevInput {
+    Key(Key),
+    Axis {
+        axis: AbsoluteAxisType,
+        range: AxisRange,
+        inverted: bool,
+    },
+    /// An `ABS_HAT*` axis held in one direction: `direction` is -1 or 1.
+    Hat {
+        axis: AbsoluteAxisType,
+        direction: i8,
+    },
+}
+
+/// How SDL numbers the buttons, axes and hats of an evdev device.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JoystickLayout {
+    pub buttons: Vec<Key>,
+    pub axes: Vec<AbsoluteAxisType>,
+    /// The hat switches present, by number: hat `n` is `ABS_HAT<n>X` and `ABS_HAT<n>Y`.
+    pub hats: Vec<u16>,
+}
+
+impl JoystickLayout {
+    pub fn new(
+        keys: Option<&AttributeSetRef<Key>>,
+        axes: Option<&AttributeSetRef<AbsoluteAxisType>>,
+    ) -> Self {
+        let mut layout = JoystickLayout::default();
+        if let Some(keys) = keys {
+            // Joystick buttons come first, then the remaining ones from BTN_MISC up
+            let misc = Key::BTN_0.code();
+            let joystick = Key::BTN_TRIGGER.code();
+            layout
+                .buttons
+                .extend(keys.iter().filter(|k| k.code() >= joystick));
+            layout
+                .buttons
+                .extend(keys.iter().filter(|k| (misc..joystick).contains(&k.code())));
+        }
+        if let Some(axes) = axes {
+            let hat0 = AbsoluteAxisType::ABS_HAT0X.0;
+            let hat3 = AbsoluteAxisType::ABS_HAT3Y.0;
+            layout
+                .axes
+                .extend(axes.iter().filter(|a| !(hat0..=hat3).contains(&a.0)));
+            for hat in 0..4 {
+                let x = AbsoluteAxisType(hat0 + hat * 2);
+                let y = AbsoluteAxisType(hat0 + hat * 2 + 1);
+                if axes.contains(x) || axes.contains(y) {
+                    layout.hats.push(hat);
+                }
+            }
+        }
+        layout
+    }
+
+    /// Returns the evdev control SDL calls `input`, if the device has it.
+    pub fn resolve(&self, input: JoystickInput) -> Option<EvdevInput> {
+        Some(match input {
+            JoystickInput::Button(index) => EvdevInput::Key(*self.buttons.get(index)?),
+            JoystickInput::Axis {
+                index,
+                range,
+                inverted,
+            } => EvdevInput::Axis {
+                axis: *self.axes.get(index)?,
+                range,
+                inverted,
+            },
+            JoystickInput::Hat { index, mask } => {
+                let x = AbsoluteAxisType::ABS_HAT0X.0 + self.hats.get(index)? * 2;
+                let (axis, direction) = match mask {
+                    1 => (x + 1, -1),
+                    2 => (x, 1),
+                    4 => (x + 1, 1),
+                    8 => (x, -1),
+                    _ => return None,
+                };
+                EvdevInput::Hat {
+                    axis: AbsoluteAxisType(axis),
+                    direction,
+                }
+            }
+        })
+    }
+}
+
+/// A mapping resolved against a device: which evdev control provides each gamepad control.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GamepadMapping {
+    pub name: String,
+    pub bindings: Vec<(GamepadControl, EvdevInput)>,
+}
+
+impl GamepadMapping {
+    pub fn new(mapping: &Mapping, layout: &JoystickLayout) -> Self {
+        GamepadMapping {
+            name: mapping.name.clone(),
+            bindings: mapping
+                .bindings
+                .iter()
+                .filter_map(|&(control, input)| Some((control, layout.resolve(input)?)))
+                .collect(),
+        }
+    }
+
+    /// Returns the evdev control bound to `button`.
+    pub fn button(&self, button: GamepadButton) -> Option<EvdevInput> {
+        self.bindings
+            .iter()
+            .find(|(c, _)| *c == GamepadControl::Button(button))
+            .map(|&(_, input)| input)
+    }
+
+    /// Returns the evdev control bound to the whole of `axis`.
+    pub fn axis(&self, axis: GamepadAxis) -> Option<EvdevInput> {
+        self.bindings
+            .iter()
+            .find(|(c, _)| *c == GamepadControl::Axis(axis, AxisRange::Full))
+            .map(|&(_, input)| input)
+    }
+}
+
+/// A set of controller mappings, typically loaded from `gamecontrollerdb.txt`.
+#[derive(Debug, Clone, Default)]
+pub struct ControllerDb {
+    mappings: Vec<Mapping>,
+}
+
+impl ControllerDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a database file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parse the contents of a database file, keeping the mappings for Linux and those without
+    /// a platform.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut db = Self::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let platform = line
+                .split(',')
+                .find_map(|field| field.strip_prefix("platform:"));
+            if platform.is_some_and(|p| p != "Linux") {
+                continue;
+            }
+            db.add(line.parse()?);
+        }
+        Ok(db)
+    }
+
+    /// Add a mapping, replacing any earlier one for the same GUID.
+    pub fn add(&mut self, mapping: Mapping) {
+        self.mappings.retain(|m| m.guid != mapping.guid);
+        self.mappings.push(mapping);
+    }
+
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    /// Find the mapping for `guid`, falling back like SDL does to entries without a name CRC
+    /// and then to entries for other versions of the same model.
+    pub fn find(&self, guid: Guid) -> Option<&Mapping> {
+        let crc_matches = |m: &Mapping| m.guid.crc() == 0 || m.guid.crc() == guid.crc();
+        let plain = guid.without_crc();
+        self.mappings
+            .iter()
+            .find(|m| m.guid == guid)
+            .or_else(|| {
+                self.mappings
+                    .iter()
+                    .find(|m| crc_matches(m) && m.guid.without_crc() == plain)
+            })
+            .or_else(|| {
+                self.mappings.iter().find(|m| {
+                    crc_matches(m)
+                        && m.guid.without_crc().without_version() == plain.without_version()
+                })
+            })
+    }
+
+    /// Find and resolve the mapping for `device`.
+    pub fn mapping_for(&self, device: &Device) -> Option<GamepadMapping> {
+        let guid = Guid::new(device.input_id(), device.name().unwrap_or_default());
+        let mapping = self.find(guid)?;
+        let layout = JoystickLayout::new(device.supported_keys(), device.supported_absolute_axes());
+        Some(GamepadMapping::new(mapping, &layout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AttributeSet, BusType};
+
+    #[test]
+    fn resolve_mapping() {
+        let db = ControllerDb::parse(
+            "# Xbox 360\n\
+             030000005e0400008e02000014010000,Xbox 360 Controller,a:b0,b:b1,dpup:h0.1,\
+             leftx:a0,+righty:a4~,lefttrigger:a2,platform:Linux,\n\
+             030000005e0400008e02000014010000,Xbox 360 Controller,a:b1,platform:Windows,\n",
+        )
+        .unwrap();
+        let id = InputId::new(BusType::BUS_USB, 0x045e, 0x028e, 0x0110);
+        let guid = Guid::new(id, "Microsoft X-Box 360 pad");
+        assert_eq!(guid.to_string(), "030000005e0400008e02000010010000");
+        let mapping = db.find(guid).unwrap();
+
+        let keys: AttributeSet<Key> = [Key::BTN_SOUTH, Key::BTN_EAST, Key::BTN_MODE]
+            .into_iter()
+            .collect();
+        let axes: AttributeSet<AbsoluteAxisType> = [
+            AbsoluteAxisType::ABS_X,
+            AbsoluteAxisType::ABS_Y,
+            AbsoluteAxisType::ABS_Z,
+            AbsoluteAxisType::ABS_RX,
+            AbsoluteAxisType::ABS_RY,
+            AbsoluteAxisType::ABS_HAT0X,
+            AbsoluteAxisType::ABS_HAT0Y,
+        ]
+        .into_iter()
+        .collect();
+        let layout = JoystickLayout::new(Some(&keys), Some(&axes));
+        let mapping = GamepadMapping::new(mapping, &layout);
+
+        assert_eq!(
+            mapping.button(GamepadButton::B),
+            Some(EvdevInput::Key(Key::BTN_EAST))
+        );
+        assert_eq!(
+            mapping.button(GamepadButton::DPadUp),
+            Some(EvdevInput::Hat {
+                axis: AbsoluteAxisType::ABS_HAT0Y,
+                direction: -1
+            })
+        );
+        assert_eq!(
+            mapping.bindings.last(),
+            Some(&(
+                GamepadControl::Axis(GamepadAxis::LeftTrigger, AxisRange::Full),
+                EvdevInput::Axis {
+                    axis: AbsoluteAxisType::ABS_Z,
+                    range: AxisRange::Full,
+                    inverted: false
+                }
+            ))
+        );
+        assert!(mapping.bindings.contains(&(
+            GamepadControl::Axis(GamepadAxis::RightY, AxisRange::Positive),
+            EvdevInput::Axis {
+                axis: AbsoluteAxisType::ABS_RY,
+                range: AxisRange::Full,
+                inverted: true
+            }
+        )));
+    }
+}
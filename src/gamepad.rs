@@ -0,0 +1,578 @@
+//! A gamepad abstraction over [`Device`].
+//!
+//! [`Gamepad`] presents a controller as an Xbox-style gamepad: buttons by role, sticks as
+//! `f32` in `[-1, 1]` and triggers in `[0, 1]`, scaled with the device's axis ranges and dead
+//! zones. By default it relies on the kernel's [gamepad layout], which most drivers follow;
+//! controllers that don't can be described with a mapping from
+//! [`gamecontrollerdb`](crate::gamecontrollerdb).
+//!
+//! ```no_run
+//! use evdev::gamepad::{Gamepad, GamepadEvent};
+//!
+//! let mut pad = Gamepad::new(evdev::Device::open("/dev/input/event0")?);
+//! loop {
+//!     for ev in pad.fetch_events()? {
+//!         match ev {
+//!             GamepadEvent::ButtonPressed(button) => println!("{:?} pressed", button),
+//!             GamepadEvent::AxisChanged(axis, value) => println!("{:?} at {:.2}", axis, value),
+//!             _ => {}
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! [gamepad layout]: https://www.kernel.org/doc/html/latest/input/gamepad.html
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use crate::gamecontrollerdb::{AxisRange, EvdevInput, GamepadControl, GamepadMapping};
+pub use crate::gamecontrollerdb::{GamepadAxis, GamepadButton};
+use crate::{AbsInfo, AbsoluteAxisType, Device, EventType, InputEvent, Key};
+
+const BUTTON_COUNT: usize = GamepadButton::Touchpad as usize + 1;
+const AXIS_COUNT: usize = GamepadAxis::RightTrigger as usize + 1;
+
+/// The buttons of the kernel's gamepad layout.
+const STANDARD_BUTTONS: [(GamepadButton, Key); 15] = [
+    (GamepadButton::A, Key::BTN_SOUTH),
+    (GamepadButton::B, Key::BTN_EAST),
+    (GamepadButton::X, Key::BTN_WEST),
+    (GamepadButton::Y, Key::BTN_NORTH),
+    (GamepadButton::Back, Key::BTN_SELECT),
+    (GamepadButton::Guide, Key::BTN_MODE),
+    (GamepadButton::Start, Key::BTN_START),
+    (GamepadButton::LeftStick, Key::BTN_THUMBL),
+    (GamepadButton::RightStick, Key::BTN_THUMBR),
+    (GamepadButton::LeftShoulder, Key::BTN_TL),
+    (GamepadButton::RightShoulder, Key::BTN_TR),
+    (GamepadButton::DPadUp, Key::BTN_DPAD_UP),
+    (GamepadButton::DPadDown, Key::BTN_DPAD_DOWN),
+    (GamepadButton::DPadLeft, Key::BTN_DPAD_LEFT),
+    (GamepadButton::DPadRight, Key::BTN_DPAD_RIGHT),
+];
+
+/// The directional pad as a hat switch, for drivers that don't report `BTN_DPAD_*`.
+const STANDARD_HAT: [(GamepadButton, AbsoluteAxisType, i8); 4] = [
+    (GamepadButton::DPadUp, AbsoluteAxisType::ABS_HAT0Y, -1),
+    (GamepadButton::DPadDown, AbsoluteAxisType::ABS_HAT0Y, 1),
+    (GamepadButton::DPadLeft, AbsoluteAxisType::ABS_HAT0X, -1),
+    (GamepadButton::DPadRight, AbsoluteAxisType::ABS_HAT0X, 1),
+];
+
+const STANDARD_AXES: [(GamepadAxis, AbsoluteAxisType); 6] = [
+    (GamepadAxis::LeftX, AbsoluteAxisType::ABS_X),
+    (GamepadAxis::LeftY, AbsoluteAxisType::ABS_Y),
+    (GamepadAxis::RightX, AbsoluteAxisType::ABS_RX),
+    (GamepadAxis::RightY, AbsoluteAxisType::ABS_RY),
+    (GamepadAxis::LeftTrigger, AbsoluteAxisType::ABS_Z),
+    (GamepadAxis::RightTrigger, AbsoluteAxisType::ABS_RZ),
+];
+
+/// Digital triggers, for controllers without analog ones.
+const STANDARD_TRIGGER_BUTTONS: [(GamepadAxis, Key); 2] = [
+    (GamepadAxis::LeftTrigger, Key::BTN_TL2),
+    (GamepadAxis::RightTrigger, Key::BTN_TR2),
+];
+
+/// Returns the key the kernel's gamepad layout uses for `button`, if it has one.
+pub(crate) fn standard_key(button: GamepadButton) -> Option<Key> {
+    let (_, key) = STANDARD_BUTTONS.iter().find(|(b, _)| *b == button)?;
+    Some(*key)
+}
+
+/// Returns the axis the kernel's gamepad layout uses for `axis`.
+pub(crate) fn standard_axis(axis: GamepadAxis) -> AbsoluteAxisType {
+    STANDARD_AXES[axis as usize].1
+}
+
+/// Build the mapping for a device that follows the kernel's gamepad layout, with the controls
+/// it supports.
+pub fn standard_mapping(device: &Device) -> GamepadMapping {
+    let has_key = |key| {
+        device
+            .supported_keys()
+            .is_some_and(|keys| keys.contains(key))
+    };
+    let has_axis = |axis| {
+        device
+            .supported_absolute_axes()
+            .is_some_and(|axes| axes.contains(axis))
+    };
+
+    let mut bindings = Vec::new();
+    for (button, key) in STANDARD_BUTTONS {
+        if has_key(key) {
+            bindings.push((GamepadControl::Button(button), EvdevInput::Key(key)));
+        }
+    }
+    for (button, axis, direction) in STANDARD_HAT {
+        if has_axis(axis) {
+            bindings.push((
+                GamepadControl::Button(button),
+                EvdevInput::Hat { axis, direction },
+            ));
+        }
+    }
+    for (control, axis) in STANDARD_AXES {
+        if has_axis(axis) {
+            bindings.push((
+                GamepadControl::Axis(control, AxisRange::Full),
+                EvdevInput::Axis {
+                    axis,
+                    range: AxisRange::Full,
+                    inverted: false,
+                },
+            ));
+        }
+    }
+    for (control, key) in STANDARD_TRIGGER_BUTTONS {
+        if has_key(key) {
+            bindings.push((
+                GamepadControl::Axis(control, AxisRange::Full),
+                EvdevInput::Key(key),
+            ));
+        }
+    }
+    GamepadMapping {
+        name: device.name().unwrap_or_default().to_owned(),
+        bindings,
+    }
+}
+
+/// A change of a gamepad control.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    ButtonPressed(GamepadButton),
+    ButtonReleased(GamepadButton),
+    /// A stick moved to a position in `[-1, 1]`, or a trigger in `[0, 1]`. Up and left are
+    /// negative.
+    AxisChanged(GamepadAxis, f32),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Binding {
+    control: GamepadControl,
+    input: EvdevInput,
+    /// The range of the input axis.
+    info: Option<AbsInfo>,
+    /// The input's current value, in `[-1, 1]` for full axes and `[0, 1]` otherwise.
+    value: f32,
+}
+
+impl Binding {
+    fn matches(&self, ev: &InputEvent) -> bool {
+        match self.input {
+            EvdevInput::Key(key) => ev.event_type() == EventType::KEY && ev.code() == key.code(),
+            EvdevInput::Axis { axis, .. } | EvdevInput::Hat { axis, .. } => {
+                ev.event_type() == EventType::ABSOLUTE && ev.code() == axis.0
+            }
+        }
+    }
+
+    fn set(&mut self, raw: i32) {
+        self.value = match self.input {
+            EvdevInput::Key(_) => (raw != 0) as u8 as f32,
+            EvdevInput::Hat { direction, .. } => {
+                (raw.signum() == i32::from(direction)) as u8 as f32
+            }
+            EvdevInput::Axis {
+                range, inverted, ..
+            } => {
+                // The dead zone is around the center, which is the rest position of sticks
+                // but the middle of the travel of triggers
+                let is_trigger = matches!(
+                    self.control,
+                    GamepadControl::Axis(GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger, _)
+                );
+                let mut value = match self.info {
+                    Some(info) if is_trigger => info.normalize(raw),
+                    Some(info) => info.normalize_with_flat(raw),
+                    None => 0.0,
+                };
+                if inverted {
+                    value = -value;
+                }
+                match range {
+                    AxisRange::Full => value,
+                    AxisRange::Positive => value.max(0.0),
+                    AxisRange::Negative => (-value).max(0.0),
+                }
+            }
+        };
+    }
+
+    /// Returns `true` if the input is a whole axis, i.e. its value is in `[-1, 1]`.
+    fn is_full_axis(&self) -> bool {
+        matches!(
+            self.input,
+            EvdevInput::Axis {
+                range: AxisRange::Full,
+                ..
+            }
+        )
+    }
+}
+
+/// The state of the controls, without the device, so they can be fed events directly.
+#[derive(Debug, Clone)]
+struct Controls {
+    bindings: Vec<Binding>,
+    buttons: [bool; BUTTON_COUNT],
+    axes: [f32; AXIS_COUNT],
+}
+
+impl Controls {
+    /// Bind the controls of `mapping`, starting from the axis ranges and values of `abs_info`
+    /// and the keys for which `key_held` returns `true`.
+    fn new(
+        mapping: &GamepadMapping,
+        abs_info: impl Fn(AbsoluteAxisType) -> Option<AbsInfo>,
+        key_held: impl Fn(Key) -> bool,
+    ) -> Self {
+        let bindings = mapping
+            .bindings
+            .iter()
+            .map(|&(control, input)| {
+                let info = match input {
+                    EvdevInput::Axis { axis, .. } | EvdevInput::Hat { axis, .. } => abs_info(axis),
+                    EvdevInput::Key(_) => None,
+                };
+                Binding {
+                    control,
+                    input,
+                    info,
+                    value: 0.0,
+                }
+            })
+            .collect();
+
+        let mut controls = Controls {
+            bindings,
+            buttons: [false; BUTTON_COUNT],
+            axes: [0.0; AXIS_COUNT],
+        };
+        for i in 0..controls.bindings.len() {
+            let binding = &mut controls.bindings[i];
+            let raw = match binding.input {
+                EvdevInput::Key(key) => key_held(key) as i32,
+                EvdevInput::Axis { .. } | EvdevInput::Hat { .. } => {
+                    binding.info.map_or(0, |info| info.value())
+                }
+            };
+            binding.set(raw);
+            let control = binding.control;
+            controls.update_control(control, &mut Vec::new());
+        }
+        controls
+    }
+
+    /// Update the state with an event from the device, appending the resulting changes to
+    /// `out`.
+    fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<GamepadEvent>) {
+        for i in 0..self.bindings.len() {
+            if self.bindings[i].matches(ev) {
+                self.bindings[i].set(ev.value());
+                let control = self.bindings[i].control;
+                self.update_control(control, out);
+            }
+        }
+    }
+
+    /// Recompute a control from its bindings and report a change.
+    fn update_control(&mut self, control: GamepadControl, out: &mut Vec<GamepadEvent>) {
+        match control {
+            GamepadControl::Button(button) => {
+                let pressed = self
+                    .bindings
+                    .iter()
+                    .any(|b| b.control == control && b.value > 0.5);
+                if pressed != self.buttons[button as usize] {
+                    self.buttons[button as usize] = pressed;
+                    out.push(if pressed {
+                        GamepadEvent::ButtonPressed(button)
+                    } else {
+                        GamepadEvent::ButtonReleased(button)
+                    });
+                }
+            }
+            GamepadControl::Axis(axis, _) => {
+                let is_trigger =
+                    matches!(axis, GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger);
+                let value: f32 = self
+                    .bindings
+                    .iter()
+                    .filter_map(|b| match b.control {
+                        GamepadControl::Axis(a, range) if a == axis => Some((b, range)),
+                        _ => None,
+                    })
+                    .map(|(b, range)| {
+                        // Whole input axes only fill half of a trigger's or half-axis' range
+                        let value = match (range, is_trigger, b.is_full_axis()) {
+                            (AxisRange::Full, false, _) => return b.value,
+                            (_, _, true) => (b.value + 1.0) / 2.0,
+                            _ => b.value,
+                        };
+                        match range {
+                            AxisRange::Negative => -value,
+                            _ => value,
+                        }
+                    })
+                    .sum();
+                let value = value.clamp(if is_trigger { 0.0 } else { -1.0 }, 1.0);
+                if value != self.axes[axis as usize] {
+                    self.axes[axis as usize] = value;
+                    out.push(GamepadEvent::AxisChanged(axis, value));
+                }
+            }
+        }
+    }
+}
+
+/// A game controller with a standard layout. See the [module documentation](self).
+pub struct Gamepad {
+    device: Device,
+    controls: Controls,
+}
+
+impl Gamepad {
+    /// Wrap a device that follows the kernel's gamepad layout.
+    pub fn new(device: Device) -> Self {
+        let mapping = standard_mapping(&device);
+        Self::with_mapping(device, &mapping)
+    }
+
+    /// Wrap a device with an explicit mapping, e.g. from the SDL controller database.
+    pub fn with_mapping(device: Device, mapping: &GamepadMapping) -> Self {
+        let keys = device.cached_state().key_vals();
+        let controls = Controls::new(
+            mapping,
+            |axis| device.abs_info(axis),
+            |key| keys.is_some_and(|keys| keys.contains(key)),
+        );
+        Gamepad { device, controls }
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+
+    /// Returns `true` if `button` is held.
+    pub fn is_pressed(&self, button: GamepadButton) -> bool {
+        self.controls.buttons[button as usize]
+    }
+
+    /// Returns the position of a stick in `[-1, 1]`, or of a trigger in `[0, 1]`.
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.controls.axes[axis as usize]
+    }
+
+    /// Returns `true` if the device has `button`.
+    pub fn has_button(&self, button: GamepadButton) -> bool {
+        self.controls
+            .bindings
+            .iter()
+            .any(|b| b.control == GamepadControl::Button(button))
+    }
+
+    /// Returns `true` if the device has `axis`.
+    pub fn has_axis(&self, axis: GamepadAxis) -> bool {
+        self.controls
+            .bindings
+            .iter()
+            .any(|b| matches!(b.control, GamepadControl::Axis(a, _) if a == axis))
+    }
+
+    /// Fetch the next batch of events from the device and translate them.
+    ///
+    /// Like [`Device::fetch_events`], this blocks unless the device is non-blocking.
+    pub fn fetch_events(&mut self) -> io::Result<impl Iterator<Item = GamepadEvent>> {
+        let events: Vec<InputEvent> = self.device.fetch_events()?.collect();
+        let mut out = Vec::new();
+        for ev in &events {
+            self.process_event(ev, &mut out);
+        }
+        Ok(out.into_iter())
+    }
+
+    /// Update the state with an event from the device, appending the resulting changes to
+    /// `out`.
+    pub fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<GamepadEvent>) {
+        self.controls.process_event(ev, out);
+    }
+}
+
+impl AsRawFd for Gamepad {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABS_Y: AbsoluteAxisType = AbsoluteAxisType::ABS_Y;
+    const ABS_Z: AbsoluteAxisType = AbsoluteAxisType::ABS_Z;
+    const ABS_RX: AbsoluteAxisType = AbsoluteAxisType::ABS_RX;
+    const ABS_HAT0Y: AbsoluteAxisType = AbsoluteAxisType::ABS_HAT0Y;
+
+    fn axis(axis: AbsoluteAxisType, range: AxisRange, inverted: bool) -> EvdevInput {
+        EvdevInput::Axis {
+            axis,
+            range,
+            inverted,
+        }
+    }
+
+    fn controls(bindings: Vec<(GamepadControl, EvdevInput)>) -> Controls {
+        let mapping = GamepadMapping {
+            name: "test".into(),
+            bindings,
+        };
+        let abs_info = |axis| match axis {
+            ABS_Z => Some(AbsInfo::new(0, 0, 255, 0, 0, 0)),
+            ABS_HAT0Y => Some(AbsInfo::new(0, -1, 1, 0, 0, 0)),
+            _ => Some(AbsInfo::new(0, -100, 100, 0, 20, 0)),
+        };
+        Controls::new(&mapping, abs_info, |_| false)
+    }
+
+    fn feed(controls: &mut Controls, ty: EventType, code: u16, value: i32) -> Vec<GamepadEvent> {
+        let mut out = Vec::new();
+        controls.process_event(&InputEvent::new(ty, code, value), &mut out);
+        out
+    }
+
+    #[test]
+    fn sticks_triggers_and_buttons() {
+        use GamepadAxis::{LeftTrigger, LeftY};
+        let mut pad = controls(vec![
+            (
+                GamepadControl::Axis(LeftY, AxisRange::Full),
+                axis(ABS_Y, AxisRange::Full, true),
+            ),
+            (
+                GamepadControl::Axis(LeftTrigger, AxisRange::Full),
+                axis(ABS_Z, AxisRange::Full, false),
+            ),
+            (
+                GamepadControl::Button(GamepadButton::A),
+                EvdevInput::Key(Key::BTN_SOUTH),
+            ),
+            (
+                GamepadControl::Button(GamepadButton::DPadUp),
+                EvdevInput::Hat {
+                    axis: ABS_HAT0Y,
+                    direction: -1,
+                },
+            ),
+        ]);
+        let abs = EventType::ABSOLUTE;
+        // The trigger rests at its minimum
+        assert_eq!(pad.axes[LeftTrigger as usize], 0.0);
+
+        // Inverted, and with the flat as a dead zone
+        assert_eq!(
+            feed(&mut pad, abs, ABS_Y.0, 100),
+            [GamepadEvent::AxisChanged(LeftY, -1.0)]
+        );
+        assert_eq!(
+            feed(&mut pad, abs, ABS_Y.0, -60),
+            [GamepadEvent::AxisChanged(LeftY, 0.5)]
+        );
+        assert_eq!(
+            feed(&mut pad, abs, ABS_Y.0, 15),
+            [GamepadEvent::AxisChanged(LeftY, 0.0)]
+        );
+        assert_eq!(feed(&mut pad, abs, ABS_Y.0, -15), []);
+
+        assert_eq!(
+            feed(&mut pad, abs, ABS_Z.0, 255),
+            [GamepadEvent::AxisChanged(LeftTrigger, 1.0)]
+        );
+        assert_eq!(
+            feed(&mut pad, EventType::KEY, Key::BTN_SOUTH.code(), 1),
+            [GamepadEvent::ButtonPressed(GamepadButton::A)]
+        );
+        assert_eq!(feed(&mut pad, EventType::KEY, Key::BTN_SOUTH.code(), 2), []);
+        assert_eq!(
+            feed(&mut pad, abs, ABS_HAT0Y.0, -1),
+            [GamepadEvent::ButtonPressed(GamepadButton::DPadUp)]
+        );
+        assert_eq!(
+            feed(&mut pad, abs, ABS_HAT0Y.0, 1),
+            [GamepadEvent::ButtonReleased(GamepadButton::DPadUp)]
+        );
+    }
+
+    #[test]
+    fn half_axes() {
+        use GamepadAxis::{LeftX, RightTrigger, RightX};
+        let mut pad = controls(vec![
+            // Keys pulling a stick each way
+            (
+                GamepadControl::Axis(LeftX, AxisRange::Negative),
+                EvdevInput::Key(Key::KEY_LEFT),
+            ),
+            (
+                GamepadControl::Axis(LeftX, AxisRange::Positive),
+                EvdevInput::Key(Key::KEY_RIGHT),
+            ),
+            // The upper half of an axis as a whole stick
+            (
+                GamepadControl::Axis(RightX, AxisRange::Full),
+                axis(ABS_RX, AxisRange::Positive, false),
+            ),
+            // A whole axis as a trigger
+            (
+                GamepadControl::Axis(RightTrigger, AxisRange::Full),
+                axis(ABS_Y, AxisRange::Full, false),
+            ),
+        ]);
+        let (abs, key) = (EventType::ABSOLUTE, EventType::KEY);
+
+        assert_eq!(
+            feed(&mut pad, key, Key::KEY_LEFT.code(), 1),
+            [GamepadEvent::AxisChanged(LeftX, -1.0)]
+        );
+        // Both held cancel out
+        assert_eq!(
+            feed(&mut pad, key, Key::KEY_RIGHT.code(), 1),
+            [GamepadEvent::AxisChanged(LeftX, 0.0)]
+        );
+        assert_eq!(
+            feed(&mut pad, key, Key::KEY_LEFT.code(), 0),
+            [GamepadEvent::AxisChanged(LeftX, 1.0)]
+        );
+
+        assert_eq!(
+            feed(&mut pad, abs, ABS_RX.0, 100),
+            [GamepadEvent::AxisChanged(RightX, 1.0)]
+        );
+        assert_eq!(
+            feed(&mut pad, abs, ABS_RX.0, -100),
+            [GamepadEvent::AxisChanged(RightX, 0.0)]
+        );
+
+        // The axis rests at its center, which is half way for the trigger
+        assert_eq!(pad.axes[RightTrigger as usize], 0.5);
+        assert_eq!(
+            feed(&mut pad, abs, ABS_Y.0, -100),
+            [GamepadEvent::AxisChanged(RightTrigger, 0.0)]
+        );
+        assert_eq!(
+            feed(&mut pad, abs, ABS_Y.0, 100),
+            [GamepadEvent::AxisChanged(RightTrigger, 1.0)]
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Reading and writing the text format of Android's `getevent`.
+//!
+//! `adb shell getevent -lt` prints one event per line, with the timestamp, the device and
+//! either symbolic names or hex numbers for the type, code and value:
+//!
+//! ```text
+//! [   12345.678901] /dev/input/event3: EV_KEY       KEY_VOLUMEDOWN       DOWN
+//! [   12345.678901] /dev/input/event3: EV_SYN       SYN_REPORT           00000000
+//! ```
+//!
+//! [`parse_line`] reads this format, with or without `-l` and `-t`, so traces captured on a
+//! phone can be [replayed](replay) through uinput, and [`GeteventFormat`] writes it, so events
+//! from a local device can be compared against Android traces.
+
+use std::io::{self, BufRead};
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::uinput::VirtualDevice;
+use crate::{
+    AbsoluteAxisType, EventType, FrameIter, InputEvent, Key, LedType, MiscType, RawEvent,
+    RelativeAxisType, SoundType, SwitchType, Synchronization,
+};
+
+/// The `EV_*` names of the event types, as printed by getevent.
+pub(crate) const TYPE_NAMES: [(&str, EventType); 12] = [
+    ("EV_SYN", EventType::SYNCHRONIZATION),
+    ("EV_KEY", EventType::KEY),
+    ("EV_REL", EventType::RELATIVE),
+    ("EV_ABS", EventType::ABSOLUTE),
+    ("EV_MSC", EventType::MISC),
+    ("EV_SW", EventType::SWITCH),
+    ("EV_LED", EventType::LED),
+    ("EV_SND", EventType::SOUND),
+    ("EV_REP", EventType::REPEAT),
+    ("EV_FF", EventType::FORCEFEEDBACK),
+    ("EV_PWR", EventType::POWER),
+    ("EV_FF_STATUS", EventType::FORCEFEEDBACKSTATUS),
+];
+
+/// The labels getevent uses for key values.
+pub(crate) const KEY_VALUES: [&str; 3] = ["UP", "DOWN", "REPEAT"];
+
+/// One event line of a getevent trace.
+#[derive(Debug, Clone)]
+pub struct GeteventLine {
+    /// The device the event came from, if the trace names it.
+    pub device: Option<String>,
+    /// The event, with its timestamp if the trace has one and zero otherwise.
+    pub event: InputEvent,
+}
+
+fn invalid(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid getevent line: {}", line),
+    )
+}
+
+/// Parse a line of getevent output.
+///
+/// Returns `Ok(None)` for lines that don't describe events, such as the `add device` header and
+/// the device properties that follow it.
+pub fn parse_line(line: &str) -> io::Result<Option<GeteventLine>> {
+    let trimmed = line.trim_end();
+    if trimmed.is_empty()
+        || trimmed.starts_with(char::is_whitespace)
+        || trimmed.starts_with("add device")
+        || trimmed.starts_with("remove device")
+        || trimmed.starts_with("could not")
+    {
+        return Ok(None);
+    }
+
+    let (sec, usec, rest) = match trimmed.strip_prefix('[') {
+        Some(rest) => {
+            let (time, rest) = rest.split_once(']').ok_or_else(|| invalid(line))?;
+            let (sec, usec) = time.trim().split_once('.').ok_or_else(|| invalid(line))?;
+            let sec = sec.parse().map_err(|_| invalid(line))?;
+            let usec = usec.parse().map_err(|_| invalid(line))?;
+            (sec, usec, rest)
+        }
+        None => (0, 0, trimmed),
+    };
+
+    let mut tokens = rest.split_whitespace().peekable();
+    let device = match tokens.peek() {
+        Some(token) if token.ends_with(':') => {
+            let device = token.trim_end_matches(':').to_owned();
+            tokens.next();
+            Some(device)
+        }
+        _ => None,
+    };
+    let (type_, code, value) = match (tokens.next(), tokens.next(), tokens.next(), tokens.next()) {
+        (Some(t), Some(c), Some(v), None) => (t, c, v),
+        _ => return Err(invalid(line)),
+    };
+
+    let event_type = TYPE_NAMES
+        .iter()
+        .find(|(name, _)| *name == type_)
+        .map(|&(_, ty)| ty)
+        .or_else(|| hex(type_).map(|t| EventType(t as u16)))
+        .ok_or_else(|| invalid(line))?;
+    let code = hex(code)
+        .map(|c| c as u16)
+        .or_else(|| parse_code(event_type, code))
+        .ok_or_else(|| invalid(line))?;
+    let value = KEY_VALUES
+        .iter()
+        .position(|&v| v == value)
+        .map(|v| v as i32)
+        .or_else(|| hex(value).map(|v| v as i32))
+        .ok_or_else(|| invalid(line))?;
+
+    let event = RawEvent {
+        sec,
+        usec,
+        type_: event_type.0,
+        code,
+        value,
+    };
+    Ok(Some(GeteventLine {
+        device,
+        event: event.into(),
+    }))
+}
+
+/// Parse every event line of a getevent trace, skipping other lines.
+pub fn parse(reader: impl BufRead) -> impl Iterator<Item = io::Result<GeteventLine>> {
+    reader
+        .lines()
+        .filter_map(|line| line.and_then(|line| parse_line(&line)).transpose())
+}
+
+fn hex(s: &str) -> Option<u32> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(s, 16).ok()
+}
+
+fn parse_code(event_type: EventType, name: &str) -> Option<u16> {
+    Some(match event_type {
+        EventType::SYNCHRONIZATION => name.parse::<Synchronization>().ok()?.0,
+        EventType::KEY => name.parse::<Key>().ok()?.code(),
+        EventType::RELATIVE => name.parse::<RelativeAxisType>().ok()?.0,
+        EventType::ABSOLUTE => name.parse::<AbsoluteAxisType>().ok()?.0,
+        EventType::MISC => name.parse::<MiscType>().ok()?.0,
+        EventType::SWITCH => name.parse::<SwitchType>().ok()?.0,
+        EventType::LED => name.parse::<LedType>().ok()?.0,
+        EventType::SOUND => name.parse::<SoundType>().ok()?.0,
+        _ => return None,
+    })
+}
+
+/// Returns the symbolic name of `code`, if it has one.
+pub(crate) fn code_name(event_type: EventType, code: u16) -> Option<String> {
+    let name = match event_type {
+        EventType::SYNCHRONIZATION => format!("{:?}", Synchronization(code)),
+        EventType::KEY => format!("{:?}", Key::new(code)),
+        EventType::RELATIVE => format!("{:?}", RelativeAxisType(code)),
+        EventType::ABSOLUTE => format!("{:?}", AbsoluteAxisType(code)),
+        EventType::MISC => format!("{:?}", MiscType(code)),
+        EventType::SWITCH => format!("{:?}", SwitchType(code)),
+        EventType::LED => format!("{:?}", LedType(code)),
+        EventType::SOUND => format!("{:?}", SoundType(code)),
+        _ => return None,
+    };
+    // Codes without a name are formatted as "unknown key: <code>"
+    (!name.contains(' ')).then_some(name)
+}
+
+/// Options for writing events in getevent's format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeteventFormat {
+    /// Print symbolic names, like `getevent -l`.
+    pub labels: bool,
+    /// Print timestamps, like `getevent -t`.
+    pub timestamps: bool,
+}
+
+impl Default for GeteventFormat {
+    /// The format of `getevent -lt`.
+    fn default() -> Self {
+        GeteventFormat {
+            labels: true,
+            timestamps: true,
+        }
+    }
+}
+
+impl GeteventFormat {
+    /// Format an event as a line of getevent output, without the trailing newline.
+    pub fn format(&self, device: Option<&str>, ev: &InputEvent) -> String {
+        let mut line = String::new();
+        if self.timestamps {
+            let raw = RawEvent::from(*ev);
+            line += &format!("[{:8}.{:06}] ", raw.sec, raw.usec);
+        }
+        if let Some(device) = device {
+            line += &format!("{}: ", device);
+        }
+        if !self.labels {
+            line += &format!(
+                "{:04x} {:04x} {:08x}",
+                ev.event_type().0,
+                ev.code(),
+                ev.value()
+            );
+            return line;
+        }
+
+        let type_name = TYPE_NAMES.iter().find(|(_, ty)| *ty == ev.event_type());
+        match type_name {
+            Some((name, _)) => line += &format!("{:<12}", name),
+            None => line += &format!("{:04x}        ", ev.event_type().0),
+        }
+        match code_name(ev.event_type(), ev.code()) {
+            Some(name) => line += &format!(" {:<20}", name),
+            None => line += &format!(" {:04x}                ", ev.code()),
+        }
+        let value_name = match ev.event_type() {
+            EventType::KEY => KEY_VALUES.get(ev.value() as usize),
+            _ => None,
+        };
+        match value_name {
+            Some(name) => line += &format!(" {:<20}", name),
+            None => line += &format!(" {:08x}            ", ev.value()),
+        }
+        line
+    }
+}
+
+/// Emit `events` on `device` frame by frame, sleeping between frames to reproduce the
+/// timing of the trace.
+///
+/// `SYN_REPORT`s come from the trace; any events after the last one are dropped.
+pub fn replay(
+    events: impl IntoIterator<Item = InputEvent>,
+    device: &mut VirtualDevice,
+) -> io::Result<()> {
+    replay_with_clock(events, device, &SystemClock)
+}
+
+/// Like [`replay`], sleeping on `clock`, e.g. a [`MockClock`](crate::clock::MockClock) to
+/// replay without waiting.
+pub fn replay_with_clock(
+    events: impl IntoIterator<Item = InputEvent>,
+    device: &mut VirtualDevice,
+    clock: &impl Clock,
+) -> io::Result<()> {
+    replay_frames(events, clock, |events| device.emit(events))
+}
+
+fn replay_frames(
+    events: impl IntoIterator<Item = InputEvent>,
+    clock: &impl Clock,
+    mut emit: impl FnMut(&[InputEvent]) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut last = None;
+    for frame in FrameIter::<_>::new(events) {
+        let Some((syn, events)) = frame.as_slice().split_last() else {
+            continue;
+        };
+        let time = syn.timestamp();
+        if let Some(delay) = last.and_then(|last| time.duration_since(last).ok()) {
+            clock.sleep(Duration::min(delay, Duration::from_secs(10)));
+        }
+        last = Some(time);
+        emit(events)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InputEventKind;
+
+    #[test]
+    fn parse_and_format() {
+        let line = "[   12345.000120] /dev/input/event3: EV_KEY       KEY_VOLUMEDOWN       DOWN                ";
+        let parsed = parse_line(line).unwrap().unwrap();
+        assert_eq!(parsed.device.as_deref(), Some("/dev/input/event3"));
+        assert_eq!(
+            parsed.event.kind(),
+            InputEventKind::Key(Key::KEY_VOLUMEDOWN)
+        );
+        assert_eq!(parsed.event.value(), 1);
+        let format = GeteventFormat::default();
+        assert_eq!(format.format(parsed.device.as_deref(), &parsed.event), line);
+
+        let line = "0003 0039 ffffffff";
+        let parsed = parse_line(line).unwrap().unwrap();
+        assert_eq!(
+            parsed.event.kind(),
+            InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_TRACKING_ID)
+        );
+        assert_eq!(parsed.event.value(), -1);
+        let format = GeteventFormat {
+            labels: false,
+            timestamps: false,
+        };
+        assert_eq!(format.format(None, &parsed.event), line);
+
+        assert!(parse_line("add device 1: /dev/input/event3")
+            .unwrap()
+            .is_none());
+        assert!(parse_line("EV_KEY KEY_A").is_err());
+    }
+
+    #[test]
+    fn replay_timing() -> io::Result<()> {
+        use crate::clock::MockClock;
+        let trace = "[   100.000000] EV_KEY KEY_A DOWN\n\
+                     [   100.000000] EV_SYN SYN_REPORT 00000000\n\
+                     [   100.250000] EV_KEY KEY_A UP\n\
+                     [   100.250000] EV_SYN SYN_REPORT 00000000\n";
+        let events = trace
+            .lines()
+            .map(|line| Ok(parse_line(line)?.unwrap().event))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let clock = MockClock::default();
+        let mut emitted = Vec::new();
+        replay_frames(events, &clock, |frame| {
+            emitted.push((clock.now(), frame.len()));
+            Ok(())
+        })?;
+        let at = |ms| std::time::SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+        assert_eq!(emitted, [(at(0), 1), (at(250), 1)]);
+        Ok(())
+    }
+}
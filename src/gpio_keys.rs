@@ -0,0 +1,415 @@
+//! Physical buttons wired to GPIOs, as exposed by the kernel's `gpio-keys` driver.
+//!
+//! Embedded boards describe their buttons in the device tree, and `gpio-keys` turns them into
+//! a single input device on the host bus. [`GpioKeys`] finds that device, names each of its
+//! keys with a label of your choosing, filters out contact bounce the hardware debounce
+//! misses, and tells short presses from long ones.
+//!
+//! ```no_run
+//! use evdev::gpio_keys::{ButtonEvent, GpioKeys};
+//! use evdev::Key;
+//! use std::time::Duration;
+//!
+//! let mut buttons = GpioKeys::find()?
+//!     .label(Key::KEY_ENTER, "select")
+//!     .label(Key::KEY_ESC, "back")
+//!     .long_press(Key::KEY_ESC, Duration::from_secs(2));
+//! loop {
+//!     for ev in buttons.fetch_events(None)? {
+//!         match ev {
+//!             ButtonEvent::Released { key, long_press: false } => {
+//!                 println!("{} clicked", buttons.label_of(key).unwrap_or("?"))
+//!             }
+//!             ButtonEvent::LongPress { key } => println!("{:?} held", key),
+//!             _ => {}
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::{BusType, Device, EventType, InputEvent, Key};
+
+/// The debounce interval of buttons not configured otherwise.
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(20);
+
+/// A change of a button.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ButtonEvent {
+    Pressed {
+        key: Key,
+    },
+    /// The button was held past its long press threshold. It is still held.
+    LongPress {
+        key: Key,
+    },
+    /// The button was released. `long_press` is `true` if a [`LongPress`](Self::LongPress)
+    /// was reported for this press, so the release shouldn't count as a click.
+    Released {
+        key: Key,
+        long_press: bool,
+    },
+}
+
+/// Returns `true` if `device` looks like a `gpio-keys` device: on the host bus, and named or
+/// located after the driver.
+pub fn is_gpio_keys(device: &Device) -> bool {
+    let gpio =
+        |s: Option<&str>| s.is_some_and(|s| s.contains("gpio-keys") || s.contains("gpio_keys"));
+    device.input_id().bus_type() == BusType::BUS_HOST
+        && (gpio(device.name()) || gpio(device.physical_path()))
+}
+
+#[derive(Debug, Clone)]
+struct Button {
+    label: String,
+    debounce: Duration,
+    long_press: Option<Duration>,
+    /// The state last read from the device.
+    raw: bool,
+    /// The debounced state.
+    pressed: bool,
+    /// When the debounced state last changed; changes within the debounce interval are
+    /// bounce.
+    changed: Option<SystemTime>,
+    long_press_sent: bool,
+}
+
+impl Button {
+    fn new(key: Key, pressed: bool) -> Self {
+        Button {
+            label: format!("{:?}", key),
+            debounce: DEFAULT_DEBOUNCE,
+            long_press: None,
+            raw: pressed,
+            pressed,
+            changed: None,
+            long_press_sent: false,
+        }
+    }
+
+    /// Returns when the raw state, if it differs, is taken over.
+    fn settle_at(&self) -> Option<SystemTime> {
+        if self.raw == self.pressed {
+            return None;
+        }
+        Some(
+            self.changed
+                .map_or(SystemTime::UNIX_EPOCH, |t| t + self.debounce),
+        )
+    }
+
+    /// Returns when the current press becomes a long press.
+    fn long_press_at(&self) -> Option<SystemTime> {
+        let threshold = self.long_press?;
+        let pressed_at = self.changed?;
+        (self.pressed && !self.long_press_sent).then(|| pressed_at + threshold)
+    }
+
+    fn next_deadline(&self) -> Option<SystemTime> {
+        match (self.settle_at(), self.long_press_at()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn set_pressed(
+        &mut self,
+        key: Key,
+        pressed: bool,
+        time: SystemTime,
+        out: &mut Vec<ButtonEvent>,
+    ) {
+        self.pressed = pressed;
+        self.changed = Some(time);
+        if pressed {
+            self.long_press_sent = false;
+            out.push(ButtonEvent::Pressed { key });
+        } else {
+            out.push(ButtonEvent::Released {
+                key,
+                long_press: self.long_press_sent,
+            });
+        }
+    }
+}
+
+/// The buttons, without the device, so they can be fed events directly.
+#[derive(Debug, Clone, Default)]
+struct Buttons {
+    buttons: BTreeMap<Key, Button>,
+}
+
+impl Buttons {
+    fn button(&mut self, key: Key) -> &mut Button {
+        self.buttons
+            .entry(key)
+            .or_insert_with(|| Button::new(key, false))
+    }
+
+    fn next_deadline(&self) -> Option<SystemTime> {
+        self.buttons
+            .values()
+            .filter_map(Button::next_deadline)
+            .min()
+    }
+
+    fn tick(&mut self, now: SystemTime, out: &mut Vec<ButtonEvent>) {
+        for (&key, button) in &mut self.buttons {
+            // At most a settle and a long press are due, in either order
+            while let Some(deadline) = button.next_deadline().filter(|&d| d <= now) {
+                if button.settle_at() == Some(deadline) {
+                    let raw = button.raw;
+                    button.set_pressed(key, raw, deadline, out);
+                } else {
+                    button.long_press_sent = true;
+                    out.push(ButtonEvent::LongPress { key });
+                }
+            }
+        }
+    }
+
+    fn process_event(&mut self, ev: &InputEvent, time: SystemTime, out: &mut Vec<ButtonEvent>) {
+        // Repeats are ignored, so are keys gpio-keys didn't declare
+        if ev.event_type() != EventType::KEY || ev.value() == 2 {
+            return;
+        }
+        self.tick(time, out);
+        let key = Key::new(ev.code());
+        let Some(button) = self.buttons.get_mut(&key) else {
+            return;
+        };
+        button.raw = ev.value() != 0;
+        let bouncing = button.changed.is_some_and(|t| time < t + button.debounce);
+        if button.raw != button.pressed && !bouncing {
+            button.set_pressed(key, button.raw, time, out);
+        }
+    }
+}
+
+/// The buttons of a `gpio-keys` device. See the [module documentation](self).
+pub struct GpioKeys {
+    device: Device,
+    buttons: Buttons,
+    clock: SharedClock,
+}
+
+impl GpioKeys {
+    /// Open the first device in `/dev/input` for which [`is_gpio_keys`] holds.
+    pub fn find() -> io::Result<Self> {
+        Self::find_by(is_gpio_keys)
+    }
+
+    /// Open the first device in `/dev/input` named `name`, for buttons whose device tree node
+    /// sets a label of its own.
+    pub fn find_by_name(name: &str) -> io::Result<Self> {
+        Self::find_by(|device| device.name() == Some(name))
+    }
+
+    fn find_by(mut f: impl FnMut(&Device) -> bool) -> io::Result<Self> {
+        crate::enumerate()
+            .map(|(_, device)| device)
+            .find(|device| f(device))
+            .map(Self::new)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no gpio-keys device found"))
+    }
+
+    /// Wrap a device, with a button for every key it declares, labeled with the key's name.
+    pub fn new(device: Device) -> Self {
+        let held = device.cached_state().key_vals();
+        let buttons = device
+            .supported_keys()
+            .map(|keys| {
+                keys.iter()
+                    .map(|key| (key, Button::new(key, held.is_some_and(|h| h.contains(key)))))
+                    .collect()
+            })
+            .unwrap_or_default();
+        GpioKeys {
+            device,
+            buttons: Buttons { buttons },
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Take the time for debouncing and long presses from `clock` rather than the system
+    /// clock, e.g. a [`MockClock`](crate::clock::MockClock) in tests. Event times come from
+    /// the same clock, see [`Clock::event_time`].
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Label the button for `key`.
+    pub fn label(mut self, key: Key, label: impl Into<String>) -> Self {
+        self.buttons.button(key).label = label.into();
+        self
+    }
+
+    /// Ignore changes of any button within `interval` of its last change. The default is
+    /// 20ms.
+    pub fn debounce(mut self, interval: Duration) -> Self {
+        for button in self.buttons.buttons.values_mut() {
+            button.debounce = interval;
+        }
+        self
+    }
+
+    /// Ignore changes of the button for `key` within `interval` of its last change.
+    pub fn debounce_for(mut self, key: Key, interval: Duration) -> Self {
+        self.buttons.button(key).debounce = interval;
+        self
+    }
+
+    /// Report a [`ButtonEvent::LongPress`] when the button for `key` is held for `threshold`.
+    pub fn long_press(mut self, key: Key, threshold: Duration) -> Self {
+        self.buttons.button(key).long_press = Some(threshold);
+        self
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+
+    /// Returns the buttons and their labels.
+    pub fn buttons(&self) -> impl Iterator<Item = (Key, &str)> {
+        self.buttons
+            .buttons
+            .iter()
+            .map(|(&key, button)| (key, button.label.as_str()))
+    }
+
+    /// Returns the label of the button for `key`.
+    pub fn label_of(&self, key: Key) -> Option<&str> {
+        self.buttons.buttons.get(&key).map(|b| b.label.as_str())
+    }
+
+    /// Returns the button with `label`.
+    pub fn key_of(&self, label: &str) -> Option<Key> {
+        self.buttons()
+            .find(|&(_, l)| l == label)
+            .map(|(key, _)| key)
+    }
+
+    /// Returns `true` if the button for `key` is held, after debouncing.
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.buttons.buttons.get(&key).is_some_and(|b| b.pressed)
+    }
+
+    /// Returns the next time [`tick`](Self::tick) has something to report.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        self.buttons.next_deadline()
+    }
+
+    /// Report the long presses and the changes held back by debouncing that are due as of
+    /// `now`.
+    pub fn tick(&mut self, now: SystemTime, out: &mut Vec<ButtonEvent>) {
+        self.buttons.tick(now, out);
+    }
+
+    /// Update the buttons with an event, appending the resulting changes to `out`.
+    pub fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<ButtonEvent>) {
+        let time = self.clock.event_time(ev);
+        self.buttons.process_event(ev, time, out);
+    }
+
+    /// Wait up to `timeout`, or indefinitely if `None`, for changes and return them.
+    ///
+    /// Returns early, possibly without changes, when a long press or debounced change is
+    /// due.
+    pub fn fetch_events(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> io::Result<impl Iterator<Item = ButtonEvent>> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let until_deadline = self
+            .next_deadline()
+            .map(|deadline| self.clock.until(deadline));
+        let timeout = match (timeout, until_deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let millis = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        let mut fds = [PollFd::new(self.device.as_raw_fd(), PollFlags::POLLIN)];
+        poll(&mut fds, millis)?;
+
+        let mut out = Vec::new();
+        if fds[0].revents().is_some_and(|r| !r.is_empty()) {
+            let events: Vec<_> = self.device.fetch_events()?.collect();
+            for ev in &events {
+                self.process_event(ev, &mut out);
+            }
+        }
+        let now = self.clock.now();
+        self.tick(now, &mut out);
+        Ok(out.into_iter())
+    }
+}
+
+impl AsRawFd for GpioKeys {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debounce_and_long_press() {
+        let (ok, back) = (Key::KEY_ENTER, Key::KEY_ESC);
+        let mut buttons = Buttons::default();
+        buttons.button(ok);
+        buttons.button(back).long_press = Some(Duration::from_secs(1));
+        let at = |ms| SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+        let mut out = Vec::new();
+        let mut feed = |key: Key, value, ms| {
+            let mut ev = InputEvent::new(EventType::KEY, key.code(), value);
+            ev.0.time = crate::systime_to_timeval(&at(ms));
+            buttons.process_event(&ev, at(ms), &mut out);
+        };
+
+        // Bounce on press and release
+        feed(ok, 1, 0);
+        feed(ok, 0, 2);
+        feed(ok, 1, 5);
+        feed(ok, 0, 100);
+        feed(ok, 1, 110);
+        feed(ok, 0, 112);
+        // Held past the threshold
+        feed(back, 1, 1000);
+        feed(back, 0, 2500);
+        assert_eq!(
+            out,
+            [
+                ButtonEvent::Pressed { key: ok },
+                ButtonEvent::Released {
+                    key: ok,
+                    long_press: false
+                },
+                ButtonEvent::Pressed { key: back },
+                ButtonEvent::LongPress { key: back },
+                ButtonEvent::Released {
+                    key: back,
+                    long_press: true
+                },
+            ]
+        );
+        assert_eq!(buttons.next_deadline(), None);
+    }
+}
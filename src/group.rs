@@ -0,0 +1,224 @@
+//! Grabbing several devices as one.
+//!
+//! A remapper that handles a keyboard made of several devices, like a keyboard with a separate
+//! device for its media keys, wants all of them or none: grabbing only some leaves the others
+//! typing into the focused window. [`DeviceGroup::grab_all`] grabs every device of a group, or
+//! releases those it took and reports the device that couldn't be grabbed. The returned
+//! [`GroupGrab`] releases the group when dropped.
+//!
+//! ```no_run
+//! use evdev::group::DeviceGroup;
+//! use evdev::Error;
+//!
+//! let mut group = DeviceGroup::new();
+//! group.add(evdev::Device::open("/dev/input/event3")?);
+//! group.add(evdev::Device::open("/dev/input/event4")?);
+//! match group.grab_all() {
+//!     Ok(mut grab) => {
+//!         for device in grab.devices_mut() {
+//!             // ...
+//!         }
+//!     }
+//!     Err(e) => match Error::from(e) {
+//!         Error::GroupGrab { index, source } => println!("device {} is busy: {}", index, source),
+//!         other => println!("error: {}", other),
+//!     },
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+
+use crate::{Device, Error};
+
+/// What grabbing needs of a device, so the rollback can be tested without devices.
+trait Grab {
+    fn grab(&mut self) -> io::Result<()>;
+    fn ungrab(&mut self) -> io::Result<()>;
+    fn is_grabbed(&self) -> bool;
+}
+
+impl Grab for Device {
+    fn grab(&mut self) -> io::Result<()> {
+        Device::grab(self)
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        Device::ungrab(self)
+    }
+
+    fn is_grabbed(&self) -> bool {
+        Device::is_grabbed(self)
+    }
+}
+
+/// Grabs the devices not grabbed yet, in order, returning which ones it grabbed. On failure,
+/// releases those again.
+fn grab_all<D: Grab>(devices: &mut [D]) -> io::Result<Vec<bool>> {
+    let mut taken = vec![false; devices.len()];
+    for index in 0..devices.len() {
+        if devices[index].is_grabbed() {
+            continue;
+        }
+        if let Err(e) = devices[index].grab() {
+            // Best effort: a failed ungrab leaves nothing better to do
+            let _ = release(devices, &taken);
+            return Err(Error::GroupGrab {
+                index,
+                source: Box::new(Error::from(e)),
+            }
+            .into());
+        }
+        taken[index] = true;
+    }
+    Ok(taken)
+}
+
+/// Releases the devices marked in `taken`, returning the first error.
+fn release<D: Grab>(devices: &mut [D], taken: &[bool]) -> io::Result<()> {
+    let mut result = Ok(());
+    for (device, _) in devices.iter_mut().zip(taken).filter(|(_, &taken)| taken) {
+        let released = device.ungrab();
+        if result.is_ok() {
+            result = released;
+        }
+    }
+    result
+}
+
+/// Devices to be grabbed together. See the [module documentation](self).
+#[derive(Default)]
+pub struct DeviceGroup {
+    devices: Vec<Device>,
+}
+
+impl DeviceGroup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a device, returning its index in the group.
+    pub fn add(&mut self, device: Device) -> usize {
+        self.devices.push(device);
+        self.devices.len() - 1
+    }
+
+    pub fn devices(&self) -> &[Device] {
+        &self.devices
+    }
+
+    pub fn devices_mut(&mut self) -> &mut [Device] {
+        &mut self.devices
+    }
+
+    /// Consume the group, returning the devices.
+    pub fn into_inner(self) -> Vec<Device> {
+        self.devices
+    }
+
+    /// Grab every device, in order. If one can't be grabbed, the devices grabbed so far are
+    /// released, and this fails with [`Error::GroupGrab`], naming the device.
+    ///
+    /// Devices that were already grabbed stay grabbed either way, and aren't released by the
+    /// returned guard.
+    pub fn grab_all(&mut self) -> io::Result<GroupGrab<'_>> {
+        let taken = grab_all(&mut self.devices)?;
+        Ok(GroupGrab { group: self, taken })
+    }
+}
+
+impl FromIterator<Device> for DeviceGroup {
+    fn from_iter<I: IntoIterator<Item = Device>>(iter: I) -> Self {
+        DeviceGroup {
+            devices: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// The grabs taken by [`DeviceGroup::grab_all`], released when dropped.
+pub struct GroupGrab<'a> {
+    group: &'a mut DeviceGroup,
+    taken: Vec<bool>,
+}
+
+impl GroupGrab<'_> {
+    pub fn devices(&self) -> &[Device] {
+        &self.group.devices
+    }
+
+    pub fn devices_mut(&mut self) -> &mut [Device] {
+        &mut self.group.devices
+    }
+
+    /// Release the grabs now, returning the first error, which dropping the guard ignores.
+    /// Every device is released regardless.
+    pub fn release(mut self) -> io::Result<()> {
+        let taken = std::mem::take(&mut self.taken);
+        release(&mut self.group.devices, &taken)
+    }
+}
+
+impl Drop for GroupGrab<'_> {
+    fn drop(&mut self) {
+        let _ = release(&mut self.group.devices, &self.taken);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeDevice {
+        grabbed: bool,
+        busy: bool,
+    }
+
+    impl Grab for FakeDevice {
+        fn grab(&mut self) -> io::Result<()> {
+            if self.busy {
+                return Err(Error::DeviceBusy {
+                    holders: Vec::new(),
+                }
+                .into());
+            }
+            self.grabbed = true;
+            Ok(())
+        }
+
+        fn ungrab(&mut self) -> io::Result<()> {
+            self.grabbed = false;
+            Ok(())
+        }
+
+        fn is_grabbed(&self) -> bool {
+            self.grabbed
+        }
+    }
+
+    #[test]
+    fn roll_back_grabs() {
+        let mut devices: Vec<FakeDevice> = (0..4).map(|_| FakeDevice::default()).collect();
+        devices[1].grabbed = true;
+        devices[2].busy = true;
+        let err = grab_all(&mut devices).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ResourceBusy);
+        match Error::from(err) {
+            Error::GroupGrab { index, source } => {
+                assert_eq!(index, 2);
+                assert!(matches!(*source, Error::DeviceBusy { .. }));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        // The grab held before is kept
+        let grabbed: Vec<_> = devices.iter().map(|d| d.grabbed).collect();
+        assert_eq!(grabbed, [false, true, false, false]);
+
+        devices[2].busy = false;
+        let taken = grab_all(&mut devices).unwrap();
+        assert!(devices.iter().all(|d| d.grabbed));
+        release(&mut devices, &taken).unwrap();
+        let grabbed: Vec<_> = devices.iter().map(|d| d.grabbed).collect();
+        assert_eq!(grabbed, [false, true, false, false]);
+    }
+}
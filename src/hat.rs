@@ -0,0 +1,200 @@
+use crate::{AbsoluteAxisType, Device, EventType, InputEvent, Synchronization};
+
+/// The number of hat switches the kernel has axes for, `ABS_HAT0X` through `ABS_HAT3Y`.
+const HAT_COUNT: usize = 4;
+
+/// The position of an eight-way hat switch, such as a gamepad's d-pad.
+///
+/// The kernel reports a hat as a pair of axes, `ABS_HAT<n>X` and `ABS_HAT<n>Y`, each -1, 0 or 1,
+/// with negative Y pointing up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum HatDirection {
+    #[default]
+    Centered,
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl HatDirection {
+    /// Decode the values of a hat's X and Y axes. Only the signs are considered, so analog hats
+    /// work too.
+    pub fn from_axes(x: i32, y: i32) -> Self {
+        match (x.signum(), y.signum()) {
+            (0, -1) => HatDirection::Up,
+            (1, -1) => HatDirection::UpRight,
+            (1, 0) => HatDirection::Right,
+            (1, 1) => HatDirection::DownRight,
+            (0, 1) => HatDirection::Down,
+            (-1, 1) => HatDirection::DownLeft,
+            (-1, 0) => HatDirection::Left,
+            (-1, -1) => HatDirection::UpLeft,
+            _ => HatDirection::Centered,
+        }
+    }
+
+    /// Returns the values of the X and Y axes for this direction.
+    pub fn axes(self) -> (i32, i32) {
+        match self {
+            HatDirection::Centered => (0, 0),
+            HatDirection::Up => (0, -1),
+            HatDirection::UpRight => (1, -1),
+            HatDirection::Right => (1, 0),
+            HatDirection::DownRight => (1, 1),
+            HatDirection::Down => (0, 1),
+            HatDirection::DownLeft => (-1, 1),
+            HatDirection::Left => (-1, 0),
+            HatDirection::UpLeft => (-1, -1),
+        }
+    }
+
+    pub fn is_up(self) -> bool {
+        self.axes().1 < 0
+    }
+
+    pub fn is_down(self) -> bool {
+        self.axes().1 > 0
+    }
+
+    pub fn is_left(self) -> bool {
+        self.axes().0 < 0
+    }
+
+    pub fn is_right(self) -> bool {
+        self.axes().0 > 0
+    }
+
+    /// Read the current direction of hat `hat` (0 to 3) from `device`. Returns `None` if the
+    /// device doesn't have that hat.
+    pub fn from_device(device: &Device, hat: u8) -> Option<Self> {
+        let (x, y) = hat_axes(hat)?;
+        let x = device.abs_info(x)?.value();
+        let y = device.abs_info(y)?.value();
+        Some(Self::from_axes(x, y))
+    }
+
+    /// Returns the events setting hat `hat` (0 to 3) to this direction, to be emitted on a
+    /// virtual device followed by a `SYN_REPORT`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hat` is greater than 3.
+    pub fn to_events(self, hat: u8) -> [InputEvent; 2] {
+        let (x_axis, y_axis) = hat_axes(hat).expect("hat number out of range");
+        let (x, y) = self.axes();
+        [
+            InputEvent::new(EventType::ABSOLUTE, x_axis.0, x),
+            InputEvent::new(EventType::ABSOLUTE, y_axis.0, y),
+        ]
+    }
+}
+
+fn hat_axes(hat: u8) -> Option<(AbsoluteAxisType, AbsoluteAxisType)> {
+    if usize::from(hat) >= HAT_COUNT {
+        return None;
+    }
+    let x = AbsoluteAxisType::ABS_HAT0X.0 + u16::from(hat) * 2;
+    Some((AbsoluteAxisType(x), AbsoluteAxisType(x + 1)))
+}
+
+/// Tracks the direction of a device's hats from its events.
+///
+/// A hat moving diagonally, or from one direction to another, changes both of its axes in the
+/// same frame. Changes are reported once the frame is complete, so consumers don't see a
+/// transient direction in between.
+#[derive(Debug, Clone, Default)]
+pub struct HatTracker {
+    axes: [(i32, i32); HAT_COUNT],
+    reported: [HatDirection; HAT_COUNT],
+}
+
+impl HatTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from the current hat positions of `device`.
+    pub fn from_device(device: &Device) -> Self {
+        let mut tracker = Self::new();
+        for hat in 0..HAT_COUNT as u8 {
+            if let Some(direction) = HatDirection::from_device(device, hat) {
+                tracker.axes[usize::from(hat)] = direction.axes();
+                tracker.reported[usize::from(hat)] = direction;
+            }
+        }
+        tracker
+    }
+
+    /// Returns the direction of hat `hat` as of the last complete frame.
+    pub fn direction(&self, hat: u8) -> HatDirection {
+        self.reported
+            .get(usize::from(hat))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Update the tracker with an event. At the end of a frame, the hats whose direction
+    /// changed are pushed to `out` with their new direction.
+    pub fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<(u8, HatDirection)>) {
+        match ev.event_type() {
+            EventType::ABSOLUTE => {
+                let hat0 = AbsoluteAxisType::ABS_HAT0X.0;
+                let Some(offset) = ev.code().checked_sub(hat0) else {
+                    return;
+                };
+                let Some(axes) = self.axes.get_mut(usize::from(offset / 2)) else {
+                    return;
+                };
+                if offset % 2 == 0 {
+                    axes.0 = ev.value();
+                } else {
+                    axes.1 = ev.value();
+                }
+            }
+            EventType::SYNCHRONIZATION if ev.code() == Synchronization::SYN_REPORT.0 => {
+                for (hat, (&(x, y), reported)) in
+                    self.axes.iter().zip(&mut self.reported).enumerate()
+                {
+                    let direction = HatDirection::from_axes(x, y);
+                    if direction != *reported {
+                        *reported = direction;
+                        out.push((hat as u8, direction));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_hat() {
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        let mut tracker = HatTracker::new();
+        let mut changes = Vec::new();
+
+        for ev in HatDirection::UpRight.to_events(1).iter().chain([&syn]) {
+            tracker.process_event(ev, &mut changes);
+        }
+        assert_eq!(changes, [(1, HatDirection::UpRight)]);
+        assert!(tracker.direction(1).is_up() && tracker.direction(1).is_right());
+
+        // Moving straight from up-right to down-left is reported once
+        changes.clear();
+        for ev in HatDirection::DownLeft.to_events(1).iter().chain([&syn]) {
+            tracker.process_event(ev, &mut changes);
+        }
+        assert_eq!(changes, [(1, HatDirection::DownLeft)]);
+        assert_eq!(HatDirection::from_axes(0, 0), HatDirection::Centered);
+        assert_eq!(tracker.direction(0), HatDirection::Centered);
+    }
+}
@@ -0,0 +1,317 @@
+//! Mapping between HID usages and evdev event codes.
+//!
+//! These are the mappings the kernel's generic `hid-input` driver applies when it creates
+//! evdev devices for HID hardware, so a usage converted here ends up as the same code the
+//! kernel would report. Vendor-specific quirks applied by dedicated HID drivers are not
+//! covered.
+//!
+//! ```
+//! use evdev::hid::{usage_pages, Application, Usage};
+//! use evdev::{InputEventKind, Key};
+//!
+//! let usage = Usage::new(usage_pages::KEYBOARD, 0x04);
+//! let kind = usage.to_evdev(false, Application::Other);
+//! assert_eq!(kind, Some(InputEventKind::Key(Key::KEY_A)));
+//! assert_eq!(Usage::from_evdev(kind.unwrap()), Some(usage));
+//! ```
+
+use crate::{AbsoluteAxisType, InputEventKind, Key, LedType, RelativeAxisType};
+
+/// Usage page ids from the HID Usage Tables.
+pub mod usage_pages {
+    pub const GENERIC_DESKTOP: u16 = 0x01;
+    pub const KEYBOARD: u16 = 0x07;
+    pub const LED: u16 = 0x08;
+    pub const BUTTON: u16 = 0x09;
+    pub const CONSUMER: u16 = 0x0c;
+    pub const DIGITIZER: u16 = 0x0d;
+}
+
+use usage_pages::*;
+
+/// A HID usage: a usage page and a usage id within it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Usage {
+    pub page: u16,
+    pub id: u16,
+}
+
+/// The kind of application collection a field belongs to, which decides where buttons are
+/// mapped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Application {
+    /// A mouse or other pointer; buttons map from `BTN_LEFT`.
+    Mouse,
+    /// Buttons map from `BTN_TRIGGER`, and past 16 to `BTN_TRIGGER_HAPPY1`.
+    Joystick,
+    /// Buttons map from `BTN_SOUTH`, and past 16 to `BTN_TRIGGER_HAPPY1`.
+    Gamepad,
+    /// Buttons map from `BTN_0`.
+    Other,
+}
+
+/// Keyboard page usages, indexed by usage id; 0 where the kernel reports nothing or
+/// `KEY_UNKNOWN`. This is `hid_keyboard` from `drivers/hid/hid-input.c`.
+#[rustfmt::skip]
+const KEYBOARD_TABLE: [u8; 256] = [
+      0,  0,  0,  0, 30, 48, 46, 32, 18, 33, 34, 35, 23, 36, 37, 38,
+     50, 49, 24, 25, 16, 19, 31, 20, 22, 47, 17, 45, 21, 44,  2,  3,
+      4,  5,  6,  7,  8,  9, 10, 11, 28,  1, 14, 15, 57, 12, 13, 26,
+     27, 43, 43, 39, 40, 41, 51, 52, 53, 58, 59, 60, 61, 62, 63, 64,
+     65, 66, 67, 68, 87, 88, 99, 70,119,110,102,104,111,107,109,106,
+    105,108,103, 69, 98, 55, 74, 78, 96, 79, 80, 81, 75, 76, 77, 71,
+     72, 73, 82, 83, 86,127,116,117,183,184,185,186,187,188,189,190,
+    191,192,193,194,134,138,130,132,128,129,131,137,133,135,136,113,
+    115,114,  0,  0,  0,121,  0, 89, 93,124, 92, 94, 95,  0,  0,  0,
+    122,123, 90, 91, 85,  0,  0,  0,  0,  0,  0,  0,111,  0,  0,  0,
+      0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
+      0,  0,  0,  0,  0,  0,179,180,  0,  0,  0,  0,  0,  0,  0,  0,
+      0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
+      0,  0,  0,  0,  0,  0,  0,  0,111,  0,  0,  0,  0,  0,  0,  0,
+     29, 42, 56,125, 97, 54,100,126,164,166,165,163,161,115,114,113,
+    150,158,159,128,136,177,178,176,142,152,173,140,  0,  0,  0,  0,
+];
+
+/// Consumer page usages that map to keys.
+const CONSUMER_KEYS: &[(u16, Key)] = &[
+    (0x030, Key::KEY_POWER),
+    (0x032, Key::KEY_SLEEP),
+    (0x040, Key::KEY_MENU),
+    (0x06f, Key::KEY_BRIGHTNESSUP),
+    (0x070, Key::KEY_BRIGHTNESSDOWN),
+    (0x0b0, Key::KEY_PLAY),
+    (0x0b1, Key::KEY_PAUSE),
+    (0x0b2, Key::KEY_RECORD),
+    (0x0b3, Key::KEY_FASTFORWARD),
+    (0x0b4, Key::KEY_REWIND),
+    (0x0b5, Key::KEY_NEXTSONG),
+    (0x0b6, Key::KEY_PREVIOUSSONG),
+    (0x0b7, Key::KEY_STOPCD),
+    (0x0b8, Key::KEY_EJECTCD),
+    (0x0cd, Key::KEY_PLAYPAUSE),
+    (0x0e2, Key::KEY_MUTE),
+    (0x0e9, Key::KEY_VOLUMEUP),
+    (0x0ea, Key::KEY_VOLUMEDOWN),
+    (0x183, Key::KEY_CONFIG),
+    (0x18a, Key::KEY_MAIL),
+    (0x192, Key::KEY_CALC),
+    (0x194, Key::KEY_FILE),
+    (0x19c, Key::KEY_LOGOFF),
+    (0x1ae, Key::KEY_KEYBOARD),
+    (0x201, Key::KEY_NEW),
+    (0x202, Key::KEY_OPEN),
+    (0x203, Key::KEY_CLOSE),
+    (0x207, Key::KEY_SAVE),
+    (0x208, Key::KEY_PRINT),
+    (0x21a, Key::KEY_UNDO),
+    (0x21b, Key::KEY_COPY),
+    (0x21c, Key::KEY_CUT),
+    (0x21d, Key::KEY_PASTE),
+    (0x221, Key::KEY_SEARCH),
+    (0x223, Key::KEY_HOMEPAGE),
+    (0x224, Key::KEY_BACK),
+    (0x225, Key::KEY_FORWARD),
+    (0x226, Key::KEY_STOP),
+    (0x227, Key::KEY_REFRESH),
+    (0x22a, Key::KEY_BOOKMARKS),
+    (0x22d, Key::KEY_ZOOMIN),
+    (0x22e, Key::KEY_ZOOMOUT),
+];
+
+/// Generic desktop usages that map to keys.
+const DESKTOP_KEYS: &[(u16, Key)] = &[
+    (0x81, Key::KEY_POWER),
+    (0x82, Key::KEY_SLEEP),
+    (0x83, Key::KEY_WAKEUP),
+    (0x90, Key::BTN_DPAD_UP),
+    (0x91, Key::BTN_DPAD_DOWN),
+    (0x92, Key::BTN_DPAD_RIGHT),
+    (0x93, Key::BTN_DPAD_LEFT),
+];
+
+/// Digitizer usages, which are the same for every field.
+const DIGITIZER_USAGES: &[(u16, InputEventKind)] = &[
+    (
+        0x30,
+        InputEventKind::AbsAxis(AbsoluteAxisType::ABS_PRESSURE),
+    ),
+    (0x32, InputEventKind::Key(Key::BTN_TOOL_PEN)),
+    (0x3c, InputEventKind::Key(Key::BTN_TOOL_RUBBER)),
+    (0x42, InputEventKind::Key(Key::BTN_TOUCH)),
+    (0x44, InputEventKind::Key(Key::BTN_STYLUS)),
+    (
+        0x48,
+        InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_TOUCH_MAJOR),
+    ),
+    (
+        0x49,
+        InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_TOUCH_MINOR),
+    ),
+    (
+        0x51,
+        InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_TRACKING_ID),
+    ),
+    (0x5a, InputEventKind::Key(Key::BTN_STYLUS2)),
+];
+
+/// AC Pan, the horizontal scroll usage on the consumer page.
+const AC_PAN: u16 = 0x238;
+/// The first button past the 16 a joystick or gamepad range holds.
+const BTN_TRIGGER_HAPPY1: u16 = 0x2c0;
+
+fn lookup<T: Copy>(table: &[(u16, T)], id: u16) -> Option<T> {
+    table
+        .iter()
+        .find(|&&(usage, _)| usage == id)
+        .map(|&(_, v)| v)
+}
+
+fn reverse<T: Copy + PartialEq>(table: &[(u16, T)], value: T) -> Option<u16> {
+    table
+        .iter()
+        .find(|&&(_, v)| v == value)
+        .map(|&(usage, _)| usage)
+}
+
+impl Usage {
+    pub const fn new(page: u16, id: u16) -> Self {
+        Usage { page, id }
+    }
+
+    /// Returns the evdev event the kernel reports for this usage.
+    ///
+    /// `relative` is whether the HID field is relative, which turns axes into relative axes.
+    /// `application` is the kind of collection the field is in, which decides the button
+    /// range.
+    pub fn to_evdev(self, relative: bool, application: Application) -> Option<InputEventKind> {
+        let key = |code: u16| Some(InputEventKind::Key(Key::new(code)));
+        match self.page {
+            KEYBOARD => match KEYBOARD_TABLE.get(self.id as usize) {
+                Some(&code) if code != 0 => key(code.into()),
+                _ => None,
+            },
+            BUTTON if self.id > 0 => {
+                let n = self.id - 1;
+                match application {
+                    Application::Mouse if n <= 0xf => key(Key::BTN_LEFT.0 + n),
+                    Application::Joystick if n <= 0xf => key(Key::BTN_TRIGGER.0 + n),
+                    Application::Gamepad if n <= 0xf => key(Key::BTN_SOUTH.0 + n),
+                    Application::Joystick | Application::Gamepad if n < 0x10 + 40 => {
+                        key(BTN_TRIGGER_HAPPY1 + n - 0x10)
+                    }
+                    Application::Other if n <= 0xf => key(Key::BTN_0.0 + n),
+                    _ => None,
+                }
+            }
+            GENERIC_DESKTOP => match self.id {
+                0x30..=0x38 if relative => {
+                    Some(InputEventKind::RelAxis(RelativeAxisType(self.id - 0x30)))
+                }
+                0x30..=0x38 => Some(InputEventKind::AbsAxis(AbsoluteAxisType(self.id - 0x30))),
+                0x39 => Some(InputEventKind::AbsAxis(AbsoluteAxisType::ABS_HAT0X)),
+                id => lookup(DESKTOP_KEYS, id).map(InputEventKind::Key),
+            },
+            LED => match self.id {
+                0x01..=0x05 => Some(InputEventKind::Led(LedType(self.id - 1))),
+                _ => None,
+            },
+            CONSUMER if self.id == AC_PAN && relative => {
+                Some(InputEventKind::RelAxis(RelativeAxisType::REL_HWHEEL))
+            }
+            CONSUMER => lookup(CONSUMER_KEYS, self.id).map(InputEventKind::Key),
+            DIGITIZER => lookup(DIGITIZER_USAGES, self.id),
+            _ => None,
+        }
+    }
+
+    /// Returns the HID usage that produces the given evdev event, if there is one.
+    ///
+    /// Where several usages map to the same code, the most common one is returned; media keys
+    /// map to the consumer page rather than the keyboard page. A hat switch reports both
+    /// `ABS_HAT0X` and `ABS_HAT0Y`, so both map back to it.
+    pub fn from_evdev(kind: InputEventKind) -> Option<Usage> {
+        let usage = match kind {
+            InputEventKind::Key(key) => {
+                let code = key.code();
+                if let Some(id) = reverse(CONSUMER_KEYS, key) {
+                    Usage::new(CONSUMER, id)
+                } else if let Some(id) = KEYBOARD_TABLE
+                    .iter()
+                    .position(|&c| c != 0 && u16::from(c) == code)
+                {
+                    Usage::new(KEYBOARD, id as u16)
+                } else if let Some(id) = reverse(DESKTOP_KEYS, key) {
+                    Usage::new(GENERIC_DESKTOP, id)
+                } else if let Some(id) = reverse(DIGITIZER_USAGES, kind) {
+                    Usage::new(DIGITIZER, id)
+                } else {
+                    let first = match code {
+                        0x100..=0x10f => Key::BTN_0.0,
+                        0x110..=0x11f => Key::BTN_LEFT.0,
+                        0x120..=0x12f => Key::BTN_TRIGGER.0,
+                        0x130..=0x13f => Key::BTN_SOUTH.0,
+                        0x2c0..=0x2e7 => BTN_TRIGGER_HAPPY1 - 0x10,
+                        _ => return None,
+                    };
+                    Usage::new(BUTTON, code - first + 1)
+                }
+            }
+            InputEventKind::RelAxis(RelativeAxisType::REL_HWHEEL) => Usage::new(CONSUMER, AC_PAN),
+            InputEventKind::RelAxis(axis) if axis.0 <= 8 => {
+                Usage::new(GENERIC_DESKTOP, 0x30 + axis.0)
+            }
+            InputEventKind::AbsAxis(axis) if axis.0 <= 8 => {
+                Usage::new(GENERIC_DESKTOP, 0x30 + axis.0)
+            }
+            InputEventKind::AbsAxis(AbsoluteAxisType::ABS_HAT0X | AbsoluteAxisType::ABS_HAT0Y) => {
+                Usage::new(GENERIC_DESKTOP, 0x39)
+            }
+            InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_POSITION_X) => {
+                Usage::new(GENERIC_DESKTOP, 0x30)
+            }
+            InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_POSITION_Y) => {
+                Usage::new(GENERIC_DESKTOP, 0x31)
+            }
+            InputEventKind::AbsAxis(_) => Usage::new(DIGITIZER, reverse(DIGITIZER_USAGES, kind)?),
+            InputEventKind::Led(led) if led.0 <= 4 => Usage::new(LED, led.0 + 1),
+            _ => return None,
+        };
+        Some(usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let cases = [
+            (Usage::new(KEYBOARD, 0x29), false, Application::Other),
+            (Usage::new(KEYBOARD, 0xe3), false, Application::Other),
+            (Usage::new(BUTTON, 2), false, Application::Mouse),
+            (Usage::new(BUTTON, 20), false, Application::Gamepad),
+            (Usage::new(GENERIC_DESKTOP, 0x38), true, Application::Mouse),
+            (
+                Usage::new(GENERIC_DESKTOP, 0x35),
+                false,
+                Application::Joystick,
+            ),
+            (Usage::new(CONSUMER, 0xe9), false, Application::Other),
+            (Usage::new(CONSUMER, AC_PAN), true, Application::Mouse),
+            (Usage::new(LED, 0x02), false, Application::Other),
+        ];
+        for (usage, relative, application) in cases {
+            let kind = usage.to_evdev(relative, application).unwrap();
+            assert_eq!(Usage::from_evdev(kind), Some(usage), "{:?}", kind);
+        }
+        assert_eq!(
+            Usage::new(KEYBOARD, 0x29).to_evdev(false, Application::Other),
+            Some(InputEventKind::Key(Key::KEY_ESC))
+        );
+        assert_eq!(
+            Usage::new(BUTTON, 1).to_evdev(false, Application::Mouse),
+            Some(InputEventKind::Key(Key::BTN_LEFT))
+        );
+    }
+}
@@ -0,0 +1,425 @@
+//! System-wide keyboard shortcuts.
+//!
+//! A [`HotkeyManager`] watches every keyboard for registered combinations like Ctrl+Alt+F7 and
+//! calls a callback when one is pressed. Modifiers are tracked per keyboard, so Ctrl held on
+//! one keyboard and F7 pressed on another is not Ctrl+F7. With [`hotplug`] enabled, keyboards
+//! plugged in later are watched too.
+//!
+//! By default the keyboards are only read, so the shortcuts also reach whoever else handles
+//! them. With [`grab`](HotkeyManager::grab) the manager grabs the keyboards instead and keeps
+//! the keys that trigger a shortcut to itself; everything else is returned by
+//! [`fetch_events`](HotkeyManager::fetch_events), to be passed on, e.g. through a
+//! [`VirtualDevice`](crate::uinput::VirtualDevice).
+//!
+//! ```no_run
+//! use evdev::hotkey::HotkeyManager;
+//!
+//! let mut hotkeys = HotkeyManager::new().with_hotplug()?;
+//! hotkeys.register("Ctrl+Alt+F7".parse()?, || println!("switching to the desktop"));
+//! hotkeys.register("Super+L".parse()?, || println!("locking the screen"));
+//! hotkeys.run()?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! [`hotplug`]: HotkeyManager::with_hotplug
+
+use std::fmt;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::hotplug::{HotplugEvent, HotplugMonitor};
+use crate::modifiers::ModifierTracker;
+pub use crate::modifiers::Modifiers;
+use crate::transform::is_syn_report;
+use crate::{AttributeSet, Device, DeviceClass, EventType, InputEvent, Key};
+
+/// A key combination: a key pressed while exactly the given modifiers are held.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl Hotkey {
+    pub fn new(modifiers: Modifiers, key: Key) -> Self {
+        Hotkey { modifiers, key }
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = io::Error;
+
+    /// Parses combinations like `Ctrl+Alt+F7` or `Super+KEY_L`: modifiers (`Shift`, `Ctrl`,
+    /// `Alt`, and `Super` or `Meta`) followed by a key, by its name with or without the `KEY_`
+    /// prefix, case-insensitively.
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid hotkey: {}", s),
+            )
+        };
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = parts.pop().filter(|k| !k.is_empty()).ok_or_else(invalid)?;
+        let mut modifiers = Modifiers::NONE;
+        for part in parts {
+            modifiers = modifiers
+                | match part.to_ascii_lowercase().as_str() {
+                    "shift" => Modifiers::SHIFT,
+                    "ctrl" | "control" => Modifiers::CTRL,
+                    "alt" => Modifiers::ALT,
+                    "super" | "meta" | "logo" => Modifiers::META,
+                    _ => return Err(invalid()),
+                };
+        }
+        let key = key.to_ascii_uppercase();
+        let key = match key.starts_with("KEY_") || key.starts_with("BTN_") {
+            true => key.parse(),
+            false => format!("KEY_{}", key).parse(),
+        };
+        Ok(Hotkey::new(modifiers, key.map_err(|_| invalid())?))
+    }
+}
+
+impl fmt::Display for Hotkey {
+    /// Formats the combination the way [`from_str`](Self::from_str) parses it, like
+    /// `Ctrl+Shift+P`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (modifier, name) in [
+            (Modifiers::CTRL, "Ctrl"),
+            (Modifiers::ALT, "Alt"),
+            (Modifiers::SHIFT, "Shift"),
+            (Modifiers::META, "Super"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        let name = format!("{:?}", self.key);
+        match name.strip_prefix("KEY_") {
+            // `Ctrl+Enter` rather than `Ctrl+ENTER`
+            Some(name) => {
+                let (first, rest) = name.split_at(1);
+                write!(f, "{}{}", first, rest.to_ascii_lowercase())
+            }
+            None => f.write_str(&name),
+        }
+    }
+}
+
+/// Identifies a registered hotkey, for [`HotkeyManager::unregister`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HotkeyId(u64);
+
+struct Registration {
+    id: HotkeyId,
+    hotkey: Hotkey,
+    callback: Box<dyn FnMut() + Send>,
+}
+
+/// The state of one keyboard.
+#[derive(Debug, Default)]
+struct Matcher {
+    modifiers: ModifierTracker,
+    /// Keys whose press triggered a hotkey, and whose repeats and release are swallowed.
+    swallowed: AttributeSet<Key>,
+    frame: Vec<InputEvent>,
+    /// Events were swallowed from the current frame.
+    trimmed: bool,
+}
+
+impl Matcher {
+    /// Process an event, appending the events to pass on to `out` a frame at a time. Returns
+    /// the index of the hotkey in `hotkeys` the event triggers, if any.
+    fn process_event(
+        &mut self,
+        ev: &InputEvent,
+        hotkeys: &[Hotkey],
+        swallow: bool,
+        out: &mut Vec<InputEvent>,
+    ) -> Option<usize> {
+        let mut triggered = None;
+        let mut keep = true;
+        if ev.event_type() == EventType::KEY {
+            let key = Key::new(ev.code());
+            if Modifiers::from_key(key).is_some() {
+                self.modifiers.process_event(0, ev);
+            } else if ev.value() == 1 {
+                let modifiers = self.modifiers.held();
+                triggered = hotkeys
+                    .iter()
+                    .position(|h| h.key == key && h.modifiers == modifiers);
+                if triggered.is_some() && swallow {
+                    self.swallowed.insert(key);
+                }
+            }
+            if self.swallowed.contains(key) {
+                keep = false;
+                if ev.value() == 0 {
+                    self.swallowed.remove(key);
+                }
+            }
+        }
+
+        if keep {
+            self.frame.push(*ev);
+        } else {
+            self.trimmed = true;
+        }
+        if is_syn_report(ev) {
+            // Don't pass on frames that held nothing but the swallowed keys
+            if !(self.trimmed && self.frame.len() == 1) {
+                out.append(&mut self.frame);
+            }
+            self.frame.clear();
+            self.trimmed = false;
+        }
+        triggered
+    }
+}
+
+struct Keyboard {
+    path: Option<PathBuf>,
+    device: Device,
+    matcher: Matcher,
+    /// Unplugged, to be dropped.
+    gone: bool,
+}
+
+fn is_gone(e: &io::Error) -> bool {
+    crate::Error::raw_os_error_of(e) == Some(libc::ENODEV)
+}
+
+/// Calls callbacks for key combinations pressed on any keyboard. See the
+/// [module documentation](self).
+pub struct HotkeyManager {
+    hotkeys: Vec<Registration>,
+    next_id: u64,
+    keyboards: Vec<Keyboard>,
+    hotplug: Option<HotplugMonitor>,
+    grab: bool,
+}
+
+impl Default for HotkeyManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HotkeyManager {
+    /// Create a manager without keyboards or hotkeys.
+    pub fn new() -> Self {
+        HotkeyManager {
+            hotkeys: Vec::new(),
+            next_id: 0,
+            keyboards: Vec::new(),
+            hotplug: None,
+            grab: false,
+        }
+    }
+
+    /// Grab the keyboards, and swallow the presses, repeats and releases of keys that trigger
+    /// a hotkey. Applies to keyboards added from now on.
+    pub fn grab(mut self, grab: bool) -> Self {
+        self.grab = grab;
+        self
+    }
+
+    /// Also watch every keyboard present in `/dev/input`, and those plugged in later.
+    pub fn with_hotplug(mut self) -> io::Result<Self> {
+        self.hotplug = Some(HotplugMonitor::new()?);
+        self.handle_hotplug()?;
+        Ok(self)
+    }
+
+    /// Call `callback` whenever `hotkey` is pressed. Key repeats don't call it again.
+    pub fn register(
+        &mut self,
+        hotkey: Hotkey,
+        callback: impl FnMut() + Send + 'static,
+    ) -> HotkeyId {
+        let id = HotkeyId(self.next_id);
+        self.next_id += 1;
+        self.hotkeys.push(Registration {
+            id,
+            hotkey,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// Remove a hotkey. Returns `false` if it was already removed.
+    pub fn unregister(&mut self, id: HotkeyId) -> bool {
+        let len = self.hotkeys.len();
+        self.hotkeys.retain(|r| r.id != id);
+        self.hotkeys.len() != len
+    }
+
+    /// Returns the registered hotkeys.
+    pub fn hotkeys(&self) -> impl Iterator<Item = (HotkeyId, Hotkey)> + '_ {
+        self.hotkeys.iter().map(|r| (r.id, r.hotkey))
+    }
+
+    /// Returns the keyboards being watched.
+    pub fn devices(&self) -> impl Iterator<Item = &Device> {
+        self.keyboards.iter().map(|k| &k.device)
+    }
+
+    /// Watch `device`, grabbing it if [`grab`](Self::grab) is set.
+    pub fn add(&mut self, device: Device) -> io::Result<()> {
+        self.add_keyboard(None, device)
+    }
+
+    fn add_keyboard(&mut self, path: Option<PathBuf>, mut device: Device) -> io::Result<()> {
+        if self.grab {
+            device.grab()?;
+        }
+        self.keyboards.push(Keyboard {
+            path,
+            device,
+            matcher: Matcher::default(),
+            gone: false,
+        });
+        Ok(())
+    }
+
+    fn handle_hotplug(&mut self) -> io::Result<()> {
+        let Some(hotplug) = &mut self.hotplug else {
+            return Ok(());
+        };
+        for event in hotplug.fetch_events(Some(Duration::ZERO))? {
+            match event {
+                HotplugEvent::Added { path, device } => {
+                    let classes = device.classes();
+                    if classes.contains(&DeviceClass::Keyboard)
+                        || classes.contains(&DeviceClass::Key)
+                    {
+                        self.add_keyboard(Some(path), *device)?;
+                    }
+                }
+                HotplugEvent::Removed { path } => {
+                    self.keyboards.retain(|k| k.path.as_ref() != Some(&path));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait up to `timeout`, or indefinitely if `None`, for input, and call the callbacks of
+    /// the hotkeys pressed.
+    ///
+    /// Returns the events read from the keyboards, a frame at a time, without those swallowed
+    /// under [`grab`](Self::grab). Keyboards that were unplugged are dropped.
+    pub fn fetch_events(&mut self, timeout: Option<Duration>) -> io::Result<Vec<InputEvent>> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let mut fds: Vec<_> = self
+            .keyboards
+            .iter()
+            .map(|k| PollFd::new(k.device.as_raw_fd(), PollFlags::POLLIN))
+            .chain(
+                self.hotplug
+                    .iter()
+                    .map(|h| PollFd::new(h.as_raw_fd(), PollFlags::POLLIN)),
+            )
+            .collect();
+        let millis = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        poll(&mut fds, millis)?;
+        let ready = |fd: &PollFd| fd.revents().is_some_and(|r| !r.is_empty());
+        let ready: Vec<bool> = fds.iter().map(ready).collect();
+
+        let hotkeys: Vec<Hotkey> = self.hotkeys.iter().map(|r| r.hotkey).collect();
+        let mut out = Vec::new();
+        let mut triggered = Vec::new();
+        for (i, keyboard) in self.keyboards.iter_mut().enumerate() {
+            if !ready[i] {
+                continue;
+            }
+            match keyboard.device.fetch_events() {
+                Ok(events) => {
+                    for ev in events {
+                        let matcher = &mut keyboard.matcher;
+                        triggered.extend(matcher.process_event(&ev, &hotkeys, self.grab, &mut out));
+                    }
+                }
+                Err(e) if is_gone(&e) => keyboard.gone = true,
+                Err(e) => return Err(e),
+            }
+        }
+        self.keyboards.retain(|k| !k.gone);
+        for i in triggered {
+            (self.hotkeys[i].callback)();
+        }
+        if self.hotplug.is_some() && ready.last() == Some(&true) {
+            self.handle_hotplug()?;
+        }
+        Ok(out)
+    }
+
+    /// Watch for hotkeys until an error occurs, discarding the other input.
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.fetch_events(None)?;
+        }
+    }
+}
+
+impl fmt::Debug for HotkeyManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HotkeyManager")
+            .field("hotkeys", &self.hotkeys().collect::<Vec<_>>())
+            .field("keyboards", &self.keyboards.len())
+            .field("hotplug", &self.hotplug.is_some())
+            .field("grab", &self.grab)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_and_swallow() {
+        let hotkeys = ["Ctrl+Alt+F7".parse::<Hotkey>().unwrap()];
+        assert_eq!(
+            hotkeys[0],
+            Hotkey::new(Modifiers::CTRL | Modifiers::ALT, Key::KEY_F7)
+        );
+        let frame = |key: Key, value| {
+            [
+                InputEvent::new(EventType::KEY, key.code(), value),
+                InputEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+            ]
+        };
+        let mut matcher = Matcher::default();
+        let mut out = Vec::new();
+        let feed = |matcher: &mut Matcher, frames: &[[InputEvent; 2]], out: &mut Vec<_>| {
+            frames
+                .iter()
+                .flatten()
+                .filter_map(|ev| matcher.process_event(ev, &hotkeys, true, out))
+                .count()
+        };
+
+        // F7 with only Ctrl held is passed on
+        let ctrl = [frame(Key::KEY_LEFTCTRL, 1), frame(Key::KEY_F7, 1)];
+        assert_eq!(feed(&mut matcher, &ctrl, &mut out), 0);
+        assert_eq!(out.len(), 4);
+
+        out.clear();
+        let frames = [
+            frame(Key::KEY_F7, 0),
+            frame(Key::KEY_RIGHTALT, 1),
+            frame(Key::KEY_F7, 1),
+            frame(Key::KEY_F7, 2),
+            frame(Key::KEY_F7, 0),
+        ];
+        assert_eq!(feed(&mut matcher, &frames, &mut out), 1);
+        // The release of the F7 pressed before, and Alt; the hotkey's F7 is swallowed
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[2].code(), Key::KEY_RIGHTALT.code());
+    }
+}
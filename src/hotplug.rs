@@ -0,0 +1,192 @@
+//! Noticing input devices as they come and go, without udev.
+//!
+//! [`HotplugMonitor`] watches `/dev/input` with inotify. Device nodes appear there as soon as
+//! the kernel registers a device, but udev may still be setting their permissions, so a node
+//! that can't be opened yet is retried when its attributes change. Nothing beyond the kernel
+//! interfaces is needed, so this works the same in static builds, containers and initramfs
+//! environments without udev; combined with [`Device::classes`], it covers what most programs
+//! use libudev for.
+//!
+//! ```no_run
+//! use evdev::hotplug::{HotplugEvent, HotplugMonitor};
+//!
+//! let mut monitor = HotplugMonitor::new()?;
+//! loop {
+//!     for event in monitor.fetch_events(None)? {
+//!         match event {
+//!             HotplugEvent::Added { path, device } => {
+//!                 println!("{}: {:?}", path.display(), device.classes())
+//!             }
+//!             HotplugEvent::Removed { path } => println!("{} removed", path.display()),
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+use crate::Device;
+
+/// A device that appeared or disappeared.
+pub enum HotplugEvent {
+    /// A device was added, and opened. Boxed, as a `Device` is large.
+    Added {
+        path: PathBuf,
+        device: Box<Device>,
+    },
+    Removed {
+        path: PathBuf,
+    },
+}
+
+impl fmt::Debug for HotplugEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HotplugEvent::Added { path, device } => f
+                .debug_struct("Added")
+                .field("path", path)
+                .field("name", &device.name())
+                .finish(),
+            HotplugEvent::Removed { path } => {
+                f.debug_struct("Removed").field("path", path).finish()
+            }
+        }
+    }
+}
+
+fn is_event_node(name: &std::ffi::OsStr) -> bool {
+    name.as_bytes().starts_with(b"event")
+}
+
+/// Watches a directory of device nodes. See the [module documentation](self).
+#[derive(Debug)]
+pub struct HotplugMonitor {
+    inotify: Inotify,
+    dir: PathBuf,
+    /// Nodes reported as added.
+    known: BTreeSet<PathBuf>,
+    /// Nodes not yet reported, starting with those present when the monitor was created.
+    pending: BTreeSet<PathBuf>,
+}
+
+impl HotplugMonitor {
+    /// Watch `/dev/input`. The devices already present are reported as added by the first
+    /// call to [`fetch_events`](Self::fetch_events).
+    pub fn new() -> io::Result<Self> {
+        Self::with_dir("/dev/input")
+    }
+
+    /// Watch another directory of device nodes, e.g. one bind-mounted into a container.
+    pub fn with_dir(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_owned();
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        // Owned right away, so the descriptor is closed if anything below fails
+        let mut monitor = HotplugMonitor {
+            inotify,
+            dir,
+            known: BTreeSet::new(),
+            pending: BTreeSet::new(),
+        };
+        let flags = AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_ATTRIB
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_MOVED_FROM;
+        monitor.inotify.add_watch(&monitor.dir, flags)?;
+        // Watch before scanning, so devices added in between aren't missed
+        for entry in std::fs::read_dir(&monitor.dir)? {
+            let entry = entry?;
+            if is_event_node(&entry.file_name()) {
+                monitor.pending.insert(entry.path());
+            }
+        }
+        Ok(monitor)
+    }
+
+    /// Returns the paths of the devices reported as added and not removed since.
+    pub fn devices(&self) -> impl Iterator<Item = &Path> {
+        self.known.iter().map(PathBuf::as_path)
+    }
+
+    /// Try to open the pending nodes, returning those that opened. Nodes that can't be opened
+    /// for lack of permissions stay pending, the others are dropped.
+    fn open_pending(&mut self, out: &mut Vec<HotplugEvent>) {
+        let pending = std::mem::take(&mut self.pending);
+        for path in pending {
+            match Device::open(&path) {
+                Ok(device) => {
+                    self.known.insert(path.clone());
+                    out.push(HotplugEvent::Added {
+                        path,
+                        device: Box::new(device),
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    self.pending.insert(path);
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// Wait up to `timeout`, or indefinitely if `None`, for devices to be added or removed.
+    pub fn fetch_events(&mut self, timeout: Option<Duration>) -> io::Result<Vec<HotplugEvent>> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let mut out = Vec::new();
+        if self.known.is_empty() && !self.pending.is_empty() {
+            self.open_pending(&mut out);
+            if !out.is_empty() {
+                return Ok(out);
+            }
+        }
+        let millis = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        poll(
+            &mut [PollFd::new(self.as_raw_fd(), PollFlags::POLLIN)],
+            millis,
+        )?;
+        let events = match self.inotify.read_events() {
+            Ok(events) => events,
+            Err(Errno::EAGAIN) => return Ok(out),
+            Err(e) => return Err(e.into()),
+        };
+        for event in events {
+            let Some(name) = event.name.filter(|name| is_event_node(name)) else {
+                continue;
+            };
+            let path = self.dir.join(name);
+            let removed = AddWatchFlags::IN_DELETE | AddWatchFlags::IN_MOVED_FROM;
+            if event.mask.intersects(removed) {
+                self.pending.remove(&path);
+                if self.known.remove(&path) {
+                    out.push(HotplugEvent::Removed { path });
+                }
+            } else if !self.known.contains(&path) {
+                self.pending.insert(path);
+            }
+        }
+        self.open_pending(&mut out);
+        Ok(out)
+    }
+}
+
+impl AsRawFd for HotplugMonitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inotify.as_raw_fd()
+    }
+}
+
+impl Drop for HotplugMonitor {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.inotify.as_raw_fd());
+    }
+}
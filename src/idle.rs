@@ -0,0 +1,225 @@
+//! Noticing when the user stops using the input devices.
+//!
+//! Screen lockers and power managers want to know how long it has been since the last key
+//! press or pointer motion, without handling the input themselves. An [`IdleMonitor`] reads
+//! devices alongside whoever consumes them (without grabbing, so nothing is taken away from
+//! them), and reports each configured timeout as it passes, then once more when input resumes.
+//!
+//! ```no_run
+//! use evdev::idle::{IdleEvent, IdleMonitor};
+//! use evdev::Device;
+//! use std::time::Duration;
+//!
+//! let mut devices: Vec<Device> = evdev::enumerate().map(|(_, d)| d).collect();
+//! let mut monitor = IdleMonitor::new()
+//!     .timeout(Duration::from_secs(60))
+//!     .timeout(Duration::from_secs(300));
+//! monitor.run(&mut devices, |ev| match ev {
+//!     IdleEvent::Idle { timeout } if timeout.as_secs() == 60 => println!("dim the screen"),
+//!     IdleEvent::Idle { .. } => println!("lock the screen"),
+//!     IdleEvent::Resumed { .. } => println!("restore brightness"),
+//! })?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::{Device, EventType, InputEvent};
+
+/// A change in whether the user is idle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IdleEvent {
+    /// There was no input for `timeout`, one of the configured timeouts.
+    Idle { timeout: Duration },
+    /// Input arrived after at least one timeout had passed, having been idle for `idle_for`.
+    Resumed { idle_for: Duration },
+}
+
+/// Returns `true` if `ev` is input from the user, i.e. a key, motion or switch change.
+fn is_activity(ev: &InputEvent) -> bool {
+    matches!(
+        ev.event_type(),
+        EventType::KEY | EventType::RELATIVE | EventType::ABSOLUTE | EventType::SWITCH
+    )
+}
+
+/// Reports idle timeouts. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct IdleMonitor {
+    /// Sorted, without duplicates.
+    timeouts: Vec<Duration>,
+    last_activity: SystemTime,
+    /// The number of timeouts reported since the last activity.
+    passed: usize,
+    clock: SharedClock,
+}
+
+impl Default for IdleMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdleMonitor {
+    /// Create a monitor without timeouts, counting the user as active now.
+    pub fn new() -> Self {
+        IdleMonitor {
+            timeouts: Vec::new(),
+            last_activity: SystemTime::now(),
+            passed: 0,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Take the time of events and in [`run`](Self::run) from `clock` rather than the system
+    /// clock, counting the user as active as of its current time.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self.last_activity = self.clock.now();
+        self
+    }
+
+    /// Report when there has been no input for `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        if let Err(i) = self.timeouts.binary_search(&timeout) {
+            self.timeouts.insert(i, timeout);
+        }
+        self
+    }
+
+    /// Returns the configured timeouts, shortest first.
+    pub fn timeouts(&self) -> &[Duration] {
+        &self.timeouts
+    }
+
+    /// Returns when the last input arrived.
+    pub fn last_activity(&self) -> SystemTime {
+        self.last_activity
+    }
+
+    /// Returns how long there has been no input, as of `now`.
+    pub fn idle_time(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.last_activity).unwrap_or_default()
+    }
+
+    /// Returns `true` if at least one timeout has passed since the last input.
+    pub fn is_idle(&self) -> bool {
+        self.passed > 0
+    }
+
+    /// Count the user as active at `now`, as if input had arrived, e.g. while a video plays.
+    /// No [`IdleEvent::Resumed`] is reported.
+    pub fn reset(&mut self, now: SystemTime) {
+        self.last_activity = now;
+        self.passed = 0;
+    }
+
+    /// Record an event, appending an [`IdleEvent::Resumed`] to `out` if it is input that ends
+    /// an idle period.
+    pub fn observe(&mut self, ev: &InputEvent, out: &mut Vec<IdleEvent>) {
+        if !is_activity(ev) {
+            return;
+        }
+        let time = self.clock.event_time(ev).max(self.last_activity);
+        if self.passed > 0 {
+            out.push(IdleEvent::Resumed {
+                idle_for: self.idle_time(time),
+            });
+        }
+        self.reset(time);
+    }
+
+    /// Returns the next time a timeout passes unless input arrives.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        let timeout = self.timeouts.get(self.passed)?;
+        Some(self.last_activity + *timeout)
+    }
+
+    /// Append an [`IdleEvent::Idle`] to `out` for every timeout that passed as of `now`.
+    pub fn check(&mut self, now: SystemTime, out: &mut Vec<IdleEvent>) {
+        while let Some(&timeout) = self.timeouts.get(self.passed) {
+            if self.last_activity + timeout > now {
+                break;
+            }
+            self.passed += 1;
+            out.push(IdleEvent::Idle { timeout });
+        }
+    }
+
+    /// Watch `devices` and call `f` with every change until an error occurs.
+    ///
+    /// Devices that report continuously without the user touching them, like accelerometers,
+    /// should be left out.
+    pub fn run(&mut self, devices: &mut [Device], mut f: impl FnMut(IdleEvent)) -> io::Result<()> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let mut out = Vec::new();
+        loop {
+            let timeout = self.next_deadline().map_or(-1, |deadline| {
+                let remaining = self.clock.until(deadline);
+                remaining.as_millis().clamp(1, i32::MAX as u128) as i32
+            });
+            let mut fds: Vec<_> = devices
+                .iter()
+                .map(|device| PollFd::new(device.as_raw_fd(), PollFlags::POLLIN))
+                .collect();
+            poll(&mut fds, timeout)?;
+            for (fd, device) in fds.iter().zip(devices.iter_mut()) {
+                if fd.revents().is_some_and(|r| !r.is_empty()) {
+                    for ev in device.fetch_events()? {
+                        self.observe(&ev, &mut out);
+                    }
+                }
+            }
+            self.check(self.clock.now(), &mut out);
+            out.drain(..).for_each(&mut f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeouts_and_resume() {
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let mut monitor = IdleMonitor::new()
+            .timeout(Duration::from_secs(300))
+            .timeout(Duration::from_secs(60));
+        monitor.reset(at(0));
+        let mut out = Vec::new();
+
+        monitor.check(at(59), &mut out);
+        assert!(out.is_empty());
+        assert_eq!(monitor.next_deadline(), Some(at(60)));
+        monitor.check(at(400), &mut out);
+        assert_eq!(
+            out,
+            [
+                IdleEvent::Idle {
+                    timeout: Duration::from_secs(60)
+                },
+                IdleEvent::Idle {
+                    timeout: Duration::from_secs(300)
+                },
+            ]
+        );
+        assert_eq!(monitor.next_deadline(), None);
+
+        out.clear();
+        let mut ev = InputEvent::new(EventType::KEY, 30, 1);
+        ev.0.time = crate::systime_to_timeval(&at(450));
+        monitor.observe(&ev, &mut out);
+        assert_eq!(
+            out,
+            [IdleEvent::Resumed {
+                idle_for: Duration::from_secs(450)
+            }]
+        );
+        assert!(!monitor.is_idle());
+        assert_eq!(monitor.next_deadline(), Some(at(510)));
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use evdev_core::evdev_enum;
+
 #[derive(Clone)]
 #[repr(transparent)]
 pub struct InputId(pub(crate) libc::input_id);
@@ -42,6 +44,15 @@ impl InputId {
     }
 }
 
+impl PartialEq for InputId {
+    fn eq(&self, other: &Self) -> bool {
+        let fields = |id: &InputId| (id.0.bustype, id.0.vendor, id.0.product, id.0.version);
+        fields(self) == fields(other)
+    }
+}
+
+impl Eq for InputId {}
+
 impl fmt::Debug for InputId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("InputId")
@@ -0,0 +1,521 @@
+//! Playing controller-only games with a keyboard and mouse.
+//!
+//! [`KbmGamepad`] grabs a keyboard and a mouse and drives a virtual gamepad from them: keys
+//! press buttons and triggers, four keys (WASD by default) push the left stick, and mouse
+//! motion pushes the right stick. The translation itself is a [`KbmGamepadTransform`], which
+//! can also run in a [`Proxy`](crate::proxy::Proxy) or any other consumer of
+//! [`EventTransform`]s.
+//!
+//! A mouse reports motion, but a stick holds a position. The right stick follows the mouse's
+//! speed through a [`MouseCurve`], and returns to the center shortly after the mouse stops.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use evdev::kbm_gamepad::{KbmGamepad, KbmMapping};
+//! use evdev::Device;
+//!
+//! let keyboard = Device::open("/dev/input/event3")?;
+//! let mouse = Device::open("/dev/input/event4")?;
+//! let mut pad = KbmGamepad::new(keyboard, mouse, KbmMapping::default())?;
+//! pad.grab()?;
+//! pad.run()
+//! # }
+//! ```
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::gamepad::{standard_axis, standard_key, GamepadAxis, GamepadButton};
+use crate::presets::ControllerPreset;
+use crate::transform::{is_syn_report, EventTransform};
+use crate::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use crate::{
+    AbsInfo, AbsoluteAxisType, AttributeSet, Device, EventType, HatDirection, InputEvent, Key,
+    RelativeAxisType,
+};
+
+/// The keys that push a stick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StickKeys {
+    pub up: Key,
+    pub down: Key,
+    pub left: Key,
+    pub right: Key,
+}
+
+/// How mouse speed translates to deflection of the right stick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MouseCurve {
+    /// The mouse speed, in counts per second, that deflects the stick fully.
+    pub full_speed: f32,
+    /// The exponent applied to the speed relative to `full_speed`. 1 is linear; larger values
+    /// give finer control at low speeds.
+    pub exponent: f32,
+    /// The smallest deflection while the mouse moves, to get past the game's dead zone.
+    pub min_deflection: f32,
+    /// How long the stick stays put after the mouse stops.
+    pub hold: Duration,
+}
+
+impl Default for MouseCurve {
+    fn default() -> Self {
+        MouseCurve {
+            full_speed: 4000.0,
+            exponent: 1.5,
+            min_deflection: 0.2,
+            hold: Duration::from_millis(50),
+        }
+    }
+}
+
+impl MouseCurve {
+    /// Returns the stick deflection for a speed in counts per second, with its sign.
+    pub fn deflection(&self, speed: f32) -> f32 {
+        if speed == 0.0 || self.full_speed <= 0.0 {
+            return 0.0;
+        }
+        let relative = (speed.abs() / self.full_speed).min(1.0).powf(self.exponent);
+        let span = 1.0 - self.min_deflection;
+        speed.signum() * (self.min_deflection + relative * span).min(1.0)
+    }
+}
+
+/// Which keys and mouse buttons do what.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KbmMapping {
+    /// Keys and mouse buttons pressing gamepad buttons. The d-pad buttons are emitted as a
+    /// hat switch.
+    pub buttons: Vec<(Key, GamepadButton)>,
+    /// Keys and mouse buttons pulling a trigger all the way.
+    pub triggers: Vec<(Key, GamepadAxis)>,
+    pub left_stick: StickKeys,
+    /// The mouse curve, or `None` to leave the right stick alone.
+    pub mouse: Option<MouseCurve>,
+    /// Invert vertical mouse motion, for flight controls.
+    pub invert_y: bool,
+}
+
+impl Default for KbmMapping {
+    /// The layout of a typical first person shooter.
+    fn default() -> Self {
+        KbmMapping {
+            buttons: vec![
+                (Key::KEY_SPACE, GamepadButton::A),
+                (Key::KEY_LEFTCTRL, GamepadButton::B),
+                (Key::KEY_R, GamepadButton::X),
+                (Key::KEY_F, GamepadButton::Y),
+                (Key::KEY_Q, GamepadButton::LeftShoulder),
+                (Key::KEY_E, GamepadButton::RightShoulder),
+                (Key::KEY_LEFTSHIFT, GamepadButton::LeftStick),
+                (Key::KEY_V, GamepadButton::RightStick),
+                (Key::BTN_MIDDLE, GamepadButton::RightStick),
+                (Key::KEY_TAB, GamepadButton::Back),
+                (Key::KEY_ESC, GamepadButton::Start),
+                (Key::KEY_UP, GamepadButton::DPadUp),
+                (Key::KEY_DOWN, GamepadButton::DPadDown),
+                (Key::KEY_LEFT, GamepadButton::DPadLeft),
+                (Key::KEY_RIGHT, GamepadButton::DPadRight),
+            ],
+            triggers: vec![
+                (Key::BTN_RIGHT, GamepadAxis::LeftTrigger),
+                (Key::BTN_LEFT, GamepadAxis::RightTrigger),
+            ],
+            left_stick: StickKeys {
+                up: Key::KEY_W,
+                down: Key::KEY_S,
+                left: Key::KEY_A,
+                right: Key::KEY_D,
+            },
+            mouse: Some(MouseCurve::default()),
+            invert_y: false,
+        }
+    }
+}
+
+/// Translates keyboard and mouse frames into frames for a virtual gamepad.
+///
+/// Events that aren't mapped are dropped. Call [`tick`](Self::tick) periodically, so the
+/// right stick returns to the center when the mouse stops.
+#[derive(Debug, Clone)]
+pub struct KbmGamepadTransform {
+    mapping: KbmMapping,
+    ranges: Vec<(AbsoluteAxisType, AbsInfo)>,
+    output_keys: AttributeSet<Key>,
+    held: AttributeSet<Key>,
+    /// The last emitted value of each axis of `ranges`.
+    emitted_axes: Vec<i32>,
+    emitted_keys: AttributeSet<Key>,
+    right_stick: (f32, f32),
+    last_motion: Option<SystemTime>,
+    clock: SharedClock,
+}
+
+impl KbmGamepadTransform {
+    /// Create a transform producing events for a controller set up with `preset`.
+    pub fn new(mapping: KbmMapping, preset: ControllerPreset) -> Self {
+        let ranges: Vec<_> = preset
+            .absolute_axes()
+            .iter()
+            .map(|setup| (setup.axis(), setup.abs_info()))
+            .collect();
+        KbmGamepadTransform {
+            mapping,
+            emitted_axes: ranges.iter().map(|(_, info)| info.value()).collect(),
+            ranges,
+            output_keys: preset.keys(),
+            held: AttributeSet::new(),
+            emitted_keys: AttributeSet::new(),
+            right_stick: (0.0, 0.0),
+            last_motion: None,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Take the time of events from `clock` rather than the system clock.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    pub fn mapping(&self) -> &KbmMapping {
+        &self.mapping
+    }
+
+    fn is_held(&self, key: Key) -> bool {
+        self.held.contains(key)
+    }
+
+    fn button_held(&self, button: GamepadButton) -> bool {
+        self.mapping
+            .buttons
+            .iter()
+            .any(|&(key, b)| b == button && self.is_held(key))
+    }
+
+    fn update_mouse(&mut self, dx: i32, dy: i32, time: SystemTime) {
+        let Some(curve) = self.mapping.mouse else {
+            return;
+        };
+        // Speed over the time since the previous motion, bounded so that the first motion
+        // after a pause doesn't look slow
+        let elapsed = self
+            .last_motion
+            .and_then(|last| time.duration_since(last).ok())
+            .unwrap_or(curve.hold)
+            .clamp(
+                Duration::from_millis(1),
+                curve.hold.max(Duration::from_millis(1)),
+            );
+        let per_second = 1.0 / elapsed.as_secs_f32();
+        let dy = if self.mapping.invert_y { -dy } else { dy };
+        self.right_stick = (
+            curve.deflection(dx as f32 * per_second),
+            curve.deflection(dy as f32 * per_second),
+        );
+        self.last_motion = Some(time);
+    }
+
+    /// Recenter the right stick if the mouse has been still for longer than the curve's hold
+    /// time, as of `now`.
+    pub fn tick(&mut self, now: SystemTime, out: &mut Vec<InputEvent>) {
+        let (Some(curve), Some(last)) = (self.mapping.mouse, self.last_motion) else {
+            return;
+        };
+        if now.duration_since(last).unwrap_or_default() >= curve.hold {
+            self.right_stick = (0.0, 0.0);
+            self.last_motion = None;
+            let syn = InputEvent::new_now(EventType::SYNCHRONIZATION, 0, 0);
+            self.emit(syn, out);
+        }
+    }
+
+    /// Append a frame with the outputs that changed since the last one.
+    fn emit(&mut self, syn: InputEvent, out: &mut Vec<InputEvent>) {
+        let start = out.len();
+        let event = |type_: EventType, code, value| {
+            InputEvent(libc::input_event {
+                type_: type_.0,
+                code,
+                value,
+                ..syn.0
+            })
+        };
+
+        let mut keys = AttributeSet::<Key>::new();
+        for &(_, button) in &self.mapping.buttons {
+            if let Some(key) = standard_key(button) {
+                if self.button_held(button) && self.output_keys.contains(key) {
+                    keys.insert(key);
+                }
+            }
+        }
+        for &(key, axis) in &self.mapping.triggers {
+            let trigger_key = match axis {
+                GamepadAxis::LeftTrigger => Key::BTN_TL2,
+                GamepadAxis::RightTrigger => Key::BTN_TR2,
+                _ => continue,
+            };
+            if self.is_held(key) && self.output_keys.contains(trigger_key) {
+                keys.insert(trigger_key);
+            }
+        }
+        for key in self.output_keys.iter() {
+            let pressed = keys.contains(key);
+            if pressed != self.emitted_keys.contains(key) {
+                out.push(event(EventType::KEY, key.code(), pressed as i32));
+            }
+        }
+        self.emitted_keys = keys;
+
+        let axis = |held: bool| held as i32 as f32;
+        let stick = self.mapping.left_stick;
+        let (mut lx, mut ly) = (
+            axis(self.is_held(stick.right)) - axis(self.is_held(stick.left)),
+            axis(self.is_held(stick.down)) - axis(self.is_held(stick.up)),
+        );
+        if lx != 0.0 && ly != 0.0 {
+            // Keep diagonals on the unit circle
+            lx *= std::f32::consts::FRAC_1_SQRT_2;
+            ly *= std::f32::consts::FRAC_1_SQRT_2;
+        }
+        let trigger = |target: GamepadAxis| {
+            let pulled = self
+                .mapping
+                .triggers
+                .iter()
+                .any(|&(key, a)| a == target && self.is_held(key));
+            // Triggers rest at their minimum
+            if pulled {
+                1.0
+            } else {
+                -1.0
+            }
+        };
+        let hat = HatDirection::from_axes(
+            self.button_held(GamepadButton::DPadRight) as i32
+                - self.button_held(GamepadButton::DPadLeft) as i32,
+            self.button_held(GamepadButton::DPadDown) as i32
+                - self.button_held(GamepadButton::DPadUp) as i32,
+        );
+        let (hat_x, hat_y) = hat.axes();
+
+        let values = [
+            (standard_axis(GamepadAxis::LeftX), lx),
+            (standard_axis(GamepadAxis::LeftY), ly),
+            (standard_axis(GamepadAxis::RightX), self.right_stick.0),
+            (standard_axis(GamepadAxis::RightY), self.right_stick.1),
+            (
+                standard_axis(GamepadAxis::LeftTrigger),
+                trigger(GamepadAxis::LeftTrigger),
+            ),
+            (
+                standard_axis(GamepadAxis::RightTrigger),
+                trigger(GamepadAxis::RightTrigger),
+            ),
+            (AbsoluteAxisType::ABS_HAT0X, hat_x as f32),
+            (AbsoluteAxisType::ABS_HAT0Y, hat_y as f32),
+        ];
+        for (code, value) in values {
+            let Some(i) = self.ranges.iter().position(|(a, _)| *a == code) else {
+                continue;
+            };
+            let info = self.ranges[i].1;
+            // Rounding would put a resting stick one off the center of an even-sized range
+            let raw = if value == 0.0 {
+                info.center()
+            } else {
+                info.denormalize(value)
+            };
+            if raw != self.emitted_axes[i] {
+                self.emitted_axes[i] = raw;
+                out.push(event(EventType::ABSOLUTE, code.0, raw));
+            }
+        }
+
+        if out.len() > start {
+            out.push(syn);
+        }
+    }
+}
+
+impl EventTransform for KbmGamepadTransform {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let (mut dx, mut dy) = (0, 0);
+        for ev in frame {
+            match ev.event_type() {
+                EventType::KEY if ev.value() == 0 => self.held.remove(Key::new(ev.code())),
+                EventType::KEY if ev.value() == 1 => self.held.insert(Key::new(ev.code())),
+                EventType::RELATIVE => match RelativeAxisType(ev.code()) {
+                    RelativeAxisType::REL_X => dx += ev.value(),
+                    RelativeAxisType::REL_Y => dy += ev.value(),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        let Some(syn) = frame.last().filter(|ev| is_syn_report(ev)) else {
+            return;
+        };
+        if dx != 0 || dy != 0 {
+            self.update_mouse(dx, dy, self.clock.event_time(syn));
+        }
+        self.emit(*syn, out);
+    }
+}
+
+/// A virtual gamepad driven by a keyboard and a mouse. See the [module documentation](self).
+pub struct KbmGamepad {
+    keyboard: Device,
+    mouse: Device,
+    pad: VirtualDevice,
+    transform: KbmGamepadTransform,
+    out: Vec<InputEvent>,
+}
+
+impl KbmGamepad {
+    /// Create a virtual Xbox 360 controller driven by `keyboard` and `mouse`.
+    pub fn new(keyboard: Device, mouse: Device, mapping: KbmMapping) -> io::Result<Self> {
+        let preset = ControllerPreset::Xbox360;
+        let pad = preset.apply(VirtualDeviceBuilder::new()?)?.build()?;
+        Ok(KbmGamepad {
+            keyboard,
+            mouse,
+            pad,
+            transform: KbmGamepadTransform::new(mapping, preset),
+            out: Vec::new(),
+        })
+    }
+
+    /// Take the time for moving and recentering the right stick from `clock` rather than the
+    /// system clock.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.transform.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Grab the keyboard and the mouse, so their input only reaches the game through the
+    /// gamepad.
+    pub fn grab(&mut self) -> io::Result<()> {
+        self.keyboard.grab()?;
+        self.mouse.grab()
+    }
+
+    /// Release the grabs taken by [`grab`](Self::grab).
+    pub fn ungrab(&mut self) -> io::Result<()> {
+        self.keyboard.ungrab()?;
+        self.mouse.ungrab()
+    }
+
+    pub fn pad(&self) -> &VirtualDevice {
+        &self.pad
+    }
+
+    /// Consume the mapper, returning the keyboard, the mouse and the virtual gamepad.
+    pub fn into_inner(self) -> (Device, Device, VirtualDevice) {
+        (self.keyboard, self.mouse, self.pad)
+    }
+
+    /// Wait up to `timeout` for input, and forward whatever arrived.
+    pub fn pump(&mut self, timeout: Duration) -> io::Result<()> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let mut fds = [
+            PollFd::new(self.keyboard.as_raw_fd(), PollFlags::POLLIN),
+            PollFd::new(self.mouse.as_raw_fd(), PollFlags::POLLIN),
+        ];
+        let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+        poll(&mut fds, millis)?;
+        let ready = |fd: &PollFd| fd.revents().is_some_and(|r| !r.is_empty());
+        let (keyboard_ready, mouse_ready) = (ready(&fds[0]), ready(&fds[1]));
+
+        self.out.clear();
+        for (ready, device) in [
+            (keyboard_ready, &mut self.keyboard),
+            (mouse_ready, &mut self.mouse),
+        ] {
+            if !ready {
+                continue;
+            }
+            let events: Vec<_> = device.fetch_events()?.collect();
+            for frame in crate::transform::frames(&events) {
+                self.transform.process(frame, &mut self.out);
+            }
+        }
+        let now = self.transform.clock.now();
+        self.transform.tick(now, &mut self.out);
+        for frame in crate::transform::frames(&self.out) {
+            self.pad.emit(&frame[..frame.len() - 1])?;
+        }
+        Ok(())
+    }
+
+    /// Forward input until an error occurs.
+    pub fn run(&mut self) -> io::Result<()> {
+        let hold = self
+            .transform
+            .mapping()
+            .mouse
+            .map_or(Duration::from_secs(1), |m| m.hold);
+        loop {
+            self.pump(hold / 2)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(events: &[(EventType, u16, i32)], ms: u64) -> Vec<InputEvent> {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+        let mut frame: Vec<_> = events
+            .iter()
+            .map(|&(t, c, v)| InputEvent::new(t, c, v))
+            .chain([InputEvent::new(EventType::SYNCHRONIZATION, 0, 0)])
+            .collect();
+        for ev in &mut frame {
+            ev.0.time = crate::systime_to_timeval(&time);
+        }
+        frame
+    }
+
+    fn value(out: &[InputEvent], axis: AbsoluteAxisType) -> Option<i32> {
+        out.iter()
+            .rev()
+            .find(|ev| ev.event_type() == EventType::ABSOLUTE && ev.code() == axis.0)
+            .map(|ev| ev.value())
+    }
+
+    #[test]
+    fn keys_and_mouse_to_gamepad() {
+        let mut transform =
+            KbmGamepadTransform::new(KbmMapping::default(), ControllerPreset::Xbox360);
+        let mut out = Vec::new();
+
+        let press = |key: Key| (EventType::KEY, key.code(), 1);
+        transform.process(
+            &frame(&[press(Key::KEY_W), press(Key::KEY_SPACE)], 0),
+            &mut out,
+        );
+        assert_eq!(value(&out, AbsoluteAxisType::ABS_Y), Some(-32768));
+        assert!(out
+            .iter()
+            .any(|ev| ev.code() == Key::BTN_SOUTH.code() && ev.value() == 1));
+
+        out.clear();
+        let motion = (EventType::RELATIVE, RelativeAxisType::REL_X.0, 400);
+        transform.process(&frame(&[motion], 10), &mut out);
+        assert_eq!(value(&out, AbsoluteAxisType::ABS_RX), Some(32767));
+
+        // The stick recenters once the mouse has been still for the hold time
+        out.clear();
+        transform.tick(SystemTime::UNIX_EPOCH + Duration::from_millis(20), &mut out);
+        assert!(out.is_empty());
+        transform.tick(
+            SystemTime::UNIX_EPOCH + Duration::from_millis(100),
+            &mut out,
+        );
+        assert_eq!(value(&out, AbsoluteAxisType::ABS_RX), Some(0));
+    }
+}
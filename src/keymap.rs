@@ -0,0 +1,297 @@
+//! Saving and restoring keymaps.
+//!
+//! A keyboard's scancode to keycode table can be changed at runtime, e.g. to swap Caps Lock and
+//! Control in the keyboard itself, but the changes are lost when the device is unplugged or the
+//! system restarts. A [`KeymapFile`] holds the remapped entries of a keymap in a small text
+//! format, which is also valid TOML, so remaps can live in config files and a daemon can
+//! restore them whenever the keyboard appears:
+//!
+//! ```toml
+//! [device]
+//! name = "AT Translated Set 2 keyboard"
+//! bustype = 0x11
+//! vendor = 0x1
+//! product = 0x1
+//! version = 0xab83
+//!
+//! [keys]
+//! 0x3a = "KEY_LEFTCTRL"
+//! 0x1d = "KEY_CAPSLOCK"
+//! ```
+//!
+//! The `[device]` table is optional; without it the remaps apply to any keyboard. Keys are
+//! scancodes, in hex, and values are key names, or key codes for keys without a name.
+//!
+//! ```no_run
+//! use evdev::keymap::KeymapFile;
+//!
+//! let keymap = KeymapFile::load("/etc/evdev/keymap.toml")?;
+//! let device = evdev::Device::open("/dev/input/event0")?;
+//! if keymap.matches(&device) {
+//!     keymap.apply(&device)?;
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{BusType, Device, InputId, Key};
+
+/// A set of scancode to keycode mappings, optionally tied to a device. See the
+/// [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeymapFile {
+    /// The name of the device the keymap is for. Only informative.
+    pub name: Option<String>,
+    /// The device the keymap is for, or `None` for any device.
+    pub input_id: Option<InputId>,
+    /// The key code of each scancode.
+    pub keys: BTreeMap<u32, Key>,
+}
+
+fn invalid(line: usize, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("keymap line {}: {}", line + 1, msg),
+    )
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c if c.is_control() => quoted += &format!("\\u{:04x}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted + "\""
+}
+
+fn unquote(s: &str) -> Option<String> {
+    let inner = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    unquoted.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                'n' => unquoted.push('\n'),
+                't' => unquoted.push('\t'),
+                c @ ('"' | '\\') => unquoted.push(c),
+                _ => return None,
+            },
+            '"' => return None,
+            c => unquoted.push(c),
+        }
+    }
+    Some(unquoted)
+}
+
+fn parse_int(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(&hex.replace('_', ""), 16).ok(),
+        None => s.replace('_', "").parse().ok(),
+    }
+}
+
+impl KeymapFile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the whole keymap of `device`, tied to `device`. Scancodes longer than 4 bytes
+    /// are left out.
+    ///
+    /// The kernel doesn't keep the default keymap, so there's no telling which entries were
+    /// remapped; edit the result down to those, so other keyboards of the same model keep their
+    /// own defaults.
+    pub fn from_device(device: &Device) -> io::Result<Self> {
+        let mut keys = BTreeMap::new();
+        for entry in device.keymap() {
+            let entry = entry?;
+            if let Some(scancode) = entry.scancode_value() {
+                keys.insert(scancode, entry.key);
+            }
+        }
+        Ok(KeymapFile {
+            name: device.name().map(str::to_owned),
+            input_id: Some(device.input_id()),
+            keys,
+        })
+    }
+
+    /// Load a keymap file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Save the keymap to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Parse the contents of a keymap file.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut keymap = Self::new();
+        let mut table = "";
+        let (mut bus, mut vendor, mut product, mut version) = (None, None, None, None);
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                table = match name.trim() {
+                    "device" => "device",
+                    "keys" => "keys",
+                    _ => return Err(invalid(n, "unknown table")),
+                };
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(n, "expected `key = value`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            match table {
+                "device" => {
+                    if key == "name" {
+                        let name = unquote(value).ok_or_else(|| invalid(n, "invalid string"))?;
+                        keymap.name = Some(name);
+                        continue;
+                    }
+                    let field = match key {
+                        "bustype" => &mut bus,
+                        "vendor" => &mut vendor,
+                        "product" => &mut product,
+                        "version" => &mut version,
+                        _ => return Err(invalid(n, "unknown device field")),
+                    };
+                    let value = parse_int(value)
+                        .and_then(|v| u16::try_from(v).ok())
+                        .ok_or_else(|| invalid(n, "invalid number"))?;
+                    *field = Some(value);
+                }
+                "keys" => {
+                    let scancode = parse_int(key).ok_or_else(|| invalid(n, "invalid scancode"))?;
+                    let key = match unquote(value) {
+                        Some(name) => name.parse().map_err(|_| invalid(n, "unknown key name"))?,
+                        None => parse_int(value)
+                            .and_then(|code| u16::try_from(code).ok())
+                            .map(Key::new)
+                            .ok_or_else(|| invalid(n, "invalid key"))?,
+                    };
+                    keymap.keys.insert(scancode, key);
+                }
+                _ => return Err(invalid(n, "entry outside of a table")),
+            }
+        }
+        keymap.input_id = match (bus, vendor, product, version) {
+            (None, None, None, None) => None,
+            (Some(bus), Some(vendor), Some(product), version) => Some(InputId::new(
+                BusType(bus),
+                vendor,
+                product,
+                version.unwrap_or(0),
+            )),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "keymap device needs a bustype, vendor and product",
+                ))
+            }
+        };
+        Ok(keymap)
+    }
+
+    /// Returns `true` if the keymap is for `device`: if it isn't tied to a device, or the bus
+    /// type, vendor and product match. The version is ignored, since it changes with firmware
+    /// updates.
+    pub fn matches(&self, device: &Device) -> bool {
+        let ids = |id: &InputId| (id.bus_type(), id.vendor(), id.product());
+        self.input_id
+            .as_ref()
+            .is_none_or(|id| ids(id) == ids(&device.input_id()))
+    }
+
+    /// Apply the mappings to `device` with `EVIOCSKEYCODE`. The changes last until the device
+    /// is removed, and affect every reader of the device.
+    ///
+    /// All mappings are tried, and the first error is returned.
+    pub fn apply(&self, device: &Device) -> io::Result<()> {
+        let mut result = Ok(());
+        for (&scancode, key) in &self.keys {
+            // SAFETY: `Device`s are evdev devices
+            let applied = unsafe { crate::raw::eviocskeycode(device, scancode, key.code().into()) };
+            if result.is_ok() {
+                result = applied;
+            }
+        }
+        result
+    }
+}
+
+impl fmt::Display for KeymapFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.name.is_some() || self.input_id.is_some() {
+            writeln!(f, "[device]")?;
+            if let Some(name) = &self.name {
+                writeln!(f, "name = {}", quote(name))?;
+            }
+            if let Some(id) = &self.input_id {
+                writeln!(f, "bustype = {:#x}", id.bus_type().0)?;
+                writeln!(f, "vendor = {:#x}", id.vendor())?;
+                writeln!(f, "product = {:#x}", id.product())?;
+                writeln!(f, "version = {:#x}", id.version())?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "[keys]")?;
+        for (scancode, key) in &self.keys {
+            let name = format!("{:?}", key);
+            match name.parse::<Key>() {
+                Ok(_) => writeln!(f, "{:#x} = {}", scancode, quote(&name))?,
+                Err(_) => writeln!(f, "{:#x} = {}", scancode, key.code())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> io::Result<()> {
+        let keymap = KeymapFile {
+            name: Some("Keyboard \"Pro\"".into()),
+            input_id: Some(InputId::new(BusType::BUS_USB, 0x46d, 0xc31c, 0x110)),
+            keys: [
+                (0x70039, Key::KEY_LEFTCTRL),
+                (0x700e0, Key::KEY_CAPSLOCK),
+                (0x70064, Key::new(0x2ff)),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let text = keymap.to_string();
+        assert!(text.contains("0x70039 = \"KEY_LEFTCTRL\"\n"));
+        assert!(text.contains("0x70064 = 767\n"));
+        assert_eq!(KeymapFile::parse(&text)?, keymap);
+
+        let err = KeymapFile::parse("[keys]\n0x1d = \"KEY_NOPE\"").unwrap_err();
+        assert_eq!(err.to_string(), "keymap line 2: unknown key name");
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+//! Measuring input latency.
+//!
+//! A [`LatencyProbe`] emits numbered probes on a virtual device and times how long each takes
+//! to come back. Probes can be read back from the device's own event node, which measures the
+//! kernel's part of the pipeline, or reported by the application under test, such as a client
+//! of a compositor, which measures everything in between. [`LatencyStats`] summarizes the
+//! round trips.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use evdev::latency::LatencyProbe;
+//! use std::time::Duration;
+//!
+//! let mut probe = LatencyProbe::new(None)?;
+//! let stats = probe.measure(1000, Duration::from_millis(2))?;
+//! println!("{}", stats);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use crate::{AttributeSet, Device, EventType, InputEvent, Key, MiscType};
+
+/// How long [`LatencyProbe::measure`] waits for a probe before counting it as lost.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Emits probes on a virtual device and collects their round-trip times.
+///
+/// Each probe is an `MSC_SCAN` event carrying its sequence number. If the probe was created
+/// with a key, the key is also pressed along with the scan code and released in a following
+/// frame, for applications that only see key events; those report probes with
+/// [`acknowledge_next`](Self::acknowledge_next).
+pub struct LatencyProbe {
+    device: VirtualDevice,
+    key: Option<Key>,
+    next_seq: i32,
+    pending: VecDeque<(i32, Instant)>,
+    samples: Vec<Duration>,
+    lost: usize,
+}
+
+impl LatencyProbe {
+    /// Create a virtual device to emit probes on, pressing `key` with each probe if given.
+    pub fn new(key: Option<Key>) -> io::Result<Self> {
+        let miscs: AttributeSet<MiscType> = [MiscType::MSC_SCAN].into_iter().collect();
+        let mut builder = VirtualDeviceBuilder::new()?
+            .name("evdev latency probe")
+            .with_miscs(&miscs)?;
+        if let Some(key) = key {
+            builder = builder.with_keys(&[key].into_iter().collect::<AttributeSet<_>>())?;
+        }
+        Ok(Self::with_device(builder.build()?, key))
+    }
+
+    /// Emit probes on an existing device, which must support `MSC_SCAN` and `key`.
+    pub fn with_device(device: VirtualDevice, key: Option<Key>) -> Self {
+        LatencyProbe {
+            device,
+            key,
+            next_seq: 0,
+            pending: VecDeque::new(),
+            samples: Vec::new(),
+            lost: 0,
+        }
+    }
+
+    pub fn device(&self) -> &VirtualDevice {
+        &self.device
+    }
+
+    /// Emit a probe, returning its sequence number.
+    pub fn send(&mut self) -> io::Result<i32> {
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        let scan = InputEvent::new(EventType::MISC, MiscType::MSC_SCAN.0, seq);
+        self.pending.push_back((seq, Instant::now()));
+        match self.key {
+            Some(key) => {
+                self.device
+                    .emit(&[scan, InputEvent::new(EventType::KEY, key.code(), 1)])?;
+                self.device
+                    .emit(&[InputEvent::new(EventType::KEY, key.code(), 0)])?;
+            }
+            None => self.device.emit(&[scan])?,
+        }
+        Ok(seq)
+    }
+
+    /// Check an event read back from the probe's device. If it is a probe, its round-trip
+    /// time is recorded and returned.
+    pub fn receive(&mut self, ev: &InputEvent) -> Option<Duration> {
+        if ev.event_type() != EventType::MISC || ev.code() != MiscType::MSC_SCAN.0 {
+            return None;
+        }
+        self.acknowledge(ev.value())
+    }
+
+    /// Record the arrival of probe `seq`, as reported by the application under test. Probes
+    /// sent before it that never arrived are counted as lost.
+    pub fn acknowledge(&mut self, seq: i32) -> Option<Duration> {
+        let pos = self.pending.iter().position(|&(s, _)| s == seq)?;
+        self.lost += pos;
+        let (_, sent) = self.pending.drain(..=pos).next_back()?;
+        let rtt = sent.elapsed();
+        self.samples.push(rtt);
+        Some(rtt)
+    }
+
+    /// Record the arrival of the oldest outstanding probe, for applications that can't see
+    /// sequence numbers.
+    pub fn acknowledge_next(&mut self) -> Option<Duration> {
+        let &(seq, _) = self.pending.front()?;
+        self.acknowledge(seq)
+    }
+
+    /// Send `count` probes `interval` apart, reading each back from the device's event node.
+    pub fn measure(&mut self, count: usize, interval: Duration) -> io::Result<LatencyStats> {
+        let devnode = self.device.devnode().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "in-memory devices can't be read back",
+            )
+        })?;
+        let mut reader = Device::open(devnode)?;
+        for _ in 0..count {
+            let seq = self.send()?;
+            let deadline = Instant::now() + PROBE_TIMEOUT;
+            'wait: while self.pending.iter().any(|&(s, _)| s == seq) {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() || !wait_readable(&reader, remaining)? {
+                    self.pending.retain(|&(s, _)| s != seq);
+                    self.lost += 1;
+                    break 'wait;
+                }
+                for ev in reader.fetch_events()? {
+                    self.receive(&ev);
+                }
+            }
+            thread::sleep(interval);
+        }
+        self.stats()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "no probe came back"))
+    }
+
+    /// Returns the round-trip times recorded so far.
+    pub fn samples(&self) -> &[Duration] {
+        &self.samples
+    }
+
+    /// Summarize the round-trip times recorded so far, or `None` if there are none.
+    pub fn stats(&self) -> Option<LatencyStats> {
+        LatencyStats::from_samples(&self.samples, self.lost)
+    }
+
+    /// Forget the recorded samples and outstanding probes.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.samples.clear();
+        self.lost = 0;
+    }
+}
+
+fn wait_readable(device: &Device, timeout: Duration) -> io::Result<bool> {
+    use nix::poll::{poll, PollFd, PollFlags};
+    let mut fds = [PollFd::new(device.as_raw_fd(), PollFlags::POLLIN)];
+    let timeout = timeout.as_millis().clamp(1, i32::MAX as u128) as i32;
+    Ok(poll(&mut fds, timeout)? > 0)
+}
+
+/// The distribution of a set of round-trip times.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// The number of probes that came back.
+    pub count: usize,
+    /// The number of probes that never came back.
+    pub lost: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl LatencyStats {
+    /// Summarize `samples`. Returns `None` if there are none.
+    pub fn from_samples(samples: &[Duration], lost: usize) -> Option<Self> {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+        Some(LatencyStats {
+            count: sorted.len(),
+            lost,
+            min: *sorted.first()?,
+            max: *sorted.last()?,
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            median: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+        })
+    }
+}
+
+impl fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} probes ({} lost): min {:?}, median {:?}, mean {:?}, p95 {:?}, p99 {:?}, max {:?}",
+            self.count, self.lost, self.min, self.median, self.mean, self.p95, self.p99, self.max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_round_trip() -> io::Result<()> {
+        let miscs: AttributeSet<MiscType> = [MiscType::MSC_SCAN].into_iter().collect();
+        let keys: AttributeSet<Key> = [Key::KEY_F24].into_iter().collect();
+        let device = VirtualDeviceBuilder::in_memory()
+            .with_miscs(&miscs)?
+            .with_keys(&keys)?
+            .build()?;
+        let sink = device.memory_sink().unwrap();
+        let mut probe = LatencyProbe::with_device(device, Some(Key::KEY_F24));
+
+        for _ in 0..3 {
+            probe.send()?;
+        }
+        // The first probe is lost, the others come back
+        let frames = sink.drain();
+        assert_eq!(frames.len(), 6);
+        for ev in frames[2..].iter().flatten() {
+            probe.receive(ev);
+        }
+        probe.send()?;
+        assert!(probe.acknowledge_next().is_some());
+
+        let stats = probe.stats().unwrap();
+        assert_eq!((stats.count, stats.lost), (3, 1));
+        assert!(stats.min <= stats.median && stats.median <= stats.max);
+        Ok(())
+    }
+}
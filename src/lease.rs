@@ -0,0 +1,280 @@
+//! Handing devices out to other components or processes, revocably.
+//!
+//! An input broker, such as a compositor or a kiosk supervisor, opens devices itself and lends
+//! them to clients, taking access back when a client loses focus or misbehaves. A
+//! [`LeaseManager`] hands out [`DeviceLease`]s: descriptors of their own for a device, which
+//! can be used in-process or passed to another process over a Unix socket. Every lease is a
+//! separate open of the device node, with the manager's [`OpenOptions`], so revoking one with
+//! `EVIOCREVOKE` cuts off that lease everywhere it was passed, without affecting the broker or
+//! other leases.
+//!
+//! ```no_run
+//! use evdev::lease::LeaseManager;
+//! use std::os::unix::net::UnixStream;
+//!
+//! let (broker_end, client_end) = UnixStream::pair()?;
+//! let mut leases = LeaseManager::new("/dev/input/event3");
+//! let lease = leases.lease()?;
+//! let id = lease.id();
+//! lease.send(&broker_end)?;
+//!
+//! // In the client
+//! let mut device = evdev::lease::receive_device(&client_end)?;
+//!
+//! // Later, in the broker; the client's reads now fail with ENODEV
+//! leases.revoke(id)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use nix::sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags, SockAddr};
+use nix::sys::uio::IoVec;
+
+use crate::error::ioctl_error;
+use crate::{sys, Device, OpenOptions};
+
+/// Identifies a lease, for [`LeaseManager::revoke`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LeaseId(u64);
+
+/// A descriptor for a device, handed out by a [`LeaseManager`].
+#[derive(Debug)]
+pub struct DeviceLease {
+    id: LeaseId,
+    fd: OwnedFd,
+}
+
+impl DeviceLease {
+    pub fn id(&self) -> LeaseId {
+        self.id
+    }
+
+    /// Use the lease in this process.
+    pub fn into_device(self) -> io::Result<Device> {
+        Device::try_from(self.fd)
+    }
+
+    /// Returns the descriptor, e.g. to pass it to another process some other way.
+    pub fn into_fd(self) -> OwnedFd {
+        self.fd
+    }
+
+    /// Pass the lease to the process at the other end of a Unix socket, which can receive it
+    /// with [`receive_device`]. The lease's descriptor is closed in this process.
+    pub fn send(self, socket: &impl AsRawFd) -> io::Result<()> {
+        let fds = [self.fd.as_raw_fd()];
+        sendmsg(
+            socket.as_raw_fd(),
+            &[IoVec::from_slice(&[0])],
+            &[ControlMessage::ScmRights(&fds)],
+            MsgFlags::empty(),
+            None::<&SockAddr>,
+        )?;
+        Ok(())
+    }
+}
+
+impl AsRawFd for DeviceLease {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for DeviceLease {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+/// Receive a device passed with [`DeviceLease::send`], blocking until one arrives.
+///
+/// Fails with [`io::ErrorKind::UnexpectedEof`] if the socket was closed, and with
+/// [`io::ErrorKind::InvalidData`] if the message carried no descriptor.
+pub fn receive_device(socket: &impl AsRawFd) -> io::Result<Device> {
+    receive_fd(socket).and_then(Device::try_from)
+}
+
+fn receive_fd(socket: &impl AsRawFd) -> io::Result<OwnedFd> {
+    let mut byte = [0];
+    let mut cmsg_buffer = nix::cmsg_space!([RawFd; 1]);
+    let msg = recvmsg(
+        socket.as_raw_fd(),
+        &[IoVec::from_mut_slice(&mut byte)],
+        Some(&mut cmsg_buffer),
+        MsgFlags::MSG_CMSG_CLOEXEC,
+    )?;
+    // SAFETY: the descriptors were just received, so nothing else owns them
+    let fds: Vec<OwnedFd> = msg
+        .cmsgs()
+        .flat_map(|cmsg| match cmsg {
+            ControlMessageOwned::ScmRights(fds) => fds,
+            _ => Vec::new(),
+        })
+        .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+        .collect();
+    match fds.into_iter().next() {
+        Some(fd) => Ok(fd),
+        None if msg.bytes == 0 => Err(io::ErrorKind::UnexpectedEof.into()),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message carried no descriptor",
+        )),
+    }
+}
+
+/// Hands out and revokes leases on a device. See the [module documentation](self).
+///
+/// Outstanding leases are revoked when the manager is dropped.
+#[derive(Debug)]
+pub struct LeaseManager {
+    path: PathBuf,
+    options: OpenOptions,
+    /// The manager's own descriptor for each lease, sharing its open file description.
+    leases: Vec<(LeaseId, OwnedFd)>,
+    next_id: u64,
+}
+
+impl LeaseManager {
+    /// Lease the device node at `path`, e.g. `/dev/input/event3`, opening it like
+    /// [`Device::open`].
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self::with_options(path, &OpenOptions::new())
+    }
+
+    /// Like [`new`](Self::new), but opens the leases with the access mode and descriptor flags
+    /// set by `options`.
+    pub fn with_options(path: impl AsRef<Path>, options: &OpenOptions) -> Self {
+        LeaseManager {
+            path: path.as_ref().to_owned(),
+            options: options.clone(),
+            leases: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Lease the device node `device` was opened from, looked up through `/proc/self/fd`,
+    /// opening it with the same access mode and descriptor flags as `device`.
+    pub fn for_device(device: &Device) -> io::Result<Self> {
+        let path = std::fs::read_link(format!("/proc/self/fd/{}", device.as_raw_fd()))?;
+        Ok(Self::with_options(path, &OpenOptions::of_fd(device)?))
+    }
+
+    /// Open the device again and hand out the new descriptor.
+    pub fn lease(&mut self) -> io::Result<DeviceLease> {
+        let file = self.options.open_device(&self.path)?;
+        let fd = OwnedFd::from(file);
+        let id = LeaseId(self.next_id);
+        self.next_id += 1;
+        self.leases.push((id, fd.try_clone()?));
+        trace_event!(debug, path = %self.path.display(), id = id.0, "leased device");
+        Ok(DeviceLease { id, fd })
+    }
+
+    /// Returns the leases that haven't been revoked.
+    pub fn leases(&self) -> impl Iterator<Item = LeaseId> + '_ {
+        self.leases.iter().map(|(id, _)| *id)
+    }
+
+    /// Revoke a lease, in every process it was passed to. Returns `false` if it was already
+    /// revoked.
+    pub fn revoke(&mut self, id: LeaseId) -> io::Result<bool> {
+        let Some(i) = self.leases.iter().position(|(lease, _)| *lease == id) else {
+            return Ok(false);
+        };
+        let (_, fd) = self.leases.swap_remove(i);
+        revoke(&fd)?;
+        Ok(true)
+    }
+
+    /// Revoke every outstanding lease. All are revoked even if some fail; the first error is
+    /// returned.
+    pub fn revoke_all(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        for (_, fd) in self.leases.drain(..) {
+            let r = revoke(&fd);
+            if result.is_ok() {
+                result = r;
+            }
+        }
+        result
+    }
+}
+
+fn revoke(fd: &OwnedFd) -> io::Result<()> {
+    unsafe { sys::eviocrevoke(fd.as_raw_fd(), 0) }.map_err(ioctl_error("EVIOCREVOKE"))?;
+    Ok(())
+}
+
+impl Drop for LeaseManager {
+    fn drop(&mut self) {
+        let _ = self.revoke_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw_stream::RawDevice;
+    use nix::fcntl::{fcntl, FcntlArg, OFlag};
+    use std::fs::File;
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    fn status(fd: &impl AsRawFd) -> OFlag {
+        OFlag::from_bits_truncate(fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL).unwrap())
+    }
+
+    #[test]
+    fn pass_over_socket() -> io::Result<()> {
+        let (broker_end, client_end) = UnixStream::pair()?;
+        let lease = DeviceLease {
+            id: LeaseId(0),
+            fd: File::open("/dev/null")?.into(),
+        };
+        lease.send(&broker_end)?;
+        let fd = receive_fd(&client_end)?;
+        let path = std::fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd()))?;
+        assert_eq!(path, Path::new("/dev/null"));
+
+        // Not an evdev device
+        let lease = DeviceLease {
+            id: LeaseId(1),
+            fd: File::open("/dev/null")?.into(),
+        };
+        lease.send(&broker_end)?;
+        assert!(receive_device(&client_end).is_err());
+
+        (&broker_end).write_all(&[0])?;
+        let err = receive_fd(&client_end).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        drop(broker_end);
+        let err = receive_fd(&client_end).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+
+    #[test]
+    fn lease_options() -> io::Result<()> {
+        let mut leases = LeaseManager::new("/dev/null");
+        let lease = leases.lease()?;
+        assert_eq!(status(&lease) & OFlag::O_ACCMODE, OFlag::O_RDWR);
+
+        let options = OpenOptions::new().write(false).nonblocking(true);
+        let file = options.open_device(Path::new("/dev/null"))?;
+        let device = Device::from_raw_device(RawDevice::from_file_unchecked(file));
+        let mut leases = LeaseManager::for_device(&device)?;
+        let lease = leases.lease()?;
+        assert_eq!(status(&lease) & OFlag::O_ACCMODE, OFlag::O_RDONLY);
+        assert!(status(&lease).contains(OFlag::O_NONBLOCK));
+
+        // /dev/null can't be revoked, but the lease is given up all the same
+        assert!(leases.revoke(lease.id()).is_err());
+        assert_eq!(leases.leases().count(), 0);
+        assert!(!leases.revoke(lease.id())?);
+        Ok(())
+    }
+}
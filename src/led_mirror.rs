@@ -0,0 +1,313 @@
+//! Keeping the lock LEDs of several keyboards in step.
+//!
+//! Whoever handles the keyboards, the console or a compositor, usually sets the lock LEDs only
+//! on the keyboard the lock key was pressed on, or on none at all when the keyboards are read
+//! directly. A [`LedMirror`] watches the LEDs of a set of keyboards and, when one changes,
+//! sets it on the others. It can also toggle the LEDs from the lock keys itself, for programs
+//! that grab keyboards and so keep the console from doing it.
+//!
+//! ```no_run
+//! use evdev::led_mirror::LedMirror;
+//!
+//! let mut mirror = LedMirror::new().toggle_on_keys(true).with_hotplug()?;
+//! mirror.run()?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::hotplug::{HotplugEvent, HotplugMonitor};
+use crate::{AttributeSet, AttributeSetRef, Device, EventType, InputEvent, Key, LedType};
+
+/// The LED toggled by a lock key.
+fn lock_led(key: Key) -> Option<LedType> {
+    match key {
+        Key::KEY_NUMLOCK => Some(LedType::LED_NUML),
+        Key::KEY_CAPSLOCK => Some(LedType::LED_CAPSL),
+        Key::KEY_SCROLLLOCK => Some(LedType::LED_SCROLLL),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Leds {
+    mirrored: AttributeSet<LedType>,
+    state: AttributeSet<LedType>,
+    toggle_on_keys: bool,
+}
+
+impl Leds {
+    /// Returns the LED to set on the devices, and whether the device the event came from needs
+    /// it too.
+    fn process_event(&mut self, ev: &InputEvent) -> Option<(LedType, bool, bool)> {
+        let (led, on, to_source) = match ev.event_type() {
+            EventType::LED => (LedType(ev.code()), ev.value() != 0, false),
+            EventType::KEY if self.toggle_on_keys && ev.value() == 1 => {
+                let led = lock_led(Key::new(ev.code()))?;
+                (led, !self.state.contains(led), true)
+            }
+            _ => return None,
+        };
+        if !self.mirrored.contains(led) || self.state.contains(led) == on {
+            return None;
+        }
+        self.state.set(led, on);
+        Some((led, on, to_source))
+    }
+}
+
+struct Keyboard {
+    path: Option<PathBuf>,
+    device: Device,
+    /// Unplugged, to be dropped.
+    gone: bool,
+}
+
+/// Mirrors LEDs between keyboards. See the [module documentation](self).
+pub struct LedMirror {
+    leds: Leds,
+    keyboards: Vec<Keyboard>,
+    hotplug: Option<HotplugMonitor>,
+}
+
+impl Default for LedMirror {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_gone(e: &io::Error) -> bool {
+    crate::Error::raw_os_error_of(e) == Some(libc::ENODEV)
+}
+
+impl LedMirror {
+    /// Mirror the Num Lock, Caps Lock and Scroll Lock LEDs.
+    pub fn new() -> Self {
+        LedMirror {
+            leds: Leds {
+                mirrored: [LedType::LED_NUML, LedType::LED_CAPSL, LedType::LED_SCROLLL]
+                    .into_iter()
+                    .collect(),
+                state: AttributeSet::new(),
+                toggle_on_keys: false,
+            },
+            keyboards: Vec::new(),
+            hotplug: None,
+        }
+    }
+
+    /// Mirror `leds` instead, e.g. to include `LED_COMPOSE` and `LED_KANA`.
+    pub fn with_leds(mut self, leds: impl IntoIterator<Item = LedType>) -> Self {
+        self.leds.mirrored = leds.into_iter().collect();
+        self
+    }
+
+    /// Toggle the lock LEDs when their keys are pressed, rather than only mirroring changes
+    /// made by others. Off by default; turn it on when nothing else sets the LEDs, e.g. because
+    /// the keyboards are grabbed.
+    pub fn toggle_on_keys(mut self, toggle: bool) -> Self {
+        self.leds.toggle_on_keys = toggle;
+        self
+    }
+
+    /// Also mirror to every keyboard present in `/dev/input`, and those plugged in later.
+    pub fn with_hotplug(mut self) -> io::Result<Self> {
+        self.hotplug = Some(HotplugMonitor::new()?);
+        self.handle_hotplug()?;
+        Ok(self)
+    }
+
+    /// Returns the current state of the mirrored LEDs.
+    pub fn state(&self) -> &AttributeSetRef<LedType> {
+        &self.leds.state
+    }
+
+    /// Returns the keyboards being mirrored to.
+    pub fn devices(&self) -> impl Iterator<Item = &Device> {
+        self.keyboards.iter().map(|k| &k.device)
+    }
+
+    /// Start mirroring to `device`. Returns `false`, dropping the device, if it has none of
+    /// the mirrored LEDs.
+    ///
+    /// The first keyboard added sets the initial state; the others are set to match it.
+    pub fn add(&mut self, device: Device) -> io::Result<bool> {
+        self.add_keyboard(None, device)
+    }
+
+    fn add_keyboard(&mut self, path: Option<PathBuf>, mut device: Device) -> io::Result<bool> {
+        let supported = device
+            .supported_leds()
+            .is_some_and(|leds| leds.iter().any(|led| self.leds.mirrored.contains(led)));
+        if !supported {
+            return Ok(false);
+        }
+        if self.keyboards.is_empty() {
+            let current = device.get_led_state()?;
+            for led in self.leds.mirrored.iter() {
+                self.leds.state.set(led, current.contains(led));
+            }
+        } else {
+            let state = self.leds.state;
+            for led in self.leds.mirrored.iter() {
+                write_led(&mut device, led, state.contains(led))?;
+            }
+        }
+        self.keyboards.push(Keyboard {
+            path,
+            device,
+            gone: false,
+        });
+        Ok(true)
+    }
+
+    /// Turn a mirrored LED on or off on every keyboard.
+    pub fn set_led(&mut self, led: LedType, on: bool) -> io::Result<()> {
+        if self.leds.mirrored.contains(led) {
+            self.leds.state.set(led, on);
+            self.write_all(None, led, on)?;
+            self.keyboards.retain(|k| !k.gone);
+        }
+        Ok(())
+    }
+
+    /// Set `led` on every keyboard except `except`, marking those that were unplugged.
+    fn write_all(&mut self, except: Option<usize>, led: LedType, on: bool) -> io::Result<()> {
+        for (i, keyboard) in self.keyboards.iter_mut().enumerate() {
+            if except == Some(i) || keyboard.gone {
+                continue;
+            }
+            match write_led(&mut keyboard.device, led, on) {
+                Err(e) if is_gone(&e) => keyboard.gone = true,
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_hotplug(&mut self) -> io::Result<()> {
+        let Some(hotplug) = &mut self.hotplug else {
+            return Ok(());
+        };
+        for event in hotplug.fetch_events(Some(Duration::ZERO))? {
+            match event {
+                HotplugEvent::Added { path, device } => {
+                    self.add_keyboard(Some(path), *device)?;
+                }
+                HotplugEvent::Removed { path } => {
+                    self.keyboards.retain(|k| k.path.as_ref() != Some(&path));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait up to `timeout`, or indefinitely if `None`, for LED changes and lock key presses,
+    /// and mirror them.
+    pub fn fetch_events(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let mut fds: Vec<_> = self
+            .keyboards
+            .iter()
+            .map(|k| PollFd::new(k.device.as_raw_fd(), PollFlags::POLLIN))
+            .chain(
+                self.hotplug
+                    .iter()
+                    .map(|h| PollFd::new(h.as_raw_fd(), PollFlags::POLLIN)),
+            )
+            .collect();
+        let millis = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        poll(&mut fds, millis)?;
+        let ready = |fd: &PollFd| fd.revents().is_some_and(|r| !r.is_empty());
+        let ready: Vec<bool> = fds.iter().map(ready).collect();
+
+        let mut changes = Vec::new();
+        for (i, keyboard) in self.keyboards.iter_mut().enumerate() {
+            if !ready[i] {
+                continue;
+            }
+            match keyboard.device.fetch_events() {
+                Ok(events) => {
+                    for ev in events {
+                        if let Some(change) = self.leds.process_event(&ev) {
+                            changes.push((i, change));
+                        }
+                    }
+                }
+                Err(e) if is_gone(&e) => keyboard.gone = true,
+                Err(e) => return Err(e),
+            }
+        }
+        for (source, (led, on, to_source)) in changes {
+            self.write_all((!to_source).then_some(source), led, on)?;
+        }
+        self.keyboards.retain(|k| !k.gone);
+        if self.hotplug.is_some() && ready.last() == Some(&true) {
+            self.handle_hotplug()?;
+        }
+        Ok(())
+    }
+
+    /// Mirror until an error occurs.
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.fetch_events(None)?;
+        }
+    }
+}
+
+fn write_led(device: &mut Device, led: LedType, on: bool) -> io::Result<()> {
+    if !device
+        .supported_leds()
+        .is_some_and(|leds| leds.contains(led))
+    {
+        return Ok(());
+    }
+    device.send_events(&[InputEvent::new(EventType::LED, led.0, on.into())])
+}
+
+impl std::fmt::Debug for LedMirror {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("LedMirror")
+            .field("state", &self.leds.state)
+            .field("keyboards", &self.keyboards.len())
+            .field("hotplug", &self.hotplug.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirror_led_changes() {
+        let mut leds = Leds {
+            mirrored: [LedType::LED_CAPSL, LedType::LED_NUML]
+                .into_iter()
+                .collect(),
+            state: AttributeSet::new(),
+            toggle_on_keys: false,
+        };
+        let led = |led: LedType, on| InputEvent::new(EventType::LED, led.0, on);
+        let caps = InputEvent::new(EventType::KEY, Key::KEY_CAPSLOCK.code(), 1);
+
+        assert_eq!(
+            leds.process_event(&led(LedType::LED_CAPSL, 1)),
+            Some((LedType::LED_CAPSL, true, false))
+        );
+        // The echo of mirroring it to the other keyboards changes nothing
+        assert_eq!(leds.process_event(&led(LedType::LED_CAPSL, 1)), None);
+        assert_eq!(leds.process_event(&led(LedType::LED_SCROLLL, 1)), None);
+        assert_eq!(leds.process_event(&caps), None);
+
+        leds.toggle_on_keys = true;
+        assert_eq!(
+            leds.process_event(&caps),
+            Some((LedType::LED_CAPSL, false, true))
+        );
+    }
+}
@@ -88,31 +88,103 @@
 // should really be cfg(target_os = "linux") and maybe also android?
 #![cfg(unix)]
 
-// has to be first for its macro
 #[macro_use]
-mod attribute_set;
+mod trace;
 
-mod constants;
+pub mod activation;
+pub mod audit;
+mod battery;
+pub mod broadcast;
+mod class;
+pub mod clock;
+pub mod combo;
+#[cfg(feature = "console")]
+pub mod console;
+pub mod consumer;
 mod device_state;
+pub mod dwell;
+mod error;
+mod ff;
+pub mod filter;
+mod finger_tracker;
+mod frame;
+pub mod gamecontrollerdb;
+pub mod gamepad;
+pub mod getevent;
+pub mod gpio_keys;
+pub mod group;
+mod hat;
+pub mod hid;
+pub mod hotkey;
+pub mod hotplug;
+pub mod idle;
 mod inputid;
+pub mod kbm_gamepad;
+pub mod keymap;
+pub mod latency;
+pub mod lease;
+pub mod led_mirror;
+pub mod liveness;
+#[cfg(feature = "logind")]
+pub mod logind;
+pub mod mask;
+pub mod merge;
+mod metrics;
+pub mod modifiers;
+mod open_options;
+pub mod power;
+pub mod presets;
+pub mod proxy;
+pub mod raw;
 pub mod raw_stream;
-mod scancodes;
+mod report;
+pub mod rotary;
+mod rumble;
+mod scancode_pairing;
+pub mod scanning;
+pub mod sensor;
+pub mod session;
+pub mod slots;
+pub mod spsc;
 mod sync_stream;
 mod sys;
+mod text;
+pub mod transform;
+pub mod uhid;
 pub mod uinput;
+mod validate;
+mod vt;
+mod watchdog;
+#[cfg(feature = "xkb")]
+pub mod xkb;
 
 use std::fmt;
 use std::path::PathBuf;
 use std::time::{Duration, SystemTime};
 
-// pub use crate::constants::FFEffect::*;
-pub use attribute_set::{AttributeSet, AttributeSetRef};
+pub use battery::{Battery, BatteryStatus, CapacityLevel};
+pub use class::DeviceClass;
 pub use constants::*;
 pub use device_state::DeviceState;
+pub use error::{DeviceHolder, Error, OpenFailure};
+use evdev_core::{attribute_set, constants, scancodes};
+pub use evdev_core::{AttributeSet, AttributeSetRef, EnumParseError};
+pub use evdev_core::{Endianness, FrameParser, RawEvent, TimeWidth, WireFormat};
+pub use ff::*;
+pub use finger_tracker::FingerTracker;
+pub use frame::{Frame, FrameIter, DEFAULT_FRAME_CAPACITY};
+pub use hat::{HatDirection, HatTracker};
 pub use inputid::*;
-pub use raw_stream::AutoRepeat;
+pub use metrics::Metrics;
+pub use open_options::OpenOptions;
+pub use raw_stream::{AutoRepeat, KeymapEntry};
+pub use report::{CapabilityReport, MetadataChanges};
+pub use rumble::{RumblePattern, RumblePlayback, RumbleSegment};
+pub use scancode_pairing::{KeyEventWithScancode, ScancodePairer, ScancodePairs};
 pub use scancodes::*;
 pub use sync_stream::*;
+pub use validate::{StreamValidator, Violation};
+pub use vt::VtGrab;
 
 const EVENT_BATCH_SIZE: usize = 32;
 
@@ -129,6 +201,7 @@ pub enum InputEventKind {
     Switch(SwitchType),
     Led(LedType),
     Sound(SoundType),
+    ForceFeedbackStatus(EffectStatus),
     Other,
 }
 
@@ -181,6 +254,10 @@ impl InputEvent {
             EventType::SWITCH => InputEventKind::Switch(SwitchType(code)),
             EventType::LED => InputEventKind::Led(LedType(code)),
             EventType::SOUND => InputEventKind::Sound(SoundType(code)),
+            EventType::FORCEFEEDBACKSTATUS => InputEventKind::ForceFeedbackStatus(EffectStatus {
+                id: code as i16,
+                playing: self.value() == FF_STATUS_PLAYING,
+            }),
             _ => InputEventKind::Other,
         }
     }
@@ -229,6 +306,34 @@ impl From<libc::input_event> for InputEvent {
     }
 }
 
+impl From<RawEvent> for InputEvent {
+    fn from(raw: RawEvent) -> Self {
+        InputEvent(libc::input_event {
+            time: libc::timeval {
+                tv_sec: raw.sec as libc::time_t,
+                tv_usec: raw.usec as libc::suseconds_t,
+            },
+            type_: raw.type_,
+            code: raw.code,
+            value: raw.value,
+        })
+    }
+}
+
+impl From<InputEvent> for RawEvent {
+    // time_t is 32 bits wide on some targets
+    #[allow(clippy::unnecessary_cast)]
+    fn from(ev: InputEvent) -> Self {
+        RawEvent {
+            sec: ev.0.time.tv_sec as i64,
+            usec: ev.0.time.tv_usec as u32,
+            type_: ev.0.type_,
+            code: ev.0.code,
+            value: ev.0.value,
+        }
+    }
+}
+
 impl AsRef<libc::input_event> for InputEvent {
     fn as_ref(&self) -> &libc::input_event {
         &self.0
@@ -251,6 +356,257 @@ impl fmt::Debug for InputEvent {
     }
 }
 
+/// A wrapped `libc::input_absinfo` describing the range and state of an absolute axis.
+///
+/// `input_absinfo` is a struct containing six fields:
+/// - `value: s32`
+/// - `minimum: s32`
+/// - `maximum: s32`
+/// - `fuzz: s32`
+/// - `flat: s32`
+/// - `resolution: s32`
+#[derive(Copy, Clone)]
+#[repr(transparent)]
+pub struct AbsInfo(libc::input_absinfo);
+
+impl AbsInfo {
+    /// Returns the current value of the axis.
+    #[inline]
+    pub fn value(&self) -> i32 {
+        self.0.value
+    }
+
+    /// Returns the minimum value the axis can report.
+    #[inline]
+    pub fn minimum(&self) -> i32 {
+        self.0.minimum
+    }
+
+    /// Returns the maximum value the axis can report.
+    #[inline]
+    pub fn maximum(&self) -> i32 {
+        self.0.maximum
+    }
+
+    /// Returns the fuzz value, used to filter noise from the event stream.
+    #[inline]
+    pub fn fuzz(&self) -> i32 {
+        self.0.fuzz
+    }
+
+    /// Returns the flat value. Values within this distance of the center are reported as the
+    /// center by joysticks.
+    #[inline]
+    pub fn flat(&self) -> i32 {
+        self.0.flat
+    }
+
+    /// Returns the resolution of the axis, in units per millimeter (or units per radian for
+    /// rotational axes).
+    #[inline]
+    pub fn resolution(&self) -> i32 {
+        self.0.resolution
+    }
+
+    /// Create a new AbsInfo, useful for setting up absolute axes on virtual devices.
+    pub fn new(
+        value: i32,
+        minimum: i32,
+        maximum: i32,
+        fuzz: i32,
+        flat: i32,
+        resolution: i32,
+    ) -> Self {
+        AbsInfo(libc::input_absinfo {
+            value,
+            minimum,
+            maximum,
+            fuzz,
+            flat,
+            resolution,
+        })
+    }
+
+    /// Returns the midpoint of the axis range, which is where centered axes such as joystick
+    /// sticks rest.
+    pub fn center(&self) -> i32 {
+        ((i64::from(self.minimum()) + i64::from(self.maximum())) / 2) as i32
+    }
+
+    /// Scale `value` from the axis range to `[-1.0, 1.0]`, clamping values outside the range.
+    ///
+    /// ```
+    /// use evdev::AbsInfo;
+    ///
+    /// let stick = AbsInfo::new(0, -32768, 32767, 16, 128, 0);
+    /// assert_eq!(stick.normalize(-32768), -1.0);
+    /// assert_eq!(stick.normalize(32767), 1.0);
+    /// assert_eq!(stick.denormalize(1.0), 32767);
+    /// ```
+    pub fn normalize(&self, value: i32) -> f32 {
+        let (min, max) = (f64::from(self.minimum()), f64::from(self.maximum()));
+        if max <= min {
+            return 0.0;
+        }
+        let value = f64::from(value).clamp(min, max);
+        ((value - min) / (max - min) * 2.0 - 1.0) as f32
+    }
+
+    /// Scale `value` from `[0.0, 1.0]` to the axis range, for axes such as triggers and
+    /// pressure that rest at their minimum.
+    pub fn normalize_unsigned(&self, value: i32) -> f32 {
+        (self.normalize(value) + 1.0) / 2.0
+    }
+
+    /// The inverse of [`normalize`](Self::normalize): scale `value` from `[-1.0, 1.0]` to the
+    /// axis range, rounding to the nearest integer.
+    pub fn denormalize(&self, value: f32) -> i32 {
+        let (min, max) = (f64::from(self.minimum()), f64::from(self.maximum()));
+        let value = f64::from(value).clamp(-1.0, 1.0);
+        (min + (value + 1.0) / 2.0 * (max - min)).round() as i32
+    }
+
+    /// The inverse of [`normalize_unsigned`](Self::normalize_unsigned).
+    pub fn denormalize_unsigned(&self, value: f32) -> i32 {
+        self.denormalize(value * 2.0 - 1.0)
+    }
+
+    /// Returns `true` if `value` is within the flat of the center.
+    pub fn is_flat(&self, value: i32) -> bool {
+        (i64::from(value) - i64::from(self.center())).abs() <= i64::from(self.flat())
+    }
+
+    /// Like [`normalize`](Self::normalize), but treats the flat around the center as a dead
+    /// zone: values within it are reported as `0.0`, and the rest of the range is stretched so
+    /// the output still reaches `-1.0` and `1.0`.
+    pub fn normalize_with_flat(&self, value: i32) -> f32 {
+        if self.is_flat(value) {
+            return 0.0;
+        }
+        let half = (f64::from(self.maximum()) - f64::from(self.minimum())) / 2.0;
+        if half <= 0.0 {
+            return 0.0;
+        }
+        let flat = f64::from(self.flat().max(0));
+        if half <= flat {
+            return self.normalize(value).signum();
+        }
+        let n = f64::from(self.normalize(value));
+        let dead = flat / half;
+        (n.signum() * (n.abs() - dead) / (1.0 - dead)) as f32
+    }
+
+    /// Filter noise the way the kernel does for axes with a fuzz: changes smaller than half the
+    /// fuzz are dropped, and changes smaller than twice the fuzz are smoothed towards the
+    /// `previous` value.
+    ///
+    /// Events read from a device are already filtered; this is for values from other sources,
+    /// such as hidraw reports or recorded traces.
+    pub fn defuzz(&self, previous: i32, value: i32) -> i32 {
+        let fuzz = i64::from(self.fuzz());
+        if fuzz <= 0 {
+            return value;
+        }
+        let (old, new) = (i64::from(previous), i64::from(value));
+        let delta = (new - old).abs();
+        let filtered = if delta < fuzz / 2 {
+            old
+        } else if delta < fuzz {
+            (old * 3 + new) / 4
+        } else if delta < fuzz * 2 {
+            (old + new) / 2
+        } else {
+            new
+        };
+        filtered as i32
+    }
+
+    /// Convert `value` to physical units using the resolution: millimeters for position axes
+    /// and radians for rotational ones. Returns `None` if the device doesn't report a
+    /// resolution.
+    pub fn to_physical(&self, value: i32) -> Option<f32> {
+        let resolution = self.resolution();
+        (resolution > 0).then(|| value as f32 / resolution as f32)
+    }
+
+    /// Returns the physical size of the axis range, in the units of
+    /// [`to_physical`](Self::to_physical).
+    pub fn physical_range(&self) -> Option<f32> {
+        let resolution = self.resolution();
+        (resolution > 0).then(|| {
+            (f64::from(self.maximum()) - f64::from(self.minimum())) as f32 / resolution as f32
+        })
+    }
+
+    /// Convert a physical quantity to axis units, the inverse of
+    /// [`to_physical`](Self::to_physical).
+    pub fn value_from_physical(&self, value: f32) -> Option<i32> {
+        let resolution = self.resolution();
+        (resolution > 0).then(|| (value * resolution as f32).round() as i32)
+    }
+}
+
+impl From<libc::input_absinfo> for AbsInfo {
+    fn from(raw: libc::input_absinfo) -> Self {
+        Self(raw)
+    }
+}
+
+impl AsRef<libc::input_absinfo> for AbsInfo {
+    fn as_ref(&self) -> &libc::input_absinfo {
+        &self.0
+    }
+}
+
+impl fmt::Debug for AbsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AbsInfo")
+            .field("value", &self.value())
+            .field("minimum", &self.minimum())
+            .field("maximum", &self.maximum())
+            .field("fuzz", &self.fuzz())
+            .field("flat", &self.flat())
+            .field("resolution", &self.resolution())
+            .finish()
+    }
+}
+
+/// A wrapped `libc::uinput_abs_setup`, used to set up an absolute axis on a virtual device.
+#[derive(Copy, Clone)]
+#[repr(transparent)]
+pub struct UinputAbsSetup(libc::uinput_abs_setup);
+
+impl UinputAbsSetup {
+    /// Returns the axis this setup applies to.
+    #[inline]
+    pub fn axis(&self) -> AbsoluteAxisType {
+        AbsoluteAxisType(self.0.code)
+    }
+
+    /// Returns the range and initial state of the axis.
+    #[inline]
+    pub fn abs_info(&self) -> AbsInfo {
+        AbsInfo(self.0.absinfo)
+    }
+
+    /// Create a new UinputAbsSetup for the given axis.
+    pub fn new(axis: AbsoluteAxisType, abs_info: AbsInfo) -> Self {
+        UinputAbsSetup(libc::uinput_abs_setup {
+            code: axis.0,
+            absinfo: abs_info.0,
+        })
+    }
+}
+
+impl fmt::Debug for UinputAbsSetup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UinputAbsSetup")
+            .field("axis", &self.axis())
+            .field("abs_info", &self.abs_info())
+            .finish()
+    }
+}
+
 /// Crawls `/dev/input` for evdev devices.
 ///
 /// Will not bubble up any errors in opening devices or traversing the directory. Instead returns
@@ -287,7 +643,7 @@ fn systime_to_timeval(time: &SystemTime) -> libc::timeval {
 }
 
 fn timeval_to_systime(tv: &libc::timeval) -> SystemTime {
-    let dur = Duration::new(tv.tv_sec.abs() as u64, tv.tv_usec as u32 * 1000);
+    let dur = Duration::new(tv.tv_sec.unsigned_abs(), tv.tv_usec as u32 * 1000);
     if tv.tv_sec >= 0 {
         SystemTime::UNIX_EPOCH + dur
     } else {
@@ -300,5 +656,100 @@ pub(crate) unsafe fn cast_to_bytes<T: ?Sized>(mem: &T) -> &[u8] {
     std::slice::from_raw_parts(mem as *const T as *const u8, std::mem::size_of_val(mem))
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct EnumParseError(());
+// The core crate can't see libc, so make sure its counts agree with the kernel headers
+const _: () = {
+    assert!(EventType::COUNT == libc::EV_CNT);
+    assert!(PropType::COUNT == libc::INPUT_PROP_CNT);
+    assert!(RelativeAxisType::COUNT == libc::REL_CNT);
+    assert!(AbsoluteAxisType::COUNT == libc::ABS_CNT);
+    assert!(SwitchType::COUNT == libc::SW_CNT);
+    assert!(LedType::COUNT == libc::LED_CNT);
+    assert!(MiscType::COUNT == libc::MSC_CNT);
+    assert!(FFEffectType::COUNT == libc::FF_CNT);
+    assert!(SoundType::COUNT == libc::SND_CNT);
+    assert!(Key::COUNT == libc::KEY_CNT);
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn abs_info_scaling() {
+        let trigger = AbsInfo::new(0, 0, 255, 0, 0, 0);
+        assert_close(trigger.normalize(0), -1.0);
+        assert_close(trigger.normalize(51), -0.6);
+        assert_close(trigger.normalize(255), 1.0);
+        assert_close(trigger.normalize(1000), 1.0);
+        assert_close(trigger.normalize_unsigned(51), 0.2);
+        let offset = AbsInfo::new(0, -100, 300, 0, 0, 0);
+        assert_eq!(offset.center(), 100);
+        assert_close(offset.normalize(0), -0.5);
+        assert_close(offset.normalize(100), 0.0);
+
+        for info in [trigger, offset, AbsInfo::new(0, -32768, 32767, 0, 0, 0)] {
+            for value in [
+                info.minimum(),
+                info.minimum() + 1,
+                info.center(),
+                info.maximum(),
+            ] {
+                assert_eq!(info.denormalize(info.normalize(value)), value);
+                assert_eq!(
+                    info.denormalize_unsigned(info.normalize_unsigned(value)),
+                    value
+                );
+            }
+        }
+
+        // Empty and inverted ranges have no meaningful scale
+        for info in [
+            AbsInfo::new(0, 5, 5, 0, 0, 0),
+            AbsInfo::new(0, 10, -10, 0, 2, 0),
+        ] {
+            assert_eq!(info.normalize(7), 0.0);
+            assert_eq!(info.normalize_with_flat(7), 0.0);
+        }
+        assert_eq!(AbsInfo::new(0, 5, 5, 0, 0, 0).denormalize(1.0), 5);
+    }
+
+    #[test]
+    fn abs_info_flat() {
+        let stick = AbsInfo::new(0, -100, 100, 0, 20, 0);
+        assert_eq!(stick.normalize_with_flat(20), 0.0);
+        assert_eq!(stick.normalize_with_flat(-20), 0.0);
+        assert_close(stick.normalize_with_flat(60), 0.5);
+        assert_close(stick.normalize_with_flat(-100), -1.0);
+
+        // A flat wider than half the range leaves only values past the ends
+        let wide = AbsInfo::new(0, -10, 10, 0, 15, 0);
+        assert_eq!(wide.normalize_with_flat(10), 0.0);
+        assert_eq!(wide.normalize_with_flat(30), 1.0);
+        assert_eq!(wide.normalize_with_flat(-30), -1.0);
+    }
+
+    #[test]
+    fn abs_info_defuzz() {
+        let info = AbsInfo::new(0, 0, 1000, 8, 0, 0);
+        assert_eq!(info.defuzz(100, 103), 100);
+        assert_eq!(info.defuzz(100, 104), 101);
+        assert_eq!(info.defuzz(100, 94), 98);
+        assert_eq!(info.defuzz(100, 108), 104);
+        assert_eq!(info.defuzz(100, 115), 107);
+        assert_eq!(info.defuzz(100, 116), 116);
+        assert_eq!(AbsInfo::new(0, 0, 1000, 0, 0, 0).defuzz(100, 101), 101);
+    }
+
+    #[test]
+    fn abs_info_physical() {
+        let info = AbsInfo::new(0, 0, 3000, 0, 0, 30);
+        assert_eq!(info.to_physical(150), Some(5.0));
+        assert_eq!(info.physical_range(), Some(100.0));
+        assert_eq!(info.value_from_physical(5.0), Some(150));
+        assert_eq!(AbsInfo::new(0, 0, 3000, 0, 0, 0).to_physical(150), None);
+    }
+}
@@ -0,0 +1,235 @@
+//! Noticing input devices that stop reporting.
+//!
+//! Touch controllers and other devices behind flaky buses occasionally wedge: the device node
+//! stays open, but no more events arrive. A [`LivenessWatchdog`] remembers when each device
+//! last showed signs of life and reports a [`LivenessEvent::Stalled`] once that is longer ago
+//! than the window, so a kiosk can rebind the driver or power cycle the controller.
+//!
+//! Most devices are silent while nobody touches them, so watching for any event only suits
+//! devices that report continuously. Controllers that send `MSC_TIMESTAMP` heartbeats can be
+//! watched for those alone with [`heartbeat`](LivenessWatchdog::heartbeat).
+//!
+//! ```no_run
+//! use evdev::liveness::{LivenessEvent, LivenessWatchdog};
+//! use evdev::Device;
+//! use std::time::Duration;
+//!
+//! let mut devices = [Device::open("/dev/input/event3")?];
+//! let mut watchdog = LivenessWatchdog::new(Duration::from_secs(5)).heartbeat(true);
+//! watchdog.run(&mut devices, |ev| {
+//!     if let LivenessEvent::Stalled { device, .. } = ev {
+//!         eprintln!("device {} stopped reporting", device);
+//!     }
+//! })?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::{Device, EventType, InputEvent, MiscType};
+
+/// A change in whether a device is alive. Devices are numbered in the order they were
+/// [watched](LivenessWatchdog::watch).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LivenessEvent {
+    /// Nothing arrived from `device` within the window since `last_seen`.
+    Stalled {
+        device: usize,
+        last_seen: SystemTime,
+    },
+    /// A stalled device reported again.
+    Recovered { device: usize },
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Watched {
+    last_seen: SystemTime,
+    stalled: bool,
+}
+
+/// Reports devices that stop sending events. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct LivenessWatchdog {
+    window: Duration,
+    heartbeat: bool,
+    devices: Vec<Watched>,
+    clock: SharedClock,
+}
+
+impl LivenessWatchdog {
+    /// Create a watchdog reporting devices that are silent for longer than `window`.
+    pub fn new(window: Duration) -> Self {
+        LivenessWatchdog {
+            window,
+            heartbeat: false,
+            devices: Vec::new(),
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Only count `MSC_TIMESTAMP` events as signs of life, ignoring everything else.
+    pub fn heartbeat(mut self, heartbeat: bool) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// Take the time of events and in [`run`](Self::run) from `clock` rather than the system
+    /// clock.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Start watching another device, as if it had last reported at `now`. Returns its number.
+    pub fn watch(&mut self, now: SystemTime) -> usize {
+        self.devices.push(Watched {
+            last_seen: now,
+            stalled: false,
+        });
+        self.devices.len() - 1
+    }
+
+    /// Returns `true` if `device` is currently reported as stalled.
+    pub fn is_stalled(&self, device: usize) -> bool {
+        self.devices.get(device).is_some_and(|d| d.stalled)
+    }
+
+    /// Returns when `device` last showed signs of life.
+    pub fn last_seen(&self, device: usize) -> Option<SystemTime> {
+        self.devices.get(device).map(|d| d.last_seen)
+    }
+
+    /// Record an event from `device`, appending a [`LivenessEvent::Recovered`] to `out` if it
+    /// was stalled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `device` isn't being watched.
+    pub fn observe(&mut self, device: usize, ev: &InputEvent, out: &mut Vec<LivenessEvent>) {
+        let alive = if self.heartbeat {
+            ev.event_type() == EventType::MISC && ev.code() == MiscType::MSC_TIMESTAMP.0
+        } else {
+            true
+        };
+        if !alive {
+            return;
+        }
+        let watched = &mut self.devices[device];
+        watched.last_seen = watched.last_seen.max(self.clock.event_time(ev));
+        if watched.stalled {
+            watched.stalled = false;
+            out.push(LivenessEvent::Recovered { device });
+        }
+    }
+
+    /// Returns the next time a device becomes stalled unless it reports.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        self.devices
+            .iter()
+            .filter(|d| !d.stalled)
+            .map(|d| d.last_seen + self.window)
+            .min()
+    }
+
+    /// Append a [`LivenessEvent::Stalled`] to `out` for every device silent for longer than
+    /// the window as of `now`.
+    pub fn check(&mut self, now: SystemTime, out: &mut Vec<LivenessEvent>) {
+        for (device, watched) in self.devices.iter_mut().enumerate() {
+            if !watched.stalled && watched.last_seen + self.window <= now {
+                watched.stalled = true;
+                out.push(LivenessEvent::Stalled {
+                    device,
+                    last_seen: watched.last_seen,
+                });
+            }
+        }
+    }
+
+    /// Watch `devices`, numbered by their index, and call `f` with every change until an
+    /// error occurs.
+    pub fn run(
+        &mut self,
+        devices: &mut [Device],
+        mut f: impl FnMut(LivenessEvent),
+    ) -> io::Result<()> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        while self.devices.len() < devices.len() {
+            self.watch(self.clock.now());
+        }
+        let mut out = Vec::new();
+        loop {
+            let timeout = self.next_deadline().map_or(-1, |deadline| {
+                let remaining = self.clock.until(deadline);
+                remaining.as_millis().clamp(1, i32::MAX as u128) as i32
+            });
+            let mut fds: Vec<_> = devices
+                .iter()
+                .map(|device| PollFd::new(device.as_raw_fd(), PollFlags::POLLIN))
+                .collect();
+            poll(&mut fds, timeout)?;
+            for (index, (fd, device)) in fds.iter().zip(devices.iter_mut()).enumerate() {
+                if fd.revents().is_some_and(|r| !r.is_empty()) {
+                    for ev in device.fetch_events()? {
+                        self.observe(index, &ev, &mut out);
+                    }
+                }
+            }
+            self.check(self.clock.now(), &mut out);
+            out.drain(..).for_each(&mut f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stall_and_recover() {
+        let at = |ms| SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+        let event = |type_, code, ms| {
+            let mut ev = InputEvent::new(type_, code, 0);
+            ev.0.time = crate::systime_to_timeval(&at(ms));
+            ev
+        };
+        let mut watchdog = LivenessWatchdog::new(Duration::from_secs(1)).heartbeat(true);
+        let device = watchdog.watch(at(0));
+        let mut out = Vec::new();
+
+        watchdog.observe(
+            device,
+            &event(EventType::MISC, MiscType::MSC_TIMESTAMP.0, 500),
+            &mut out,
+        );
+        watchdog.check(at(1200), &mut out);
+        assert!(out.is_empty());
+        assert_eq!(watchdog.next_deadline(), Some(at(1500)));
+
+        // Other events aren't heartbeats
+        watchdog.observe(
+            device,
+            &event(EventType::SYNCHRONIZATION, 0, 1400),
+            &mut out,
+        );
+        watchdog.check(at(1500), &mut out);
+        watchdog.check(at(1600), &mut out);
+        watchdog.observe(
+            device,
+            &event(EventType::MISC, MiscType::MSC_TIMESTAMP.0, 2000),
+            &mut out,
+        );
+        assert_eq!(
+            out,
+            [
+                LivenessEvent::Stalled {
+                    device,
+                    last_seen: at(500)
+                },
+                LivenessEvent::Recovered { device },
+            ]
+        );
+    }
+}
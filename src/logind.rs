@@ -0,0 +1,131 @@
+//! Opening devices through systemd-logind.
+//!
+//! The controller of a logind session, typically a compositor or kiosk shell, can have logind
+//! open input devices on its behalf with `TakeDevice`. This works without root or membership
+//! of the `input` group, and logind revokes the fds while the session is in the background, so
+//! an inactive session can't snoop on input.
+//!
+//! ```no_run
+//! use evdev::logind::LogindSession;
+//!
+//! let session = LogindSession::open()?;
+//! let mut device = session.take_device("/dev/input/event0")?;
+//! for ev in device.fetch_events()? {
+//!     println!("{:?}", ev);
+//! }
+//! session.release_device(&device)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use nix::sys::stat::{fstat, major, minor};
+use zbus_5::blocking::{Connection, Proxy};
+use zbus_5::zvariant::{self, OwnedObjectPath};
+
+use crate::raw_stream::RawDevice;
+use crate::Device;
+
+const LOGIND: &str = "org.freedesktop.login1";
+
+/// Control of a logind session, used to open devices through logind.
+///
+/// Control is released when this is dropped, which also revokes every device taken through
+/// it.
+pub struct LogindSession {
+    session: Proxy<'static>,
+}
+
+fn dbus_error(e: zbus_5::Error) -> io::Error {
+    match e {
+        zbus_5::Error::InputOutput(e) => io::Error::new(e.kind(), e.to_string()),
+        zbus_5::Error::MethodError(ref name, ..) if name.as_str().ends_with(".AccessDenied") => {
+            io::Error::new(io::ErrorKind::PermissionDenied, e)
+        }
+        e => io::Error::other(e),
+    }
+}
+
+impl LogindSession {
+    /// Take control of the session the calling process belongs to.
+    ///
+    /// Fails if the process isn't part of a session, or another process already controls it.
+    pub fn open() -> io::Result<Self> {
+        let conn = Connection::system().map_err(dbus_error)?;
+        let manager = Proxy::new(
+            &conn,
+            LOGIND,
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+        )
+        .map_err(dbus_error)?;
+        let path: OwnedObjectPath = manager.call("GetSession", &("auto",)).map_err(dbus_error)?;
+        Self::with_session(&conn, path)
+    }
+
+    /// Take control of the session at the given object path, e.g.
+    /// `/org/freedesktop/login1/session/_32`.
+    pub fn with_session(conn: &Connection, path: OwnedObjectPath) -> io::Result<Self> {
+        let session = Proxy::new_owned(
+            conn.clone(),
+            LOGIND.to_owned(),
+            path,
+            "org.freedesktop.login1.Session".to_owned(),
+        )
+        .map_err(dbus_error)?;
+        session
+            .call::<_, _, ()>("TakeControl", &(false,))
+            .map_err(dbus_error)?;
+        trace_event!(debug, path = %session.path(), "took control of logind session");
+        Ok(LogindSession { session })
+    }
+
+    /// Returns `true` if the session is in the foreground, i.e. its devices are usable.
+    pub fn is_active(&self) -> io::Result<bool> {
+        self.session.get_property("Active").map_err(dbus_error)
+    }
+
+    /// Open the device at `path` through logind.
+    ///
+    /// If the session is currently inactive, logind hands out the device already paused and
+    /// reads fail until the session comes back to the foreground.
+    pub fn take_device(&self, path: impl AsRef<Path>) -> io::Result<Device> {
+        let rdev = std::fs::metadata(path.as_ref())?.rdev();
+        let (fd, _inactive): (zvariant::OwnedFd, bool) = self
+            .session
+            .call("TakeDevice", &(major(rdev) as u32, minor(rdev) as u32))
+            .map_err(dbus_error)?;
+        trace_event!(debug, path = %path.as_ref().display(), inactive = _inactive, "took device from logind");
+        let file = File::from(OwnedFd::from(fd));
+        RawDevice::from_file(file).map(Device::from_raw_device)
+    }
+
+    /// Hand a device obtained from [`take_device`](Self::take_device) back to logind.
+    ///
+    /// The device's fd is revoked, so the `Device` should be dropped afterwards.
+    pub fn release_device(&self, device: &Device) -> io::Result<()> {
+        let rdev = fstat(device.as_raw_fd())?.st_rdev;
+        self.session
+            .call::<_, _, ()>("ReleaseDevice", &(major(rdev) as u32, minor(rdev) as u32))
+            .map_err(dbus_error)
+    }
+}
+
+impl Drop for LogindSession {
+    fn drop(&mut self) {
+        let _ = self.session.call::<_, _, ()>("ReleaseControl", &());
+    }
+}
+
+impl std::fmt::Debug for LogindSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("LogindSession")
+            .field("path", &self.session.path().as_str())
+            .finish()
+    }
+}
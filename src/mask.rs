@@ -0,0 +1,384 @@
+//! Sharing one device between consumers interested in different events.
+//!
+//! Since Linux 4.4, `EVIOCSMASK` lets a process tell the kernel which events it wants from a
+//! file descriptor, so events nobody wants don't wake it up or fill the buffer. Several parts
+//! of a program, such as hotkey handling that only wants a few keys and pointer handling that
+//! only wants motion and buttons, can declare what they want to an [`EventMaskManager`]. It
+//! masks the device to the union of their interests and hands each consumer only the events
+//! it asked for, through a single descriptor.
+//!
+//! ```no_run
+//! use evdev::mask::{EventMask, EventMaskManager};
+//! use evdev::{Key, RelativeAxisType};
+//!
+//! let device = evdev::Device::open("/dev/input/event3")?;
+//! let mut manager = EventMaskManager::new(device)?;
+//! let hotkeys = manager.add_consumer(EventMask::new().with_keys([Key::KEY_F12]))?;
+//! let pointer = manager.add_consumer(
+//!     EventMask::new()
+//!         .with_relative_axes([RelativeAxisType::REL_X, RelativeAxisType::REL_Y])
+//!         .with_keys([Key::BTN_LEFT, Key::BTN_RIGHT]),
+//! )?;
+//! loop {
+//!     for (consumer, ev) in manager.fetch_events()? {
+//!         if consumer == hotkeys {
+//!             println!("hotkey: {:?}", ev);
+//!         } else if consumer == pointer {
+//!             println!("pointer: {:?}", ev);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use crate::constants::*;
+use crate::{Device, EventType, InputEvent, Key};
+
+/// The number of codes of an event type, if the kernel can mask it.
+fn code_count(ty: EventType) -> Option<usize> {
+    Some(match ty {
+        EventType::KEY => Key::COUNT,
+        EventType::RELATIVE => RelativeAxisType::COUNT,
+        EventType::ABSOLUTE => AbsoluteAxisType::COUNT,
+        EventType::MISC => MiscType::COUNT,
+        EventType::SWITCH => SwitchType::COUNT,
+        EventType::LED => LedType::COUNT,
+        EventType::SOUND => SoundType::COUNT,
+        EventType::FORCEFEEDBACK => FFEffectType::COUNT,
+        _ => return None,
+    })
+}
+
+/// A set of events, by type and code.
+///
+/// Synchronization events, and events of other types the kernel can't mask, always match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventMask {
+    /// Bitmaps of codes, by event type.
+    codes: BTreeMap<u16, Vec<u8>>,
+}
+
+impl EventMask {
+    /// An empty mask, matching only events that can't be masked.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A mask matching every event.
+    pub fn all() -> Self {
+        [
+            EventType::KEY,
+            EventType::RELATIVE,
+            EventType::ABSOLUTE,
+            EventType::MISC,
+            EventType::SWITCH,
+            EventType::LED,
+            EventType::SOUND,
+            EventType::FORCEFEEDBACK,
+        ]
+        .into_iter()
+        .fold(Self::new(), Self::with_type)
+    }
+
+    fn bitmap_mut(&mut self, ty: EventType) -> Option<&mut Vec<u8>> {
+        let count = code_count(ty)?;
+        Some(
+            self.codes
+                .entry(ty.0)
+                .or_insert_with(|| vec![0; count.div_ceil(8)]),
+        )
+    }
+
+    /// Add every code of `ty`.
+    pub fn with_type(mut self, ty: EventType) -> Self {
+        if let Some(count) = code_count(ty) {
+            let bitmap = self.bitmap_mut(ty).unwrap();
+            for code in 0..count {
+                bitmap[code / 8] |= 1 << (code % 8);
+            }
+        }
+        self
+    }
+
+    /// Add the code `code` of `ty`.
+    pub fn with_code(mut self, ty: EventType, code: u16) -> Self {
+        self.insert(ty, code);
+        self
+    }
+
+    pub fn with_keys(self, keys: impl IntoIterator<Item = Key>) -> Self {
+        keys.into_iter()
+            .fold(self, |mask, key| mask.with_code(EventType::KEY, key.code()))
+    }
+
+    pub fn with_relative_axes(self, axes: impl IntoIterator<Item = RelativeAxisType>) -> Self {
+        axes.into_iter().fold(self, |mask, axis| {
+            mask.with_code(EventType::RELATIVE, axis.0)
+        })
+    }
+
+    pub fn with_absolute_axes(self, axes: impl IntoIterator<Item = AbsoluteAxisType>) -> Self {
+        axes.into_iter().fold(self, |mask, axis| {
+            mask.with_code(EventType::ABSOLUTE, axis.0)
+        })
+    }
+
+    pub fn with_switches(self, switches: impl IntoIterator<Item = SwitchType>) -> Self {
+        switches.into_iter().fold(self, |mask, switch| {
+            mask.with_code(EventType::SWITCH, switch.0)
+        })
+    }
+
+    /// Add the code `code` of `ty`. Codes out of range for their type are ignored.
+    pub fn insert(&mut self, ty: EventType, code: u16) {
+        if let Some(bitmap) = self.bitmap_mut(ty) {
+            if let Some(byte) = bitmap.get_mut(usize::from(code) / 8) {
+                *byte |= 1 << (code % 8);
+            }
+        }
+    }
+
+    /// Returns `true` if the mask contains the code `code` of `ty`.
+    pub fn contains(&self, ty: EventType, code: u16) -> bool {
+        if code_count(ty).is_none() {
+            return true;
+        }
+        self.codes
+            .get(&ty.0)
+            .and_then(|bitmap| bitmap.get(usize::from(code) / 8))
+            .is_some_and(|byte| byte & (1 << (code % 8)) != 0)
+    }
+
+    /// Returns `true` if the mask contains the event's type and code.
+    pub fn matches(&self, ev: &InputEvent) -> bool {
+        self.contains(ev.event_type(), ev.code())
+    }
+
+    /// Add every event of `other`.
+    pub fn union_with(&mut self, other: &EventMask) {
+        for (&ty, bits) in &other.codes {
+            let bitmap = self.codes.entry(ty).or_insert_with(|| vec![0; bits.len()]);
+            for (byte, other) in bitmap.iter_mut().zip(bits) {
+                *byte |= other;
+            }
+        }
+    }
+
+    /// Returns the types the kernel can mask, with the bitmaps to pass to `EVIOCSMASK`.
+    pub(crate) fn kernel_bitmaps(&self) -> impl Iterator<Item = (EventType, Vec<u8>)> + '_ {
+        (0..EventType::COUNT as u16)
+            .map(EventType)
+            .filter_map(move |ty| {
+                let count = code_count(ty)?;
+                let bitmap = self.codes.get(&ty.0).cloned();
+                Some((ty, bitmap.unwrap_or_else(|| vec![0; count.div_ceil(8)])))
+            })
+    }
+}
+
+/// Identifies a consumer of an [`EventMaskManager`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ConsumerId(usize);
+
+#[derive(Debug, Clone)]
+struct Consumer {
+    mask: EventMask,
+    /// Whether the consumer was given an event of the current frame.
+    in_frame: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Consumers(Vec<Option<Consumer>>);
+
+impl Consumers {
+    fn union(&self) -> EventMask {
+        let mut union = EventMask::new();
+        for consumer in self.0.iter().flatten() {
+            union.union_with(&consumer.mask);
+        }
+        union
+    }
+
+    fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<(ConsumerId, InputEvent)>) {
+        let consumers = self.0.iter_mut().enumerate();
+        let consumers = consumers.filter_map(|(i, c)| Some((ConsumerId(i), c.as_mut()?)));
+        if ev.event_type() == EventType::SYNCHRONIZATION {
+            let dropped = ev.code() == Synchronization::SYN_DROPPED.0;
+            for (id, consumer) in consumers {
+                // Frames end only for the consumers that got something in them
+                if consumer.in_frame || dropped {
+                    out.push((id, *ev));
+                }
+                if ev.code() == Synchronization::SYN_REPORT.0 || dropped {
+                    consumer.in_frame = false;
+                }
+            }
+        } else {
+            for (id, consumer) in consumers {
+                if consumer.mask.matches(ev) {
+                    consumer.in_frame = true;
+                    out.push((id, *ev));
+                }
+            }
+        }
+    }
+}
+
+/// A device shared between consumers. See the [module documentation](self).
+///
+/// As the kernel no longer delivers events outside the union of the consumers' masks, the
+/// device's [cached state](Device::cached_state) isn't kept up to date for them.
+pub struct EventMaskManager {
+    device: Device,
+    consumers: Consumers,
+}
+
+impl EventMaskManager {
+    /// Wrap a device. Until consumers are added, the device is masked to deliver nothing.
+    ///
+    /// Fails if the kernel doesn't support `EVIOCSMASK`.
+    pub fn new(mut device: Device) -> io::Result<Self> {
+        device.set_event_mask(&EventMask::new())?;
+        Ok(EventMaskManager {
+            device,
+            consumers: Consumers::default(),
+        })
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    /// Returns the device, with its mask reset to deliver every event.
+    pub fn into_inner(mut self) -> io::Result<Device> {
+        self.device.set_event_mask(&EventMask::all())?;
+        Ok(self.device)
+    }
+
+    /// Returns the union of the consumers' masks, which the device is masked to.
+    pub fn union(&self) -> EventMask {
+        self.consumers.union()
+    }
+
+    fn update_kernel_mask(&mut self) -> io::Result<()> {
+        let union = self.consumers.union();
+        self.device.set_event_mask(&union)
+    }
+
+    /// Add a consumer interested in the events of `mask`.
+    pub fn add_consumer(&mut self, mask: EventMask) -> io::Result<ConsumerId> {
+        self.consumers.0.push(Some(Consumer {
+            mask,
+            in_frame: false,
+        }));
+        self.update_kernel_mask()?;
+        Ok(ConsumerId(self.consumers.0.len() - 1))
+    }
+
+    /// Change the events a consumer is interested in.
+    pub fn set_mask(&mut self, id: ConsumerId, mask: EventMask) -> io::Result<()> {
+        if let Some(Some(consumer)) = self.consumers.0.get_mut(id.0) {
+            consumer.mask = mask;
+            self.update_kernel_mask()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the events a consumer is interested in.
+    pub fn mask(&self, id: ConsumerId) -> Option<&EventMask> {
+        Some(&self.consumers.0.get(id.0)?.as_ref()?.mask)
+    }
+
+    /// Remove a consumer, and stop the kernel from delivering events only it wanted.
+    pub fn remove_consumer(&mut self, id: ConsumerId) -> io::Result<()> {
+        if let Some(consumer) = self.consumers.0.get_mut(id.0) {
+            if consumer.take().is_some() {
+                self.update_kernel_mask()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand an event to the consumers interested in it, appending it to `out` once for each.
+    ///
+    /// Synchronization events are handed to the consumers that were given an event since the
+    /// last `SYN_REPORT`, so no consumer sees empty frames, except `SYN_DROPPED`, which every
+    /// consumer is given.
+    pub fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<(ConsumerId, InputEvent)>) {
+        self.consumers.process_event(ev, out);
+    }
+
+    /// Fetch the next batch of events from the device, with the consumers they are for.
+    ///
+    /// Like [`Device::fetch_events`], this blocks unless the device is non-blocking.
+    pub fn fetch_events(&mut self) -> io::Result<Vec<(ConsumerId, InputEvent)>> {
+        let mut out = Vec::new();
+        for ev in self.device.fetch_events()? {
+            self.consumers.process_event(&ev, &mut out);
+        }
+        Ok(out)
+    }
+}
+
+impl AsRawFd for EventMaskManager {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_consumer_filtering() {
+        let hotkeys = EventMask::new().with_keys([Key::KEY_F12]);
+        let pointer = EventMask::new()
+            .with_relative_axes([RelativeAxisType::REL_X])
+            .with_keys([Key::BTN_LEFT]);
+        let mut consumers = Consumers(vec![
+            Some(Consumer {
+                mask: hotkeys,
+                in_frame: false,
+            }),
+            Some(Consumer {
+                mask: pointer,
+                in_frame: false,
+            }),
+        ]);
+        let union = consumers.union();
+        assert!(union.contains(EventType::KEY, Key::KEY_F12.code()));
+        assert!(union.contains(EventType::KEY, Key::BTN_LEFT.code()));
+        assert!(!union.contains(EventType::KEY, Key::KEY_A.code()));
+        assert!(!union.contains(EventType::RELATIVE, RelativeAxisType::REL_Y.0));
+
+        let mut out = Vec::new();
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        for ev in [
+            InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_X.0, 3),
+            syn,
+            InputEvent::new(EventType::KEY, Key::KEY_F12.code(), 1),
+            syn,
+        ] {
+            consumers.process_event(&ev, &mut out);
+        }
+        let ids: Vec<_> = out.iter().map(|(id, ev)| (id.0, ev.event_type())).collect();
+        assert_eq!(
+            ids,
+            [
+                (1, EventType::RELATIVE),
+                (1, EventType::SYNCHRONIZATION),
+                (0, EventType::KEY),
+                (0, EventType::SYNCHRONIZATION),
+            ]
+        );
+    }
+}
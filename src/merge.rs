@@ -0,0 +1,342 @@
+//! Merging the events of several devices in timestamp order.
+//!
+//! Reading several devices one after the other yields their events in the order they were read,
+//! not the order they happened in: a touchpad frame read after a keyboard frame may well be
+//! older. Gesture recognition across devices, like a modifier held on the keyboard while
+//! swiping, and recordings that should replay faithfully need the real order. A
+//! [`FrameMerge`] holds frames back for a bounded reordering window and releases them sorted
+//! by timestamp; [`MergedDevices`] does that for a set of [`Device`]s.
+//!
+//! Frames are merged whole, so the events of one device's frame stay together.
+//!
+//! ```no_run
+//! use evdev::merge::MergedDevices;
+//! use std::time::Duration;
+//!
+//! let mut merged = MergedDevices::new(Duration::from_millis(10));
+//! let keyboard = merged.add(evdev::Device::open("/dev/input/event3")?);
+//! let touchpad = merged.add(evdev::Device::open("/dev/input/event7")?);
+//! loop {
+//!     for (source, frame) in merged.fetch_frames(None)? {
+//!         let name = if source == keyboard { "keyboard" } else { "touchpad" };
+//!         println!("{}: {:?}", name, frame);
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::transform::is_syn_report;
+use crate::{Device, InputEvent};
+
+/// A complete frame waiting to be released.
+struct Held {
+    time: SystemTime,
+    /// Arrival order, to keep frames with equal timestamps in the order they were read.
+    seq: u64,
+    source: usize,
+    events: Vec<InputEvent>,
+}
+
+impl PartialEq for Held {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Held {}
+
+impl PartialOrd for Held {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Held {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.time, self.seq).cmp(&(other.time, other.seq))
+    }
+}
+
+/// Sorts frames from several sources by timestamp, within a reordering window. See the
+/// [module documentation](self).
+///
+/// A frame is released once it is older than the newest frame pushed, or the current time, by
+/// more than the window. A frame that arrives after newer ones were already released is
+/// released right away, out of order, and counted in [`late_count`](Self::late_count); a
+/// window a bit longer than the time between reads avoids that.
+pub struct FrameMerge {
+    window: Duration,
+    /// The events since the last `SYN_REPORT` of each source.
+    partial: HashMap<usize, Vec<InputEvent>>,
+    held: BinaryHeap<Reverse<Held>>,
+    newest: Option<SystemTime>,
+    released: Option<SystemTime>,
+    seq: u64,
+    late_count: u64,
+}
+
+impl FrameMerge {
+    pub fn new(window: Duration) -> Self {
+        FrameMerge {
+            window,
+            partial: HashMap::new(),
+            held: BinaryHeap::new(),
+            newest: None,
+            released: None,
+            seq: 0,
+            late_count: 0,
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Add an event read from `source`, a number of the caller's choosing, such as an index in
+    /// a list of devices.
+    pub fn push(&mut self, source: usize, ev: InputEvent) {
+        self.push_at(source, ev, ev.timestamp());
+    }
+
+    /// Add an event, taking a complete frame to have happened at `time`.
+    fn push_at(&mut self, source: usize, ev: InputEvent, time: SystemTime) {
+        let partial = self.partial.entry(source).or_default();
+        partial.push(ev);
+        if !is_syn_report(&ev) {
+            return;
+        }
+        let events = std::mem::take(partial);
+        self.newest = self.newest.max(Some(time));
+        self.held.push(Reverse(Held {
+            time,
+            seq: self.seq,
+            source,
+            events,
+        }));
+        self.seq += 1;
+    }
+
+    /// Forget the incomplete frame of `source`, e.g. once it was unplugged. Its complete frames
+    /// are still released.
+    pub fn remove_source(&mut self, source: usize) {
+        self.partial.remove(&source);
+    }
+
+    /// Returns the next frame that is due as of `now`, with its source.
+    pub fn pop(&mut self, now: SystemTime) -> Option<(usize, Vec<InputEvent>)> {
+        let horizon = self.newest.max(Some(now))?.checked_sub(self.window)?;
+        let Reverse(first) = self.held.peek()?;
+        if first.time > horizon && self.released.is_none_or(|released| first.time >= released) {
+            return None;
+        }
+        self.release()
+    }
+
+    /// Returns the next frame regardless of the window, e.g. when shutting down.
+    pub fn flush(&mut self) -> Option<(usize, Vec<InputEvent>)> {
+        self.release()
+    }
+
+    fn release(&mut self) -> Option<(usize, Vec<InputEvent>)> {
+        let Reverse(frame) = self.held.pop()?;
+        match self.released {
+            Some(released) if frame.time < released => self.late_count += 1,
+            _ => self.released = Some(frame.time),
+        }
+        Some((frame.source, frame.events))
+    }
+
+    /// Returns how long until the oldest held frame is due, as of `now`, or `None` if no frame
+    /// is held.
+    pub fn next_due(&self, now: SystemTime) -> Option<Duration> {
+        let Reverse(first) = self.held.peek()?;
+        let due = first.time + self.window;
+        Some(due.duration_since(now).unwrap_or_default())
+    }
+
+    /// Returns the number of frames released out of order, because they arrived too late.
+    pub fn late_count(&self) -> u64 {
+        self.late_count
+    }
+}
+
+impl std::fmt::Debug for FrameMerge {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("FrameMerge")
+            .field("window", &self.window)
+            .field("held", &self.held.len())
+            .field("late_count", &self.late_count)
+            .finish_non_exhaustive()
+    }
+}
+
+fn is_gone(e: &io::Error) -> bool {
+    crate::Error::raw_os_error_of(e) == Some(libc::ENODEV)
+}
+
+/// Reads several devices and returns their frames in timestamp order. See the
+/// [module documentation](self).
+///
+/// Devices are expected to use the default realtime clock for their timestamps.
+pub struct MergedDevices {
+    devices: Vec<(usize, Device)>,
+    merge: FrameMerge,
+    next_source: usize,
+    clock: SharedClock,
+}
+
+impl MergedDevices {
+    /// Merge frames that are at most `window` apart.
+    pub fn new(window: Duration) -> Self {
+        MergedDevices {
+            devices: Vec::new(),
+            merge: FrameMerge::new(window),
+            next_source: 0,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Decide which frames are due with the time of `clock` rather than of the system clock.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Add a device, returning the source number its frames are returned with.
+    pub fn add(&mut self, device: Device) -> usize {
+        let source = self.next_source;
+        self.next_source += 1;
+        self.devices.push((source, device));
+        source
+    }
+
+    /// Returns the devices with their source numbers. Unplugged devices are removed.
+    pub fn devices(&self) -> impl Iterator<Item = (usize, &Device)> {
+        self.devices
+            .iter()
+            .map(|(source, device)| (*source, device))
+    }
+
+    /// Remove a device, returning it. Its frames already read are still returned.
+    pub fn remove(&mut self, source: usize) -> Option<Device> {
+        let idx = self.devices.iter().position(|(s, _)| *s == source)?;
+        self.merge.remove_source(source);
+        Some(self.devices.remove(idx).1)
+    }
+
+    pub fn merge(&self) -> &FrameMerge {
+        &self.merge
+    }
+
+    /// Wait up to `timeout`, or indefinitely if `None`, for frames, and return those that are
+    /// due, oldest first, with their source numbers.
+    ///
+    /// Returns early once held frames are due, and may return nothing if `timeout` passes
+    /// first.
+    pub fn fetch_frames(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> io::Result<Vec<(usize, Vec<InputEvent>)>> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let mut fds: Vec<_> = self
+            .devices
+            .iter()
+            .map(|(_, device)| PollFd::new(device.as_raw_fd(), PollFlags::POLLIN))
+            .collect();
+        let due = self.merge.next_due(self.clock.now());
+        let timeout = match (timeout, due) {
+            (Some(timeout), Some(due)) => Some(timeout.min(due)),
+            (timeout, due) => timeout.or(due),
+        };
+        let millis = timeout.map_or(-1, |t| {
+            let millis = t.as_nanos().div_ceil(1_000_000);
+            millis.min(i32::MAX as u128) as i32
+        });
+        poll(&mut fds, millis)?;
+        let ready = |fd: &PollFd| fd.revents().is_some_and(|r| !r.is_empty());
+        let ready: Vec<bool> = fds.iter().map(ready).collect();
+
+        let mut gone = Vec::new();
+        for (i, (source, device)) in self.devices.iter_mut().enumerate() {
+            if !ready[i] {
+                continue;
+            }
+            match device.fetch_events() {
+                Ok(events) => {
+                    for ev in events {
+                        let time = self.clock.event_time(&ev);
+                        self.merge.push_at(*source, ev, time);
+                    }
+                }
+                Err(e) if is_gone(&e) => gone.push(*source),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+        for source in gone {
+            self.remove(source);
+        }
+
+        let now = self.clock.now();
+        Ok(std::iter::from_fn(|| self.merge.pop(now)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventType, Key};
+
+    #[test]
+    fn merge_in_order() {
+        let at = |millis| SystemTime::UNIX_EPOCH + Duration::from_millis(millis);
+        let ev = |ty: EventType, code, millis| {
+            let mut ev = InputEvent::new(ty, code, 1);
+            let time = at(millis).duration_since(SystemTime::UNIX_EPOCH).unwrap();
+            ev.0.time.tv_sec = time.as_secs() as _;
+            ev.0.time.tv_usec = time.subsec_micros() as _;
+            ev
+        };
+        let frame = |code, millis| {
+            [
+                ev(EventType::KEY, code, millis),
+                ev(EventType::SYNCHRONIZATION, 0, millis),
+            ]
+        };
+        let codes = |frames: Vec<(usize, Vec<InputEvent>)>| -> Vec<(usize, u16)> {
+            frames.iter().map(|(s, f)| (*s, f[0].code())).collect()
+        };
+        let mut merge = FrameMerge::new(Duration::from_millis(10));
+
+        // Device 1 is read after device 0, but its frame is older
+        merge.extend_from(0, frame(Key::KEY_A.code(), 105));
+        merge.extend_from(0, frame(Key::KEY_B.code(), 112));
+        merge.extend_from(1, frame(Key::BTN_LEFT.code(), 103));
+        assert!(merge.pop(at(0)).is_none());
+        let due: Vec<_> = std::iter::from_fn(|| merge.pop(at(120))).collect();
+        assert_eq!(
+            codes(due),
+            [(1, Key::BTN_LEFT.code()), (0, Key::KEY_A.code())]
+        );
+
+        // Too late to be put in order
+        merge.extend_from(1, frame(Key::BTN_RIGHT.code(), 104));
+        assert_eq!(merge.pop(at(0)).map(|(source, _)| source), Some(1));
+        assert_eq!(merge.late_count(), 1);
+        assert_eq!(merge.flush().map(|(source, _)| source), Some(0));
+        assert!(merge.flush().is_none());
+    }
+
+    impl FrameMerge {
+        fn extend_from(&mut self, source: usize, events: impl IntoIterator<Item = InputEvent>) {
+            events.into_iter().for_each(|ev| self.push(source, ev));
+        }
+    }
+}
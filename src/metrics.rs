@@ -0,0 +1,138 @@
+//! Event statistics for the proxy pipeline.
+//!
+//! With the `metrics` feature enabled, everything recorded here is also reported through the
+//! [`metrics`](https://docs.rs/metrics) facade, as `evdev_events_total` (labelled by `type`),
+//! `evdev_frames_total`, `evdev_syn_dropped_total` and the `evdev_latency_seconds` histogram.
+
+use std::time::{Duration, Instant};
+
+use crate::{EventType, InputEvent};
+
+/// How often [`Metrics::frames_per_second`] is updated.
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+/// Counters and timings collected while forwarding events.
+///
+/// Enable collection on a [`Proxy`](crate::proxy::Proxy) with
+/// [`with_metrics`](crate::proxy::Proxy::with_metrics).
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    events: [u64; EventType::COUNT],
+    frames: u64,
+    syn_dropped: u64,
+    latency_total: Duration,
+    latency_max: Duration,
+    window_start: Instant,
+    window_frames: u64,
+    fps: f64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            events: [0; EventType::COUNT],
+            frames: 0,
+            syn_dropped: 0,
+            latency_total: Duration::ZERO,
+            latency_max: Duration::ZERO,
+            window_start: Instant::now(),
+            window_frames: 0,
+            fps: 0.0,
+        }
+    }
+
+    /// Returns how many events of the given type have been read.
+    pub fn events(&self, ty: EventType) -> u64 {
+        self.events.get(ty.0 as usize).copied().unwrap_or(0)
+    }
+
+    /// Returns how many events have been read in total.
+    pub fn total_events(&self) -> u64 {
+        self.events.iter().sum()
+    }
+
+    /// Returns how many frames have been forwarded.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the frame rate over the last complete one-second window.
+    pub fn frames_per_second(&self) -> f64 {
+        self.fps
+    }
+
+    /// Returns how many times the kernel dropped events because they weren't read fast enough.
+    pub fn syn_dropped(&self) -> u64 {
+        self.syn_dropped
+    }
+
+    /// Returns the average time between reading a frame and emitting it, if any were emitted.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        let frames = u32::try_from(self.frames).ok().filter(|&n| n > 0)?;
+        Some(self.latency_total / frames)
+    }
+
+    /// Returns the longest time between reading a frame and emitting it.
+    pub fn max_latency(&self) -> Duration {
+        self.latency_max
+    }
+
+    /// Reset all counters to zero.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Record a frame that was read at `read_at` and has just been emitted.
+    pub(crate) fn record_frame(&mut self, frame: &[InputEvent], read_at: Instant) {
+        let now = Instant::now();
+        let latency = now.saturating_duration_since(read_at);
+        for ev in frame {
+            if let Some(count) = self.events.get_mut(ev.event_type().0 as usize) {
+                *count += 1;
+            }
+        }
+        self.frames += 1;
+        self.latency_total += latency;
+        self.latency_max = self.latency_max.max(latency);
+
+        self.window_frames += 1;
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed >= FPS_WINDOW {
+            self.fps = self.window_frames as f64 / elapsed.as_secs_f64();
+            self.window_start = now;
+            self.window_frames = 0;
+        }
+
+        #[cfg(feature = "metrics")]
+        report_frame(frame, latency);
+    }
+
+    /// Record that the kernel dropped events `count` times.
+    pub(crate) fn record_syn_dropped(&mut self, count: u64) {
+        self.syn_dropped += count;
+        #[cfg(feature = "metrics")]
+        metrics_0::counter!("evdev_syn_dropped_total").increment(count);
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn report_frame(frame: &[InputEvent], latency: Duration) {
+    let mut counts = [0u64; EventType::COUNT];
+    for ev in frame {
+        if let Some(count) = counts.get_mut(ev.event_type().0 as usize) {
+            *count += 1;
+        }
+    }
+    for (ty, &count) in counts.iter().enumerate().filter(|(_, &n)| n > 0) {
+        let ty = format!("{:?}", EventType(ty as u16));
+        metrics_0::counter!("evdev_events_total", "type" => ty).increment(count);
+    }
+    metrics_0::counter!("evdev_frames_total").increment(1);
+    metrics_0::histogram!("evdev_latency_seconds").record(latency.as_secs_f64());
+}
@@ -0,0 +1,244 @@
+//! Tracking the modifier state of several keyboards as one.
+//!
+//! Holding Shift on one keyboard and typing on another still types capitals, and Caps Lock is
+//! on for every keyboard once it is on for one. A [`ModifierTracker`] follows the key events of
+//! any number of keyboards and works out the combined [`Modifiers`]: those held on any of them,
+//! and the lock states. Lock keys toggle their lock when pressed, but the keyboards' lock LEDs
+//! have the last word, so the state stays right when something else, like the console or a
+//! compositor, decides the locks.
+//!
+//! ```no_run
+//! use evdev::modifiers::{ModifierTracker, Modifiers};
+//!
+//! let mut keyboards: Vec<evdev::Device> = evdev::enumerate().map(|(_, d)| d).collect();
+//! let mut tracker = ModifierTracker::new();
+//! if let Some(keyboard) = keyboards.first() {
+//!     tracker.sync_leds(&keyboard.get_led_state()?);
+//! }
+//! loop {
+//!     for (source, keyboard) in keyboards.iter_mut().enumerate() {
+//!         for ev in keyboard.fetch_events()? {
+//!             if tracker.process_event(source, &ev) {
+//!                 let shift = tracker.modifiers().contains(Modifiers::SHIFT);
+//!                 let caps = tracker.modifiers().contains(Modifiers::CAPS_LOCK);
+//!                 println!("upper case: {}", shift != caps);
+//!             }
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::HashMap;
+use std::ops::BitOr;
+
+use crate::{AttributeSet, AttributeSetRef, EventType, InputEvent, Key, LedType};
+
+/// A set of modifiers, without distinguishing the left and right keys.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const SHIFT: Modifiers = Modifiers(1 << 0);
+    pub const CTRL: Modifiers = Modifiers(1 << 1);
+    pub const ALT: Modifiers = Modifiers(1 << 2);
+    /// The Super, Windows or Command key.
+    pub const META: Modifiers = Modifiers(1 << 3);
+    pub const CAPS_LOCK: Modifiers = Modifiers(1 << 4);
+    pub const NUM_LOCK: Modifiers = Modifiers(1 << 5);
+    pub const SCROLL_LOCK: Modifiers = Modifiers(1 << 6);
+
+    /// The modifiers that are on while their key is held.
+    pub const HELD: Modifiers = Modifiers(0b1111);
+    /// The modifiers toggled by their key.
+    pub const LOCKS: Modifiers = Modifiers(0b111_0000);
+
+    /// Returns the modifier `key` holds, if any.
+    pub fn from_key(key: Key) -> Option<Modifiers> {
+        match key {
+            Key::KEY_LEFTSHIFT | Key::KEY_RIGHTSHIFT => Some(Modifiers::SHIFT),
+            Key::KEY_LEFTCTRL | Key::KEY_RIGHTCTRL => Some(Modifiers::CTRL),
+            Key::KEY_LEFTALT | Key::KEY_RIGHTALT => Some(Modifiers::ALT),
+            Key::KEY_LEFTMETA | Key::KEY_RIGHTMETA => Some(Modifiers::META),
+            _ => None,
+        }
+    }
+
+    /// Returns the lock `key` toggles, if any.
+    pub fn from_lock_key(key: Key) -> Option<Modifiers> {
+        match key {
+            Key::KEY_CAPSLOCK => Some(Modifiers::CAPS_LOCK),
+            Key::KEY_NUMLOCK => Some(Modifiers::NUM_LOCK),
+            Key::KEY_SCROLLLOCK => Some(Modifiers::SCROLL_LOCK),
+            _ => None,
+        }
+    }
+
+    /// Returns the lock `led` shows, if any.
+    pub fn from_led(led: LedType) -> Option<Modifiers> {
+        match led {
+            LedType::LED_CAPSL => Some(Modifiers::CAPS_LOCK),
+            LedType::LED_NUML => Some(Modifiers::NUM_LOCK),
+            LedType::LED_SCROLLL => Some(Modifiers::SCROLL_LOCK),
+            _ => None,
+        }
+    }
+
+    pub fn contains(self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the modifiers in both `self` and `other`.
+    pub fn intersection(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 & other.0)
+    }
+
+    /// Returns the modifiers in `self` but not in `other`.
+    pub fn difference(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 & !other.0)
+    }
+
+    fn set(&mut self, other: Modifiers, on: bool) {
+        match on {
+            true => self.0 |= other.0,
+            false => self.0 &= !other.0,
+        }
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 | other.0)
+    }
+}
+
+/// Merges the modifier state of several keyboards. See the [module documentation](self).
+///
+/// Keyboards are identified by a `source` number of the caller's choosing, such as their
+/// index in a list.
+#[derive(Debug, Clone, Default)]
+pub struct ModifierTracker {
+    /// The modifier keys held on each keyboard.
+    held: HashMap<usize, AttributeSet<Key>>,
+    locked: Modifiers,
+}
+
+impl ModifierTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the modifiers held on any keyboard, and the locks that are on.
+    pub fn modifiers(&self) -> Modifiers {
+        self.held() | self.locked
+    }
+
+    /// Returns the modifiers held on any keyboard.
+    pub fn held(&self) -> Modifiers {
+        self.held
+            .values()
+            .flat_map(|keys| keys.iter())
+            .filter_map(Modifiers::from_key)
+            .fold(Modifiers::NONE, BitOr::bitor)
+    }
+
+    /// Returns the modifiers held on the keyboard `source`.
+    pub fn held_on(&self, source: usize) -> Modifiers {
+        self.held.get(&source).map_or(Modifiers::NONE, |keys| {
+            keys.iter()
+                .filter_map(Modifiers::from_key)
+                .fold(Modifiers::NONE, BitOr::bitor)
+        })
+    }
+
+    /// Returns the locks that are on.
+    pub fn locked(&self) -> Modifiers {
+        self.locked
+    }
+
+    /// Set the locks, e.g. from a keymap's state.
+    pub fn set_locked(&mut self, locks: Modifiers) {
+        self.locked = locks.intersection(Modifiers::LOCKS);
+    }
+
+    /// Set the locks from a keyboard's LEDs, e.g. from [`Device::get_led_state`] when starting.
+    ///
+    /// [`Device::get_led_state`]: crate::Device::get_led_state
+    pub fn sync_leds(&mut self, leds: &AttributeSetRef<LedType>) {
+        for led in [LedType::LED_CAPSL, LedType::LED_NUML, LedType::LED_SCROLLL] {
+            if let Some(lock) = Modifiers::from_led(led) {
+                self.locked.set(lock, leds.contains(led));
+            }
+        }
+    }
+
+    /// Update the state from an event read from the keyboard `source`. Returns `true` if
+    /// [`modifiers`](Self::modifiers) changed.
+    pub fn process_event(&mut self, source: usize, ev: &InputEvent) -> bool {
+        let before = self.modifiers();
+        match ev.event_type() {
+            EventType::KEY => {
+                let key = Key::new(ev.code());
+                if Modifiers::from_key(key).is_some() {
+                    self.held
+                        .entry(source)
+                        .or_default()
+                        .set(key, ev.value() != 0);
+                } else if let Some(lock) = Modifiers::from_lock_key(key) {
+                    if ev.value() == 1 {
+                        let on = !self.locked.contains(lock);
+                        self.locked.set(lock, on);
+                    }
+                }
+            }
+            EventType::LED => {
+                if let Some(lock) = Modifiers::from_led(LedType(ev.code())) {
+                    self.locked.set(lock, ev.value() != 0);
+                }
+            }
+            _ => {}
+        }
+        self.modifiers() != before
+    }
+
+    /// Forget the keys held on the keyboard `source`, e.g. once it was unplugged. Returns
+    /// `true` if [`modifiers`](Self::modifiers) changed.
+    pub fn remove_source(&mut self, source: usize) -> bool {
+        let before = self.modifiers();
+        self.held.remove(&source);
+        self.modifiers() != before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_keyboards() {
+        let key = |key: Key, value| InputEvent::new(EventType::KEY, key.code(), value);
+        let led = |led: LedType, value| InputEvent::new(EventType::LED, led.0, value);
+        let mut tracker = ModifierTracker::new();
+
+        assert!(tracker.process_event(0, &key(Key::KEY_LEFTSHIFT, 1)));
+        assert!(!tracker.process_event(1, &key(Key::KEY_RIGHTSHIFT, 1)));
+        assert!(!tracker.process_event(0, &key(Key::KEY_LEFTSHIFT, 0)));
+        assert_eq!(tracker.modifiers(), Modifiers::SHIFT);
+        assert_eq!(tracker.held_on(0), Modifiers::NONE);
+        assert!(tracker.remove_source(1));
+
+        assert!(tracker.process_event(1, &key(Key::KEY_CAPSLOCK, 1)));
+        // The LED confirming the toggle changes nothing, but it overrides a missed toggle
+        assert!(!tracker.process_event(0, &led(LedType::LED_CAPSL, 1)));
+        assert!(!tracker.process_event(1, &key(Key::KEY_CAPSLOCK, 2)));
+        assert!(tracker.process_event(0, &led(LedType::LED_CAPSL, 0)));
+        assert_eq!(tracker.modifiers(), Modifiers::NONE);
+    }
+}
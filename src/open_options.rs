@@ -0,0 +1,156 @@
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+
+use crate::error::{open_error, uinput_open_error};
+
+/// The default location of the uinput device node.
+pub(crate) const UINPUT_PATH: &str = "/dev/uinput";
+
+/// Options for opening devices, with [`Device::open_with`](crate::Device::open_with) and
+/// [`RawDevice::open_with`](crate::raw_stream::RawDevice::open_with), and uinput, with
+/// [`VirtualDeviceBuilder::with_options`](crate::uinput::VirtualDeviceBuilder::with_options).
+///
+/// The defaults are those of [`Device::open`](crate::Device::open) and
+/// [`VirtualDeviceBuilder::new`](crate::uinput::VirtualDeviceBuilder::new).
+///
+/// ```no_run
+/// use evdev::{Device, OpenOptions};
+///
+/// // A read-only descriptor that a child process can inherit
+/// let options = OpenOptions::new().write(false).cloexec(false);
+/// let device = Device::open_with("/dev/input/event3", &options)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenOptions {
+    write: Option<bool>,
+    cloexec: bool,
+    nonblocking: Option<bool>,
+    uinput_path: PathBuf,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        OpenOptions {
+            write: None,
+            cloexec: true,
+            nonblocking: None,
+            uinput_path: PathBuf::from(UINPUT_PATH),
+        }
+    }
+
+    /// Open devices read-write, failing if that isn't allowed, or read-only. By default they
+    /// are opened read-write if allowed and read-only otherwise; writing is only needed to
+    /// send events to the device, like LED changes and force feedback.
+    ///
+    /// uinput is always opened read-write, and ignores this.
+    pub fn write(mut self, write: bool) -> Self {
+        self.write = Some(write);
+        self
+    }
+
+    /// Close the descriptor when the process executes another program. On by default.
+    pub fn cloexec(mut self, cloexec: bool) -> Self {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Open in non-blocking mode, so reads fail with [`io::ErrorKind::WouldBlock`] rather than
+    /// wait for events. Off by default for devices, and on by default for uinput, where reads
+    /// only fetch force feedback requests.
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = Some(nonblocking);
+        self
+    }
+
+    /// Open uinput at `path` instead of `/dev/uinput`, e.g. `/dev/input/uinput` on some
+    /// systems, or a node bind-mounted into a container.
+    pub fn uinput_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.uinput_path = path.as_ref().to_owned();
+        self
+    }
+
+    /// The access mode and descriptor flags `fd` was opened with.
+    pub(crate) fn of_fd(fd: &impl AsRawFd) -> io::Result<Self> {
+        let fd_flags = FdFlag::from_bits_truncate(fcntl(fd.as_raw_fd(), FcntlArg::F_GETFD)?);
+        let status = OFlag::from_bits_truncate(fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL)?);
+        Ok(OpenOptions::new()
+            .write(status & OFlag::O_ACCMODE != OFlag::O_RDONLY)
+            .cloexec(fd_flags.contains(FdFlag::FD_CLOEXEC))
+            .nonblocking(status.contains(OFlag::O_NONBLOCK)))
+    }
+
+    fn open(&self, path: &Path, write: bool, nonblocking: bool) -> io::Result<File> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(write)
+            .custom_flags(if nonblocking { libc::O_NONBLOCK } else { 0 })
+            .open(path)?;
+        // The standard library always opens with O_CLOEXEC
+        if !self.cloexec {
+            fcntl(file.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::empty()))?;
+        }
+        Ok(file)
+    }
+
+    /// Open the device node at `path`.
+    pub(crate) fn open_device(&self, path: &Path) -> io::Result<File> {
+        let nonblocking = self.nonblocking.unwrap_or(false);
+        let file = match self.write {
+            Some(write) => self.open(path, write, nonblocking),
+            // Try to load read/write, then fall back to read-only.
+            None => self
+                .open(path, true, nonblocking)
+                .or_else(|_| self.open(path, false, nonblocking)),
+        };
+        file.map_err(|e| open_error(path, e))
+    }
+
+    /// Open uinput. Reading is needed to receive force feedback requests.
+    pub(crate) fn open_uinput(&self) -> io::Result<File> {
+        let path = &self.uinput_path;
+        self.open(path, true, self.nonblocking.unwrap_or(true))
+            .map_err(|e| uinput_open_error(path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptor_flags() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("evdev-open-options-{}", std::process::id()));
+        File::create(&path)?;
+        let flags = |file: &File| -> io::Result<(bool, bool, OFlag)> {
+            let fd = FdFlag::from_bits_truncate(fcntl(file.as_raw_fd(), FcntlArg::F_GETFD)?);
+            let fl = OFlag::from_bits_truncate(fcntl(file.as_raw_fd(), FcntlArg::F_GETFL)?);
+            Ok((
+                fd.contains(FdFlag::FD_CLOEXEC),
+                fl.contains(OFlag::O_NONBLOCK),
+                fl & OFlag::O_ACCMODE,
+            ))
+        };
+
+        let default = OpenOptions::new().open_device(&path)?;
+        assert_eq!(flags(&default)?, (true, false, OFlag::O_RDWR));
+        let custom = OpenOptions::new()
+            .write(false)
+            .cloexec(false)
+            .nonblocking(true)
+            .open_device(&path)?;
+        assert_eq!(flags(&custom)?, (false, true, OFlag::O_RDONLY));
+        std::fs::remove_file(&path)
+    }
+}
@@ -0,0 +1,213 @@
+//! Watching the power button, the lid switch and the tablet mode switch.
+//!
+//! These usually live on small devices of their own, like `Power Button` and `Lid Switch` from
+//! ACPI, or on the laptop keyboard. [`PowerWatcher`] finds every device exposing `KEY_POWER`,
+//! `SW_LID` or `SW_TABLET_MODE`, and turns their events into [`PowerEvent`]s. The current
+//! state of the switches is delivered first, so a session daemon starting with the lid closed
+//! knows about it without waiting for it to open.
+//!
+//! ```no_run
+//! use evdev::power::{PowerEvent, PowerWatcher};
+//!
+//! let mut watcher = PowerWatcher::new()?;
+//! watcher.run(|ev| match ev {
+//!     PowerEvent::PowerButtonPressed => println!("power button"),
+//!     PowerEvent::LidClosed(closed) => println!("lid closed: {}", closed),
+//!     PowerEvent::TabletMode(tablet) => println!("tablet mode: {}", tablet),
+//! })?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use crate::{Device, EventType, InputEvent, Key, SwitchType};
+
+/// A change of the power button or the switches.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PowerEvent {
+    PowerButtonPressed,
+    /// The lid was closed (`true`) or opened (`false`).
+    LidClosed(bool),
+    /// A convertible was folded into tablet mode (`true`) or back (`false`).
+    TabletMode(bool),
+}
+
+/// Returns `true` if `device` has any of the inputs [`PowerWatcher`] watches.
+fn is_power_device(device: &Device) -> bool {
+    let power = device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(Key::KEY_POWER));
+    let switches = device.supported_switches().is_some_and(|switches| {
+        switches.contains(SwitchType::SW_LID) || switches.contains(SwitchType::SW_TABLET_MODE)
+    });
+    power || switches
+}
+
+#[derive(Debug, Default, Clone)]
+struct Switches {
+    lid: Option<bool>,
+    tablet: Option<bool>,
+}
+
+impl Switches {
+    fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<PowerEvent>) {
+        match ev.event_type() {
+            EventType::KEY if ev.code() == Key::KEY_POWER.code() && ev.value() == 1 => {
+                out.push(PowerEvent::PowerButtonPressed);
+            }
+            EventType::SWITCH => {
+                let on = ev.value() != 0;
+                let (state, event) = match SwitchType(ev.code()) {
+                    SwitchType::SW_LID => (&mut self.lid, PowerEvent::LidClosed(on)),
+                    SwitchType::SW_TABLET_MODE => (&mut self.tablet, PowerEvent::TabletMode(on)),
+                    _ => return,
+                };
+                // The same switch can be reported by more than one device
+                if *state != Some(on) {
+                    *state = Some(on);
+                    out.push(event);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Watches the power button and the lid and tablet mode switches. See the
+/// [module documentation](self).
+pub struct PowerWatcher {
+    devices: Vec<Device>,
+    switches: Switches,
+    /// Events not yet delivered, starting with the initial state.
+    pending: Vec<PowerEvent>,
+}
+
+impl PowerWatcher {
+    /// Watch every device in `/dev/input` with a power button, lid switch or tablet mode
+    /// switch.
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] if there is none, or none could be opened.
+    pub fn new() -> io::Result<Self> {
+        let watcher = Self::from_devices(crate::enumerate().map(|(_, device)| device));
+        if watcher.devices.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no power button, lid switch or tablet mode switch found",
+            ));
+        }
+        Ok(watcher)
+    }
+
+    /// Watch those of `devices` with a power button, lid switch or tablet mode switch,
+    /// dropping the rest.
+    pub fn from_devices(devices: impl IntoIterator<Item = Device>) -> Self {
+        let devices: Vec<_> = devices.into_iter().filter(is_power_device).collect();
+        let mut watcher = PowerWatcher {
+            devices,
+            switches: Switches::default(),
+            pending: Vec::new(),
+        };
+        for device in &watcher.devices {
+            let (Some(supported), Some(state)) = (
+                device.supported_switches(),
+                device.cached_state().switch_vals(),
+            ) else {
+                continue;
+            };
+            for switch in [SwitchType::SW_LID, SwitchType::SW_TABLET_MODE] {
+                if supported.contains(switch) {
+                    let value = state.contains(switch) as i32;
+                    let ev = InputEvent::new(EventType::SWITCH, switch.0, value);
+                    watcher.switches.process_event(&ev, &mut watcher.pending);
+                }
+            }
+        }
+        watcher
+    }
+
+    /// Returns the devices being watched.
+    pub fn devices(&self) -> &[Device] {
+        &self.devices
+    }
+
+    /// Returns whether the lid is closed, or `None` if there is no lid switch.
+    pub fn lid_closed(&self) -> Option<bool> {
+        self.switches.lid
+    }
+
+    /// Returns whether the device is in tablet mode, or `None` if there is no tablet mode
+    /// switch.
+    pub fn tablet_mode(&self) -> Option<bool> {
+        self.switches.tablet
+    }
+
+    /// Wait up to `timeout`, or indefinitely if `None`, for changes and return them.
+    ///
+    /// The first call returns the initial state of the switches right away.
+    pub fn fetch_events(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> io::Result<impl Iterator<Item = PowerEvent>> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let mut out = std::mem::take(&mut self.pending);
+        if !out.is_empty() {
+            return Ok(out.into_iter());
+        }
+        let mut fds: Vec<_> = self
+            .devices
+            .iter()
+            .map(|device| PollFd::new(device.as_raw_fd(), PollFlags::POLLIN))
+            .collect();
+        let millis = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        poll(&mut fds, millis)?;
+        for (fd, device) in fds.iter().zip(self.devices.iter_mut()) {
+            if fd.revents().is_some_and(|r| !r.is_empty()) {
+                for ev in device.fetch_events()? {
+                    self.switches.process_event(&ev, &mut out);
+                }
+            }
+        }
+        Ok(out.into_iter())
+    }
+
+    /// Call `f` with every change, starting with the initial state, until an error occurs.
+    pub fn run(&mut self, mut f: impl FnMut(PowerEvent)) -> io::Result<()> {
+        loop {
+            self.fetch_events(None)?.for_each(&mut f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_events() {
+        let mut switches = Switches::default();
+        let mut out = Vec::new();
+        for (type_, code, value) in [
+            (EventType::SWITCH, SwitchType::SW_LID.0, 1),
+            // Reported again by a second device
+            (EventType::SWITCH, SwitchType::SW_LID.0, 1),
+            (EventType::KEY, Key::KEY_POWER.code(), 1),
+            (EventType::KEY, Key::KEY_POWER.code(), 0),
+            (EventType::SWITCH, SwitchType::SW_TABLET_MODE.0, 1),
+            (EventType::SWITCH, SwitchType::SW_LID.0, 0),
+        ] {
+            switches.process_event(&InputEvent::new(type_, code, value), &mut out);
+        }
+        assert_eq!(
+            out,
+            [
+                PowerEvent::LidClosed(true),
+                PowerEvent::PowerButtonPressed,
+                PowerEvent::TabletMode(true),
+                PowerEvent::LidClosed(false),
+            ]
+        );
+        assert_eq!((switches.lid, switches.tablet), (Some(false), Some(true)));
+    }
+}
@@ -0,0 +1,211 @@
+//! Virtual controllers that look like well-known gamepads.
+//!
+//! Many games only recognize controllers they know by vendor and product id, and expect the
+//! exact buttons and axis ranges of the real hardware. A [`ControllerPreset`] sets up a
+//! [`VirtualDeviceBuilder`] the way the kernel's drivers set up the genuine article (`xpad` for
+//! the Xbox 360 pad, `hid-sony` for the DualShock 4), so input remapped from any device is
+//! picked up as that controller.
+//!
+//! Games send rumble to the virtual pad. An [`FFPassthrough`](crate::proxy::FFPassthrough)
+//! forwards it to a physical device; for anything else, handle the requests from
+//! [`VirtualDevice::fetch_ff_events`] directly.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use evdev::presets::ControllerPreset;
+//! use evdev::proxy::FFPassthrough;
+//! use evdev::Device;
+//!
+//! let mut physical = Device::open("/dev/input/event0")?;
+//! let mut pad = ControllerPreset::Xbox360.build()?;
+//! let mut rumble = FFPassthrough::new();
+//! loop {
+//!     // ... translate events from `physical` and emit them on `pad` ...
+//!     rumble.pump(&mut pad, &mut physical)?;
+//! }
+//! # }
+//! ```
+
+use std::io;
+
+use crate::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use crate::{
+    AbsInfo, AbsoluteAxisType, AttributeSet, BusType, FFEffectType, InputId, Key, UinputAbsSetup,
+};
+
+/// A well-known controller to imitate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ControllerPreset {
+    /// A wired Xbox 360 controller, as set up by `xpad`.
+    Xbox360,
+    /// A DualShock 4 (second revision) on USB, as set up by `hid-sony`. Only the gamepad node
+    /// is replicated; the real controller has separate devices for its touchpad and motion
+    /// sensors.
+    DualShock4,
+}
+
+const XBOX360_KEYS: [Key; 11] = [
+    Key::BTN_SOUTH,
+    Key::BTN_EAST,
+    Key::BTN_NORTH,
+    Key::BTN_WEST,
+    Key::BTN_TL,
+    Key::BTN_TR,
+    Key::BTN_SELECT,
+    Key::BTN_START,
+    Key::BTN_MODE,
+    Key::BTN_THUMBL,
+    Key::BTN_THUMBR,
+];
+
+const DUALSHOCK4_KEYS: [Key; 13] = [
+    Key::BTN_SOUTH,
+    Key::BTN_EAST,
+    Key::BTN_NORTH,
+    Key::BTN_WEST,
+    Key::BTN_TL,
+    Key::BTN_TR,
+    Key::BTN_TL2,
+    Key::BTN_TR2,
+    Key::BTN_SELECT,
+    Key::BTN_START,
+    Key::BTN_MODE,
+    Key::BTN_THUMBL,
+    Key::BTN_THUMBR,
+];
+
+/// The effects `ff-memless` drivers such as `xpad` advertise.
+const XBOX360_FF: [FFEffectType; 6] = [
+    FFEffectType::FF_RUMBLE,
+    FFEffectType::FF_PERIODIC,
+    FFEffectType::FF_SQUARE,
+    FFEffectType::FF_TRIANGLE,
+    FFEffectType::FF_SINE,
+    FFEffectType::FF_GAIN,
+];
+
+const DUALSHOCK4_FF: [FFEffectType; 1] = [FFEffectType::FF_RUMBLE];
+
+impl ControllerPreset {
+    /// Returns the device name the real controller reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            ControllerPreset::Xbox360 => "Microsoft X-Box 360 pad",
+            ControllerPreset::DualShock4 => "Sony Interactive Entertainment Wireless Controller",
+        }
+    }
+
+    /// Returns the bus, vendor, product and version the real controller reports.
+    pub fn input_id(self) -> InputId {
+        match self {
+            ControllerPreset::Xbox360 => InputId::new(BusType::BUS_USB, 0x045e, 0x028e, 0x0114),
+            ControllerPreset::DualShock4 => InputId::new(BusType::BUS_USB, 0x054c, 0x09cc, 0x8111),
+        }
+    }
+
+    /// Returns the buttons of the controller.
+    pub fn keys(self) -> AttributeSet<Key> {
+        let keys: &[Key] = match self {
+            ControllerPreset::Xbox360 => &XBOX360_KEYS,
+            ControllerPreset::DualShock4 => &DUALSHOCK4_KEYS,
+        };
+        keys.iter().copied().collect()
+    }
+
+    /// Returns the axes of the controller with their ranges, centered.
+    pub fn absolute_axes(self) -> Vec<UinputAbsSetup> {
+        let axis = |axis, min, max, fuzz, flat| {
+            let value = if min < 0 {
+                0
+            } else if is_trigger(axis) {
+                min
+            } else {
+                (min + max) / 2
+            };
+            UinputAbsSetup::new(axis, AbsInfo::new(value, min, max, fuzz, flat, 0))
+        };
+        let (stick, trigger) = match self {
+            // xpad: 16 bit sticks with a small dead zone, 8 bit triggers
+            ControllerPreset::Xbox360 => ((-32768, 32767, 16, 128), (0, 255, 0, 0)),
+            // hid-input: 8 bit everything, with fuzz and flat derived from the range
+            ControllerPreset::DualShock4 => ((0, 255, 0, 15), (0, 255, 0, 15)),
+        };
+        let mut axes = Vec::new();
+        for code in [
+            AbsoluteAxisType::ABS_X,
+            AbsoluteAxisType::ABS_Y,
+            AbsoluteAxisType::ABS_RX,
+            AbsoluteAxisType::ABS_RY,
+        ] {
+            let (min, max, fuzz, flat) = stick;
+            axes.push(axis(code, min, max, fuzz, flat));
+        }
+        for code in [AbsoluteAxisType::ABS_Z, AbsoluteAxisType::ABS_RZ] {
+            let (min, max, fuzz, flat) = trigger;
+            axes.push(axis(code, min, max, fuzz, flat));
+        }
+        for code in [AbsoluteAxisType::ABS_HAT0X, AbsoluteAxisType::ABS_HAT0Y] {
+            axes.push(axis(code, -1, 1, 0, 0));
+        }
+        axes
+    }
+
+    /// Returns the force feedback effects the controller's driver supports.
+    pub fn ff_effects(self) -> AttributeSet<FFEffectType> {
+        let effects: &[FFEffectType] = match self {
+            ControllerPreset::Xbox360 => &XBOX360_FF,
+            ControllerPreset::DualShock4 => &DUALSHOCK4_FF,
+        };
+        effects.iter().copied().collect()
+    }
+
+    /// Set up `builder` as this controller.
+    pub fn apply<'a>(
+        self,
+        builder: VirtualDeviceBuilder<'a>,
+    ) -> io::Result<VirtualDeviceBuilder<'a>> {
+        let mut builder = builder
+            .name(self.name())
+            .input_id(self.input_id())
+            .with_keys(&self.keys())?
+            .with_ff(&self.ff_effects())?
+            .with_ff_effects_max(16);
+        for axis in self.absolute_axes() {
+            builder = builder.with_absolute_axis(&axis)?;
+        }
+        Ok(builder)
+    }
+
+    /// Create a virtual controller through `/dev/uinput`.
+    pub fn build(self) -> io::Result<VirtualDevice> {
+        self.apply(VirtualDeviceBuilder::new()?)?.build()
+    }
+}
+
+fn is_trigger(axis: AbsoluteAxisType) -> bool {
+    axis == AbsoluteAxisType::ABS_Z || axis == AbsoluteAxisType::ABS_RZ
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventType, InputEvent};
+
+    #[test]
+    fn dualshock4_capabilities() {
+        let preset = ControllerPreset::DualShock4;
+        let builder = preset.apply(VirtualDeviceBuilder::in_memory()).unwrap();
+        let device = builder.build().unwrap();
+
+        let press = InputEvent::new(EventType::KEY, Key::BTN_TL2.code(), 1);
+        let hat = InputEvent::new(EventType::ABSOLUTE, AbsoluteAxisType::ABS_HAT0X.0, -1);
+        assert!(device.check_events(&[press, hat]).is_ok());
+        let rel = InputEvent::new(EventType::RELATIVE, 0, 1);
+        assert!(device.check_events(&[rel]).is_err());
+
+        let stick = preset.absolute_axes()[0].abs_info();
+        assert_eq!((stick.value(), stick.maximum()), (127, 255));
+        assert!(!ControllerPreset::Xbox360.keys().contains(Key::BTN_TL2));
+    }
+}
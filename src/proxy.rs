@@ -0,0 +1,345 @@
+//! Forwarding events from a physical device to a virtual one.
+//!
+//! A [`Proxy`] reads frames from a source [`Device`], runs them through a chain of
+//! [`EventTransform`]s and emits the result on a [`VirtualDevice`]. This is the building block
+//! for remappers, filters and other tools that sit between the hardware and the rest of the
+//! system.
+//!
+//! Force feedback flows the other way: an [`FFPassthrough`] replays effects uploaded to the
+//! virtual device on the physical one, so that rumble keeps working through a remapper.
+//!
+//! Hooks registered with [`Proxy::on_event`] observe the forwarded events without changing
+//! them, e.g. to play a sound when caps lock is pressed or pulse a controller's rumble on a
+//! key press.
+//!
+//! An [`AuditLog`] attached with [`Proxy::with_audit`] records the frames read, with key codes
+//! masked or hashed.
+
+use std::collections::HashMap;
+use std::io;
+use std::time::Instant;
+
+use crate::audit::AuditLog;
+use crate::transform::{frames, is_syn_report, EventTransform, Pipeline};
+use crate::uinput::{VirtualDevice, VirtualFFEvent};
+use crate::{Device, EventType, FFEffectHandle, InputEvent, Key, Metrics};
+
+/// Forwards force feedback requests received by a [`VirtualDevice`] to a physical [`Device`].
+///
+/// Effects are uploaded to the physical device as they are uploaded to the virtual one, and
+/// erased from it when the virtual effect is erased or the passthrough is dropped.
+#[derive(Debug, Default)]
+pub struct FFPassthrough {
+    effects: HashMap<i16, FFEffectHandle>,
+    events: Vec<VirtualFFEvent>,
+}
+
+impl FFPassthrough {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a single request from the virtual device to `target`.
+    pub fn handle(&mut self, event: VirtualFFEvent, target: &mut Device) -> io::Result<()> {
+        match event {
+            VirtualFFEvent::Uploaded { id, effect } => match self.effects.get_mut(&id) {
+                Some(handle) => handle.update(&effect)?,
+                None => {
+                    let handle = target.upload_ff_effect(&effect)?;
+                    self.effects.insert(id, handle);
+                }
+            },
+            VirtualFFEvent::Erased { id } => {
+                self.effects.remove(&id);
+            }
+            VirtualFFEvent::Play { id, count } => {
+                if let Some(handle) = self.effects.get_mut(&id) {
+                    handle.play(count)?;
+                }
+            }
+            VirtualFFEvent::Stop { id } => {
+                if let Some(handle) = self.effects.get_mut(&id) {
+                    handle.stop()?;
+                }
+            }
+            VirtualFFEvent::Gain(gain) => target.set_ff_gain(gain)?,
+            VirtualFFEvent::Autocenter(strength) => target.set_ff_autocenter(strength)?,
+        }
+        Ok(())
+    }
+
+    /// Forward all pending force feedback requests from `source` to `target`.
+    ///
+    /// Returns the number of requests forwarded, which is 0 if none were pending.
+    pub fn pump(&mut self, source: &mut VirtualDevice, target: &mut Device) -> io::Result<usize> {
+        self.events.clear();
+        match source.fetch_ff_events() {
+            Ok(events) => self.events.extend(events),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(0),
+            Err(e) => return Err(e),
+        }
+        let events = std::mem::take(&mut self.events);
+        let res = events
+            .iter()
+            .try_for_each(|&event| self.handle(event, target));
+        self.events = events;
+        res.map(|()| self.events.len())
+    }
+}
+
+/// Matches the events a [`Proxy`] hook is called for.
+///
+/// Each part of the pattern is optional, and an empty pattern matches every event except the
+/// `SYN_REPORT`s terminating frames.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct EventPattern {
+    event_type: Option<EventType>,
+    code: Option<u16>,
+    value: Option<i32>,
+}
+
+impl EventPattern {
+    /// Create a pattern matching every event.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match presses of `key`, not releases or repeats.
+    pub fn key_press(key: Key) -> Self {
+        Self::new().key(key).value(1)
+    }
+
+    /// Match events of `event_type`.
+    pub fn event_type(mut self, event_type: EventType) -> Self {
+        self.event_type = Some(event_type);
+        self
+    }
+
+    /// Match events with `code`.
+    pub fn code(mut self, code: u16) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Match events of `key`.
+    pub fn key(self, key: Key) -> Self {
+        self.event_type(EventType::KEY).code(key.code())
+    }
+
+    /// Match events with `value`.
+    pub fn value(mut self, value: i32) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Returns `true` if `ev` matches the pattern.
+    pub fn matches(&self, ev: &InputEvent) -> bool {
+        if self.event_type.is_none() && is_syn_report(ev) {
+            return false;
+        }
+        self.event_type.is_none_or(|t| t == ev.event_type())
+            && self.code.is_none_or(|c| c == ev.code())
+            && self.value.is_none_or(|v| v == ev.value())
+    }
+}
+
+type Hook = Box<dyn FnMut(&InputEvent) + Send>;
+
+/// Reads frames from a device, transforms them, and re-emits them on a virtual device.
+pub struct Proxy {
+    source: Device,
+    sink: VirtualDevice,
+    ff: FFPassthrough,
+    metrics: Option<Metrics>,
+    audit: Option<AuditLog>,
+    dropped_count: u64,
+    pipeline: Pipeline,
+    hooks: Vec<(EventPattern, Hook)>,
+    pending: Vec<InputEvent>,
+    out: Vec<InputEvent>,
+}
+
+impl Proxy {
+    /// Create a proxy forwarding `source` to `sink` unchanged.
+    pub fn new(source: Device, sink: VirtualDevice) -> Self {
+        Proxy {
+            source,
+            sink,
+            ff: FFPassthrough::new(),
+            metrics: None,
+            audit: None,
+            dropped_count: 0,
+            pipeline: Pipeline::new(),
+            hooks: Vec::new(),
+            pending: Vec::new(),
+            out: Vec::new(),
+        }
+    }
+
+    /// Append a transform to the end of the chain.
+    pub fn with_transform(mut self, transform: impl EventTransform + Send + 'static) -> Self {
+        self.pipeline.push(transform);
+        self
+    }
+
+    /// Call `hook` for every forwarded event matching `pattern`, after it was emitted.
+    ///
+    /// Hooks see the output of the transform chain, and are called in the order they were
+    /// added. They should return quickly, since they delay the next frame.
+    pub fn on_event(
+        mut self,
+        pattern: EventPattern,
+        hook: impl FnMut(&InputEvent) + Send + 'static,
+    ) -> Self {
+        self.hooks.push((pattern, Box::new(hook)));
+        self
+    }
+
+    /// Collect [`Metrics`] about the forwarded events.
+    pub fn with_metrics(mut self) -> Self {
+        self.metrics = Some(Metrics::new());
+        self
+    }
+
+    /// Returns the collected metrics, if enabled with [`with_metrics`](Self::with_metrics).
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Returns the collected metrics mutably, e.g. to reset them.
+    pub fn metrics_mut(&mut self) -> Option<&mut Metrics> {
+        self.metrics.as_mut()
+    }
+
+    /// Record every frame read from the source in `log`, before it is transformed.
+    pub fn with_audit(mut self, log: AuditLog) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    /// Returns the audit log, if enabled with [`with_audit`](Self::with_audit).
+    pub fn audit_mut(&mut self) -> Option<&mut AuditLog> {
+        self.audit.as_mut()
+    }
+
+    /// Returns a reference to the source device.
+    pub fn source(&self) -> &Device {
+        &self.source
+    }
+
+    /// Returns a mutable reference to the source device.
+    pub fn source_mut(&mut self) -> &mut Device {
+        &mut self.source
+    }
+
+    /// Returns a reference to the virtual output device.
+    pub fn sink(&self) -> &VirtualDevice {
+        &self.sink
+    }
+
+    /// Returns a mutable reference to the virtual output device.
+    pub fn sink_mut(&mut self) -> &mut VirtualDevice {
+        &mut self.sink
+    }
+
+    /// Grab the source device, so that only the virtual device's events reach other clients.
+    pub fn grab(&mut self) -> io::Result<()> {
+        self.source.grab()
+    }
+
+    /// Consume the proxy, returning the source and output devices.
+    pub fn into_inner(self) -> (Device, VirtualDevice) {
+        (self.source, self.sink)
+    }
+
+    /// Fetch one batch of events from the source and forward every complete frame.
+    ///
+    /// By default this will block until events are available.
+    pub fn pump(&mut self) -> io::Result<()> {
+        self.pending.extend(self.source.fetch_events()?);
+        let read_at = Instant::now();
+        if let Some(metrics) = &mut self.metrics {
+            let dropped = self.source.dropped_count();
+            metrics.record_syn_dropped(dropped - self.dropped_count);
+            self.dropped_count = dropped;
+        }
+        let end = match self.pending.iter().rposition(is_syn_report) {
+            Some(idx) => idx + 1,
+            None => return Ok(()),
+        };
+        let pending = std::mem::take(&mut self.pending);
+        let res = frames(&pending[..end]).try_for_each(|frame| {
+            if let Some(audit) = &mut self.audit {
+                audit.record_frame(frame)?;
+            }
+            self.forward(frame)?;
+            if let Some(metrics) = &mut self.metrics {
+                metrics.record_frame(frame, read_at);
+            }
+            Ok(())
+        });
+        self.pending = pending;
+        self.pending.drain(..end);
+        res
+    }
+
+    /// Forward pending force feedback requests from the virtual device back to the source.
+    ///
+    /// The virtual device must have been built with
+    /// [`with_ff`](crate::uinput::VirtualDeviceBuilder::with_ff). Call this whenever the
+    /// virtual device's fd becomes readable; it never blocks.
+    pub fn pump_ff(&mut self) -> io::Result<usize> {
+        self.ff.pump(&mut self.sink, &mut self.source)
+    }
+
+    /// Forward events until an error occurs.
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.pump()?;
+        }
+    }
+
+    /// Run a complete frame through the transform chain and emit the result.
+    ///
+    /// Transforms see the source's [cached state](Device::cached_state) as of the last read.
+    pub fn forward(&mut self, frame: &[InputEvent]) -> io::Result<()> {
+        self.out.clear();
+        self.pipeline
+            .process_with_state(frame, self.source.cached_state(), &mut self.out);
+        for frame in frames(&self.out) {
+            // `emit` terminates the batch with its own SYN_REPORT
+            let events = &frame[..frame.len() - 1];
+            if !events.is_empty() {
+                self.sink.emit(events)?;
+            }
+            for ev in events {
+                for (pattern, hook) in &mut self.hooks {
+                    if pattern.matches(ev) {
+                        hook(ev);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_patterns() {
+        let press = InputEvent::new(EventType::KEY, Key::KEY_CAPSLOCK.code(), 1);
+        let release = InputEvent::new(EventType::KEY, Key::KEY_CAPSLOCK.code(), 0);
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+
+        let caps = EventPattern::key_press(Key::KEY_CAPSLOCK);
+        assert!(caps.matches(&press));
+        assert!(!caps.matches(&release));
+        assert!(EventPattern::new().matches(&release));
+        assert!(!EventPattern::new().matches(&syn));
+        let syns = EventPattern::new().event_type(EventType::SYNCHRONIZATION);
+        assert!(syns.matches(&syn));
+    }
+}
@@ -0,0 +1,617 @@
+//! Thin wrappers for the evdev and uinput ioctls.
+//!
+//! [`Device`](crate::Device) and [`VirtualDevice`](crate::uinput::VirtualDevice) cover what
+//! most programs need, but some want to talk to the kernel directly: to query a descriptor
+//! received from elsewhere, to issue an ioctl the higher-level types don't expose, or to build
+//! their own abstractions. The functions here do one ioctl each, named after the ioctl, on any
+//! descriptor implementing [`AsFd`].
+//!
+//! Failures are reported as [`Error::Ioctl`](crate::Error::Ioctl), carrying the ioctl's name.
+//!
+//! Bitmask ioctls fill byte buffers, in the layout of the kernel's `unsigned long` arrays: bit
+//! `n` is bit `n % 8` of byte `n / 8`.
+//!
+//! ```no_run
+//! use evdev::raw;
+//! use std::fs::File;
+//!
+//! let file = File::open("/dev/input/event3")?;
+//! // SAFETY: event nodes are evdev devices
+//! unsafe {
+//!     let name = raw::eviocgname(&file)?;
+//!     let id = raw::eviocgid(&file)?;
+//!     println!("{} ({:?})", String::from_utf8_lossy(&name), id);
+//!     raw::eviocgrab(&file, true)?;
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! # Safety
+//!
+//! The size of every ioctl's argument is encoded in its request number, and the buffers passed
+//! are always that size. The request numbers are only unique per driver, though: evdev shares
+//! its `'E'` magic with other drivers, sound among them, which may take the same number for a
+//! different request. So the functions are `unsafe`, and `fd` must be an evdev device for the
+//! `evioc*` functions and a uinput descriptor for the `ui_*` ones, e.g. one taken from a
+//! [`Device`](crate::Device) or [`VirtualDevice`](crate::uinput::VirtualDevice).
+
+use std::ffi::CStr;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsFd, AsRawFd};
+
+use libc::c_int;
+
+use crate::error::ioctl_error;
+use crate::{
+    sys, AbsoluteAxisType, AutoRepeat, EventType, FFEffectType, InputId, Key, LedType, MiscType,
+    PropType, RelativeAxisType, SoundType, SwitchType,
+};
+
+fn raw(fd: &impl AsFd) -> c_int {
+    fd.as_fd().as_raw_fd()
+}
+
+/// Read a string ioctl, returning it without the trailing NUL.
+fn get_string(
+    f: unsafe fn(c_int, &mut [u8]) -> nix::Result<c_int>,
+    name: &'static str,
+    fd: c_int,
+) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; 256];
+    let len = unsafe { f(fd, &mut buf) }.map_err(ioctl_error(name))?;
+    buf.truncate(len.clamp(0, 256) as usize);
+    if let Some(nul) = buf.iter().position(|&b| b == 0) {
+        buf.truncate(nul);
+    }
+    Ok(buf)
+}
+
+/// `EVIOCGVERSION`: returns the version of the evdev protocol, e.g. `0x010001`.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgversion(fd: impl AsFd) -> io::Result<i32> {
+    let mut version = 0;
+    unsafe { sys::eviocgversion(raw(&fd), &mut version) }.map_err(ioctl_error("EVIOCGVERSION"))?;
+    Ok(version)
+}
+
+/// `EVIOCGID`: returns the bus type, vendor, product and version of the device.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgid(fd: impl AsFd) -> io::Result<InputId> {
+    let mut id = MaybeUninit::uninit();
+    unsafe { sys::eviocgid(raw(&fd), id.as_mut_ptr()) }.map_err(ioctl_error("EVIOCGID"))?;
+    // SAFETY: the kernel filled it in
+    Ok(InputId::from(unsafe { id.assume_init() }))
+}
+
+/// `EVIOCGREP`: returns the key repeat settings.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgrep(fd: impl AsFd) -> io::Result<AutoRepeat> {
+    let mut rep = [0; 2];
+    unsafe { sys::eviocgrep(raw(&fd), &mut rep) }.map_err(ioctl_error("EVIOCGREP"))?;
+    Ok(AutoRepeat {
+        delay: rep[0],
+        period: rep[1],
+    })
+}
+
+/// `EVIOCSREP`: changes the key repeat settings.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocsrep(fd: impl AsFd, repeat: &AutoRepeat) -> io::Result<()> {
+    let rep = [repeat.delay, repeat.period];
+    unsafe { sys::eviocsrep(raw(&fd), &rep) }.map_err(ioctl_error("EVIOCSREP"))?;
+    Ok(())
+}
+
+/// `EVIOCGKEYCODE`: returns the key code a scancode is mapped to.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgkeycode(fd: impl AsFd, scancode: u32) -> io::Result<u32> {
+    let mut entry = [scancode, 0];
+    unsafe { sys::eviocgkeycode(raw(&fd), &mut entry) }.map_err(ioctl_error("EVIOCGKEYCODE"))?;
+    Ok(entry[1])
+}
+
+/// `EVIOCSKEYCODE`: maps a scancode to a key code.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocskeycode(fd: impl AsFd, scancode: u32, keycode: u32) -> io::Result<()> {
+    unsafe { sys::eviocskeycode(raw(&fd), &[scancode, keycode]) }
+        .map_err(ioctl_error("EVIOCSKEYCODE"))?;
+    Ok(())
+}
+
+/// `EVIOCGKEYCODE_V2`: looks up a keymap entry, by the scancode in `entry`, or by its index
+/// if `entry.flags` has `INPUT_KEYMAP_BY_INDEX`, and fills in the rest.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgkeycode_v2(
+    fd: impl AsFd,
+    entry: &mut libc::input_keymap_entry,
+) -> io::Result<()> {
+    unsafe { sys::eviocgkeycode_v2(raw(&fd), entry) }.map_err(ioctl_error("EVIOCGKEYCODE_V2"))?;
+    Ok(())
+}
+
+/// `EVIOCSKEYCODE_V2`: changes a keymap entry.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocskeycode_v2(fd: impl AsFd, entry: &libc::input_keymap_entry) -> io::Result<()> {
+    unsafe { sys::eviocskeycode_v2(raw(&fd), entry) }.map_err(ioctl_error("EVIOCSKEYCODE_V2"))?;
+    Ok(())
+}
+
+/// `EVIOCGNAME`: returns the device name.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgname(fd: impl AsFd) -> io::Result<Vec<u8>> {
+    get_string(sys::eviocgname, "EVIOCGNAME", raw(&fd))
+}
+
+/// `EVIOCGPHYS`: returns the physical location of the device, e.g. `usb-0000:00:14.0-2/input0`.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgphys(fd: impl AsFd) -> io::Result<Vec<u8>> {
+    get_string(sys::eviocgphys, "EVIOCGPHYS", raw(&fd))
+}
+
+/// `EVIOCGUNIQ`: returns the unique identifier of the device, such as a serial number.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocguniq(fd: impl AsFd) -> io::Result<Vec<u8>> {
+    get_string(sys::eviocguniq, "EVIOCGUNIQ", raw(&fd))
+}
+
+/// `EVIOCGPROP`: fills `buf` with the bitmask of the device's [`PropType`]s. Returns the
+/// number of bytes filled.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgprop(fd: impl AsFd, buf: &mut [u8]) -> io::Result<usize> {
+    let len = unsafe { sys::eviocgprop(raw(&fd), buf) }.map_err(ioctl_error("EVIOCGPROP"))?;
+    Ok(len as usize)
+}
+
+/// `EVIOCGMTSLOTS`: fills `values` with the value of the multitouch `axis` in each slot,
+/// starting with slot 0.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgmtslots(
+    fd: impl AsFd,
+    axis: AbsoluteAxisType,
+    values: &mut [i32],
+) -> io::Result<()> {
+    // The kernel reads the axis from the first element and fills in the rest
+    let mut buf = vec![0i32; values.len() + 1];
+    buf[0] = axis.0.into();
+    // SAFETY: any bytes are valid i32s
+    let bytes =
+        unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr().cast::<u8>(), buf.len() * 4) };
+    unsafe { sys::eviocgmtslots(raw(&fd), bytes) }.map_err(ioctl_error("EVIOCGMTSLOTS"))?;
+    values.copy_from_slice(&buf[1..]);
+    Ok(())
+}
+
+/// `EVIOCGKEY`: fills `buf` with the bitmask of the [`Key`]s held. Returns the number of
+/// bytes filled.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgkey(fd: impl AsFd, buf: &mut [u8]) -> io::Result<usize> {
+    let len = unsafe { sys::eviocgkey(raw(&fd), buf) }.map_err(ioctl_error("EVIOCGKEY"))?;
+    Ok(len as usize)
+}
+
+/// `EVIOCGLED`: fills `buf` with the bitmask of the [`LedType`]s lit. Returns the number of
+/// bytes filled.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgled(fd: impl AsFd, buf: &mut [u8]) -> io::Result<usize> {
+    let len = unsafe { sys::eviocgled(raw(&fd), buf) }.map_err(ioctl_error("EVIOCGLED"))?;
+    Ok(len as usize)
+}
+
+/// `EVIOCGSND`: fills `buf` with the bitmask of the [`SoundType`]s playing. Returns the number
+/// of bytes filled.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgsnd(fd: impl AsFd, buf: &mut [u8]) -> io::Result<usize> {
+    let len = unsafe { sys::eviocgsnd(raw(&fd), buf) }.map_err(ioctl_error("EVIOCGSND"))?;
+    Ok(len as usize)
+}
+
+/// `EVIOCGSW`: fills `buf` with the bitmask of the [`SwitchType`]s on. Returns the number of
+/// bytes filled.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgsw(fd: impl AsFd, buf: &mut [u8]) -> io::Result<usize> {
+    let len = unsafe { sys::eviocgsw(raw(&fd), buf) }.map_err(ioctl_error("EVIOCGSW"))?;
+    Ok(len as usize)
+}
+
+/// `EVIOCGBIT`: fills `buf` with the bitmask of the codes of `event_type` the device
+/// supports, or of the supported event types if `event_type` is
+/// [`SYNCHRONIZATION`](EventType::SYNCHRONIZATION). Returns the number of bytes filled.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgbit(fd: impl AsFd, event_type: EventType, buf: &mut [u8]) -> io::Result<usize> {
+    let len = unsafe { sys::eviocgbit(raw(&fd), event_type.0.into(), buf) }
+        .map_err(ioctl_error("EVIOCGBIT"))?;
+    Ok(len as usize)
+}
+
+fn check_axis(axis: AbsoluteAxisType) -> io::Result<u32> {
+    match axis.0 {
+        axis @ 0..=0x3f => Ok(axis.into()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "absolute axis out of range",
+        )),
+    }
+}
+
+/// `EVIOCGABS`: returns the value and limits of an absolute axis.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgabs(fd: impl AsFd, axis: AbsoluteAxisType) -> io::Result<libc::input_absinfo> {
+    let axis = check_axis(axis)?;
+    let mut info = MaybeUninit::zeroed();
+    // SAFETY: the axis is in range, and the kernel checks that the device has it
+    unsafe { sys::eviocgabs(raw(&fd), axis, &mut *info.as_mut_ptr()) }
+        .map_err(ioctl_error("EVIOCGABS"))?;
+    Ok(unsafe { info.assume_init() })
+}
+
+/// `EVIOCSABS`: changes the value and limits of an absolute axis.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocsabs(
+    fd: impl AsFd,
+    axis: AbsoluteAxisType,
+    info: &libc::input_absinfo,
+) -> io::Result<()> {
+    let axis = check_axis(axis)?;
+    unsafe { sys::eviocsabs(raw(&fd), axis, info) }.map_err(ioctl_error("EVIOCSABS"))?;
+    Ok(())
+}
+
+/// `EVIOCSFF`: uploads a force feedback effect, or updates the one with `effect.id`. Returns
+/// the id of the effect, which the kernel assigns when `effect.id` is -1.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocsff(fd: impl AsFd, effect: &mut libc::ff_effect) -> io::Result<i16> {
+    unsafe { sys::eviocsff(raw(&fd), effect) }.map_err(ioctl_error("EVIOCSFF"))?;
+    Ok(effect.id)
+}
+
+/// `EVIOCRMFF`: erases an uploaded force feedback effect.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocrmff(fd: impl AsFd, id: i16) -> io::Result<()> {
+    unsafe { sys::eviocrmff(raw(&fd), id as _) }.map_err(ioctl_error("EVIOCRMFF"))?;
+    Ok(())
+}
+
+/// `EVIOCGEFFECTS`: returns how many force feedback effects the device can play at once.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgeffects(fd: impl AsFd) -> io::Result<usize> {
+    let mut effects = 0;
+    unsafe { sys::eviocgeffects(raw(&fd), &mut effects) }.map_err(ioctl_error("EVIOCGEFFECTS"))?;
+    Ok(effects as usize)
+}
+
+/// `EVIOCGRAB`: grabs the device, so that only this descriptor receives its events, or
+/// releases it.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgrab(fd: impl AsFd, grab: bool) -> io::Result<()> {
+    unsafe { sys::eviocgrab(raw(&fd), grab.into()) }.map_err(ioctl_error("EVIOCGRAB"))?;
+    Ok(())
+}
+
+/// `EVIOCREVOKE`: revokes access to the device through this open file, and every descriptor
+/// sharing it.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocrevoke(fd: impl AsFd) -> io::Result<()> {
+    unsafe { sys::eviocrevoke(raw(&fd), 0) }.map_err(ioctl_error("EVIOCREVOKE"))?;
+    Ok(())
+}
+
+/// `EVIOCGMASK`: fills `buf` with the bitmask of the codes of `event_type` this descriptor
+/// receives. Codes beyond the end of `buf` are left out.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocgmask(fd: impl AsFd, event_type: EventType, buf: &mut [u8]) -> io::Result<()> {
+    let mut mask = libc::input_mask {
+        type_: event_type.0.into(),
+        codes_size: buf.len() as u32,
+        codes_ptr: buf.as_mut_ptr() as u64,
+    };
+    unsafe { sys::eviocgmask(raw(&fd), &mut mask) }.map_err(ioctl_error("EVIOCGMASK"))?;
+    Ok(())
+}
+
+/// `EVIOCSMASK`: sets which codes of `event_type` this descriptor receives, as a bitmask.
+/// Codes beyond the end of `mask` are masked out.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocsmask(fd: impl AsFd, event_type: EventType, mask: &[u8]) -> io::Result<()> {
+    let mask = libc::input_mask {
+        type_: event_type.0.into(),
+        codes_size: mask.len() as u32,
+        codes_ptr: mask.as_ptr() as u64,
+    };
+    unsafe { sys::eviocsmask(raw(&fd), &mask) }.map_err(ioctl_error("EVIOCSMASK"))?;
+    Ok(())
+}
+
+/// `EVIOCSCLOCKID`: sets the clock the event timestamps come from, one of
+/// `CLOCK_REALTIME`, `CLOCK_MONOTONIC` and `CLOCK_BOOTTIME`.
+///
+/// # Safety
+///
+/// `fd` must be an evdev device, see [Safety](self#safety).
+pub unsafe fn eviocsclockid(fd: impl AsFd, clock: libc::clockid_t) -> io::Result<()> {
+    unsafe { sys::eviocsclockid(raw(&fd), clock as _) }.map_err(ioctl_error("EVIOCSCLOCKID"))?;
+    Ok(())
+}
+
+/// `UI_GET_VERSION`: returns the version of the uinput protocol.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_get_version(fd: impl AsFd) -> io::Result<u32> {
+    let mut version = 0;
+    unsafe { sys::ui_get_version(raw(&fd), &mut version) }
+        .map_err(ioctl_error("UI_GET_VERSION"))?;
+    Ok(version)
+}
+
+/// `UI_DEV_SETUP`: sets the name, id and force feedback effect count of the device to create.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_dev_setup(fd: impl AsFd, setup: &libc::uinput_setup) -> io::Result<()> {
+    unsafe { sys::ui_dev_setup(raw(&fd), setup) }.map_err(ioctl_error("UI_DEV_SETUP"))?;
+    Ok(())
+}
+
+/// `UI_ABS_SETUP`: sets up an absolute axis of the device to create.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_abs_setup(fd: impl AsFd, setup: &libc::uinput_abs_setup) -> io::Result<()> {
+    unsafe { sys::ui_abs_setup(raw(&fd), setup) }.map_err(ioctl_error("UI_ABS_SETUP"))?;
+    Ok(())
+}
+
+/// `UI_DEV_CREATE`: creates the device.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_dev_create(fd: impl AsFd) -> io::Result<()> {
+    unsafe { sys::ui_dev_create(raw(&fd)) }.map_err(ioctl_error("UI_DEV_CREATE"))?;
+    Ok(())
+}
+
+/// `UI_DEV_DESTROY`: destroys the device, as closing the descriptor does.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_dev_destroy(fd: impl AsFd) -> io::Result<()> {
+    unsafe { sys::ui_dev_destroy(raw(&fd)) }.map_err(ioctl_error("UI_DEV_DESTROY"))?;
+    Ok(())
+}
+
+/// `UI_GET_SYSNAME`: returns the name of the created device in `/sys/devices/virtual/input`,
+/// e.g. `input42`.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_get_sysname(fd: impl AsFd) -> io::Result<Vec<u8>> {
+    get_string(sys::ui_get_sysname, "UI_GET_SYSNAME", raw(&fd))
+}
+
+/// `UI_SET_PHYS`: sets the physical location of the device to create.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_set_phys(fd: impl AsFd, phys: &CStr) -> io::Result<()> {
+    unsafe { sys::ui_set_phys(raw(&fd), phys.to_bytes_with_nul()) }
+        .map_err(ioctl_error("UI_SET_PHYS"))?;
+    Ok(())
+}
+
+macro_rules! ui_set_bit {
+    ($(#[$doc:meta])* $name:ident, $ty:ty, $ioctl:literal) => {
+        $(#[$doc])*
+        ///
+        /// # Safety
+        ///
+        /// `fd` must be a uinput descriptor, see [Safety](self#safety).
+        pub unsafe fn $name(fd: impl AsFd, bit: $ty) -> io::Result<()> {
+            unsafe { sys::$name(raw(&fd), bit.0.into()) }.map_err(ioctl_error($ioctl))?;
+            Ok(())
+        }
+    };
+}
+
+ui_set_bit!(
+    /// `UI_SET_EVBIT`: enables an event type on the device to create.
+    ui_set_evbit, EventType, "UI_SET_EVBIT"
+);
+ui_set_bit!(
+    /// `UI_SET_KEYBIT`: enables a key on the device to create.
+    ui_set_keybit, Key, "UI_SET_KEYBIT"
+);
+ui_set_bit!(
+    /// `UI_SET_RELBIT`: enables a relative axis on the device to create.
+    ui_set_relbit, RelativeAxisType, "UI_SET_RELBIT"
+);
+ui_set_bit!(
+    /// `UI_SET_ABSBIT`: enables an absolute axis on the device to create.
+    ui_set_absbit, AbsoluteAxisType, "UI_SET_ABSBIT"
+);
+ui_set_bit!(
+    /// `UI_SET_MSCBIT`: enables a misc event on the device to create.
+    ui_set_mscbit, MiscType, "UI_SET_MSCBIT"
+);
+ui_set_bit!(
+    /// `UI_SET_LEDBIT`: enables an LED on the device to create.
+    ui_set_ledbit, LedType, "UI_SET_LEDBIT"
+);
+ui_set_bit!(
+    /// `UI_SET_SNDBIT`: enables a sound on the device to create.
+    ui_set_sndbit, SoundType, "UI_SET_SNDBIT"
+);
+ui_set_bit!(
+    /// `UI_SET_FFBIT`: enables a force feedback effect type on the device to create.
+    ui_set_ffbit, FFEffectType, "UI_SET_FFBIT"
+);
+ui_set_bit!(
+    /// `UI_SET_SWBIT`: enables a switch on the device to create.
+    ui_set_swbit, SwitchType, "UI_SET_SWBIT"
+);
+ui_set_bit!(
+    /// `UI_SET_PROPBIT`: sets a property of the device to create.
+    ui_set_propbit, PropType, "UI_SET_PROPBIT"
+);
+
+/// `UI_BEGIN_FF_UPLOAD`: fetches the effect of the upload request `upload.request_id`, read
+/// as a `UI_FF_UPLOAD` event.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_begin_ff_upload(
+    fd: impl AsFd,
+    upload: &mut libc::uinput_ff_upload,
+) -> io::Result<()> {
+    unsafe { sys::ui_begin_ff_upload(raw(&fd), upload) }
+        .map_err(ioctl_error("UI_BEGIN_FF_UPLOAD"))?;
+    Ok(())
+}
+
+/// `UI_END_FF_UPLOAD`: completes an upload request, with the result in `upload.retval`.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_end_ff_upload(fd: impl AsFd, upload: &libc::uinput_ff_upload) -> io::Result<()> {
+    unsafe { sys::ui_end_ff_upload(raw(&fd), upload) }.map_err(ioctl_error("UI_END_FF_UPLOAD"))?;
+    Ok(())
+}
+
+/// `UI_BEGIN_FF_ERASE`: fetches the effect id of the erase request `erase.request_id`, read
+/// as a `UI_FF_ERASE` event.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_begin_ff_erase(
+    fd: impl AsFd,
+    erase: &mut libc::uinput_ff_erase,
+) -> io::Result<()> {
+    unsafe { sys::ui_begin_ff_erase(raw(&fd), erase) }.map_err(ioctl_error("UI_BEGIN_FF_ERASE"))?;
+    Ok(())
+}
+
+/// `UI_END_FF_ERASE`: completes an erase request, with the result in `erase.retval`.
+///
+/// # Safety
+///
+/// `fd` must be a uinput descriptor, see [Safety](self#safety).
+pub unsafe fn ui_end_ff_erase(fd: impl AsFd, erase: &libc::uinput_ff_erase) -> io::Result<()> {
+    unsafe { sys::ui_end_ff_erase(raw(&fd), erase) }.map_err(ioctl_error("UI_END_FF_ERASE"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_a_device() {
+        let file = std::fs::File::open("/dev/null").unwrap();
+        // SAFETY: /dev/null has no ioctls
+        let err = unsafe { eviocgversion(&file) }.unwrap_err();
+        assert!(matches!(
+            crate::Error::from(err),
+            crate::Error::Ioctl {
+                name: "EVIOCGVERSION",
+                errno: nix::errno::Errno::ENOTTY,
+            }
+        ));
+        assert_eq!(
+            unsafe { eviocgabs(&file, AbsoluteAxisType(0x40)) }
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
@@ -1,12 +1,21 @@
-use std::fs::{File, OpenOptions};
+use std::fs::File;
 use std::io::Write;
 use std::mem::MaybeUninit;
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{io, mem};
 
+use nix::errno::Errno;
+
 use crate::constants::*;
-use crate::{sys, AttributeSet, AttributeSetRef, InputEvent, InputId, Key};
+use crate::error::{device_holders, ioctl_error, Error};
+use crate::{
+    sys, AbsInfo, AttributeSet, AttributeSetRef, CapabilityReport, FFEffect, FFEffectHandle,
+    FFEffectKind, FFRumble, InputEvent, InputId, Key, MetadataChanges, OpenOptions,
+    FF_MAX_DURATION,
+};
 
 fn ioctl_get_cstring(
     f: unsafe fn(RawFd, &mut [u8]) -> nix::Result<libc::c_int>,
@@ -48,7 +57,8 @@ const INPUT_KEYMAP_BY_INDEX: u8 = 1;
 /// and reflects changes in its position via "relative axis" reports.
 #[derive(Debug)]
 pub struct RawDevice {
-    file: File,
+    /// Shared with the [`FFEffectHandle`]s, which mustn't close a duplicate of it.
+    file: Arc<File>,
     ty: AttributeSet<EventType>,
     name: Option<String>,
     phys: Option<String>,
@@ -63,11 +73,15 @@ pub struct RawDevice {
     supported_led: Option<AttributeSet<LedType>>,
     supported_misc: Option<AttributeSet<MiscType>>,
     auto_repeat: Option<AutoRepeat>,
-    // ff: Option<AttributeSet<_>>,
+    supported_ff: Option<AttributeSet<FFEffectType>>,
     // ff_stat: Option<FFStatus>,
     supported_snd: Option<AttributeSet<SoundType>>,
+    abs_info: Option<Box<[libc::input_absinfo; AbsoluteAxisType::COUNT]>>,
     pub(crate) event_buf: Vec<libc::input_event>,
+    event_buf_size: usize,
     grabbed: bool,
+    /// The effect played by [`rumble`](Self::rumble), and when it finishes.
+    rumble: Option<(FFEffectHandle, Instant)>,
 }
 
 #[derive(Debug, Clone)]
@@ -87,18 +101,81 @@ impl RawDevice {
     }
 
     fn _open(path: &Path) -> io::Result<RawDevice> {
-        let mut options = OpenOptions::new();
+        Self::open_with(path, &OpenOptions::new())
+    }
+
+    /// Opens a device like [`open`](Self::open), with the access mode and descriptor flags set
+    /// by `options`.
+    pub fn open_with(path: impl AsRef<Path>, options: &OpenOptions) -> io::Result<RawDevice> {
+        Self::from_file(options.open_device(path.as_ref())?)
+    }
+
+    /// A device with nothing but keys and rumble using `file`, e.g. a pipe, for tests that
+    /// have no real device.
+    #[cfg(test)]
+    pub(crate) fn from_file_unchecked(file: File) -> RawDevice {
+        let mut ty = AttributeSet::new();
+        ty.insert(EventType::KEY);
+        ty.insert(EventType::FORCEFEEDBACK);
+        let mut ff = AttributeSet::new();
+        ff.insert(FFEffectType::FF_RUMBLE);
+        RawDevice {
+            file: Arc::new(file),
+            ty,
+            name: None,
+            phys: None,
+            uniq: None,
+            id: libc::input_id {
+                bustype: 0,
+                vendor: 0,
+                product: 0,
+                version: 0,
+            },
+            props: AttributeSet::new(),
+            driver_version: (1, 0, 1),
+            supported_keys: Some(AttributeSet::new()),
+            supported_relative: None,
+            supported_absolute: None,
+            supported_switch: None,
+            supported_led: None,
+            supported_misc: None,
+            auto_repeat: None,
+            supported_ff: Some(ff),
+            supported_snd: None,
+            abs_info: None,
+            event_buf: Vec::new(),
+            event_buf_size: crate::EVENT_BATCH_SIZE,
+            grabbed: false,
+            rumble: None,
+        }
+    }
 
-        // Try to load read/write, then fall back to read-only.
-        let file = options
-            .read(true)
-            .write(true)
-            .open(path)
-            .or_else(|_| options.write(false).open(path))?;
+    /// Query the capabilities of the device behind `file`.
+    pub(crate) fn from_file(file: File) -> io::Result<RawDevice> {
+        Self::query(Arc::new(file))
+    }
+
+    /// Query the capabilities of the device behind `file`, without duplicating it: closing a
+    /// duplicate would erase the effects uploaded through `file`.
+    fn query(file: Arc<File>) -> io::Result<RawDevice> {
+        // Checked first, so anything but an evdev node fails on it
+        let mut driver_version: i32 = 0;
+        unsafe {
+            sys::eviocgversion(file.as_raw_fd(), &mut driver_version)
+                .map_err(ioctl_error("EVIOCGVERSION"))?;
+        }
+        let driver_version = (
+            ((driver_version >> 16) & 0xff) as u8,
+            ((driver_version >> 8) & 0xff) as u8,
+            (driver_version & 0xff) as u8,
+        );
 
         let ty = {
             let mut ty = AttributeSet::<EventType>::new();
-            unsafe { sys::eviocgbit_type(file.as_raw_fd(), ty.as_mut_raw_slice())? };
+            unsafe {
+                sys::eviocgbit_type(file.as_raw_fd(), ty.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGBIT"))?
+            };
             ty
         };
 
@@ -111,28 +188,25 @@ impl RawDevice {
 
         let id = unsafe {
             let mut id = MaybeUninit::uninit();
-            sys::eviocgid(file.as_raw_fd(), id.as_mut_ptr())?;
+            sys::eviocgid(file.as_raw_fd(), id.as_mut_ptr()).map_err(ioctl_error("EVIOCGID"))?;
             id.assume_init()
         };
-        let mut driver_version: i32 = 0;
-        unsafe {
-            sys::eviocgversion(file.as_raw_fd(), &mut driver_version)?;
-        }
-        let driver_version = (
-            ((driver_version >> 16) & 0xff) as u8,
-            ((driver_version >> 8) & 0xff) as u8,
-            (driver_version & 0xff) as u8,
-        );
 
         let props = {
             let mut props = AttributeSet::<PropType>::new();
-            unsafe { sys::eviocgprop(file.as_raw_fd(), props.as_mut_raw_slice())? };
+            unsafe {
+                sys::eviocgprop(file.as_raw_fd(), props.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGPROP"))?
+            };
             props
         }; // FIXME: handle old kernel
 
         let supported_keys = if ty.contains(EventType::KEY) {
             let mut keys = AttributeSet::<Key>::new();
-            unsafe { sys::eviocgbit_key(file.as_raw_fd(), keys.as_mut_raw_slice())? };
+            unsafe {
+                sys::eviocgbit_key(file.as_raw_fd(), keys.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGBIT"))?
+            };
             Some(keys)
         } else {
             None
@@ -140,7 +214,10 @@ impl RawDevice {
 
         let supported_relative = if ty.contains(EventType::RELATIVE) {
             let mut rel = AttributeSet::<RelativeAxisType>::new();
-            unsafe { sys::eviocgbit_relative(file.as_raw_fd(), rel.as_mut_raw_slice())? };
+            unsafe {
+                sys::eviocgbit_relative(file.as_raw_fd(), rel.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGBIT"))?
+            };
             Some(rel)
         } else {
             None
@@ -148,7 +225,10 @@ impl RawDevice {
 
         let supported_absolute = if ty.contains(EventType::ABSOLUTE) {
             let mut abs = AttributeSet::<AbsoluteAxisType>::new();
-            unsafe { sys::eviocgbit_absolute(file.as_raw_fd(), abs.as_mut_raw_slice())? };
+            unsafe {
+                sys::eviocgbit_absolute(file.as_raw_fd(), abs.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGBIT"))?
+            };
             Some(abs)
         } else {
             None
@@ -156,7 +236,10 @@ impl RawDevice {
 
         let supported_switch = if ty.contains(EventType::SWITCH) {
             let mut switch = AttributeSet::<SwitchType>::new();
-            unsafe { sys::eviocgbit_switch(file.as_raw_fd(), switch.as_mut_raw_slice())? };
+            unsafe {
+                sys::eviocgbit_switch(file.as_raw_fd(), switch.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGBIT"))?
+            };
             Some(switch)
         } else {
             None
@@ -164,7 +247,10 @@ impl RawDevice {
 
         let supported_led = if ty.contains(EventType::LED) {
             let mut led = AttributeSet::<LedType>::new();
-            unsafe { sys::eviocgbit_led(file.as_raw_fd(), led.as_mut_raw_slice())? };
+            unsafe {
+                sys::eviocgbit_led(file.as_raw_fd(), led.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGBIT"))?
+            };
             Some(led)
         } else {
             None
@@ -172,17 +258,32 @@ impl RawDevice {
 
         let supported_misc = if ty.contains(EventType::MISC) {
             let mut misc = AttributeSet::<MiscType>::new();
-            unsafe { sys::eviocgbit_misc(file.as_raw_fd(), misc.as_mut_raw_slice())? };
+            unsafe {
+                sys::eviocgbit_misc(file.as_raw_fd(), misc.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGBIT"))?
+            };
             Some(misc)
         } else {
             None
         };
 
-        //unsafe { sys::eviocgbit(file.as_raw_fd(), ffs(FORCEFEEDBACK.bits()), 0x7f, bits_as_u8_slice)?; }
+        let supported_ff = if ty.contains(EventType::FORCEFEEDBACK) {
+            let mut ff = AttributeSet::<FFEffectType>::new();
+            unsafe {
+                sys::eviocgbit_ff(file.as_raw_fd(), ff.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGBIT"))?
+            };
+            Some(ff)
+        } else {
+            None
+        };
 
         let supported_snd = if ty.contains(EventType::SOUND) {
             let mut snd = AttributeSet::<SoundType>::new();
-            unsafe { sys::eviocgbit_sound(file.as_raw_fd(), snd.as_mut_raw_slice())? };
+            unsafe {
+                sys::eviocgbit_sound(file.as_raw_fd(), snd.as_mut_raw_slice())
+                    .map_err(ioctl_error("EVIOCGBIT"))?
+            };
             Some(snd)
         } else {
             None
@@ -198,7 +299,8 @@ impl RawDevice {
                 sys::eviocgrep(
                     file.as_raw_fd(),
                     &mut auto_repeat as *mut AutoRepeat as *mut [u32; 2],
-                )?;
+                )
+                .map_err(ioctl_error("EVIOCGREP"))?;
             }
 
             Some(auto_repeat)
@@ -206,6 +308,24 @@ impl RawDevice {
             None
         };
 
+        let abs_info = match &supported_absolute {
+            Some(axes) => {
+                let mut abs_info = Box::new(ABS_VALS_INIT);
+                for axis in axes.iter() {
+                    unsafe {
+                        sys::eviocgabs(
+                            file.as_raw_fd(),
+                            axis.0 as u32,
+                            &mut abs_info[axis.0 as usize],
+                        )
+                        .map_err(ioctl_error("EVIOCGABS"))?
+                    };
+                }
+                Some(abs_info)
+            }
+            None => None,
+        };
+
         Ok(RawDevice {
             file,
             ty,
@@ -223,8 +343,89 @@ impl RawDevice {
             supported_misc,
             supported_snd,
             auto_repeat,
+            supported_ff,
+            abs_info,
             event_buf: Vec::new(),
+            event_buf_size: crate::EVENT_BATCH_SIZE,
             grabbed: false,
+            rumble: None,
+        })
+    }
+
+    /// Like [`from_file`](Self::from_file), but takes ownership of `fd` only once the device
+    /// is set up: if that fails, `fd` is left open.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open descriptor that nothing else in the process owns.
+    pub(crate) unsafe fn adopt_fd(fd: RawFd) -> io::Result<RawDevice> {
+        // Set up a duplicate, so a failure closes it rather than `fd`
+        let dup = BorrowedFd::borrow_raw(fd).try_clone_to_owned()?;
+        let mut raw = Self::from_file(File::from(dup))?;
+        raw.file = Arc::new(File::from_raw_fd(fd));
+        Ok(raw)
+    }
+
+    /// Re-query the device's capabilities from the kernel.
+    ///
+    /// Capabilities are read once when the device is opened and cached, so that querying them
+    /// doesn't cost a syscall. Call this if they may have changed since, for example after
+    /// a driver reconfigured the device's axes.
+    pub fn refresh_capabilities(&mut self) -> io::Result<()> {
+        let RawDevice {
+            ty,
+            name,
+            phys,
+            uniq,
+            id,
+            props,
+            driver_version,
+            supported_keys,
+            supported_relative,
+            supported_absolute,
+            supported_switch,
+            supported_led,
+            supported_misc,
+            auto_repeat,
+            supported_ff,
+            supported_snd,
+            abs_info,
+            ..
+        } = Self::query(self.file.clone())?;
+        self.ty = ty;
+        self.name = name;
+        self.phys = phys;
+        self.uniq = uniq;
+        self.id = id;
+        self.props = props;
+        self.driver_version = driver_version;
+        self.supported_keys = supported_keys;
+        self.supported_relative = supported_relative;
+        self.supported_absolute = supported_absolute;
+        self.supported_switch = supported_switch;
+        self.supported_led = supported_led;
+        self.supported_misc = supported_misc;
+        self.auto_repeat = auto_repeat;
+        self.supported_ff = supported_ff;
+        self.supported_snd = supported_snd;
+        self.abs_info = abs_info;
+        Ok(())
+    }
+
+    /// Re-query the device's metadata from the kernel like
+    /// [`refresh_capabilities`](Self::refresh_capabilities), and return what changed, e.g. after
+    /// another process set an axis' range with `EVIOCSABS`.
+    ///
+    /// The keymap isn't cached: [`get_scancode_by_keycode`](Self::get_scancode_by_keycode) and
+    /// the like always query the kernel, so they see remapped keys without a refresh.
+    pub fn refresh(&mut self) -> io::Result<MetadataChanges> {
+        let before = self.capability_report();
+        let auto_repeat = self.auto_repeat.clone();
+        self.refresh_capabilities()?;
+        let repeat = |repeat: Option<AutoRepeat>| repeat.map(|r| (r.delay, r.period));
+        Ok(MetadataChanges {
+            auto_repeat: repeat(auto_repeat) != repeat(self.auto_repeat.clone()),
+            ..before.changes(&self.capability_report())
         })
     }
 
@@ -248,6 +449,68 @@ impl RawDevice {
         InputId::from(self.id)
     }
 
+    /// Returns the sysfs directory of the input device, e.g. `/sys/devices/.../input/input12`.
+    pub fn sysfs_path(&self) -> io::Result<PathBuf> {
+        crate::battery::sysfs_path(self.as_raw_fd())
+    }
+
+    /// Returns the battery of the device, if it has one the kernel knows about.
+    ///
+    /// Wireless controllers usually do, through their driver; see [`Battery`](crate::Battery).
+    pub fn battery(&self) -> io::Result<Option<crate::Battery>> {
+        crate::battery::find_battery(&self.sysfs_path()?)
+    }
+
+    /// Returns `true` if the device is inhibited, so the kernel drops its events.
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] on kernels that can't inhibit devices, before
+    /// 5.11.
+    pub fn is_inhibited(&self) -> io::Result<bool> {
+        let value = std::fs::read_to_string(self.sysfs_path()?.join("inhibited"))?;
+        Ok(value.trim() == "1")
+    }
+
+    /// Inhibit the device, or let its events through again.
+    ///
+    /// An inhibited device is closed on the driver side and drops its events for every reader,
+    /// unlike [`grab`](Self::grab), which only diverts them; a convertible can switch off its
+    /// keyboard while folded this way. Needs write access to the device's sysfs `inhibited`
+    /// attribute, which is usually limited to root.
+    pub fn set_inhibited(&self, inhibited: bool) -> io::Result<()> {
+        let value = if inhibited { "1" } else { "0" };
+        std::fs::write(self.sysfs_path()?.join("inhibited"), value)
+    }
+
+    /// Returns the `power/wakeup` attribute controlling the device, which belongs to the
+    /// nearest ancestor of the input device that can wake the system, e.g. its USB device.
+    fn wakeup_attribute(&self) -> io::Result<PathBuf> {
+        let input = self.sysfs_path()?;
+        input
+            .ancestors()
+            .take_while(|dir| dir.starts_with("/sys/devices/"))
+            .map(|dir| dir.join("power/wakeup"))
+            .find(|attribute| attribute.exists())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "device can't wake the system"))
+    }
+
+    /// Returns `true` if the device may wake the system from suspend.
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] if the device can't wake the system at all.
+    pub fn wakeup_enabled(&self) -> io::Result<bool> {
+        let value = std::fs::read_to_string(self.wakeup_attribute()?)?;
+        Ok(value.trim() == "enabled")
+    }
+
+    /// Allow or forbid the device to wake the system from suspend.
+    ///
+    /// The setting belongs to the hardware rather than the input device, so it applies to
+    /// every input device of e.g. a USB receiver. Needs write access to sysfs, which is usually
+    /// limited to root.
+    pub fn set_wakeup(&self, enabled: bool) -> io::Result<()> {
+        let value = if enabled { "enabled" } else { "disabled" };
+        std::fs::write(self.wakeup_attribute()?, value)
+    }
+
     /// Returns the current auto repeat settings
     pub fn get_auto_repeat(&self) -> Option<AutoRepeat> {
         self.auto_repeat.clone()
@@ -383,13 +646,163 @@ impl RawDevice {
         self.supported_snd.as_deref()
     }
 
+    /// Returns the set of supported force feedback effects supported by a device.
+    ///
+    /// Besides effect types, this also contains [`FFEffectType::FF_GAIN`] and
+    /// [`FFEffectType::FF_AUTOCENTER`] if the device supports those controls, and the waveforms
+    /// (e.g. [`FFEffectType::FF_SINE`]) supported for periodic effects.
+    pub fn supported_ff_effects(&self) -> Option<&AttributeSetRef<FFEffectType>> {
+        self.supported_ff.as_deref()
+    }
+
+    /// Returns `true` if the device supports events of type `ty`.
+    pub fn supports_event_type(&self, ty: EventType) -> bool {
+        self.ty.contains(ty)
+    }
+
+    /// Returns `true` if the device has the key or button `key`.
+    ///
+    /// Like the other `supports_*` methods, this checks the capabilities read when the device
+    /// was opened, without a syscall or copying the set.
+    pub fn supports_key(&self, key: Key) -> bool {
+        self.supported_keys
+            .as_ref()
+            .is_some_and(|keys| keys.contains(key))
+    }
+
+    /// Returns `true` if the device has the relative axis `axis`.
+    pub fn supports_relative_axis(&self, axis: RelativeAxisType) -> bool {
+        self.supported_relative
+            .as_ref()
+            .is_some_and(|axes| axes.contains(axis))
+    }
+
+    /// Returns `true` if the device has the absolute axis `axis`.
+    pub fn supports_absolute_axis(&self, axis: AbsoluteAxisType) -> bool {
+        self.supported_absolute
+            .as_ref()
+            .is_some_and(|axes| axes.contains(axis))
+    }
+
+    /// Returns `true` if the device has the switch `switch`.
+    pub fn supports_switch(&self, switch: SwitchType) -> bool {
+        self.supported_switch
+            .as_ref()
+            .is_some_and(|switches| switches.contains(switch))
+    }
+
+    /// Returns `true` if the device has the LED `led`.
+    pub fn supports_led(&self, led: LedType) -> bool {
+        self.supported_led
+            .as_ref()
+            .is_some_and(|leds| leds.contains(led))
+    }
+
+    /// Returns `true` if the device reports the miscellaneous event `misc`.
+    pub fn supports_misc(&self, misc: MiscType) -> bool {
+        self.supported_misc
+            .as_ref()
+            .is_some_and(|misc_types| misc_types.contains(misc))
+    }
+
+    /// Returns `true` if the device can play the sound `sound`.
+    pub fn supports_sound(&self, sound: SoundType) -> bool {
+        self.supported_snd
+            .as_ref()
+            .is_some_and(|sounds| sounds.contains(sound))
+    }
+
+    /// Returns `true` if the device supports the force feedback effect, waveform or control
+    /// `effect`.
+    pub fn supports_ff_effect(&self, effect: FFEffectType) -> bool {
+        self.supported_ff
+            .as_ref()
+            .is_some_and(|effects| effects.contains(effect))
+    }
+
+    /// Returns `true` if the device has the property `prop`.
+    pub fn has_property(&self, prop: PropType) -> bool {
+        self.props.contains(prop)
+    }
+
+    /// Returns `true` if the device can emit the event `code` of type `ty`, e.g. to check an
+    /// event read from elsewhere before writing it to the device.
+    ///
+    /// Types without codes, like [`EventType::SYNCHRONIZATION`], are supported if the type is.
+    pub fn supports_code(&self, ty: EventType, code: u16) -> bool {
+        match ty {
+            EventType::KEY => self.supports_key(Key::new(code)),
+            EventType::RELATIVE => self.supports_relative_axis(RelativeAxisType(code)),
+            EventType::ABSOLUTE => self.supports_absolute_axis(AbsoluteAxisType(code)),
+            EventType::SWITCH => self.supports_switch(SwitchType(code)),
+            EventType::LED => self.supports_led(LedType(code)),
+            EventType::MISC => self.supports_misc(MiscType(code)),
+            EventType::SOUND => self.supports_sound(SoundType(code)),
+            EventType::FORCEFEEDBACK => self.supports_ff_effect(FFEffectType(code)),
+            ty => self.supports_event_type(ty),
+        }
+    }
+
+    /// Returns a snapshot of the device's identity and capabilities, printable in the style of
+    /// `evtest`.
+    pub fn capability_report(&self) -> CapabilityReport {
+        CapabilityReport::new(self)
+    }
+
+    /// Returns the range, resolution and noise parameters of an absolute axis, as read when the
+    /// capabilities were last queried.
+    ///
+    /// The [`value`](AbsInfo::value) is the axis's value at that time, and may be stale; use
+    /// [`get_abs_state`](Self::get_abs_state) for current values.
+    pub fn abs_info(&self, axis: AbsoluteAxisType) -> Option<AbsInfo> {
+        let abs_info = self.abs_info.as_ref()?;
+        if !self.supported_absolute.as_ref()?.contains(axis) {
+            return None;
+        }
+        Some(AbsInfo(abs_info[axis.0 as usize]))
+    }
+
+    /// Change the range, resolution and noise parameters of an absolute axis, e.g. to apply a
+    /// calibration to everyone reading the device.
+    ///
+    /// The kernel keeps the new parameters until the device is removed. The cached
+    /// [`abs_info`](Self::abs_info) is updated to match.
+    pub fn set_abs_info(&mut self, axis: AbsoluteAxisType, info: AbsInfo) -> io::Result<()> {
+        let supported = self
+            .supported_absolute
+            .as_ref()
+            .is_some_and(|axes| axes.contains(axis));
+        if !supported {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "axis not supported by the device",
+            ));
+        }
+        unsafe { sys::eviocsabs(self.as_raw_fd(), axis.0 as u32, &info.0) }
+            .map_err(ioctl_error("EVIOCSABS"))?;
+        if let Some(abs_info) = &mut self.abs_info {
+            abs_info[axis.0 as usize] = info.0;
+        }
+        Ok(())
+    }
+
+    /// Returns the maximum number of force feedback effects that can be uploaded to the device
+    /// at the same time.
+    pub fn max_ff_effects(&self) -> io::Result<usize> {
+        let mut max_effects = 0;
+        unsafe { sys::eviocgeffects(self.as_raw_fd(), &mut max_effects) }
+            .map_err(ioctl_error("EVIOCGEFFECTS"))?;
+        Ok(max_effects as usize)
+    }
+
     /// Read a maximum of `num` events into the internal buffer. If the underlying fd is not
     /// O_NONBLOCK, this will block.
     ///
     /// Returns the number of events that were read, or an error.
     pub(crate) fn fill_events(&mut self) -> io::Result<usize> {
         let fd = self.as_raw_fd();
-        self.event_buf.reserve(crate::EVENT_BATCH_SIZE);
+        trace_span!("read", fd);
+        self.event_buf.reserve(self.event_buf_size);
 
         // TODO: use Vec::spare_capacity_mut or Vec::split_at_spare_mut when they stabilize
         let spare_capacity = vec_spare_capacity_mut(&mut self.event_buf);
@@ -397,15 +810,91 @@ impl RawDevice {
 
         // use libc::read instead of nix::unistd::read b/c we need to pass an uninitialized buf
         let res = unsafe { libc::read(fd, spare_capacity.as_mut_ptr() as _, spare_capacity_size) };
-        let bytes_read = nix::errno::Errno::result(res)?;
+        let bytes_read = Errno::result(res).inspect_err(|&errno| {
+            if errno != Errno::EAGAIN {
+                trace_event!(debug, %errno, "read failed");
+            }
+        })?;
         let num_read = bytes_read as usize / mem::size_of::<libc::input_event>();
         unsafe {
             let len = self.event_buf.len();
             self.event_buf.set_len(len + num_read);
         }
+        trace_event!(trace, events = num_read, "read events");
         Ok(num_read)
     }
 
+    /// Returns the size, in events, of the buffer used for each read from the kernel.
+    pub fn event_buffer_size(&self) -> usize {
+        self.event_buf_size
+    }
+
+    /// Set the size, in events, of the buffer used for each read from the kernel. Defaults to 32.
+    ///
+    /// Devices producing many events per frame, like multitouch panels or high-rate mice, are
+    /// less likely to overflow the kernel's buffer (and produce SYN_DROPPED) with a larger
+    /// buffer. Set this before converting the device into an event stream.
+    pub fn set_event_buffer_size(&mut self, size: usize) {
+        self.event_buf_size = size.max(1);
+        self.event_buf
+            .reserve(self.event_buf_size.saturating_sub(self.event_buf.len()));
+    }
+
+    /// Read events from the kernel directly into `buf`, without going through the internal
+    /// buffer. If the underlying fd is not O_NONBLOCK, this will block.
+    ///
+    /// Returns the number of events written to the start of `buf`. No synchronization is done
+    /// on SYN_DROPPED.
+    pub fn read_into(&mut self, buf: &mut [InputEvent]) -> io::Result<usize> {
+        // InputEvent is repr(transparent) over input_event, so the kernel can write to it
+        // directly; it never writes partial events.
+        let res = unsafe {
+            libc::read(
+                self.as_raw_fd(),
+                buf.as_mut_ptr() as _,
+                mem::size_of_val(buf),
+            )
+        };
+        let bytes_read = Errno::result(res)?;
+        Ok(bytes_read as usize / mem::size_of::<libc::input_event>())
+    }
+
+    /// Discard the events queued on the device, without decoding them, e.g. right after
+    /// grabbing it or resuming from suspend, so stale events aren't processed. Returns the
+    /// number of events discarded.
+    ///
+    /// Never blocks, whether or not the fd is O_NONBLOCK.
+    pub fn drain(&mut self) -> io::Result<usize> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let mut discarded = self.event_buf.len();
+        self.event_buf.clear();
+        let mut buf = [MaybeUninit::<libc::input_event>::uninit(); 64];
+        loop {
+            let mut fds = [PollFd::new(self.as_raw_fd(), PollFlags::POLLIN)];
+            poll(&mut fds, 0)?;
+            if !fds[0]
+                .revents()
+                .is_some_and(|r| r.contains(PollFlags::POLLIN))
+            {
+                break;
+            }
+            let res = unsafe {
+                libc::read(
+                    self.as_raw_fd(),
+                    buf.as_mut_ptr() as _,
+                    mem::size_of_val(&buf),
+                )
+            };
+            match Errno::result(res) {
+                Ok(0) | Err(Errno::EAGAIN) => break,
+                Ok(bytes) => discarded += bytes as usize / mem::size_of::<libc::input_event>(),
+                Err(errno) => return Err(errno.into()),
+            }
+        }
+        trace_event!(debug, events = discarded, "drained events");
+        Ok(discarded)
+    }
+
     /// Fetches and returns events from the kernel ring buffer without doing synchronization on
     /// SYN_DROPPED.
     ///
@@ -453,7 +942,8 @@ impl RawDevice {
     /// [`get_key_state`](Self::get_key_state) instead.
     #[inline]
     pub fn update_key_state(&self, key_vals: &mut AttributeSet<Key>) -> io::Result<()> {
-        unsafe { sys::eviocgkey(self.as_raw_fd(), key_vals.as_mut_raw_slice())? };
+        unsafe { sys::eviocgkey(self.as_raw_fd(), key_vals.as_mut_raw_slice()) }
+            .map_err(ioctl_error("EVIOCGKEY"))?;
         Ok(())
     }
 
@@ -472,8 +962,9 @@ impl RawDevice {
                 // handling later removed. not sure what the intention of "handling that later" was
                 // the abs data seems to be fine (tested ABS_MT_POSITION_X/Y)
                 unsafe {
-                    sys::eviocgabs(self.as_raw_fd(), idx as u32, &mut abs_vals[idx as usize])?
-                };
+                    sys::eviocgabs(self.as_raw_fd(), idx as u32, &mut abs_vals[idx as usize])
+                }
+                .map_err(ioctl_error("EVIOCGABS"))?;
             }
         }
         Ok(())
@@ -487,7 +978,8 @@ impl RawDevice {
         &self,
         switch_vals: &mut AttributeSet<SwitchType>,
     ) -> io::Result<()> {
-        unsafe { sys::eviocgsw(self.as_raw_fd(), switch_vals.as_mut_raw_slice())? };
+        unsafe { sys::eviocgsw(self.as_raw_fd(), switch_vals.as_mut_raw_slice()) }
+            .map_err(ioctl_error("EVIOCGSW"))?;
         Ok(())
     }
 
@@ -496,7 +988,8 @@ impl RawDevice {
     /// [`get_led_state`](Self::get_led_state) instead.
     #[inline]
     pub fn update_led_state(&self, led_vals: &mut AttributeSet<LedType>) -> io::Result<()> {
-        unsafe { sys::eviocgled(self.as_raw_fd(), led_vals.as_mut_raw_slice())? };
+        unsafe { sys::eviocgled(self.as_raw_fd(), led_vals.as_mut_raw_slice()) }
+            .map_err(ioctl_error("EVIOCGLED"))?;
         Ok(())
     }
 
@@ -507,8 +1000,9 @@ impl RawDevice {
             sys::eviocsrep(
                 self.as_raw_fd(),
                 repeat as *const AutoRepeat as *const [u32; 2],
-            )?;
+            )
         }
+        .map_err(ioctl_error("EVIOCSREP"))?;
         self.auto_repeat = Some(repeat.clone());
         Ok(())
     }
@@ -522,7 +1016,8 @@ impl RawDevice {
             keycode,
             scancode: [0u8; 32],
         };
-        unsafe { sys::eviocgkeycode_v2(self.as_raw_fd(), &mut keymap)? };
+        unsafe { sys::eviocgkeycode_v2(self.as_raw_fd(), &mut keymap) }
+            .map_err(ioctl_error("EVIOCGKEYCODE_V2"))?;
         Ok(keymap.scancode[..keymap.len as usize].to_vec())
     }
 
@@ -536,13 +1031,25 @@ impl RawDevice {
             scancode: [0u8; 32],
         };
 
-        unsafe { sys::eviocgkeycode_v2(self.as_raw_fd(), &mut keymap)? };
+        unsafe { sys::eviocgkeycode_v2(self.as_raw_fd(), &mut keymap) }
+            .map_err(ioctl_error("EVIOCGKEYCODE_V2"))?;
         Ok((
             keymap.keycode,
             keymap.scancode[..keymap.len as usize].to_vec(),
         ))
     }
 
+    /// Iterate over the device's whole scancode to keycode table, e.g. to display or back it up.
+    ///
+    /// Entries are read one by one with `EVIOCGKEYCODE_V2`, by index. Devices without a
+    /// keymap, like most that aren't keyboards, yield a single error.
+    pub fn keymap(&self) -> Keymap<'_> {
+        Keymap {
+            device: self,
+            index: Some(0),
+        }
+    }
+
     /// Update a scancode by index. The return value is the previous keycode
     pub fn update_scancode_by_index(
         &self,
@@ -562,7 +1069,8 @@ impl RawDevice {
 
         keymap.scancode[..len].copy_from_slice(scancode);
 
-        let keycode = unsafe { sys::eviocskeycode_v2(self.as_raw_fd(), &keymap)? };
+        let keycode = unsafe { sys::eviocskeycode_v2(self.as_raw_fd(), &keymap) }
+            .map_err(ioctl_error("EVIOCSKEYCODE_V2"))?;
 
         Ok(keycode as u32)
     }
@@ -581,7 +1089,8 @@ impl RawDevice {
 
         keymap.scancode[..len].copy_from_slice(scancode);
 
-        let keycode = unsafe { sys::eviocskeycode_v2(self.as_raw_fd(), &keymap)? };
+        let keycode = unsafe { sys::eviocskeycode_v2(self.as_raw_fd(), &keymap) }
+            .map_err(ioctl_error("EVIOCSKEYCODE_V2"))?;
 
         Ok(keycode as u32)
     }
@@ -592,26 +1101,173 @@ impl RawDevice {
         EventStream::new(self)
     }
 
+    /// Grab the device through a kernel syscall.
+    ///
+    /// If another client already holds a grab, this fails with [`Error::DeviceBusy`], listing
+    /// the processes that have the device open.
     pub fn grab(&mut self) -> io::Result<()> {
         if !self.grabbed {
-            unsafe {
-                sys::eviocgrab(self.as_raw_fd(), 1)?;
-            }
+            unsafe { sys::eviocgrab(self.as_raw_fd(), 1) }.map_err(|errno| match errno {
+                Errno::EBUSY => self.busy_error(),
+                errno => ioctl_error("EVIOCGRAB")(errno),
+            })?;
+            trace_event!(debug, fd = self.as_raw_fd(), "grabbed device");
             self.grabbed = true;
         }
         Ok(())
     }
 
-    pub fn ungrab(&mut self) -> io::Result<()> {
+    /// Returns `true` if this handle holds a grab on the device.
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    /// Returns `true` if another client currently holds a grab on the device.
+    ///
+    /// This is best-effort: it briefly grabs and releases the device, so it can race with
+    /// other clients doing the same. Always `false` if this handle holds the grab itself.
+    pub fn is_grabbed_by_other(&mut self) -> io::Result<bool> {
         if self.grabbed {
-            unsafe {
-                sys::eviocgrab(self.as_raw_fd(), 0)?;
+            return Ok(false);
+        }
+        let fd = self.as_raw_fd();
+        match unsafe { sys::eviocgrab(fd, 1) } {
+            Ok(_) => {
+                unsafe { sys::eviocgrab(fd, 0) }.map_err(ioctl_error("EVIOCGRAB"))?;
+                Ok(false)
             }
+            Err(Errno::EBUSY) => Ok(true),
+            Err(errno) => Err(ioctl_error("EVIOCGRAB")(errno)),
+        }
+    }
+
+    fn busy_error(&self) -> io::Error {
+        use std::os::unix::fs::MetadataExt;
+        let holders = self
+            .file
+            .metadata()
+            .map(|m| device_holders(m.rdev()))
+            .unwrap_or_default();
+        Error::DeviceBusy { holders }.into()
+    }
+
+    pub fn ungrab(&mut self) -> io::Result<()> {
+        if self.grabbed {
+            unsafe { sys::eviocgrab(self.as_raw_fd(), 0) }.map_err(ioctl_error("EVIOCGRAB"))?;
+            trace_event!(debug, fd = self.as_raw_fd(), "released device");
             self.grabbed = false;
         }
         Ok(())
     }
 
+    /// Tell the kernel to deliver only the events in `mask` through this file descriptor.
+    ///
+    /// Every event type the device supports and the kernel can mask is set, so events of types
+    /// absent from `mask` are no longer delivered. Requires Linux 4.4 or later.
+    pub fn set_event_mask(&mut self, mask: &crate::mask::EventMask) -> io::Result<()> {
+        for (ty, codes) in mask.kernel_bitmaps() {
+            if !self.ty.contains(ty) {
+                continue;
+            }
+            let input_mask = libc::input_mask {
+                type_: ty.0.into(),
+                codes_size: codes.len() as u32,
+                codes_ptr: codes.as_ptr() as u64,
+            };
+            unsafe { sys::eviocsmask(self.as_raw_fd(), &input_mask) }
+                .map_err(ioctl_error("EVIOCSMASK"))?;
+        }
+        Ok(())
+    }
+
+    /// Revoke this file descriptor's access to the device.
+    ///
+    /// Afterwards reads and ioctls fail with `ENODEV`, for this descriptor and any duplicates
+    /// of it, e.g. ones passed to other processes. This can't be undone: open the device again
+    /// to regain access.
+    pub fn revoke(&mut self) -> io::Result<()> {
+        unsafe { sys::eviocrevoke(self.as_raw_fd(), 0) }.map_err(ioctl_error("EVIOCREVOKE"))?;
+        trace_event!(debug, fd = self.as_raw_fd(), "revoked device");
+        self.grabbed = false;
+        Ok(())
+    }
+
+    /// Upload a force feedback effect to the device.
+    ///
+    /// The effect stays on the device until the returned handle is dropped.
+    pub fn upload_ff_effect(&mut self, effect: &FFEffect) -> io::Result<FFEffectHandle> {
+        if self.supported_ff.is_none() {
+            return Err(Error::NotSupportedByDevice("force feedback").into());
+        }
+        self.release_rumble(Instant::now());
+        FFEffectHandle::upload(&self.file, effect)
+    }
+
+    /// Rumble the device's motors for `duration`, at most [`FF_MAX_DURATION`] milliseconds.
+    ///
+    /// This plays an [`FFRumble`] effect, which the device stops on its own once `duration`
+    /// has passed, so this returns immediately. A call while the device is still rumbling
+    /// replaces the rumble, and a zero `duration` stops it.
+    ///
+    /// The effect takes up one of the device's effect slots, of which gamepads often have only
+    /// a few. Once it has finished, the slot is freed by the next call to this or to
+    /// [`upload_ff_effect`](Self::upload_ff_effect); stopping the rumble frees it right away.
+    pub fn rumble(&mut self, strong: u16, weak: u16, duration: Duration) -> io::Result<()> {
+        let length = duration.as_millis().min(FF_MAX_DURATION.into()) as u16;
+        if length == 0 {
+            return match self.rumble.take() {
+                Some((mut handle, _)) => handle.stop(),
+                None => Ok(()),
+            };
+        }
+        let effect = FFEffect::new(
+            FFEffectKind::Rumble(FFRumble {
+                strong_magnitude: strong,
+                weak_magnitude: weak,
+            }),
+            length,
+        );
+        let handle = match self.rumble.take() {
+            Some((mut handle, _)) => {
+                handle.update(&effect)?;
+                handle
+            }
+            None => self.upload_ff_effect(&effect)?,
+        };
+        let end = Instant::now() + Duration::from_millis(length.into());
+        let (handle, _) = self.rumble.insert((handle, end));
+        handle.play(1)
+    }
+
+    /// Erase the effect played by [`rumble`](Self::rumble) if it finished by `now`.
+    fn release_rumble(&mut self, now: Instant) {
+        if self.rumble.as_ref().is_some_and(|(_, end)| *end <= now) {
+            self.rumble = None;
+        }
+    }
+
+    /// Set the overall strength of force feedback effects, from 0 to 0xffff.
+    ///
+    /// Only supported if the device supports [`FFEffectType::FF_GAIN`].
+    pub fn set_ff_gain(&mut self, gain: u16) -> io::Result<()> {
+        self.send_events(&[InputEvent::new(
+            EventType::FORCEFEEDBACK,
+            FFEffectType::FF_GAIN.0,
+            gain as i32,
+        )])
+    }
+
+    /// Set the strength of the device's autocenter spring, from 0 (off) to 0xffff.
+    ///
+    /// Only supported if the device supports [`FFEffectType::FF_AUTOCENTER`].
+    pub fn set_ff_autocenter(&mut self, strength: u16) -> io::Result<()> {
+        self.send_events(&[InputEvent::new(
+            EventType::FORCEFEEDBACK,
+            FFEffectType::FF_AUTOCENTER.0,
+            strength as i32,
+        )])
+    }
+
     /// Send an event to the device.
     ///
     /// Events that are typically sent to devices are
@@ -620,7 +1276,26 @@ impl RawDevice {
     /// and [EventType::FORCEFEEDBACK] (play force feedback effects on the device, i.e. rumble).
     pub fn send_events(&mut self, events: &[InputEvent]) -> io::Result<()> {
         let bytes = unsafe { crate::cast_to_bytes(events) };
-        self.file.write_all(bytes)
+        (&*self.file).write_all(bytes)
+    }
+
+    /// The device's descriptor, which effect handles refer to.
+    pub(crate) fn shared_file(&self) -> &Arc<File> {
+        &self.file
+    }
+
+    fn into_file(self) -> File {
+        let mut file = self.file;
+        // Effect handles only hold on to the descriptor for the duration of a call
+        loop {
+            match Arc::try_unwrap(file) {
+                Ok(file) => return file,
+                Err(shared) => {
+                    file = shared;
+                    std::thread::yield_now();
+                }
+            }
+        }
     }
 }
 
@@ -630,9 +1305,45 @@ impl AsRawFd for RawDevice {
     }
 }
 
+impl AsFd for RawDevice {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+impl IntoRawFd for RawDevice {
+    fn into_raw_fd(self) -> RawFd {
+        self.into_file().into_raw_fd()
+    }
+}
+
+impl From<RawDevice> for OwnedFd {
+    fn from(device: RawDevice) -> OwnedFd {
+        device.into_file().into()
+    }
+}
+
+/// Use an already opened device node, e.g. one received from another process or opened by a
+/// privileged helper.
+impl TryFrom<File> for RawDevice {
+    type Error = io::Error;
+
+    fn try_from(file: File) -> io::Result<Self> {
+        RawDevice::from_file(file)
+    }
+}
+
+impl TryFrom<OwnedFd> for RawDevice {
+    type Error = io::Error;
+
+    fn try_from(fd: OwnedFd) -> io::Result<Self> {
+        RawDevice::from_file(File::from(fd))
+    }
+}
+
 /// A copy of the unstable Vec::spare_capacity_mut
 #[inline]
-fn vec_spare_capacity_mut<T>(v: &mut Vec<T>) -> &mut [mem::MaybeUninit<T>] {
+pub(crate) fn vec_spare_capacity_mut<T>(v: &mut Vec<T>) -> &mut [mem::MaybeUninit<T>] {
     let (len, cap) = (v.len(), v.capacity());
     unsafe {
         std::slice::from_raw_parts_mut(
@@ -674,6 +1385,59 @@ impl Iterator for EnumerateDevices {
     }
 }
 
+/// An entry of a device's scancode to keycode table, as returned by [`RawDevice::keymap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeymapEntry {
+    /// The entry's position in the table.
+    pub index: u16,
+    /// The scancode, in the device's native byte order and length; usually 4 bytes.
+    pub scancode: Vec<u8>,
+    pub key: Key,
+}
+
+impl KeymapEntry {
+    /// Returns the scancode as a number, if it is at most 4 bytes long.
+    pub fn scancode_value(&self) -> Option<u32> {
+        let mut bytes = [0; 4];
+        bytes
+            .get_mut(..self.scancode.len())?
+            .copy_from_slice(&self.scancode);
+        Some(u32::from_ne_bytes(bytes))
+    }
+}
+
+/// Iterates over a device's keymap. See [`RawDevice::keymap`].
+#[derive(Debug)]
+pub struct Keymap<'a> {
+    device: &'a RawDevice,
+    index: Option<u16>,
+}
+
+impl Iterator for Keymap<'_> {
+    type Item = io::Result<KeymapEntry>;
+
+    fn next(&mut self) -> Option<io::Result<KeymapEntry>> {
+        let index = self.index?;
+        self.index = index.checked_add(1);
+        match self.device.get_scancode_by_index(index) {
+            Ok((keycode, scancode)) => Some(Ok(KeymapEntry {
+                index,
+                scancode,
+                key: Key::new(keycode as u16),
+            })),
+            // The kernel reports the end of the table as an invalid index
+            Err(e) if Error::raw_os_error_of(&e) == Some(libc::EINVAL) && index > 0 => {
+                self.index = None;
+                None
+            }
+            Err(e) => {
+                self.index = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 #[cfg(feature = "tokio")]
 mod tokio_stream {
     use super::*;
@@ -716,6 +1480,39 @@ mod tokio_stream {
             poll_fn(|cx| self.poll_event(cx)).await
         }
 
+        /// Wait for events and read them directly into `buf`, returning how many were read.
+        ///
+        /// Events already buffered by [`next_event`](Self::next_event) are returned first.
+        pub async fn read_into(&mut self, buf: &mut [InputEvent]) -> io::Result<usize> {
+            poll_fn(|cx| self.poll_read_into(cx, buf)).await
+        }
+
+        /// A lower-level version of [`read_into`](Self::read_into) for use inside a
+        /// `Future::poll` impl.
+        pub fn poll_read_into(
+            &mut self,
+            cx: &mut Context<'_>,
+            buf: &mut [InputEvent],
+        ) -> Poll<io::Result<usize>> {
+            let pending = &self.device.get_ref().event_buf[self.index..];
+            if !pending.is_empty() {
+                let n = pending.len().min(buf.len());
+                for (dst, &src) in buf.iter_mut().zip(&pending[..n]) {
+                    *dst = InputEvent(src);
+                }
+                self.index += n;
+                return Poll::Ready(Ok(n));
+            }
+
+            loop {
+                let mut guard = ready!(self.device.poll_read_ready_mut(cx))?;
+                match guard.try_io(|device| device.get_mut().read_into(buf)) {
+                    Ok(res) => return Poll::Ready(res),
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+
         /// A lower-level function for directly polling this stream.
         pub fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<InputEvent>> {
             'outer: loop {
@@ -750,6 +1547,25 @@ mod tokio_stream {
         }
     }
 
+    impl AsRawFd for EventStream {
+        fn as_raw_fd(&self) -> RawFd {
+            self.device.get_ref().as_raw_fd()
+        }
+    }
+
+    impl AsFd for EventStream {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.device.get_ref().as_fd()
+        }
+    }
+
+    impl IntoRawFd for EventStream {
+        /// Deregisters the descriptor from the runtime and returns it, still non-blocking.
+        fn into_raw_fd(self) -> RawFd {
+            self.device.into_inner().into_raw_fd()
+        }
+    }
+
     // version of futures_util::future::poll_fn
     pub(crate) fn poll_fn<T, F: FnMut(&mut Context<'_>) -> Poll<T> + Unpin>(f: F) -> PollFn<F> {
         PollFn(f)
@@ -766,3 +1582,36 @@ mod tokio_stream {
 pub(crate) use tokio_stream::poll_fn;
 #[cfg(feature = "tokio")]
 pub use tokio_stream::EventStream;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ff::fake_slots;
+
+    #[test]
+    fn rumble_frees_its_slot() -> io::Result<()> {
+        let (read, write) = nix::unistd::pipe()?;
+        // SAFETY: the pipe was just created and nothing else owns its ends
+        let _read = unsafe { File::from_raw_fd(read) };
+        let mut device = RawDevice::from_file_unchecked(unsafe { File::from_raw_fd(write) });
+        let effect = FFEffect::new(FFEffectKind::Rumble(FFRumble::default()), 100);
+
+        device.rumble(0x8000, 0, Duration::from_secs(10))?;
+        device.rumble(0, 0x8000, Duration::from_secs(10))?;
+        let _held = device.upload_ff_effect(&effect)?;
+        assert_eq!(fake_slots::uploaded(&device).len(), fake_slots::SLOTS);
+        assert!(device.upload_ff_effect(&effect).is_err());
+
+        // The slot is freed once the rumble is over
+        device.release_rumble(Instant::now() + Duration::from_secs(10));
+        let next = device.upload_ff_effect(&effect)?;
+        assert!(device.rumble.is_none());
+
+        // or when it's stopped
+        drop(next);
+        device.rumble(0x8000, 0, Duration::from_secs(10))?;
+        device.rumble(0, 0, Duration::ZERO)?;
+        assert_eq!(fake_slots::uploaded(&device).len(), 1);
+        Ok(())
+    }
+}
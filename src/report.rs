@@ -0,0 +1,298 @@
+//! Human-readable summaries of device capabilities.
+
+use std::fmt;
+
+use crate::attribute_set::ArrayedEvdevEnum;
+use crate::constants::*;
+use crate::raw_stream::RawDevice;
+use crate::{AbsInfo, AttributeSet, AttributeSetRef, InputId, Key};
+
+/// A snapshot of everything a device reports about itself, for debug dumps and bug reports.
+///
+/// Obtained from [`Device::capability_report`](crate::Device::capability_report). The
+/// [`Display`](fmt::Display) output follows the layout of `evtest`, so it can be compared
+/// against reports produced by that tool:
+///
+/// ```no_run
+/// let device = evdev::Device::open("/dev/input/event0")?;
+/// print!("{}", device.capability_report());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct CapabilityReport {
+    pub name: Option<String>,
+    pub physical_path: Option<String>,
+    pub unique_name: Option<String>,
+    pub input_id: InputId,
+    pub driver_version: (u8, u8, u8),
+    pub properties: AttributeSet<PropType>,
+    pub event_types: AttributeSet<EventType>,
+    pub keys: Option<AttributeSet<Key>>,
+    pub relative_axes: Option<AttributeSet<RelativeAxisType>>,
+    /// Absolute axes, with their ranges as read when capabilities were last queried.
+    pub absolute_axes: Vec<(AbsoluteAxisType, AbsInfo)>,
+    pub misc: Option<AttributeSet<MiscType>>,
+    pub switches: Option<AttributeSet<SwitchType>>,
+    pub leds: Option<AttributeSet<LedType>>,
+    pub sounds: Option<AttributeSet<SoundType>>,
+    pub ff_effects: Option<AttributeSet<FFEffectType>>,
+}
+
+fn owned<T: ArrayedEvdevEnum>(set: Option<&AttributeSetRef<T>>) -> Option<AttributeSet<T>> {
+    set.map(|set| set.iter().collect())
+}
+
+impl CapabilityReport {
+    pub(crate) fn new(dev: &RawDevice) -> Self {
+        let absolute_axes = dev
+            .supported_absolute_axes()
+            .into_iter()
+            .flat_map(|axes| axes.iter())
+            .filter_map(|axis| Some((axis, dev.abs_info(axis)?)))
+            .collect();
+        CapabilityReport {
+            name: dev.name().map(str::to_owned),
+            physical_path: dev.physical_path().map(str::to_owned),
+            unique_name: dev.unique_name().map(str::to_owned),
+            input_id: dev.input_id(),
+            driver_version: dev.driver_version(),
+            properties: dev.properties().iter().collect(),
+            event_types: dev.supported_events().iter().collect(),
+            keys: owned(dev.supported_keys()),
+            relative_axes: owned(dev.supported_relative_axes()),
+            absolute_axes,
+            misc: owned(dev.misc_properties()),
+            switches: owned(dev.supported_switches()),
+            leds: owned(dev.supported_leds()),
+            sounds: owned(dev.supported_sounds()),
+            ff_effects: owned(dev.supported_ff_effects()),
+        }
+    }
+}
+
+/// Which of a device's metadata changed, as returned by
+/// [`Device::refresh`](crate::Device::refresh).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct MetadataChanges {
+    /// The name, physical path, unique name, [`InputId`] or driver version.
+    pub identity: bool,
+    pub properties: bool,
+    /// The supported event types or codes.
+    pub capabilities: bool,
+    /// The range, fuzz, flat or resolution of an absolute axis.
+    pub abs_info: bool,
+    pub auto_repeat: bool,
+}
+
+impl MetadataChanges {
+    /// Returns `true` if anything changed.
+    pub fn any(&self) -> bool {
+        *self != MetadataChanges::default()
+    }
+}
+
+fn same<T: ArrayedEvdevEnum + PartialEq>(
+    a: Option<&AttributeSetRef<T>>,
+    b: Option<&AttributeSetRef<T>>,
+) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.iter().eq(b.iter()),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+impl CapabilityReport {
+    /// Compares the report with a later one of the same device. Auto repeat isn't part of the
+    /// report, so it is left unchanged.
+    pub(crate) fn changes(&self, after: &CapabilityReport) -> MetadataChanges {
+        // The current axis values change all the time; only the parameters are metadata
+        let abs_params = |report: &CapabilityReport| -> Vec<_> {
+            report
+                .absolute_axes
+                .iter()
+                .map(|(axis, info)| {
+                    let (min, max) = (info.minimum(), info.maximum());
+                    (
+                        axis.0,
+                        min,
+                        max,
+                        info.fuzz(),
+                        info.flat(),
+                        info.resolution(),
+                    )
+                })
+                .collect()
+        };
+        MetadataChanges {
+            identity: self.name != after.name
+                || self.physical_path != after.physical_path
+                || self.unique_name != after.unique_name
+                || self.input_id != after.input_id
+                || self.driver_version != after.driver_version,
+            properties: !same(Some(&self.properties), Some(&after.properties)),
+            capabilities: !same(Some(&self.event_types), Some(&after.event_types))
+                || !same(self.keys.as_deref(), after.keys.as_deref())
+                || !same(
+                    self.relative_axes.as_deref(),
+                    after.relative_axes.as_deref(),
+                )
+                || self.absolute_axes.len() != after.absolute_axes.len()
+                || !self
+                    .absolute_axes
+                    .iter()
+                    .zip(&after.absolute_axes)
+                    .all(|((a, _), (b, _))| a == b)
+                || !same(self.misc.as_deref(), after.misc.as_deref())
+                || !same(self.switches.as_deref(), after.switches.as_deref())
+                || !same(self.leds.as_deref(), after.leds.as_deref())
+                || !same(self.sounds.as_deref(), after.sounds.as_deref())
+                || !same(self.ff_effects.as_deref(), after.ff_effects.as_deref()),
+            abs_info: abs_params(self) != abs_params(after),
+            auto_repeat: false,
+        }
+    }
+}
+
+fn write_codes<T: fmt::Debug>(
+    f: &mut fmt::Formatter,
+    codes: impl Iterator<Item = (u16, T)>,
+) -> fmt::Result {
+    for (code, name) in codes {
+        writeln!(f, "    Event code {} ({:?})", code, name)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (maj, min, pat) = self.driver_version;
+        writeln!(f, "Input driver version is {}.{}.{}", maj, min, pat)?;
+        let id = &self.input_id;
+        writeln!(
+            f,
+            "Input device ID: bus {:#x} vendor {:#x} product {:#x} version {:#x}",
+            id.bus_type().0,
+            id.vendor(),
+            id.product(),
+            id.version()
+        )?;
+        writeln!(
+            f,
+            "Input device name: {:?}",
+            self.name.as_deref().unwrap_or("")
+        )?;
+        if let Some(phys) = &self.physical_path {
+            writeln!(f, "Input device physical path: {:?}", phys)?;
+        }
+        if let Some(uniq) = &self.unique_name {
+            writeln!(f, "Input device unique name: {:?}", uniq)?;
+        }
+
+        writeln!(f, "Supported events:")?;
+        for ty in self.event_types.iter() {
+            writeln!(f, "  Event type {} ({:?})", ty.0, ty)?;
+            match ty {
+                EventType::KEY => {
+                    write_codes(f, self.keys.iter().flat_map(|s| s.iter().map(|k| (k.0, k))))?
+                }
+                EventType::RELATIVE => write_codes(
+                    f,
+                    self.relative_axes
+                        .iter()
+                        .flat_map(|s| s.iter().map(|a| (a.0, a))),
+                )?,
+                EventType::ABSOLUTE => {
+                    for (axis, info) in &self.absolute_axes {
+                        writeln!(f, "    Event code {} ({:?})", axis.0, axis)?;
+                        writeln!(f, "      Value {:6}", info.value())?;
+                        writeln!(f, "      Min   {:6}", info.minimum())?;
+                        writeln!(f, "      Max   {:6}", info.maximum())?;
+                        if info.fuzz() != 0 {
+                            writeln!(f, "      Fuzz  {:6}", info.fuzz())?;
+                        }
+                        if info.flat() != 0 {
+                            writeln!(f, "      Flat  {:6}", info.flat())?;
+                        }
+                        if info.resolution() != 0 {
+                            writeln!(f, "      Resolution {:6}", info.resolution())?;
+                        }
+                    }
+                }
+                EventType::MISC => {
+                    write_codes(f, self.misc.iter().flat_map(|s| s.iter().map(|m| (m.0, m))))?
+                }
+                EventType::SWITCH => write_codes(
+                    f,
+                    self.switches
+                        .iter()
+                        .flat_map(|s| s.iter().map(|m| (m.0, m))),
+                )?,
+                EventType::LED => {
+                    write_codes(f, self.leds.iter().flat_map(|s| s.iter().map(|m| (m.0, m))))?
+                }
+                EventType::SOUND => write_codes(
+                    f,
+                    self.sounds.iter().flat_map(|s| s.iter().map(|m| (m.0, m))),
+                )?,
+                EventType::FORCEFEEDBACK => write_codes(
+                    f,
+                    self.ff_effects
+                        .iter()
+                        .flat_map(|s| s.iter().map(|m| (m.0, m))),
+                )?,
+                _ => {}
+            }
+        }
+
+        writeln!(f, "Properties:")?;
+        for prop in self.properties.iter() {
+            writeln!(f, "  Property type {} ({:?})", prop.0, prop)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BusType;
+
+    #[test]
+    fn detect_changes() {
+        let report = CapabilityReport {
+            name: Some("pad".into()),
+            physical_path: None,
+            unique_name: None,
+            input_id: InputId::new(BusType::BUS_USB, 0x1234, 0x5678, 1),
+            driver_version: (1, 0, 1),
+            properties: AttributeSet::new(),
+            event_types: [EventType::SYNCHRONIZATION, EventType::ABSOLUTE]
+                .into_iter()
+                .collect(),
+            keys: None,
+            relative_axes: None,
+            absolute_axes: vec![(AbsoluteAxisType::ABS_X, AbsInfo::new(0, 0, 1023, 0, 0, 10))],
+            misc: None,
+            switches: None,
+            leds: None,
+            sounds: None,
+            ff_effects: None,
+        };
+
+        let mut moved = report.clone();
+        moved.absolute_axes[0].1 = AbsInfo::new(512, 0, 1023, 0, 0, 10);
+        assert!(!report.changes(&moved).any());
+
+        let mut recalibrated = moved.clone();
+        recalibrated.absolute_axes[0].1 = AbsInfo::new(512, 16, 1007, 4, 8, 10);
+        recalibrated.name = Some("calibrated pad".into());
+        assert_eq!(
+            report.changes(&recalibrated),
+            MetadataChanges {
+                identity: true,
+                abs_info: true,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Rotary encoders, as found on control panels.
+//!
+//! The kernel's `rotary-encoder` and `gpio-keys` drivers, and USB volume knobs, report a knob
+//! as relative motion on `REL_DIAL`, `REL_WHEEL` or another relative axis, one step at a
+//! time. [`RotaryEncoder`] turns those steps into detents, estimates how fast the knob is
+//! turned, e.g. to scroll faster through a long list, and tracks the push button many
+//! encoders have.
+//!
+//! ```no_run
+//! use evdev::rotary::{RotaryEncoder, RotaryEvent};
+//!
+//! let mut knob = RotaryEncoder::new(evdev::Device::open("/dev/input/event2")?);
+//! loop {
+//!     for ev in knob.fetch_events()? {
+//!         match ev {
+//!             RotaryEvent::Rotated { detents, velocity } => {
+//!                 println!("turned {} at {:.1}/s", detents, velocity)
+//!             }
+//!             RotaryEvent::Pressed => println!("pushed"),
+//!             RotaryEvent::Released => {}
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::{Duration, SystemTime};
+
+use crate::{Device, EventType, InputEvent, Key, RelativeAxisType};
+
+/// The axes encoders are reported on, in order of preference.
+const ENCODER_AXES: [RelativeAxisType; 5] = [
+    RelativeAxisType::REL_DIAL,
+    RelativeAxisType::REL_WHEEL,
+    RelativeAxisType::REL_HWHEEL,
+    RelativeAxisType::REL_MISC,
+    RelativeAxisType::REL_X,
+];
+
+/// Rotation further apart than this starts a new velocity estimate.
+const IDLE: Duration = Duration::from_millis(500);
+
+/// A change of a rotary encoder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RotaryEvent {
+    /// The knob turned by `detents`, positive clockwise, at an estimated `velocity` in detents
+    /// per second.
+    Rotated {
+        detents: i32,
+        velocity: f32,
+    },
+    Pressed,
+    Released,
+}
+
+#[derive(Debug, Clone)]
+struct Counter {
+    axis: RelativeAxisType,
+    button: Option<Key>,
+    steps_per_detent: i32,
+    /// Steps not yet making up a detent.
+    steps: i32,
+    /// Detents in the frame being read.
+    detents: i32,
+    position: i64,
+    velocity: f32,
+    last_rotation: Option<SystemTime>,
+    pressed: bool,
+}
+
+impl Counter {
+    fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<RotaryEvent>) {
+        match ev.event_type() {
+            EventType::RELATIVE if ev.code() == self.axis.0 => {
+                self.steps += ev.value();
+                let detents = self.steps / self.steps_per_detent;
+                self.steps -= detents * self.steps_per_detent;
+                self.detents += detents;
+            }
+            EventType::KEY if self.button.is_some_and(|b| b.code() == ev.code()) => {
+                let pressed = ev.value() != 0;
+                if pressed != self.pressed {
+                    self.pressed = pressed;
+                    out.push(if pressed {
+                        RotaryEvent::Pressed
+                    } else {
+                        RotaryEvent::Released
+                    });
+                }
+            }
+            EventType::SYNCHRONIZATION if self.detents != 0 => {
+                let detents = std::mem::take(&mut self.detents);
+                self.rotate(detents, ev.timestamp());
+                out.push(RotaryEvent::Rotated {
+                    detents,
+                    velocity: self.velocity,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn rotate(&mut self, detents: i32, time: SystemTime) {
+        self.position += i64::from(detents);
+        let elapsed = self
+            .last_rotation
+            .and_then(|last| time.duration_since(last).ok())
+            .filter(|&elapsed| elapsed < IDLE);
+        self.velocity = match elapsed {
+            Some(elapsed) => {
+                let instant = detents as f32 / elapsed.as_secs_f32().max(0.001);
+                // Reversing restarts the estimate, rather than averaging to zero
+                if instant.signum() == self.velocity.signum() {
+                    0.5 * self.velocity + 0.5 * instant
+                } else {
+                    instant
+                }
+            }
+            // A single detent after a pause
+            None => detents as f32 / IDLE.as_secs_f32(),
+        };
+        self.last_rotation = Some(time);
+    }
+}
+
+/// A rotary encoder, optionally with a push button. See the [module documentation](self).
+pub struct RotaryEncoder {
+    device: Device,
+    counter: Counter,
+}
+
+impl RotaryEncoder {
+    /// Wrap a device, counting the first of `REL_DIAL`, `REL_WHEEL`, `REL_HWHEEL`, `REL_MISC`
+    /// and `REL_X` it supports, with one step per detent. If the device has exactly one key,
+    /// it is the push button.
+    pub fn new(device: Device) -> Self {
+        let supported = device.supported_relative_axes();
+        let axis = ENCODER_AXES
+            .into_iter()
+            .find(|&axis| supported.is_some_and(|axes| axes.contains(axis)))
+            .unwrap_or(RelativeAxisType::REL_DIAL);
+        let button = device.supported_keys().and_then(|keys| {
+            let mut keys = keys.iter();
+            keys.next().filter(|_| keys.next().is_none())
+        });
+        Self::with_axis(device, axis, button)
+    }
+
+    /// Wrap a device, counting `axis` and treating `button` as the push button.
+    pub fn with_axis(device: Device, axis: RelativeAxisType, button: Option<Key>) -> Self {
+        let pressed = button.is_some_and(|button| {
+            let keys = device.cached_state().key_vals();
+            keys.is_some_and(|keys| keys.contains(button))
+        });
+        RotaryEncoder {
+            device,
+            counter: Counter {
+                axis,
+                button,
+                steps_per_detent: 1,
+                steps: 0,
+                detents: 0,
+                position: 0,
+                velocity: 0.0,
+                last_rotation: None,
+                pressed,
+            },
+        }
+    }
+
+    /// Count `steps` steps as one detent, for encoders reporting every edge of their signals.
+    pub fn steps_per_detent(mut self, steps: i32) -> Self {
+        self.counter.steps_per_detent = steps.max(1);
+        self
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+
+    /// Returns the axis counted as rotation.
+    pub fn axis(&self) -> RelativeAxisType {
+        self.counter.axis
+    }
+
+    /// Returns the detents turned since the encoder was wrapped, positive clockwise.
+    pub fn position(&self) -> i64 {
+        self.counter.position
+    }
+
+    /// Reset the position to zero.
+    pub fn reset_position(&mut self) {
+        self.counter.position = 0;
+    }
+
+    /// Returns the estimated speed of the last rotation, in detents per second.
+    pub fn velocity(&self) -> f32 {
+        self.counter.velocity
+    }
+
+    /// Returns `true` if the push button is held.
+    pub fn is_pressed(&self) -> bool {
+        self.counter.pressed
+    }
+
+    /// Fetch the next batch of events from the device and translate them.
+    ///
+    /// Like [`Device::fetch_events`], this blocks unless the device is non-blocking.
+    pub fn fetch_events(&mut self) -> io::Result<impl Iterator<Item = RotaryEvent>> {
+        let events: Vec<InputEvent> = self.device.fetch_events()?.collect();
+        let mut out = Vec::new();
+        for ev in &events {
+            self.process_event(ev, &mut out);
+        }
+        Ok(out.into_iter())
+    }
+
+    /// Update the state with an event, appending the resulting changes to `out`.
+    ///
+    /// Events can come from another device too, e.g. a push button exposed through
+    /// `gpio-keys`.
+    pub fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<RotaryEvent>) {
+        self.counter.process_event(ev, out);
+    }
+}
+
+impl AsRawFd for RotaryEncoder {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detents_and_velocity() {
+        let mut counter = Counter {
+            axis: RelativeAxisType::REL_DIAL,
+            button: Some(Key::KEY_ENTER),
+            steps_per_detent: 2,
+            steps: 0,
+            detents: 0,
+            position: 0,
+            velocity: 0.0,
+            last_rotation: None,
+            pressed: false,
+        };
+        let mut out = Vec::new();
+        let mut feed = |events: &[(EventType, u16, i32)], ms: u64| {
+            let time =
+                crate::systime_to_timeval(&(SystemTime::UNIX_EPOCH + Duration::from_millis(ms)));
+            for &(type_, code, value) in events.iter().chain(&[(EventType::SYNCHRONIZATION, 0, 0)])
+            {
+                let mut ev = InputEvent::new(type_, code, value);
+                ev.0.time = time;
+                counter.process_event(&ev, &mut out);
+            }
+        };
+        let step = |value| (EventType::RELATIVE, RelativeAxisType::REL_DIAL.0, value);
+
+        feed(&[step(1)], 0);
+        feed(&[step(1)], 10);
+        feed(&[step(2)], 110);
+        feed(&[(EventType::KEY, Key::KEY_ENTER.code(), 1)], 200);
+        assert_eq!(
+            out,
+            [
+                RotaryEvent::Rotated {
+                    detents: 1,
+                    velocity: 2.0
+                },
+                // Halfway between the first estimate and one detent in 100ms
+                RotaryEvent::Rotated {
+                    detents: 1,
+                    velocity: 6.0
+                },
+                RotaryEvent::Pressed,
+            ]
+        );
+        assert_eq!(counter.position, 2);
+    }
+}
@@ -0,0 +1,358 @@
+//! Sequenced rumble patterns.
+//!
+//! A [`RumblePattern`] is a list of segments, each holding the motors at a fixed strength for a
+//! while. Playing it on one or more devices returns a [`RumblePlayback`], which can be waited on
+//! (blocking or as a [`Future`]) or cancelled.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SystemClock};
+use crate::{Device, FFEffect, FFEffectHandle, FFEffectKind, FFRumble};
+
+/// One step of a [`RumblePattern`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RumbleSegment {
+    pub strong: u16,
+    pub weak: u16,
+    pub duration: Duration,
+}
+
+/// A sequence of rumble strengths to play back to back.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RumblePattern {
+    segments: Vec<RumbleSegment>,
+}
+
+impl RumblePattern {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a segment rumbling the strong and weak motors for `duration`.
+    pub fn segment(mut self, strong: u16, weak: u16, duration: Duration) -> Self {
+        self.segments.push(RumbleSegment {
+            strong,
+            weak,
+            duration,
+        });
+        self
+    }
+
+    /// Append a segment with the motors off for `duration`.
+    pub fn pause(self, duration: Duration) -> Self {
+        self.segment(0, 0, duration)
+    }
+
+    pub fn segments(&self) -> &[RumbleSegment] {
+        &self.segments
+    }
+
+    /// Returns how long the whole pattern takes to play.
+    pub fn duration(&self) -> Duration {
+        self.segments.iter().map(|s| s.duration).sum()
+    }
+
+    /// Start playing the pattern on each of `devices` at once.
+    ///
+    /// An effect is uploaded to every device up front, so this fails early if one of them
+    /// doesn't support rumble or has no free effect slots. Playback then happens on a
+    /// background thread, and the effects are erased once it finishes.
+    pub fn play<'a>(
+        &self,
+        devices: impl IntoIterator<Item = &'a mut Device>,
+    ) -> io::Result<RumblePlayback> {
+        self.play_with_clock(devices, SystemClock)
+    }
+
+    /// Like [`play`](Self::play), timing the segments with `clock`.
+    ///
+    /// A [`MockClock`](crate::clock::MockClock) is moved forward through the segments without
+    /// waiting, so the pattern plays as fast as the devices take it.
+    pub fn play_with_clock<'a>(
+        &self,
+        devices: impl IntoIterator<Item = &'a mut Device>,
+        clock: impl Clock + Send + 'static,
+    ) -> io::Result<RumblePlayback> {
+        let effect = rumble_effect(0, 0);
+        let handles = devices
+            .into_iter()
+            .map(|dev| dev.upload_ff_effect(&effect))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(start(self.segments.clone(), handles, clock))
+    }
+}
+
+fn rumble_effect(strong: u16, weak: u16) -> FFEffect {
+    // A length of 0 plays until stopped; segments are timed by the playback thread.
+    FFEffect::new(
+        FFEffectKind::Rumble(FFRumble {
+            strong_magnitude: strong,
+            weak_magnitude: weak,
+        }),
+        0,
+    )
+}
+
+/// The motors of a device a pattern plays on.
+trait Motors {
+    /// Run the motors at the given strengths, or stop them if both are zero.
+    fn rumble(&mut self, strong: u16, weak: u16) -> io::Result<()>;
+}
+
+impl Motors for FFEffectHandle {
+    fn rumble(&mut self, strong: u16, weak: u16) -> io::Result<()> {
+        if strong == 0 && weak == 0 {
+            self.stop()
+        } else {
+            self.update(&rumble_effect(strong, weak))?;
+            self.play(1)
+        }
+    }
+}
+
+fn stop_all(motors: &mut [impl Motors]) -> io::Result<()> {
+    motors.iter_mut().try_for_each(|m| m.rumble(0, 0))
+}
+
+/// Steps through the segments of a pattern as time passes.
+struct Sequencer<'a> {
+    segments: &'a [RumbleSegment],
+    next: usize,
+    /// When the current segment ends.
+    deadline: Option<SystemTime>,
+}
+
+impl<'a> Sequencer<'a> {
+    fn new(segments: &'a [RumbleSegment]) -> Self {
+        Sequencer {
+            segments,
+            next: 0,
+            deadline: None,
+        }
+    }
+
+    /// Start the segment due as of `now` on `motors`. Returns when the next one is due, or
+    /// `None` once the pattern is over and the motors are stopped.
+    fn tick(
+        &mut self,
+        now: SystemTime,
+        motors: &mut [impl Motors],
+    ) -> io::Result<Option<SystemTime>> {
+        loop {
+            if let Some(deadline) = self.deadline.filter(|&deadline| deadline > now) {
+                return Ok(Some(deadline));
+            }
+            let Some(segment) = self.segments.get(self.next) else {
+                stop_all(motors)?;
+                return Ok(None);
+            };
+            self.next += 1;
+            for m in motors.iter_mut() {
+                m.rumble(segment.strong, segment.weak)?;
+            }
+            // Chain from the previous deadline, so late wakeups don't stretch the pattern
+            self.deadline = Some(self.deadline.unwrap_or(now) + segment.duration);
+        }
+    }
+}
+
+fn start<M: Motors + Send + 'static>(
+    segments: Vec<RumbleSegment>,
+    motors: Vec<M>,
+    clock: impl Clock + Send + 'static,
+) -> RumblePlayback {
+    let shared = Arc::new(Shared::default());
+    let thread_shared = shared.clone();
+    std::thread::spawn(move || {
+        let res = run(&segments, motors, &thread_shared, &clock);
+        let mut state = thread_shared.state.lock().unwrap();
+        state.result = Some(res);
+        state.finished = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        thread_shared.cond.notify_all();
+    });
+    RumblePlayback { shared }
+}
+
+fn run(
+    segments: &[RumbleSegment],
+    mut motors: Vec<impl Motors>,
+    shared: &Shared,
+    clock: &impl Clock,
+) -> io::Result<()> {
+    let mut sequencer = Sequencer::new(segments);
+    while let Some(deadline) = sequencer.tick(clock.now(), &mut motors)? {
+        let mut state = shared.state.lock().unwrap();
+        loop {
+            if state.cancelled {
+                drop(state);
+                return stop_all(&mut motors);
+            }
+            if clock.until(deadline).is_zero() {
+                break;
+            }
+            state = shared
+                .cond
+                .wait_timeout(state, clock.timeout(deadline))
+                .unwrap()
+                .0;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    finished: bool,
+    /// The outcome, until it is taken by [`RumblePlayback::wait`] or the future.
+    result: Option<io::Result<()>>,
+    waker: Option<Waker>,
+}
+
+/// A rumble pattern playing in the background.
+///
+/// Dropping this lets the pattern play to completion. It can also be awaited, resolving once
+/// the pattern has finished or been cancelled.
+pub struct RumblePlayback {
+    shared: Arc<Shared>,
+}
+
+impl RumblePlayback {
+    /// Stop playback early. The motors are stopped right away, without finishing the current
+    /// segment.
+    pub fn cancel(&self) {
+        self.shared.state.lock().unwrap().cancelled = true;
+        self.shared.cond.notify_all();
+    }
+
+    /// Returns true once the pattern has finished playing.
+    pub fn is_finished(&self) -> bool {
+        self.shared.state.lock().unwrap().finished
+    }
+
+    /// Block until the pattern has finished playing.
+    ///
+    /// Returns the first error encountered while playing, if any.
+    pub fn wait(self) -> io::Result<()> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(res) = state.result.take() {
+                return res;
+            }
+            state = self.shared.cond.wait(state).unwrap();
+        }
+    }
+}
+
+impl Future for RumblePlayback {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.result.take() {
+            Some(res) => Poll::Ready(res),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::task::Waker;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(u16, u16)>>>);
+
+    impl Motors for Recorder {
+        fn rumble(&mut self, strong: u16, weak: u16) -> io::Result<()> {
+            self.0.lock().unwrap().push((strong, weak));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn segments_in_sequence() -> io::Result<()> {
+        let ms = Duration::from_millis;
+        let pattern = RumblePattern::new()
+            .segment(100, 50, ms(10))
+            .pause(ms(5))
+            .segment(200, 0, ms(20));
+        let clock = MockClock::default();
+        let recorder = Recorder::default();
+        let mut motors = [recorder.clone()];
+        let mut sequencer = Sequencer::new(pattern.segments());
+        let at = |millis| SystemTime::UNIX_EPOCH + ms(millis);
+
+        assert_eq!(sequencer.tick(clock.now(), &mut motors)?, Some(at(10)));
+        clock.advance(ms(9));
+        assert_eq!(sequencer.tick(clock.now(), &mut motors)?, Some(at(10)));
+        assert_eq!(*recorder.0.lock().unwrap(), [(100, 50)]);
+        // A late tick doesn't push the rest of the pattern back
+        clock.advance(ms(3));
+        assert_eq!(sequencer.tick(clock.now(), &mut motors)?, Some(at(15)));
+        clock.advance(ms(3));
+        assert_eq!(sequencer.tick(clock.now(), &mut motors)?, Some(at(35)));
+        clock.advance(ms(20));
+        assert_eq!(sequencer.tick(clock.now(), &mut motors)?, None);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [(100, 50), (0, 0), (200, 0), (0, 0)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn mock_clock_drives_playback() -> io::Result<()> {
+        let pattern = RumblePattern::new()
+            .segment(100, 50, Duration::from_secs(3600))
+            .pause(Duration::from_secs(60));
+        let clock = MockClock::default();
+        let recorder = Recorder::default();
+        let playback = start(
+            pattern.segments.clone(),
+            vec![recorder.clone()],
+            clock.clone(),
+        );
+        playback.wait()?;
+        assert_eq!(clock.now(), SystemTime::UNIX_EPOCH + pattern.duration());
+        assert_eq!(*recorder.0.lock().unwrap(), [(100, 50), (0, 0), (0, 0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn cancel_stops_motors() {
+        let pattern = RumblePattern::new().segment(100, 50, Duration::from_secs(3600));
+        let recorder = Recorder::default();
+        let mut playback = start(pattern.segments, vec![recorder.clone()], SystemClock);
+        playback.cancel();
+        let mut state = playback.shared.state.lock().unwrap();
+        while !state.finished {
+            state = playback.shared.cond.wait(state).unwrap();
+        }
+        drop(state);
+        let mut cx = Context::from_waker(Waker::noop());
+        let res = Pin::new(&mut playback).poll(&mut cx);
+        assert!(matches!(res, Poll::Ready(Ok(()))));
+        assert!(playback.is_finished());
+        assert_eq!(*recorder.0.lock().unwrap(), [(100, 50), (0, 0)]);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use crate::transform::is_syn_report;
+use crate::{EventType, InputEvent, Key, MiscType};
+
+/// A key event together with the scancode the device reported for it.
+///
+/// Keyboards report the scancode of a key in an `MSC_SCAN` event just before the key event, in
+/// the same frame, so the scancode is known even for keys that aren't mapped to a keycode, or
+/// that a keymap mapped to another one. This is what a remapper needs to target physical keys.
+#[derive(Debug, Copy, Clone)]
+pub struct KeyEventWithScancode {
+    pub event: InputEvent,
+    /// The scancode, e.g. a HID usage like `0x70004` for USB keyboards, or `None` if the device
+    /// didn't report one.
+    pub scancode: Option<u32>,
+}
+
+impl KeyEventWithScancode {
+    pub fn key(&self) -> Key {
+        Key::new(self.event.code())
+    }
+
+    /// Returns 0 for a release, 1 for a press and 2 for a repeat.
+    pub fn value(&self) -> i32 {
+        self.event.value()
+    }
+}
+
+/// Pairs `MSC_SCAN` events with the key events they precede.
+///
+/// Feed it every event read from a device, in order. Repeats carry the scancode of the press
+/// when the device doesn't report one for them.
+#[derive(Debug, Clone, Default)]
+pub struct ScancodePairer {
+    /// The scancode reported since the last key event in the current frame.
+    pending: Option<u32>,
+    /// The scancodes of the keys that are down.
+    held: HashMap<Key, u32>,
+}
+
+impl ScancodePairer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process an event, returning the paired key event if it was one.
+    pub fn process_event(&mut self, ev: &InputEvent) -> Option<KeyEventWithScancode> {
+        match ev.event_type() {
+            EventType::MISC if MiscType(ev.code()) == MiscType::MSC_SCAN => {
+                self.pending = Some(ev.value() as u32);
+                None
+            }
+            EventType::KEY => {
+                let key = Key::new(ev.code());
+                let scancode = match self.pending.take() {
+                    Some(scancode) => Some(scancode),
+                    None if ev.value() == 2 => self.held.get(&key).copied(),
+                    None => None,
+                };
+                match (ev.value(), scancode) {
+                    (0, _) => {
+                        self.held.remove(&key);
+                    }
+                    (1, Some(scancode)) => {
+                        self.held.insert(key, scancode);
+                    }
+                    _ => {}
+                }
+                Some(KeyEventWithScancode {
+                    event: *ev,
+                    scancode,
+                })
+            }
+            _ => {
+                // A scancode doesn't carry over to the next frame
+                if is_syn_report(ev) {
+                    self.pending = None;
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Yields the key events of an iterator of events, paired with their scancodes.
+///
+/// ```no_run
+/// use evdev::{Device, ScancodePairs};
+///
+/// let mut device = Device::open("/dev/input/event0")?;
+/// loop {
+///     for ev in ScancodePairs::new(device.fetch_events()?) {
+///         println!("{:?} {:x?} {}", ev.key(), ev.scancode, ev.value());
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// A new `ScancodePairs` per read is fine, since devices report the scancode in the same read
+/// as its key event, but it can't pair repeats with the scancode of their press; for that,
+/// keep one [`ScancodePairer`] across reads.
+#[derive(Debug, Clone)]
+pub struct ScancodePairs<I> {
+    events: I,
+    pairer: ScancodePairer,
+}
+
+impl<I: Iterator<Item = InputEvent>> ScancodePairs<I> {
+    pub fn new(events: impl IntoIterator<IntoIter = I>) -> Self {
+        ScancodePairs {
+            events: events.into_iter(),
+            pairer: ScancodePairer::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = InputEvent>> Iterator for ScancodePairs<I> {
+    type Item = KeyEventWithScancode;
+
+    fn next(&mut self) -> Option<KeyEventWithScancode> {
+        let pairer = &mut self.pairer;
+        self.events.find_map(|ev| pairer.process_event(&ev))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pair_scancodes() {
+        let scan = |code| InputEvent::new(EventType::MISC, MiscType::MSC_SCAN.0, code);
+        let key = |key: Key, value| InputEvent::new(EventType::KEY, key.code(), value);
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        let events = [
+            scan(0x70004),
+            key(Key::KEY_A, 1),
+            syn,
+            key(Key::KEY_A, 2),
+            syn,
+            scan(0x70005),
+            syn,
+            key(Key::KEY_B, 1),
+            syn,
+            scan(0x70004),
+            key(Key::KEY_A, 0),
+            syn,
+        ];
+
+        let paired: Vec<_> = ScancodePairs::new(events)
+            .map(|ev| (ev.key(), ev.value(), ev.scancode))
+            .collect();
+        assert_eq!(
+            paired,
+            [
+                (Key::KEY_A, 1, Some(0x70004)),
+                (Key::KEY_A, 2, Some(0x70004)),
+                (Key::KEY_B, 1, None),
+                (Key::KEY_A, 0, Some(0x70004)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Full keyboard and pointer control from one or two switches.
+//!
+//! Users who can only operate a switch, e.g. a button pressed with the head, select keys by
+//! row-column scanning: a [`Scanner`] highlights the rows of a [`ScanGrid`] in turn, pressing
+//! the select switch enters the highlighted row, whose items are then highlighted in turn,
+//! and pressing it again performs the highlighted item on a virtual device.
+//!
+//! With a single switch the highlight advances automatically at the scan rate. With a second,
+//! advance switch it only moves when that switch is pressed, so users set their own pace.
+//! The highlight is available through [`Scanner::highlight`], for an on-screen display.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use evdev::scanning::{ScanGrid, Scanner, SwitchInput};
+//! use evdev::{Device, Key};
+//! use std::time::Duration;
+//!
+//! let mut switch = Device::open("/dev/input/event6")?;
+//! switch.grab()?;
+//! let mut scanner = Scanner::new(
+//!     ScanGrid::keyboard(),
+//!     SwitchInput::Key(Key::KEY_ENTER),
+//!     Duration::from_millis(1000),
+//! )?;
+//! scanner.run(&mut [switch])
+//! # }
+//! ```
+
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use crate::{AttributeSet, Device, EventType, InputEvent, Key, RelativeAxisType, SwitchType};
+
+/// How many times the items of a row are scanned before returning to the rows.
+const ROW_PASSES: usize = 2;
+
+/// A physical switch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SwitchInput {
+    /// A key or button, activated when pressed.
+    Key(Key),
+    /// A switch, activated when it turns on.
+    Switch(SwitchType),
+}
+
+impl SwitchInput {
+    /// Returns `true` if `ev` activates the switch.
+    pub fn is_activated_by(&self, ev: &InputEvent) -> bool {
+        let (type_, code) = match *self {
+            SwitchInput::Key(key) => (EventType::KEY, key.code()),
+            SwitchInput::Switch(switch) => (EventType::SWITCH, switch.0),
+        };
+        ev.event_type() == type_ && ev.code() == code && ev.value() == 1
+    }
+}
+
+/// Something a scanner can do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScanItem {
+    /// Press and release a key.
+    Key(Key),
+    /// Click a pointer button.
+    Click(Key),
+    /// Move the pointer. The item stays highlighted, so it can be selected repeatedly.
+    Move { dx: i32, dy: i32 },
+}
+
+/// The rows of items a [`Scanner`] goes through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanGrid {
+    pub rows: Vec<Vec<ScanItem>>,
+}
+
+impl ScanGrid {
+    pub fn new(rows: Vec<Vec<ScanItem>>) -> Self {
+        ScanGrid { rows }
+    }
+
+    /// A grid with the letters ordered by frequency, the digits, common editing keys, and a
+    /// row for the pointer.
+    pub fn keyboard() -> Self {
+        use Key as K;
+        let keys = |keys: &[Key]| keys.iter().map(|&k| ScanItem::Key(k)).collect();
+        let step = 20;
+        ScanGrid::new(vec![
+            keys(&[
+                K::KEY_SPACE,
+                K::KEY_E,
+                K::KEY_T,
+                K::KEY_A,
+                K::KEY_O,
+                K::KEY_I,
+                K::KEY_N,
+            ]),
+            keys(&[
+                K::KEY_S,
+                K::KEY_H,
+                K::KEY_R,
+                K::KEY_D,
+                K::KEY_L,
+                K::KEY_C,
+                K::KEY_U,
+            ]),
+            keys(&[
+                K::KEY_M,
+                K::KEY_W,
+                K::KEY_F,
+                K::KEY_G,
+                K::KEY_Y,
+                K::KEY_P,
+                K::KEY_B,
+            ]),
+            keys(&[
+                K::KEY_V,
+                K::KEY_K,
+                K::KEY_J,
+                K::KEY_X,
+                K::KEY_Q,
+                K::KEY_Z,
+                K::KEY_DOT,
+            ]),
+            keys(&[K::KEY_1, K::KEY_2, K::KEY_3, K::KEY_4, K::KEY_5]),
+            keys(&[K::KEY_6, K::KEY_7, K::KEY_8, K::KEY_9, K::KEY_0]),
+            keys(&[
+                K::KEY_BACKSPACE,
+                K::KEY_ENTER,
+                K::KEY_TAB,
+                K::KEY_ESC,
+                K::KEY_LEFT,
+                K::KEY_RIGHT,
+                K::KEY_UP,
+                K::KEY_DOWN,
+            ]),
+            vec![
+                ScanItem::Click(K::BTN_LEFT),
+                ScanItem::Click(K::BTN_RIGHT),
+                ScanItem::Move { dx: 0, dy: -step },
+                ScanItem::Move { dx: 0, dy: step },
+                ScanItem::Move { dx: -step, dy: 0 },
+                ScanItem::Move { dx: step, dy: 0 },
+            ],
+        ])
+    }
+}
+
+/// What a [`Scanner`] highlights.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Highlight {
+    Row(usize),
+    Item { row: usize, column: usize },
+}
+
+/// Performs the items of a grid selected with switches. See the
+/// [module documentation](self).
+pub struct Scanner {
+    device: VirtualDevice,
+    grid: ScanGrid,
+    select: SwitchInput,
+    advance: Option<SwitchInput>,
+    rate: Duration,
+    highlight: Highlight,
+    /// Items highlighted in the current row since it was entered.
+    steps: usize,
+    next_step: Option<SystemTime>,
+    clock: SharedClock,
+}
+
+impl Scanner {
+    /// Create a virtual keyboard and pointer for the items of `grid`, scanned at `rate` and
+    /// selected with `select`.
+    pub fn new(grid: ScanGrid, select: SwitchInput, rate: Duration) -> io::Result<Self> {
+        let mut keys = AttributeSet::<Key>::new();
+        for item in grid.rows.iter().flatten() {
+            match *item {
+                ScanItem::Key(key) | ScanItem::Click(key) => keys.insert(key),
+                ScanItem::Move { .. } => {}
+            }
+        }
+        keys.insert(Key::BTN_LEFT);
+        let axes: AttributeSet<RelativeAxisType> =
+            [RelativeAxisType::REL_X, RelativeAxisType::REL_Y]
+                .into_iter()
+                .collect();
+        let device = VirtualDeviceBuilder::new()?
+            .name("evdev switch scanning")
+            .with_keys(&keys)?
+            .with_relative_axes(&axes)?
+            .build()?;
+        Ok(Self::with_device(device, grid, select, rate))
+    }
+
+    /// Perform the items on an existing device, which must support them.
+    pub fn with_device(
+        device: VirtualDevice,
+        grid: ScanGrid,
+        select: SwitchInput,
+        rate: Duration,
+    ) -> Self {
+        Scanner {
+            device,
+            grid,
+            select,
+            advance: None,
+            rate,
+            highlight: Highlight::Row(0),
+            steps: 0,
+            next_step: None,
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Only move the highlight when `switch` is activated, instead of at the scan rate.
+    pub fn advance_switch(mut self, switch: SwitchInput) -> Self {
+        self.advance = Some(switch);
+        self
+    }
+
+    /// Take the time in [`run`](Self::run) from `clock` rather than the system clock.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Set the time each row or item is highlighted for.
+    pub fn set_rate(&mut self, rate: Duration) {
+        self.rate = rate;
+    }
+
+    pub fn device(&self) -> &VirtualDevice {
+        &self.device
+    }
+
+    pub fn grid(&self) -> &ScanGrid {
+        &self.grid
+    }
+
+    pub fn highlight(&self) -> Highlight {
+        self.highlight
+    }
+
+    /// Returns the time the highlight moves next, when scanning automatically.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        self.next_step
+    }
+
+    /// Move the highlight to the next row or item.
+    pub fn step(&mut self) {
+        let rows = self.grid.rows.len().max(1);
+        self.highlight = match self.highlight {
+            Highlight::Row(row) => Highlight::Row((row + 1) % rows),
+            Highlight::Item { row, column } => {
+                let columns = self.grid.rows.get(row).map_or(0, Vec::len);
+                self.steps += 1;
+                if columns == 0 || self.steps >= columns * ROW_PASSES {
+                    Highlight::Row(row)
+                } else {
+                    Highlight::Item {
+                        row,
+                        column: (column + 1) % columns,
+                    }
+                }
+            }
+        };
+    }
+
+    /// Select the highlighted row or item.
+    pub fn select(&mut self) -> io::Result<()> {
+        match self.highlight {
+            Highlight::Row(row) => {
+                if self.grid.rows.get(row).is_some_and(|r| !r.is_empty()) {
+                    self.highlight = Highlight::Item { row, column: 0 };
+                    self.steps = 0;
+                }
+            }
+            Highlight::Item { row, column } => {
+                let item = self.grid.rows[row][column];
+                self.perform(item)?;
+                if !matches!(item, ScanItem::Move { .. }) {
+                    self.highlight = Highlight::Row(0);
+                }
+                self.steps = 0;
+            }
+        }
+        Ok(())
+    }
+
+    fn perform(&mut self, item: ScanItem) -> io::Result<()> {
+        match item {
+            ScanItem::Key(key) | ScanItem::Click(key) => {
+                self.device
+                    .emit(&[InputEvent::new(EventType::KEY, key.code(), 1)])?;
+                self.device
+                    .emit(&[InputEvent::new(EventType::KEY, key.code(), 0)])
+            }
+            ScanItem::Move { dx, dy } => self.device.emit(&[
+                InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_X.0, dx),
+                InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_Y.0, dy),
+            ]),
+        }
+    }
+
+    /// Take an event from one of the switches into account.
+    pub fn observe(&mut self, ev: &InputEvent) -> io::Result<()> {
+        if self.select.is_activated_by(ev) {
+            self.select()?;
+            // Give the user the full time on the new highlight
+            self.next_step = None;
+        } else if self
+            .advance
+            .is_some_and(|advance| advance.is_activated_by(ev))
+        {
+            self.step();
+        }
+        Ok(())
+    }
+
+    /// Move the highlight if it is due as of `now`, when scanning automatically.
+    pub fn tick(&mut self, now: SystemTime) {
+        if self.advance.is_some() {
+            return;
+        }
+        match self.next_step {
+            Some(next) if next <= now => {
+                self.step();
+                self.next_step = Some(now + self.rate);
+            }
+            Some(_) => {}
+            None => self.next_step = Some(now + self.rate),
+        }
+    }
+
+    /// Scan with the switches of `devices` until an error occurs.
+    pub fn run(&mut self, devices: &mut [Device]) -> io::Result<()> {
+        use nix::poll::{poll, PollFd, PollFlags};
+        loop {
+            self.tick(self.clock.now());
+            let timeout = self.next_deadline().map_or(-1, |deadline| {
+                let remaining = self.clock.until(deadline);
+                remaining.as_millis().clamp(1, i32::MAX as u128) as i32
+            });
+            let mut fds: Vec<_> = devices
+                .iter()
+                .map(|device| PollFd::new(device.as_raw_fd(), PollFlags::POLLIN))
+                .collect();
+            poll(&mut fds, timeout)?;
+            for (fd, device) in fds.iter().zip(devices.iter_mut()) {
+                if fd.revents().is_some_and(|r| !r.is_empty()) {
+                    let events: Vec<_> = device.fetch_events()?.collect();
+                    for ev in &events {
+                        self.observe(ev)?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_column_scanning() -> io::Result<()> {
+        let grid = ScanGrid::new(vec![
+            vec![ScanItem::Key(Key::KEY_A), ScanItem::Key(Key::KEY_B)],
+            vec![ScanItem::Key(Key::KEY_C), ScanItem::Key(Key::KEY_D)],
+        ]);
+        let keys: AttributeSet<Key> = [Key::KEY_A, Key::KEY_B, Key::KEY_C, Key::KEY_D]
+            .into_iter()
+            .collect();
+        let device = VirtualDeviceBuilder::in_memory()
+            .with_keys(&keys)?
+            .build()?;
+        let sink = device.memory_sink().unwrap();
+        let switch = SwitchInput::Key(Key::KEY_ENTER);
+        let mut scanner = Scanner::with_device(device, grid, switch, Duration::from_secs(1));
+        let press = InputEvent::new(EventType::KEY, Key::KEY_ENTER.code(), 1);
+        let at = |s| SystemTime::UNIX_EPOCH + Duration::from_secs(s);
+
+        // Wait for the second row, enter it, wait for its second item and select it
+        scanner.tick(at(0));
+        scanner.tick(at(1));
+        assert_eq!(scanner.highlight(), Highlight::Row(1));
+        scanner.observe(&press)?;
+        scanner.tick(at(2));
+        scanner.tick(at(3));
+        assert_eq!(scanner.highlight(), Highlight::Item { row: 1, column: 1 });
+        scanner.observe(&press)?;
+        assert_eq!(scanner.highlight(), Highlight::Row(0));
+        let frames = sink.drain();
+        assert_eq!(frames[0][0].code(), Key::KEY_D.code());
+
+        // Without a selection, scanning returns to the rows after two passes
+        scanner.observe(&press)?;
+        for _ in 0..4 {
+            scanner.step();
+        }
+        assert_eq!(scanner.highlight(), Highlight::Row(0));
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+//! Sensors that report through input devices.
+//!
+//! Accelerometers of tablets and game controllers, and some ambient light and proximity
+//! sensors, report their readings as absolute axes rather than through IIO. The kernel sets
+//! `INPUT_PROP_ACCELEROMETER` on motion sensors and gives the scale as the axis resolution: in
+//! units per g for acceleration and units per degree per second for rotation.
+//! [`SensorStream`] finds the sensors of a device and turns its events into readings in
+//! physical units.
+//!
+//! ```no_run
+//! use evdev::sensor::{SensorReading, SensorStream};
+//!
+//! let mut sensor = SensorStream::new(evdev::Device::open("/dev/input/event7")?)?;
+//! loop {
+//!     for reading in sensor.fetch_readings()? {
+//!         if let SensorReading::Acceleration { x, y, z } = reading {
+//!             println!("{:.2} {:.2} {:.2} m/s²", x, y, z);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use crate::transform::is_syn_report;
+use crate::{AbsInfo, AbsoluteAxisType, Device, EventType, InputEvent, PropType};
+
+/// Standard gravity, in m/s².
+const G: f64 = 9.80665;
+
+/// A kind of sensor an input device can have.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SensorKind {
+    /// Acceleration on `ABS_X`, `ABS_Y` and `ABS_Z`, of a device with
+    /// `INPUT_PROP_ACCELEROMETER`.
+    Accelerometer,
+    /// Angular velocity on `ABS_RX`, `ABS_RY` and `ABS_RZ`, of a device with
+    /// `INPUT_PROP_ACCELEROMETER`.
+    Gyroscope,
+    /// Illuminance on `ABS_MISC`.
+    Light,
+    /// Distance to a nearby object on `ABS_DISTANCE`.
+    Proximity,
+}
+
+impl SensorKind {
+    fn axes(self) -> &'static [AbsoluteAxisType] {
+        match self {
+            SensorKind::Accelerometer => &[
+                AbsoluteAxisType::ABS_X,
+                AbsoluteAxisType::ABS_Y,
+                AbsoluteAxisType::ABS_Z,
+            ],
+            SensorKind::Gyroscope => &[
+                AbsoluteAxisType::ABS_RX,
+                AbsoluteAxisType::ABS_RY,
+                AbsoluteAxisType::ABS_RZ,
+            ],
+            SensorKind::Light => &[AbsoluteAxisType::ABS_MISC],
+            SensorKind::Proximity => &[AbsoluteAxisType::ABS_DISTANCE],
+        }
+    }
+
+    /// Returns the sensors of `device`.
+    ///
+    /// Light and proximity sensors have no property of their own, so they are only detected
+    /// on devices with no keys and no absolute axes besides `ABS_MISC` and `ABS_DISTANCE`.
+    pub fn detect(device: &Device) -> Vec<SensorKind> {
+        let Some(axes) = device.supported_absolute_axes() else {
+            return Vec::new();
+        };
+        let has_all = |kind: SensorKind| kind.axes().iter().all(|&axis| axes.contains(axis));
+        let candidates: &[SensorKind] = if device.properties().contains(PropType::ACCELEROMETER) {
+            &[SensorKind::Accelerometer, SensorKind::Gyroscope]
+        } else if device.supported_keys().is_none()
+            && axes.iter().all(|axis| {
+                axis == AbsoluteAxisType::ABS_MISC || axis == AbsoluteAxisType::ABS_DISTANCE
+            })
+        {
+            &[SensorKind::Light, SensorKind::Proximity]
+        } else {
+            &[]
+        };
+        candidates.iter().copied().filter(|&k| has_all(k)).collect()
+    }
+}
+
+/// A reading of a sensor, in physical units where the driver gives the scale.
+///
+/// Axes without a resolution are reported unscaled.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SensorReading {
+    /// Acceleration in m/s², including gravity.
+    Acceleration { x: f64, y: f64, z: f64 },
+    /// Angular velocity in degrees per second.
+    AngularVelocity { x: f64, y: f64, z: f64 },
+    /// Illuminance, in lux if the driver sets a resolution.
+    Light(f64),
+    /// Distance, in the units of the driver; often just near or far.
+    Proximity(f64),
+}
+
+#[derive(Debug, Clone)]
+struct Channel {
+    kind: SensorKind,
+    axes: Vec<(AbsoluteAxisType, AbsInfo)>,
+    values: Vec<i32>,
+    changed: bool,
+}
+
+impl Channel {
+    fn scaled(&self, i: usize) -> f64 {
+        let resolution = self.axes[i].1.resolution();
+        let value = f64::from(self.values[i]);
+        if resolution > 0 {
+            value / f64::from(resolution)
+        } else {
+            value
+        }
+    }
+
+    fn reading(&self) -> SensorReading {
+        match self.kind {
+            SensorKind::Accelerometer => SensorReading::Acceleration {
+                x: self.scaled(0) * G,
+                y: self.scaled(1) * G,
+                z: self.scaled(2) * G,
+            },
+            SensorKind::Gyroscope => SensorReading::AngularVelocity {
+                x: self.scaled(0),
+                y: self.scaled(1),
+                z: self.scaled(2),
+            },
+            SensorKind::Light => SensorReading::Light(self.scaled(0)),
+            SensorKind::Proximity => SensorReading::Proximity(self.scaled(0)),
+        }
+    }
+}
+
+/// The sensors of an input device. See the [module documentation](self).
+pub struct SensorStream {
+    device: Device,
+    channels: Vec<Channel>,
+}
+
+impl SensorStream {
+    /// Wrap a device, reading every sensor [`SensorKind::detect`] finds.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if it finds none.
+    pub fn new(device: Device) -> io::Result<Self> {
+        let kinds = SensorKind::detect(&device);
+        Self::with_kinds(device, &kinds)
+    }
+
+    /// Wrap a device, reading the sensors `kinds`, for devices whose sensors aren't detected.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if `kinds` is empty or the device lacks an
+    /// axis of one of them.
+    pub fn with_kinds(device: Device, kinds: &[SensorKind]) -> io::Result<Self> {
+        let unsupported = || io::Error::new(io::ErrorKind::Unsupported, "no sensor on device");
+        if kinds.is_empty() {
+            return Err(unsupported());
+        }
+        let mut channels = Vec::new();
+        for &kind in kinds {
+            let axes = kind
+                .axes()
+                .iter()
+                .map(|&axis| Some((axis, device.abs_info(axis)?)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(unsupported)?;
+            channels.push(Channel {
+                kind,
+                values: axes.iter().map(|(_, info)| info.value()).collect(),
+                axes,
+                changed: false,
+            });
+        }
+        Ok(SensorStream { device, channels })
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+
+    /// Returns the sensors being read.
+    pub fn kinds(&self) -> impl Iterator<Item = SensorKind> + '_ {
+        self.channels.iter().map(|c| c.kind)
+    }
+
+    /// Returns the latest reading of the sensor `kind`.
+    pub fn reading(&self, kind: SensorKind) -> Option<SensorReading> {
+        self.channels
+            .iter()
+            .find(|c| c.kind == kind)
+            .map(Channel::reading)
+    }
+
+    /// Update the sensors with an event. At the end of a frame, appends a reading to `out` for
+    /// every sensor that changed.
+    pub fn process_event(&mut self, ev: &InputEvent, out: &mut Vec<SensorReading>) {
+        process_event(&mut self.channels, ev, out);
+    }
+
+    /// Fetch the next batch of events from the device and return the readings.
+    ///
+    /// Like [`Device::fetch_events`], this blocks unless the device is non-blocking.
+    pub fn fetch_readings(&mut self) -> io::Result<impl Iterator<Item = SensorReading>> {
+        let events: Vec<InputEvent> = self.device.fetch_events()?.collect();
+        let mut out = Vec::new();
+        for ev in &events {
+            process_event(&mut self.channels, ev, &mut out);
+        }
+        Ok(out.into_iter())
+    }
+}
+
+fn process_event(channels: &mut [Channel], ev: &InputEvent, out: &mut Vec<SensorReading>) {
+    if is_syn_report(ev) {
+        for channel in channels.iter_mut().filter(|c| c.changed) {
+            channel.changed = false;
+            out.push(channel.reading());
+        }
+    } else if ev.event_type() == EventType::ABSOLUTE {
+        for channel in channels.iter_mut() {
+            if let Some(i) = channel.axes.iter().position(|(a, _)| a.0 == ev.code()) {
+                channel.values[i] = ev.value();
+                channel.changed = true;
+            }
+        }
+    }
+}
+
+impl AsRawFd for SensorStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.device.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaled_readings() {
+        // hid-sony: 8192 units per g, 1024 units per degree per second
+        let channel = |kind: SensorKind, resolution| {
+            let info = AbsInfo::new(0, -32768, 32767, 0, 0, resolution);
+            Channel {
+                kind,
+                axes: kind.axes().iter().map(|&axis| (axis, info)).collect(),
+                values: vec![0; kind.axes().len()],
+                changed: false,
+            }
+        };
+        let mut channels = [
+            channel(SensorKind::Accelerometer, 8192),
+            channel(SensorKind::Gyroscope, 1024),
+        ];
+        let mut out = Vec::new();
+        for (axis, value) in [
+            (AbsoluteAxisType::ABS_Z, 8192),
+            (AbsoluteAxisType::ABS_X, -4096),
+        ] {
+            process_event(
+                &mut channels,
+                &InputEvent::new(EventType::ABSOLUTE, axis.0, value),
+                &mut out,
+            );
+        }
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        process_event(&mut channels, &syn, &mut out);
+        // Only sensors that changed report
+        assert_eq!(
+            out,
+            [SensorReading::Acceleration {
+                x: -G / 2.0,
+                y: 0.0,
+                z: G
+            }]
+        );
+        process_event(
+            &mut channels,
+            &InputEvent::new(EventType::ABSOLUTE, AbsoluteAxisType::ABS_RY.0, 2048),
+            &mut out,
+        );
+        process_event(&mut channels, &syn, &mut out);
+        assert_eq!(
+            out[1],
+            SensorReading::AngularVelocity {
+                x: 0.0,
+                y: 2.0,
+                z: 0.0
+            }
+        );
+    }
+}
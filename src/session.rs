@@ -0,0 +1,463 @@
+//! Managing devices across session switches.
+//!
+//! A compositor or kiosk shell must stop reading input while its session is in the background,
+//! and once it's back, pick up where the devices are now: keys released and pressed in the
+//! meantime, a lid closed, a pen lifted. [`SessionDeviceManager`] does this for a set of
+//! devices. Tell it when the session changes, e.g. from [`VtGrab::active_vt`](crate::VtGrab)
+//! or logind's `Active` property, and on activation the next fetch starts with the events
+//! that bring the consumer's view up to date, as after a `SYN_DROPPED`.
+//!
+//! ```no_run
+//! use evdev::session::{Deactivation, SessionDeviceManager};
+//! use std::time::Duration;
+//!
+//! let mut manager = SessionDeviceManager::new(Deactivation::Revoke).grab_devices(true);
+//! let keyboard = manager.add("/dev/input/event3")?;
+//! loop {
+//!     let active = evdev::VtGrab::active_vt()? == 2;
+//!     manager.set_active(active)?;
+//!     for (id, ev) in manager.fetch_events(Some(Duration::from_millis(500)))? {
+//!         if id == keyboard {
+//!             println!("{:?}", ev);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fmt;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use nix::poll::{poll, PollFd, PollFlags};
+
+use crate::device_state::DeviceState;
+use crate::{Device, InputEvent};
+
+/// What happens to the devices while the session is inactive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Deactivation {
+    /// Keep the devices open, release their grabs and discard their events.
+    Pause,
+    /// Revoke and close the devices, so nothing holding a duplicate of their descriptors can
+    /// read them either, and open them again on activation.
+    Revoke,
+}
+
+/// Identifies a device of a [`SessionDeviceManager`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceId(usize);
+
+type Opener = Box<dyn FnMut(&Path) -> io::Result<Device> + Send>;
+
+struct Entry {
+    path: PathBuf,
+    /// `None` while revoked.
+    device: Option<Device>,
+    /// The state when the session was deactivated, to resynchronize from.
+    last_known: Option<DeviceState>,
+}
+
+/// A set of devices that follows a session. See the [module documentation](self).
+pub struct SessionDeviceManager {
+    entries: Vec<Option<Entry>>,
+    active: bool,
+    deactivation: Deactivation,
+    grab: bool,
+    opener: Opener,
+    /// An error held back by [`fetch_events`](Self::fetch_events) to return its events first.
+    pending_error: Option<io::Error>,
+}
+
+impl SessionDeviceManager {
+    /// Create a manager for a session that is currently active.
+    pub fn new(deactivation: Deactivation) -> Self {
+        SessionDeviceManager {
+            entries: Vec::new(),
+            active: true,
+            deactivation,
+            grab: false,
+            opener: Box::new(|path| Device::open(path)),
+            pending_error: None,
+        }
+    }
+
+    /// Grab the devices while the session is active. Off by default.
+    pub fn grab_devices(mut self, grab: bool) -> Self {
+        self.grab = grab;
+        self
+    }
+
+    /// Open devices with `opener` instead of [`Device::open`], e.g. to have a privileged
+    /// helper open them.
+    pub fn opener(
+        mut self,
+        opener: impl FnMut(&Path) -> io::Result<Device> + Send + 'static,
+    ) -> Self {
+        self.opener = Box::new(opener);
+        self
+    }
+
+    /// Returns `true` if the session is active, i.e. events are being delivered.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Open the device at `path` and manage it.
+    ///
+    /// While the session is inactive under [`Deactivation::Revoke`], the device isn't opened
+    /// until the session is activated.
+    pub fn add(&mut self, path: impl AsRef<Path>) -> io::Result<DeviceId> {
+        let path = path.as_ref().to_owned();
+        let device = if self.active || self.deactivation == Deactivation::Pause {
+            let mut device = (self.opener)(&path)?;
+            if self.active && self.grab {
+                device.grab()?;
+            }
+            Some(device)
+        } else {
+            None
+        };
+        let last_known = device.as_ref().map(|d| d.cached_state().clone());
+        self.entries.push(Some(Entry {
+            path,
+            device,
+            last_known: last_known.filter(|_| !self.active),
+        }));
+        Ok(DeviceId(self.entries.len() - 1))
+    }
+
+    /// Stop managing a device, returning it unless it's currently revoked.
+    pub fn remove(&mut self, id: DeviceId) -> Option<Device> {
+        self.entries.get_mut(id.0)?.take()?.device
+    }
+
+    /// Returns the path a device was added with.
+    pub fn path(&self, id: DeviceId) -> Option<&Path> {
+        Some(&self.entries.get(id.0)?.as_ref()?.path)
+    }
+
+    /// Returns a device, unless it's currently revoked.
+    pub fn device(&self, id: DeviceId) -> Option<&Device> {
+        self.entries.get(id.0)?.as_ref()?.device.as_ref()
+    }
+
+    pub fn device_mut(&mut self, id: DeviceId) -> Option<&mut Device> {
+        self.entries.get_mut(id.0)?.as_mut()?.device.as_mut()
+    }
+
+    /// Returns the devices that are currently open.
+    pub fn devices(&self) -> impl Iterator<Item = (DeviceId, &Device)> {
+        self.entries.iter().enumerate().filter_map(|(i, entry)| {
+            let device = entry.as_ref()?.device.as_ref()?;
+            Some((DeviceId(i), device))
+        })
+    }
+
+    /// Tell the manager whether the session is active. Does nothing if it already knew.
+    ///
+    /// Every device is handled even if some fail, e.g. because they were unplugged; the
+    /// first error is returned. A device that can't be opened again on activation stays
+    /// revoked until the next activation.
+    pub fn set_active(&mut self, active: bool) -> io::Result<()> {
+        if active == self.active {
+            return Ok(());
+        }
+        self.active = active;
+        trace_event!(debug, active, "session changed");
+        let mut result = Ok(());
+        for entry in self.entries.iter_mut().flatten() {
+            let r = if active {
+                activate(entry, self.grab, &mut self.opener)
+            } else {
+                deactivate(entry, self.deactivation)
+            };
+            if result.is_ok() {
+                result = r;
+            }
+        }
+        result
+    }
+
+    /// Wait up to `timeout`, or indefinitely if `None`, for events from the devices.
+    ///
+    /// While the session is inactive, events are read and discarded under
+    /// [`Deactivation::Pause`], and this only waits out the timeout under
+    /// [`Deactivation::Revoke`].
+    ///
+    /// Every ready device is read even if some fail, e.g. because they were unplugged. The
+    /// first error is returned, unless events were read from other devices: then those are
+    /// returned, and the error by the next call.
+    pub fn fetch_events(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> io::Result<Vec<(DeviceId, InputEvent)>> {
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
+        let mut open: Vec<_> = self
+            .entries
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, entry)| Some((DeviceId(i), entry.as_mut()?.device.as_mut()?)))
+            .collect();
+        let mut fds: Vec<_> = open
+            .iter()
+            .map(|(_, device)| PollFd::new(device.as_raw_fd(), PollFlags::POLLIN))
+            .collect();
+        // A device resynchronizing after activation is ready without any new events
+        let millis = if open.iter().any(|(_, device)| device.resync_pending()) {
+            0
+        } else {
+            timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32)
+        };
+        poll(&mut fds, millis)?;
+        let mut out = Vec::new();
+        let mut error = None;
+        for (fd, (id, device)) in fds.iter().zip(open.iter_mut()) {
+            if fd.revents().is_none_or(|r| r.is_empty()) && !device.resync_pending() {
+                continue;
+            }
+            match device.fetch_events() {
+                Ok(events) if self.active => out.extend(events.map(|ev| (*id, ev))),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        match error {
+            Some(e) if out.is_empty() => Err(e),
+            Some(e) => {
+                self.pending_error = Some(e);
+                Ok(out)
+            }
+            None => Ok(out),
+        }
+    }
+}
+
+fn deactivate(entry: &mut Entry, deactivation: Deactivation) -> io::Result<()> {
+    let Some(device) = &mut entry.device else {
+        return Ok(());
+    };
+    entry.last_known = Some(device.cached_state().clone());
+    match deactivation {
+        Deactivation::Pause => device.ungrab(),
+        Deactivation::Revoke => {
+            let result = device.revoke();
+            entry.device = None;
+            result
+        }
+    }
+}
+
+fn activate(entry: &mut Entry, grab: bool, opener: &mut Opener) -> io::Result<()> {
+    let device = match &mut entry.device {
+        Some(device) => device,
+        None => entry.device.insert(opener(&entry.path)?),
+    };
+    if grab {
+        device.grab()?;
+    }
+    // Anything queued predates the resync, and would be replayed on top of it
+    discard_pending(device)?;
+    if let Some(last_known) = entry.last_known.take() {
+        device.resync_from(last_known);
+    }
+    Ok(())
+}
+
+fn discard_pending(device: &mut Device) -> io::Result<()> {
+    loop {
+        let mut fd = [PollFd::new(device.as_raw_fd(), PollFlags::POLLIN)];
+        if poll(&mut fd, 0)? == 0 {
+            return Ok(());
+        }
+        device.fetch_events()?.for_each(drop);
+    }
+}
+
+impl fmt::Debug for SessionDeviceManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let paths: Vec<_> = self.entries.iter().flatten().map(|e| &e.path).collect();
+        f.debug_struct("SessionDeviceManager")
+            .field("devices", &paths)
+            .field("active", &self.active)
+            .field("deactivation", &self.deactivation)
+            .field("grab", &self.grab)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw_stream::RawDevice;
+    use crate::{EventType, Key};
+    use nix::fcntl::OFlag;
+    use std::fs::File;
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Opened {
+        pipes: Vec<(PathBuf, File)>,
+        failing: Vec<PathBuf>,
+    }
+
+    /// Opens devices reading from pipes, keeping the write ends by path.
+    #[derive(Clone, Default)]
+    struct Pipes(Arc<Mutex<Opened>>);
+
+    impl Pipes {
+        fn open(&self, path: &Path) -> io::Result<Device> {
+            let mut opened = self.0.lock().unwrap();
+            if opened.failing.iter().any(|p| p == path) {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            let (read, write) = nix::unistd::pipe2(OFlag::O_NONBLOCK | OFlag::O_CLOEXEC)?;
+            // SAFETY: the pipe was just created and nothing else owns its ends
+            let (read, write) = unsafe { (File::from_raw_fd(read), File::from_raw_fd(write)) };
+            let file = if path.ends_with("broken") {
+                // Polling the write end of a pipe without readers reports an error, and so
+                // does reading it
+                drop(read);
+                write
+            } else {
+                opened.pipes.push((path.to_owned(), write));
+                read
+            };
+            Ok(Device::from_raw_device(RawDevice::from_file_unchecked(
+                file,
+            )))
+        }
+
+        fn fail(&self, path: &str) {
+            self.0.lock().unwrap().failing.push(path.into());
+        }
+
+        fn opened(&self, path: &str) -> usize {
+            let opened = self.0.lock().unwrap();
+            opened
+                .pipes
+                .iter()
+                .filter(|(p, _)| p == Path::new(path))
+                .count()
+        }
+
+        /// Write a key press frame to the latest pipe opened for `path`.
+        fn press(&self, path: &str, key: Key) {
+            let frame = [
+                InputEvent::new(EventType::KEY, key.code(), 1),
+                InputEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+            ];
+            let mut opened = self.0.lock().unwrap();
+            let (_, file) = opened
+                .pipes
+                .iter_mut()
+                .rev()
+                .find(|(p, _)| p == Path::new(path))
+                .unwrap();
+            file.write_all(unsafe { crate::cast_to_bytes(frame.as_slice()) })
+                .unwrap();
+        }
+    }
+
+    fn manager(deactivation: Deactivation, pipes: &Pipes) -> SessionDeviceManager {
+        let pipes = pipes.clone();
+        SessionDeviceManager::new(deactivation).opener(move |path| pipes.open(path))
+    }
+
+    fn keys(events: Vec<(DeviceId, InputEvent)>) -> Vec<(DeviceId, u16)> {
+        events
+            .into_iter()
+            .filter(|(_, ev)| ev.event_type() == EventType::KEY)
+            .map(|(id, ev)| (id, ev.code()))
+            .collect()
+    }
+
+    fn fetch(manager: &mut SessionDeviceManager) -> Vec<(DeviceId, u16)> {
+        keys(manager.fetch_events(Some(Duration::ZERO)).unwrap())
+    }
+
+    fn held(manager: &SessionDeviceManager, id: DeviceId) -> Vec<Key> {
+        let state = manager.device(id).unwrap().cached_state();
+        state.key_vals().unwrap().iter().collect()
+    }
+
+    #[test]
+    fn pause_discards_events() -> io::Result<()> {
+        let pipes = Pipes::default();
+        let mut manager = manager(Deactivation::Pause, &pipes);
+        let id = manager.add("/dev/input/event0")?;
+        pipes.press("/dev/input/event0", Key::KEY_A);
+        assert_eq!(fetch(&mut manager), [(id, Key::KEY_A.code())]);
+
+        manager.set_active(false)?;
+        assert!(manager.device(id).is_some());
+        pipes.press("/dev/input/event0", Key::KEY_B);
+        assert_eq!(fetch(&mut manager), []);
+
+        // Events queued before activation predate the resync, which starts from the state
+        // known when the session was deactivated
+        pipes.press("/dev/input/event0", Key::KEY_C);
+        manager.set_active(true)?;
+        assert_eq!(held(&manager, id), [Key::KEY_A]);
+        // A pipe can't report the kernel's key state
+        assert!(manager.fetch_events(Some(Duration::ZERO)).is_err());
+        pipes.press("/dev/input/event0", Key::KEY_D);
+        assert_eq!(fetch(&mut manager), [(id, Key::KEY_D.code())]);
+        assert_eq!(pipes.opened("/dev/input/event0"), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn revoke_reopens() -> io::Result<()> {
+        let pipes = Pipes::default();
+        let mut manager = manager(Deactivation::Revoke, &pipes);
+        let kept = manager.add("/dev/input/event0")?;
+        let gone = manager.add("/dev/input/event1")?;
+        pipes.press("/dev/input/event0", Key::KEY_A);
+        assert_eq!(fetch(&mut manager), [(kept, Key::KEY_A.code())]);
+
+        // Pipes can't be revoked, but are closed all the same
+        assert!(manager.set_active(false).is_err());
+        assert!(manager.device(kept).is_none());
+        assert!(manager.device(gone).is_none());
+        assert_eq!(fetch(&mut manager), []);
+        let late = manager.add("/dev/input/event2")?;
+        assert_eq!(pipes.opened("/dev/input/event2"), 0);
+
+        // A device that can't be reopened doesn't keep the others closed
+        pipes.fail("/dev/input/event1");
+        assert!(manager.set_active(true).is_err());
+        assert!(manager.device(gone).is_none());
+        assert!(manager.device(late).is_some());
+        assert_eq!(pipes.opened("/dev/input/event0"), 2);
+        // The reopened device resyncs from the state known before it was revoked
+        assert_eq!(held(&manager, kept), [Key::KEY_A]);
+        assert!(manager.fetch_events(Some(Duration::ZERO)).is_err());
+        pipes.press("/dev/input/event0", Key::KEY_B);
+        assert_eq!(fetch(&mut manager), [(kept, Key::KEY_B.code())]);
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_keeps_events_before_errors() -> io::Result<()> {
+        let pipes = Pipes::default();
+        let mut manager = manager(Deactivation::Pause, &pipes);
+        let good = manager.add("/dev/input/event0")?;
+        manager.add("/dev/input/broken")?;
+        pipes.press("/dev/input/event0", Key::KEY_A);
+        let events = manager.fetch_events(Some(Duration::ZERO))?;
+        assert_eq!(keys(events), [(good, Key::KEY_A.code())]);
+        assert!(manager.fetch_events(Some(Duration::ZERO)).is_err());
+        assert!(manager.fetch_events(Some(Duration::ZERO)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+//! Stable player numbers for hotplugged controllers.
+//!
+//! Local multiplayer games give each controller a player number, and expect a controller that
+//! drops out and comes back, e.g. after its battery ran out, to get its old number back.
+//! [`ControllerSlots`] does that bookkeeping. Disconnected controllers keep their slot
+//! reserved; new controllers take free slots first, and only take over a reserved slot when
+//! there is no other room.
+//!
+//! Controllers are recognized by their unique id (the Bluetooth address of wireless
+//! controllers) when they have one, and otherwise by their ids, name and physical path, which
+//! for wired controllers includes the USB port.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use evdev::slots::{ControllerSlots, SlotEvent};
+//!
+//! let mut slots = ControllerSlots::new(4);
+//! loop {
+//!     for event in slots.scan() {
+//!         match event {
+//!             SlotEvent::Connected { slot } => println!("player {} joined", slot + 1),
+//!             SlotEvent::Reconnected { slot } => println!("player {} is back", slot + 1),
+//!             SlotEvent::Disconnected { slot } => println!("player {} left", slot + 1),
+//!         }
+//!     }
+//!     std::thread::sleep(std::time::Duration::from_secs(1));
+//! }
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use crate::{Device, InputId, Key};
+
+/// What identifies a controller across reconnections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerIdentity {
+    pub input_id: InputId,
+    pub name: Option<String>,
+    /// The unique id, if the device has one.
+    pub uniq: Option<String>,
+    /// The physical path, e.g. the USB port.
+    pub phys: Option<String>,
+}
+
+impl ControllerIdentity {
+    pub fn from_device(device: &Device) -> Self {
+        let non_empty = |s: Option<&str>| s.filter(|s| !s.is_empty()).map(str::to_owned);
+        ControllerIdentity {
+            input_id: device.input_id(),
+            name: non_empty(device.name()),
+            uniq: non_empty(device.unique_name()),
+            phys: non_empty(device.physical_path()),
+        }
+    }
+
+    /// Returns `true` if `other` is the same controller.
+    pub fn matches(&self, other: &ControllerIdentity) -> bool {
+        match (&self.uniq, &other.uniq) {
+            (Some(a), Some(b)) => a == b,
+            _ => self == other,
+        }
+    }
+}
+
+/// A change in the assignment of controllers to slots.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SlotEvent {
+    /// A new controller was assigned to `slot`.
+    Connected { slot: usize },
+    /// The controller that had `slot` before it disconnected is back.
+    Reconnected { slot: usize },
+    /// The controller in `slot` was disconnected. The slot stays reserved for it.
+    Disconnected { slot: usize },
+}
+
+struct Slot {
+    identity: ControllerIdentity,
+    /// The device and its path, while connected.
+    device: Option<(PathBuf, Device)>,
+    /// When the controller disconnected, for picking the slot to give away when full.
+    disconnected_at: u64,
+}
+
+/// Assigns player numbers to controllers. See the [module documentation](self).
+pub struct ControllerSlots {
+    slots: Vec<Option<Slot>>,
+    disconnects: u64,
+}
+
+/// Returns `true` if `device` looks like a gamepad or joystick.
+pub fn is_controller(device: &Device) -> bool {
+    device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(Key::BTN_SOUTH) || keys.contains(Key::BTN_TRIGGER))
+}
+
+impl ControllerSlots {
+    /// Create a manager for up to `max_slots` controllers.
+    pub fn new(max_slots: usize) -> Self {
+        ControllerSlots {
+            slots: (0..max_slots).map(|_| None).collect(),
+            disconnects: 0,
+        }
+    }
+
+    /// Returns the number of slots.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Find the slot for a controller, without assigning it.
+    fn pick(&self, identity: &ControllerIdentity) -> Option<(usize, bool)> {
+        let reserved = self.slots.iter().position(|slot| {
+            slot.as_ref()
+                .is_some_and(|s| s.device.is_none() && s.identity.matches(identity))
+        });
+        if let Some(slot) = reserved {
+            return Some((slot, true));
+        }
+        if let Some(slot) = self.slots.iter().position(Option::is_none) {
+            return Some((slot, false));
+        }
+        // Take over the slot whose controller has been gone the longest
+        let oldest = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((i, slot.as_ref()?)))
+            .filter(|(_, slot)| slot.device.is_none())
+            .min_by_key(|(_, slot)| slot.disconnected_at)?;
+        Some((oldest.0, false))
+    }
+
+    /// Assign a controller to a slot. Returns `None`, dropping the device, if every slot is
+    /// taken by a connected controller.
+    pub fn attach(&mut self, path: PathBuf, device: Device) -> Option<SlotEvent> {
+        let identity = ControllerIdentity::from_device(&device);
+        let (slot, reconnected) = self.pick(&identity)?;
+        self.slots[slot] = Some(Slot {
+            identity,
+            device: Some((path, device)),
+            disconnected_at: 0,
+        });
+        Some(if reconnected {
+            SlotEvent::Reconnected { slot }
+        } else {
+            SlotEvent::Connected { slot }
+        })
+    }
+
+    /// Mark the controller at `path` as disconnected, e.g. after reading from it failed with
+    /// `ENODEV`. Its slot stays reserved for it.
+    pub fn detach(&mut self, path: &Path) -> Option<SlotEvent> {
+        let slot = self.slot_of(path)?;
+        self.disconnects += 1;
+        let s = self.slots[slot].as_mut()?;
+        s.device = None;
+        s.disconnected_at = self.disconnects;
+        Some(SlotEvent::Disconnected { slot })
+    }
+
+    /// Free `slot`, disconnecting its controller if it is connected and dropping its
+    /// reservation.
+    pub fn release(&mut self, slot: usize) -> Option<Device> {
+        let slot = self.slots.get_mut(slot)?.take()?;
+        slot.device.map(|(_, device)| device)
+    }
+
+    /// Look for controllers that were plugged in or unplugged since the last scan, and update
+    /// the slots accordingly.
+    pub fn scan(&mut self) -> Vec<SlotEvent> {
+        let mut events = Vec::new();
+        let present: Vec<_> = crate::enumerate()
+            .filter(|(_, device)| is_controller(device))
+            .collect();
+        let gone: Vec<PathBuf> = self
+            .iter()
+            .map(|(_, path, _)| path)
+            .filter(|path| !present.iter().any(|(p, _)| p == path))
+            .map(Path::to_owned)
+            .collect();
+        events.extend(gone.iter().filter_map(|path| self.detach(path)));
+        for (path, device) in present {
+            if self.slot_of(&path).is_none() {
+                events.extend(self.attach(path, device));
+            }
+        }
+        events
+    }
+
+    /// Returns the slot of the connected controller at `path`.
+    pub fn slot_of(&self, path: &Path) -> Option<usize> {
+        self.slots.iter().position(|slot| {
+            let device = slot.as_ref().and_then(|s| s.device.as_ref());
+            device.is_some_and(|(p, _)| p == path)
+        })
+    }
+
+    /// Returns the controller in `slot`, if it is connected.
+    pub fn device(&self, slot: usize) -> Option<&Device> {
+        let (_, device) = self.slots.get(slot)?.as_ref()?.device.as_ref()?;
+        Some(device)
+    }
+
+    pub fn device_mut(&mut self, slot: usize) -> Option<&mut Device> {
+        let (_, device) = self.slots.get_mut(slot)?.as_mut()?.device.as_mut()?;
+        Some(device)
+    }
+
+    /// Returns the identity of the controller that has `slot`, connected or not.
+    pub fn identity(&self, slot: usize) -> Option<&ControllerIdentity> {
+        Some(&self.slots.get(slot)?.as_ref()?.identity)
+    }
+
+    /// Iterate over the connected controllers, with their slots and paths.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Path, &Device)> + '_ {
+        self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            let (path, device) = slot.as_ref()?.device.as_ref()?;
+            Some((i, path.as_path(), device))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &Path, &mut Device)> + '_ {
+        self.slots.iter_mut().enumerate().filter_map(|(i, slot)| {
+            let (path, device) = slot.as_mut()?.device.as_mut()?;
+            Some((i, path.as_path(), device))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BusType;
+
+    fn identity(product: u16, uniq: &str) -> ControllerIdentity {
+        ControllerIdentity {
+            input_id: InputId::new(BusType::BUS_BLUETOOTH, 0x054c, product, 0),
+            name: Some("Wireless Controller".into()),
+            uniq: Some(uniq.into()),
+            phys: None,
+        }
+    }
+
+    #[test]
+    fn reserved_slots() {
+        let mut slots = ControllerSlots::new(2);
+        let reserve = |slots: &mut ControllerSlots, slot: usize, id: ControllerIdentity| {
+            slots.disconnects += 1;
+            slots.slots[slot] = Some(Slot {
+                identity: id,
+                device: None,
+                disconnected_at: slots.disconnects,
+            });
+        };
+        reserve(&mut slots, 1, identity(0x09cc, "aa"));
+        // A new controller takes the free slot, not the reserved one
+        assert_eq!(slots.pick(&identity(0x09cc, "bb")), Some((0, false)));
+        // The controller that had slot 1 gets it back
+        assert_eq!(slots.pick(&identity(0x09cc, "aa")), Some((1, true)));
+
+        // When full, the slot disconnected longest ago is given away
+        reserve(&mut slots, 0, identity(0x09cc, "bb"));
+        reserve(&mut slots, 1, identity(0x09cc, "aa"));
+        assert_eq!(slots.pick(&identity(0x05c4, "cc")), Some((0, false)));
+    }
+}
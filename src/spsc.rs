@@ -0,0 +1,207 @@
+//! A lock-free single-producer single-consumer queue of event frames.
+//!
+//! This connects a thread reading (and typically grabbing) a device with a thread emitting on
+//! a virtual device, without locks or allocation on either side. The producer only publishes
+//! complete frames, so the consumer never sees half of one.
+//!
+//! ```
+//! use evdev::{spsc, EventType, Frame, InputEvent};
+//!
+//! let (mut tx, mut rx) = spsc::frame_channel(256);
+//! let frame = [
+//!     InputEvent::new(EventType::RELATIVE, 0, 5),
+//!     InputEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+//! ];
+//! tx.push_frame(&frame).unwrap();
+//!
+//! let mut out = Frame::<16>::new();
+//! assert!(rx.pop_frame(&mut out));
+//! assert_eq!(out.len(), 2);
+//! ```
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::transform::is_syn_report;
+use crate::{Frame, InputEvent};
+
+/// Keeps the producer's and consumer's indices on separate cache lines, so that they don't
+/// contend with each other.
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+struct Ring {
+    head: CachePadded<AtomicUsize>,
+    tail: CachePadded<AtomicUsize>,
+    slots: Box<[UnsafeCell<InputEvent>]>,
+    mask: usize,
+}
+
+// SAFETY: slots between head and tail are only read by the consumer, and slots outside of
+// that range are only written by the producer. The indices are published with release/acquire
+// ordering, so the writes to a slot happen before it is read.
+unsafe impl Sync for Ring {}
+unsafe impl Send for Ring {}
+
+/// Create a queue that can hold at least `capacity` events, rounded up to a power of two.
+pub fn frame_channel(capacity: usize) -> (FrameProducer, FrameConsumer) {
+    let capacity = capacity.max(2).next_power_of_two();
+    let empty = InputEvent::new(crate::EventType::SYNCHRONIZATION, 0, 0);
+    let ring = Arc::new(Ring {
+        head: CachePadded(AtomicUsize::new(0)),
+        tail: CachePadded(AtomicUsize::new(0)),
+        slots: (0..capacity).map(|_| UnsafeCell::new(empty)).collect(),
+        mask: capacity - 1,
+    });
+    (
+        FrameProducer {
+            ring: ring.clone(),
+            tail: 0,
+            cached_head: 0,
+        },
+        FrameConsumer {
+            ring,
+            head: 0,
+            cached_tail: 0,
+        },
+    )
+}
+
+/// The error returned when a frame doesn't fit in the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFull;
+
+impl fmt::Display for QueueFull {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("frame queue is full")
+    }
+}
+
+impl std::error::Error for QueueFull {}
+
+/// The sending half of a [`frame_channel`].
+pub struct FrameProducer {
+    ring: Arc<Ring>,
+    tail: usize,
+    cached_head: usize,
+}
+
+impl FrameProducer {
+    /// Queue a complete frame.
+    ///
+    /// The frame must end with `SYN_REPORT`; one is appended if it doesn't. Either the whole
+    /// frame is queued or, if there isn't room for it, none of it is. A frame larger than the
+    /// queue's capacity never fits.
+    pub fn push_frame(&mut self, frame: &[InputEvent]) -> Result<(), QueueFull> {
+        let terminated = frame.last().is_some_and(is_syn_report);
+        let needed = frame.len() + usize::from(!terminated);
+        let capacity = self.ring.slots.len();
+        if self.tail - self.cached_head + needed > capacity {
+            self.cached_head = self.ring.head.0.load(Ordering::Acquire);
+            if self.tail - self.cached_head + needed > capacity {
+                return Err(QueueFull);
+            }
+        }
+
+        let syn = InputEvent::new(crate::EventType::SYNCHRONIZATION, 0, 0);
+        let extra = if terminated { None } else { Some(syn) };
+        for (i, &ev) in frame.iter().chain(extra.iter()).enumerate() {
+            let slot = &self.ring.slots[(self.tail + i) & self.ring.mask];
+            // SAFETY: the slot is outside of the range the consumer may read from.
+            unsafe { *slot.get() = ev };
+        }
+        self.tail += needed;
+        self.ring.tail.0.store(self.tail, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns true if the consumer has been dropped.
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.ring) == 1
+    }
+}
+
+/// The receiving half of a [`frame_channel`].
+pub struct FrameConsumer {
+    ring: Arc<Ring>,
+    head: usize,
+    cached_tail: usize,
+}
+
+impl FrameConsumer {
+    /// Move the next frame into `out`, replacing its contents.
+    ///
+    /// Returns false, leaving `out` empty, if no frame is queued.
+    pub fn pop_frame<const N: usize>(&mut self, out: &mut Frame<N>) -> bool {
+        out.clear();
+        if self.head == self.cached_tail {
+            self.cached_tail = self.ring.tail.0.load(Ordering::Acquire);
+            if self.head == self.cached_tail {
+                return false;
+            }
+        }
+
+        // Only whole frames are published, so a SYN_REPORT is always found before the tail.
+        while self.head != self.cached_tail {
+            let slot = &self.ring.slots[self.head & self.ring.mask];
+            // SAFETY: the slot is between head and tail, which the producer doesn't touch.
+            let ev = unsafe { *slot.get() };
+            self.head += 1;
+            out.push(ev);
+            if is_syn_report(&ev) {
+                break;
+            }
+        }
+        self.ring.head.0.store(self.head, Ordering::Release);
+        true
+    }
+
+    /// Returns true if no frames are queued.
+    pub fn is_empty(&self) -> bool {
+        self.head == self.ring.tail.0.load(Ordering::Acquire)
+    }
+
+    /// Returns true if the producer has been dropped. Frames queued before that can still be
+    /// popped.
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.ring) == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventType;
+
+    #[test]
+    fn frames_cross_threads_whole() {
+        let (mut tx, mut rx) = frame_channel(8);
+        let producer = std::thread::spawn(move || {
+            for i in 0..1000 {
+                let frame = [
+                    InputEvent::new(EventType::RELATIVE, 0, i),
+                    InputEvent::new(EventType::RELATIVE, 1, i),
+                ];
+                while tx.push_frame(&frame).is_err() {
+                    std::thread::yield_now();
+                }
+            }
+        });
+
+        let mut frame = Frame::<4>::new();
+        let mut next = 0;
+        while next < 1000 {
+            if rx.pop_frame(&mut frame) {
+                assert_eq!(frame.len(), 3);
+                assert!(frame[..2].iter().all(|ev| ev.value() == next));
+                next += 1;
+            } else {
+                std::thread::yield_now();
+            }
+        }
+        producer.join().unwrap();
+        assert!(rx.is_empty() && rx.is_abandoned());
+    }
+}
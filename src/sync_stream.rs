@@ -1,10 +1,14 @@
 use crate::constants::*;
 use crate::device_state::DeviceState;
 use crate::raw_stream::RawDevice;
-use crate::{AttributeSet, AttributeSetRef, AutoRepeat, InputEvent, InputEventKind, InputId, Key};
-use std::os::unix::io::{AsRawFd, RawFd};
-use std::path::Path;
-use std::time::SystemTime;
+use crate::{
+    AbsInfo, AttributeSet, AttributeSetRef, AutoRepeat, CapabilityReport, FFEffect, FFEffectHandle,
+    InputEvent, InputEventKind, InputId, Key, StreamValidator, Violation,
+};
+use std::fs::File;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use std::{fmt, io};
 
 /// A physical or virtual device supported by evdev.
@@ -24,12 +28,21 @@ pub struct Device {
     prev_state: DeviceState,
     state: DeviceState,
     block_dropped: bool,
+    dropped_count: u64,
+    validator: Option<StreamValidator>,
+    violation_count: u64,
+    last_violation: Option<Violation>,
 }
 
 impl Device {
     /// Opens a device, given its system path.
     ///
     /// Paths are typically something like `/dev/input/event0`.
+    ///
+    /// If the device can't be opened for lack of permissions or because it doesn't exist, the
+    /// error converts into an [`Error::Open`](crate::Error::Open) explaining the likely cause.
+    /// The OS error is then found with
+    /// [`Error::raw_os_error_of`](crate::Error::raw_os_error_of).
     #[inline(always)]
     pub fn open(path: impl AsRef<Path>) -> io::Result<Device> {
         Self::_open(path.as_ref())
@@ -40,6 +53,29 @@ impl Device {
         RawDevice::open(path).map(Self::from_raw_device)
     }
 
+    /// Opens a device like [`open`](Self::open), with the access mode and descriptor flags set
+    /// by `options`.
+    pub fn open_with(path: impl AsRef<Path>, options: &crate::OpenOptions) -> io::Result<Device> {
+        RawDevice::open_with(path, options).map(Self::from_raw_device)
+    }
+
+    /// Adopts the descriptor `fd` inherited from the parent process, e.g. a privileged launcher
+    /// that opened the device before dropping privileges and passed its number on the command
+    /// line. For descriptors passed by systemd, see [`activation`](crate::activation).
+    ///
+    /// The descriptor is only adopted once the device is set up, starting with an
+    /// `EVIOCGVERSION` check; if any of the ioctls fails, it is left open.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open descriptor that nothing else in the process owns, since the returned
+    /// device closes it. It must also be an evdev device, or one without ioctls of evdev's kind,
+    /// since other drivers may take evdev's ioctls for different requests, see
+    /// [`raw`](crate::raw#safety).
+    pub unsafe fn from_inherited_fd(fd: RawFd) -> io::Result<Device> {
+        RawDevice::adopt_fd(fd).map(Device::from_raw_device)
+    }
+
     // TODO: should this be public?
     pub(crate) fn from_raw_device(raw: RawDevice) -> Device {
         let state = DeviceState::new(&raw);
@@ -50,6 +86,52 @@ impl Device {
             prev_state,
             state,
             block_dropped: false,
+            dropped_count: 0,
+            validator: None,
+            violation_count: 0,
+            last_violation: None,
+        }
+    }
+
+    /// Returns how many times the kernel has dropped events (reported with `SYN_DROPPED`) since
+    /// the device was opened.
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped_count
+    }
+
+    /// Enable or disable validation of the events read from the device.
+    ///
+    /// While enabled, every event is checked by a [`StreamValidator`] for broken SYN framing,
+    /// out-of-range MT slots and reused tracking ids. Violations don't interrupt reading; they
+    /// are counted, kept in [`last_violation`](Self::last_violation), and logged with the
+    /// `tracing` feature.
+    pub fn set_validation(&mut self, enabled: bool) {
+        self.validator = enabled.then(|| {
+            let validator = StreamValidator::new();
+            match self.raw.abs_info(AbsoluteAxisType::ABS_MT_SLOT) {
+                Some(info) => validator.with_slot_count(info.maximum().max(0) as usize + 1),
+                None => validator,
+            }
+        });
+    }
+
+    /// Returns how many protocol violations validation has found.
+    pub fn violation_count(&self) -> u64 {
+        self.violation_count
+    }
+
+    /// Returns the most recent protocol violation found by validation.
+    pub fn last_violation(&self) -> Option<&Violation> {
+        self.last_violation.as_ref()
+    }
+
+    fn validate(&mut self, ev: &InputEvent) {
+        if let Some(validator) = &mut self.validator {
+            if let Err(violation) = validator.check(ev) {
+                trace_event!(warn, %violation, "invalid event stream");
+                self.violation_count += 1;
+                self.last_violation = Some(violation);
+            }
         }
     }
 
@@ -85,6 +167,54 @@ impl Device {
         self.raw.input_id()
     }
 
+    /// Returns the sysfs directory of the input device, e.g. `/sys/devices/.../input/input12`.
+    pub fn sysfs_path(&self) -> io::Result<PathBuf> {
+        self.raw.sysfs_path()
+    }
+
+    /// Returns the battery of the device, if it has one the kernel knows about.
+    ///
+    /// Wireless controllers usually do, through their driver. Battery levels change slowly;
+    /// the returned [`Battery`](crate::Battery) reads the current values from sysfs on each
+    /// call.
+    pub fn battery(&self) -> io::Result<Option<crate::Battery>> {
+        self.raw.battery()
+    }
+
+    /// Returns `true` if the device is inhibited, so the kernel drops its events.
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] on kernels that can't inhibit devices, before
+    /// 5.11.
+    pub fn is_inhibited(&self) -> io::Result<bool> {
+        self.raw.is_inhibited()
+    }
+
+    /// Inhibit the device, or let its events through again.
+    ///
+    /// An inhibited device is closed on the driver side and drops its events for every reader,
+    /// unlike [`grab`](Self::grab), which only diverts them; a convertible can switch off its
+    /// keyboard while folded this way. Needs write access to the device's sysfs `inhibited`
+    /// attribute, which is usually limited to root.
+    pub fn set_inhibited(&self, inhibited: bool) -> io::Result<()> {
+        self.raw.set_inhibited(inhibited)
+    }
+
+    /// Returns `true` if the device may wake the system from suspend.
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] if the device can't wake the system at all.
+    pub fn wakeup_enabled(&self) -> io::Result<bool> {
+        self.raw.wakeup_enabled()
+    }
+
+    /// Allow or forbid the device to wake the system from suspend.
+    ///
+    /// The setting is the sysfs `power/wakeup` attribute of the nearest ancestor of the input
+    /// device that has one, e.g. its USB device, so it applies to every input device of e.g. a
+    /// USB receiver. Needs write access to sysfs, which is usually limited to root.
+    pub fn set_wakeup(&self, enabled: bool) -> io::Result<()> {
+        self.raw.set_wakeup(enabled)
+    }
+
     /// Returns a struct containing the delay and period for auto repeat
     pub fn get_auto_repeat(&self) -> Option<AutoRepeat> {
         self.raw.get_auto_repeat()
@@ -105,6 +235,23 @@ impl Device {
         self.raw.get_scancode_by_index(index)
     }
 
+    /// Iterate over the device's whole scancode to keycode table, e.g. to display or back it up.
+    ///
+    /// ```no_run
+    /// let device = evdev::Device::open("/dev/input/event0")?;
+    /// for entry in device.keymap() {
+    ///     let entry = entry?;
+    ///     println!("{:02x?} => {:?}", entry.scancode, entry.key);
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    ///
+    /// Entries are read one by one with `EVIOCGKEYCODE_V2`, by index. Devices without a
+    /// keymap, like most that aren't keyboards, yield a single error.
+    pub fn keymap(&self) -> crate::raw_stream::Keymap<'_> {
+        self.raw.keymap()
+    }
+
     /// Update a scancode. The return value is the previous keycode
     pub fn update_scancode(&self, keycode: Key, scancode: &[u8]) -> io::Result<Key> {
         self.raw
@@ -253,6 +400,157 @@ impl Device {
         self.raw.supported_sounds()
     }
 
+    /// Returns the set of supported force feedback effects supported by a device.
+    ///
+    /// Besides effect types, this also contains [`FFEffectType::FF_GAIN`] and
+    /// [`FFEffectType::FF_AUTOCENTER`] if the device supports those controls, and the waveforms
+    /// (e.g. [`FFEffectType::FF_SINE`]) supported for periodic effects.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use evdev::{Device, FFEffectType};
+    /// let device = Device::open("/dev/input/event0")?;
+    ///
+    /// // Can the device rumble?
+    /// let supported = device
+    ///     .supported_ff_effects()
+    ///     .map_or(false, |effects| effects.contains(FFEffectType::FF_RUMBLE));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn supported_ff_effects(&self) -> Option<&AttributeSetRef<FFEffectType>> {
+        self.raw.supported_ff_effects()
+    }
+
+    /// Returns `true` if the device supports events of type `ty`.
+    pub fn supports_event_type(&self, ty: EventType) -> bool {
+        self.raw.supports_event_type(ty)
+    }
+
+    /// Returns `true` if the device has the key or button `key`.
+    ///
+    /// Like the other `supports_*` methods, this checks the capabilities read when the device
+    /// was opened, without a syscall or copying the set.
+    pub fn supports_key(&self, key: Key) -> bool {
+        self.raw.supports_key(key)
+    }
+
+    /// Returns `true` if the device has the relative axis `axis`.
+    pub fn supports_relative_axis(&self, axis: RelativeAxisType) -> bool {
+        self.raw.supports_relative_axis(axis)
+    }
+
+    /// Returns `true` if the device has the absolute axis `axis`.
+    pub fn supports_absolute_axis(&self, axis: AbsoluteAxisType) -> bool {
+        self.raw.supports_absolute_axis(axis)
+    }
+
+    /// Returns `true` if the device has the switch `switch`.
+    pub fn supports_switch(&self, switch: SwitchType) -> bool {
+        self.raw.supports_switch(switch)
+    }
+
+    /// Returns `true` if the device has the LED `led`.
+    pub fn supports_led(&self, led: LedType) -> bool {
+        self.raw.supports_led(led)
+    }
+
+    /// Returns `true` if the device reports the miscellaneous event `misc`.
+    pub fn supports_misc(&self, misc: MiscType) -> bool {
+        self.raw.supports_misc(misc)
+    }
+
+    /// Returns `true` if the device can play the sound `sound`.
+    pub fn supports_sound(&self, sound: SoundType) -> bool {
+        self.raw.supports_sound(sound)
+    }
+
+    /// Returns `true` if the device supports the force feedback effect, waveform or control
+    /// `effect`.
+    pub fn supports_ff_effect(&self, effect: FFEffectType) -> bool {
+        self.raw.supports_ff_effect(effect)
+    }
+
+    /// Returns `true` if the device has the property `prop`.
+    pub fn has_property(&self, prop: PropType) -> bool {
+        self.raw.has_property(prop)
+    }
+
+    /// Returns `true` if the device can emit the event `code` of type `ty`, e.g. to check an
+    /// event read from elsewhere before writing it to the device.
+    ///
+    /// Types without codes, like [`EventType::SYNCHRONIZATION`], are supported if the type is.
+    pub fn supports_code(&self, ty: EventType, code: u16) -> bool {
+        self.raw.supports_code(ty, code)
+    }
+
+    /// Returns a snapshot of the device's identity and capabilities, printable in the style of
+    /// `evtest`. Useful for debug dumps and bug reports.
+    pub fn capability_report(&self) -> CapabilityReport {
+        self.raw.capability_report()
+    }
+
+    /// Returns what kind of device this is, worked out from its capabilities like udev does.
+    pub fn classes(&self) -> Vec<crate::DeviceClass> {
+        crate::class::classify(
+            self.properties(),
+            self.supported_keys(),
+            self.supported_relative_axes(),
+            self.supported_absolute_axes(),
+            self.supported_switches(),
+        )
+    }
+
+    /// Returns the range, resolution and noise parameters of an absolute axis, as read when the
+    /// capabilities were last queried.
+    ///
+    /// The [`value`](AbsInfo::value) is the axis's value at that time, and may be stale; see
+    /// [`cached_state`](Self::cached_state) for current values.
+    pub fn abs_info(&self, axis: AbsoluteAxisType) -> Option<AbsInfo> {
+        self.raw.abs_info(axis)
+    }
+
+    /// Change the range, resolution and noise parameters of an absolute axis, e.g. to apply a
+    /// calibration to everyone reading the device.
+    ///
+    /// The kernel keeps the new parameters until the device is removed. The cached
+    /// [`abs_info`](Self::abs_info) is updated to match.
+    pub fn set_abs_info(&mut self, axis: AbsoluteAxisType, info: AbsInfo) -> io::Result<()> {
+        self.raw.set_abs_info(axis, info)
+    }
+
+    /// Re-query the device's capabilities from the kernel.
+    ///
+    /// Capabilities are read once when the device is opened and cached, so that querying them
+    /// doesn't cost a syscall. Call this if they may have changed since. The cached device
+    /// state is reset to match.
+    pub fn refresh_capabilities(&mut self) -> io::Result<()> {
+        self.refresh().map(drop)
+    }
+
+    /// Re-query the device's metadata from the kernel like
+    /// [`refresh_capabilities`](Self::refresh_capabilities), and return what changed, e.g. after
+    /// another process set an axis' range with `EVIOCSABS`. Long-running daemons can call this
+    /// periodically, or when told about a change, to keep the cached metadata current.
+    ///
+    /// The keymap isn't cached: [`get_scancode_by_keycode`](Self::get_scancode_by_keycode) and
+    /// the like always query the kernel, so they see remapped keys without a refresh.
+    pub fn refresh(&mut self) -> io::Result<crate::MetadataChanges> {
+        let changes = self.raw.refresh()?;
+        self.state = DeviceState::new(&self.raw);
+        self.sync_state(SystemTime::now())?;
+        self.prev_state.clone_from(&self.state);
+        Ok(changes)
+    }
+
+    /// Returns the maximum number of force feedback effects that can be uploaded to the device
+    /// at the same time.
+    pub fn max_ff_effects(&self) -> io::Result<usize> {
+        self.raw.max_ff_effects()
+    }
+
     /// Retrieve the current keypress state directly via kernel syscall.
     pub fn get_key_state(&self) -> io::Result<AttributeSet<Key>> {
         self.raw.get_key_state()
@@ -290,10 +588,29 @@ impl Device {
         Ok(())
     }
 
+    /// Resynchronize on the next fetch as if events had been dropped, injecting events for
+    /// every difference between `last_known` and the kernel's state. `last_known` is ignored if
+    /// it doesn't have the same event types as this device.
+    pub(crate) fn resync_from(&mut self, last_known: DeviceState) {
+        let same_types = last_known.key_vals.is_some() == self.state.key_vals.is_some()
+            && last_known.abs_vals.is_some() == self.state.abs_vals.is_some()
+            && last_known.switch_vals.is_some() == self.state.switch_vals.is_some()
+            && last_known.led_vals.is_some() == self.state.led_vals.is_some();
+        if same_types {
+            self.state = last_known;
+        }
+        self.block_dropped = true;
+    }
+
     fn fetch_events_inner(&mut self) -> io::Result<Option<SyncState>> {
         let block_dropped = std::mem::take(&mut self.block_dropped);
         let sync = if block_dropped {
+            trace_span!("resync", dropped = self.dropped_count);
+            trace_event!(warn, "events were dropped, resynchronizing device state");
             self.prev_state.clone_from(&self.state);
+            if let Some(validator) = &mut self.validator {
+                validator.reset();
+            }
             let now = SystemTime::now();
             self.sync_state(now)?;
             Some(SyncState::Keys {
@@ -304,11 +621,68 @@ impl Device {
             None
         };
 
-        self.raw.fill_events()?;
+        match self.raw.fill_events() {
+            // The resync still has to be delivered, even with nothing new to read
+            Err(e) if sync.is_some() && e.kind() == io::ErrorKind::WouldBlock => {}
+            r => {
+                r?;
+            }
+        }
 
         Ok(sync)
     }
 
+    pub(crate) fn shared_file(&self) -> &std::sync::Arc<std::fs::File> {
+        self.raw.shared_file()
+    }
+
+    /// Returns `true` if the next fetch resynchronizes the device state.
+    pub(crate) fn resync_pending(&self) -> bool {
+        self.block_dropped
+    }
+
+    /// Returns the size, in events, of the buffer used for each read from the kernel.
+    pub fn event_buffer_size(&self) -> usize {
+        self.raw.event_buffer_size()
+    }
+
+    /// Set the size, in events, of the buffer used for each read from the kernel. Defaults to 32.
+    ///
+    /// Devices producing many events per frame, like multitouch panels or high-rate mice, are
+    /// less likely to overflow the kernel's buffer (and produce SYN_DROPPED) with a larger
+    /// buffer. Set this before converting the device into an event stream.
+    pub fn set_event_buffer_size(&mut self, size: usize) {
+        self.raw.set_event_buffer_size(size)
+    }
+
+    /// Read events from the kernel directly into `buf`, without going through the internal
+    /// buffer. Returns the number of events written to the start of `buf`.
+    ///
+    /// This is a raw read: no synchronization is done on SYN_DROPPED, and the cached device
+    /// state is not updated. Don't mix it with [`fetch_events`](Self::fetch_events) unless
+    /// you resynchronize yourself.
+    pub fn read_into(&mut self, buf: &mut [InputEvent]) -> io::Result<usize> {
+        self.raw.read_into(buf)
+    }
+
+    /// Discard the events queued on the device, without decoding them, e.g. right after
+    /// grabbing it or resuming from suspend, so stale events aren't processed. Returns the
+    /// number of events discarded.
+    ///
+    /// The cached device state is then synchronized with the kernel's, as if events had been
+    /// dropped, but without injecting events for the differences. Never blocks, whether or not
+    /// the fd is O_NONBLOCK.
+    pub fn drain(&mut self) -> io::Result<usize> {
+        let discarded = self.raw.drain()?;
+        self.block_dropped = false;
+        if let Some(validator) = &mut self.validator {
+            validator.reset();
+        }
+        self.sync_state(SystemTime::now())?;
+        self.prev_state.clone_from(&self.state);
+        Ok(discarded)
+    }
+
     /// Fetches and returns events from the kernel ring buffer, doing synchronization on SYN_DROPPED.
     ///
     /// By default this will block until events are available. Typically, users will want to call
@@ -334,15 +708,92 @@ impl Device {
     ///
     /// This prevents other clients (including kernel-internal ones such as rfkill) from receiving
     /// events from this device.
+    ///
+    /// If another client already holds a grab, this fails with
+    /// [`Error::DeviceBusy`](crate::Error::DeviceBusy), listing the processes that have the
+    /// device open.
     pub fn grab(&mut self) -> io::Result<()> {
         self.raw.grab()
     }
 
+    /// Returns `true` if this device holds a grab, taken with [`grab`](Self::grab).
+    pub fn is_grabbed(&self) -> bool {
+        self.raw.is_grabbed()
+    }
+
+    /// Returns `true` if another client, such as a remapping daemon, currently holds a grab on
+    /// the device.
+    ///
+    /// This is best-effort: it briefly grabs and releases the device, so it can race with
+    /// other clients doing the same. Always `false` if this device holds the grab itself.
+    pub fn is_grabbed_by_other(&mut self) -> io::Result<bool> {
+        self.raw.is_grabbed_by_other()
+    }
+
     /// Ungrab the device through a kernel syscall.
     pub fn ungrab(&mut self) -> io::Result<()> {
         self.raw.ungrab()
     }
 
+    /// Tell the kernel to deliver only the events in `mask` through this file descriptor.
+    ///
+    /// Every event type the device supports and the kernel can mask is set, so events of types
+    /// absent from `mask` are no longer delivered. Requires Linux 4.4 or later. To share a
+    /// device between consumers interested in different events, see
+    /// [`EventMaskManager`](crate::mask::EventMaskManager).
+    pub fn set_event_mask(&mut self, mask: &crate::mask::EventMask) -> io::Result<()> {
+        self.raw.set_event_mask(mask)
+    }
+
+    /// Revoke this file descriptor's access to the device.
+    ///
+    /// Afterwards reads fail with `ENODEV`, for this descriptor and any duplicates of it, e.g.
+    /// ones passed to other processes. This can't be undone: open the device again to regain
+    /// access.
+    pub fn revoke(&mut self) -> io::Result<()> {
+        self.raw.revoke()
+    }
+
+    /// Upload a force feedback effect to the device.
+    ///
+    /// The effect stays on the device until the returned handle is dropped.
+    pub fn upload_ff_effect(&mut self, effect: &FFEffect) -> io::Result<FFEffectHandle> {
+        self.raw.upload_ff_effect(effect)
+    }
+
+    /// Rumble the device's motors for `duration`.
+    ///
+    /// See [`RawDevice::rumble`](crate::raw_stream::RawDevice::rumble): the effect stops on its
+    /// own, and each call replaces the rumble of the previous one.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::time::Duration;
+    /// let mut device = evdev::Device::open("/dev/input/event0")?;
+    /// device.rumble(0xffff, 0x8000, Duration::from_millis(500))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rumble(&mut self, strong: u16, weak: u16, duration: Duration) -> io::Result<()> {
+        self.raw.rumble(strong, weak, duration)
+    }
+
+    /// Set the overall strength of force feedback effects, from 0 to 0xffff.
+    ///
+    /// Only supported if the device supports [`FFEffectType::FF_GAIN`].
+    pub fn set_ff_gain(&mut self, gain: u16) -> io::Result<()> {
+        self.raw.set_ff_gain(gain)
+    }
+
+    /// Set the strength of the device's autocenter spring, from 0 (off) to 0xffff.
+    ///
+    /// Only supported if the device supports [`FFEffectType::FF_AUTOCENTER`].
+    pub fn set_ff_autocenter(&mut self, strength: u16) -> io::Result<()> {
+        self.raw.set_ff_autocenter(strength)
+    }
+
     /// Send an event to the device.
     ///
     /// Events that are typically sent to devices are
@@ -360,6 +811,42 @@ impl AsRawFd for Device {
     }
 }
 
+impl AsFd for Device {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.raw.as_fd()
+    }
+}
+
+impl IntoRawFd for Device {
+    fn into_raw_fd(self) -> RawFd {
+        self.raw.into_raw_fd()
+    }
+}
+
+impl From<Device> for OwnedFd {
+    fn from(device: Device) -> OwnedFd {
+        device.raw.into()
+    }
+}
+
+/// Use an already opened device node, e.g. one received from another process or opened by a
+/// privileged helper.
+impl TryFrom<File> for Device {
+    type Error = io::Error;
+
+    fn try_from(file: File) -> io::Result<Self> {
+        RawDevice::try_from(file).map(Device::from_raw_device)
+    }
+}
+
+impl TryFrom<OwnedFd> for Device {
+    type Error = io::Error;
+
+    fn try_from(fd: OwnedFd) -> io::Result<Self> {
+        RawDevice::try_from(fd).map(Device::from_raw_device)
+    }
+}
+
 /// An iterator over events of a [`Device`], produced by [`Device::fetch_events`].
 pub struct FetchEventsSynced<'a> {
     dev: &'a mut Device,
@@ -518,10 +1005,15 @@ impl<'a> Iterator for FetchEventsSynced<'a> {
             self.consumed_to = end
         }
         match res {
-            Ok(ev) => Some(InputEvent(ev)),
+            Ok(ev) => {
+                let ev = InputEvent(ev);
+                self.dev.validate(&ev);
+                Some(ev)
+            }
             Err(requires_sync) => {
                 if requires_sync {
                     self.dev.block_dropped = true;
+                    self.dev.dropped_count += 1;
                 }
                 None
             }
@@ -679,8 +1171,8 @@ impl fmt::Display for Device {
 
         let evs = self.supported_events();
 
-        if evs.contains(EventType::FORCEFEEDBACK) {
-            writeln!(f, "  Force Feedback supported")?;
+        if let Some(supported_ff) = self.supported_ff_effects() {
+            writeln!(f, "  Force Feedback: {:?}", supported_ff)?;
         }
 
         if evs.contains(EventType::POWER) {
@@ -745,6 +1237,33 @@ mod tokio_stream {
             poll_fn(|cx| self.poll_event(cx)).await
         }
 
+        /// Wait for events and read them from the kernel directly into `buf`, returning how
+        /// many were read.
+        ///
+        /// Like [`Device::read_into`], this is a raw read: no synchronization is done on
+        /// SYN_DROPPED, the cached device state is not updated, and events already buffered by
+        /// [`next_event`](Self::next_event) are not returned. Don't mix the two unless you
+        /// resynchronize yourself.
+        pub async fn read_into(&mut self, buf: &mut [InputEvent]) -> io::Result<usize> {
+            poll_fn(|cx| self.poll_read_into(cx, buf)).await
+        }
+
+        /// A lower-level version of [`read_into`](Self::read_into) for use inside a
+        /// `Future::poll` impl.
+        pub fn poll_read_into(
+            &mut self,
+            cx: &mut Context<'_>,
+            buf: &mut [InputEvent],
+        ) -> Poll<io::Result<usize>> {
+            loop {
+                let mut guard = ready!(self.device.poll_read_ready_mut(cx))?;
+                match guard.try_io(|device| device.get_mut().read_into(buf)) {
+                    Ok(res) => return Poll::Ready(res),
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+
         /// A lower-level function for directly polling this stream.
         pub fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<InputEvent>> {
             'outer: loop {
@@ -761,10 +1280,15 @@ mod tokio_stream {
                     self.consumed_to = end
                 }
                 match res {
-                    Ok(ev) => return Poll::Ready(Ok(InputEvent(ev))),
+                    Ok(ev) => {
+                        let ev = InputEvent(ev);
+                        dev.validate(&ev);
+                        return Poll::Ready(Ok(ev));
+                    }
                     Err(requires_sync) => {
                         if requires_sync {
                             dev.block_dropped = true;
+                            dev.dropped_count += 1;
                         }
                     }
                 }
@@ -794,6 +1318,25 @@ mod tokio_stream {
             self.get_mut().poll_event(cx).map(Some)
         }
     }
+
+    impl AsRawFd for EventStream {
+        fn as_raw_fd(&self) -> RawFd {
+            self.device.get_ref().as_raw_fd()
+        }
+    }
+
+    impl AsFd for EventStream {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            self.device.get_ref().as_fd()
+        }
+    }
+
+    impl IntoRawFd for EventStream {
+        /// Deregisters the descriptor from the runtime and returns it, still non-blocking.
+        fn into_raw_fd(self) -> RawFd {
+            self.device.into_inner().into_raw_fd()
+        }
+    }
 }
 #[cfg(feature = "tokio")]
 pub use tokio_stream::EventStream;
@@ -868,4 +1411,13 @@ mod tests {
         assert_eq!(next(), (Err(false), None));
         assert_eq!(next(), (Err(false), None));
     }
+
+    #[test]
+    fn inherited_fd_left_open_on_failure() {
+        let file = File::open("/dev/null").unwrap();
+        // SAFETY: the descriptor stays owned by `file`, which is only dropped afterwards;
+        // /dev/null has no ioctls
+        assert!(unsafe { Device::from_inherited_fd(file.as_raw_fd()) }.is_err());
+        assert!(nix::fcntl::fcntl(file.as_raw_fd(), nix::fcntl::F_GETFD).is_ok());
+    }
 }
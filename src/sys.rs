@@ -1,12 +1,15 @@
 use libc::c_int;
-use libc::{ff_effect, input_absinfo, input_id, input_keymap_entry, uinput_setup};
+use libc::{
+    ff_effect, input_absinfo, input_id, input_keymap_entry, input_mask, uinput_abs_setup,
+    uinput_ff_erase, uinput_ff_upload, uinput_setup,
+};
 // use libc::{
 //     ff_condition_effect, ff_constant_effect, ff_envelope, ff_periodic_effect, ff_ramp_effect,
 //     ff_replay, ff_rumble_effect, ff_trigger, input_event, input_keymap_entry,
 // };
 use nix::{
-    convert_ioctl_res, ioctl_none, ioctl_read, ioctl_read_buf, ioctl_write_buf, ioctl_write_int,
-    ioctl_write_ptr, request_code_read,
+    convert_ioctl_res, ioctl_none, ioctl_read, ioctl_read_buf, ioctl_readwrite, ioctl_write_buf,
+    ioctl_write_int, ioctl_write_ptr, request_code_read, request_code_write,
 };
 
 ioctl_read!(eviocgeffects, b'E', 0x84, ::libc::c_int);
@@ -32,15 +35,19 @@ ioctl_read_buf!(eviocgled, b'E', 0x19, u8);
 ioctl_read_buf!(eviocgsnd, b'E', 0x1a, u8);
 ioctl_read_buf!(eviocgsw, b'E', 0x1b, u8);
 
-ioctl_write_ptr!(eviocsff, b'E', 0x80, ff_effect);
 ioctl_write_int!(eviocgrab, b'E', 0x90);
 ioctl_write_int!(eviocrevoke, b'E', 0x91);
 ioctl_write_int!(eviocsclockid, b'E', 0xa0);
+ioctl_read!(eviocgmask, b'E', 0x92, input_mask);
+ioctl_write_ptr!(eviocsmask, b'E', 0x93, input_mask);
 
 const UINPUT_IOCTL_BASE: u8 = b'U';
 ioctl_write_ptr!(ui_dev_setup, UINPUT_IOCTL_BASE, 3, uinput_setup);
 ioctl_none!(ui_dev_create, UINPUT_IOCTL_BASE, 1);
+ioctl_none!(ui_dev_destroy, UINPUT_IOCTL_BASE, 2);
+ioctl_write_ptr!(ui_abs_setup, UINPUT_IOCTL_BASE, 4, uinput_abs_setup);
 ioctl_read_buf!(ui_get_sysname, UINPUT_IOCTL_BASE, 44, u8);
+ioctl_read!(ui_get_version, UINPUT_IOCTL_BASE, 45, u32);
 
 ioctl_write_int!(ui_set_evbit, UINPUT_IOCTL_BASE, 100);
 ioctl_write_int!(ui_set_keybit, UINPUT_IOCTL_BASE, 101);
@@ -54,6 +61,11 @@ ioctl_write_buf!(ui_set_phys, UINPUT_IOCTL_BASE, 108, u8);
 ioctl_write_int!(ui_set_swbit, UINPUT_IOCTL_BASE, 109);
 ioctl_write_int!(ui_set_propbit, UINPUT_IOCTL_BASE, 110);
 
+ioctl_readwrite!(ui_begin_ff_upload, UINPUT_IOCTL_BASE, 200, uinput_ff_upload);
+ioctl_write_ptr!(ui_end_ff_upload, UINPUT_IOCTL_BASE, 201, uinput_ff_upload);
+ioctl_readwrite!(ui_begin_ff_erase, UINPUT_IOCTL_BASE, 202, uinput_ff_erase);
+ioctl_write_ptr!(ui_end_ff_erase, UINPUT_IOCTL_BASE, 203, uinput_ff_erase);
+
 macro_rules! eviocgbit_ioctl {
     ($mac:ident!($name:ident, $ev:ident, $ty:ty)) => {
         eviocgbit_ioctl!($mac!($name, $crate::EventType::$ev.0, $ty));
@@ -76,6 +88,22 @@ eviocgbit_ioctl!(ioctl_read_buf!(eviocgbit_ff, FORCEFEEDBACK, u8));
 eviocgbit_ioctl!(ioctl_read_buf!(eviocgbit_power, POWER, u8));
 eviocgbit_ioctl!(ioctl_read_buf!(eviocgbit_ffstatus, FORCEFEEDBACKSTATUS, u8));
 
+/// ioctl: "get event bits"
+///
+/// Like the `eviocgbit_*` functions, for an event type only known at runtime; 0 gets the
+/// supported event types.
+///
+/// # Safety
+///
+/// `fd` must be a valid file descriptor.
+pub unsafe fn eviocgbit(fd: ::libc::c_int, ev: u32, buf: &mut [u8]) -> ::nix::Result<c_int> {
+    convert_ioctl_res!(::nix::libc::ioctl(
+        fd,
+        request_code_read!(b'E', 0x20 + ev, buf.len()),
+        buf.as_mut_ptr()
+    ))
+}
+
 /// ioctl: "get abs value/limits"
 ///
 /// `abs` should be one of the "Absolute axes" values defined in the Linux kernel headers.
@@ -102,3 +130,52 @@ pub unsafe fn eviocgabs(
         buf as *mut input_absinfo
     ))
 }
+
+/// ioctl: "set abs value/limits"
+///
+/// # Panics
+///
+/// Calling this with a value greater than the kernel-defined `ABS_MAX` (typically 0x3f) will panic.
+///
+/// # Safety
+///
+/// 'abs' must be a valid axis number and supported by the device, otherwise the behavior is
+/// undefined.
+pub unsafe fn eviocsabs(fd: ::libc::c_int, abs: u32, buf: &input_absinfo) -> ::nix::Result<c_int> {
+    assert!(abs <= 0x3f);
+    convert_ioctl_res!(::nix::libc::ioctl(
+        fd,
+        request_code_write!(b'E', 0xc0 + abs, ::std::mem::size_of::<input_absinfo>()),
+        buf as *const input_absinfo
+    ))
+}
+
+/// ioctl: "send a force effect to a force feedback device"
+///
+/// The ioctl is declared as write-only, but the kernel writes the id of the uploaded effect back
+/// into `effect.id` when it was -1.
+///
+/// # Safety
+///
+/// `fd` must be a valid file descriptor and `effect` a fully initialized effect.
+pub unsafe fn eviocsff(fd: ::libc::c_int, effect: &mut ff_effect) -> ::nix::Result<c_int> {
+    convert_ioctl_res!(::nix::libc::ioctl(
+        fd,
+        request_code_write!(b'E', 0x80, ::std::mem::size_of::<ff_effect>()),
+        effect as *mut ff_effect
+    ))
+}
+
+/// `struct kbentry` from `linux/kd.h`, an entry of the console keymap.
+#[cfg(feature = "console")]
+#[repr(C)]
+#[derive(Default)]
+#[allow(non_camel_case_types)]
+pub struct kbentry {
+    pub kb_table: u8,
+    pub kb_index: u8,
+    pub kb_value: u16,
+}
+
+#[cfg(feature = "console")]
+nix::ioctl_readwrite_bad!(kdgkbent, 0x4B46, kbentry);
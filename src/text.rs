@@ -0,0 +1,118 @@
+//! Typing text as key taps, assuming a US keyboard layout.
+
+use crate::{AttributeSet, EventType, InputEvent, Key};
+
+const LETTERS: [Key; 26] = [
+    Key::KEY_A,
+    Key::KEY_B,
+    Key::KEY_C,
+    Key::KEY_D,
+    Key::KEY_E,
+    Key::KEY_F,
+    Key::KEY_G,
+    Key::KEY_H,
+    Key::KEY_I,
+    Key::KEY_J,
+    Key::KEY_K,
+    Key::KEY_L,
+    Key::KEY_M,
+    Key::KEY_N,
+    Key::KEY_O,
+    Key::KEY_P,
+    Key::KEY_Q,
+    Key::KEY_R,
+    Key::KEY_S,
+    Key::KEY_T,
+    Key::KEY_U,
+    Key::KEY_V,
+    Key::KEY_W,
+    Key::KEY_X,
+    Key::KEY_Y,
+    Key::KEY_Z,
+];
+
+const DIGITS: [Key; 10] = [
+    Key::KEY_0,
+    Key::KEY_1,
+    Key::KEY_2,
+    Key::KEY_3,
+    Key::KEY_4,
+    Key::KEY_5,
+    Key::KEY_6,
+    Key::KEY_7,
+    Key::KEY_8,
+    Key::KEY_9,
+];
+
+/// Punctuation, with the character typed without and with shift.
+const SYMBOLS: [(Key, char, char); 11] = [
+    (Key::KEY_MINUS, '-', '_'),
+    (Key::KEY_EQUAL, '=', '+'),
+    (Key::KEY_LEFTBRACE, '[', '{'),
+    (Key::KEY_RIGHTBRACE, ']', '}'),
+    (Key::KEY_BACKSLASH, '\\', '|'),
+    (Key::KEY_SEMICOLON, ';', ':'),
+    (Key::KEY_APOSTROPHE, '\'', '"'),
+    (Key::KEY_GRAVE, '`', '~'),
+    (Key::KEY_COMMA, ',', '<'),
+    (Key::KEY_DOT, '.', '>'),
+    (Key::KEY_SLASH, '/', '?'),
+];
+
+const SHIFTED_DIGITS: [char; 10] = [')', '!', '@', '#', '$', '%', '^', '&', '*', '('];
+
+/// Returns the key typing `c` on a US layout, and whether shift must be held.
+pub(crate) fn key_for_char(c: char) -> Option<(Key, bool)> {
+    Some(match c {
+        'a'..='z' => (LETTERS[c as usize - 'a' as usize], false),
+        'A'..='Z' => (LETTERS[c as usize - 'A' as usize], true),
+        '0'..='9' => (DIGITS[c as usize - '0' as usize], false),
+        ' ' => (Key::KEY_SPACE, false),
+        '\n' => (Key::KEY_ENTER, false),
+        '\t' => (Key::KEY_TAB, false),
+        _ => {
+            if let Some(digit) = SHIFTED_DIGITS.iter().position(|&s| s == c) {
+                return Some((DIGITS[digit], true));
+            }
+            let &(key, plain, _) = SYMBOLS
+                .iter()
+                .find(|&&(_, plain, shifted)| c == plain || c == shifted)?;
+            (key, c != plain)
+        }
+    })
+}
+
+/// Returns the keys needed to type any text [`key_for_char`] supports.
+pub(crate) fn text_keys() -> AttributeSet<Key> {
+    let mut keys: AttributeSet<Key> = LETTERS.into_iter().chain(DIGITS).collect();
+    for (key, _, _) in SYMBOLS {
+        keys.insert(key);
+    }
+    for key in [
+        Key::KEY_SPACE,
+        Key::KEY_ENTER,
+        Key::KEY_TAB,
+        Key::KEY_LEFTSHIFT,
+    ] {
+        keys.insert(key);
+    }
+    keys
+}
+
+/// Returns the frames, without their `SYN_REPORT`s, tapping the keys for `text`. Fails with
+/// the first character that can't be typed.
+pub(crate) fn text_frames(text: &str) -> Result<Vec<Vec<InputEvent>>, char> {
+    let key = |key: Key, value| InputEvent::new(EventType::KEY, key.code(), value);
+    let mut frames = Vec::new();
+    for c in text.chars() {
+        let (k, shift) = key_for_char(c).ok_or(c)?;
+        if shift {
+            frames.push(vec![key(Key::KEY_LEFTSHIFT, 1), key(k, 1)]);
+            frames.push(vec![key(k, 0), key(Key::KEY_LEFTSHIFT, 0)]);
+        } else {
+            frames.push(vec![key(k, 1)]);
+            frames.push(vec![key(k, 0)]);
+        }
+    }
+    Ok(frames)
+}
@@ -0,0 +1,21 @@
+//! Internal instrumentation, compiled out unless the `tracing` feature is enabled.
+//!
+//! With the feature on, reads, emits, grabs, resyncs and failed ioctls are reported through
+//! the [`tracing`](https://docs.rs/tracing) facade under the `evdev` target.
+
+/// Emit a `tracing` event at the given level; a no-op without the `tracing` feature.
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing_0::$level!(target: "evdev", $($arg)+);
+    };
+}
+
+/// Enter a debug-level span for the rest of the enclosing block; a no-op without the `tracing`
+/// feature.
+macro_rules! trace_span {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing_0::debug_span!(target: "evdev", $($arg)+).entered();
+    };
+}
@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use crate::transform::{is_syn_report, EventTransform};
+use crate::{EventType, InputEvent, RelativeAxisType};
+
+/// How far back pointer velocity is measured, in milliseconds.
+const VELOCITY_WINDOW_MS: f64 = 300.0;
+/// The most motion frames pointer velocity is measured over.
+const VELOCITY_FRAMES: usize = 16;
+
+/// A pointer acceleration profile, following libinput's.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AccelProfile {
+    /// Motion is scaled by a constant factor set by the speed, and never accelerated.
+    Flat,
+    /// Slow motion is slowed down further for precision, and fast motion sped up, up to a
+    /// maximum. The speed moves the point where acceleration starts, how quickly it ramps up,
+    /// and the maximum.
+    Adaptive,
+}
+
+/// Accelerates relative `REL_X`/`REL_Y` pointer motion.
+///
+/// Motion is first normalized to a 1000 DPI mouse, so mice of different resolutions move the
+/// pointer the same distance for the same hand movement; set the mouse's resolution with
+/// [`dpi`](Self::dpi). It is then scaled according to the [`AccelProfile`] and the
+/// [`speed`](Self::speed), which work like libinput's profiles and speed setting. Velocity is
+/// measured from the event timestamps, over the motion of the last 300 ms.
+///
+/// Other events are passed through unchanged.
+#[derive(Debug, Clone)]
+pub struct PointerAccel {
+    profile: AccelProfile,
+    speed: f32,
+    dpi: u32,
+    /// Recent motion frames, with their distance in normalized units.
+    trackers: VecDeque<(SystemTime, f64)>,
+    last_velocity: f64,
+    remainder: (f64, f64),
+    rest: Vec<InputEvent>,
+}
+
+impl PointerAccel {
+    pub fn new(profile: AccelProfile) -> Self {
+        PointerAccel {
+            profile,
+            speed: 0.0,
+            dpi: 1000,
+            trackers: VecDeque::with_capacity(VELOCITY_FRAMES),
+            last_velocity: 0.0,
+            remainder: (0.0, 0.0),
+            rest: Vec::new(),
+        }
+    }
+
+    /// Set the speed, from -1.0 (slowest) to 1.0 (fastest). Defaults to 0.0.
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed.clamp(-1.0, 1.0);
+        self
+    }
+
+    /// Set the resolution of the mouse, in dots per inch. Defaults to 1000.
+    ///
+    /// Mice don't report their resolution; libinput keeps a database of them in its hwdb,
+    /// as `MOUSE_DPI`.
+    pub fn dpi(mut self, dpi: u32) -> Self {
+        self.dpi = dpi.max(1);
+        self
+    }
+
+    pub fn profile(&self) -> AccelProfile {
+        self.profile
+    }
+
+    /// Returns the factor motion is scaled by at `velocity`, in units per millisecond of a 1000
+    /// DPI mouse.
+    pub fn factor(&self, velocity: f64) -> f64 {
+        let speed = f64::from(self.speed);
+        match self.profile {
+            AccelProfile::Flat => (1.0 + speed).max(0.005),
+            AccelProfile::Adaptive => {
+                let threshold = (0.4 - 0.25 * speed).max(0.2);
+                let max_accel = 2.0 + 1.5 * speed;
+                let incline = 1.1 + 0.75 * speed;
+                let factor = if velocity < 0.07 {
+                    10.0 * velocity + 0.3
+                } else if velocity < threshold {
+                    1.0
+                } else {
+                    incline * (velocity - threshold) + 1.0
+                };
+                factor.min(max_accel)
+            }
+        }
+    }
+
+    /// Measure the velocity up to a frame at `time` moving `distance`, in units per
+    /// millisecond. Motion after a pause starts from rest.
+    fn velocity(&mut self, time: SystemTime, distance: f64) -> f64 {
+        let age_ms = |t: SystemTime| {
+            time.duration_since(t)
+                .map_or(0.0, |age| age.as_secs_f64() * 1000.0)
+        };
+        self.trackers
+            .retain(|&(t, _)| age_ms(t) <= VELOCITY_WINDOW_MS);
+        let velocity = match self.trackers.front() {
+            Some(&(oldest, _)) => {
+                let moved: f64 = self.trackers.iter().skip(1).map(|&(_, d)| d).sum();
+                (moved + distance) / age_ms(oldest).max(1.0)
+            }
+            None => {
+                self.last_velocity = 0.0;
+                0.0
+            }
+        };
+        if self.trackers.len() == VELOCITY_FRAMES {
+            self.trackers.pop_front();
+        }
+        self.trackers.push_back((time, distance));
+        velocity
+    }
+}
+
+impl EventTransform for PointerAccel {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let (mut dx, mut dy) = (0, 0);
+        self.rest.clear();
+        for ev in frame.iter().filter(|ev| !is_syn_report(ev)) {
+            match (ev.event_type(), RelativeAxisType(ev.code())) {
+                (EventType::RELATIVE, RelativeAxisType::REL_X) => dx += ev.value(),
+                (EventType::RELATIVE, RelativeAxisType::REL_Y) => dy += ev.value(),
+                _ => self.rest.push(*ev),
+            }
+        }
+        if dx == 0 && dy == 0 {
+            out.extend_from_slice(frame);
+            return;
+        }
+        let syn = *frame.last().unwrap();
+        let scale = 1000.0 / f64::from(self.dpi);
+        let (dx, dy) = (f64::from(dx) * scale, f64::from(dy) * scale);
+        let velocity = self.velocity(syn.timestamp(), dx.hypot(dy));
+        let factor = match self.profile {
+            AccelProfile::Flat => self.factor(velocity),
+            // Averaged over the change in velocity since the last frame, with Simpson's rule
+            AccelProfile::Adaptive => {
+                let last = self.last_velocity;
+                (self.factor(last)
+                    + 4.0 * self.factor((last + velocity) / 2.0)
+                    + self.factor(velocity))
+                    / 6.0
+            }
+        };
+        self.last_velocity = velocity;
+
+        let rx = dx * factor + self.remainder.0;
+        let ry = dy * factor + self.remainder.1;
+        let (ix, iy) = (rx.trunc(), ry.trunc());
+        self.remainder = (rx - ix, ry - iy);
+        let start = out.len();
+        for (axis, value) in [(RelativeAxisType::REL_X, ix), (RelativeAxisType::REL_Y, iy)] {
+            if value != 0.0 {
+                out.push(InputEvent(libc::input_event {
+                    type_: EventType::RELATIVE.0,
+                    code: axis.0,
+                    value: value as i32,
+                    ..syn.0
+                }));
+            }
+        }
+        out.extend_from_slice(&self.rest);
+        if out.len() > start {
+            out.push(syn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn flat_and_adaptive() {
+        let frame = |ms, dx| {
+            let time =
+                crate::systime_to_timeval(&(SystemTime::UNIX_EPOCH + Duration::from_millis(ms)));
+            let mut motion = InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_X.0, dx);
+            let mut syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+            motion.0.time = time;
+            syn.0.time = time;
+            [motion, syn]
+        };
+        let moved = |accel: &mut PointerAccel, frames: &[[InputEvent; 2]]| {
+            let mut out = Vec::new();
+            for f in frames {
+                accel.process(f, &mut out);
+            }
+            out.iter()
+                .filter(|ev| ev.event_type() == EventType::RELATIVE)
+                .map(|ev| ev.value())
+                .sum::<i32>()
+        };
+
+        // A 2000 DPI mouse moves twice as many units for the same distance
+        let mut flat = PointerAccel::new(AccelProfile::Flat).dpi(2000);
+        assert_eq!(
+            moved(&mut flat, &[frame(0, 10), frame(8, 10), frame(16, 10)]),
+            15
+        );
+
+        let slow: Vec<_> = (0..10).map(|i| frame(i * 8, 1)).collect();
+        let fast: Vec<_> = (0..10).map(|i| frame(i * 8, 40)).collect();
+        let mut adaptive = PointerAccel::new(AccelProfile::Adaptive);
+        assert!(moved(&mut adaptive, &slow) < 10);
+        let mut adaptive = PointerAccel::new(AccelProfile::Adaptive);
+        assert!(moved(&mut adaptive, &fast) > 400);
+    }
+}
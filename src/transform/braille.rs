@@ -0,0 +1,237 @@
+use std::io;
+
+use crate::text::{text_frames, text_keys};
+use crate::transform::{is_syn_report, EventTransform};
+use crate::uinput::VirtualDeviceBuilder;
+use crate::{AttributeSet, EventType, InputEvent, Key};
+
+/// The characters of North American computer braille, indexed by the dots of the cell with
+/// dot 1 as the lowest bit.
+const COMPUTER_BRAILLE: &[u8; 64] =
+    b" A1B'K2L@CIF/MSP\"E3H9O6R^DJG>NTQ,*5<-U8V.%[$+X!&;:4\\0Z7(_?W]#Y)=";
+
+/// The keys of a Perkins-style layout on a QWERTY keyboard, for dots 1 to 8.
+const PERKINS_KEYS: [Key; 8] = [
+    Key::KEY_F,
+    Key::KEY_D,
+    Key::KEY_S,
+    Key::KEY_J,
+    Key::KEY_K,
+    Key::KEY_L,
+    Key::KEY_A,
+    Key::KEY_SEMICOLON,
+];
+
+/// What [`BrailleChords`] types for a cell.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BrailleOutput {
+    /// The Unicode braille pattern, e.g. `⠓` for dots 1-2-5. It is entered with the
+    /// `Ctrl+Shift+U` hex code sequence, which GTK and IBus understand.
+    Unicode,
+    /// The character of the cell in computer braille, e.g. `h` for dots 1-2-5. Letters are
+    /// capitals when dot 7 is part of the chord.
+    Text,
+}
+
+/// Typing braille with chords on a standard keyboard, as on a Perkins brailler.
+///
+/// Each of `F`, `D`, `S`, `J`, `K` and `L` is a dot of the six-dot cell, with `A` and `;` as
+/// dots 7 and 8. Pressing keys together forms a cell, which is typed once they are all
+/// released; space on its own types a space. Other keys are passed through, so backspace and
+/// enter work as usual.
+///
+/// The output is typed with a US keyboard layout. The output device needs the keys set up by
+/// [`configure_output`](Self::configure_output).
+#[derive(Debug, Clone)]
+pub struct BrailleChords {
+    output: BrailleOutput,
+    dot_keys: [Key; 8],
+    dots: u8,
+    space: bool,
+    held: AttributeSet<Key>,
+    frame: Vec<InputEvent>,
+}
+
+impl BrailleChords {
+    pub fn new(output: BrailleOutput) -> Self {
+        BrailleChords {
+            output,
+            dot_keys: PERKINS_KEYS,
+            dots: 0,
+            space: false,
+            held: AttributeSet::new(),
+            frame: Vec::new(),
+        }
+    }
+
+    /// Use `key` for `dot`, from 1 to 8.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dot` isn't between 1 and 8.
+    pub fn dot_key(mut self, dot: u8, key: Key) -> Self {
+        assert!((1..=8).contains(&dot), "braille dots are numbered 1 to 8");
+        self.dot_keys[dot as usize - 1] = key;
+        self
+    }
+
+    /// Enable the keys on `builder` that this transform types with.
+    pub fn configure_output<'a>(
+        &self,
+        builder: VirtualDeviceBuilder<'a>,
+    ) -> io::Result<VirtualDeviceBuilder<'a>> {
+        let mut keys = text_keys();
+        keys.insert(Key::KEY_LEFTCTRL);
+        builder.with_keys(&keys)
+    }
+
+    /// Returns the dots pressed so far in the current chord, with dot 1 as the lowest bit.
+    pub fn pending_dots(&self) -> u8 {
+        self.dots
+    }
+
+    /// Returns the text typed for a chord of `dots`, or `None` if it types nothing.
+    pub fn translate(&self, dots: u8) -> Option<String> {
+        match self.output {
+            BrailleOutput::Unicode => {
+                let c = char::from_u32(0x2800 + u32::from(dots))?;
+                Some(c.to_string())
+            }
+            BrailleOutput::Text => {
+                let c = COMPUTER_BRAILLE[usize::from(dots & 0x3f)] as char;
+                let capital = dots & 0x40 != 0;
+                Some(if capital { c } else { c.to_ascii_lowercase() }.to_string())
+            }
+        }
+    }
+
+    /// Append the frames typing `dots`, with the timestamp of `ev`.
+    fn type_cell(&mut self, dots: u8, ev: &InputEvent) {
+        let frames = match (self.output, self.translate(dots)) {
+            (_, None) => return,
+            (BrailleOutput::Unicode, Some(text)) => {
+                let key = |key: Key, value| InputEvent::new(EventType::KEY, key.code(), value);
+                let (ctrl, shift, u) = (Key::KEY_LEFTCTRL, Key::KEY_LEFTSHIFT, Key::KEY_U);
+                let mut frames = vec![
+                    vec![key(ctrl, 1), key(shift, 1), key(u, 1)],
+                    vec![key(u, 0), key(shift, 0), key(ctrl, 0)],
+                ];
+                let code = text.chars().next().map_or(0, u32::from);
+                frames.extend(text_frames(&format!("{:x} ", code)).unwrap_or_default());
+                frames
+            }
+            (BrailleOutput::Text, Some(text)) => text_frames(&text).unwrap_or_default(),
+        };
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        for ev_out in frames
+            .into_iter()
+            .flat_map(|frame| frame.into_iter().chain([syn]))
+        {
+            self.frame.push(InputEvent(libc::input_event {
+                time: ev.0.time,
+                ..ev_out.0
+            }));
+        }
+    }
+}
+
+impl EventTransform for BrailleChords {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let Some(syn) = frame.last().filter(|ev| is_syn_report(ev)) else {
+            return;
+        };
+        self.frame.clear();
+        for ev in &frame[..frame.len() - 1] {
+            let key = Key::new(ev.code());
+            let dot = self.dot_keys.iter().position(|&k| k == key);
+            let chord_key = dot.is_some() || key == Key::KEY_SPACE;
+            if ev.event_type() != EventType::KEY || !chord_key {
+                self.frame.push(*ev);
+                continue;
+            }
+            match ev.value() {
+                1 => {
+                    self.held.insert(key);
+                    match dot {
+                        Some(dot) => self.dots |= 1 << dot,
+                        None => self.space = true,
+                    }
+                }
+                0 if self.held.contains(key) => {
+                    self.held.remove(key);
+                    if self.held.iter().next().is_none() {
+                        let (dots, space) = (self.dots, self.space);
+                        self.dots = 0;
+                        self.space = false;
+                        if dots != 0 {
+                            if !self.frame.is_empty() {
+                                self.frame.push(*syn);
+                            }
+                            self.type_cell(dots, ev);
+                        } else if space {
+                            self.frame.extend([
+                                InputEvent(libc::input_event { value: 1, ..ev.0 }),
+                                *syn,
+                                *ev,
+                                *syn,
+                            ]);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Typed cells end with their own SYN_REPORT
+        if self.frame.last().is_some_and(|ev| !is_syn_report(ev)) {
+            self.frame.push(*syn);
+        }
+        out.extend_from_slice(&self.frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(braille: &mut BrailleChords, keys: &[Key]) -> String {
+        let mut out = Vec::new();
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        for value in [1, 0] {
+            for key in keys {
+                let ev = InputEvent::new(EventType::KEY, key.code(), value);
+                braille.process(&[ev, syn], &mut out);
+            }
+        }
+        let mut shift = false;
+        let mut text = String::new();
+        for ev in out.iter().filter(|ev| ev.event_type() == EventType::KEY) {
+            let key = Key::new(ev.code());
+            if key == Key::KEY_LEFTSHIFT {
+                shift = ev.value() == 1;
+            } else if ev.value() == 1 {
+                text.extend(
+                    (' '..='~').filter(|&c| crate::text::key_for_char(c) == Some((key, shift))),
+                );
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn type_cells() {
+        let mut braille = BrailleChords::new(BrailleOutput::Text);
+        // Dots 1-2-5
+        assert_eq!(
+            chord(&mut braille, &[Key::KEY_F, Key::KEY_D, Key::KEY_K]),
+            "h"
+        );
+        assert_eq!(chord(&mut braille, &[Key::KEY_A, Key::KEY_F]), "A");
+        assert_eq!(chord(&mut braille, &[Key::KEY_SPACE]), " ");
+
+        let mut braille = BrailleChords::new(BrailleOutput::Unicode);
+        assert_eq!(
+            chord(&mut braille, &[Key::KEY_F, Key::KEY_D, Key::KEY_K]),
+            "U2813 "
+        );
+    }
+}
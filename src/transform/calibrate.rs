@@ -0,0 +1,375 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::transform::EventTransform;
+use crate::{AbsInfo, AbsoluteAxisType, Device, EventType, InputEvent};
+
+/// The measured behavior of one axis: the raw values it reports at either end and at rest,
+/// and how far from rest it wanders without being touched.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AxisCalibration {
+    pub min: i32,
+    pub center: i32,
+    pub max: i32,
+    /// Raw values within this distance of `center` are treated as the center.
+    pub deadzone: i32,
+}
+
+impl AxisCalibration {
+    /// The calibration the kernel claims: the advertised range, centered, with the flat as
+    /// dead zone.
+    pub fn from_abs_info(info: &AbsInfo) -> Self {
+        AxisCalibration {
+            min: info.minimum(),
+            center: info.center(),
+            max: info.maximum(),
+            deadzone: info.flat(),
+        }
+    }
+
+    /// Map a raw value onto the range of `out`, so that `min`, `center` and `max` land on its
+    /// minimum, midpoint and maximum.
+    pub fn apply(&self, raw: i32, out: &AbsInfo) -> i32 {
+        let (out_min, out_max) = (i64::from(out.minimum()), i64::from(out.maximum()));
+        let out_center = i64::from(out.center());
+        let raw = i64::from(raw.clamp(self.min, self.max));
+        let (min, center, max) = (
+            i64::from(self.min),
+            i64::from(self.center),
+            i64::from(self.max),
+        );
+        let deadzone = i64::from(self.deadzone.max(0));
+
+        let value = if (raw - center).abs() <= deadzone {
+            out_center
+        } else if raw < center {
+            let span = center - deadzone - min;
+            if span <= 0 {
+                out_min
+            } else {
+                out_center - (center - deadzone - raw) * (out_center - out_min) / span
+            }
+        } else {
+            let span = max - (center + deadzone);
+            if span <= 0 {
+                out_max
+            } else {
+                out_center + (raw - center - deadzone) * (out_max - out_center) / span
+            }
+        };
+        value as i32
+    }
+}
+
+fn midpoint(a: i32, b: i32) -> i32 {
+    ((i64::from(a) + i64::from(b)) / 2) as i32
+}
+
+/// Calibrations for the axes of a device.
+///
+/// Calibrations are saved as text, one axis per line:
+///
+/// ```text
+/// # axis min center max deadzone
+/// ABS_X 12 131 250 4
+/// ABS_Y 8 127 247 4
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Calibration {
+    axes: Vec<(AbsoluteAxisType, AxisCalibration)>,
+}
+
+impl Calibration {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the calibration of `axis`, replacing any previous one.
+    pub fn set(&mut self, axis: AbsoluteAxisType, calibration: AxisCalibration) {
+        match self.axes.iter_mut().find(|(a, _)| *a == axis) {
+            Some((_, c)) => *c = calibration,
+            None => self.axes.push((axis, calibration)),
+        }
+    }
+
+    pub fn get(&self, axis: AbsoluteAxisType) -> Option<&AxisCalibration> {
+        self.axes.iter().find(|(a, _)| *a == axis).map(|(_, c)| c)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (AbsoluteAxisType, &AxisCalibration)> + '_ {
+        self.axes.iter().map(|(a, c)| (*a, c))
+    }
+
+    /// Read a calibration saved with [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Calibration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# axis min center max deadzone")?;
+        for (axis, c) in &self.axes {
+            writeln!(
+                f,
+                "{:?} {} {} {} {}",
+                axis, c.min, c.center, c.max, c.deadzone
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Calibration {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let mut calibration = Calibration::new();
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid calibration line: {}", line),
+                )
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (axis, values) = match fields.as_slice() {
+                [axis, values @ ..] if values.len() == 4 => (axis, values),
+                _ => return Err(invalid()),
+            };
+            let axis: AbsoluteAxisType = axis.parse().map_err(|_| invalid())?;
+            let mut numbers = [0; 4];
+            for (n, v) in numbers.iter_mut().zip(values) {
+                *n = v.parse().map_err(|_| invalid())?;
+            }
+            let [min, center, max, deadzone] = numbers;
+            calibration.set(
+                axis,
+                AxisCalibration {
+                    min,
+                    center,
+                    max,
+                    deadzone,
+                },
+            );
+        }
+        Ok(calibration)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CapturedAxis {
+    axis: AbsoluteAxisType,
+    /// The range seen while the axis was at rest.
+    rest: Option<(i32, i32)>,
+    /// The full range seen.
+    range: Option<(i32, i32)>,
+}
+
+fn widen(range: &mut Option<(i32, i32)>, value: i32) {
+    *range = Some(match *range {
+        Some((min, max)) => (min.min(value), max.max(value)),
+        None => (value, value),
+    });
+}
+
+/// Measures a calibration from live events.
+///
+/// Calibrating interactively takes two steps: with the controls untouched, feed events to
+/// [`capture_center`](Self::capture_center) for a second or so to find where each axis rests
+/// and how much it jitters; then ask the user to move every control to its extremes, feeding
+/// events to [`capture_range`](Self::capture_range). [`finish`](Self::finish) produces the
+/// calibration.
+#[derive(Debug, Clone, Default)]
+pub struct CalibrationCapture {
+    axes: Vec<CapturedAxis>,
+}
+
+impl CalibrationCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from the current positions of the absolute axes of `device`, taken as resting
+    /// positions, since idle axes don't send events.
+    pub fn from_device(device: &Device) -> Self {
+        let mut capture = Self::new();
+        let axes = device
+            .supported_absolute_axes()
+            .into_iter()
+            .flat_map(|a| a.iter());
+        for axis in axes.filter(|a| a.0 < AbsoluteAxisType::ABS_MT_SLOT.0) {
+            if let Some(info) = device.abs_info(axis) {
+                capture.record(axis, info.value(), true);
+            }
+        }
+        capture
+    }
+
+    fn record(&mut self, axis: AbsoluteAxisType, value: i32, at_rest: bool) {
+        let pos = match self.axes.iter().position(|a| a.axis == axis) {
+            Some(pos) => pos,
+            None => {
+                self.axes.push(CapturedAxis {
+                    axis,
+                    rest: None,
+                    range: None,
+                });
+                self.axes.len() - 1
+            }
+        };
+        let captured = &mut self.axes[pos];
+        if at_rest {
+            widen(&mut captured.rest, value);
+        }
+        widen(&mut captured.range, value);
+    }
+
+    /// Record an event while the controls are at rest.
+    pub fn capture_center(&mut self, ev: &InputEvent) {
+        if ev.event_type() == EventType::ABSOLUTE {
+            self.record(AbsoluteAxisType(ev.code()), ev.value(), true);
+        }
+    }
+
+    /// Record an event while the controls are moved through their range.
+    pub fn capture_range(&mut self, ev: &InputEvent) {
+        if ev.event_type() == EventType::ABSOLUTE {
+            self.record(AbsoluteAxisType(ev.code()), ev.value(), false);
+        }
+    }
+
+    /// Build the calibration from what was captured. Axes that never moved are left out.
+    ///
+    /// Axes without a resting position, such as triggers that were only captured while
+    /// moving, are centered in their range.
+    pub fn finish(&self) -> Calibration {
+        let mut calibration = Calibration::new();
+        for captured in &self.axes {
+            let (min, max) = match captured.range {
+                Some((min, max)) if min < max => (min, max),
+                _ => continue,
+            };
+            let (center, deadzone) = match captured.rest {
+                Some((lo, hi)) => (midpoint(lo, hi), (hi - lo + 1) / 2),
+                None => (midpoint(min, max), 0),
+            };
+            calibration.set(
+                captured.axis,
+                AxisCalibration {
+                    min,
+                    center,
+                    max,
+                    deadzone,
+                },
+            );
+        }
+        calibration
+    }
+}
+
+/// Applies a [`Calibration`], rescaling calibrated axes onto their advertised ranges.
+#[derive(Debug, Clone)]
+pub struct CalibrateTransform {
+    axes: Vec<(AbsoluteAxisType, AxisCalibration, AbsInfo)>,
+}
+
+impl CalibrateTransform {
+    /// Create a transform mapping each calibrated axis onto the range given for it in
+    /// `ranges`. Calibrated axes without a range are left alone.
+    pub fn new(
+        calibration: &Calibration,
+        ranges: impl IntoIterator<Item = (AbsoluteAxisType, AbsInfo)>,
+    ) -> Self {
+        let axes = ranges
+            .into_iter()
+            .filter_map(|(axis, range)| Some((axis, *calibration.get(axis)?, range)))
+            .collect();
+        CalibrateTransform { axes }
+    }
+
+    /// Create a transform mapping each calibrated axis onto the range `device` advertises for
+    /// it.
+    pub fn from_device(calibration: &Calibration, device: &Device) -> Self {
+        let ranges = calibration
+            .iter()
+            .filter_map(|(axis, _)| Some((axis, device.abs_info(axis)?)));
+        Self::new(calibration, ranges)
+    }
+
+    /// Calibrate a single event. Events on other axes are returned unchanged.
+    pub fn calibrate_event(&self, ev: InputEvent) -> InputEvent {
+        if ev.event_type() != EventType::ABSOLUTE {
+            return ev;
+        }
+        match self.axes.iter().find(|(axis, ..)| axis.0 == ev.code()) {
+            Some((_, calibration, range)) => InputEvent(libc::input_event {
+                value: calibration.apply(ev.value(), range),
+                ..ev.0
+            }),
+            None => ev,
+        }
+    }
+}
+
+impl EventTransform for CalibrateTransform {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        out.extend(frame.iter().map(|&ev| self.calibrate_event(ev)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abs(axis: AbsoluteAxisType, value: i32) -> InputEvent {
+        InputEvent::new(EventType::ABSOLUTE, axis.0, value)
+    }
+
+    #[test]
+    fn capture_and_apply() {
+        // A stick that claims 0..=255 but rests at 140 and only reaches 20..=230
+        let mut capture = CalibrationCapture::new();
+        for value in [138, 142, 140] {
+            capture.capture_center(&abs(AbsoluteAxisType::ABS_X, value));
+        }
+        for value in [20, 230, 100] {
+            capture.capture_range(&abs(AbsoluteAxisType::ABS_X, value));
+        }
+        let calibration = capture.finish();
+        let expected = AxisCalibration {
+            min: 20,
+            center: 140,
+            max: 230,
+            deadzone: 2,
+        };
+        assert_eq!(calibration.get(AbsoluteAxisType::ABS_X), Some(&expected));
+        assert_eq!(
+            calibration.to_string().parse::<Calibration>().unwrap(),
+            calibration
+        );
+
+        let range = AbsInfo::new(0, 0, 255, 0, 0, 0);
+        let transform = CalibrateTransform::new(&calibration, [(AbsoluteAxisType::ABS_X, range)]);
+        let apply = |v| {
+            transform
+                .calibrate_event(abs(AbsoluteAxisType::ABS_X, v))
+                .value()
+        };
+        assert_eq!(apply(141), 127);
+        assert_eq!(apply(20), 0);
+        assert_eq!(apply(10), 0);
+        assert_eq!(apply(230), 255);
+    }
+}
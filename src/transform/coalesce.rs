@@ -0,0 +1,115 @@
+use crate::transform::{is_syn_report, EventTransform};
+use crate::{EventType, InputEvent, RelativeAxisType};
+
+/// Merges relative `REL_X`/`REL_Y` motion into fewer events.
+///
+/// Repeated deltas within one frame are always summed. Frames containing nothing but pointer
+/// motion are additionally held back and merged with the following ones, up to `window`
+/// frames in total, so consumers that only care about aggregate motion process fewer frames.
+/// A frame with any other event flushes the held motion first, so clicks and scrolls still
+/// happen where the pointer was moved to.
+///
+/// Motion that is held back when input stops is emitted by [`flush`](Self::flush).
+#[derive(Debug, Clone)]
+pub struct CoalesceMotion {
+    window: usize,
+    held: usize,
+    dx: i32,
+    dy: i32,
+    syn: Option<InputEvent>,
+    rest: Vec<InputEvent>,
+}
+
+impl CoalesceMotion {
+    /// Merge motion over up to `window` frames. A window of 1 only merges within a frame.
+    pub fn new(window: usize) -> Self {
+        CoalesceMotion {
+            window: window.max(1),
+            held: 0,
+            dx: 0,
+            dy: 0,
+            syn: None,
+            rest: Vec::new(),
+        }
+    }
+
+    /// Emit any motion that is being held back as a single frame.
+    pub fn flush(&mut self, out: &mut Vec<InputEvent>) {
+        if let Some(syn) = self.syn.take() {
+            let rel = |axis: RelativeAxisType, value| {
+                InputEvent(libc::input_event {
+                    type_: EventType::RELATIVE.0,
+                    code: axis.0,
+                    value,
+                    ..syn.0
+                })
+            };
+            if self.dx != 0 {
+                out.push(rel(RelativeAxisType::REL_X, self.dx));
+            }
+            if self.dy != 0 {
+                out.push(rel(RelativeAxisType::REL_Y, self.dy));
+            }
+            if self.dx != 0 || self.dy != 0 {
+                out.push(syn);
+            }
+        }
+        self.held = 0;
+        self.dx = 0;
+        self.dy = 0;
+    }
+}
+
+impl EventTransform for CoalesceMotion {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        self.rest.clear();
+        for ev in frame {
+            match (ev.event_type(), RelativeAxisType(ev.code())) {
+                (EventType::RELATIVE, RelativeAxisType::REL_X) => self.dx += ev.value(),
+                (EventType::RELATIVE, RelativeAxisType::REL_Y) => self.dy += ev.value(),
+                _ if is_syn_report(ev) => self.syn = Some(*ev),
+                _ => self.rest.push(*ev),
+            }
+        }
+        self.held += 1;
+
+        if !self.rest.is_empty() {
+            self.flush(out);
+            out.extend_from_slice(&self.rest);
+            out.push(*frame.last().unwrap());
+        } else if self.held >= self.window {
+            self.flush(out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Key;
+
+    fn rel(axis: RelativeAxisType, value: i32) -> InputEvent {
+        InputEvent::new(EventType::RELATIVE, axis.0, value)
+    }
+
+    #[test]
+    fn merges_until_window_or_other_event() {
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        let mut coalesce = CoalesceMotion::new(3);
+        let mut out = Vec::new();
+
+        coalesce.process(&[rel(RelativeAxisType::REL_X, 2), syn], &mut out);
+        coalesce.process(&[rel(RelativeAxisType::REL_Y, 1), syn], &mut out);
+        assert!(out.is_empty());
+        coalesce.process(&[rel(RelativeAxisType::REL_X, 3), syn], &mut out);
+        let values: Vec<_> = out.iter().map(|ev| (ev.code(), ev.value())).collect();
+        assert_eq!(values, [(0, 5), (1, 1), (0, 0)]);
+
+        out.clear();
+        let click = InputEvent::new(EventType::KEY, Key::BTN_LEFT.code(), 1);
+        coalesce.process(&[rel(RelativeAxisType::REL_X, -1), syn], &mut out);
+        coalesce.process(&[rel(RelativeAxisType::REL_X, -1), click, syn], &mut out);
+        let values: Vec<_> = out.iter().map(|ev| (ev.code(), ev.value())).collect();
+        assert_eq!(values, [(0, -2), (0, 0), (Key::BTN_LEFT.code(), 1), (0, 0)]);
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::transform::{is_syn_report, EventTransform};
+use crate::{AttributeSet, EventType, InputEvent, Key};
+
+/// What a key filter did with a key, for audible or visual feedback.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyFeedback {
+    /// The key was pressed, and will register if it is held long enough.
+    Pending(Key),
+    /// The key was held long enough, and its press was forwarded.
+    Accepted(Key),
+    /// The key was released too early, or pressed again too soon, and was dropped.
+    Rejected(Key),
+}
+
+type FeedbackFn = Box<dyn FnMut(KeyFeedback) + Send>;
+
+fn key_event(key: Key, value: i32, time: SystemTime) -> InputEvent {
+    let mut ev = InputEvent::new(EventType::KEY, key.code(), value);
+    ev.0.time = crate::systime_to_timeval(&time);
+    ev
+}
+
+/// Ignores key presses that aren't held for a while, for users who brush keys by accident.
+///
+/// A press is only forwarded once the key has been held for the delay, and is dropped
+/// entirely if the key is released before then. Presses that become due between frames are
+/// emitted by [`tick`](Self::tick); [`next_deadline`](Self::next_deadline) tells when to call
+/// it.
+pub struct SlowKeys {
+    delay: Duration,
+    overrides: HashMap<Key, Duration>,
+    /// Keys held but not yet accepted, with the time they are accepted at.
+    pending: Vec<(Key, SystemTime)>,
+    accepted: AttributeSet<Key>,
+    feedback: Option<FeedbackFn>,
+    frame: Vec<InputEvent>,
+    clock: SharedClock,
+}
+
+impl fmt::Debug for SlowKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SlowKeys")
+            .field("delay", &self.delay)
+            .field("overrides", &self.overrides)
+            .field("pending", &self.pending)
+            .field("accepted", &self.accepted)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SlowKeys {
+    /// Only forward keys held for `delay`.
+    pub fn new(delay: Duration) -> Self {
+        SlowKeys {
+            delay,
+            overrides: HashMap::new(),
+            pending: Vec::new(),
+            accepted: AttributeSet::new(),
+            feedback: None,
+            frame: Vec::new(),
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Use a different delay for `key`. A delay of zero forwards it immediately.
+    pub fn delay_for(mut self, key: Key, delay: Duration) -> Self {
+        self.overrides.insert(key, delay);
+        self
+    }
+
+    /// Take the time from `clock`, e.g. a [`MockClock`](crate::clock::MockClock) in tests,
+    /// rather than from event timestamps and the system clock.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Call `f` whenever a key is pressed, accepted or rejected.
+    pub fn on_feedback(mut self, f: impl FnMut(KeyFeedback) + Send + 'static) -> Self {
+        self.feedback = Some(Box::new(f));
+        self
+    }
+
+    fn feedback(&mut self, feedback: KeyFeedback) {
+        if let Some(f) = &mut self.feedback {
+            f(feedback);
+        }
+    }
+
+    /// Returns the time the next held key will be accepted at.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        self.pending.iter().map(|&(_, deadline)| deadline).min()
+    }
+
+    /// Forward the presses of keys that have been held for their delay as of `now`, in a
+    /// single frame.
+    pub fn tick(&mut self, now: SystemTime, out: &mut Vec<InputEvent>) {
+        let start = out.len();
+        let mut i = 0;
+        while i < self.pending.len() {
+            let (key, deadline) = self.pending[i];
+            if deadline > now {
+                i += 1;
+                continue;
+            }
+            self.pending.remove(i);
+            self.accepted.insert(key);
+            out.push(key_event(key, 1, deadline));
+            self.feedback(KeyFeedback::Accepted(key));
+        }
+        if out.len() > start {
+            let mut syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+            syn.0.time = crate::systime_to_timeval(&now);
+            out.push(syn);
+        }
+    }
+
+    /// Like [`tick`](Self::tick), as of the clock's current time.
+    pub fn tick_now(&mut self, out: &mut Vec<InputEvent>) {
+        let now = self.clock.now();
+        self.tick(now, out)
+    }
+}
+
+impl EventTransform for SlowKeys {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        if let Some(syn) = frame.last() {
+            self.tick(self.clock.event_time(syn), out);
+        }
+        self.frame.clear();
+        for ev in frame {
+            if ev.event_type() != EventType::KEY {
+                self.frame.push(*ev);
+                continue;
+            }
+            let key = Key::new(ev.code());
+            let pending = self.pending.iter().position(|&(k, _)| k == key);
+            match (ev.value(), pending) {
+                (0, Some(i)) => {
+                    self.pending.remove(i);
+                    self.feedback(KeyFeedback::Rejected(key));
+                }
+                // Repeats of a key that isn't accepted yet
+                (2, Some(_)) => {}
+                (1, None) if !self.accepted.contains(key) => {
+                    let delay = self.overrides.get(&key).copied().unwrap_or(self.delay);
+                    if delay.is_zero() {
+                        self.accepted.insert(key);
+                        self.frame.push(*ev);
+                    } else {
+                        self.pending.push((key, self.clock.event_time(ev) + delay));
+                        self.feedback(KeyFeedback::Pending(key));
+                    }
+                }
+                (0, None) => {
+                    self.accepted.remove(key);
+                    self.frame.push(*ev);
+                }
+                _ => self.frame.push(*ev),
+            }
+        }
+        if self.frame.iter().any(|ev| !is_syn_report(ev)) {
+            out.extend_from_slice(&self.frame);
+        }
+    }
+}
+
+/// Ignores presses of a key shortly after it was released, for users whose fingers bounce.
+pub struct BounceKeys {
+    window: Duration,
+    overrides: HashMap<Key, Duration>,
+    last_release: HashMap<Key, SystemTime>,
+    /// Keys whose press was dropped, so their release is dropped too.
+    suppressed: AttributeSet<Key>,
+    feedback: Option<FeedbackFn>,
+    frame: Vec<InputEvent>,
+    clock: SharedClock,
+}
+
+impl fmt::Debug for BounceKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BounceKeys")
+            .field("window", &self.window)
+            .field("overrides", &self.overrides)
+            .field("suppressed", &self.suppressed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BounceKeys {
+    /// Drop presses of a key within `window` of its release.
+    pub fn new(window: Duration) -> Self {
+        BounceKeys {
+            window,
+            overrides: HashMap::new(),
+            last_release: HashMap::new(),
+            suppressed: AttributeSet::new(),
+            feedback: None,
+            frame: Vec::new(),
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Use a different window for `key`. A window of zero never drops it.
+    pub fn window_for(mut self, key: Key, window: Duration) -> Self {
+        self.overrides.insert(key, window);
+        self
+    }
+
+    /// Take the time from `clock`, e.g. a [`MockClock`](crate::clock::MockClock) in tests,
+    /// rather than from event timestamps.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Call `f` whenever a press is rejected.
+    pub fn on_feedback(mut self, f: impl FnMut(KeyFeedback) + Send + 'static) -> Self {
+        self.feedback = Some(Box::new(f));
+        self
+    }
+}
+
+impl EventTransform for BounceKeys {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        self.frame.clear();
+        for ev in frame {
+            if ev.event_type() != EventType::KEY {
+                self.frame.push(*ev);
+                continue;
+            }
+            let key = Key::new(ev.code());
+            match ev.value() {
+                1 => {
+                    let window = self.overrides.get(&key).copied().unwrap_or(self.window);
+                    let bounced = self.last_release.get(&key).is_some_and(|&released| {
+                        self.clock
+                            .event_time(ev)
+                            .duration_since(released)
+                            .is_ok_and(|since| since < window)
+                    });
+                    if bounced {
+                        self.suppressed.insert(key);
+                        if let Some(f) = &mut self.feedback {
+                            f(KeyFeedback::Rejected(key));
+                        }
+                    } else {
+                        self.frame.push(*ev);
+                    }
+                }
+                _ if self.suppressed.contains(key) => {
+                    if ev.value() == 0 {
+                        self.suppressed.remove(key);
+                    }
+                }
+                value => {
+                    if value == 0 {
+                        self.last_release.insert(key, self.clock.event_time(ev));
+                    }
+                    self.frame.push(*ev);
+                }
+            }
+        }
+        if self.frame.iter().any(|ev| !is_syn_report(ev)) {
+            out.extend_from_slice(&self.frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn key_frame(key: Key, value: i32, ms: u64) -> [InputEvent; 2] {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+        let mut syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        syn.0.time = crate::systime_to_timeval(&time);
+        [key_event(key, value, time), syn]
+    }
+
+    fn keys(out: &[InputEvent]) -> Vec<(Key, i32)> {
+        out.iter()
+            .filter(|ev| ev.event_type() == EventType::KEY)
+            .map(|ev| (Key::new(ev.code()), ev.value()))
+            .collect()
+    }
+
+    #[test]
+    fn slow_and_bounce_keys() {
+        let feedback = Arc::new(Mutex::new(Vec::new()));
+        let log = feedback.clone();
+        let mut slow = SlowKeys::new(Duration::from_millis(300))
+            .delay_for(Key::KEY_ENTER, Duration::ZERO)
+            .on_feedback(move |f| log.lock().unwrap().push(f));
+        let mut out = Vec::new();
+
+        // Brushed, then held long enough
+        slow.process(&key_frame(Key::KEY_A, 1, 0), &mut out);
+        slow.process(&key_frame(Key::KEY_A, 0, 100), &mut out);
+        slow.process(&key_frame(Key::KEY_A, 1, 1000), &mut out);
+        assert_eq!(
+            slow.next_deadline(),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1300))
+        );
+        slow.tick(
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1300),
+            &mut out,
+        );
+        slow.process(&key_frame(Key::KEY_A, 0, 1500), &mut out);
+        slow.process(&key_frame(Key::KEY_ENTER, 1, 1600), &mut out);
+        assert_eq!(
+            keys(&out),
+            [(Key::KEY_A, 1), (Key::KEY_A, 0), (Key::KEY_ENTER, 1)]
+        );
+        assert_eq!(
+            *feedback.lock().unwrap(),
+            [
+                KeyFeedback::Pending(Key::KEY_A),
+                KeyFeedback::Rejected(Key::KEY_A),
+                KeyFeedback::Pending(Key::KEY_A),
+                KeyFeedback::Accepted(Key::KEY_A),
+            ]
+        );
+
+        let mut bounce = BounceKeys::new(Duration::from_millis(200));
+        out.clear();
+        for (value, ms) in [(1, 0), (0, 50), (1, 80), (0, 120), (1, 400), (0, 450)] {
+            bounce.process(&key_frame(Key::KEY_B, value, ms), &mut out);
+        }
+        assert_eq!(
+            keys(&out),
+            [
+                (Key::KEY_B, 1),
+                (Key::KEY_B, 0),
+                (Key::KEY_B, 1),
+                (Key::KEY_B, 0)
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::transform::{is_syn_report, EventTransform};
+use crate::{AttributeSet, EventType, InputEvent, Key};
+
+/// What holding a key does instead of pressing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LongPressAction {
+    /// Tap another key.
+    Key(Key),
+    /// Press the keys together, in order, and release them, e.g. for a shortcut.
+    Chord(Vec<Key>),
+    /// Tap the keys one after another.
+    Sequence(Vec<Key>),
+}
+
+/// The progress of a long press, e.g. for showing a ring filling up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LongPressFeedback {
+    /// The key was pressed, and triggers its alternate action at `deadline` if still held.
+    Started { key: Key, deadline: SystemTime },
+    /// The key was released, or another key was pressed, before the deadline, so it was
+    /// pressed as usual.
+    Cancelled { key: Key },
+    /// The key was held long enough, and its alternate action was performed.
+    Triggered { key: Key },
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    action: LongPressAction,
+    threshold: Duration,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Pending {
+    key: Key,
+    pressed: InputEvent,
+    deadline: SystemTime,
+}
+
+/// Performs an alternate action when a key is held past a threshold, e.g. a capital letter
+/// for a held letter key, as on touch keyboards.
+///
+/// A bound key's press is held back until it is released, which presses and releases it as
+/// usual, or until the threshold passes, which performs the alternate action and drops the
+/// key. Pressing another key in between also presses the bound key as usual, so typing
+/// quickly isn't affected. Actions due between frames are performed by [`tick`](Self::tick).
+///
+/// Unlike a tap-hold modifier, the held key is never pressed along with other keys.
+pub struct LongPress {
+    bindings: HashMap<Key, Binding>,
+    pending: Option<Pending>,
+    /// Keys whose alternate action was performed, so their release is dropped.
+    triggered: AttributeSet<Key>,
+    feedback: Option<Box<dyn FnMut(LongPressFeedback) + Send>>,
+    frame: Vec<InputEvent>,
+    clock: SharedClock,
+}
+
+impl fmt::Debug for LongPress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LongPress")
+            .field("bindings", &self.bindings)
+            .field("pending", &self.pending)
+            .field("triggered", &self.triggered)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for LongPress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn event(type_: EventType, code: u16, value: i32, time: SystemTime) -> InputEvent {
+    let mut ev = InputEvent::new(type_, code, value);
+    ev.0.time = crate::systime_to_timeval(&time);
+    ev
+}
+
+impl LongPress {
+    /// Create a transform without bindings, which passes everything through.
+    pub fn new() -> Self {
+        LongPress {
+            bindings: HashMap::new(),
+            pending: None,
+            triggered: AttributeSet::new(),
+            feedback: None,
+            frame: Vec::new(),
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Perform `action` when `key` is held for `threshold`.
+    pub fn bind(mut self, key: Key, threshold: Duration, action: LongPressAction) -> Self {
+        self.bindings.insert(key, Binding { action, threshold });
+        self
+    }
+
+    /// Take the time from `clock`, e.g. a [`MockClock`](crate::clock::MockClock) in tests,
+    /// rather than from event timestamps and the system clock.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    /// Call `f` when a long press starts, is cancelled or triggers.
+    pub fn on_feedback(mut self, f: impl FnMut(LongPressFeedback) + Send + 'static) -> Self {
+        self.feedback = Some(Box::new(f));
+        self
+    }
+
+    fn feedback(&mut self, feedback: LongPressFeedback) {
+        if let Some(f) = &mut self.feedback {
+            f(feedback);
+        }
+    }
+
+    /// Returns the time the held key triggers its alternate action.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        self.pending.map(|p| p.deadline)
+    }
+
+    /// Perform the alternate action of the held key if its threshold has passed as of `now`.
+    pub fn tick(&mut self, now: SystemTime, out: &mut Vec<InputEvent>) {
+        let Some(pending) = self.pending.filter(|p| p.deadline <= now) else {
+            return;
+        };
+        self.pending = None;
+        self.triggered.insert(pending.key);
+        let time = pending.deadline;
+        let syn = event(EventType::SYNCHRONIZATION, 0, 0, time);
+        let tap = |out: &mut Vec<InputEvent>, keys: &[Key]| {
+            for value in [1, 0] {
+                for key in keys {
+                    out.push(event(EventType::KEY, key.code(), value, time));
+                }
+                out.push(syn);
+            }
+        };
+        match &self.bindings[&pending.key].action {
+            LongPressAction::Key(key) => tap(out, &[*key]),
+            LongPressAction::Chord(keys) => {
+                for key in keys {
+                    out.push(event(EventType::KEY, key.code(), 1, time));
+                }
+                out.push(syn);
+                for key in keys.iter().rev() {
+                    out.push(event(EventType::KEY, key.code(), 0, time));
+                }
+                out.push(syn);
+            }
+            LongPressAction::Sequence(keys) => {
+                for key in keys {
+                    tap(out, &[*key]);
+                }
+            }
+        }
+        self.feedback(LongPressFeedback::Triggered { key: pending.key });
+    }
+
+    /// Like [`tick`](Self::tick), as of the clock's current time.
+    pub fn tick_now(&mut self, out: &mut Vec<InputEvent>) {
+        let now = self.clock.now();
+        self.tick(now, out)
+    }
+
+    /// Press the held key as usual, in the frame being built.
+    fn cancel(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.frame.push(pending.pressed);
+            self.feedback(LongPressFeedback::Cancelled { key: pending.key });
+        }
+    }
+}
+
+impl EventTransform for LongPress {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let Some(syn) = frame.last().filter(|ev| is_syn_report(ev)) else {
+            return;
+        };
+        self.tick(self.clock.event_time(syn), out);
+        self.frame.clear();
+        for ev in &frame[..frame.len() - 1] {
+            if ev.event_type() != EventType::KEY {
+                self.frame.push(*ev);
+                continue;
+            }
+            let key = Key::new(ev.code());
+            let is_pending = self.pending.is_some_and(|p| p.key == key);
+            match ev.value() {
+                _ if self.triggered.contains(key) => {
+                    if ev.value() == 0 {
+                        self.triggered.remove(key);
+                    }
+                }
+                // Repeats of the held key
+                2 if is_pending => {}
+                0 if is_pending => {
+                    self.cancel();
+                    // The press and the release in frames of their own
+                    self.frame.push(*syn);
+                    self.frame.push(*ev);
+                }
+                1 => {
+                    self.cancel();
+                    match self.bindings.get(&key) {
+                        Some(binding) => {
+                            let deadline = self.clock.event_time(ev) + binding.threshold;
+                            self.pending = Some(Pending {
+                                key,
+                                pressed: *ev,
+                                deadline,
+                            });
+                            self.feedback(LongPressFeedback::Started { key, deadline });
+                        }
+                        None => self.frame.push(*ev),
+                    }
+                }
+                _ => self.frame.push(*ev),
+            }
+        }
+        if !self.frame.is_empty() {
+            out.extend_from_slice(&self.frame);
+            out.push(*syn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::keys;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn hold_for_alternate() {
+        let (a, b, shift) = (Key::KEY_A, Key::KEY_B, Key::KEY_LEFTSHIFT);
+        let triggered = Arc::new(Mutex::new(0));
+        let count = triggered.clone();
+        let mut long_press = LongPress::new()
+            .bind(
+                a,
+                Duration::from_millis(400),
+                LongPressAction::Chord(vec![shift, a]),
+            )
+            .on_feedback(move |f| {
+                if let LongPressFeedback::Triggered { .. } = f {
+                    *count.lock().unwrap() += 1;
+                }
+            });
+
+        // Released early, or interrupted, the key is pressed as usual
+        assert_eq!(
+            keys(&mut long_press, &[(a, 1, 0), (a, 0, 100)]),
+            [(a, 1), (a, 0)]
+        );
+        assert_eq!(
+            keys(&mut long_press, &[(a, 1, 200), (b, 1, 250), (a, 0, 260)]),
+            [(a, 1), (b, 1), (a, 0)]
+        );
+
+        // Held, it performs the alternate action instead
+        let out = keys(&mut long_press, &[(a, 1, 1000), (a, 2, 1300), (a, 0, 1500)]);
+        assert_eq!(out, [(shift, 1), (a, 1), (a, 0), (shift, 0)]);
+        assert_eq!(*triggered.lock().unwrap(), 1);
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use crate::transform::{is_syn_report, EventTransform};
+use crate::{AttributeSet, EventType, InputEvent, Key};
+
+/// The keys that swap places when mirroring a QWERTY keyboard.
+const QWERTY_PAIRS: [(Key, Key); 24] = [
+    (Key::KEY_1, Key::KEY_0),
+    (Key::KEY_2, Key::KEY_9),
+    (Key::KEY_3, Key::KEY_8),
+    (Key::KEY_4, Key::KEY_7),
+    (Key::KEY_5, Key::KEY_6),
+    (Key::KEY_Q, Key::KEY_P),
+    (Key::KEY_W, Key::KEY_O),
+    (Key::KEY_E, Key::KEY_I),
+    (Key::KEY_R, Key::KEY_U),
+    (Key::KEY_T, Key::KEY_Y),
+    (Key::KEY_A, Key::KEY_SEMICOLON),
+    (Key::KEY_S, Key::KEY_L),
+    (Key::KEY_D, Key::KEY_K),
+    (Key::KEY_F, Key::KEY_J),
+    (Key::KEY_G, Key::KEY_H),
+    (Key::KEY_Z, Key::KEY_SLASH),
+    (Key::KEY_X, Key::KEY_DOT),
+    (Key::KEY_C, Key::KEY_COMMA),
+    (Key::KEY_V, Key::KEY_M),
+    (Key::KEY_B, Key::KEY_N),
+    (Key::KEY_GRAVE, Key::KEY_MINUS),
+    (Key::KEY_TAB, Key::KEY_BACKSPACE),
+    (Key::KEY_CAPSLOCK, Key::KEY_ENTER),
+    (Key::KEY_LEFTBRACE, Key::KEY_EQUAL),
+];
+
+/// Mirrored typing for one-handed users: while the trigger key is held, every key types its
+/// counterpart from the other half of the keyboard.
+///
+/// With the default QWERTY mirror, holding space and pressing `F` types `J`. Tapping the
+/// trigger on its own still types it, on release; holding it for longer than the tap time
+/// without typing anything, or typing a mirrored key, doesn't. Keys keep their meaning until
+/// they are released, even if the trigger is released first.
+#[derive(Debug, Clone)]
+pub struct MirrorKeys {
+    trigger: Key,
+    tap_time: Option<Duration>,
+    mirror: HashMap<Key, Key>,
+    /// When the trigger was pressed, while it is held.
+    trigger_down: Option<SystemTime>,
+    /// A key was mirrored since the trigger was pressed.
+    used: bool,
+    /// Physical keys that were pressed mirrored.
+    mirrored: AttributeSet<Key>,
+    frame: Vec<InputEvent>,
+}
+
+impl Default for MirrorKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MirrorKeys {
+    /// Mirror a QWERTY keyboard while space is held. Space is typed when tapped within 300ms.
+    pub fn new() -> Self {
+        let mut mirror = HashMap::new();
+        for (left, right) in QWERTY_PAIRS {
+            mirror.insert(left, right);
+            mirror.insert(right, left);
+        }
+        MirrorKeys {
+            trigger: Key::KEY_SPACE,
+            tap_time: Some(Duration::from_millis(300)),
+            mirror,
+            trigger_down: None,
+            used: false,
+            mirrored: AttributeSet::new(),
+            frame: Vec::new(),
+        }
+    }
+
+    /// Mirror while `key` is held, instead of space.
+    pub fn trigger(mut self, key: Key) -> Self {
+        self.mirror.remove(&key);
+        self.trigger = key;
+        self
+    }
+
+    /// Type the trigger if it is released within `time` without typing anything, or no matter
+    /// how long it was held if `None`.
+    pub fn tap_time(mut self, time: Option<Duration>) -> Self {
+        self.tap_time = time;
+        self
+    }
+
+    /// Swap `a` and `b` while mirroring, replacing their existing counterparts.
+    pub fn pair(mut self, a: Key, b: Key) -> Self {
+        self.mirror.insert(a, b);
+        self.mirror.insert(b, a);
+        self
+    }
+
+    /// Returns `true` while the trigger is held.
+    pub fn mirroring(&self) -> bool {
+        self.trigger_down.is_some()
+    }
+
+    fn push(&mut self, ev: &InputEvent, key: Key) {
+        self.frame.push(InputEvent(libc::input_event {
+            code: key.code(),
+            ..ev.0
+        }));
+    }
+
+    fn trigger_event(&mut self, ev: &InputEvent) {
+        match ev.value() {
+            1 => {
+                self.trigger_down = Some(ev.timestamp());
+                self.used = false;
+            }
+            0 => {
+                let Some(down) = self.trigger_down.take() else {
+                    return;
+                };
+                let quick = self.tap_time.is_none_or(|tap_time| {
+                    ev.timestamp()
+                        .duration_since(down)
+                        .is_ok_and(|held| held <= tap_time)
+                });
+                if !self.used && quick {
+                    let mut press = *ev;
+                    press.0.value = 1;
+                    // The press in a frame of its own, so it isn't merged with the release
+                    let syn = InputEvent(libc::input_event {
+                        type_: EventType::SYNCHRONIZATION.0,
+                        code: 0,
+                        value: 0,
+                        ..ev.0
+                    });
+                    self.frame.extend([press, syn, *ev]);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl EventTransform for MirrorKeys {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let Some(syn) = frame.last().filter(|ev| is_syn_report(ev)) else {
+            return;
+        };
+        self.frame.clear();
+        for ev in &frame[..frame.len() - 1] {
+            if ev.event_type() != EventType::KEY {
+                self.frame.push(*ev);
+                continue;
+            }
+            let key = Key::new(ev.code());
+            if key == self.trigger {
+                self.trigger_event(ev);
+                continue;
+            }
+            let target = self.mirror.get(&key).copied();
+            match (ev.value(), target) {
+                (1, Some(target)) if self.trigger_down.is_some() => {
+                    self.used = true;
+                    self.mirrored.insert(key);
+                    self.push(ev, target);
+                }
+                (_, Some(target)) if self.mirrored.contains(key) => {
+                    if ev.value() == 0 {
+                        self.mirrored.remove(key);
+                    }
+                    self.push(ev, target);
+                }
+                _ => self.frame.push(*ev),
+            }
+        }
+        if !self.frame.is_empty() {
+            out.extend_from_slice(&self.frame);
+            out.push(*syn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::keys;
+
+    #[test]
+    fn mirror_while_space_held() {
+        let (space, f, j) = (Key::KEY_SPACE, Key::KEY_F, Key::KEY_J);
+        let mut mirror = MirrorKeys::new();
+
+        // Tapped, space types itself
+        assert_eq!(
+            keys(&mut mirror, &[(space, 1, 0), (space, 0, 100)]),
+            [(space, 1), (space, 0)]
+        );
+        // Held, it mirrors, and the key is released mirrored after space
+        let out = keys(
+            &mut mirror,
+            &[
+                (space, 1, 1000),
+                (f, 1, 1050),
+                (space, 0, 1100),
+                (f, 0, 1150),
+            ],
+        );
+        assert_eq!(out, [(j, 1), (j, 0)]);
+        // Held too long, it types nothing
+        assert!(keys(&mut mirror, &[(space, 1, 2000), (space, 0, 3000)]).is_empty());
+        assert_eq!(keys(&mut mirror, &[(f, 1, 4000)]), [(f, 1)]);
+    }
+}
@@ -0,0 +1,133 @@
+//! Reusable transformations over streams of input events.
+//!
+//! A transform consumes one frame of events at a time (a batch of events terminated by a
+//! `SYN_REPORT`) and writes zero or more frames to an output buffer. Transforms can be driven
+//! directly by a consumer reading from a [`Device`](crate::Device), or chained inside a
+//! [`Proxy`](crate::proxy::Proxy) that forwards a physical device to a virtual one. A
+//! [`Pipeline`] chains transforms for either use.
+//!
+//! Transforms only need to implement [`EventTransform`], so crates can publish their own that
+//! work with this one.
+
+use crate::{DeviceState, EventType, InputEvent, Synchronization};
+
+mod accel;
+mod braille;
+mod calibrate;
+mod coalesce;
+mod keyfilter;
+mod longpress;
+mod mirror;
+mod mousekeys;
+mod oneshot;
+mod palm;
+mod pipeline;
+mod pool;
+mod rotate;
+mod scroll;
+mod sticky;
+mod touchcal;
+mod touchpad;
+
+pub use accel::{AccelProfile, PointerAccel};
+pub use braille::{BrailleChords, BrailleOutput};
+pub use calibrate::{AxisCalibration, CalibrateTransform, Calibration, CalibrationCapture};
+pub use coalesce::CoalesceMotion;
+pub use keyfilter::{BounceKeys, KeyFeedback, SlowKeys};
+pub use longpress::{LongPress, LongPressAction, LongPressFeedback};
+pub use mirror::MirrorKeys;
+pub use mousekeys::MouseKeys;
+pub use oneshot::OneShotModifiers;
+pub use palm::PalmRejection;
+pub use pipeline::Pipeline;
+pub use pool::{FramePool, PooledFrame};
+pub use rotate::{RotateTransform, Rotation};
+pub use scroll::{ScrollMethod, ScrollTransform};
+pub use sticky::{StickyKeys, StickyState};
+pub use touchcal::{TouchCalibrateTransform, TouchCalibration, TouchCalibrationCapture};
+pub use touchpad::TouchpadPointer;
+
+/// A transformation applied to frames of input events.
+pub trait EventTransform {
+    /// Process a single frame of events.
+    ///
+    /// `frame` always ends with a `SYN_REPORT` event. Implementations append zero or more
+    /// complete frames, each terminated by a `SYN_REPORT`, to `out`.
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>);
+
+    /// Process a single frame read from a device, whose [cached state](crate::Device::cached_state)
+    /// as of the read is `state`.
+    ///
+    /// Transforms that depend on the state of the device, e.g. on which keys were already held
+    /// when they started, override this. By default it calls [`process`](Self::process).
+    fn process_with_state(
+        &mut self,
+        frame: &[InputEvent],
+        state: &DeviceState,
+        out: &mut Vec<InputEvent>,
+    ) {
+        let _ = state;
+        self.process(frame, out)
+    }
+}
+
+impl<T: EventTransform + ?Sized> EventTransform for Box<T> {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        (**self).process(frame, out)
+    }
+
+    fn process_with_state(
+        &mut self,
+        frame: &[InputEvent],
+        state: &DeviceState,
+        out: &mut Vec<InputEvent>,
+    ) {
+        (**self).process_with_state(frame, state, out)
+    }
+}
+
+/// Returns `true` if the event terminates a frame.
+#[inline]
+pub(crate) fn is_syn_report(ev: &InputEvent) -> bool {
+    ev.event_type() == EventType::SYNCHRONIZATION && ev.code() == Synchronization::SYN_REPORT.0
+}
+
+/// Splits a buffer of events into frames, each ending with a `SYN_REPORT`.
+///
+/// Any trailing events that are not followed by a `SYN_REPORT` are not returned.
+pub fn frames(events: &[InputEvent]) -> impl Iterator<Item = &[InputEvent]> + '_ {
+    let mut rest = events;
+    std::iter::from_fn(move || {
+        let end = rest.iter().position(is_syn_report)?;
+        let (frame, tail) = rest.split_at(end + 1);
+        rest = tail;
+        Some(frame)
+    })
+}
+
+/// Feeds `transform` a frame for each `(key, value, milliseconds since the epoch)`, and returns
+/// the key events it writes.
+#[cfg(test)]
+pub(crate) fn keys(
+    transform: &mut impl EventTransform,
+    events: &[(crate::Key, i32, u64)],
+) -> Vec<(crate::Key, i32)> {
+    use std::time::{Duration, SystemTime};
+
+    let mut out = Vec::new();
+    for &(key, value, ms) in events {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+        let mut frame = [
+            InputEvent::new(EventType::KEY, key.code(), value),
+            InputEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+        ];
+        for ev in &mut frame {
+            ev.0.time = crate::systime_to_timeval(&time);
+        }
+        transform.process(&frame, &mut out);
+    }
+    out.iter()
+        .filter(|ev| !is_syn_report(ev))
+        .map(|ev| (crate::Key::new(ev.code()), ev.value()))
+        .collect()
+}
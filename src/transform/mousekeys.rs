@@ -0,0 +1,372 @@
+use std::io;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::transform::{is_syn_report, EventTransform};
+use crate::uinput::VirtualDeviceBuilder;
+use crate::{AttributeSet, EventType, InputEvent, Key, RelativeAxisType};
+
+/// The numpad keys that move the pointer, with their direction.
+const DIRECTIONS: [(Key, i32, i32); 8] = [
+    (Key::KEY_KP1, -1, 1),
+    (Key::KEY_KP2, 0, 1),
+    (Key::KEY_KP3, 1, 1),
+    (Key::KEY_KP4, -1, 0),
+    (Key::KEY_KP6, 1, 0),
+    (Key::KEY_KP7, -1, -1),
+    (Key::KEY_KP8, 0, -1),
+    (Key::KEY_KP9, 1, -1),
+];
+
+#[derive(Debug, Copy, Clone)]
+struct Motion {
+    /// The number of moves since the keys were pressed.
+    step: u32,
+    next: SystemTime,
+}
+
+/// Controls the pointer from the numpad, like the MouseKeys of X.
+///
+/// The keys around `5` move the pointer in their direction. The pointer moves once when the
+/// key is pressed and, after a delay, repeatedly while it is held, speeding up to the maximum
+/// speed along a curve. `5` clicks the selected button, `+` double-clicks it, and `/`, `*` and
+/// `-` select the left, middle and right button. `0` presses the selected button and holds it,
+/// e.g. for dragging, until `.` releases it.
+///
+/// Numpad keys are consumed while the transform is enabled; everything else is passed through.
+/// The motion while a key is held is emitted by [`tick`](Self::tick), which should be called
+/// at [`next_deadline`](Self::next_deadline). The output device needs the capabilities set up
+/// by [`configure_output`](Self::configure_output).
+#[derive(Debug, Clone)]
+pub struct MouseKeys {
+    enabled: bool,
+    delay: Duration,
+    interval: Duration,
+    time_to_max: Duration,
+    max_speed: i32,
+    curve: i32,
+    button: Key,
+    held: AttributeSet<Key>,
+    locked: AttributeSet<Key>,
+    motion: Option<Motion>,
+    frame: Vec<InputEvent>,
+    clock: SharedClock,
+}
+
+impl Default for MouseKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn event(type_: EventType, code: u16, value: i32, time: SystemTime) -> InputEvent {
+    let mut ev = InputEvent::new(type_, code, value);
+    ev.0.time = crate::systime_to_timeval(&time);
+    ev
+}
+
+impl MouseKeys {
+    /// Create an enabled transform with the defaults of X: a delay of 160ms, a move every
+    /// 40ms, and a maximum speed of 30 units per move reached after 1.2s.
+    pub fn new() -> Self {
+        MouseKeys {
+            enabled: true,
+            delay: Duration::from_millis(160),
+            interval: Duration::from_millis(40),
+            time_to_max: Duration::from_millis(1200),
+            max_speed: 30,
+            curve: 0,
+            button: Key::BTN_LEFT,
+            held: AttributeSet::new(),
+            locked: AttributeSet::new(),
+            motion: None,
+            frame: Vec::new(),
+            clock: SharedClock::default(),
+        }
+    }
+
+    /// Set the time between pressing a key and the pointer starting to move continuously.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Set the time between moves while a key is held.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Set the time it takes to speed up to the maximum speed.
+    pub fn time_to_max(mut self, time: Duration) -> Self {
+        self.time_to_max = time;
+        self
+    }
+
+    /// Set the maximum speed, in units per move.
+    pub fn max_speed(mut self, speed: i32) -> Self {
+        self.max_speed = speed.max(1);
+        self
+    }
+
+    /// Set the shape of the acceleration, from -1000 to 1000. 0 speeds up linearly; positive
+    /// values start slower, negative values faster.
+    pub fn curve(mut self, curve: i32) -> Self {
+        self.curve = curve.clamp(-1000, 1000);
+        self
+    }
+
+    /// Take the time from `clock`, e.g. a [`MockClock`](crate::clock::MockClock) in tests,
+    /// rather than from event timestamps and the system clock.
+    pub fn clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable the transform. While disabled, numpad keys are passed through.
+    ///
+    /// Disabling it stops the pointer, but a button held with `0` stays held until `.` is
+    /// pressed with the transform enabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.held = AttributeSet::new();
+        self.motion = None;
+    }
+
+    /// Returns the button clicked by `5`.
+    pub fn button(&self) -> Key {
+        self.button
+    }
+
+    /// Enable the capabilities on `builder` that this transform emits: relative X/Y motion and
+    /// the left, right and middle buttons.
+    pub fn configure_output<'a>(
+        &self,
+        builder: VirtualDeviceBuilder<'a>,
+    ) -> io::Result<VirtualDeviceBuilder<'a>> {
+        let keys: AttributeSet<Key> = [Key::BTN_LEFT, Key::BTN_RIGHT, Key::BTN_MIDDLE]
+            .into_iter()
+            .collect();
+        let axes: AttributeSet<RelativeAxisType> =
+            [RelativeAxisType::REL_X, RelativeAxisType::REL_Y]
+                .into_iter()
+                .collect();
+        builder.with_keys(&keys)?.with_relative_axes(&axes)
+    }
+
+    fn direction(&self) -> (i32, i32) {
+        let (dx, dy) = DIRECTIONS
+            .iter()
+            .filter(|(key, _, _)| self.held.contains(*key))
+            .fold((0, 0), |(x, y), (_, dx, dy)| (x + dx, y + dy));
+        (dx.clamp(-1, 1), dy.clamp(-1, 1))
+    }
+
+    /// Returns the distance moved by move number `step`.
+    fn speed(&self, step: u32) -> i32 {
+        if step == 0 {
+            return 1;
+        }
+        let steps = (self.time_to_max.as_secs_f32() / self.interval.as_secs_f32()).max(1.0);
+        let exponent = 1.0 + self.curve as f32 / 1000.0;
+        let speed = self.max_speed as f32 * (step as f32 / steps).min(1.0).powf(exponent);
+        (speed.round() as i32).max(1)
+    }
+
+    fn push_motion(&mut self, dx: i32, dy: i32, time: SystemTime) {
+        if dx != 0 {
+            let code = RelativeAxisType::REL_X.0;
+            self.frame.push(event(EventType::RELATIVE, code, dx, time));
+        }
+        if dy != 0 {
+            let code = RelativeAxisType::REL_Y.0;
+            self.frame.push(event(EventType::RELATIVE, code, dy, time));
+        }
+    }
+
+    /// Returns the time the pointer moves next, while a direction key is held.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        self.motion.map(|m| m.next)
+    }
+
+    /// Move the pointer for a held direction key, if a move is due as of `now`.
+    pub fn tick(&mut self, now: SystemTime, out: &mut Vec<InputEvent>) {
+        let Some(mut motion) = self.motion else {
+            return;
+        };
+        let (dx, dy) = self.direction();
+        let mut distance = 0;
+        // Moves that are overdue are merged, rather than emitted in a burst
+        while motion.next <= now {
+            motion.step += 1;
+            motion.next += self.interval;
+            distance += self.speed(motion.step);
+        }
+        self.motion = Some(motion);
+        if distance > 0 {
+            self.frame.clear();
+            self.push_motion(dx * distance, dy * distance, now);
+            out.extend_from_slice(&self.frame);
+            out.push(event(EventType::SYNCHRONIZATION, 0, 0, now));
+        }
+    }
+
+    /// Like [`tick`](Self::tick), as of the clock's current time.
+    pub fn tick_now(&mut self, out: &mut Vec<InputEvent>) {
+        let now = self.clock.now();
+        self.tick(now, out)
+    }
+
+    fn click(&mut self, time: SystemTime, out: &mut Vec<InputEvent>) {
+        let code = self.button.code();
+        for value in [1, 0] {
+            out.push(event(EventType::KEY, code, value, time));
+            out.push(event(EventType::SYNCHRONIZATION, 0, 0, time));
+        }
+    }
+
+    fn key(&mut self, key: Key, value: i32, time: SystemTime, out: &mut Vec<InputEvent>) -> bool {
+        if DIRECTIONS.iter().any(|(k, _, _)| *k == key) {
+            match value {
+                1 => {
+                    self.held.insert(key);
+                    if self.motion.is_none() {
+                        let (dx, dy) = self.direction();
+                        self.push_motion(dx, dy, time);
+                        self.motion = Some(Motion {
+                            step: 0,
+                            next: time + self.delay,
+                        });
+                    }
+                }
+                0 => {
+                    self.held.remove(key);
+                    if self.direction() == (0, 0) {
+                        self.motion = None;
+                    }
+                }
+                _ => {}
+            }
+            return true;
+        }
+        let button = self.button.code();
+        match key {
+            Key::KEY_KP5 if value != 2 => {
+                self.frame.push(event(EventType::KEY, button, value, time));
+            }
+            Key::KEY_KPPLUS if value == 1 => {
+                // Each click in a frame of its own, so they aren't merged
+                self.click(time, out);
+                self.click(time, out);
+            }
+            Key::KEY_KPSLASH => self.button = Key::BTN_LEFT,
+            Key::KEY_KPASTERISK => self.button = Key::BTN_MIDDLE,
+            Key::KEY_KPMINUS => self.button = Key::BTN_RIGHT,
+            Key::KEY_KP0 if value == 1 && !self.locked.contains(self.button) => {
+                self.locked.insert(self.button);
+                self.frame.push(event(EventType::KEY, button, 1, time));
+            }
+            Key::KEY_KPDOT if value == 1 => {
+                for locked in self.locked.iter() {
+                    self.frame
+                        .push(event(EventType::KEY, locked.code(), 0, time));
+                }
+                self.locked = AttributeSet::new();
+            }
+            Key::KEY_KP5 | Key::KEY_KPPLUS | Key::KEY_KP0 | Key::KEY_KPDOT => {}
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl EventTransform for MouseKeys {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        if !self.enabled {
+            out.extend_from_slice(frame);
+            return;
+        }
+        let Some(syn) = frame.last().filter(|ev| is_syn_report(ev)) else {
+            return;
+        };
+        let time = self.clock.event_time(syn);
+        self.tick(time, out);
+        self.frame.clear();
+        for ev in &frame[..frame.len() - 1] {
+            let consumed = ev.event_type() == EventType::KEY
+                && self.key(Key::new(ev.code()), ev.value(), time, out);
+            if !consumed {
+                self.frame.push(*ev);
+            }
+        }
+        if !self.frame.is_empty() {
+            out.extend_from_slice(&self.frame);
+            out.push(*syn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ms: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(ms)
+    }
+
+    fn key_frame(key: Key, value: i32, ms: u64) -> [InputEvent; 2] {
+        [
+            event(EventType::KEY, key.code(), value, at(ms)),
+            event(EventType::SYNCHRONIZATION, 0, 0, at(ms)),
+        ]
+    }
+
+    fn summary(out: &[InputEvent]) -> Vec<(EventType, u16, i32)> {
+        out.iter()
+            .filter(|ev| !is_syn_report(ev))
+            .map(|ev| (ev.event_type(), ev.code(), ev.value()))
+            .collect()
+    }
+
+    #[test]
+    fn move_and_click() {
+        let mut keys = MouseKeys::new();
+        let mut out = Vec::new();
+        let rel_x = |v| (EventType::RELATIVE, RelativeAxisType::REL_X.0, v);
+
+        // One step on press, then continuous motion speeding up after the delay
+        keys.process(&key_frame(Key::KEY_KP6, 1, 0), &mut out);
+        assert_eq!(summary(&out), [rel_x(1)]);
+        assert_eq!(keys.next_deadline(), Some(at(160)));
+        out.clear();
+        keys.tick(at(160), &mut out);
+        keys.tick(at(200), &mut out);
+        keys.tick(at(2000), &mut out);
+        let moves: Vec<_> = summary(&out).iter().map(|&(_, _, v)| v).collect();
+        assert_eq!(moves[..2], [1, 2]);
+        assert!(moves[2] > 30);
+        out.clear();
+        keys.process(&key_frame(Key::KEY_KP6, 0, 2010), &mut out);
+        keys.tick(at(3000), &mut out);
+        assert!(out.is_empty());
+
+        // Clicks with the selected button, other keys pass through
+        keys.process(&key_frame(Key::KEY_KPMINUS, 1, 3000), &mut out);
+        keys.process(&key_frame(Key::KEY_KP5, 1, 3010), &mut out);
+        keys.process(&key_frame(Key::KEY_KP5, 0, 3020), &mut out);
+        keys.process(&key_frame(Key::KEY_A, 1, 3030), &mut out);
+        let right = Key::BTN_RIGHT.code();
+        assert_eq!(
+            summary(&out),
+            [
+                (EventType::KEY, right, 1),
+                (EventType::KEY, right, 0),
+                (EventType::KEY, Key::KEY_A.code(), 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use std::time::{Duration, SystemTime};
+
+use crate::transform::{is_syn_report, EventTransform};
+use crate::{EventType, InputEvent, Key};
+
+const DEFAULT_MODIFIERS: [Key; 8] = [
+    Key::KEY_LEFTSHIFT,
+    Key::KEY_RIGHTSHIFT,
+    Key::KEY_LEFTCTRL,
+    Key::KEY_RIGHTCTRL,
+    Key::KEY_LEFTALT,
+    Key::KEY_RIGHTALT,
+    Key::KEY_LEFTMETA,
+    Key::KEY_RIGHTMETA,
+];
+
+#[derive(Debug, Copy, Clone)]
+struct Held {
+    key: Key,
+    /// The press was forwarded, because another key was pressed while it was held.
+    forwarded: bool,
+}
+
+/// Makes a tapped modifier apply to the next key only.
+///
+/// Tapping a modifier queues it without pressing it. The next key that isn't a modifier is
+/// pressed with every queued modifier, in the order they were tapped, and the modifiers are
+/// released along with it. Tapping a queued modifier again takes it out of the queue, and the
+/// whole queue is dropped if no key follows within the timeout; either way, nothing reaches
+/// the output. Modifiers held while another key is pressed work as usual.
+///
+/// Unlike [`StickyKeys`](super::StickyKeys), modifiers can't be locked, and a modifier that
+/// isn't used is never pressed at all.
+#[derive(Debug, Clone)]
+pub struct OneShotModifiers {
+    modifiers: Vec<Key>,
+    timeout: Option<Duration>,
+    queue: Vec<Key>,
+    /// The time of the last tap, while the queue isn't empty.
+    queued_at: Option<SystemTime>,
+    held: Vec<Held>,
+    /// Modifiers pressed for the key being held, released with it.
+    applied: Vec<Key>,
+    /// The key the applied modifiers were pressed for.
+    applied_to: Option<Key>,
+    frame: Vec<InputEvent>,
+}
+
+impl Default for OneShotModifiers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OneShotModifiers {
+    /// Create a transform for the shift, control, alt and meta keys, which drops queued
+    /// modifiers after 2 seconds.
+    pub fn new() -> Self {
+        OneShotModifiers {
+            modifiers: DEFAULT_MODIFIERS.to_vec(),
+            timeout: Some(Duration::from_secs(2)),
+            queue: Vec::new(),
+            queued_at: None,
+            held: Vec::new(),
+            applied: Vec::new(),
+            applied_to: None,
+            frame: Vec::new(),
+        }
+    }
+
+    /// Treat `keys` as the modifiers, instead of the default ones.
+    pub fn modifiers(mut self, keys: &[Key]) -> Self {
+        self.modifiers = keys.to_vec();
+        self
+    }
+
+    /// Drop queued modifiers if no key follows within `timeout`, or never if `None`.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the queued modifiers, in the order they were tapped.
+    pub fn queued(&self) -> &[Key] {
+        &self.queue
+    }
+
+    /// Drop the queued modifiers.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.queued_at = None;
+    }
+
+    /// Returns the time the queue will be dropped at.
+    pub fn next_deadline(&self) -> Option<SystemTime> {
+        Some(self.queued_at? + self.timeout?)
+    }
+
+    /// Drop the queue if it has timed out as of `now`.
+    pub fn tick(&mut self, now: SystemTime) {
+        if self.next_deadline().is_some_and(|deadline| deadline <= now) {
+            self.clear();
+        }
+    }
+
+    fn push(&mut self, ev: &InputEvent, key: Key, value: i32) {
+        self.frame.push(InputEvent(libc::input_event {
+            code: key.code(),
+            value,
+            ..ev.0
+        }));
+    }
+
+    fn modifier(&mut self, ev: &InputEvent, key: Key) {
+        let held = self.held.iter().position(|h| h.key == key);
+        match (ev.value(), held) {
+            (1, None) => self.held.push(Held {
+                key,
+                forwarded: false,
+            }),
+            (0, Some(i)) => {
+                let held = self.held.remove(i);
+                if held.forwarded {
+                    self.frame.push(*ev);
+                } else if let Some(queued) = self.queue.iter().position(|&k| k == key) {
+                    self.queue.remove(queued);
+                } else {
+                    self.queue.push(key);
+                    self.queued_at = Some(ev.timestamp());
+                }
+                if self.queue.is_empty() {
+                    self.queued_at = None;
+                }
+            }
+            (2, Some(i)) if self.held[i].forwarded => self.frame.push(*ev),
+            _ => {}
+        }
+    }
+
+    fn key(&mut self, ev: &InputEvent, key: Key) {
+        match ev.value() {
+            1 => {
+                // Held modifiers are being used as usual, not tapped
+                for i in 0..self.held.len() {
+                    if !self.held[i].forwarded {
+                        self.held[i].forwarded = true;
+                        self.push(ev, self.held[i].key, 1);
+                    }
+                }
+                let queue = std::mem::take(&mut self.queue);
+                self.queued_at = None;
+                for &modifier in &queue {
+                    self.push(ev, modifier, 1);
+                }
+                self.applied.extend(queue);
+                if !self.applied.is_empty() && self.applied_to.is_none() {
+                    self.applied_to = Some(key);
+                }
+                self.frame.push(*ev);
+            }
+            0 => {
+                self.frame.push(*ev);
+                if self.applied_to == Some(key) {
+                    self.applied_to = None;
+                    for modifier in std::mem::take(&mut self.applied) {
+                        self.push(ev, modifier, 0);
+                    }
+                }
+            }
+            _ => self.frame.push(*ev),
+        }
+    }
+}
+
+impl EventTransform for OneShotModifiers {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let Some(syn) = frame.last().filter(|ev| is_syn_report(ev)) else {
+            return;
+        };
+        self.tick(syn.timestamp());
+        self.frame.clear();
+        for ev in &frame[..frame.len() - 1] {
+            if ev.event_type() != EventType::KEY {
+                self.frame.push(*ev);
+                continue;
+            }
+            let key = Key::new(ev.code());
+            if self.modifiers.contains(&key) {
+                self.modifier(ev, key);
+            } else {
+                self.key(ev, key);
+            }
+        }
+        if !self.frame.is_empty() {
+            out.extend_from_slice(&self.frame);
+            out.push(*syn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::keys;
+
+    #[test]
+    fn queue_and_timeout() {
+        let (ctrl, shift, t) = (Key::KEY_LEFTCTRL, Key::KEY_LEFTSHIFT, Key::KEY_T);
+        let mut oneshot = OneShotModifiers::new();
+
+        // Both queued modifiers apply to the next key only
+        let out = keys(
+            &mut oneshot,
+            &[(ctrl, 1, 0), (ctrl, 0, 10), (shift, 1, 20), (shift, 0, 30)],
+        );
+        assert!(out.is_empty());
+        assert_eq!(oneshot.queued(), [ctrl, shift]);
+        let out = keys(&mut oneshot, &[(t, 1, 100), (t, 0, 110), (t, 1, 120)]);
+        assert_eq!(
+            out,
+            [
+                (ctrl, 1),
+                (shift, 1),
+                (t, 1),
+                (t, 0),
+                (ctrl, 0),
+                (shift, 0),
+                (t, 1)
+            ]
+        );
+
+        // A queued modifier is dropped after the timeout
+        keys(&mut oneshot, &[(t, 0, 130), (ctrl, 1, 200), (ctrl, 0, 210)]);
+        let out = keys(&mut oneshot, &[(t, 1, 5000), (t, 0, 5010)]);
+        assert_eq!(out, [(t, 1), (t, 0)]);
+    }
+}
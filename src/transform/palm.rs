@@ -0,0 +1,306 @@
+use crate::transform::{is_syn_report, EventTransform};
+use crate::{AbsInfo, AbsoluteAxisType, EventType, InputEvent, InputEventKind, Key};
+
+const TOOL_KEYS: [Key; 4] = [
+    Key::BTN_TOOL_FINGER,
+    Key::BTN_TOOL_DOUBLETAP,
+    Key::BTN_TOOL_TRIPLETAP,
+    Key::BTN_TOOL_QUADTAP,
+];
+
+#[derive(Debug, Default, Clone)]
+struct Slot {
+    /// The kernel considers this contact active.
+    active: bool,
+    /// The contact was classified as a palm and is being suppressed until it lifts.
+    palm: bool,
+    /// The consumer has been told about this contact.
+    reported: bool,
+    /// Events for this slot in the frame being processed.
+    events: Vec<InputEvent>,
+    /// The contact began in the frame being processed.
+    started: bool,
+}
+
+/// Drops multitouch contacts that look like a resting palm.
+///
+/// A contact is classified as a palm when its `ABS_MT_TOUCH_MAJOR` or `ABS_MT_PRESSURE` exceeds
+/// the configured threshold, or when it first lands inside the configured edge zone. Once
+/// classified, every event of that contact is dropped until it is lifted. If the contact had
+/// already been forwarded, it is ended with a `ABS_MT_TRACKING_ID` of -1 so consumers don't see a
+/// stuck touch.
+///
+/// For multitouch devices `BTN_TOUCH` and the `BTN_TOOL_*` finger count keys are recomputed from
+/// the remaining contacts, and the single-touch `ABS_X`/`ABS_Y` emulation is only forwarded while
+/// at least one non-palm contact is down. Devices that never report `ABS_MT_*` events are passed
+/// through untouched.
+#[derive(Debug, Clone, Default)]
+pub struct PalmRejection {
+    max_touch_major: Option<i32>,
+    max_pressure: Option<i32>,
+    edge_zone: Option<(i32, i32)>,
+    slots: Vec<Slot>,
+    current: usize,
+    seen_mt: bool,
+    fingers: usize,
+}
+
+impl PalmRejection {
+    /// Create a filter with no thresholds set, which drops nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Contacts with an `ABS_MT_TOUCH_MAJOR` above `value` are treated as palms.
+    pub fn max_touch_major(mut self, value: i32) -> Self {
+        self.max_touch_major = Some(value);
+        self
+    }
+
+    /// Contacts with an `ABS_MT_PRESSURE` above `value` are treated as palms.
+    pub fn max_pressure(mut self, value: i32) -> Self {
+        self.max_pressure = Some(value);
+        self
+    }
+
+    /// Contacts that land with an `ABS_MT_POSITION_X` below `min_x` or above `max_x` are treated
+    /// as palms.
+    pub fn edge_zone(mut self, min_x: i32, max_x: i32) -> Self {
+        self.edge_zone = Some((min_x, max_x));
+        self
+    }
+
+    /// Set the edge zone to a fraction of the width of the X axis on each side, e.g. 0.05 for
+    /// the outer 5%.
+    pub fn edge_fraction(self, x: AbsInfo, fraction: f32) -> Self {
+        let width = (x.maximum() - x.minimum()) as f32;
+        let margin = (width * fraction) as i32;
+        self.edge_zone(x.minimum() + margin, x.maximum() - margin)
+    }
+
+    /// Returns `true` if the contact in `slot` is currently being suppressed as a palm.
+    pub fn is_palm(&self, slot: usize) -> bool {
+        self.slots.get(slot).is_some_and(|s| s.palm)
+    }
+
+    /// Returns the number of active contacts that are not palms.
+    pub fn finger_count(&self) -> usize {
+        self.fingers
+    }
+
+    fn slot(&mut self) -> &mut Slot {
+        if self.slots.len() <= self.current {
+            self.slots.resize_with(self.current + 1, Slot::default);
+        }
+        &mut self.slots[self.current]
+    }
+
+    fn classify(&mut self, ev: &InputEvent) {
+        let (max_touch_major, max_pressure, edge_zone) =
+            (self.max_touch_major, self.max_pressure, self.edge_zone);
+        let slot = self.slot();
+        let palm = match ev.kind() {
+            InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_TOUCH_MAJOR) => {
+                max_touch_major.is_some_and(|max| ev.value() > max)
+            }
+            InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_PRESSURE) => {
+                max_pressure.is_some_and(|max| ev.value() > max)
+            }
+            InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_POSITION_X) if slot.started => {
+                edge_zone.is_some_and(|(min, max)| ev.value() < min || ev.value() > max)
+            }
+            _ => false,
+        };
+        slot.palm |= palm;
+    }
+}
+
+impl EventTransform for PalmRejection {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let mut passthrough = Vec::new();
+        let mut single_touch = Vec::new();
+        for ev in frame.iter().filter(|ev| !is_syn_report(ev)) {
+            match ev.kind() {
+                InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_SLOT) => {
+                    self.seen_mt = true;
+                    self.current = ev.value().max(0) as usize;
+                }
+                InputEventKind::AbsAxis(AbsoluteAxisType::ABS_MT_TRACKING_ID) => {
+                    self.seen_mt = true;
+                    let slot = self.slot();
+                    if ev.value() >= 0 {
+                        *slot = Slot {
+                            active: true,
+                            started: true,
+                            ..Slot::default()
+                        };
+                    } else {
+                        slot.active = false;
+                    }
+                    slot.events.push(*ev);
+                }
+                InputEventKind::AbsAxis(axis) if axis.0 >= AbsoluteAxisType::ABS_MT_SLOT.0 => {
+                    self.seen_mt = true;
+                    self.classify(ev);
+                    self.slot().events.push(*ev);
+                }
+                InputEventKind::AbsAxis(
+                    AbsoluteAxisType::ABS_X
+                    | AbsoluteAxisType::ABS_Y
+                    | AbsoluteAxisType::ABS_PRESSURE
+                    | AbsoluteAxisType::ABS_TOOL_WIDTH,
+                ) => single_touch.push(*ev),
+                InputEventKind::Key(key) if key == Key::BTN_TOUCH || TOOL_KEYS.contains(&key) => {
+                    single_touch.push(*ev)
+                }
+                _ => passthrough.push(*ev),
+            }
+        }
+
+        if !self.seen_mt {
+            out.extend_from_slice(frame);
+            return;
+        }
+
+        let start = out.len();
+        out.extend(passthrough);
+        let mut last_slot = None;
+        for (idx, slot) in self.slots.iter_mut().enumerate() {
+            let events = std::mem::take(&mut slot.events);
+            slot.started = false;
+            let mut select = |out: &mut Vec<InputEvent>| {
+                if last_slot != Some(idx) {
+                    last_slot = Some(idx);
+                    out.push(InputEvent::new(
+                        EventType::ABSOLUTE,
+                        AbsoluteAxisType::ABS_MT_SLOT.0,
+                        idx as i32,
+                    ));
+                }
+            };
+            if slot.palm {
+                if slot.reported {
+                    select(out);
+                    out.push(InputEvent::new(
+                        EventType::ABSOLUTE,
+                        AbsoluteAxisType::ABS_MT_TRACKING_ID.0,
+                        -1,
+                    ));
+                    slot.reported = false;
+                }
+                if !slot.active {
+                    slot.palm = false;
+                }
+            } else if !events.is_empty() {
+                select(out);
+                out.extend(events);
+                slot.reported = slot.active;
+            }
+        }
+
+        let fingers = self.slots.iter().filter(|s| s.reported).count();
+        if fingers > 0 {
+            out.extend(
+                single_touch
+                    .iter()
+                    .filter(|ev| ev.event_type() == EventType::ABSOLUTE),
+            );
+        }
+        if fingers != self.fingers {
+            if (fingers > 0) != (self.fingers > 0) {
+                out.push(InputEvent::new(
+                    EventType::KEY,
+                    Key::BTN_TOUCH.code(),
+                    (fingers > 0) as i32,
+                ));
+            }
+            let tool = |n: usize| {
+                n.checked_sub(1)
+                    .map(|i| TOOL_KEYS[i.min(TOOL_KEYS.len() - 1)])
+            };
+            let (old, new) = (tool(self.fingers), tool(fingers));
+            if old != new {
+                if let Some(old) = old {
+                    out.push(InputEvent::new(EventType::KEY, old.code(), 0));
+                }
+                if let Some(new) = new {
+                    out.push(InputEvent::new(EventType::KEY, new.code(), 1));
+                }
+            }
+            self.fingers = fingers;
+        }
+
+        if out.len() > start {
+            out.push(*frame.last().unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Synchronization;
+
+    fn abs(axis: AbsoluteAxisType, value: i32) -> InputEvent {
+        InputEvent::new(EventType::ABSOLUTE, axis.0, value)
+    }
+
+    fn syn() -> InputEvent {
+        InputEvent::new(EventType::SYNCHRONIZATION, Synchronization::SYN_REPORT.0, 0)
+    }
+
+    fn run(filter: &mut PalmRejection, frame: &[InputEvent]) -> Vec<(u16, u16, i32)> {
+        let mut out = Vec::new();
+        filter.process(frame, &mut out);
+        out.iter()
+            .map(|ev| (ev.event_type().0, ev.code(), ev.value()))
+            .collect()
+    }
+
+    #[test]
+    fn test_palm_dropped() {
+        let mut filter = PalmRejection::new().max_touch_major(10);
+        let palm = [
+            abs(AbsoluteAxisType::ABS_MT_SLOT, 0),
+            abs(AbsoluteAxisType::ABS_MT_TRACKING_ID, 1),
+            abs(AbsoluteAxisType::ABS_MT_TOUCH_MAJOR, 20),
+            syn(),
+        ];
+        assert!(run(&mut filter, &palm).is_empty());
+        assert!(filter.is_palm(0));
+        assert_eq!(filter.finger_count(), 0);
+
+        let finger = [
+            abs(AbsoluteAxisType::ABS_MT_SLOT, 1),
+            abs(AbsoluteAxisType::ABS_MT_TRACKING_ID, 2),
+            abs(AbsoluteAxisType::ABS_MT_TOUCH_MAJOR, 5),
+            syn(),
+        ];
+        let out = run(&mut filter, &finger);
+        assert_eq!(
+            out[0],
+            (EventType::ABSOLUTE.0, AbsoluteAxisType::ABS_MT_SLOT.0, 1)
+        );
+        assert!(out.contains(&(EventType::KEY.0, Key::BTN_TOUCH.0, 1)));
+        assert_eq!(filter.finger_count(), 1);
+    }
+
+    #[test]
+    fn test_reported_contact_becomes_palm() {
+        let mut filter = PalmRejection::new().max_pressure(100);
+        run(
+            &mut filter,
+            &[abs(AbsoluteAxisType::ABS_MT_TRACKING_ID, 1), syn()],
+        );
+        let out = run(
+            &mut filter,
+            &[abs(AbsoluteAxisType::ABS_MT_PRESSURE, 200), syn()],
+        );
+        assert!(out.contains(&(
+            EventType::ABSOLUTE.0,
+            AbsoluteAxisType::ABS_MT_TRACKING_ID.0,
+            -1
+        )));
+        assert!(out.contains(&(EventType::KEY.0, Key::BTN_TOUCH.0, 0)));
+    }
+}
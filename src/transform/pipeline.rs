@@ -0,0 +1,139 @@
+use crate::transform::{frames, EventTransform};
+use crate::{DeviceState, InputEvent};
+
+/// A chain of transforms, run one after the other.
+///
+/// Each frame is processed by the first transform, every frame it produces by the second, and
+/// so on. A pipeline is itself an [`EventTransform`], so it can be driven directly by a
+/// consumer, or handed to a [`Proxy`](crate::proxy::Proxy).
+///
+/// ```no_run
+/// use evdev::transform::{BounceKeys, CoalesceMotion, EventTransform, Pipeline};
+/// use std::time::Duration;
+///
+/// let mut device = evdev::Device::open("/dev/input/event0")?;
+/// let mut pipeline = Pipeline::new()
+///     .with(BounceKeys::new(Duration::from_millis(30)))
+///     .with(CoalesceMotion::new(4));
+/// let mut out = Vec::new();
+/// loop {
+///     let events: Vec<_> = device.fetch_events()?.collect();
+///     for frame in evdev::transform::frames(&events) {
+///         pipeline.process_with_state(frame, device.cached_state(), &mut out);
+///     }
+///     for ev in out.drain(..) {
+///         println!("{:?}", ev);
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Default)]
+pub struct Pipeline {
+    transforms: Vec<Box<dyn EventTransform + Send>>,
+    buf: Vec<InputEvent>,
+    next: Vec<InputEvent>,
+}
+
+impl Pipeline {
+    /// An empty pipeline, passing frames through unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a transform to the end of the chain.
+    pub fn with(mut self, transform: impl EventTransform + Send + 'static) -> Self {
+        self.push(transform);
+        self
+    }
+
+    /// Append a transform to the end of the chain.
+    pub fn push(&mut self, transform: impl EventTransform + Send + 'static) {
+        self.transforms.push(Box::new(transform));
+    }
+
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    fn run(
+        &mut self,
+        frame: &[InputEvent],
+        state: Option<&DeviceState>,
+        out: &mut Vec<InputEvent>,
+    ) {
+        self.buf.clear();
+        self.buf.extend_from_slice(frame);
+        for transform in &mut self.transforms {
+            self.next.clear();
+            for frame in frames(&self.buf) {
+                match state {
+                    Some(state) => transform.process_with_state(frame, state, &mut self.next),
+                    None => transform.process(frame, &mut self.next),
+                }
+            }
+            std::mem::swap(&mut self.buf, &mut self.next);
+        }
+        out.append(&mut self.buf);
+    }
+}
+
+impl EventTransform for Pipeline {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        self.run(frame, None, out)
+    }
+
+    /// Every transform sees the same `state`: that of the device the frames were read from,
+    /// not of the output of the transforms before it.
+    fn process_with_state(
+        &mut self,
+        frame: &[InputEvent],
+        state: &DeviceState,
+        out: &mut Vec<InputEvent>,
+    ) {
+        self.run(frame, Some(state), out)
+    }
+}
+
+impl std::fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("transforms", &self.transforms.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventType, Key};
+
+    /// Doubles every frame.
+    struct Repeat;
+
+    impl EventTransform for Repeat {
+        fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+            out.extend_from_slice(frame);
+            out.extend_from_slice(frame);
+        }
+    }
+
+    #[test]
+    fn chain_transforms() {
+        let key = InputEvent::new(EventType::KEY, Key::KEY_A.code(), 1);
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        let mut out = vec![syn];
+
+        Pipeline::new().process(&[key, syn], &mut out);
+        assert_eq!(out.len(), 3);
+
+        out.clear();
+        let mut pipeline = Pipeline::new().with(Repeat).with(Repeat);
+        pipeline.process(&[key, syn], &mut out);
+        assert_eq!(out.len(), 8);
+        assert_eq!(pipeline.len(), 2);
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+use crate::InputEvent;
+
+/// A pool of reusable event buffers.
+///
+/// Buffers handed out by [`get`](Self::get) return to the pool when dropped, keeping their
+/// capacity, so code that passes frames around (between threads, or through transforms that
+/// need scratch space) stops allocating once the pool has warmed up. Cloning the pool gives
+/// another handle to the same buffers.
+#[derive(Clone)]
+pub struct FramePool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    free: Mutex<Vec<Vec<InputEvent>>>,
+    max_retained: usize,
+}
+
+impl FramePool {
+    /// Create a pool that keeps at most `max_retained` idle buffers around.
+    pub fn new(max_retained: usize) -> Self {
+        FramePool {
+            inner: Arc::new(Inner {
+                free: Mutex::new(Vec::with_capacity(max_retained)),
+                max_retained,
+            }),
+        }
+    }
+
+    /// Take an empty buffer from the pool, allocating a new one if none are idle.
+    pub fn get(&self) -> PooledFrame {
+        let buf = self.inner.free.lock().unwrap().pop().unwrap_or_default();
+        PooledFrame {
+            buf,
+            pool: self.inner.clone(),
+        }
+    }
+
+    /// Returns the number of idle buffers in the pool.
+    pub fn idle(&self) -> usize {
+        self.inner.free.lock().unwrap().len()
+    }
+}
+
+impl Default for FramePool {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+impl fmt::Debug for FramePool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FramePool")
+            .field("idle", &self.idle())
+            .field("max_retained", &self.inner.max_retained)
+            .finish()
+    }
+}
+
+/// An event buffer borrowed from a [`FramePool`], returned to it on drop.
+pub struct PooledFrame {
+    buf: Vec<InputEvent>,
+    pool: Arc<Inner>,
+}
+
+impl PooledFrame {
+    /// Detach the buffer from the pool.
+    pub fn into_inner(mut self) -> Vec<InputEvent> {
+        std::mem::take(&mut self.buf)
+    }
+}
+
+impl Deref for PooledFrame {
+    type Target = Vec<InputEvent>;
+
+    fn deref(&self) -> &Vec<InputEvent> {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledFrame {
+    fn deref_mut(&mut self) -> &mut Vec<InputEvent> {
+        &mut self.buf
+    }
+}
+
+impl fmt::Debug for PooledFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.buf.fmt(f)
+    }
+}
+
+impl Drop for PooledFrame {
+    fn drop(&mut self) {
+        if self.buf.capacity() == 0 {
+            return;
+        }
+        let mut free = self.pool.free.lock().unwrap();
+        if free.len() < self.pool.max_retained {
+            let mut buf = std::mem::take(&mut self.buf);
+            buf.clear();
+            free.push(buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventType;
+
+    #[test]
+    fn buffers_are_reused() {
+        let pool = FramePool::new(1);
+        let mut a = pool.get();
+        a.push(InputEvent::new(EventType::SYNCHRONIZATION, 0, 0));
+        let ptr = a.as_ptr();
+        let b = pool.get();
+        drop(a);
+        drop(b);
+        assert_eq!(pool.idle(), 1);
+        let c = pool.get();
+        assert!(c.is_empty());
+        assert_eq!(c.as_ptr(), ptr);
+    }
+}
@@ -0,0 +1,198 @@
+use std::io;
+
+use crate::transform::EventTransform;
+use crate::{AbsInfo, AbsoluteAxisType, Device, EventType, InputEvent, UinputAbsSetup};
+
+/// A clockwise screen rotation, in multiples of 90 degrees.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    /// Returns `true` if this rotation swaps the X and Y axes.
+    #[inline]
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Rotation::Deg90 | Rotation::Deg270)
+    }
+}
+
+/// The pairs of axes rotated together, as (X, Y).
+const AXIS_PAIRS: [(AbsoluteAxisType, AbsoluteAxisType); 3] = [
+    (AbsoluteAxisType::ABS_X, AbsoluteAxisType::ABS_Y),
+    (
+        AbsoluteAxisType::ABS_MT_POSITION_X,
+        AbsoluteAxisType::ABS_MT_POSITION_Y,
+    ),
+    (
+        AbsoluteAxisType::ABS_MT_TOOL_X,
+        AbsoluteAxisType::ABS_MT_TOOL_Y,
+    ),
+];
+
+#[derive(Debug, Copy, Clone)]
+struct AxisPair {
+    x: AbsInfo,
+    y: AbsInfo,
+}
+
+/// Rotates absolute (and multitouch) coordinates by a multiple of 90 degrees.
+///
+/// Every rotation by a right angle maps each input axis onto exactly one output axis, possibly
+/// mirrored within its range, so events are rewritten one at a time without buffering.
+///
+/// When the rotation swaps the axes, the virtual output device has to advertise the swapped
+/// ranges too; [`output_abs_setups`](Self::output_abs_setups) provides them.
+#[derive(Debug, Clone)]
+pub struct RotateTransform {
+    rotation: Rotation,
+    pairs: [Option<AxisPair>; AXIS_PAIRS.len()],
+}
+
+impl RotateTransform {
+    /// Create a transform rotating `ABS_X`/`ABS_Y`, given the ranges of those axes.
+    pub fn new(rotation: Rotation, x: AbsInfo, y: AbsInfo) -> Self {
+        let mut pairs = [None; AXIS_PAIRS.len()];
+        pairs[0] = Some(AxisPair { x, y });
+        RotateTransform { rotation, pairs }
+    }
+
+    /// Also rotate `ABS_MT_POSITION_X`/`ABS_MT_POSITION_Y` and the `ABS_MT_TOOL_X`/`ABS_MT_TOOL_Y`
+    /// pair, given the ranges of the position axes.
+    pub fn with_mt(mut self, x: AbsInfo, y: AbsInfo) -> Self {
+        self.pairs[1] = Some(AxisPair { x, y });
+        self.pairs[2] = Some(AxisPair { x, y });
+        self
+    }
+
+    /// Create a transform for every supported axis pair of `device`, using the device's
+    /// cached axis ranges.
+    pub fn from_device(rotation: Rotation, device: &Device) -> io::Result<Self> {
+        let mut pairs = [None; AXIS_PAIRS.len()];
+        for (pair, (x, y)) in pairs.iter_mut().zip(AXIS_PAIRS) {
+            if let (Some(x), Some(y)) = (device.abs_info(x), device.abs_info(y)) {
+                *pair = Some(AxisPair { x, y });
+            }
+        }
+        Ok(RotateTransform { rotation, pairs })
+    }
+
+    /// Returns the rotation applied by this transform.
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Returns the axis setups the virtual output device should be created with.
+    ///
+    /// For 90 and 270 degree rotations, the X and Y ranges are swapped.
+    pub fn output_abs_setups(&self) -> Vec<UinputAbsSetup> {
+        let mut setups = Vec::new();
+        for (pair, (x, y)) in self.pairs.iter().zip(AXIS_PAIRS) {
+            if let Some(pair) = pair {
+                let (out_x, out_y) = if self.rotation.swaps_axes() {
+                    (pair.y, pair.x)
+                } else {
+                    (pair.x, pair.y)
+                };
+                setups.push(UinputAbsSetup::new(x, out_x));
+                setups.push(UinputAbsSetup::new(y, out_y));
+            }
+        }
+        setups
+    }
+
+    /// Rotate a single event. Events that aren't on a rotated axis are returned unchanged.
+    pub fn rotate_event(&self, ev: InputEvent) -> InputEvent {
+        if ev.event_type() != EventType::ABSOLUTE {
+            return ev;
+        }
+        let code = AbsoluteAxisType(ev.code());
+        for (pair, (x, y)) in self.pairs.iter().zip(AXIS_PAIRS) {
+            let pair = match pair {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let is_x = if code == x {
+                true
+            } else if code == y {
+                false
+            } else {
+                continue;
+            };
+            let value = ev.value();
+            let flip_x = pair.x.minimum() + pair.x.maximum() - value;
+            let flip_y = pair.y.minimum() + pair.y.maximum() - value;
+            let (code, value) = match (self.rotation, is_x) {
+                (Rotation::Deg0, true) => (x, value),
+                (Rotation::Deg0, false) => (y, value),
+                (Rotation::Deg90, true) => (y, value),
+                (Rotation::Deg90, false) => (x, flip_y),
+                (Rotation::Deg180, true) => (x, flip_x),
+                (Rotation::Deg180, false) => (y, flip_y),
+                (Rotation::Deg270, true) => (y, flip_x),
+                (Rotation::Deg270, false) => (x, value),
+            };
+            return InputEvent(libc::input_event {
+                code: code.0,
+                value,
+                ..ev.0
+            });
+        }
+        ev
+    }
+}
+
+impl EventTransform for RotateTransform {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        out.extend(frame.iter().map(|&ev| self.rotate_event(ev)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abs(axis: AbsoluteAxisType, value: i32) -> InputEvent {
+        InputEvent::new(EventType::ABSOLUTE, axis.0, value)
+    }
+
+    fn rotated(rotation: Rotation, ev: InputEvent) -> (u16, i32) {
+        let t = RotateTransform::new(
+            rotation,
+            AbsInfo::new(0, 0, 100, 0, 0, 0),
+            AbsInfo::new(0, 0, 50, 0, 0, 0),
+        );
+        let ev = t.rotate_event(ev);
+        (ev.code(), ev.value())
+    }
+
+    #[test]
+    fn test_rotate_axes() {
+        let x = abs(AbsoluteAxisType::ABS_X, 10);
+        let y = abs(AbsoluteAxisType::ABS_Y, 20);
+        let (abs_x, abs_y) = (AbsoluteAxisType::ABS_X.0, AbsoluteAxisType::ABS_Y.0);
+        assert_eq!(rotated(Rotation::Deg0, x), (abs_x, 10));
+        assert_eq!(rotated(Rotation::Deg90, x), (abs_y, 10));
+        assert_eq!(rotated(Rotation::Deg90, y), (abs_x, 30));
+        assert_eq!(rotated(Rotation::Deg180, x), (abs_x, 90));
+        assert_eq!(rotated(Rotation::Deg180, y), (abs_y, 30));
+        assert_eq!(rotated(Rotation::Deg270, x), (abs_y, 90));
+        assert_eq!(rotated(Rotation::Deg270, y), (abs_x, 20));
+    }
+
+    #[test]
+    fn test_output_setups_swap() {
+        let t = RotateTransform::new(
+            Rotation::Deg90,
+            AbsInfo::new(0, 0, 100, 0, 0, 0),
+            AbsInfo::new(0, 0, 50, 0, 0, 0),
+        );
+        let setups = t.output_abs_setups();
+        assert_eq!(setups[0].axis(), AbsoluteAxisType::ABS_X);
+        assert_eq!(setups[0].abs_info().maximum(), 50);
+        assert_eq!(setups[1].abs_info().maximum(), 100);
+    }
+}
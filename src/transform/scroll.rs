@@ -0,0 +1,288 @@
+use std::io;
+
+use crate::transform::{is_syn_report, EventTransform};
+use crate::uinput::VirtualDeviceBuilder;
+use crate::{
+    AbsInfo, AbsoluteAxisType, AttributeSet, EventType, FingerTracker, InputEvent, InputEventKind,
+    RelativeAxisType, Synchronization,
+};
+
+/// High-resolution wheel units per wheel detent, as defined by the kernel.
+const HI_RES_PER_DETENT: i32 = 120;
+
+/// How scrolling is triggered on the touchpad.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ScrollMethod {
+    /// Moving two fingers scrolls vertically and horizontally.
+    TwoFinger,
+    /// A single finger that lands to the right of `min_x` scrolls vertically.
+    Edge { min_x: i32 },
+}
+
+impl ScrollMethod {
+    /// Edge scrolling on the rightmost `fraction` of the X axis, e.g. 0.1 for the outer 10%.
+    pub fn edge_fraction(x: AbsInfo, fraction: f32) -> Self {
+        let width = (x.maximum() - x.minimum()) as f32;
+        ScrollMethod::Edge {
+            min_x: x.maximum() - (width * fraction) as i32,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct Axis {
+    /// Fractional high-resolution units not yet emitted.
+    remainder: f32,
+    /// High-resolution units emitted since the last full detent.
+    detent: i32,
+}
+
+/// Converts touchpad motion into scroll wheel events.
+///
+/// While scrolling, `REL_WHEEL_HI_RES`/`REL_HWHEEL_HI_RES` events are emitted continuously and
+/// the classic `REL_WHEEL`/`REL_HWHEEL` events are emitted whenever the accumulated motion
+/// crosses a full detent, so both old and new consumers scroll smoothly.
+///
+/// Absolute position events that drove the scroll are consumed; everything else is passed
+/// through, so this is usually placed in front of a
+/// [`TouchpadPointer`](crate::transform::TouchpadPointer).
+#[derive(Debug, Clone)]
+pub struct ScrollTransform {
+    method: ScrollMethod,
+    natural: bool,
+    distance: f32,
+    fingers: FingerTracker,
+    /// Whether the current touch landed in the edge zone, once known.
+    edge: Option<bool>,
+    /// The last reported position, kept across touches since the kernel only reports axes
+    /// that changed.
+    pos: (Option<i32>, Option<i32>),
+    last: Option<(i32, i32)>,
+    vertical: Axis,
+    horizontal: Axis,
+}
+
+impl ScrollTransform {
+    /// Create a scroll transform. `distance` is how far, in touchpad units, the fingers must
+    /// move to scroll by one wheel detent.
+    pub fn new(method: ScrollMethod, distance: f32) -> Self {
+        ScrollTransform {
+            method,
+            natural: false,
+            distance,
+            fingers: FingerTracker::new(),
+            edge: None,
+            pos: (None, None),
+            last: None,
+            vertical: Axis::default(),
+            horizontal: Axis::default(),
+        }
+    }
+
+    /// Invert the scroll direction so that content follows the fingers.
+    pub fn natural(mut self, natural: bool) -> Self {
+        self.natural = natural;
+        self
+    }
+
+    /// Returns `true` while the current touch is being interpreted as a scroll.
+    pub fn scrolling(&self) -> bool {
+        match self.method {
+            ScrollMethod::TwoFinger => self.fingers.touching() && self.fingers.finger_count() == 2,
+            ScrollMethod::Edge { .. } => self.fingers.touching() && self.edge == Some(true),
+        }
+    }
+
+    /// Enable the wheel axes this transform emits on `builder`.
+    pub fn configure_output<'a>(
+        &self,
+        builder: VirtualDeviceBuilder<'a>,
+    ) -> io::Result<VirtualDeviceBuilder<'a>> {
+        let axes: AttributeSet<RelativeAxisType> = [
+            RelativeAxisType::REL_WHEEL,
+            RelativeAxisType::REL_WHEEL_HI_RES,
+            RelativeAxisType::REL_HWHEEL,
+            RelativeAxisType::REL_HWHEEL_HI_RES,
+        ]
+        .into_iter()
+        .collect();
+        builder.with_relative_axes(&axes)
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+        self.vertical = Axis::default();
+        self.horizontal = Axis::default();
+    }
+
+    fn scroll(
+        axis: &mut Axis,
+        delta: f32,
+        hi_res: RelativeAxisType,
+        lo_res: RelativeAxisType,
+        out: &mut Vec<InputEvent>,
+    ) {
+        let units = axis.remainder + delta;
+        let whole = units.trunc();
+        axis.remainder = units - whole;
+        let whole = whole as i32;
+        if whole == 0 {
+            return;
+        }
+        out.push(InputEvent::new(EventType::RELATIVE, hi_res.0, whole));
+        axis.detent += whole;
+        let detents = axis.detent / HI_RES_PER_DETENT;
+        if detents != 0 {
+            axis.detent -= detents * HI_RES_PER_DETENT;
+            out.push(InputEvent::new(EventType::RELATIVE, lo_res.0, detents));
+        }
+    }
+
+    fn motion(&mut self, out: &mut Vec<InputEvent>) {
+        let (x, y) = match self.pos {
+            (Some(x), Some(y)) => (x, y),
+            _ => return,
+        };
+        let (last_x, last_y) = match self.last.replace((x, y)) {
+            Some(last) => last,
+            None => return,
+        };
+        let scale = HI_RES_PER_DETENT as f32 / self.distance;
+        let sign = if self.natural { -1.0 } else { 1.0 };
+        // moving the fingers down scrolls down, which is a negative wheel value
+        let dy = -(y - last_y) as f32 * scale * sign;
+        let dx = (x - last_x) as f32 * scale * sign;
+        Self::scroll(
+            &mut self.vertical,
+            dy,
+            RelativeAxisType::REL_WHEEL_HI_RES,
+            RelativeAxisType::REL_WHEEL,
+            out,
+        );
+        if self.method == ScrollMethod::TwoFinger {
+            Self::scroll(
+                &mut self.horizontal,
+                dx,
+                RelativeAxisType::REL_HWHEEL_HI_RES,
+                RelativeAxisType::REL_HWHEEL,
+                out,
+            );
+        }
+    }
+}
+
+impl EventTransform for ScrollTransform {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let was_scrolling = self.scrolling();
+        let mut position = Vec::new();
+        let mut passthrough = Vec::new();
+        for ev in frame.iter().filter(|ev| !is_syn_report(ev)) {
+            self.fingers.process_event(ev);
+            match ev.kind() {
+                InputEventKind::AbsAxis(AbsoluteAxisType::ABS_X) => {
+                    self.pos.0 = Some(ev.value());
+                    position.push(*ev);
+                    continue;
+                }
+                InputEventKind::AbsAxis(AbsoluteAxisType::ABS_Y) => {
+                    self.pos.1 = Some(ev.value());
+                    position.push(*ev);
+                    continue;
+                }
+                _ => {}
+            }
+            passthrough.push(*ev);
+        }
+        if !self.fingers.touching() {
+            self.edge = None;
+        }
+
+        if let ScrollMethod::Edge { min_x } = self.method {
+            // only a touch that lands in the edge zone starts an edge scroll
+            if self.fingers.touching() && self.edge.is_none() {
+                if let Some(x) = self.pos.0 {
+                    self.edge = Some(self.fingers.finger_count() <= 1 && x > min_x);
+                }
+            }
+        }
+
+        let start = out.len();
+        out.extend(passthrough);
+        if self.scrolling() {
+            if !was_scrolling {
+                self.reset();
+            }
+            self.motion(out);
+        } else {
+            if was_scrolling {
+                self.reset();
+            }
+            out.extend(position);
+        }
+
+        if out.len() > start {
+            out.push(InputEvent::new(
+                EventType::SYNCHRONIZATION,
+                Synchronization::SYN_REPORT.0,
+                0,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::{Pipeline, TouchpadPointer};
+    use crate::Key;
+
+    #[test]
+    fn two_finger_scroll_then_pointer() {
+        let key = |key: Key, value| InputEvent::new(EventType::KEY, key.code(), value);
+        let x = |value| InputEvent::new(EventType::ABSOLUTE, AbsoluteAxisType::ABS_X.0, value);
+        let y = |value| InputEvent::new(EventType::ABSOLUTE, AbsoluteAxisType::ABS_Y.0, value);
+        let mut pipeline = Pipeline::new()
+            .with(ScrollTransform::new(ScrollMethod::TwoFinger, 50.0))
+            .with(TouchpadPointer::new());
+        let mut run = |frame: &[InputEvent]| -> Vec<(u16, i32)> {
+            let mut frame = frame.to_vec();
+            frame.push(InputEvent::new(EventType::SYNCHRONIZATION, 0, 0));
+            let mut out = Vec::new();
+            pipeline.process(&frame, &mut out);
+            out.iter()
+                .filter(|ev| !is_syn_report(ev))
+                .map(|ev| (ev.code(), ev.value()))
+                .collect()
+        };
+        let rel = |axis: RelativeAxisType, value| (axis.0, value);
+
+        // Two fingers down and moving scroll, without moving the pointer
+        let down = [
+            key(Key::BTN_TOUCH, 1),
+            key(Key::BTN_TOOL_DOUBLETAP, 1),
+            x(100),
+            y(100),
+        ];
+        assert_eq!(run(&down), []);
+        assert_eq!(
+            run(&[y(150)]),
+            [
+                rel(RelativeAxisType::REL_WHEEL_HI_RES, -120),
+                rel(RelativeAxisType::REL_WHEEL, -1)
+            ]
+        );
+        assert_eq!(
+            run(&[x(125)]),
+            [rel(RelativeAxisType::REL_HWHEEL_HI_RES, 60)]
+        );
+
+        // Lifting a finger hands the motion back to the pointer
+        let one = [
+            key(Key::BTN_TOOL_DOUBLETAP, 0),
+            key(Key::BTN_TOOL_FINGER, 1),
+        ];
+        assert_eq!(run(&one), []);
+        assert_eq!(run(&[x(130), y(160)]), []);
+        assert_eq!(run(&[x(140)]), [rel(RelativeAxisType::REL_X, 10)]);
+    }
+}
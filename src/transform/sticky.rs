@@ -0,0 +1,274 @@
+use std::time::{Duration, SystemTime};
+
+use crate::transform::{is_syn_report, EventTransform};
+use crate::{AttributeSet, EventType, InputEvent, Key};
+
+const DEFAULT_MODIFIERS: [Key; 8] = [
+    Key::KEY_LEFTSHIFT,
+    Key::KEY_RIGHTSHIFT,
+    Key::KEY_LEFTCTRL,
+    Key::KEY_RIGHTCTRL,
+    Key::KEY_LEFTALT,
+    Key::KEY_RIGHTALT,
+    Key::KEY_LEFTMETA,
+    Key::KEY_RIGHTMETA,
+];
+
+/// The state of a modifier under [`StickyKeys`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum StickyState {
+    /// The modifier is up, unless it is physically held.
+    #[default]
+    Released,
+    /// The modifier was tapped, and stays down until the next non-modifier key is released.
+    Latched,
+    /// The modifier was double-tapped, and stays down until it is tapped again.
+    Locked,
+}
+
+#[derive(Debug, Clone)]
+struct Modifier {
+    key: Key,
+    state: StickyState,
+    /// The modifier is physically held.
+    held: bool,
+    /// No other key was pressed since the modifier was pressed, so releasing it is a tap.
+    tap: bool,
+    last_tap: Option<SystemTime>,
+}
+
+/// Lets modifiers be pressed one at a time, for users who can't hold several keys at once.
+///
+/// Tapping a modifier latches it: it stays down until a non-modifier key is pressed and
+/// released. Tapping it twice within the double-tap time locks it until it is tapped once
+/// more. Modifiers held while another key is pressed work as usual.
+///
+/// The state of the modifiers is available through [`state`](Self::state) and
+/// [`active`](Self::active), e.g. for an on-screen indicator.
+#[derive(Debug, Clone)]
+pub struct StickyKeys {
+    modifiers: Vec<Modifier>,
+    double_tap: Duration,
+    /// Non-modifier keys that are held.
+    pressed: AttributeSet<Key>,
+    frame: Vec<InputEvent>,
+}
+
+impl Default for StickyKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StickyKeys {
+    /// Create a transform for the shift, control, alt and meta keys, with a double-tap time
+    /// of 500ms.
+    pub fn new() -> Self {
+        StickyKeys {
+            modifiers: Vec::new(),
+            double_tap: Duration::from_millis(500),
+            pressed: AttributeSet::new(),
+            frame: Vec::new(),
+        }
+        .modifiers(&DEFAULT_MODIFIERS)
+    }
+
+    /// Treat `keys` as the modifiers, instead of the default ones.
+    pub fn modifiers(mut self, keys: &[Key]) -> Self {
+        self.modifiers = keys
+            .iter()
+            .map(|&key| Modifier {
+                key,
+                state: StickyState::Released,
+                held: false,
+                tap: false,
+                last_tap: None,
+            })
+            .collect();
+        self
+    }
+
+    /// Two taps of a modifier within `time` lock it.
+    pub fn double_tap(mut self, time: Duration) -> Self {
+        self.double_tap = time;
+        self
+    }
+
+    /// Returns the state of `key`, which is [`Released`](StickyState::Released) for keys that
+    /// aren't modifiers.
+    pub fn state(&self, key: Key) -> StickyState {
+        self.modifiers
+            .iter()
+            .find(|m| m.key == key)
+            .map_or(StickyState::Released, |m| m.state)
+    }
+
+    /// Iterate over the modifiers that are latched or locked.
+    pub fn active(&self) -> impl Iterator<Item = (Key, StickyState)> + '_ {
+        self.modifiers
+            .iter()
+            .filter(|m| m.state != StickyState::Released)
+            .map(|m| (m.key, m.state))
+    }
+
+    /// Release every latched and locked modifier that isn't physically held.
+    pub fn release_all(&mut self, out: &mut Vec<InputEvent>) {
+        let start = out.len();
+        for m in self.modifiers.iter_mut().filter(|m| !m.held) {
+            if m.state != StickyState::Released {
+                m.state = StickyState::Released;
+                out.push(InputEvent::new_now(EventType::KEY, m.key.code(), 0));
+            }
+        }
+        if out.len() > start {
+            out.push(InputEvent::new_now(EventType::SYNCHRONIZATION, 0, 0));
+        }
+    }
+
+    fn press_modifier(&mut self, i: usize, ev: &InputEvent) {
+        for m in self.modifiers.iter_mut().filter(|m| m.held) {
+            m.tap = false;
+        }
+        let m = &mut self.modifiers[i];
+        m.held = true;
+        m.tap = true;
+        if m.state == StickyState::Released {
+            self.frame.push(*ev);
+        }
+    }
+
+    fn release_modifier(&mut self, i: usize, ev: &InputEvent) {
+        let double_tap = self.double_tap;
+        let m = &mut self.modifiers[i];
+        m.held = false;
+        let time = ev.timestamp();
+        let next = if !m.tap {
+            StickyState::Released
+        } else {
+            match m.state {
+                StickyState::Released => StickyState::Latched,
+                StickyState::Latched => {
+                    let quick = m
+                        .last_tap
+                        .and_then(|last| time.duration_since(last).ok())
+                        .is_some_and(|since| since <= double_tap);
+                    if quick {
+                        StickyState::Locked
+                    } else {
+                        StickyState::Released
+                    }
+                }
+                StickyState::Locked => StickyState::Released,
+            }
+        };
+        if m.tap {
+            m.last_tap = Some(time);
+        }
+        m.state = next;
+        if next == StickyState::Released {
+            self.frame.push(*ev);
+        }
+    }
+
+    fn release_latched(&mut self, ev: &InputEvent) {
+        for m in self.modifiers.iter_mut() {
+            if m.state == StickyState::Latched && !m.held {
+                m.state = StickyState::Released;
+                self.frame.push(InputEvent(libc::input_event {
+                    code: m.key.code(),
+                    value: 0,
+                    ..ev.0
+                }));
+            }
+        }
+    }
+}
+
+impl EventTransform for StickyKeys {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        self.frame.clear();
+        for ev in frame {
+            if is_syn_report(ev) {
+                continue;
+            }
+            if ev.event_type() != EventType::KEY || ev.value() == 2 {
+                self.frame.push(*ev);
+                continue;
+            }
+            let key = Key::new(ev.code());
+            let modifier = self.modifiers.iter().position(|m| m.key == key);
+            match (modifier, ev.value()) {
+                (Some(i), 0) => self.release_modifier(i, ev),
+                (Some(i), _) => self.press_modifier(i, ev),
+                (None, 0) => {
+                    self.pressed.remove(key);
+                    self.frame.push(*ev);
+                    if self.pressed.iter().next().is_none() {
+                        self.release_latched(ev);
+                    }
+                }
+                (None, _) => {
+                    for m in self.modifiers.iter_mut() {
+                        m.tap = false;
+                    }
+                    self.pressed.insert(key);
+                    self.frame.push(*ev);
+                }
+            }
+        }
+        if !self.frame.is_empty() {
+            out.extend_from_slice(&self.frame);
+            out.extend(frame.last().filter(|ev| is_syn_report(ev)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(sticky: &mut StickyKeys, events: &[(Key, i32)]) -> Vec<(Key, i32)> {
+        let mut out = Vec::new();
+        for &(key, value) in events {
+            let frame = [
+                InputEvent::new(EventType::KEY, key.code(), value),
+                InputEvent::new(EventType::SYNCHRONIZATION, 0, 0),
+            ];
+            sticky.process(&frame, &mut out);
+        }
+        out.iter()
+            .filter(|ev| ev.event_type() == EventType::KEY)
+            .map(|ev| (Key::new(ev.code()), ev.value()))
+            .collect()
+    }
+
+    #[test]
+    fn latch_and_lock() {
+        let (shift, a) = (Key::KEY_LEFTSHIFT, Key::KEY_A);
+        let mut sticky = StickyKeys::new();
+
+        // A tap latches shift for the next key
+        let out = keys(&mut sticky, &[(shift, 1), (shift, 0)]);
+        assert_eq!(out, [(shift, 1)]);
+        assert_eq!(sticky.state(shift), StickyState::Latched);
+        let out = keys(&mut sticky, &[(a, 1), (a, 0), (a, 1), (a, 0)]);
+        assert_eq!(out, [(a, 1), (a, 0), (shift, 0), (a, 1), (a, 0)]);
+
+        // A double tap locks it until the next tap
+        keys(
+            &mut sticky,
+            &[(shift, 1), (shift, 0), (shift, 1), (shift, 0)],
+        );
+        assert_eq!(
+            sticky.active().collect::<Vec<_>>(),
+            [(shift, StickyState::Locked)]
+        );
+        assert_eq!(keys(&mut sticky, &[(a, 1), (a, 0)]), [(a, 1), (a, 0)]);
+        assert_eq!(keys(&mut sticky, &[(shift, 1), (shift, 0)]), [(shift, 0)]);
+
+        // Held as usual, it doesn't latch
+        let out = keys(&mut sticky, &[(shift, 1), (a, 1), (a, 0), (shift, 0)]);
+        assert_eq!(out, [(shift, 1), (a, 1), (a, 0), (shift, 0)]);
+        assert_eq!(sticky.state(shift), StickyState::Released);
+    }
+}
@@ -0,0 +1,499 @@
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use crate::transform::{is_syn_report, EventTransform};
+use crate::{AbsInfo, AbsoluteAxisType, Device, EventType, InputEvent, Key};
+
+/// Scale `value` from the axis range to `[0.0, 1.0]`, without clamping.
+fn to_unit(info: &AbsInfo, value: i32) -> f64 {
+    let (min, max) = (f64::from(info.minimum()), f64::from(info.maximum()));
+    if max <= min {
+        return 0.0;
+    }
+    (f64::from(value) - min) / (max - min)
+}
+
+/// The inverse of [`to_unit`], rounding and clamping to the axis range.
+fn from_unit(info: &AbsInfo, value: f64) -> i32 {
+    let (min, max) = (f64::from(info.minimum()), f64::from(info.maximum()));
+    (min + value * (max - min)).round().clamp(min, max) as i32
+}
+
+/// Solve the 3x3 system `m * p = v` by Cramer's rule, or `None` if it is singular.
+fn solve3(m: [[f64; 3]; 3], v: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(m);
+    if d.abs() < 1e-12 {
+        return None;
+    }
+    let mut p = [0.0; 3];
+    for (i, p) in p.iter_mut().enumerate() {
+        let mut mi = m;
+        for (row, &v) in mi.iter_mut().zip(&v) {
+            row[i] = v;
+        }
+        *p = det(mi) / d;
+    }
+    Some(p)
+}
+
+/// An affine correction of touchscreen coordinates, as a 3x3 matrix whose last row is
+/// `0 0 1`.
+///
+/// Coordinates are scaled to `[0.0, 1.0]` across the axis ranges before the matrix is
+/// applied, which makes the six values the same as libinput's `LIBINPUT_CALIBRATION_MATRIX`
+/// udev property, and the [`Display`](fmt::Display) output can be used there directly.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TouchCalibration {
+    matrix: [f64; 6],
+}
+
+impl Default for TouchCalibration {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl TouchCalibration {
+    /// The calibration leaving coordinates unchanged.
+    pub const IDENTITY: TouchCalibration = TouchCalibration {
+        matrix: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+    };
+
+    /// Create a calibration from the first two rows of the matrix, in row order.
+    pub fn new(matrix: [f64; 6]) -> Self {
+        TouchCalibration { matrix }
+    }
+
+    /// Fit the calibration mapping the `measured` points onto their `targets` with least
+    /// squares, all scaled to `[0.0, 1.0]`.
+    ///
+    /// Returns `None` unless there are at least three pairs of points, not on a line.
+    pub fn fit(targets: &[(f64, f64)], measured: &[(f64, f64)]) -> Option<Self> {
+        // The normal equations of both rows share the same matrix
+        let mut m = [[0.0; 3]; 3];
+        let (mut vx, mut vy) = ([0.0; 3], [0.0; 3]);
+        for (&(tx, ty), &(x, y)) in targets.iter().zip(measured) {
+            let row = [x, y, 1.0];
+            for i in 0..3 {
+                for j in 0..3 {
+                    m[i][j] += row[i] * row[j];
+                }
+                vx[i] += row[i] * tx;
+                vy[i] += row[i] * ty;
+            }
+        }
+        let [a, b, c] = solve3(m, vx)?;
+        let [d, e, f] = solve3(m, vy)?;
+        Some(TouchCalibration {
+            matrix: [a, b, c, d, e, f],
+        })
+    }
+
+    /// Returns the first two rows of the matrix, in row order.
+    pub fn matrix(&self) -> [f64; 6] {
+        self.matrix
+    }
+
+    /// Map a point, scaled to `[0.0, 1.0]`.
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let [a, b, c, d, e, f] = self.matrix;
+        (a * x + b * y + c, d * x + e * y + f)
+    }
+
+    /// Returns the ranges that make the kernel report calibrated coordinates for axes with the
+    /// ranges `x` and `y`, for writing back with [`Device::set_abs_info`].
+    ///
+    /// Only scaling and offsets can be expressed this way; returns `None` if the calibration
+    /// rotates, shears or mirrors.
+    pub fn abs_infos(&self, x: AbsInfo, y: AbsInfo) -> Option<(AbsInfo, AbsInfo)> {
+        let [a, b, c, d, e, f] = self.matrix;
+        if b.abs() > 1e-3 || d.abs() > 1e-3 || a <= 0.0 || e <= 0.0 {
+            return None;
+        }
+        // Solve (raw - min') / (max' - min') = scale * (raw - min) / (max - min) + offset
+        let range = |info: AbsInfo, scale: f64, offset: f64| {
+            let width = f64::from(info.maximum()) - f64::from(info.minimum());
+            let min = f64::from(info.minimum()) - offset * width / scale;
+            let max = min + width / scale;
+            if min < f64::from(i32::MIN) || max > f64::from(i32::MAX) {
+                return None;
+            }
+            Some(AbsInfo::new(
+                info.value(),
+                min.round() as i32,
+                max.round() as i32,
+                info.fuzz(),
+                info.flat(),
+                info.resolution(),
+            ))
+        };
+        Some((range(x, a, c)?, range(y, e, f)?))
+    }
+
+    /// Write the calibration back to the position axes of `device` with
+    /// [`Device::set_abs_info`], so every reader gets calibrated coordinates.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the calibration can't be expressed as axis
+    /// ranges; see [`abs_infos`](Self::abs_infos).
+    pub fn write_to_device(&self, device: &mut Device) -> io::Result<()> {
+        for (x, y) in POSITION_AXES {
+            let (Some(x_info), Some(y_info)) = (device.abs_info(x), device.abs_info(y)) else {
+                continue;
+            };
+            let (x_info, y_info) = self.abs_infos(x_info, y_info).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "calibration needs more than scaling and offsets",
+                )
+            })?;
+            device.set_abs_info(x, x_info)?;
+            device.set_abs_info(y, y_info)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TouchCalibration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.matrix;
+        write!(f, "{} {} {} {} {} {}", a, b, c, d, e, g)
+    }
+}
+
+impl FromStr for TouchCalibration {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid calibration matrix: {}", s),
+            )
+        };
+        let mut matrix = [0.0; 6];
+        let mut values = s.split_whitespace();
+        for m in &mut matrix {
+            *m = values
+                .next()
+                .ok_or_else(invalid)?
+                .parse()
+                .map_err(|_| invalid())?;
+        }
+        if values.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(TouchCalibration { matrix })
+    }
+}
+
+/// The pairs of axes calibrated together, as (X, Y).
+const POSITION_AXES: [(AbsoluteAxisType, AbsoluteAxisType); 2] = [
+    (AbsoluteAxisType::ABS_X, AbsoluteAxisType::ABS_Y),
+    (
+        AbsoluteAxisType::ABS_MT_POSITION_X,
+        AbsoluteAxisType::ABS_MT_POSITION_Y,
+    ),
+];
+
+/// Collects touches on target points to fit a [`TouchCalibration`].
+///
+/// Show the user each target in turn, at the position [`target`](Self::target) returns, and
+/// feed the events of the touchscreen to [`capture`](Self::capture). The position of each
+/// touch is averaged over its duration; lifting the finger moves on to the next target. Four
+/// targets near the corners and one in the middle make a good fit.
+#[derive(Debug, Clone)]
+pub struct TouchCalibrationCapture {
+    x: AbsInfo,
+    y: AbsInfo,
+    targets: Vec<(f64, f64)>,
+    measured: Vec<(f64, f64)>,
+    position: (i32, i32),
+    touching: bool,
+    /// Sum and count of the positions of the current touch.
+    sum: (f64, f64, u32),
+}
+
+impl TouchCalibrationCapture {
+    /// Start capturing touches on `targets`, given as screen positions scaled to
+    /// `[0.0, 1.0]`, for a touchscreen whose `ABS_X` and `ABS_Y` have the ranges `x` and `y`.
+    pub fn new(targets: Vec<(f64, f64)>, x: AbsInfo, y: AbsInfo) -> Self {
+        TouchCalibrationCapture {
+            x,
+            y,
+            targets,
+            measured: Vec::new(),
+            position: (x.value(), y.value()),
+            touching: false,
+            sum: (0.0, 0.0, 0),
+        }
+    }
+
+    /// Start capturing touches on `targets` on `device`.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if the device lacks `ABS_X` or `ABS_Y`.
+    pub fn from_device(targets: Vec<(f64, f64)>, device: &Device) -> io::Result<Self> {
+        let axis = |axis| {
+            device.abs_info(axis).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::Unsupported, "device has no ABS_X and ABS_Y")
+            })
+        };
+        let (x, y) = (
+            axis(AbsoluteAxisType::ABS_X)?,
+            axis(AbsoluteAxisType::ABS_Y)?,
+        );
+        Ok(Self::new(targets, x, y))
+    }
+
+    /// Returns the target to touch next, or `None` once every target was touched.
+    pub fn target(&self) -> Option<(f64, f64)> {
+        self.targets.get(self.measured.len()).copied()
+    }
+
+    /// Returns the index of the target to touch next.
+    pub fn progress(&self) -> usize {
+        self.measured.len()
+    }
+
+    /// Start over with the current target, e.g. after a touch far off it.
+    pub fn retry(&mut self) {
+        self.measured.pop();
+    }
+
+    /// Record an event from the touchscreen. Returns `true` when a touch on the current target
+    /// ends, so the next target should be shown.
+    pub fn capture(&mut self, ev: &InputEvent) -> bool {
+        if self.target().is_none() {
+            return false;
+        }
+        match ev.event_type() {
+            EventType::ABSOLUTE if ev.code() == AbsoluteAxisType::ABS_X.0 => {
+                self.position.0 = ev.value()
+            }
+            EventType::ABSOLUTE if ev.code() == AbsoluteAxisType::ABS_Y.0 => {
+                self.position.1 = ev.value()
+            }
+            EventType::KEY if ev.code() == Key::BTN_TOUCH.code() => {
+                self.touching = ev.value() != 0;
+            }
+            _ if is_syn_report(ev) && self.touching => {
+                let (x, y) = (
+                    to_unit(&self.x, self.position.0),
+                    to_unit(&self.y, self.position.1),
+                );
+                self.sum = (self.sum.0 + x, self.sum.1 + y, self.sum.2 + 1);
+            }
+            _ if is_syn_report(ev) && self.sum.2 > 0 => {
+                let (x, y, n) = std::mem::replace(&mut self.sum, (0.0, 0.0, 0));
+                self.measured.push((x / f64::from(n), y / f64::from(n)));
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// Fit the calibration to the touches so far. Returns `None` with fewer than three
+    /// targets touched, or if they are on a line.
+    pub fn finish(&self) -> Option<TouchCalibration> {
+        TouchCalibration::fit(&self.targets, &self.measured)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct Position {
+    x: i32,
+    y: i32,
+    dirty: bool,
+}
+
+/// Applies a [`TouchCalibration`] to `ABS_X`/`ABS_Y` and, per slot, to
+/// `ABS_MT_POSITION_X`/`ABS_MT_POSITION_Y`.
+///
+/// The matrix can mix the axes, so both coordinates of a touch are written whenever either
+/// changes, after the other events of its slot. Results are clamped to the axis ranges.
+#[derive(Debug, Clone)]
+pub struct TouchCalibrateTransform {
+    calibration: TouchCalibration,
+    /// Ranges of the axis pairs the device has.
+    ranges: [Option<(AbsInfo, AbsInfo)>; POSITION_AXES.len()],
+    single: Position,
+    slots: Vec<Position>,
+    slot: usize,
+    frame: Vec<InputEvent>,
+}
+
+impl TouchCalibrateTransform {
+    /// Create a transform for axes with the given ranges; `mt` are the ranges of the
+    /// multitouch position axes, if the device has them.
+    pub fn new(
+        calibration: TouchCalibration,
+        (x, y): (AbsInfo, AbsInfo),
+        mt: Option<(AbsInfo, AbsInfo)>,
+    ) -> Self {
+        let single = Position {
+            x: x.value(),
+            y: y.value(),
+            dirty: false,
+        };
+        TouchCalibrateTransform {
+            calibration,
+            ranges: [Some((x, y)), mt],
+            single,
+            slots: Vec::new(),
+            slot: 0,
+            frame: Vec::new(),
+        }
+    }
+
+    /// Create a transform for the position axes of `device`.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if the device lacks `ABS_X` or `ABS_Y`.
+    pub fn from_device(calibration: TouchCalibration, device: &Device) -> io::Result<Self> {
+        let pair = |(x, y)| Some((device.abs_info(x)?, device.abs_info(y)?));
+        let single = pair(POSITION_AXES[0]).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "device has no ABS_X and ABS_Y")
+        })?;
+        Ok(Self::new(calibration, single, pair(POSITION_AXES[1])))
+    }
+
+    /// Append the calibrated coordinates of `position` on the axis pair `pair`, if they
+    /// changed.
+    fn flush(&mut self, pair: usize, template: &InputEvent) {
+        let position = if pair == 0 {
+            &mut self.single
+        } else {
+            match self.slots.get_mut(self.slot) {
+                Some(position) => position,
+                None => return,
+            }
+        };
+        let Some((x_info, y_info)) = self.ranges[pair].filter(|_| position.dirty) else {
+            return;
+        };
+        position.dirty = false;
+        let (x, y) = self
+            .calibration
+            .apply(to_unit(&x_info, position.x), to_unit(&y_info, position.y));
+        let (x_axis, y_axis) = POSITION_AXES[pair];
+        for (axis, value) in [
+            (x_axis, from_unit(&x_info, x)),
+            (y_axis, from_unit(&y_info, y)),
+        ] {
+            self.frame.push(InputEvent(libc::input_event {
+                type_: EventType::ABSOLUTE.0,
+                code: axis.0,
+                value,
+                ..template.0
+            }));
+        }
+    }
+}
+
+impl EventTransform for TouchCalibrateTransform {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let Some(syn) = frame.last().filter(|ev| is_syn_report(ev)) else {
+            return;
+        };
+        self.frame.clear();
+        for ev in &frame[..frame.len() - 1] {
+            if ev.event_type() != EventType::ABSOLUTE {
+                self.frame.push(*ev);
+                continue;
+            }
+            let axis = AbsoluteAxisType(ev.code());
+            let position = match axis {
+                AbsoluteAxisType::ABS_X | AbsoluteAxisType::ABS_Y if self.ranges[0].is_some() => {
+                    &mut self.single
+                }
+                AbsoluteAxisType::ABS_MT_POSITION_X | AbsoluteAxisType::ABS_MT_POSITION_Y
+                    if self.ranges[1].is_some() =>
+                {
+                    if self.slots.len() <= self.slot {
+                        self.slots.resize(self.slot + 1, Position::default());
+                    }
+                    &mut self.slots[self.slot]
+                }
+                AbsoluteAxisType::ABS_MT_SLOT => {
+                    self.flush(1, ev);
+                    self.slot = ev.value().max(0) as usize;
+                    self.frame.push(*ev);
+                    continue;
+                }
+                _ => {
+                    self.frame.push(*ev);
+                    continue;
+                }
+            };
+            match axis {
+                AbsoluteAxisType::ABS_X | AbsoluteAxisType::ABS_MT_POSITION_X => {
+                    position.x = ev.value()
+                }
+                _ => position.y = ev.value(),
+            }
+            position.dirty = true;
+        }
+        self.flush(1, syn);
+        self.flush(0, syn);
+        out.extend_from_slice(&self.frame);
+        out.push(*syn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_fit_and_apply() {
+        // A panel reporting 0..=1000 whose touches land 10% right and 5% low of where they
+        // are shown, at 80% scale
+        let range = AbsInfo::new(0, 0, 1000, 0, 0, 0);
+        let targets = vec![(0.1, 0.1), (0.9, 0.1), (0.9, 0.9), (0.1, 0.9), (0.5, 0.5)];
+        let raw = |(x, y): (f64, f64)| ((x * 0.8 + 0.1) * 1000.0, (y * 0.8 + 0.05) * 1000.0);
+        let mut capture = TouchCalibrationCapture::new(targets.clone(), range, range);
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        for &target in &targets {
+            let (x, y) = raw(target);
+            // Two reports a few units apart, averaged
+            for (dx, value) in [(-2, 1), (2, 1), (0, 0)] {
+                let events = [
+                    InputEvent::new(EventType::KEY, Key::BTN_TOUCH.code(), value),
+                    InputEvent::new(EventType::ABSOLUTE, 0, x as i32 + dx),
+                    InputEvent::new(EventType::ABSOLUTE, 1, y as i32),
+                    syn,
+                ];
+                let done: Vec<bool> = events.iter().map(|ev| capture.capture(ev)).collect();
+                assert_eq!(done[3], value == 0);
+            }
+        }
+        assert_eq!(capture.target(), None);
+
+        let calibration = capture.finish().unwrap();
+        let (x, y) = calibration.apply(0.1 * 0.8 + 0.1, 0.5 * 0.8 + 0.05);
+        assert!((x - 0.1).abs() < 1e-3 && (y - 0.5).abs() < 1e-3);
+        assert_eq!(
+            calibration.to_string().parse::<TouchCalibration>().unwrap(),
+            calibration
+        );
+
+        let mut transform = TouchCalibrateTransform::new(calibration, (range, range), None);
+        let mut out = Vec::new();
+        transform.process(
+            &[InputEvent::new(EventType::ABSOLUTE, 0, 900), syn],
+            &mut out,
+        );
+        let values: Vec<_> = out.iter().map(|ev| (ev.code(), ev.value())).collect();
+        assert_eq!(values, [(0, 1000), (1, 0), (0, 0)]);
+
+        // Writing back stretches the range the raw values cover
+        let (x_info, y_info) = calibration.abs_infos(range, range).unwrap();
+        assert_eq!((x_info.minimum(), x_info.maximum()), (100, 900));
+        assert_eq!((y_info.minimum(), y_info.maximum()), (50, 850));
+    }
+}
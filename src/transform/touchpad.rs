@@ -0,0 +1,258 @@
+use std::io;
+
+use crate::transform::is_syn_report;
+use crate::transform::EventTransform;
+use crate::uinput::VirtualDeviceBuilder;
+use crate::{
+    AbsoluteAxisType, AttributeSet, DeviceState, EventType, FingerTracker, InputEvent,
+    InputEventKind, Key, RelativeAxisType, Synchronization,
+};
+
+/// Converts absolute touchpad motion into relative pointer motion.
+///
+/// Only the single-touch `ABS_X`/`ABS_Y` axes are used, so the pointer follows the first finger
+/// down. Motion is scaled by [`speed`](Self::speed) and then accelerated proportionally to how
+/// far the finger moved within a frame.
+///
+/// The pointer only moves while a single finger is on the pad, leaving multi-finger motion to
+/// gestures such as [`ScrollTransform`](crate::transform::ScrollTransform).
+///
+/// Physical clicks on a clickpad (`BTN_LEFT`) are emulated as a left, right or middle click
+/// depending on how many fingers are on the pad when the button goes down: one, two, or three.
+/// Relative events from earlier transforms are passed through; all other events are dropped, so
+/// the output only contains what a plain mouse would send.
+#[derive(Debug, Clone)]
+pub struct TouchpadPointer {
+    speed: f32,
+    acceleration: f32,
+    fingers: FingerTracker,
+    /// The last reported position. The kernel only reports axes that changed, so this is kept
+    /// across touches.
+    pos: (Option<i32>, Option<i32>),
+    last: Option<(i32, i32)>,
+    remainder: (f32, f32),
+    pressed: Option<Key>,
+}
+
+impl Default for TouchpadPointer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TouchpadPointer {
+    pub fn new() -> Self {
+        TouchpadPointer {
+            speed: 1.0,
+            acceleration: 0.0,
+            fingers: FingerTracker::new(),
+            pos: (None, None),
+            last: None,
+            remainder: (0.0, 0.0),
+            pressed: None,
+        }
+    }
+
+    /// Set the base multiplier from touchpad units to pointer units. Defaults to 1.0.
+    pub fn speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Set the acceleration factor. A frame moving `d` touchpad units is scaled by an additional
+    /// `1 + acceleration * d`. Defaults to 0.0, i.e. no acceleration.
+    pub fn acceleration(mut self, acceleration: f32) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    /// Enable the capabilities on `builder` that this transform emits: relative X/Y motion and
+    /// the left, right and middle buttons.
+    pub fn configure_output<'a>(
+        &self,
+        builder: VirtualDeviceBuilder<'a>,
+    ) -> io::Result<VirtualDeviceBuilder<'a>> {
+        let keys: AttributeSet<Key> = [Key::BTN_LEFT, Key::BTN_RIGHT, Key::BTN_MIDDLE]
+            .into_iter()
+            .collect();
+        let axes: AttributeSet<RelativeAxisType> =
+            [RelativeAxisType::REL_X, RelativeAxisType::REL_Y]
+                .into_iter()
+                .collect();
+        builder.with_keys(&keys)?.with_relative_axes(&axes)
+    }
+
+    fn click_button(&self) -> Key {
+        match self.fingers.finger_count() {
+            2 => Key::BTN_RIGHT,
+            3 => Key::BTN_MIDDLE,
+            _ => Key::BTN_LEFT,
+        }
+    }
+
+    fn motion(&mut self, out: &mut Vec<InputEvent>) {
+        let (x, y) = match self.pos {
+            (Some(x), Some(y)) => (x, y),
+            _ => return,
+        };
+        let (last_x, last_y) = match self.last.replace((x, y)) {
+            Some(last) => last,
+            None => return,
+        };
+        let (dx, dy) = ((x - last_x) as f32, (y - last_y) as f32);
+        let factor = self.speed * (1.0 + self.acceleration * dx.hypot(dy));
+        let rx = dx * factor + self.remainder.0;
+        let ry = dy * factor + self.remainder.1;
+        let (ix, iy) = (rx.trunc(), ry.trunc());
+        self.remainder = (rx - ix, ry - iy);
+        if ix != 0.0 {
+            out.push(InputEvent::new(
+                EventType::RELATIVE,
+                RelativeAxisType::REL_X.0,
+                ix as i32,
+            ));
+        }
+        if iy != 0.0 {
+            out.push(InputEvent::new(
+                EventType::RELATIVE,
+                RelativeAxisType::REL_Y.0,
+                iy as i32,
+            ));
+        }
+    }
+}
+
+impl EventTransform for TouchpadPointer {
+    fn process(&mut self, frame: &[InputEvent], out: &mut Vec<InputEvent>) {
+        let start = out.len();
+        let fingers = self.fingers.finger_count();
+        for ev in frame.iter().filter(|ev| !is_syn_report(ev)) {
+            self.fingers.process_event(ev);
+            match ev.kind() {
+                InputEventKind::AbsAxis(AbsoluteAxisType::ABS_X) => self.pos.0 = Some(ev.value()),
+                InputEventKind::AbsAxis(AbsoluteAxisType::ABS_Y) => self.pos.1 = Some(ev.value()),
+                InputEventKind::Key(key) if ev.value() != 0 => match key {
+                    Key::BTN_LEFT if self.pressed.is_none() => {
+                        let button = self.click_button();
+                        self.pressed = Some(button);
+                        out.push(InputEvent::new(EventType::KEY, button.code(), 1));
+                    }
+                    _ => {}
+                },
+                InputEventKind::Key(Key::BTN_LEFT) => {
+                    if let Some(button) = self.pressed.take() {
+                        out.push(InputEvent::new(EventType::KEY, button.code(), 0));
+                    }
+                }
+                InputEventKind::RelAxis(_) => out.push(*ev),
+                _ => {}
+            }
+        }
+
+        if fingers != self.fingers.finger_count() {
+            // the reported position may jump to another contact
+            self.last = None;
+        }
+        if self.fingers.touching() && self.fingers.finger_count() <= 1 {
+            self.motion(out);
+        } else {
+            self.last = None;
+            self.remainder = (0.0, 0.0);
+        }
+
+        if out.len() > start {
+            out.push(InputEvent::new(
+                EventType::SYNCHRONIZATION,
+                Synchronization::SYN_REPORT.0,
+                0,
+            ));
+        }
+    }
+
+    /// Seeds the position from the device's axes when the transform starts mid-touch, or on a
+    /// touch that doesn't report an axis because it didn't change.
+    fn process_with_state(
+        &mut self,
+        frame: &[InputEvent],
+        state: &DeviceState,
+        out: &mut Vec<InputEvent>,
+    ) {
+        if let Some(abs) = state.abs_vals() {
+            let axis = |axis: AbsoluteAxisType| abs.get(axis.0 as usize).map(|info| info.value);
+            self.pos.0 = self.pos.0.or_else(|| axis(AbsoluteAxisType::ABS_X));
+            self.pos.1 = self.pos.1.or_else(|| axis(AbsoluteAxisType::ABS_Y));
+        }
+        self.process(frame, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: Key, value: i32) -> InputEvent {
+        InputEvent::new(EventType::KEY, key.code(), value)
+    }
+
+    fn abs(axis: AbsoluteAxisType, value: i32) -> InputEvent {
+        InputEvent::new(EventType::ABSOLUTE, axis.0, value)
+    }
+
+    fn run(pointer: &mut TouchpadPointer, frame: &[InputEvent]) -> Vec<(u16, u16, i32)> {
+        let mut frame = frame.to_vec();
+        frame.push(InputEvent::new(EventType::SYNCHRONIZATION, 0, 0));
+        let mut out = Vec::new();
+        pointer.process(&frame, &mut out);
+        out.iter()
+            .map(|ev| (ev.event_type().0, ev.code(), ev.value()))
+            .collect()
+    }
+
+    #[test]
+    fn touch_move_click_lift() {
+        const ABS_X: AbsoluteAxisType = AbsoluteAxisType::ABS_X;
+        const ABS_Y: AbsoluteAxisType = AbsoluteAxisType::ABS_Y;
+        let (rel, syn) = (EventType::RELATIVE.0, (0, 0, 0));
+        let mut pointer = TouchpadPointer::new();
+
+        // Touch-down only anchors the pointer
+        let down = [
+            key(Key::BTN_TOUCH, 1),
+            key(Key::BTN_TOOL_FINGER, 1),
+            abs(ABS_X, 100),
+            abs(ABS_Y, 100),
+        ];
+        assert_eq!(run(&mut pointer, &down), []);
+        assert_eq!(
+            run(&mut pointer, &[abs(ABS_X, 110), abs(ABS_Y, 95)]),
+            [(rel, 0, 10), (rel, 1, -5), syn]
+        );
+
+        // Two fingers down: no motion, and the click is a right click
+        let two = [
+            key(Key::BTN_TOOL_FINGER, 0),
+            key(Key::BTN_TOOL_DOUBLETAP, 1),
+        ];
+        assert_eq!(run(&mut pointer, &two), []);
+        assert_eq!(run(&mut pointer, &[abs(ABS_X, 150)]), []);
+        assert_eq!(
+            run(&mut pointer, &[key(Key::BTN_LEFT, 1)]),
+            [(1, Key::BTN_RIGHT.code(), 1), syn]
+        );
+        assert_eq!(
+            run(&mut pointer, &[key(Key::BTN_LEFT, 0)]),
+            [(1, Key::BTN_RIGHT.code(), 0), syn]
+        );
+
+        // Lift, then touch down again where only X changed; moving along X alone still moves
+        let lift = [key(Key::BTN_TOUCH, 0), key(Key::BTN_TOOL_DOUBLETAP, 0)];
+        assert_eq!(run(&mut pointer, &lift), []);
+        let down = [
+            key(Key::BTN_TOUCH, 1),
+            key(Key::BTN_TOOL_FINGER, 1),
+            abs(ABS_X, 200),
+        ];
+        assert_eq!(run(&mut pointer, &down), []);
+        assert_eq!(run(&mut pointer, &[abs(ABS_X, 204)]), [(rel, 0, 4), syn]);
+    }
+}
@@ -0,0 +1,395 @@
+//! Virtual HID devices via uhid.
+//!
+//! Where [`uinput`](crate::uinput) creates evdev nodes directly, uhid creates a HID device from
+//! a report descriptor. The kernel's HID drivers then bind to it as they would to real
+//! hardware, so consumers that talk HID themselves (games using hidraw, firmware updaters)
+//! see an actual HID device, and evdev nodes are created by the usual HID input mapping.
+//!
+//! ```no_run
+//! use evdev::uhid::UhidDeviceBuilder;
+//!
+//! # const DESCRIPTOR: &[u8] = &[];
+//! let mut device = UhidDeviceBuilder::new()?
+//!     .name("Virtual gamepad")
+//!     .report_descriptor(DESCRIPTOR)
+//!     .build()?;
+//! device.write_report(&[0x01, 0x00, 0x7f, 0x7f])?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+
+use crate::error::Error;
+use crate::{BusType, InputId};
+
+const UHID_PATH: &str = "/dev/uhid";
+
+/// The largest report that can be passed through uhid.
+pub const UHID_DATA_MAX: usize = 4096;
+/// The largest report descriptor uhid accepts.
+pub const HID_MAX_DESCRIPTOR_SIZE: usize = 4096;
+
+// Event types from <linux/uhid.h>
+const UHID_DESTROY: u32 = 1;
+const UHID_START: u32 = 2;
+const UHID_STOP: u32 = 3;
+const UHID_OPEN: u32 = 4;
+const UHID_CLOSE: u32 = 5;
+const UHID_OUTPUT: u32 = 6;
+const UHID_GET_REPORT: u32 = 9;
+const UHID_GET_REPORT_REPLY: u32 = 10;
+const UHID_CREATE2: u32 = 11;
+const UHID_INPUT2: u32 = 12;
+const UHID_SET_REPORT: u32 = 13;
+const UHID_SET_REPORT_REPLY: u32 = 14;
+
+/// `sizeof(struct uhid_event)`: the type, plus the largest payload (`uhid_create2_req`).
+const UHID_EVENT_SIZE: usize = 4 + 128 + 64 + 64 + 2 + 2 + 4 * 4 + HID_MAX_DESCRIPTOR_SIZE;
+
+/// The kind of a HID report.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReportType {
+    Feature,
+    Output,
+    Input,
+}
+
+impl ReportType {
+    fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(ReportType::Feature),
+            1 => Some(ReportType::Output),
+            2 => Some(ReportType::Input),
+            _ => None,
+        }
+    }
+}
+
+/// A request from the kernel to a [`UhidDevice`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UhidEvent {
+    /// A HID driver bound to the device. `flags` are the `UHID_DEV_*` flags.
+    Start { flags: u64 },
+    /// The HID driver unbound; no more reports are accepted until the next `Start`.
+    Stop,
+    /// A consumer opened the device; reports should now be sent.
+    Open,
+    /// The last consumer closed the device.
+    Close,
+    /// A report to deliver to the device, such as LED state.
+    Output {
+        data: Vec<u8>,
+        report_type: ReportType,
+    },
+    /// A request to read a report. Answer it with [`UhidDevice::reply_get_report`].
+    GetReport {
+        id: u32,
+        report_number: u8,
+        report_type: ReportType,
+    },
+    /// A request to write a report. Answer it with [`UhidDevice::reply_set_report`].
+    SetReport {
+        id: u32,
+        report_number: u8,
+        report_type: ReportType,
+        data: Vec<u8>,
+    },
+}
+
+/// A builder for a [`UhidDevice`].
+pub struct UhidDeviceBuilder<'a> {
+    file: File,
+    name: &'a [u8],
+    phys: &'a [u8],
+    uniq: &'a [u8],
+    id: InputId,
+    country: u32,
+    descriptor: &'a [u8],
+}
+
+impl<'a> UhidDeviceBuilder<'a> {
+    /// Open `/dev/uhid` to start building a device.
+    pub fn new() -> io::Result<Self> {
+        Self::with_uhid_path(UHID_PATH)
+    }
+
+    /// Like [`new`](Self::new), but opens uhid at `path`.
+    pub fn with_uhid_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+            .open(path)?;
+        Ok(UhidDeviceBuilder {
+            file,
+            name: Default::default(),
+            phys: Default::default(),
+            uniq: Default::default(),
+            id: InputId::new(BusType::BUS_USB, 0x1234, 0x5678, 0x111),
+            country: 0,
+            descriptor: Default::default(),
+        })
+    }
+
+    #[inline]
+    pub fn name<S: AsRef<[u8]> + ?Sized>(mut self, name: &'a S) -> Self {
+        self.name = name.as_ref();
+        self
+    }
+
+    #[inline]
+    pub fn physical_path<S: AsRef<[u8]> + ?Sized>(mut self, phys: &'a S) -> Self {
+        self.phys = phys.as_ref();
+        self
+    }
+
+    #[inline]
+    pub fn unique_name<S: AsRef<[u8]> + ?Sized>(mut self, uniq: &'a S) -> Self {
+        self.uniq = uniq.as_ref();
+        self
+    }
+
+    #[inline]
+    pub fn input_id(mut self, id: InputId) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Set the HID country code. Defaults to 0 (not localized).
+    #[inline]
+    pub fn country(mut self, country: u32) -> Self {
+        self.country = country;
+        self
+    }
+
+    /// Set the report descriptor describing the device's reports.
+    #[inline]
+    pub fn report_descriptor(mut self, descriptor: &'a [u8]) -> Self {
+        self.descriptor = descriptor;
+        self
+    }
+
+    pub fn build(self) -> io::Result<UhidDevice> {
+        if self.descriptor.len() > HID_MAX_DESCRIPTOR_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "report descriptor is too long",
+            ));
+        }
+
+        let mut ev = vec![0u8; UHID_EVENT_SIZE];
+        put(&mut ev, 0, &UHID_CREATE2.to_ne_bytes());
+        put_str(&mut ev[4..132], self.name)?;
+        put_str(&mut ev[132..196], self.phys)?;
+        put_str(&mut ev[196..260], self.uniq)?;
+        put(&mut ev, 260, &(self.descriptor.len() as u16).to_ne_bytes());
+        put(&mut ev, 262, &self.id.bus_type().0.to_ne_bytes());
+        put(&mut ev, 264, &u32::from(self.id.vendor()).to_ne_bytes());
+        put(&mut ev, 268, &u32::from(self.id.product()).to_ne_bytes());
+        put(&mut ev, 272, &u32::from(self.id.version()).to_ne_bytes());
+        put(&mut ev, 276, &self.country.to_ne_bytes());
+        put(&mut ev, 280, self.descriptor);
+
+        let mut device = UhidDevice {
+            file: self.file,
+            buf: ev,
+        };
+        device.write_event(UHID_EVENT_SIZE)?;
+        Ok(device)
+    }
+}
+
+fn put(buf: &mut [u8], offset: usize, data: &[u8]) {
+    buf[offset..offset + data.len()].copy_from_slice(data);
+}
+
+/// Copy a string into a fixed-size, NUL-terminated field.
+fn put_str(field: &mut [u8], s: &[u8]) -> io::Result<()> {
+    if s.len() >= field.len() || s.contains(&0) {
+        return Err(Error::InvalidName.into());
+    }
+    field[..s.len()].copy_from_slice(s);
+    Ok(())
+}
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_ne_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// A virtual HID device created through uhid.
+///
+/// The device is destroyed when this is dropped.
+pub struct UhidDevice {
+    file: File,
+    /// Scratch space for one `struct uhid_event`.
+    buf: Vec<u8>,
+}
+
+impl UhidDevice {
+    /// Write the first `len` bytes of `buf` as one event.
+    fn write_event(&mut self, len: usize) -> io::Result<()> {
+        let written = (&self.file).write(&self.buf[..len])?;
+        if written != len {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        Ok(())
+    }
+
+    fn start_event(&mut self, ty: u32) {
+        self.buf.fill(0);
+        put(&mut self.buf, 0, &ty.to_ne_bytes());
+    }
+
+    /// Send an input report to the kernel, as if the device had produced it.
+    pub fn write_report(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.len() > UHID_DATA_MAX {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "report is too long",
+            ));
+        }
+        self.start_event(UHID_INPUT2);
+        put(&mut self.buf, 4, &(data.len() as u16).to_ne_bytes());
+        put(&mut self.buf, 6, data);
+        self.write_event(6 + data.len())
+    }
+
+    /// Answer a [`UhidEvent::GetReport`] request, with the report or an errno.
+    pub fn reply_get_report(&mut self, id: u32, result: Result<&[u8], i32>) -> io::Result<()> {
+        let (err, data) = match result {
+            Ok(data) if data.len() > UHID_DATA_MAX => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "report is too long",
+                ))
+            }
+            Ok(data) => (0, data),
+            Err(errno) => (errno as u16, &[][..]),
+        };
+        self.start_event(UHID_GET_REPORT_REPLY);
+        put(&mut self.buf, 4, &id.to_ne_bytes());
+        put(&mut self.buf, 8, &err.to_ne_bytes());
+        put(&mut self.buf, 10, &(data.len() as u16).to_ne_bytes());
+        put(&mut self.buf, 12, data);
+        self.write_event(12 + data.len())
+    }
+
+    /// Answer a [`UhidEvent::SetReport`] request, with 0 on success or an errno.
+    pub fn reply_set_report(&mut self, id: u32, errno: i32) -> io::Result<()> {
+        self.start_event(UHID_SET_REPORT_REPLY);
+        put(&mut self.buf, 4, &id.to_ne_bytes());
+        put(&mut self.buf, 8, &(errno as u16).to_ne_bytes());
+        self.write_event(10)
+    }
+
+    /// Destroy the device, without closing the uhid fd.
+    pub fn destroy(mut self) -> io::Result<()> {
+        self.start_event(UHID_DESTROY);
+        self.write_event(4)
+    }
+
+    /// Read the next request from the kernel, if one is pending.
+    ///
+    /// Returns `Ok(None)` if there is nothing to read. The fd becomes readable when a request
+    /// is pending, so this can be driven by epoll or an async runtime.
+    pub fn next_event(&mut self) -> io::Result<Option<UhidEvent>> {
+        loop {
+            let n = match (&self.file).read(&mut self.buf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            if n < 4 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            // Skip event types this version doesn't know about
+            if let Some(ev) = parse_event(&self.buf[..n]) {
+                return Ok(Some(ev));
+            }
+        }
+    }
+}
+
+fn parse_event(buf: &[u8]) -> Option<UhidEvent> {
+    let report_type = |offset: usize| ReportType::from_raw(*buf.get(offset)?);
+    let data = |offset: usize, size: u16| {
+        let end = (offset + size as usize).min(buf.len());
+        buf[offset..end].to_vec()
+    };
+    let ev = match get_u32(buf, 0) {
+        UHID_START => UhidEvent::Start {
+            flags: u64::from_ne_bytes(buf.get(4..12)?.try_into().unwrap()),
+        },
+        UHID_STOP => UhidEvent::Stop,
+        UHID_OPEN => UhidEvent::Open,
+        UHID_CLOSE => UhidEvent::Close,
+        UHID_OUTPUT => {
+            let size = get_u16(buf.get(..4102)?, 4100);
+            UhidEvent::Output {
+                data: data(4, size),
+                report_type: report_type(4102)?,
+            }
+        }
+        UHID_GET_REPORT => UhidEvent::GetReport {
+            id: get_u32(buf.get(..8)?, 4),
+            report_number: *buf.get(8)?,
+            report_type: report_type(9)?,
+        },
+        UHID_SET_REPORT => UhidEvent::SetReport {
+            id: get_u32(buf.get(..8)?, 4),
+            report_number: *buf.get(8)?,
+            report_type: report_type(9)?,
+            data: data(12, get_u16(buf.get(..12)?, 10)),
+        },
+        _ => return None,
+    };
+    Some(ev)
+}
+
+impl AsRawFd for UhidDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_requests() {
+        let mut buf = vec![0u8; UHID_EVENT_SIZE];
+        put(&mut buf, 0, &UHID_OUTPUT.to_ne_bytes());
+        put(&mut buf, 4, &[0x01, 0x02]);
+        put(&mut buf, 4100, &2u16.to_ne_bytes());
+        buf[4102] = 1;
+        assert_eq!(
+            parse_event(&buf),
+            Some(UhidEvent::Output {
+                data: vec![0x01, 0x02],
+                report_type: ReportType::Output
+            })
+        );
+
+        buf.fill(0);
+        put(&mut buf, 0, &UHID_GET_REPORT.to_ne_bytes());
+        put(&mut buf, 4, &7u32.to_ne_bytes());
+        buf[8] = 3;
+        assert_eq!(
+            parse_event(&buf),
+            Some(UhidEvent::GetReport {
+                id: 7,
+                report_number: 3,
+                report_type: ReportType::Feature
+            })
+        );
+    }
+}
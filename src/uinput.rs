@@ -2,43 +2,214 @@
 //!
 //! This is quite useful when testing/debugging devices, or synchronization.
 
+use crate::clock::{Clock, SharedClock, SystemClock};
 use crate::constants::EventType;
+use crate::error::{ioctl_error, Error};
 use crate::inputid::{BusType, InputId};
+use crate::watchdog::{self, Watchdog};
 use crate::{
-    sys, AttributeSet, AttributeSetRef, InputEvent, Key, LedType, MiscType, RelativeAxisType,
-    SwitchType,
+    sys, AbsoluteAxisType, AttributeSet, AttributeSetRef, FFEffect, FFEffectType, InputEvent, Key,
+    LedType, MiscType, OpenOptions, RelativeAxisType, StreamValidator, SwitchType, UinputAbsSetup,
 };
 use libc::O_NONBLOCK;
-use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
-use std::os::unix::{fs::OpenOptionsExt, io::AsRawFd};
-
-const UINPUT_PATH: &str = "/dev/uinput";
+use nix::errno::Errno;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, IoSlice, Write};
+use std::mem;
+use std::os::unix::{
+    fs::OpenOptionsExt,
+    io::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd},
+};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct VirtualDeviceBuilder<'a> {
-    file: File,
+    /// The uinput fd; `None` when building an in-memory device.
+    file: Option<File>,
     name: &'a [u8],
     id: Option<libc::input_id>,
+    ff_effects_max: u32,
+    caps: Capabilities,
+    version: Option<u32>,
+    /// Axis setups to pass through `uinput_user_dev` on kernels without `UI_ABS_SETUP`.
+    legacy_abs: Vec<UinputAbsSetup>,
+}
+
+/// The first uinput version with `UI_DEV_SETUP`, `UI_ABS_SETUP` and `UI_GET_VERSION`.
+const UINPUT_VERSION_SETUP: u32 = 5;
+
+/// The first uinput version with `UI_GET_SYSNAME`.
+const UINPUT_VERSION_SYSNAME: u32 = 4;
+
+/// Where the kernel lists virtual input devices, uinput's among them.
+const SYS_VIRTUAL_INPUT: &str = "/sys/devices/virtual/input";
+
+/// Find the sysfs directory of the virtual device called `name`, for uinput versions that
+/// can't report it.
+///
+/// Devices with the same name can't be told apart, so the one created last is taken.
+fn find_input_dir(name: Option<&[u8]>) -> io::Result<PathBuf> {
+    let Some(name) = name else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "uinput can't report the sysfs name of the device",
+        ));
+    };
+    let mut newest: Option<(u32, PathBuf)> = None;
+    for entry in fs::read_dir(SYS_VIRTUAL_INPUT)?.flatten() {
+        let number = entry
+            .file_name()
+            .to_str()
+            .and_then(|f| f.strip_prefix("input").and_then(|n| n.parse::<u32>().ok()));
+        let Some(number) = number else {
+            continue;
+        };
+        let Ok(dev_name) = fs::read(entry.path().join("name")) else {
+            continue;
+        };
+        if dev_name.strip_suffix(b"\n").unwrap_or(&dev_name) == name
+            && newest.as_ref().is_none_or(|(n, _)| number > *n)
+        {
+            newest = Some((number, entry.path()));
+        }
+    }
+    match newest {
+        Some((_, dir)) => Ok(dir),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "Failed to find input {:?} in {}",
+                String::from_utf8_lossy(name),
+                SYS_VIRTUAL_INPUT
+            ),
+        )),
+    }
+}
+
+/// Query the uinput protocol version behind `file`.
+///
+/// Returns `None` on kernels older than 4.5, which predate the version query (and speak
+/// version 4 or older).
+fn query_version(file: &File) -> io::Result<Option<u32>> {
+    let mut version = 0;
+    match unsafe { sys::ui_get_version(file.as_raw_fd(), &mut version) } {
+        Ok(_) => Ok(Some(version)),
+        Err(Errno::ENOTTY | Errno::EINVAL) => Ok(None),
+        Err(errno) => Err(ioctl_error("UI_GET_VERSION")(errno)),
+    }
+}
+
+/// The event types and codes enabled on a virtual device.
+#[derive(Debug, Default, Clone)]
+struct Capabilities {
+    types: AttributeSet<EventType>,
+    keys: AttributeSet<Key>,
+    relative: AttributeSet<RelativeAxisType>,
+    absolute: AttributeSet<AbsoluteAxisType>,
+    misc: AttributeSet<MiscType>,
+    leds: AttributeSet<LedType>,
+    switches: AttributeSet<SwitchType>,
+}
+
+impl Capabilities {
+    fn allows(&self, ev: &InputEvent) -> bool {
+        let code = ev.code();
+        match ev.event_type() {
+            EventType::SYNCHRONIZATION => true,
+            EventType::KEY => self.keys.contains(Key::new(code)),
+            EventType::RELATIVE => self.relative.contains(RelativeAxisType(code)),
+            EventType::ABSOLUTE => self.absolute.contains(AbsoluteAxisType(code)),
+            EventType::MISC => self.misc.contains(MiscType(code)),
+            EventType::LED => self.leds.contains(LedType(code)),
+            EventType::SWITCH => self.switches.contains(SwitchType(code)),
+            ty => self.types.contains(ty),
+        }
+    }
+}
+
+/// Returns true if virtual devices can be created, i.e. uinput is loaded and accessible.
+pub fn is_available() -> bool {
+    OpenOptions::new().open_uinput().is_ok()
 }
 
 impl<'a> VirtualDeviceBuilder<'a> {
+    /// Start building a virtual device, opening `/dev/uinput`.
+    ///
+    /// If that fails, the error converts into an [`Error::Open`] whose
+    /// [`reason`](crate::OpenFailure) tells apart a missing module, a missing device node and
+    /// a lack of permissions. The OS error, e.g. `EACCES`, is found with
+    /// [`Error::raw_os_error_of`] rather than [`io::Error::raw_os_error`].
     pub fn new() -> io::Result<Self> {
-        let mut options = OpenOptions::new();
+        Self::with_options(&OpenOptions::new())
+    }
+
+    /// Like [`new`](Self::new), but opens uinput at `path`. Useful in containers or on systems
+    /// where it lives at `/dev/input/uinput`.
+    pub fn with_uinput_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_options(&OpenOptions::new().uinput_path(path))
+    }
 
-        // Open in write-only, in nonblocking mode.
-        let file = options
-            .write(true)
-            .custom_flags(O_NONBLOCK)
-            .open(UINPUT_PATH)?;
+    /// Like [`new`](Self::new), but opens uinput with the path and descriptor flags set by
+    /// `options`.
+    pub fn with_options(options: &OpenOptions) -> io::Result<Self> {
+        let file = options.open_uinput()?;
+        let version = query_version(&file)?;
 
         Ok(VirtualDeviceBuilder {
-            file,
+            file: Some(file),
             name: Default::default(),
             id: None,
+            ff_effects_max: 0,
+            caps: Capabilities::default(),
+            version,
+            legacy_abs: Vec::new(),
         })
     }
 
+    /// Start building a device that records emitted events in memory instead of creating a
+    /// real device through uinput.
+    ///
+    /// This lets tests of code that emits events run without root or a uinput-enabled kernel.
+    /// Retrieve the events with [`VirtualDevice::memory_sink`]. Key, switch and LED state is
+    /// tracked from the emitted events; force feedback is never requested.
+    pub fn in_memory() -> Self {
+        VirtualDeviceBuilder {
+            file: None,
+            name: Default::default(),
+            id: None,
+            ff_effects_max: 0,
+            caps: Capabilities::default(),
+            version: None,
+            legacy_abs: Vec::new(),
+        }
+    }
+
+    /// Set a capability bit with one of the `UI_SET_*BIT` ioctls. In-memory devices only
+    /// record capabilities in `caps`.
+    fn set_bit(
+        &self,
+        ioctl: unsafe fn(RawFd, nix::sys::ioctl::ioctl_param_type) -> nix::Result<libc::c_int>,
+        bit: u16,
+    ) -> io::Result<()> {
+        if let Some(file) = &self.file {
+            unsafe { ioctl(file.as_raw_fd(), bit as nix::sys::ioctl::ioctl_param_type)? };
+        }
+        Ok(())
+    }
+
+    /// Returns the uinput protocol version, or `None` on kernels older than 4.5 that can't
+    /// report it. Older versions are supported through the legacy setup interface.
+    pub fn uinput_version(&self) -> Option<u32> {
+        self.version
+    }
+
+    fn is_legacy(&self) -> bool {
+        self.version.is_none_or(|v| v < UINPUT_VERSION_SETUP)
+    }
+
     #[inline]
     pub fn name<S: AsRef<[u8]> + ?Sized>(mut self, name: &'a S) -> Self {
         self.name = name.as_ref();
@@ -51,128 +222,197 @@ impl<'a> VirtualDeviceBuilder<'a> {
         self
     }
 
-    pub fn with_keys(self, keys: &AttributeSetRef<Key>) -> io::Result<Self> {
+    pub fn with_keys(mut self, keys: &AttributeSetRef<Key>) -> io::Result<Self> {
         // Run ioctls for setting capability bits
-        unsafe {
-            sys::ui_set_evbit(
-                self.file.as_raw_fd(),
-                crate::EventType::KEY.0 as nix::sys::ioctl::ioctl_param_type,
-            )?;
-        }
+        self.set_bit(sys::ui_set_evbit, EventType::KEY.0)?;
 
         for bit in keys.iter() {
-            unsafe {
-                sys::ui_set_keybit(
-                    self.file.as_raw_fd(),
-                    bit.0 as nix::sys::ioctl::ioctl_param_type,
-                )?;
-            }
+            self.set_bit(sys::ui_set_keybit, bit.0)?;
         }
 
+        self.caps.types.insert(EventType::KEY);
+        self.caps.keys.extend(keys.iter());
+
         Ok(self)
     }
 
-    pub fn with_miscs(self, keys: &AttributeSetRef<MiscType>) -> io::Result<Self> {
-        unsafe {
-            sys::ui_set_evbit(
-                self.file.as_raw_fd(),
-                crate::EventType::MISC.0 as nix::sys::ioctl::ioctl_param_type,
-            )?;
-        }
+    pub fn with_miscs(mut self, keys: &AttributeSetRef<MiscType>) -> io::Result<Self> {
+        self.set_bit(sys::ui_set_evbit, EventType::MISC.0)?;
 
         for bit in keys.iter() {
-            unsafe {
-                sys::ui_set_mscbit(
-                    self.file.as_raw_fd(),
-                    bit.0 as nix::sys::ioctl::ioctl_param_type,
-                )?;
-            }
+            self.set_bit(sys::ui_set_mscbit, bit.0)?;
         }
 
+        self.caps.types.insert(EventType::MISC);
+        self.caps.misc.extend(keys.iter());
+
         Ok(self)
     }
 
-    pub fn with_leds(self, keys: &AttributeSetRef<LedType>) -> io::Result<Self> {
-        unsafe {
-            sys::ui_set_evbit(
-                self.file.as_raw_fd(),
-                crate::EventType::LED.0 as nix::sys::ioctl::ioctl_param_type,
-            )?;
-        }
+    pub fn with_leds(mut self, keys: &AttributeSetRef<LedType>) -> io::Result<Self> {
+        self.set_bit(sys::ui_set_evbit, EventType::LED.0)?;
 
         for bit in keys.iter() {
-            unsafe {
-                sys::ui_set_ledbit(
-                    self.file.as_raw_fd(),
-                    bit.0 as nix::sys::ioctl::ioctl_param_type,
-                )?;
-            }
+            self.set_bit(sys::ui_set_ledbit, bit.0)?;
         }
 
+        self.caps.types.insert(EventType::LED);
+        self.caps.leds.extend(keys.iter());
+
         Ok(self)
     }
 
-    pub fn with_relative_axes(self, axes: &AttributeSetRef<RelativeAxisType>) -> io::Result<Self> {
-        unsafe {
-            sys::ui_set_evbit(
-                self.file.as_raw_fd(),
-                crate::EventType::RELATIVE.0 as nix::sys::ioctl::ioctl_param_type,
-            )?;
-        }
+    pub fn with_relative_axes(
+        mut self,
+        axes: &AttributeSetRef<RelativeAxisType>,
+    ) -> io::Result<Self> {
+        self.set_bit(sys::ui_set_evbit, EventType::RELATIVE.0)?;
 
         for bit in axes.iter() {
-            unsafe {
-                sys::ui_set_relbit(
-                    self.file.as_raw_fd(),
-                    bit.0 as nix::sys::ioctl::ioctl_param_type,
-                )?;
-            }
+            self.set_bit(sys::ui_set_relbit, bit.0)?;
         }
 
+        self.caps.types.insert(EventType::RELATIVE);
+        self.caps.relative.extend(axes.iter());
+
         Ok(self)
     }
 
-    pub fn with_switches(self, switches: &AttributeSetRef<SwitchType>) -> io::Result<Self> {
-        unsafe {
-            sys::ui_set_evbit(
-                self.file.as_raw_fd(),
-                crate::EventType::SWITCH.0 as nix::sys::ioctl::ioctl_param_type,
-            )?;
+    pub fn with_absolute_axis(mut self, axis: &UinputAbsSetup) -> io::Result<Self> {
+        self.set_bit(sys::ui_set_evbit, EventType::ABSOLUTE.0)?;
+        self.set_bit(sys::ui_set_absbit, axis.axis().0)?;
+        match &self.file {
+            Some(_) if self.is_legacy() => self.legacy_abs.push(*axis),
+            Some(file) => unsafe { sys::ui_abs_setup(file.as_raw_fd(), &axis.0) }
+                .map(drop)
+                .map_err(ioctl_error("UI_ABS_SETUP"))?,
+            None => {}
         }
 
+        self.caps.types.insert(EventType::ABSOLUTE);
+        self.caps.absolute.insert(axis.axis());
+
+        Ok(self)
+    }
+
+    pub fn with_switches(mut self, switches: &AttributeSetRef<SwitchType>) -> io::Result<Self> {
+        self.set_bit(sys::ui_set_evbit, EventType::SWITCH.0)?;
+
         for bit in switches.iter() {
-            unsafe {
-                sys::ui_set_swbit(
-                    self.file.as_raw_fd(),
-                    bit.0 as nix::sys::ioctl::ioctl_param_type,
-                )?;
-            }
+            self.set_bit(sys::ui_set_swbit, bit.0)?;
         }
 
+        self.caps.types.insert(EventType::SWITCH);
+        self.caps.switches.extend(switches.iter());
+
         Ok(self)
     }
 
-    pub fn build(self) -> io::Result<VirtualDevice> {
-        // Populate the uinput_setup struct
+    /// Advertise support for the given force feedback effect types.
+    ///
+    /// Uploads and playback requests from consumers of the device are then delivered through
+    /// [`VirtualDevice::fetch_ff_events`]. Use [`with_ff_effects_max`](Self::with_ff_effects_max)
+    /// to set how many effects can be uploaded at once.
+    pub fn with_ff(mut self, ff: &AttributeSetRef<FFEffectType>) -> io::Result<Self> {
+        self.set_bit(sys::ui_set_evbit, EventType::FORCEFEEDBACK.0)?;
 
-        let mut usetup = libc::uinput_setup {
-            id: self.id.unwrap_or(DEFAULT_ID),
-            name: [0; libc::UINPUT_MAX_NAME_SIZE],
-            ff_effects_max: 0,
-        };
+        for bit in ff.iter() {
+            self.set_bit(sys::ui_set_ffbit, bit.0)?;
+        }
+
+        self.caps.types.insert(EventType::FORCEFEEDBACK);
+
+        Ok(self)
+    }
 
+    /// Set the maximum number of force feedback effects that can be uploaded at once.
+    #[inline]
+    pub fn with_ff_effects_max(mut self, ff_effects_max: u32) -> Self {
+        self.ff_effects_max = ff_effects_max;
+        self
+    }
+
+    pub fn build(mut self) -> io::Result<VirtualDevice> {
         // SAFETY: either casting [u8] to [u8], or [u8] to [i8], which is the same size
         let name_bytes = unsafe { &*(self.name as *const [u8] as *const [libc::c_char]) };
-        // Panic if we're doing something really stupid
-        // + 1 for the null terminator; usetup.name was zero-initialized so there will be null
-        // bytes after the part we copy into
-        assert!(name_bytes.len() + 1 < libc::UINPUT_MAX_NAME_SIZE);
-        usetup.name[..name_bytes.len()].copy_from_slice(name_bytes);
+        // + 1 for the null terminator; the name buffers are zero-initialized so there will be
+        // null bytes after the part we copy into
+        if name_bytes.len() + 1 >= libc::UINPUT_MAX_NAME_SIZE || name_bytes.contains(&0) {
+            return Err(Error::InvalidName.into());
+        }
 
-        VirtualDevice::new(self.file, &usetup)
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => return Ok(VirtualDevice::in_memory(self.caps)),
+        };
+        if self.is_legacy() {
+            // Kernels before 4.5 take the device setup as a struct written to the fd
+            let mut user_dev: libc::uinput_user_dev = unsafe { mem::zeroed() };
+            user_dev.name[..name_bytes.len()].copy_from_slice(name_bytes);
+            user_dev.id = self.id.unwrap_or(DEFAULT_ID);
+            user_dev.ff_effects_max = self.ff_effects_max;
+            for setup in &self.legacy_abs {
+                let (axis, info) = (setup.axis().0 as usize, setup.abs_info());
+                user_dev.absmin[axis] = info.minimum();
+                user_dev.absmax[axis] = info.maximum();
+                user_dev.absfuzz[axis] = info.fuzz();
+                user_dev.absflat[axis] = info.flat();
+            }
+            (&file).write_all(unsafe { crate::cast_to_bytes(&user_dev) })?;
+        } else {
+            let mut usetup = libc::uinput_setup {
+                id: self.id.unwrap_or(DEFAULT_ID),
+                name: [0; libc::UINPUT_MAX_NAME_SIZE],
+                ff_effects_max: self.ff_effects_max,
+            };
+            usetup.name[..name_bytes.len()].copy_from_slice(name_bytes);
+            unsafe { sys::ui_dev_setup(file.as_raw_fd(), &usetup) }
+                .map_err(ioctl_error("UI_DEV_SETUP"))?;
+        }
+
+        VirtualDevice::new(file, self.caps, self.version, self.name)
     }
 }
 
+/// Take over a uinput descriptor whose device was already created, e.g. one received from
+/// another process. Its capabilities are read back from its event node.
+impl TryFrom<File> for VirtualDevice {
+    type Error = io::Error;
+
+    fn try_from(file: File) -> io::Result<Self> {
+        let version = query_version(&file)?;
+        let (file_event, devnode) = VirtualDevice::open_event_file(&file, version, None)?;
+        let report =
+            crate::raw_stream::RawDevice::from_file(file_event.try_clone()?)?.capability_report();
+        let caps = Capabilities {
+            types: report.event_types,
+            keys: report.keys.unwrap_or_default(),
+            relative: report.relative_axes.unwrap_or_default(),
+            absolute: report.absolute_axes.iter().map(|&(axis, _)| axis).collect(),
+            misc: report.misc.unwrap_or_default(),
+            leds: report.leds.unwrap_or_default(),
+            switches: report.switches.unwrap_or_default(),
+        };
+        let backend = Backend::Uinput {
+            file,
+            file_event,
+            devnode,
+        };
+        Ok(VirtualDevice::with_backend(backend, caps, version))
+    }
+}
+
+impl TryFrom<OwnedFd> for VirtualDevice {
+    type Error = io::Error;
+
+    fn try_from(fd: OwnedFd) -> io::Result<Self> {
+        VirtualDevice::try_from(File::from(fd))
+    }
+}
+
+const UI_FF_UPLOAD: u16 = 1;
+const UI_FF_ERASE: u16 = 2;
+
 const DEFAULT_ID: libc::input_id = libc::input_id {
     bustype: BusType::BUS_USB.0,
     vendor: 0x1234,  /* sample vendor */
@@ -180,79 +420,240 @@ const DEFAULT_ID: libc::input_id = libc::input_id {
     version: 0x111,
 };
 
+/// A force feedback request made by a consumer of a [`VirtualDevice`].
+///
+/// Uploads and erasures have already been acknowledged to the kernel by the time they are
+/// returned, and are reflected in [`VirtualDevice::ff_effects`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VirtualFFEvent {
+    /// An effect was uploaded, either new or replacing the effect previously stored at `id`.
+    Uploaded { id: i16, effect: FFEffect },
+    /// The effect at `id` was erased.
+    Erased { id: i16 },
+    /// Playback of the effect at `id` was requested, repeating it `count` times.
+    Play { id: i16, count: i32 },
+    /// Playback of the effect at `id` should stop.
+    Stop { id: i16 },
+    /// The overall force feedback gain was set.
+    Gain(u16),
+    /// The autocenter strength was set.
+    Autocenter(u16),
+}
+
+/// Where a [`VirtualDevice`]'s events go.
+enum Backend {
+    Uinput {
+        file: File,
+        file_event: File,
+        devnode: PathBuf,
+    },
+    Memory {
+        sink: MemorySink,
+        keys: AttributeSet<Key>,
+        switches: AttributeSet<SwitchType>,
+        leds: AttributeSet<LedType>,
+    },
+}
+
+/// The events emitted on an in-memory [`VirtualDevice`], one frame per
+/// [`emit`](VirtualDevice::emit) call.
+///
+/// Cloning the sink gives another handle to the same queue, so it can be kept while the
+/// device is moved into the code under test.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink {
+    frames: Arc<Mutex<VecDeque<Vec<InputEvent>>>>,
+}
+
+impl MemorySink {
+    /// Remove and return the oldest emitted frame, including its terminating `SYN_REPORT`.
+    pub fn pop_frame(&self) -> Option<Vec<InputEvent>> {
+        self.frames.lock().unwrap().pop_front()
+    }
+
+    /// Remove and return all emitted frames, oldest first.
+    pub fn drain(&self) -> Vec<Vec<InputEvent>> {
+        self.frames.lock().unwrap().drain(..).collect()
+    }
+
+    /// Returns the number of frames waiting in the queue.
+    pub fn len(&self) -> usize {
+        self.frames.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn push(&self, frame: Vec<InputEvent>) {
+        self.frames.lock().unwrap().push_back(frame);
+    }
+}
+
 pub struct VirtualDevice {
-    file: File,
-    file_event: File,
+    backend: Backend,
+    ff_effects: HashMap<i16, FFEffect>,
+    caps: Capabilities,
+    strict: bool,
+    validator: Option<StreamValidator>,
+    version: Option<u32>,
+    event_buf: Vec<libc::input_event>,
+    ff_buf: Vec<VirtualFFEvent>,
+    watchdog: Option<Watchdog>,
 }
 
 impl VirtualDevice {
     /// Create a new virtual device.
-    fn new(file: File, usetup: &libc::uinput_setup) -> io::Result<Self> {
-        unsafe { sys::ui_dev_setup(file.as_raw_fd(), usetup)? };
-        unsafe { sys::ui_dev_create(file.as_raw_fd())? };
+    fn new(file: File, caps: Capabilities, version: Option<u32>, name: &[u8]) -> io::Result<Self> {
+        unsafe { sys::ui_dev_create(file.as_raw_fd()) }.map_err(ioctl_error("UI_DEV_CREATE"))?;
 
-        let file_event = Self::open_event_file(&file)?;
+        let (file_event, devnode) = Self::open_event_file(&file, version, Some(name))?;
 
-        Ok(VirtualDevice { file, file_event })
+        Ok(Self::with_backend(
+            Backend::Uinput {
+                file,
+                file_event,
+                devnode,
+            },
+            caps,
+            version,
+        ))
     }
 
-    fn open_event_file(file: &File) -> io::Result<File> {
-        unsafe {
-            let mut name = [0u8; 32];
-            sys::ui_get_sysname(file.as_raw_fd(), &mut name)?;
-
-            let mut first_nul = name.len() - 1;
-            for i in 0..first_nul {
-                if name[i] == 0 {
-                    first_nul = i;
-                    break;
-                }
-            }
+    fn in_memory(caps: Capabilities) -> Self {
+        let backend = Backend::Memory {
+            sink: MemorySink::default(),
+            keys: AttributeSet::new(),
+            switches: AttributeSet::new(),
+            leds: AttributeSet::new(),
+        };
+        Self::with_backend(backend, caps, None)
+    }
 
-            match std::str::from_utf8(&name[0..first_nul]) {
-                Ok(input_name) => {
-                    let input_dir = format!("/sys/devices/virtual/input/{}", input_name);
-                    let mut readdir = std::fs::read_dir(&input_dir)?;
-                    use std::os::unix::ffi::OsStrExt;
-                    loop {
-                        match readdir.next() {
-                            Some(Ok(entry)) => {
-                                if let Some(fname) = entry.path().file_name() {
-                                    if fname.as_bytes().starts_with(b"event") {
-                                        let event_file =
-                                            format!("/dev/input/{}", fname.to_string_lossy());
-                                        return OpenOptions::new()
-                                            .read(true)
-                                            // .write(true)
-                                            .custom_flags(O_NONBLOCK)
-                                            .open(event_file);
-                                    }
-                                }
-                            }
-                            None => {
-                                return Err(io::Error::new(
-                                    io::ErrorKind::NotFound,
-                                    format!("Failed to find event of input: {}", &input_dir),
-                                ));
-                            }
-                            Some(Err(_e)) => {
-                                // ignore
-                            }
-                        }
-                    }
-                }
-                Err(e) => Err(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("Failed to find event, err: {}", e),
-                )),
+    fn with_backend(backend: Backend, caps: Capabilities, version: Option<u32>) -> Self {
+        VirtualDevice {
+            backend,
+            ff_effects: HashMap::new(),
+            caps,
+            strict: false,
+            validator: None,
+            version,
+            event_buf: Vec::new(),
+            ff_buf: Vec::new(),
+            watchdog: None,
+        }
+    }
+
+    /// Returns a handle to the emitted events if this device was built with
+    /// [`VirtualDeviceBuilder::in_memory`].
+    pub fn memory_sink(&self) -> Option<MemorySink> {
+        match &self.backend {
+            Backend::Memory { sink, .. } => Some(sink.clone()),
+            Backend::Uinput { .. } => None,
+        }
+    }
+
+    /// Returns the path of the device's event node in `/dev/input`, which can be opened with
+    /// [`Device::open`](crate::Device::open) to read back the emitted events. Returns `None`
+    /// for in-memory devices.
+    pub fn devnode(&self) -> Option<&Path> {
+        match &self.backend {
+            Backend::Uinput { devnode, .. } => Some(devnode),
+            Backend::Memory { .. } => None,
+        }
+    }
+
+    /// Returns the uinput file descriptor, which becomes readable when force feedback requests
+    /// are pending, e.g. to register it with an external poller. Returns `None` for in-memory
+    /// devices.
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        match &self.backend {
+            Backend::Uinput { file, .. } => Some(file.as_fd()),
+            Backend::Memory { .. } => None,
+        }
+    }
+
+    /// Consume the device, returning the uinput file descriptor; the device lives on until it
+    /// is closed. Returns `None` for in-memory devices.
+    pub fn into_fd(self) -> Option<OwnedFd> {
+        match self.backend {
+            Backend::Uinput { file, .. } => Some(file.into()),
+            Backend::Memory { .. } => None,
+        }
+    }
+
+    /// Returns the uinput fd. Only called on paths that in-memory devices never reach.
+    fn uinput_fd(&self) -> RawFd {
+        match &self.backend {
+            Backend::Uinput { file, .. } => file.as_raw_fd(),
+            Backend::Memory { .. } => unreachable!("in-memory devices have no uinput fd"),
+        }
+    }
+
+    /// Open the event node of the device created on `file`, named `name` if known.
+    fn open_event_file(
+        file: &File,
+        version: Option<u32>,
+        name: Option<&[u8]>,
+    ) -> io::Result<(File, PathBuf)> {
+        let input_dir = match Self::sysname(file, version)? {
+            Some(sysname) => {
+                trace_event!(debug, sysname = sysname.as_str(), "created virtual device");
+                Path::new(SYS_VIRTUAL_INPUT).join(sysname)
+            }
+            None => find_input_dir(name)?,
+        };
+        use std::os::unix::ffi::OsStrExt;
+        for entry in fs::read_dir(&input_dir)?.flatten() {
+            if entry.file_name().as_bytes().starts_with(b"event") {
+                let event_file = Path::new("/dev/input").join(entry.file_name());
+                let file_event = fs::OpenOptions::new()
+                    .read(true)
+                    .custom_flags(O_NONBLOCK)
+                    .open(&event_file)?;
+                return Ok((file_event, event_file));
             }
         }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Failed to find event of input: {}", input_dir.display()),
+        ))
     }
 
-    #[inline]
-    fn write_raw(&mut self, messages: &[InputEvent]) -> io::Result<()> {
-        let bytes = unsafe { crate::cast_to_bytes(messages) };
-        self.file.write_all(bytes)
+    /// Returns the sysfs name of the device created on `file`, or `None` if uinput is too old
+    /// to report it.
+    fn sysname(file: &File, version: Option<u32>) -> io::Result<Option<String>> {
+        if version.is_some_and(|v| v < UINPUT_VERSION_SYSNAME) {
+            return Ok(None);
+        }
+        let mut name = [0u8; 32];
+        match unsafe { sys::ui_get_sysname(file.as_raw_fd(), &mut name) } {
+            Ok(_) => {}
+            // Kernels without the version query may predate this one too
+            Err(Errno::ENOTTY | Errno::EINVAL) if version.is_none() => return Ok(None),
+            Err(errno) => return Err(ioctl_error("UI_GET_SYSNAME")(errno)),
+        }
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        match std::str::from_utf8(&name[..len]) {
+            Ok(name) => Ok(Some(name.to_owned())),
+            Err(e) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Failed to find event, err: {}", e),
+            )),
+        }
+    }
+
+    /// Write all of `bufs` to the uinput fd, in as few syscalls as possible.
+    fn write_all_vectored(mut file: &File, mut bufs: &mut [IoSlice<'_>]) -> io::Result<()> {
+        while !bufs.is_empty() {
+            match file.write_vectored(bufs) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
     }
 
     /// Post a batch of events to the virtual device.
@@ -262,10 +663,280 @@ impl VirtualDevice {
     /// of a mouse triggers a movement events for the X and Y axes separately in a batch of 2 events.
     ///
     /// Single events such as a `KEY` event must still be followed by a `SYN_REPORT`.
+    ///
+    /// The batch and its terminator are submitted together in a single `writev` call.
+    ///
+    /// In [strict mode](Self::set_strict), nothing is written if any of the events wasn't
+    /// enabled on the device.
     pub fn emit(&mut self, messages: &[InputEvent]) -> io::Result<()> {
-        self.write_raw(messages)?;
+        trace_span!("emit", events = messages.len());
+        if self.strict {
+            self.check_events(messages)?;
+        }
         let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
-        self.write_raw(&[syn])
+        if let Some(validator) = &mut self.validator {
+            // Check on a copy, so a rejected batch leaves the validator's state untouched
+            let mut next = validator.clone();
+            for ev in messages.iter().chain([&syn]) {
+                next.check(ev).map_err(Error::InvalidStream)?;
+            }
+            *validator = next;
+        }
+        // Hold the watchdog's lock while writing, so it can't release keys in between
+        let watchdog = self.watchdog.as_ref().map(Watchdog::lock);
+        match &mut self.backend {
+            Backend::Uinput { file, .. } => {
+                let (bytes, syn) =
+                    unsafe { (crate::cast_to_bytes(messages), crate::cast_to_bytes(&syn)) };
+                Self::write_all_vectored(file, &mut [IoSlice::new(bytes), IoSlice::new(syn)])?;
+            }
+            Backend::Memory {
+                sink,
+                keys,
+                switches,
+                leds,
+            } => {
+                for ev in messages {
+                    let (on, code) = (ev.value() != 0, usize::from(ev.code()));
+                    match ev.event_type() {
+                        // Like the kernel, drop codes past the end of their type
+                        EventType::KEY if code >= Key::COUNT => {}
+                        EventType::SWITCH if code >= SwitchType::COUNT => {}
+                        EventType::LED if code >= LedType::COUNT => {}
+                        EventType::KEY if on => keys.insert(Key(ev.code())),
+                        EventType::KEY => keys.remove(Key(ev.code())),
+                        EventType::SWITCH if on => switches.insert(SwitchType(ev.code())),
+                        EventType::SWITCH => switches.remove(SwitchType(ev.code())),
+                        EventType::LED if on => leds.insert(LedType(ev.code())),
+                        EventType::LED => leds.remove(LedType(ev.code())),
+                        _ => {}
+                    }
+                }
+                let mut frame = Vec::with_capacity(messages.len() + 1);
+                frame.extend_from_slice(messages);
+                frame.push(syn);
+                sink.push(frame);
+            }
+        }
+        if let Some(mut state) = watchdog {
+            state.record(messages);
+            drop(state);
+            if let Some(watchdog) = &self.watchdog {
+                watchdog.notify();
+            }
+        }
+        Ok(())
+    }
+
+    /// Enable the stuck-input watchdog, which tracks the keys and buttons held on the device
+    /// and releases them:
+    ///
+    /// - when the device is dropped,
+    /// - when any thread of the process panics,
+    /// - and, if `timeout` is set, when keys are held and nothing was emitted for that long.
+    ///
+    /// This keeps a crashed or hung remapper from leaving a key held down. Calling this again
+    /// replaces the previous watchdog, forgetting the keys it tracked.
+    pub fn enable_watchdog(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.enable_watchdog_with_clock(timeout, SystemClock)
+    }
+
+    /// Like [`enable_watchdog`](Self::enable_watchdog), measuring the timeout with `clock`.
+    ///
+    /// The watchdog checks a [`MockClock`](crate::clock::MockClock) about once per remaining
+    /// timeout; [`check_watchdog`](Self::check_watchdog) checks it right away.
+    pub fn enable_watchdog_with_clock(
+        &mut self,
+        timeout: Option<Duration>,
+        clock: impl Clock + Send + Sync + 'static,
+    ) -> io::Result<()> {
+        let sink = match &self.backend {
+            Backend::Uinput { file, .. } => watchdog::Sink::File(file.try_clone()?),
+            Backend::Memory { sink, .. } => watchdog::Sink::Memory(sink.clone()),
+        };
+        self.watchdog = Some(Watchdog::new(sink, timeout, SharedClock::new(clock)));
+        Ok(())
+    }
+
+    /// Disable the stuck-input watchdog. Keys held at this point stay held.
+    pub fn disable_watchdog(&mut self) {
+        if let Some(watchdog) = self.watchdog.take() {
+            // Dropping the watchdog would release them
+            watchdog.lock().forget();
+        }
+    }
+
+    /// Release the held keys if the watchdog's timeout passed, as of its clock's time. Does
+    /// nothing if the watchdog isn't enabled.
+    pub fn check_watchdog(&mut self) -> io::Result<()> {
+        match &self.watchdog {
+            Some(watchdog) => watchdog.lock().tick(),
+            None => Ok(()),
+        }
+    }
+
+    /// Release every key the watchdog saw held, right away. Does nothing if the watchdog isn't
+    /// enabled.
+    pub fn release_held(&mut self) -> io::Result<()> {
+        match &self.watchdog {
+            Some(watchdog) => watchdog.lock().release_all(),
+            None => Ok(()),
+        }
+    }
+
+    /// Type `text` by tapping keys, one frame per press and release.
+    ///
+    /// Characters are mapped to keys assuming the consumer uses a US keyboard layout, and
+    /// only ASCII letters, digits, punctuation, space, tab and newline can be typed. The
+    /// device needs the keys for the text and `KEY_LEFTSHIFT`. Nothing is typed if the text
+    /// contains a character that can't be typed.
+    pub fn type_text(&mut self, text: &str) -> io::Result<()> {
+        let frames = crate::text::text_frames(text).map_err(|c| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("can't type {:?}", c))
+        })?;
+        for frame in frames {
+            self.emit(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the uinput protocol version, or `None` on kernels older than 4.5 that can't
+    /// report it.
+    pub fn uinput_version(&self) -> Option<u32> {
+        self.version
+    }
+
+    /// Enable or disable strict mode.
+    ///
+    /// The kernel silently drops events whose type and code weren't enabled when the device
+    /// was built. In strict mode, [`emit`](Self::emit) rejects such batches with
+    /// [`Error::UndeclaredEvent`] instead, which makes mistakes in the device setup easy to
+    /// spot.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Enable or disable protocol validation of emitted events.
+    ///
+    /// While enabled, [`emit`](Self::emit) checks each batch with a [`StreamValidator`] and
+    /// rejects it with [`Error::InvalidStream`] if it would break SYN framing, MT slot
+    /// sequencing or tracking id uniqueness.
+    pub fn set_validation(&mut self, enabled: bool) {
+        self.validator = enabled.then(StreamValidator::new);
+    }
+
+    /// Check that every event's type and code was enabled on the device, as strict mode does.
+    ///
+    /// This can be used to log undeclared events without rejecting them.
+    pub fn check_events(&self, events: &[InputEvent]) -> io::Result<()> {
+        match events.iter().find(|ev| !self.caps.allows(ev)) {
+            Some(ev) => Err(Error::UndeclaredEvent {
+                event_type: ev.event_type(),
+                code: ev.code(),
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the force feedback effect currently uploaded at `id`, if any.
+    pub fn ff_effect(&self, id: i16) -> Option<&FFEffect> {
+        self.ff_effects.get(&id)
+    }
+
+    /// Returns an iterator over all currently uploaded force feedback effects and their ids.
+    pub fn ff_effects(&self) -> impl Iterator<Item = (i16, &FFEffect)> + '_ {
+        self.ff_effects.iter().map(|(&id, effect)| (id, effect))
+    }
+
+    /// Fetch force feedback requests made by consumers of this device.
+    ///
+    /// Effect uploads and erasures are answered and recorded before being returned. Effects of
+    /// a kind this crate can't represent, such as custom waveforms, are rejected with `EINVAL`.
+    ///
+    /// The device is opened in nonblocking mode, so this returns an error of kind
+    /// [`WouldBlock`](io::ErrorKind::WouldBlock) when there are no pending requests.
+    pub fn fetch_ff_events(&mut self) -> io::Result<impl Iterator<Item = VirtualFFEvent> + '_> {
+        self.fill_events()?;
+        let events = mem::take(&mut self.event_buf);
+        let res = events
+            .iter()
+            .try_for_each(|ev| self.process_ff_event(InputEvent(*ev)));
+        self.event_buf = events;
+        self.event_buf.clear();
+        res?;
+        Ok(self.ff_buf.drain(..))
+    }
+
+    fn fill_events(&mut self) -> io::Result<usize> {
+        if let Backend::Memory { .. } = self.backend {
+            // Nothing can make requests to an in-memory device
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let fd = self.uinput_fd();
+        self.event_buf.reserve(crate::EVENT_BATCH_SIZE);
+
+        let spare_capacity = crate::raw_stream::vec_spare_capacity_mut(&mut self.event_buf);
+        let spare_capacity_size = std::mem::size_of_val(spare_capacity);
+
+        // use libc::read instead of nix::unistd::read b/c we need to pass an uninitialized buf
+        let res = unsafe { libc::read(fd, spare_capacity.as_mut_ptr() as _, spare_capacity_size) };
+        let bytes_read = nix::errno::Errno::result(res)?;
+        let num_read = bytes_read as usize / mem::size_of::<libc::input_event>();
+        unsafe {
+            let len = self.event_buf.len();
+            self.event_buf.set_len(len + num_read);
+        }
+        Ok(num_read)
+    }
+
+    fn process_ff_event(&mut self, event: InputEvent) -> io::Result<()> {
+        let ev = match event.event_type() {
+            EventType::UINPUT if event.code() == UI_FF_UPLOAD => {
+                let mut upload: libc::uinput_ff_upload = unsafe { mem::zeroed() };
+                upload.request_id = event.value() as u32;
+                unsafe { sys::ui_begin_ff_upload(self.uinput_fd(), &mut upload)? };
+                let effect = FFEffect::from_raw(&upload.effect);
+                upload.retval = if effect.is_some() { 0 } else { -libc::EINVAL };
+                unsafe { sys::ui_end_ff_upload(self.uinput_fd(), &upload)? };
+                match effect {
+                    Some(effect) => {
+                        let id = upload.effect.id;
+                        self.ff_effects.insert(id, effect);
+                        VirtualFFEvent::Uploaded { id, effect }
+                    }
+                    None => return Ok(()),
+                }
+            }
+            EventType::UINPUT if event.code() == UI_FF_ERASE => {
+                let mut erase: libc::uinput_ff_erase = unsafe { mem::zeroed() };
+                erase.request_id = event.value() as u32;
+                unsafe { sys::ui_begin_ff_erase(self.uinput_fd(), &mut erase)? };
+                erase.retval = 0;
+                unsafe { sys::ui_end_ff_erase(self.uinput_fd(), &erase)? };
+                let id = erase.effect_id as i16;
+                self.ff_effects.remove(&id);
+                VirtualFFEvent::Erased { id }
+            }
+            EventType::FORCEFEEDBACK => match FFEffectType(event.code()) {
+                FFEffectType::FF_GAIN => VirtualFFEvent::Gain(event.value() as u16),
+                FFEffectType::FF_AUTOCENTER => VirtualFFEvent::Autocenter(event.value() as u16),
+                _ => {
+                    let id = event.code() as i16;
+                    if event.value() > 0 {
+                        VirtualFFEvent::Play {
+                            id,
+                            count: event.value(),
+                        }
+                    } else {
+                        VirtualFFEvent::Stop { id }
+                    }
+                }
+            },
+            _ => return Ok(()),
+        };
+        self.ff_buf.push(ev);
+        Ok(())
     }
 
     /// Retrieve the current keypress state directly via kernel syscall.
@@ -281,7 +952,12 @@ impl VirtualDevice {
     /// [`get_key_state`](Self::get_key_state) instead.
     #[inline]
     pub fn update_key_state(&self, key_vals: &mut AttributeSet<Key>) -> io::Result<()> {
-        unsafe { sys::eviocgkey(self.file_event.as_raw_fd(), key_vals.as_mut_raw_slice())? };
+        match &self.backend {
+            Backend::Uinput { file_event, .. } => unsafe {
+                sys::eviocgkey(file_event.as_raw_fd(), key_vals.as_mut_raw_slice())?;
+            },
+            Backend::Memory { keys, .. } => *key_vals = *keys,
+        }
         Ok(())
     }
 
@@ -309,7 +985,12 @@ impl VirtualDevice {
         &self,
         switch_vals: &mut AttributeSet<SwitchType>,
     ) -> io::Result<()> {
-        unsafe { sys::eviocgsw(self.file_event.as_raw_fd(), switch_vals.as_mut_raw_slice())? };
+        match &self.backend {
+            Backend::Uinput { file_event, .. } => unsafe {
+                sys::eviocgsw(file_event.as_raw_fd(), switch_vals.as_mut_raw_slice())?;
+            },
+            Backend::Memory { switches, .. } => *switch_vals = *switches,
+        }
         Ok(())
     }
 
@@ -318,7 +999,78 @@ impl VirtualDevice {
     /// [`get_led_state`](Self::get_led_state) instead.
     #[inline]
     pub fn update_led_state(&self, led_vals: &mut AttributeSet<LedType>) -> io::Result<()> {
-        unsafe { sys::eviocgled(self.file_event.as_raw_fd(), led_vals.as_mut_raw_slice())? };
+        match &self.backend {
+            Backend::Uinput { file_event, .. } => unsafe {
+                sys::eviocgled(file_event.as_raw_fd(), led_vals.as_mut_raw_slice())?;
+            },
+            Backend::Memory { leds, .. } => *led_vals = *leds,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn in_memory_device() -> io::Result<()> {
+        let keys: AttributeSet<Key> = [Key::KEY_A].into_iter().collect();
+        let mut device = VirtualDeviceBuilder::in_memory()
+            .name("test")
+            .with_keys(&keys)?
+            .build()?;
+        device.set_strict(true);
+        assert!(device.fd().is_none());
+        let sink = device.memory_sink().unwrap();
+
+        let press = InputEvent::new(EventType::KEY, Key::KEY_A.code(), 1);
+        device.emit(&[press])?;
+        assert!(device
+            .emit(&[InputEvent::new(EventType::KEY, Key::KEY_B.code(), 1)])
+            .is_err());
+
+        let frame = sink.pop_frame().unwrap();
+        assert_eq!(frame.len(), 2);
+        assert_eq!(frame[0].code(), Key::KEY_A.code());
+        assert!(sink.is_empty());
+        assert!(device.get_key_state()?.contains(Key::KEY_A));
+
+        device.set_strict(false);
+        device.emit(&[InputEvent::new(EventType::KEY, u16::MAX, 1)])?;
+        assert_eq!(device.get_key_state()?.iter().count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn watchdog_releases_held_keys() -> io::Result<()> {
+        let keys: AttributeSet<Key> = [Key::KEY_A, Key::KEY_B].into_iter().collect();
+        let mut device = VirtualDeviceBuilder::in_memory()
+            .with_keys(&keys)?
+            .build()?;
+        let clock = MockClock::default();
+        device.enable_watchdog_with_clock(Some(Duration::from_millis(20)), clock.clone())?;
+        let sink = device.memory_sink().unwrap();
+
+        let press = |key: Key| InputEvent::new(EventType::KEY, key.code(), 1);
+        device.emit(&[press(Key::KEY_A)])?;
+        clock.advance(Duration::from_millis(19));
+        device.check_watchdog()?;
+        assert_eq!(sink.drain().len(), 1);
+        clock.advance(Duration::from_millis(1));
+        device.check_watchdog()?;
+        let frames = sink.drain();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0][0].code(), Key::KEY_A.code());
+        assert_eq!(frames[0][0].value(), 0);
+
+        device.emit(&[press(Key::KEY_B)])?;
+        drop(device);
+        let frames = sink.drain();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1][0].code(), Key::KEY_B.code());
+        assert_eq!(frames[1][0].value(), 0);
         Ok(())
     }
 }
@@ -0,0 +1,229 @@
+//! Checking event streams against the evdev protocol.
+
+use std::fmt;
+
+use crate::{AbsoluteAxisType, EventType, InputEvent, Synchronization};
+
+/// A way in which an event stream breaks the evdev protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// A `SYN_REPORT` closed a frame that contained no events.
+    EmptyFrame,
+    /// A synchronization event with a code the kernel never produces.
+    UnknownSyncCode(u16),
+    /// Both the slotted (type B) and the `SYN_MT_REPORT` (type A) multitouch protocols were
+    /// used on the same stream.
+    MixedMtProtocols,
+    /// `ABS_MT_SLOT` selected a slot outside the device's range.
+    SlotOutOfRange { slot: i32 },
+    /// A tracking id was assigned to a slot while another slot still held it.
+    TrackingIdInUse {
+        tracking_id: i32,
+        slot: i32,
+        other_slot: i32,
+    },
+    /// Contact data was sent for a slot with no active contact.
+    InactiveSlotUpdate { slot: i32, axis: AbsoluteAxisType },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::EmptyFrame => f.write_str("SYN_REPORT terminated an empty frame"),
+            Violation::UnknownSyncCode(code) => write!(f, "unknown synchronization code {}", code),
+            Violation::MixedMtProtocols => {
+                f.write_str("slotted and SYN_MT_REPORT multitouch protocols were mixed")
+            }
+            Violation::SlotOutOfRange { slot } => write!(f, "slot {} is out of range", slot),
+            Violation::TrackingIdInUse {
+                tracking_id,
+                slot,
+                other_slot,
+            } => write!(
+                f,
+                "tracking id {} assigned to slot {} is still in use by slot {}",
+                tracking_id, slot, other_slot
+            ),
+            Violation::InactiveSlotUpdate { slot, axis } => {
+                write!(f, "{:?} sent for inactive slot {}", axis, slot)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// Checks a stream of events, one at a time, for protocol violations.
+///
+/// This catches malformed emitters and broken drivers early. It can be enabled on a
+/// [`Device`](crate::Device) with [`set_validation`](crate::Device::set_validation) and on a
+/// [`VirtualDevice`](crate::uinput::VirtualDevice) with
+/// [`set_validation`](crate::uinput::VirtualDevice::set_validation), or driven directly.
+#[derive(Debug, Clone, Default)]
+pub struct StreamValidator {
+    frame_len: usize,
+    slot: i32,
+    /// The tracking id of each slot seen so far; -1 for inactive slots.
+    tracking_ids: Vec<i32>,
+    slot_count: Option<usize>,
+    seen_slots: bool,
+    seen_mt_report: bool,
+}
+
+impl StreamValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also check that slots stay within `0..count`.
+    pub fn with_slot_count(mut self, count: usize) -> Self {
+        self.slot_count = Some(count);
+        self
+    }
+
+    /// Forget all tracked state, e.g. after events were dropped and the stream resynchronized.
+    pub fn reset(&mut self) {
+        *self = StreamValidator {
+            slot_count: self.slot_count,
+            ..Self::default()
+        };
+    }
+
+    /// Check the next event in the stream.
+    pub fn check(&mut self, ev: &InputEvent) -> Result<(), Violation> {
+        match ev.event_type() {
+            EventType::SYNCHRONIZATION => self.check_sync(ev.code()),
+            EventType::ABSOLUTE => {
+                self.frame_len += 1;
+                self.check_abs(AbsoluteAxisType(ev.code()), ev.value())
+            }
+            _ => {
+                self.frame_len += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn check_sync(&mut self, code: u16) -> Result<(), Violation> {
+        match Synchronization(code) {
+            Synchronization::SYN_REPORT => {
+                let len = std::mem::take(&mut self.frame_len);
+                if len == 0 {
+                    return Err(Violation::EmptyFrame);
+                }
+            }
+            Synchronization::SYN_MT_REPORT => {
+                self.frame_len += 1;
+                self.seen_mt_report = true;
+                if self.seen_slots {
+                    return Err(Violation::MixedMtProtocols);
+                }
+            }
+            Synchronization::SYN_CONFIG => self.frame_len += 1,
+            Synchronization::SYN_DROPPED => self.reset(),
+            _ => return Err(Violation::UnknownSyncCode(code)),
+        }
+        Ok(())
+    }
+
+    fn check_abs(&mut self, axis: AbsoluteAxisType, value: i32) -> Result<(), Violation> {
+        if axis.0 < AbsoluteAxisType::ABS_MT_SLOT.0 || axis.0 > AbsoluteAxisType::ABS_MT_TOOL_Y.0 {
+            return Ok(());
+        }
+        match axis {
+            AbsoluteAxisType::ABS_MT_SLOT => {
+                self.seen_slots = true;
+                if self.seen_mt_report {
+                    return Err(Violation::MixedMtProtocols);
+                }
+                let in_range = value >= 0 && self.slot_count.is_none_or(|n| (value as usize) < n);
+                if !in_range {
+                    return Err(Violation::SlotOutOfRange { slot: value });
+                }
+                self.slot = value;
+            }
+            AbsoluteAxisType::ABS_MT_TRACKING_ID if !self.seen_mt_report => {
+                let slot = self.slot;
+                if value >= 0 {
+                    let other = self
+                        .tracking_ids
+                        .iter()
+                        .position(|&id| id == value)
+                        .filter(|&other| other as i32 != slot);
+                    if let Some(other_slot) = other {
+                        return Err(Violation::TrackingIdInUse {
+                            tracking_id: value,
+                            slot,
+                            other_slot: other_slot as i32,
+                        });
+                    }
+                }
+                let slot = slot as usize;
+                if self.tracking_ids.len() <= slot {
+                    self.tracking_ids.resize(slot + 1, -1);
+                }
+                self.tracking_ids[slot] = value;
+            }
+            // Without tracking ids there's no way to tell active slots apart
+            _ if self.seen_mt_report || self.tracking_ids.is_empty() => {}
+            _ => {
+                let active = self.tracking_ids.get(self.slot as usize).copied();
+                if active.unwrap_or(-1) < 0 {
+                    return Err(Violation::InactiveSlotUpdate {
+                        slot: self.slot,
+                        axis,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abs(axis: AbsoluteAxisType, value: i32) -> InputEvent {
+        InputEvent::new(EventType::ABSOLUTE, axis.0, value)
+    }
+
+    #[test]
+    fn detects_violations() {
+        let syn = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        let mut v = StreamValidator::new().with_slot_count(2);
+
+        assert_eq!(v.check(&syn), Err(Violation::EmptyFrame));
+
+        let touch = [
+            abs(AbsoluteAxisType::ABS_MT_SLOT, 0),
+            abs(AbsoluteAxisType::ABS_MT_TRACKING_ID, 7),
+            abs(AbsoluteAxisType::ABS_MT_POSITION_X, 10),
+            abs(AbsoluteAxisType::ABS_MT_SLOT, 1),
+        ];
+        for ev in &touch {
+            assert_eq!(v.check(ev), Ok(()));
+        }
+        assert_eq!(
+            v.check(&abs(AbsoluteAxisType::ABS_MT_POSITION_X, 5)),
+            Err(Violation::InactiveSlotUpdate {
+                slot: 1,
+                axis: AbsoluteAxisType::ABS_MT_POSITION_X
+            })
+        );
+        assert_eq!(
+            v.check(&abs(AbsoluteAxisType::ABS_MT_TRACKING_ID, 7)),
+            Err(Violation::TrackingIdInUse {
+                tracking_id: 7,
+                slot: 1,
+                other_slot: 0
+            })
+        );
+        assert_eq!(
+            v.check(&abs(AbsoluteAxisType::ABS_MT_SLOT, 2)),
+            Err(Violation::SlotOutOfRange { slot: 2 })
+        );
+        assert_eq!(v.check(&syn), Ok(()));
+    }
+}
@@ -0,0 +1,118 @@
+use std::fs;
+use std::io;
+
+use crate::Device;
+
+/// The sysfs attribute naming the foreground virtual terminal, e.g. `tty3`.
+const ACTIVE_VT: &str = "/sys/class/tty/tty0/active";
+
+/// The character device major number of `/dev/ttyN`.
+const TTY_MAJOR: u32 = 4;
+
+/// Holds a grab on a device only while a session is in the foreground.
+///
+/// A program that grabs a keyboard, such as a key remapper, keeps every other program from
+/// seeing its input, including the login prompt on another virtual terminal. Call
+/// [`update`](Self::update) periodically, e.g. whenever the event loop wakes up, to release the
+/// grab when the user switches away from the program's VT and take it again when they come
+/// back. Programs that track their session through logind can instead pass its `Active`
+/// property to [`set_active`](Self::set_active).
+#[derive(Debug)]
+pub struct VtGrab {
+    vt: u32,
+    grabbed: bool,
+}
+
+impl VtGrab {
+    /// Manage the grab for virtual terminal `vt`, i.e. `/dev/tty<vt>`.
+    ///
+    /// The device isn't grabbed until the first [`update`](Self::update).
+    pub fn new(vt: u32) -> Self {
+        VtGrab { vt, grabbed: false }
+    }
+
+    /// Manage the grab for the virtual terminal that is the calling process's controlling
+    /// terminal.
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] if the process isn't running on a VT, e.g.
+    /// under a terminal emulator or over SSH; [`active_vt`](Self::active_vt) may be a useful
+    /// fallback there.
+    pub fn current() -> io::Result<Self> {
+        let stat = fs::read_to_string("/proc/self/stat")?;
+        controlling_vt(&stat).map(Self::new).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "not running on a virtual terminal")
+        })
+    }
+
+    /// Returns the number of the virtual terminal currently in the foreground.
+    pub fn active_vt() -> io::Result<u32> {
+        let active = fs::read_to_string(ACTIVE_VT)?;
+        active
+            .trim()
+            .strip_prefix("tty")
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unexpected active VT"))
+    }
+
+    /// The virtual terminal this grab belongs to.
+    pub fn vt(&self) -> u32 {
+        self.vt
+    }
+
+    /// Returns `true` if the device is currently grabbed through this helper.
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    /// Grab or release `device` depending on whether the VT is in the foreground. Returns
+    /// whether the device is grabbed afterwards.
+    pub fn update(&mut self, device: &mut Device) -> io::Result<bool> {
+        let active = Self::active_vt()? == self.vt;
+        self.set_active(device, active)
+    }
+
+    /// Grab `device` if `active` is `true`, and release it otherwise. Returns whether the
+    /// device is grabbed afterwards.
+    pub fn set_active(&mut self, device: &mut Device, active: bool) -> io::Result<bool> {
+        if active && !self.grabbed {
+            device.grab()?;
+            self.grabbed = true;
+            trace_event!(debug, vt = self.vt, "VT active, grabbed device");
+        } else if !active && self.grabbed {
+            self.grabbed = false;
+            device.ungrab()?;
+            trace_event!(debug, vt = self.vt, "VT inactive, released device");
+        }
+        Ok(self.grabbed)
+    }
+
+    /// Release the grab, if held, regardless of the VT state.
+    pub fn release(&mut self, device: &mut Device) -> io::Result<()> {
+        self.set_active(device, false).map(drop)
+    }
+}
+
+/// Extracts the VT number from the `tty_nr` field of `/proc/<pid>/stat`.
+fn controlling_vt(stat: &str) -> Option<u32> {
+    // The command name may contain spaces and parentheses, so skip past the last ')'
+    let fields = &stat[stat.rfind(')')? + 1..];
+    let tty_nr: u32 = fields.split_whitespace().nth(4)?.parse::<i32>().ok()? as u32;
+    let major = (tty_nr >> 8) & 0xfff;
+    let minor = (tty_nr & 0xff) | ((tty_nr >> 12) & 0xfff00);
+    // Minors 1 to 63 are the virtual consoles; higher ones are serial ports
+    (major == TTY_MAJOR && (1..64).contains(&minor)).then_some(minor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::controlling_vt;
+
+    #[test]
+    fn parse_tty_nr() {
+        let stat = "1234 (my (prog)) S 1 1234 1234 1026 1234 4194304";
+        assert_eq!(controlling_vt(stat), Some(2));
+        // A pseudo-terminal, /dev/pts/0
+        let stat = "1234 (sh) S 1 1234 1234 34816 1234 4194304";
+        assert_eq!(controlling_vt(stat), None);
+    }
+}
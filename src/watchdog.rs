@@ -0,0 +1,268 @@
+//! Releasing keys held on a virtual device when its owner stops driving it.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::panic;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Once, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use crate::clock::{Clock, SharedClock};
+use crate::uinput::MemorySink;
+use crate::{AttributeSet, EventType, InputEvent, Key};
+
+/// Watchdogs alive in the process, for the panic hook.
+static WATCHDOGS: Mutex<Vec<Weak<Shared>>> = Mutex::new(Vec::new());
+static PANIC_HOOK: Once = Once::new();
+
+/// Where release events are written.
+#[derive(Debug)]
+pub(crate) enum Sink {
+    /// A duplicate of the uinput fd.
+    File(File),
+    Memory(MemorySink),
+}
+
+#[derive(Debug)]
+pub(crate) struct State {
+    sink: Sink,
+    pressed: AttributeSet<Key>,
+    last_emit: SystemTime,
+    timeout: Option<Duration>,
+    stopped: bool,
+    clock: SharedClock,
+}
+
+impl State {
+    /// Update the set of held keys after `events` were emitted.
+    pub(crate) fn record(&mut self, events: &[InputEvent]) {
+        for ev in events.iter().filter(|ev| ev.event_type() == EventType::KEY) {
+            if ev.value() == 0 {
+                self.pressed.remove(Key::new(ev.code()));
+            } else {
+                self.pressed.insert(Key::new(ev.code()));
+            }
+        }
+        self.last_emit = self.clock.now();
+    }
+
+    /// Returns when the held keys are released unless something is emitted.
+    fn deadline(&self) -> Option<SystemTime> {
+        let timeout = self.timeout?;
+        self.pressed.iter().next()?;
+        Some(self.last_emit + timeout)
+    }
+
+    /// Release the held keys if the timeout passed, as of the clock's current time.
+    ///
+    /// If the release can't be written, the keys are forgotten anyway: the deadline stays in
+    /// the past until they are, and retrying on every wakeup would only spin.
+    pub(crate) fn tick(&mut self) -> io::Result<()> {
+        match self.deadline() {
+            Some(deadline) if deadline <= self.clock.now() => {
+                self.release_all().inspect_err(|_| self.forget())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Stop tracking the held keys without releasing them.
+    pub(crate) fn forget(&mut self) {
+        self.pressed = AttributeSet::new();
+    }
+
+    /// Emit a release for every held key.
+    pub(crate) fn release_all(&mut self) -> io::Result<()> {
+        if self.pressed.iter().next().is_none() {
+            return Ok(());
+        }
+        let mut frame: Vec<_> = self
+            .pressed
+            .iter()
+            .map(|key| InputEvent::new(EventType::KEY, key.code(), 0))
+            .collect();
+        frame.push(InputEvent::new(EventType::SYNCHRONIZATION, 0, 0));
+        trace_event!(warn, keys = frame.len() - 1, "watchdog releasing held keys");
+        match &mut self.sink {
+            Sink::File(file) => {
+                file.write_all(unsafe { crate::cast_to_bytes(frame.as_slice()) })?
+            }
+            Sink::Memory(sink) => sink.push(frame),
+        }
+        self.pressed = AttributeSet::new();
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The state stays consistent even if a holder panicked, and releasing keys after a
+        // panic is the point
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Tracks the keys held on a virtual device and releases them when the device is dropped,
+/// the process panics, or nothing is emitted for a while.
+#[derive(Debug)]
+pub(crate) struct Watchdog {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub(crate) fn new(sink: Sink, timeout: Option<Duration>, clock: SharedClock) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                sink,
+                pressed: AttributeSet::new(),
+                last_emit: clock.now(),
+                timeout,
+                stopped: false,
+                clock,
+            }),
+            cond: Condvar::new(),
+        });
+        install_panic_hook();
+        let mut watchdogs = WATCHDOGS.lock().unwrap_or_else(|e| e.into_inner());
+        watchdogs.retain(|w| w.strong_count() > 0);
+        watchdogs.push(Arc::downgrade(&shared));
+        drop(watchdogs);
+
+        let thread = timeout.map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || run(&shared))
+        });
+        Watchdog { shared, thread }
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared.lock()
+    }
+
+    /// Wake the timeout thread, so it notices held keys and the new deadline.
+    pub(crate) fn notify(&self) {
+        self.shared.cond.notify_all();
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.stopped = true;
+        let _ = state.release_all();
+        drop(state);
+        self.shared.cond.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(shared: &Shared) {
+    let mut state = shared.lock();
+    while !state.stopped {
+        let Some(deadline) = state.deadline() else {
+            state = shared.cond.wait(state).unwrap_or_else(|e| e.into_inner());
+            continue;
+        };
+        let remaining = state.clock.until(deadline);
+        if remaining.is_zero() {
+            if let Err(_error) = state.tick() {
+                trace_event!(warn, error = %_error, "watchdog failed to release held keys");
+            }
+        } else {
+            state = shared
+                .cond
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // Don't wait on locks here: the panicking thread may be holding them
+            if let Ok(watchdogs) = WATCHDOGS.try_lock() {
+                for shared in watchdogs.iter().filter_map(Weak::upgrade) {
+                    if let Ok(mut state) = shared.state.try_lock() {
+                        let _ = state.release_all();
+                    }
+                }
+            }
+            previous(info);
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::os::unix::io::FromRawFd;
+    use std::time::Instant;
+
+    const TIMEOUT: Duration = Duration::from_millis(20);
+
+    fn press(watchdog: &Watchdog) {
+        let ev = InputEvent::new(EventType::KEY, Key::KEY_A.code(), 1);
+        watchdog.lock().record(&[ev]);
+        watchdog.notify();
+    }
+
+    /// Wait for the timeout thread to stop tracking held keys.
+    fn released(watchdog: &Watchdog) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if watchdog.lock().deadline().is_none() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        false
+    }
+
+    #[test]
+    fn timeout_thread_releases() {
+        let sink = MemorySink::default();
+        let clock = MockClock::default();
+        let watchdog = Watchdog::new(
+            Sink::Memory(sink.clone()),
+            Some(TIMEOUT),
+            SharedClock::new(clock.clone()),
+        );
+        press(&watchdog);
+        clock.advance(TIMEOUT);
+        assert!(released(&watchdog));
+        let frames = sink.drain();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0][0].code(), Key::KEY_A.code());
+        assert_eq!(frames[0][0].value(), 0);
+    }
+
+    #[test]
+    fn failed_release_forgets_keys() {
+        let (read, write) = nix::unistd::pipe().unwrap();
+        nix::unistd::close(read).unwrap();
+        let clock = MockClock::default();
+        let watchdog = Watchdog::new(
+            // SAFETY: the pipe was just created and nothing else owns its write end
+            Sink::File(unsafe { File::from_raw_fd(write) }),
+            Some(TIMEOUT),
+            SharedClock::new(clock.clone()),
+        );
+        press(&watchdog);
+        clock.advance(TIMEOUT);
+        // The write fails with EPIPE; the thread must go back to waiting rather than retry
+        assert!(released(&watchdog));
+    }
+}
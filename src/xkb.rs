@@ -0,0 +1,272 @@
+//! Layout-aware key translation through xkbcommon.
+//!
+//! evdev reports physical keys: `KEY_Q` is the key in the top left letter position, whether
+//! the active layout puts a Q, an A or a Cyrillic Й there. [`XkbTranslator`] runs key events
+//! through an XKB keymap, tracking modifier and lock state, to get the keysyms and text a
+//! desktop would produce.
+//!
+//! libxkbcommon is loaded at runtime, so it's only needed on systems that use this module.
+//!
+//! ```no_run
+//! use evdev::xkb::{XkbNames, XkbTranslator};
+//!
+//! let mut device = evdev::Device::open("/dev/input/event0")?;
+//! let names = XkbNames {
+//!     layout: "de".into(),
+//!     ..Default::default()
+//! };
+//! let mut xkb = XkbTranslator::new(&names)?;
+//! loop {
+//!     for ev in device.fetch_events()? {
+//!         if let Some(press) = xkb.process(&ev) {
+//!             print!("{}", press.text);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::io;
+use std::os::raw::c_char;
+
+use xkbcommon_dl::{
+    xkb_context, xkb_context_flags, xkb_key_direction, xkb_keymap, xkb_keymap_compile_flags,
+    xkb_rule_names, xkb_state, xkb_state_component, XkbCommon,
+};
+
+use crate::{EventType, InputEvent, Key};
+
+/// The offset between evdev key codes and XKB keycodes.
+const EVDEV_OFFSET: u32 = 8;
+
+/// The rules, model, layout, variant and options ("RMLVO") to compile a keymap from.
+///
+/// Empty fields fall back to the `XKB_DEFAULT_*` environment variables, then to the system
+/// defaults (usually a US layout). Several layouts can be given separated by commas.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XkbNames {
+    pub rules: String,
+    pub model: String,
+    pub layout: String,
+    pub variant: String,
+    pub options: String,
+}
+
+/// An XKB keysym, such as `a`, `Return` or `dead_acute`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Keysym(pub u32);
+
+impl Keysym {
+    pub const NO_SYMBOL: Keysym = Keysym(0);
+
+    /// Returns the keysym's name, as used in keymap files.
+    pub fn name(self) -> String {
+        let lib = match xkbcommon_dl::xkbcommon_option() {
+            Some(lib) => lib,
+            None => return format!("{:#x}", self.0),
+        };
+        let mut buf = [0 as c_char; 64];
+        let len = unsafe { (lib.xkb_keysym_get_name)(self.0, buf.as_mut_ptr(), buf.len()) };
+        if len < 0 {
+            return format!("{:#x}", self.0);
+        }
+        unsafe { CStr::from_ptr(buf.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Returns the character this keysym types, if any.
+    pub fn to_char(self) -> Option<char> {
+        let lib = xkbcommon_dl::xkbcommon_option()?;
+        match unsafe { (lib.xkb_keysym_to_utf32)(self.0) } {
+            0 => None,
+            c => char::from_u32(c),
+        }
+    }
+}
+
+impl fmt::Debug for Keysym {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Keysym").field(&self.name()).finish()
+    }
+}
+
+/// A key press or repeat, translated through the keymap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPress {
+    pub key: Key,
+    pub keysym: Keysym,
+    /// The text the key types, taking modifiers into account; empty for keys that don't type
+    /// anything.
+    pub text: String,
+    pub repeat: bool,
+}
+
+/// Translates key events into keysyms and text according to an XKB keymap.
+///
+/// Feed it every key event from a device, so it can track which modifiers are held and
+/// which locks are active.
+pub struct XkbTranslator {
+    lib: &'static XkbCommon,
+    context: *mut xkb_context,
+    keymap: *mut xkb_keymap,
+    state: *mut xkb_state,
+}
+
+// SAFETY: the context, keymap and state are only ever used through `&mut self` or, for
+// read-only queries, `&self`; libxkbcommon objects have no thread affinity.
+unsafe impl Send for XkbTranslator {}
+
+fn c_string(s: &str) -> io::Result<CString> {
+    CString::new(s).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "name contains NUL"))
+}
+
+impl XkbTranslator {
+    /// Compile a keymap from `names`.
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] if libxkbcommon can't be loaded, and
+    /// [`io::ErrorKind::InvalidInput`] if the keymap doesn't compile.
+    pub fn new(names: &XkbNames) -> io::Result<Self> {
+        let lib = xkbcommon_dl::xkbcommon_option().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "libxkbcommon could not be loaded")
+        })?;
+        let strings = [
+            c_string(&names.rules)?,
+            c_string(&names.model)?,
+            c_string(&names.layout)?,
+            c_string(&names.variant)?,
+            c_string(&names.options)?,
+        ];
+        let rule_names = xkb_rule_names {
+            rules: strings[0].as_ptr(),
+            model: strings[1].as_ptr(),
+            layout: strings[2].as_ptr(),
+            variant: strings[3].as_ptr(),
+            options: strings[4].as_ptr(),
+        };
+
+        unsafe {
+            let context = (lib.xkb_context_new)(xkb_context_flags::XKB_CONTEXT_NO_FLAGS);
+            if context.is_null() {
+                return Err(io::Error::other("failed to create xkb context"));
+            }
+            let keymap = (lib.xkb_keymap_new_from_names)(
+                context,
+                &rule_names,
+                xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            );
+            if keymap.is_null() {
+                (lib.xkb_context_unref)(context);
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "failed to compile xkb keymap",
+                ));
+            }
+            let state = (lib.xkb_state_new)(keymap);
+            if state.is_null() {
+                (lib.xkb_keymap_unref)(keymap);
+                (lib.xkb_context_unref)(context);
+                return Err(io::Error::other("failed to create xkb state"));
+            }
+            Ok(XkbTranslator {
+                lib,
+                context,
+                keymap,
+                state,
+            })
+        }
+    }
+
+    /// Update the state with a key event, returning the translated key for presses and
+    /// repeats.
+    ///
+    /// Events other than key events are ignored.
+    pub fn process(&mut self, ev: &InputEvent) -> Option<KeyPress> {
+        if ev.event_type() != EventType::KEY {
+            return None;
+        }
+        let key = Key::new(ev.code());
+        let keycode = u32::from(key.code()) + EVDEV_OFFSET;
+        match ev.value() {
+            0 => {
+                unsafe {
+                    (self.lib.xkb_state_update_key)(
+                        self.state,
+                        keycode,
+                        xkb_key_direction::XKB_KEY_UP,
+                    )
+                };
+                None
+            }
+            value => {
+                // Translate with the state from before the press, so a modifier doesn't
+                // apply to itself
+                let press = KeyPress {
+                    key,
+                    keysym: self.keysym(key),
+                    text: self.text(key),
+                    repeat: value == 2,
+                };
+                if value == 1 {
+                    unsafe {
+                        (self.lib.xkb_state_update_key)(
+                            self.state,
+                            keycode,
+                            xkb_key_direction::XKB_KEY_DOWN,
+                        )
+                    };
+                }
+                Some(press)
+            }
+        }
+    }
+
+    /// Returns the keysym `key` produces in the current state.
+    pub fn keysym(&self, key: Key) -> Keysym {
+        let keycode = u32::from(key.code()) + EVDEV_OFFSET;
+        Keysym(unsafe { (self.lib.xkb_state_key_get_one_sym)(self.state, keycode) })
+    }
+
+    /// Returns the text `key` types in the current state.
+    pub fn text(&self, key: Key) -> String {
+        let keycode = u32::from(key.code()) + EVDEV_OFFSET;
+        let mut buf = [0 as c_char; 64];
+        unsafe {
+            (self.lib.xkb_state_key_get_utf8)(self.state, keycode, buf.as_mut_ptr(), buf.len());
+            CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned()
+        }
+    }
+
+    /// Returns `true` if the named modifier, such as `"Shift"`, `"Control"` or `"Lock"`, is
+    /// currently in effect.
+    pub fn is_modifier_active(&self, name: &str) -> bool {
+        let Ok(name) = CString::new(name) else {
+            return false;
+        };
+        unsafe {
+            (self.lib.xkb_state_mod_name_is_active)(
+                self.state,
+                name.as_ptr(),
+                xkb_state_component::XKB_STATE_MODS_EFFECTIVE,
+            ) > 0
+        }
+    }
+}
+
+impl fmt::Debug for XkbTranslator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("XkbTranslator").finish_non_exhaustive()
+    }
+}
+
+impl Drop for XkbTranslator {
+    fn drop(&mut self) {
+        unsafe {
+            (self.lib.xkb_state_unref)(self.state);
+            (self.lib.xkb_keymap_unref)(self.keymap);
+            (self.lib.xkb_context_unref)(self.context);
+        }
+    }
+}